@@ -0,0 +1,342 @@
+use crate::voxel_manager::VoxelManager;
+
+/// Offsets of the six face neighbours of a cell
+pub const FACE_OFFSETS: [[i64; 3]; 6] = [
+    [1, 0, 0],
+    [-1, 0, 0],
+    [0, 1, 0],
+    [0, -1, 0],
+    [0, 0, 1],
+    [0, 0, -1],
+];
+
+/// Voxel counts and shape measures of the scene
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SceneStats {
+    /// Palette index, color and number of voxels of every color in use
+    pub color_counts: Vec<(u8, [f32; 4], usize)>,
+    pub voxel_count: usize,
+    /// Lowest and highest filled cells
+    pub bounds: Option<([usize; 3], [usize; 3])>,
+    /// Number of voxel faces not covered by a neighbouring voxel
+    pub surface: usize,
+    /// Number of separate, face connected groups of voxels
+    pub component_count: usize,
+}
+
+impl SceneStats {
+    pub fn dimensions(&self) -> [usize; 3] {
+        match self.bounds {
+            Some((min, max)) => [
+                max[0] - min[0] + 1,
+                max[1] - min[1] + 1,
+                max[2] - min[2] + 1,
+            ],
+            None => [0, 0, 0],
+        }
+    }
+
+    /// Exposed faces per voxel, lower is better for dense, cheap to render models
+    pub fn surface_to_volume(&self) -> f32 {
+        if self.voxel_count == 0 {
+            0.0
+        } else {
+            self.surface as f32 / self.voxel_count as f32
+        }
+    }
+}
+
+/// Returns the neighbour cell in the direction of the offset if it's inside the grid
+pub fn neighbour(extent: usize, cell: [usize; 3], offset: [i64; 3]) -> Option<[usize; 3]> {
+    let mut result = [0; 3];
+    for i in 0..3 {
+        let c = cell[i] as i64 + offset[i];
+        if c < 0 || c >= extent as i64 {
+            return None;
+        }
+        result[i] = c as usize;
+    }
+    Some(result)
+}
+
+/// The filled cells with their palette index
+pub fn filled_cells(voxel_manager: &VoxelManager) -> Vec<([usize; 3], u8)> {
+    let extent = voxel_manager.extent();
+    let mut cells = Vec::new();
+    for x in 0..extent {
+        for y in 0..extent {
+            for z in 0..extent {
+                if let Some(idx) = voxel_manager.voxel(x, y, z) {
+                    cells.push(([x, y, z], idx));
+                }
+            }
+        }
+    }
+    cells
+}
+
+pub fn scene_stats(voxel_manager: &VoxelManager) -> SceneStats {
+    let extent = voxel_manager.extent();
+    let mut counts = vec![0; voxel_manager.palette().colors().len()];
+    let mut stats = SceneStats::default();
+    for (cell, idx) in filled_cells(voxel_manager) {
+        counts[idx as usize] += 1;
+        stats.voxel_count += 1;
+        stats.bounds = Some(match stats.bounds {
+            Some((min, max)) => (
+                [
+                    min[0].min(cell[0]),
+                    min[1].min(cell[1]),
+                    min[2].min(cell[2]),
+                ],
+                [
+                    max[0].max(cell[0]),
+                    max[1].max(cell[1]),
+                    max[2].max(cell[2]),
+                ],
+            ),
+            None => (cell, cell),
+        });
+        stats.surface += FACE_OFFSETS
+            .iter()
+            .filter(|offset| match neighbour(extent, cell, **offset) {
+                Some([x, y, z]) => voxel_manager.voxel(x, y, z).is_none(),
+                None => true,
+            })
+            .count();
+    }
+    stats.color_counts = counts
+        .into_iter()
+        .enumerate()
+        .filter(|(_, count)| *count > 0)
+        .map(|(idx, count)| (idx as u8, voxel_manager.palette().color(idx as u8), count))
+        .collect();
+    stats.component_count = components(voxel_manager).len();
+    stats
+}
+
+/// Labels the face connected groups of filled cells, largest first
+pub fn components(voxel_manager: &VoxelManager) -> Vec<Vec<[usize; 3]>> {
+    let extent = voxel_manager.extent();
+    flood_fill_all(extent, |[x, y, z]| voxel_manager.voxel(x, y, z).is_some())
+}
+
+/// The connected objects of every scene with the scene they belong to, in the order of the
+/// scenes
+pub fn part_components<'a>(parts: &[&'a VoxelManager]) -> Vec<(&'a VoxelManager, Vec<[usize; 3]>)> {
+    parts
+        .iter()
+        .flat_map(|part| {
+            components(part)
+                .into_iter()
+                .map(move |cells| (*part, cells))
+        })
+        .collect()
+}
+
+/// The filled cells connected to the given one, empty when the cell is empty
+pub fn component_at(voxel_manager: &VoxelManager, cell: [usize; 3]) -> Vec<[usize; 3]> {
+    let extent = voxel_manager.extent();
+    let filled = |[x, y, z]: [usize; 3]| voxel_manager.voxel(x, y, z).is_some();
+    if cell.iter().any(|c| *c >= extent) || !filled(cell) {
+        return Vec::new();
+    }
+    let mut visited = vec![false; extent * extent * extent];
+    flood_fill(extent, cell, &filled, &mut visited)
+}
+
+fn cell_index(extent: usize, [x, y, z]: [usize; 3]) -> usize {
+    (x * extent + y) * extent + z
+}
+
+/// Collects the cells matching the predicate connected to the start cell
+fn flood_fill(
+    extent: usize,
+    start: [usize; 3],
+    matches: &impl Fn([usize; 3]) -> bool,
+    visited: &mut [bool],
+) -> Vec<[usize; 3]> {
+    visited[cell_index(extent, start)] = true;
+    let mut component = Vec::new();
+    let mut stack = vec![start];
+    while let Some(cell) = stack.pop() {
+        component.push(cell);
+        for offset in FACE_OFFSETS.iter() {
+            if let Some(next) = neighbour(extent, cell, *offset) {
+                if !visited[cell_index(extent, next)] && matches(next) {
+                    visited[cell_index(extent, next)] = true;
+                    stack.push(next);
+                }
+            }
+        }
+    }
+    component
+}
+
+/// Groups the cells matching the predicate into face connected components, largest first
+fn flood_fill_all(extent: usize, matches: impl Fn([usize; 3]) -> bool) -> Vec<Vec<[usize; 3]>> {
+    let mut visited = vec![false; extent * extent * extent];
+    let mut components = Vec::new();
+    for x in 0..extent {
+        for y in 0..extent {
+            for z in 0..extent {
+                let start = [x, y, z];
+                if !visited[cell_index(extent, start)] && matches(start) {
+                    components.push(flood_fill(extent, start, &matches, &mut visited));
+                }
+            }
+        }
+    }
+    components.sort_by(|a, b| b.len().cmp(&a.len()));
+    components
+}
+
+/// Problems that make a model fail or print poorly
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ValidationReport {
+    /// Voxels of the components without support
+    pub floating: Vec<[usize; 3]>,
+    /// Voxels of one voxel wide lines, which break easily
+    pub thin: Vec<[usize; 3]>,
+    /// Empty cells enclosed by the model, trapping resin or powder
+    pub voids: Vec<[usize; 3]>,
+}
+
+impl ValidationReport {
+    pub fn problem_cells(&self) -> Vec<[usize; 3]> {
+        let mut cells = self.floating.clone();
+        cells.extend(self.thin.iter());
+        cells.extend(self.voids.iter());
+        cells
+    }
+}
+
+/// Checks the scene for 3D printing. Components not resting on the ground plane float,
+/// if nothing rests on it every component but the largest does.
+pub fn validate(voxel_manager: &VoxelManager) -> ValidationReport {
+    let extent = voxel_manager.extent();
+    let filled = |[x, y, z]: [usize; 3]| voxel_manager.voxel(x, y, z).is_some();
+    let mut report = ValidationReport::default();
+
+    let components = components(voxel_manager);
+    let grounded = |component: &Vec<[usize; 3]>| component.iter().any(|cell| cell[1] == 0);
+    let any_grounded = components.iter().any(grounded);
+    for (i, component) in components.iter().enumerate() {
+        let floating = if any_grounded {
+            !grounded(component)
+        } else {
+            i > 0
+        };
+        if floating {
+            report.floating.extend(component.iter());
+        }
+
+        for cell in component.iter() {
+            let filled_offsets: Vec<&[i64; 3]> = FACE_OFFSETS
+                .iter()
+                .filter(|offset| neighbour(extent, *cell, **offset).map_or(false, &filled))
+                .collect();
+            let is_bridge = filled_offsets.len() == 2
+                && filled_offsets[0]
+                    .iter()
+                    .zip(filled_offsets[1].iter())
+                    .all(|(a, b)| a + b == 0);
+            if is_bridge {
+                report.thin.push(*cell);
+            }
+        }
+    }
+
+    // Empty cells the outside of the grid can't be reached from are enclosed
+    let on_boundary = |cell: &[usize; 3]| cell.iter().any(|c| *c == 0 || *c == extent - 1);
+    for empty in flood_fill_all(extent, |cell| !filled(cell)) {
+        if !empty.iter().any(on_boundary) {
+            report.voids.extend(empty);
+        }
+    }
+    report
+}
+
+pub fn cells_with_color(voxel_manager: &VoxelManager, color_index: u8) -> Vec<[usize; 3]> {
+    filled_cells(voxel_manager)
+        .into_iter()
+        .filter(|(_, idx)| *idx == color_index)
+        .map(|(cell, _)| cell)
+        .collect()
+}
+
+/// The scene seen from above, the height and color of the highest voxel of every column by
+/// `x + z * extent`, None for empty columns
+pub fn top_view(voxel_manager: &VoxelManager) -> Vec<Option<(usize, [f32; 4])>> {
+    let extent = voxel_manager.extent();
+    let mut columns = Vec::with_capacity(extent * extent);
+    for z in 0..extent {
+        for x in 0..extent {
+            columns.push((0..extent).rev().find_map(|y| {
+                let idx = voxel_manager.voxel(x, y, z)?;
+                Some((y, voxel_manager.palette().color(idx)))
+            }));
+        }
+    }
+    columns
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn counts_colors_bounds_and_surface() {
+        let mut voxel_manager = VoxelManager::new(8);
+        voxel_manager.paint_cell([1, 2, 3], [1.0, 0.0, 0.0, 1.0], true);
+        voxel_manager.paint_cell([2, 2, 3], [1.0, 0.0, 0.0, 1.0], true);
+        voxel_manager.paint_cell([7, 0, 0], [0.0, 0.0, 1.0, 1.0], true);
+
+        let stats = scene_stats(&voxel_manager);
+        assert_eq!(stats.voxel_count, 3);
+        assert_eq!(
+            stats.color_counts,
+            vec![(0, [1.0, 0.0, 0.0, 1.0], 2), (1, [0.0, 0.0, 1.0, 1.0], 1)]
+        );
+        assert_eq!(stats.dimensions(), [7, 3, 4]);
+        // Two touching voxels hide one face each
+        assert_eq!(stats.surface, 6 + 6 + 4);
+        assert_eq!(stats.component_count, 2);
+        assert_eq!(cells_with_color(&voxel_manager, 1), vec![[7, 0, 0]]);
+        let columns = top_view(&voxel_manager);
+        assert_eq!(columns[1 + 3 * 8], Some((2, [1.0, 0.0, 0.0, 1.0])));
+        assert_eq!(columns[7], Some((0, [0.0, 0.0, 1.0, 1.0])));
+        assert_eq!(columns.iter().flatten().count(), 3);
+    }
+
+    #[test]
+    fn validation_finds_islands_bridges_and_voids() {
+        let mut voxel_manager = VoxelManager::new(8);
+        let red = [1.0, 0.0, 0.0, 1.0];
+        // A hollow 3x3x3 cube on the ground
+        for x in 0..3 {
+            for y in 0..3 {
+                for z in 0..3 {
+                    if [x, y, z] != [1, 1, 1] {
+                        voxel_manager.paint_cell([x, y, z], red, true);
+                    }
+                }
+            }
+        }
+        // A floating line of three voxels
+        for x in 4..7 {
+            voxel_manager.paint_cell([x, 5, 5], red, true);
+        }
+
+        let report = validate(&voxel_manager);
+        assert_eq!(components(&voxel_manager).len(), 2);
+        assert_eq!(component_at(&voxel_manager, [5, 5, 5]).len(), 3);
+        assert!(component_at(&voxel_manager, [1, 1, 1]).is_empty());
+        assert_eq!(report.floating.len(), 3);
+        assert_eq!(report.thin, vec![[5, 5, 5]]);
+        assert_eq!(report.voids, vec![[1, 1, 1]]);
+    }
+}
@@ -0,0 +1,155 @@
+//! Bevy integration behind the `bevy` feature, loading .vxe projects as ready to render meshes
+//! and materials:
+//!
+//! ```ignore
+//! App::build()
+//!     .add_plugins(DefaultPlugins)
+//!     .add_plugin(VoxelEditorPlugin)
+//!     .add_startup_system(setup.system())
+//!     .run();
+//!
+//! fn setup(commands: &mut Commands, asset_server: Res<AssetServer>) {
+//!     // A single color of the model can be loaded directly too, as "castle.vxe#Mesh3"
+//!     let castle: Handle<VoxelModel> = asset_server.load("castle.vxe");
+//! }
+//! ```
+use crate::project;
+use bevy::asset::{AssetLoader, AssetPath, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::render::mesh::Indices;
+use bevy::render::pipeline::PrimitiveTopology;
+use bevy::utils::BoxedFuture;
+use std::io;
+
+/// A loaded project, with a mesh and a material for every palette color in use
+#[derive(Debug, TypeUuid)]
+#[uuid = "9d5e3a4c-1f6b-4c2e-8a47-3b0f6d2c9e51"]
+pub struct VoxelModel {
+    pub parts: Vec<VoxelModelPart>,
+}
+
+#[derive(Debug)]
+pub struct VoxelModelPart {
+    pub color_index: u8,
+    pub mesh: Handle<Mesh>,
+    pub material: Handle<StandardMaterial>,
+}
+
+impl VoxelModel {
+    /// Bundles rendering the model, all placed with the same transform
+    pub fn bundles(&self, transform: Transform) -> Vec<PbrBundle> {
+        self.parts
+            .iter()
+            .map(|part| PbrBundle {
+                mesh: part.mesh.clone(),
+                material: part.material.clone(),
+                transform,
+                ..Default::default()
+            })
+            .collect()
+    }
+}
+
+/// The mesh and the material of every palette color the saved project uses
+pub fn model_meshes(bytes: &[u8]) -> io::Result<Vec<(u8, Mesh, StandardMaterial)>> {
+    let voxel_manager = project::read_project(&mut &bytes[..], &mut |_| true)?;
+    let mut meshes = Vec::new();
+    for (color_index, vertices, indices) in voxel_manager.vertices_by_color() {
+        let positions: Vec<[f32; 3]> = vertices.iter().map(|v| v.pos).collect();
+        let normals: Vec<[f32; 3]> = vertices.iter().map(|v| v.normal).collect();
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+        mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+        // The voxels are flat colored, the default pipeline still expects UVs
+        mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, vec![[0.0f32, 0.0]; vertices.len()]);
+        mesh.set_indices(Some(Indices::U32(indices)));
+
+        let [r, g, b, a] = voxel_manager.palette().color(color_index);
+        // Emissive colors are unlit, the way the editor shows them
+        let material = StandardMaterial {
+            albedo: Color::rgba(r, g, b, a),
+            shaded: !voxel_manager.palette().is_emissive(color_index),
+            ..Default::default()
+        };
+        meshes.push((color_index, mesh, material));
+    }
+    Ok(meshes)
+}
+
+/// Loads .vxe files as VoxelModel assets. The parts are labeled assets as well, "Mesh3" and
+/// "Material3" belong to palette index 3.
+#[derive(Default)]
+pub struct VxeLoader;
+
+impl AssetLoader for VxeLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let mut parts = Vec::new();
+            for (color_index, mesh, material) in model_meshes(bytes)? {
+                let mesh_label = format!("Mesh{}", color_index);
+                let material_label = format!("Material{}", color_index);
+                load_context.set_labeled_asset(&mesh_label, LoadedAsset::new(mesh));
+                load_context.set_labeled_asset(&material_label, LoadedAsset::new(material));
+                let mesh_path = AssetPath::new_ref(load_context.path(), Some(&mesh_label));
+                let material_path = AssetPath::new_ref(load_context.path(), Some(&material_label));
+                parts.push(VoxelModelPart {
+                    color_index,
+                    mesh: load_context.get_handle(mesh_path),
+                    material: load_context.get_handle(material_path),
+                });
+            }
+            load_context.set_default_asset(LoadedAsset::new(VoxelModel { parts }));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["vxe"]
+    }
+}
+
+/// Registers the VoxelModel asset and its loader
+pub struct VoxelEditorPlugin;
+
+impl Plugin for VoxelEditorPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_asset::<VoxelModel>()
+            .init_asset_loader::<VxeLoader>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::voxel_manager::VoxelManager;
+
+    #[test]
+    fn saved_projects_load_as_a_mesh_per_color() {
+        let mut voxel_manager = VoxelManager::new(8);
+        let red = voxel_manager.palette_mut().index_of([1.0, 0.0, 0.0, 1.0]);
+        let blue = voxel_manager.palette_mut().index_of([0.0, 0.0, 1.0, 1.0]);
+        voxel_manager.set_voxel(0, 0, 0, Some(red));
+        voxel_manager.set_voxel(4, 4, 4, Some(blue));
+        let mut bytes = Vec::new();
+        project::write_project(&voxel_manager, &mut bytes, &mut |_| true).unwrap();
+
+        let meshes = model_meshes(&bytes).unwrap();
+        assert_eq!(meshes.len(), 2);
+        let (color_index, mesh, material) = &meshes[0];
+        assert_eq!(*color_index, red);
+        // A lone voxel is 6 quads of 4 vertices
+        assert_eq!(mesh.count_vertices(), 24);
+        match mesh.indices() {
+            Some(Indices::U32(indices)) => assert_eq!(indices.len(), 36),
+            _ => panic!("expected 32 bit indices"),
+        }
+        assert_eq!(material.albedo, Color::rgba(1.0, 0.0, 0.0, 1.0));
+        assert!(model_meshes(b"not a project").is_err());
+    }
+}
@@ -0,0 +1,225 @@
+use crate::analysis::{self, neighbour, FACE_OFFSETS};
+use crate::settings::Settings;
+use crate::voxel_manager::VoxelManager;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Triangles of a voxel with an exposed face, the exporters mesh all six faces of it
+const TRIANGLES_PER_VOXEL: usize = 12;
+
+/// Limits of the voxel and triangle counts of a project, for the scene as a whole and for
+/// every connected object in it. None means no limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Budget {
+    pub voxels: Option<usize>,
+    pub triangles: Option<usize>,
+    pub object_voxels: Option<usize>,
+    pub object_triangles: Option<usize>,
+}
+
+/// Written as `voxels triangles object_voxels object_triangles`, with `-` for no limit
+impl fmt::Display for Budget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let limit = |limit: Option<usize>| limit.map_or_else(|| "-".to_owned(), |l| l.to_string());
+        write!(
+            f,
+            "{} {} {} {}",
+            limit(self.voxels),
+            limit(self.triangles),
+            limit(self.object_voxels),
+            limit(self.object_triangles)
+        )
+    }
+}
+
+/// Missing trailing limits are no limits
+impl FromStr for Budget {
+    type Err = ();
+
+    fn from_str(text: &str) -> Result<Self, ()> {
+        let mut limits = Vec::new();
+        for part in text.split_whitespace() {
+            limits.push(match part {
+                "-" => None,
+                _ => Some(part.parse().map_err(|_| ())?),
+            });
+        }
+        if limits.len() > 4 {
+            return Err(());
+        }
+        limits.resize(4, None);
+        Ok(Budget {
+            voxels: limits[0],
+            triangles: limits[1],
+            object_voxels: limits[2],
+            object_triangles: limits[3],
+        })
+    }
+}
+
+/// A count of the scene or of one of its objects above its limit
+#[derive(Debug, Clone, PartialEq)]
+pub struct BudgetWarning {
+    /// Number of the object in the order of the batch export, None for the whole scene
+    pub object: Option<usize>,
+    /// What is counted, `voxels` or `triangles`
+    pub measure: &'static str,
+    pub count: usize,
+    pub limit: usize,
+}
+
+impl fmt::Display for BudgetWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.object {
+            Some(n) => write!(f, "Object {}: ", n)?,
+            None => write!(f, "Scene: ")?,
+        }
+        write!(f, "{} {}, budget {}", self.count, self.measure, self.limit)
+    }
+}
+
+impl Budget {
+    /// The sidecar file a project's budget is kept in
+    pub fn path(project_path: &str) -> PathBuf {
+        PathBuf::from(format!("{}.budget", project_path))
+    }
+
+    /// Reads the budget of the project, no limits when it has no sidecar file yet
+    pub fn load(project_path: &str) -> Self {
+        match Settings::read(&Budget::path(project_path)) {
+            Ok(settings) => Budget {
+                voxels: settings.get("budget.voxels"),
+                triangles: settings.get("budget.triangles"),
+                object_voxels: settings.get("budget.object_voxels"),
+                object_triangles: settings.get("budget.object_triangles"),
+            },
+            Err(_) => Budget::default(),
+        }
+    }
+
+    pub fn save(&self, project_path: &str) -> io::Result<()> {
+        let mut settings = Settings::default();
+        let limits = [
+            ("budget.voxels", self.voxels),
+            ("budget.triangles", self.triangles),
+            ("budget.object_voxels", self.object_voxels),
+            ("budget.object_triangles", self.object_triangles),
+        ];
+        for (key, limit) in limits.iter() {
+            if let Some(limit) = limit {
+                settings.set(key, limit);
+            }
+        }
+        settings.write(&Budget::path(project_path))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Budget::default()
+    }
+
+    /// The counts of the scene and its objects above their limits
+    pub fn check(&self, voxel_manager: &VoxelManager) -> Vec<BudgetWarning> {
+        let mut warnings = Vec::new();
+        if self.voxels.is_some() || self.triangles.is_some() {
+            let cells: Vec<[usize; 3]> = analysis::filled_cells(voxel_manager)
+                .into_iter()
+                .map(|(cell, _)| cell)
+                .collect();
+            let counts = counts(voxel_manager, &cells);
+            warn(None, counts, [self.voxels, self.triangles], &mut warnings);
+        }
+        if self.object_voxels.is_some() || self.object_triangles.is_some() {
+            let limits = [self.object_voxels, self.object_triangles];
+            for (i, cells) in analysis::components(voxel_manager).iter().enumerate() {
+                let counts = counts(voxel_manager, cells);
+                warn(Some(i + 1), counts, limits, &mut warnings);
+            }
+        }
+        warnings
+    }
+}
+
+/// Adds a warning for each of the voxel and triangle counts above its limit
+fn warn(
+    object: Option<usize>,
+    counts: [usize; 2],
+    limits: [Option<usize>; 2],
+    warnings: &mut Vec<BudgetWarning>,
+) {
+    for ((measure, count), limit) in ["voxels", "triangles"].iter().zip(&counts).zip(&limits) {
+        match limit {
+            Some(limit) if count > limit => warnings.push(BudgetWarning {
+                object,
+                measure,
+                count: *count,
+                limit: *limit,
+            }),
+            _ => (),
+        }
+    }
+}
+
+/// Voxels and exported triangles of the filled cells
+fn counts(voxel_manager: &VoxelManager, cells: &[[usize; 3]]) -> [usize; 2] {
+    let extent = voxel_manager.extent();
+    let exposed = cells
+        .iter()
+        .filter(|cell| {
+            FACE_OFFSETS
+                .iter()
+                .any(|offset| match neighbour(extent, **cell, *offset) {
+                    Some([x, y, z]) => voxel_manager.voxel(x, y, z).is_none(),
+                    None => true,
+                })
+        })
+        .count();
+    [cells.len(), exposed * TRIANGLES_PER_VOXEL]
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn warns_about_the_scene_and_objects_over_budget() {
+        let mut scene = VoxelManager::new(8);
+        let red = scene.palette_mut().index_of([1.0, 0.0, 0.0, 1.0]);
+        // A 3x3x3 cube, its middle voxel is hidden, and a single voxel away from it
+        for x in 0..3 {
+            for y in 0..3 {
+                for z in 0..3 {
+                    scene.set_voxel(x, y, z, Some(red));
+                }
+            }
+        }
+        scene.set_voxel(6, 0, 0, Some(red));
+
+        let budget: Budget = "30 - 20 300".parse().unwrap();
+        assert_eq!(budget.to_string(), "30 - 20 300");
+        assert_eq!(
+            budget.check(&scene),
+            vec![
+                BudgetWarning {
+                    object: Some(1),
+                    measure: "voxels",
+                    count: 27,
+                    limit: 20,
+                },
+                BudgetWarning {
+                    object: Some(1),
+                    measure: "triangles",
+                    count: 26 * 12,
+                    limit: 300,
+                },
+            ]
+        );
+        assert!("1 2 3 4 5".parse::<Budget>().is_err());
+        assert!(Budget::default().check(&scene).is_empty());
+    }
+}
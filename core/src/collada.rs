@@ -0,0 +1,209 @@
+//! Collada 1.4 export. The scene is a root node with a child node for every object, translated
+//! to the pivot of saved masks that have one, each instancing a geometry with a triangle group
+//! per color.
+use crate::export::ExportObject;
+use crate::palette::Palette;
+use crate::task::{cancelled, Progress};
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+
+fn join<T: std::fmt::Display>(values: impl Iterator<Item = T>) -> String {
+    let mut out = String::new();
+    for (i, value) in values.enumerate() {
+        if i > 0 {
+            out.push(' ');
+        }
+        let _ = write!(out, "{}", value);
+    }
+    out
+}
+
+/// A float source of 3 component vectors
+fn source(out: &mut String, id: &str, values: &[[f32; 3]], params: [&str; 3]) {
+    let _ = write!(
+        out,
+        "        <source id=\"{0}\">\n          <float_array id=\"{0}-array\" count=\"{1}\">{2}</float_array>\n          <technique_common>\n            <accessor source=\"#{0}-array\" count=\"{3}\" stride=\"3\">\n",
+        id,
+        values.len() * 3,
+        join(values.iter().flat_map(|v| v.to_vec())),
+        values.len()
+    );
+    for param in params.iter() {
+        let _ = writeln!(
+            out,
+            "              <param name=\"{}\" type=\"float\"/>",
+            param
+        );
+    }
+    out.push_str("            </accessor>\n          </technique_common>\n        </source>\n");
+}
+
+/// The objects as a Collada document, their colors are entries of the palette
+pub fn dae(
+    objects: &[ExportObject],
+    palette: &Palette,
+    progress: &mut Progress,
+) -> io::Result<String> {
+    let mut geometries = String::new();
+    let mut nodes = String::new();
+    let mut used = BTreeSet::new();
+
+    for (i, object) in objects.iter().enumerate() {
+        if !progress(i as f32 / objects.len() as f32) {
+            return Err(cancelled());
+        }
+        let (translation, origin) = object.transform();
+        // One vertex list shared by the triangle groups of all colors
+        let mut positions = Vec::new();
+        let mut normals = Vec::new();
+        let mut groups = Vec::new();
+        for (color_index, vertices, indices) in object.part.cells_by_color(&object.cells, origin) {
+            let offset = positions.len() as u32;
+            positions.extend(vertices.iter().map(|v| v.pos));
+            normals.extend(vertices.iter().map(|v| v.normal));
+            groups.push((
+                color_index,
+                indices.iter().map(|i| i + offset).collect::<Vec<_>>(),
+            ));
+            used.insert(color_index);
+        }
+
+        let name = object.node_name(i + 1);
+        let _ = writeln!(
+            geometries,
+            "    <geometry id=\"{0}-mesh\" name=\"{0}\">\n      <mesh>",
+            name
+        );
+        source(
+            &mut geometries,
+            &format!("{}-positions", name),
+            &positions,
+            ["X", "Y", "Z"],
+        );
+        source(
+            &mut geometries,
+            &format!("{}-normals", name),
+            &normals,
+            ["X", "Y", "Z"],
+        );
+        let _ = writeln!(
+            geometries,
+            "        <vertices id=\"{0}-vertices\">\n          <input semantic=\"POSITION\" source=\"#{0}-positions\"/>\n        </vertices>",
+            name
+        );
+        for (color_index, indices) in groups.iter() {
+            let _ = writeln!(
+                geometries,
+                "        <triangles material=\"Color_{1}\" count=\"{2}\">\n          <input semantic=\"VERTEX\" source=\"#{0}-vertices\" offset=\"0\"/>\n          <input semantic=\"NORMAL\" source=\"#{0}-normals\" offset=\"0\"/>\n          <p>{3}</p>\n        </triangles>",
+                name,
+                color_index,
+                indices.len() / 3,
+                join(indices.iter())
+            );
+        }
+        geometries.push_str("      </mesh>\n    </geometry>\n");
+
+        let _ = writeln!(
+            nodes,
+            "        <node id=\"{0}\" name=\"{0}\" type=\"NODE\">",
+            name
+        );
+        if object.pivot.is_some() {
+            let [x, y, z] = translation;
+            let _ = writeln!(
+                nodes,
+                "          <translate sid=\"translate\">{} {} {}</translate>",
+                x, y, z
+            );
+        }
+        let _ = write!(
+            nodes,
+            "          <instance_geometry url=\"#{}-mesh\">\n            <bind_material>\n              <technique_common>\n",
+            name
+        );
+        for (color_index, _) in groups.iter() {
+            let _ = writeln!(
+                nodes,
+                "                <instance_material symbol=\"Color_{0}\" target=\"#Color_{0}\"/>",
+                color_index
+            );
+        }
+        nodes.push_str("              </technique_common>\n            </bind_material>\n          </instance_geometry>\n        </node>\n");
+    }
+
+    let mut effects = String::new();
+    let mut materials = String::new();
+    for color_index in used.iter() {
+        let [r, g, b, a] = palette.color(*color_index);
+        let _ = writeln!(
+            effects,
+            "    <effect id=\"Color_{}-effect\">\n      <profile_COMMON>\n        <technique sid=\"common\">\n          <lambert>\n            <diffuse>\n              <color sid=\"diffuse\">{} {} {} {}</color>\n            </diffuse>\n          </lambert>\n        </technique>\n      </profile_COMMON>\n    </effect>",
+            color_index, r, g, b, a
+        );
+        let _ = writeln!(
+            materials,
+            "    <material id=\"Color_{0}\" name=\"Color_{0}\">\n      <instance_effect url=\"#Color_{0}-effect\"/>\n    </material>",
+            color_index
+        );
+    }
+
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    out.push_str(
+        "<COLLADA xmlns=\"http://www.collada.org/2005/11/COLLADASchema\" version=\"1.4.1\">\n",
+    );
+    // Y up, one voxel is 10 cm
+    out.push_str("  <asset>\n    <contributor>\n      <authoring_tool>voxel-editor</authoring_tool>\n    </contributor>\n    <unit name=\"voxel\" meter=\"0.1\"/>\n    <up_axis>Y_UP</up_axis>\n  </asset>\n");
+    let _ = write!(
+        out,
+        "  <library_effects>\n{}  </library_effects>\n  <library_materials>\n{}  </library_materials>\n  <library_geometries>\n{}  </library_geometries>\n",
+        effects, materials, geometries
+    );
+    let _ = write!(
+        out,
+        "  <library_visual_scenes>\n    <visual_scene id=\"Scene\" name=\"Scene\">\n      <node id=\"Voxels\" name=\"Voxels\" type=\"NODE\">\n{}      </node>\n    </visual_scene>\n  </library_visual_scenes>\n",
+        nodes
+    );
+    out.push_str(
+        "  <scene>\n    <instance_visual_scene url=\"#Scene\"/>\n  </scene>\n</COLLADA>\n",
+    );
+    Ok(out)
+}
+
+pub fn write_dae(
+    objects: &[ExportObject],
+    palette: &Palette,
+    file_path: &str,
+    progress: &mut Progress,
+) -> io::Result<()> {
+    fs::write(file_path, dae(objects, palette, progress)?)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::export::export_objects;
+    use crate::selection::SavedMasks;
+    use crate::voxel_manager::VoxelManager;
+
+    #[test]
+    fn objects_are_nodes_under_the_scene_root() {
+        let mut voxel_manager = VoxelManager::new(8);
+        let red = voxel_manager.palette_mut().index_of([1.0, 0.0, 0.0, 1.0]);
+        let blue = voxel_manager.palette_mut().index_of([0.0, 0.0, 1.0, 1.0]);
+        voxel_manager.set_voxel(0, 0, 0, Some(red));
+        voxel_manager.set_voxel(1, 0, 0, Some(blue));
+        voxel_manager.set_voxel(5, 5, 5, Some(red));
+
+        let objects = export_objects(&[&voxel_manager], &SavedMasks::default(), [0; 3]);
+        let document = dae(&objects, voxel_manager.palette(), &mut |_| true).unwrap();
+        assert_eq!(document.matches("type=\"NODE\"").count(), 3);
+        assert_eq!(document.matches("<material id=").count(), 2);
+        // The first object has a triangle group per color, the second indexes from its own start
+        assert_eq!(document.matches("<triangles ").count(), 3);
+        assert!(document.contains("<triangles material=\"Color_1\" count=\"12\">"));
+        assert!(document.contains("<p>24 25 26"));
+    }
+}
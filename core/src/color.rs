@@ -0,0 +1,78 @@
+pub const RED: [f32; 4] = [1.0, 0.0, 0.0, 1.0];
+pub const HALF_ALPHA_RED: [f32; 4] = [1.0, 0.0, 0.0, 0.2];
+pub const GREEN: [f32; 4] = [0.0, 1.0, 0.0, 1.0];
+pub const BLUE: [f32; 4] = [0.0, 0.0, 1.0, 1.0];
+pub const WHITE: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+pub const TRANSPARENT: [f32; 4] = [0.0, 0.0, 0.0, 0.0];
+
+/// Reads an opaque `#rrggbb` color, the `#` is optional
+pub fn parse_hex(text: &str) -> Option<[f32; 4]> {
+    let digits = text.trim().trim_start_matches('#');
+    if digits.len() != 6 || !digits.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| {
+        u8::from_str_radix(&digits[i * 2..i * 2 + 2], 16)
+            .ok()
+            .map(|c| c as f32 / 255.0)
+    };
+    Some([channel(0)?, channel(1)?, channel(2)?, 1.0])
+}
+
+/// Levels per channel of the colors edits compute, like baked shading and smoothed colors. Nearly
+/// equal colors are quantized to the same level so they share a palette entry, a full palette
+/// still snaps them to its closest entries.
+pub const COLOR_LEVELS: f32 = 32.0;
+
+/// The channel quantized to the closest of the COLOR_LEVELS levels
+pub fn quantized(c: f32) -> f32 {
+    (c.max(0.0).min(1.0) * COLOR_LEVELS).round() / COLOR_LEVELS
+}
+
+/// The sRGB encoded channel in linear light
+pub fn to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// The channel in linear light encoded as sRGB
+pub fn from_linear(c: f32) -> f32 {
+    let c = c.max(0.0).min(1.0);
+    if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// The sRGB color in linear light, for blending. Alpha isn't encoded.
+pub fn linear([r, g, b, a]: [f32; 4]) -> [f32; 4] {
+    [to_linear(r), to_linear(g), to_linear(b), a]
+}
+
+/// The color in linear light encoded as sRGB
+pub fn srgb([r, g, b, a]: [f32; 4]) -> [f32; 4] {
+    [from_linear(r), from_linear(g), from_linear(b), a]
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn srgb_colors_round_trip_through_linear_light() {
+        let gray = parse_hex("#bcbcbc").unwrap();
+        // Half of the light of white is a lot brighter than half of its code value
+        assert!((linear(gray)[0] - 0.5).abs() < 0.01);
+        for c in 0..=255 {
+            let c = c as f32 / 255.0;
+            assert!((from_linear(to_linear(c)) - c).abs() < 1e-5);
+        }
+        // Alpha isn't encoded
+        assert_eq!(srgb([0.0, 0.5, 0.0, 0.5])[3], 0.5);
+    }
+}
@@ -0,0 +1,795 @@
+use crate::analysis;
+use crate::collada;
+use crate::fbx;
+use crate::palette::{Palette, PALETTE_SIZE};
+use crate::selection::SavedMasks;
+use crate::settings::Settings;
+use crate::subgrid::SubGrid;
+use crate::task::{cancelled, Progress};
+use crate::transform::{bounds, Pivot};
+use crate::usd;
+use crate::vdb;
+use crate::vertex::VoxelVertex;
+use crate::voxel_manager::VoxelManager;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+// The atlas has a texel for every palette entry
+const ATLAS_SIZE: usize = 16;
+
+/// Texture coordinates of the center of the texel of the palette entry
+fn atlas_uv(index: u8) -> [f32; 2] {
+    let (column, row) = (index as usize % ATLAS_SIZE, index as usize / ATLAS_SIZE);
+    [
+        (column as f32 + 0.5) / ATLAS_SIZE as f32,
+        1.0 - (row as f32 + 0.5) / ATLAS_SIZE as f32,
+    ]
+}
+
+/// The palette as an uncompressed 32 bit .tga image with the first entry in the top left corner
+fn atlas_tga(palette: &Palette) -> Vec<u8> {
+    let mut data = vec![0u8; 18];
+    data[2] = 2;
+    data[12] = ATLAS_SIZE as u8;
+    data[14] = ATLAS_SIZE as u8;
+    data[16] = 32;
+    data[17] = 0x28;
+    let channel = |c: f32| (c.max(0.0).min(1.0) * 255.0).round() as u8;
+    let raw = palette.raw();
+    for color in raw.iter().take(PALETTE_SIZE) {
+        data.extend_from_slice(&[
+            channel(color[2]),
+            channel(color[1]),
+            channel(color[0]),
+            channel(color[3]),
+        ]);
+    }
+    data
+}
+
+/// Writes the material library and the atlas image next to the .obj file,
+/// returning the file name of the library
+fn write_atlas(palette: &Palette, file_path: &str) -> io::Result<String> {
+    let path = Path::new(file_path);
+    let mtl_path = path.with_extension("mtl");
+    let tga_path = path.with_extension("tga");
+    let file_name = |path: &Path| {
+        path.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    };
+    File::create(&tga_path)?.write_all(&atlas_tga(palette))?;
+    let mut mtl = BufWriter::new(File::create(&mtl_path)?);
+    mtl.write_all(
+        format!(
+            "newmtl atlas\nKa 1.000 1.000 1.000\nKd 1.000 1.000 1.000\nmap_Kd {}\n",
+            file_name(&tga_path)
+        )
+        .as_ref(),
+    )?;
+    mtl.flush()?;
+    Ok(file_name(&mtl_path))
+}
+
+/// Writes the visible faces of the scene as a Wavefront .obj file. With texture_atlas the voxel
+/// colors are baked into a texture next to it, and every face samples its color from there.
+pub fn write_obj(
+    voxel_manager: &VoxelManager,
+    file_path: &str,
+    texture_atlas: bool,
+    progress: &mut Progress,
+) -> io::Result<()> {
+    let mesh = voxel_manager.vertices_by_color();
+    write_obj_parts(&[(voxel_manager, mesh)], file_path, texture_atlas, progress)
+}
+
+/// Writes the meshes by color of the scenes into one .obj file, the colors of the later scenes
+/// are mapped into the palette of the first
+fn write_obj_parts(
+    parts: &[(&VoxelManager, Vec<(u8, Vec<VoxelVertex>, Vec<u32>)>)],
+    file_path: &str,
+    texture_atlas: bool,
+    progress: &mut Progress,
+) -> io::Result<()> {
+    let mut palette = parts[0].0.palette().clone();
+    let mut vertex_data = Vec::new();
+    let mut indices = Vec::new();
+    let mut color_indices = Vec::new();
+    for (part, mesh) in parts.iter() {
+        for (color_index, part_vertices, part_indices) in mesh.iter() {
+            let base = vertex_data.len() as u32;
+            vertex_data.extend(part_vertices.iter().cloned());
+            indices.extend(part_indices.iter().map(|i| i + base));
+            if texture_atlas {
+                let index = palette.index_of(part.palette().color(*color_index));
+                color_indices.extend(std::iter::repeat(index).take(part_vertices.len()));
+            }
+        }
+    }
+    let mut buffer = BufWriter::new(File::create(file_path)?);
+    let total = (vertex_data.len() + indices.len() / 3) as f32;
+    let colors = if texture_atlas {
+        let mtl = write_atlas(&palette, file_path)?;
+        buffer.write_all(format!("mtllib {}\nusemtl atlas\n", mtl).as_ref())?;
+        buffer.write_all(b"# Texture coordinates of the palette entries in the atlas.\n")?;
+        for index in 0..PALETTE_SIZE {
+            let [u, v] = atlas_uv(index as u8);
+            buffer.write_all(format!("vt {:.5} {:.5}\n", u, v).as_ref())?;
+        }
+        Some(color_indices)
+    } else {
+        None
+    };
+
+    buffer.write_all(b"# List of geometric vertices, with (x, y, z [,w]) coordinates, w is optional and defaults to 1.0.\n")?;
+    buffer.write_all(
+        b"# List of vertex normals in (x,y,z) form; normals might not be unit vectors.\n",
+    )?;
+
+    for (i, vd) in vertex_data.iter().enumerate() {
+        if !progress(i as f32 / total) {
+            return Err(cancelled());
+        }
+        buffer.write_all(
+            format!(
+                "v {:.3} {:.3} {:.3} 1.0\n",
+                vd.pos[0] as f32, vd.pos[1] as f32, vd.pos[2] as f32
+            )
+            .as_ref(),
+        )?;
+        buffer.write_all(
+            format!(
+                "vn {:.3} {:.3} {:.3}\n",
+                vd.normal[0], vd.normal[1], vd.normal[2]
+            )
+            .as_ref(),
+        )?;
+    }
+
+    buffer.write_all(b"# Polygonal face element\n")?;
+
+    for (i, id) in indices.chunks(3).enumerate() {
+        if !progress((vertex_data.len() + i) as f32 / total) {
+            return Err(cancelled());
+        }
+        let face = match &colors {
+            Some(colors) => {
+                let uv = colors[id[0] as usize] as usize + 1;
+                format!(
+                    "f {0}/{3}/{0} {1}/{3}/{1} {2}/{3}/{2}\n",
+                    id[0] + 1,
+                    id[1] + 1,
+                    id[2] + 1,
+                    uv
+                )
+            }
+            None => format!(
+                "f {0}//{0} {1}//{1} {2}//{2}\n",
+                id[0] + 1,
+                id[1] + 1,
+                id[2] + 1
+            ),
+        };
+        buffer.write_all(face.as_ref())?;
+    }
+
+    buffer.flush()?;
+    Ok(())
+}
+
+/// Path of the n-th object of a batch export, placed next to the chosen file. {name} in the
+/// template is replaced with the name of the object's saved mask, or the chosen file's name for
+/// objects outside of the masks, and {n} with the object's number. Templates that tell the
+/// objects apart by neither get _{n} appended so the objects don't overwrite each other.
+fn batch_path(file_path: &str, template: &str, object: Option<&str>, n: usize) -> PathBuf {
+    let path = Path::new(file_path);
+    let name = match object {
+        Some(name) => name.to_owned(),
+        None => path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+    };
+    let named = object.is_some() && template.contains("{name}");
+    let template = if template.contains("{n}") || named {
+        template.to_owned()
+    } else {
+        format!("{}_{{n}}", template)
+    };
+    let file_name = template
+        .replace("{name}", &name)
+        .replace("{n}", &n.to_string());
+    path.with_file_name(file_name).with_extension("obj")
+}
+
+/// An object of an export, with the cells of it in its scene
+pub struct ExportObject<'a> {
+    pub part: &'a VoxelManager,
+    /// Name of the saved mask it is, None for the connected objects outside of the masks
+    pub name: Option<String>,
+    pub cells: Vec<[usize; 3]>,
+    /// The pivot of the saved mask in the grid of the part
+    pub pivot: Option<[f32; 3]>,
+}
+
+impl ExportObject<'_> {
+    /// Name of the object's node, the mask's name made an identifier or Object_{n}
+    pub fn node_name(&self, n: usize) -> String {
+        match &self.name {
+            Some(name) => {
+                let mut node: String = name
+                    .chars()
+                    .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                    .collect();
+                if node.starts_with(|c: char| c.is_ascii_digit()) {
+                    node.insert(0, '_');
+                }
+                node
+            }
+            None => format!("Object_{}", n),
+        }
+    }
+
+    /// Translation of the object's node and the origin of its mesh. Objects with a pivot have
+    /// their node at it and the mesh around the node's origin, the others keep the origin of
+    /// their scene.
+    pub fn transform(&self) -> ([f32; 3], [f32; 3]) {
+        let (origin, scale) = (self.part.origin(), self.part.voxel_scale());
+        match self.pivot {
+            Some(pivot) => {
+                let mut translation = [0.0; 3];
+                let mut mesh_origin = [0.0; 3];
+                for i in 0..3 {
+                    translation[i] = pivot[i] * scale[i] + origin[i];
+                    mesh_origin[i] = -pivot[i] * scale[i];
+                }
+                (translation, mesh_origin)
+            }
+            None => ([0.0; 3], origin),
+        }
+    }
+}
+
+/// The named objects of the first scene, its saved masks with their filled cells, followed by
+/// the connected objects of the voxels outside of every mask and of the other scenes. The cells
+/// of the masks and their pivots are moved back by the offset, the corner the scene was cropped
+/// to.
+pub fn export_objects<'a>(
+    parts: &[&'a VoxelManager],
+    masks: &SavedMasks,
+    offset: [usize; 3],
+) -> Vec<ExportObject<'a>> {
+    let scene = parts[0];
+    let extent = scene.extent();
+    let mut objects = Vec::new();
+    let mut masked = HashSet::new();
+    for name in masks.names() {
+        let mut cells: Vec<[usize; 3]> = masks
+            .get(name)
+            .map_or_else(Vec::new, |mask| mask.cells())
+            .into_iter()
+            .filter_map(|cell| {
+                let mut moved = [0; 3];
+                for i in 0..3 {
+                    moved[i] = cell[i].checked_sub(offset[i]).filter(|c| *c < extent)?;
+                }
+                scene.voxel(moved[0], moved[1], moved[2])?;
+                Some(moved)
+            })
+            .collect();
+        if cells.is_empty() {
+            continue;
+        }
+        cells.sort();
+        // Bounds pivots go by the cells in the scene, cell pivots may be outside of the crop
+        let pivot = masks.pivot(name).and_then(|pivot| {
+            let unmoved: Vec<[usize; 3]> = cells
+                .iter()
+                .map(|[x, y, z]| [x + offset[0], y + offset[1], z + offset[2]])
+                .collect();
+            let mut point = pivot.point(&unmoved)?;
+            for i in 0..3 {
+                point[i] -= offset[i] as f32;
+            }
+            Some(point)
+        });
+        masked.extend(cells.iter().cloned());
+        objects.push(ExportObject {
+            part: scene,
+            name: Some(name.to_owned()),
+            cells,
+            pivot,
+        });
+    }
+    let mut rest = scene.clone();
+    for [x, y, z] in masked {
+        rest.set_voxel(x, y, z, None);
+    }
+    let unmasked = analysis::components(&rest)
+        .into_iter()
+        .map(|cells| (scene, cells));
+    let others = analysis::part_components(&parts[1..]);
+    for (part, cells) in unmasked.chain(others) {
+        objects.push(ExportObject {
+            part,
+            name: None,
+            cells,
+            pivot: None,
+        });
+    }
+    objects
+}
+
+/// The mesh origin placing the pivot of the bounds of the cells at the origin, for cells of the
+/// given size
+fn pivot_origin(cells: &[[usize; 3]], pivot: Pivot, voxel_scale: [f32; 3]) -> Option<[f32; 3]> {
+    let (min, max) = bounds(cells)?;
+    let [x, y, z] = pivot.point(min, max);
+    let [sx, sy, sz] = voxel_scale;
+    Some([-x * sx, -y * sy, -z * sz])
+}
+
+/// Writes every object into its own .obj file, named by the template. Saved masks with a pivot
+/// are moved to have it at the origin, the other objects keep the origin of their scene.
+/// Returns the number of written files.
+pub fn write_objects(
+    objects: &[ExportObject],
+    file_path: &str,
+    template: &str,
+    texture_atlas: bool,
+    progress: &mut Progress,
+) -> io::Result<usize> {
+    let count = objects.len();
+    for (i, object) in objects.iter().enumerate() {
+        let part = object.part;
+        let (_, origin) = object.transform();
+        let mesh = part.cells_by_color(&object.cells, origin);
+        let path = batch_path(file_path, template, object.name.as_deref(), i + 1);
+        let mut object_progress = |p: f32| progress((i as f32 + p) / count as f32);
+        write_obj_parts(
+            &[(part, mesh)],
+            &path.to_string_lossy(),
+            texture_atlas,
+            &mut object_progress,
+        )?;
+    }
+    Ok(count)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ExportFormat {
+    /// The whole scene as a single .obj file
+    Obj,
+    /// Every connected object as its own .obj file
+    Objects,
+    /// A .usda layer or .usdz archive, chosen by the file extension
+    Usd,
+    /// An ASCII .fbx file
+    Fbx,
+    /// A Collada .dae file
+    Collada,
+    /// An OpenVDB .vdb volume
+    Vdb,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 6] = [
+        ExportFormat::Obj,
+        ExportFormat::Objects,
+        ExportFormat::Usd,
+        ExportFormat::Fbx,
+        ExportFormat::Collada,
+        ExportFormat::Vdb,
+    ];
+}
+
+impl std::fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Self, ()> {
+        ExportFormat::ALL
+            .iter()
+            .find(|format| format.to_string() == name)
+            .copied()
+            .ok_or(())
+    }
+}
+
+/// How much of the grid around the voxels an export keeps
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Crop {
+    /// The whole grid
+    Grid,
+    /// The bounds of the voxels
+    Content,
+}
+
+impl Crop {
+    pub const ALL: [Crop; 2] = [Crop::Grid, Crop::Content];
+}
+
+impl Default for Crop {
+    fn default() -> Crop {
+        Crop::Grid
+    }
+}
+
+impl std::fmt::Display for Crop {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl FromStr for Crop {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Self, ()> {
+        Crop::ALL
+            .iter()
+            .find(|crop| crop.to_string() == name)
+            .copied()
+            .ok_or(())
+    }
+}
+
+/// The scene as it is exported: cropped into a grid just large enough for the bounds of the
+/// voxels, its corner at the first filled cell, and with the meshes and volumes moved to have
+/// the pivot of the bounds at the origin. None when it is exported as it is and for empty
+/// scenes.
+pub fn placed(
+    voxel_manager: &VoxelManager,
+    crop: Crop,
+    pivot: Option<Pivot>,
+) -> Option<VoxelManager> {
+    if crop == Crop::Grid && pivot.is_none() {
+        return None;
+    }
+    let cells: Vec<[usize; 3]> = analysis::filled_cells(voxel_manager)
+        .into_iter()
+        .map(|(cell, _)| cell)
+        .collect();
+    let (min, max) = bounds(&cells)?;
+    let mut placed = match crop {
+        Crop::Grid => voxel_manager.clone(),
+        Crop::Content => {
+            let size = (0..3).map(|i| max[i] - min[i] + 1).max().unwrap_or(1);
+            let mut content = VoxelManager::new(size);
+            content.set_palette(voxel_manager.palette().clone());
+            content.set_voxel_scale(voxel_manager.voxel_scale());
+            for [x, y, z] in cells.iter() {
+                let value = voxel_manager.voxel(*x, *y, *z);
+                content.set_voxel(x - min[0], y - min[1], z - min[2], value);
+            }
+            content
+        }
+    };
+    if let Some(pivot) = pivot {
+        let cells: Vec<[usize; 3]> = analysis::filled_cells(&placed)
+            .into_iter()
+            .map(|(cell, _)| cell)
+            .collect();
+        placed.set_origin(pivot_origin(&cells, pivot, placed.voxel_scale())?);
+    }
+    Some(placed)
+}
+
+/// Moves the colors of the later scenes into the palette of the first and gives every scene
+/// that palette
+fn share_palette(parts: &mut [VoxelManager]) {
+    let mut palette = parts[0].palette().clone();
+    for part in parts[1..].iter_mut() {
+        let mut indices = BTreeMap::new();
+        for ([x, y, z], value) in analysis::filled_cells(part) {
+            let color = part.palette().color(value);
+            let index = *indices
+                .entry(value)
+                .or_insert_with(|| palette.index_of(color));
+            part.set_voxel(x, y, z, Some(index));
+        }
+    }
+    for part in parts.iter_mut() {
+        part.set_palette(palette.clone());
+    }
+}
+
+/// The cell of the scene the grid of the placed scene starts at
+fn crop_corner(scene: &VoxelManager, crop: Crop) -> [usize; 3] {
+    match crop {
+        Crop::Grid => [0; 3],
+        Crop::Content => {
+            let cells: Vec<[usize; 3]> = analysis::filled_cells(scene)
+                .into_iter()
+                .map(|(cell, _)| cell)
+                .collect();
+            bounds(&cells).map_or([0; 3], |(min, _)| min)
+        }
+    }
+}
+
+/// Where the corner of the scene's grid is among the meshes of the placed scene
+fn placed_grid_corner(scene: &VoxelManager, placed: &VoxelManager, crop: Crop) -> [f32; 3] {
+    let min = crop_corner(scene, crop);
+    let (origin, scale) = (placed.origin(), placed.voxel_scale());
+    let mut corner = [0.0; 3];
+    for i in 0..3 {
+        corner[i] = origin[i] - min[i] as f32 * scale[i];
+    }
+    corner
+}
+
+/// Everything needed to repeat an export without going through the dialogs again
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ExportPreset {
+    pub format: ExportFormat,
+    pub file_path: String,
+    pub texture_atlas: bool,
+    /// File name template of the batch export
+    pub template: String,
+    pub crop: Crop,
+    /// Point of the bounds of the voxels placed at the origin, None keeps the grid's corner there
+    pub pivot: Option<Pivot>,
+}
+
+impl ExportPreset {
+    /// The sidecar file a project's preset is kept in
+    pub fn path(project_path: &str) -> PathBuf {
+        PathBuf::from(format!("{}.export", project_path))
+    }
+
+    /// Reads the preset of the project, None when it has none yet
+    pub fn load(project_path: &str) -> Option<Self> {
+        let settings = Settings::read(&ExportPreset::path(project_path)).ok()?;
+        Some(ExportPreset {
+            format: settings.get("export.format")?,
+            file_path: settings.get("export.path")?,
+            texture_atlas: settings.get("export.texture_atlas").unwrap_or(false),
+            template: settings
+                .get("export.template")
+                .unwrap_or_else(|| "{name}_{n}".to_owned()),
+            crop: settings.get("export.crop").unwrap_or_default(),
+            pivot: settings.get("export.pivot"),
+        })
+    }
+
+    pub fn save(&self, project_path: &str) -> io::Result<()> {
+        let mut settings = Settings::default();
+        settings.set("export.format", self.format);
+        settings.set("export.path", &self.file_path);
+        settings.set("export.texture_atlas", self.texture_atlas);
+        settings.set("export.template", &self.template);
+        settings.set("export.crop", self.crop);
+        if let Some(pivot) = self.pivot {
+            settings.set("export.pivot", pivot);
+        }
+        settings.write(&ExportPreset::path(project_path))
+    }
+
+    pub fn export(&self, voxel_manager: &VoxelManager, progress: &mut Progress) -> io::Result<()> {
+        self.export_with_sub_grids(voxel_manager, &[], &SavedMasks::default(), progress)
+    }
+
+    /// Exports the scene with the finer sub-grids embedded in it. The meshes of the sub-grids
+    /// are placed alongside the scene's, volumes have a single voxel size and get the
+    /// sub-grids flattened into the scene instead. The crop and the pivot go by the scene's
+    /// own voxels. The saved masks are the named objects of the scene, placed at their pivots.
+    pub fn export_with_sub_grids(
+        &self,
+        voxel_manager: &VoxelManager,
+        sub_grids: &[SubGrid],
+        masks: &SavedMasks,
+        progress: &mut Progress,
+    ) -> io::Result<()> {
+        let flattened;
+        let (voxel_manager, sub_grids) = if self.format == ExportFormat::Vdb {
+            let mut scene = voxel_manager.clone();
+            for sub_grid in sub_grids.iter() {
+                sub_grid.flatten(&mut scene);
+            }
+            flattened = scene;
+            (&flattened, &[][..])
+        } else {
+            (voxel_manager, sub_grids)
+        };
+        let placed = placed(voxel_manager, self.crop, self.pivot);
+        let scene = voxel_manager;
+        let voxel_manager = placed.as_ref().unwrap_or(voxel_manager);
+        let grid_corner = placed_grid_corner(scene, voxel_manager, self.crop);
+        let mut parts = vec![voxel_manager.clone()];
+        parts.extend(
+            sub_grids
+                .iter()
+                .map(|sub_grid| sub_grid.placed(grid_corner, voxel_manager.voxel_scale())),
+        );
+        share_palette(&mut parts);
+        let parts: Vec<&VoxelManager> = parts.iter().collect();
+        let objects = || export_objects(&parts, masks, crop_corner(scene, self.crop));
+        let palette = parts[0].palette();
+        match self.format {
+            ExportFormat::Obj => {
+                let meshes: Vec<_> = parts
+                    .iter()
+                    .map(|part| (*part, part.vertices_by_color()))
+                    .collect();
+                write_obj_parts(&meshes, &self.file_path, self.texture_atlas, progress)
+            }
+            ExportFormat::Objects => write_objects(
+                &objects(),
+                &self.file_path,
+                &self.template,
+                self.texture_atlas,
+                progress,
+            )
+            .map(|_| ()),
+            ExportFormat::Usd => usd::write_usd(&objects(), palette, &self.file_path, progress),
+            ExportFormat::Fbx => fbx::write_fbx(&objects(), palette, &self.file_path, progress),
+            ExportFormat::Collada => {
+                collada::write_dae(&objects(), palette, &self.file_path, progress)
+            }
+            ExportFormat::Vdb => vdb::write_vdb(parts[0], &self.file_path, progress),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::selection::Selection;
+    use crate::transform::ObjectPivot;
+
+    #[test]
+    fn palette_entries_map_to_their_atlas_texels() {
+        assert_eq!(atlas_uv(0), [0.5 / 16.0, 1.0 - 0.5 / 16.0]);
+        assert_eq!(atlas_uv(17), [1.5 / 16.0, 1.0 - 1.5 / 16.0]);
+
+        let palette = Palette::from_colors(vec![[1.0, 0.5, 0.0, 1.0], [0.0, 0.0, 1.0, 1.0]]);
+        let tga = atlas_tga(&palette);
+        assert_eq!(tga.len(), 18 + 4 * PALETTE_SIZE);
+        assert_eq!(tga[18..26], [0, 128, 255, 255, 255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn presets_are_kept_next_to_the_project() {
+        let project_path = std::env::temp_dir().join("voxel-editor-preset-test.vxe");
+        let project_path = project_path.to_string_lossy();
+        let preset = ExportPreset {
+            format: ExportFormat::Objects,
+            file_path: "/tmp/game/props.obj".to_owned(),
+            texture_atlas: true,
+            template: "prop_{n}".to_owned(),
+            crop: Crop::Content,
+            pivot: Some(Pivot::BottomCenter),
+        };
+        preset.save(&project_path).unwrap();
+        assert_eq!(ExportPreset::load(&project_path), Some(preset));
+        std::fs::remove_file(ExportPreset::path(&project_path)).unwrap();
+        assert_eq!(ExportPreset::load(&project_path), None);
+    }
+
+    #[test]
+    fn crops_trim_the_empty_grid_and_pivots_move_to_the_origin() {
+        let mut scene = VoxelManager::new(32);
+        let red = scene.palette_mut().index_of([1.0, 0.0, 0.0, 1.0]);
+        scene.set_voxel(10, 4, 20, Some(red));
+        scene.set_voxel(13, 4, 21, Some(red));
+        assert!(placed(&scene, Crop::Grid, None).is_none());
+        assert!(placed(&VoxelManager::new(32), Crop::Content, None).is_none());
+
+        let content = placed(&scene, Crop::Content, None).unwrap();
+        assert_eq!(content.extent(), 4);
+        assert_eq!(content.voxel(0, 0, 0), Some(red));
+        assert_eq!(content.voxel(3, 0, 1), Some(red));
+
+        let lowest = |scene: &VoxelManager| {
+            let (vertices, _) = scene.vertices();
+            let lowest = |i: usize| vertices.iter().map(|v| v.pos[i]).fold(f32::MAX, f32::min);
+            [lowest(0), lowest(1), lowest(2)]
+        };
+        let centered = placed(&scene, Crop::Content, Some(Pivot::Center)).unwrap();
+        assert_eq!(lowest(&centered), [-2.0, -0.5, -1.0]);
+        let standing = placed(&scene, Crop::Grid, Some(Pivot::BottomCenter)).unwrap();
+        assert_eq!(standing.extent(), 32);
+        assert_eq!(lowest(&standing), [-2.0, 0.0, -1.0]);
+    }
+
+    #[test]
+    fn sub_grids_are_exported_with_the_scene() {
+        let mut scene = VoxelManager::new(8);
+        let red = scene.palette_mut().index_of([1.0, 0.0, 0.0, 1.0]);
+        scene.set_voxel(0, 0, 0, Some(red));
+        scene.set_voxel(6, 6, 6, Some(red));
+        let mut sub_grid = SubGrid::refine(&mut scene, [6, 6, 6], 4).unwrap();
+        let blue = sub_grid.scene.palette_mut().index_of([0.0, 0.0, 1.0, 1.0]);
+        sub_grid.scene.set_voxel(0, 0, 0, Some(blue));
+
+        let path = std::env::temp_dir().join("voxel-editor-sub-grid-test.usda");
+        let preset = ExportPreset {
+            format: ExportFormat::Usd,
+            file_path: path.to_string_lossy().into_owned(),
+            texture_atlas: false,
+            template: "{name}_{n}".to_owned(),
+            crop: Crop::Grid,
+            pivot: None,
+        };
+        preset
+            .export_with_sub_grids(&scene, &[sub_grid], &SavedMasks::default(), &mut |_| true)
+            .unwrap();
+        let layer = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(layer.matches("def Xform \"Object_").count(), 2);
+        assert_eq!(layer.matches("def Material").count(), 2);
+    }
+
+    #[test]
+    fn batch_paths_follow_the_template() {
+        let path = batch_path("/tmp/props/scene.obj", "{name}_{n}", None, 3);
+        assert_eq!(path, Path::new("/tmp/props/scene_3.obj"));
+        let path = batch_path("scene.obj", "crate-{n}", None, 12);
+        assert_eq!(path, Path::new("crate-12.obj"));
+        let path = batch_path("/tmp/scene.obj", "{name}", None, 2);
+        assert_eq!(path, Path::new("/tmp/scene_2.obj"));
+        let path = batch_path("/tmp/scene.obj", "{name}", Some("barrel"), 2);
+        assert_eq!(path, Path::new("/tmp/barrel.obj"));
+        let path = batch_path("/tmp/scene.obj", "prop", Some("barrel"), 2);
+        assert_eq!(path, Path::new("/tmp/prop_2.obj"));
+    }
+
+    #[test]
+    fn saved_masks_are_the_named_objects() {
+        let mut scene = VoxelManager::new(16);
+        let red = scene.palette_mut().index_of([1.0, 0.0, 0.0, 1.0]);
+        for x in 4..8 {
+            scene.set_voxel(x, 2, 2, Some(red));
+        }
+        scene.set_voxel(10, 2, 2, Some(red));
+        let mut masks = SavedMasks::default();
+        // Empty cells of the mask aren't part of the object
+        masks.insert(
+            "barrel",
+            Selection::from_cells(vec![[4, 2, 2], [5, 2, 2], [5, 3, 2]]),
+        );
+
+        // Cropped to the voxels the grid starts at the first filled cell
+        let cropped = placed(&scene, Crop::Content, None).unwrap();
+        let objects = export_objects(&[&cropped], &masks, crop_corner(&scene, Crop::Content));
+        assert_eq!(objects.len(), 3);
+        assert_eq!(objects[0].name.as_deref(), Some("barrel"));
+        assert_eq!(objects[0].cells, vec![[0, 0, 0], [1, 0, 0]]);
+        assert_eq!(objects[1].name, None);
+        assert_eq!(objects[1].cells.len(), 2);
+        assert_eq!(objects[2].cells, vec![[6, 0, 0]]);
+
+        // The node of a mask with a pivot is placed at it, the pivot follows the crop
+        masks.set_pivot("barrel", Some(ObjectPivot::Bounds(Pivot::BottomCenter)));
+        let objects = export_objects(&[&cropped], &masks, crop_corner(&scene, Crop::Content));
+        assert_eq!(objects[0].pivot, Some([1.0, 0.0, 0.5]));
+        assert_eq!(objects[0].transform(), ([1.0, 0.0, 0.5], [-1.0, 0.0, -0.5]));
+        assert_eq!(objects[1].transform(), ([0.0; 3], [0.0; 3]));
+        let palette = cropped.palette();
+        let layer = usd::usda(&objects, palette, &mut |_| true).unwrap();
+        assert!(layer.contains(
+            "def Xform \"barrel\"\n    {\n        double3 xformOp:translate = (1, 0, 0.5)\n"
+        ));
+        assert_eq!(layer.matches("xformOpOrder").count(), 1);
+        let document = fbx::fbx(&objects, palette, &mut |_| true).unwrap();
+        assert!(document.contains("\"Model::barrel\", \"Mesh\" {\n\t\tVersion: 232\n\t\tProperties70:  {\n\t\t\tP: \"Lcl Translation\", \"Lcl Translation\", \"\", \"A\",1,0,0.5\n"));
+        let document = collada::dae(&objects, palette, &mut |_| true).unwrap();
+        assert!(document.contains("<node id=\"barrel\" name=\"barrel\" type=\"NODE\">\n          <translate sid=\"translate\">1 0 0.5</translate>\n"));
+        assert_eq!(document.matches("<translate ").count(), 1);
+    }
+}
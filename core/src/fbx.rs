@@ -0,0 +1,215 @@
+//! ASCII FBX 7.4 export. Every object becomes a mesh model, translated to the pivot of saved
+//! masks that have one, the palette colors it uses are lambert materials assigned per face.
+use crate::export::ExportObject;
+use crate::palette::Palette;
+use crate::task::{cancelled, Progress};
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+
+const GEOMETRY_ID: usize = 1_000_000;
+const MODEL_ID: usize = 2_000_000;
+const MATERIAL_ID: usize = 3_000_000;
+
+fn join<T: std::fmt::Display>(values: impl Iterator<Item = T>) -> String {
+    let mut out = String::new();
+    for (i, value) in values.enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        let _ = write!(out, "{}", value);
+    }
+    out
+}
+
+/// Writes an array property, FBX prefixes arrays with their length
+fn array(out: &mut String, indent: &str, name: &str, len: usize, values: String) {
+    let _ = write!(
+        out,
+        "{0}{1}: *{2} {{\n{0}\ta: {3}\n{0}}}\n",
+        indent, name, len, values
+    );
+}
+
+/// The objects as an ASCII FBX document, their colors are entries of the palette
+pub fn fbx(
+    objects: &[ExportObject],
+    palette: &Palette,
+    progress: &mut Progress,
+) -> io::Result<String> {
+    let mut objects_section = String::new();
+    let mut connections = String::new();
+    let mut used = BTreeSet::new();
+
+    for (i, object) in objects.iter().enumerate() {
+        if !progress(i as f32 / objects.len() as f32) {
+            return Err(cancelled());
+        }
+        let (translation, origin) = object.transform();
+        let meshes = object.part.cells_by_color(&object.cells, origin);
+        // Material indices of a model count in the order its materials are connected
+        let materials: Vec<u8> = meshes
+            .iter()
+            .map(|(color_index, _, _)| *color_index)
+            .collect();
+        let vertices: Vec<_> = meshes
+            .iter()
+            .flat_map(|(_, vertices, _)| vertices.iter())
+            .collect();
+        used.extend(materials.iter().cloned());
+
+        let name = object.node_name(i + 1);
+        let _ = writeln!(
+            objects_section,
+            "\tGeometry: {}, \"Geometry::{}\", \"Mesh\" {{",
+            GEOMETRY_ID + i,
+            name
+        );
+        array(
+            &mut objects_section,
+            "\t\t",
+            "Vertices",
+            vertices.len() * 3,
+            join(vertices.iter().flat_map(|v| v.pos.to_vec())),
+        );
+        // Faces are quads of 4 consecutive vertices, the last index of a face is negated
+        array(
+            &mut objects_section,
+            "\t\t",
+            "PolygonVertexIndex",
+            vertices.len(),
+            join((0..vertices.len() as i64).map(|v| if v % 4 == 3 { -v - 1 } else { v })),
+        );
+        objects_section.push_str("\t\tGeometryVersion: 124\n");
+        objects_section.push_str("\t\tLayerElementNormal: 0 {\n\t\t\tVersion: 101\n\t\t\tName: \"\"\n\t\t\tMappingInformationType: \"ByPolygonVertex\"\n\t\t\tReferenceInformationType: \"Direct\"\n");
+        array(
+            &mut objects_section,
+            "\t\t\t",
+            "Normals",
+            vertices.len() * 3,
+            join(vertices.iter().flat_map(|v| v.normal.to_vec())),
+        );
+        objects_section.push_str("\t\t}\n");
+        objects_section.push_str("\t\tLayerElementMaterial: 0 {\n\t\t\tVersion: 101\n\t\t\tName: \"\"\n\t\t\tMappingInformationType: \"ByPolygon\"\n\t\t\tReferenceInformationType: \"IndexToDirect\"\n");
+        // Faces are quads of 4 consecutive vertices of their color
+        let face_materials = meshes
+            .iter()
+            .enumerate()
+            .flat_map(|(material, (_, vertices, _))| {
+                std::iter::repeat(material).take(vertices.len() / 4)
+            });
+        array(
+            &mut objects_section,
+            "\t\t\t",
+            "Materials",
+            vertices.len() / 4,
+            join(face_materials),
+        );
+        objects_section.push_str("\t\t}\n");
+        objects_section.push_str("\t\tLayer: 0 {\n\t\t\tVersion: 100\n\t\t\tLayerElement:  {\n\t\t\t\tType: \"LayerElementNormal\"\n\t\t\t\tTypedIndex: 0\n\t\t\t}\n\t\t\tLayerElement:  {\n\t\t\t\tType: \"LayerElementMaterial\"\n\t\t\t\tTypedIndex: 0\n\t\t\t}\n\t\t}\n");
+        objects_section.push_str("\t}\n");
+
+        let _ = writeln!(
+            objects_section,
+            "\tModel: {}, \"Model::{}\", \"Mesh\" {{\n\t\tVersion: 232",
+            MODEL_ID + i,
+            name
+        );
+        if object.pivot.is_some() {
+            let [x, y, z] = translation;
+            let _ = writeln!(
+                objects_section,
+                "\t\tProperties70:  {{\n\t\t\tP: \"Lcl Translation\", \"Lcl Translation\", \"\", \"A\",{},{},{}\n\t\t}}",
+                x, y, z
+            );
+        }
+        objects_section.push_str("\t\tCulling: \"CullingOff\"\n\t}\n");
+
+        let _ = writeln!(connections, "\tC: \"OO\",{},0", MODEL_ID + i);
+        let _ = writeln!(
+            connections,
+            "\tC: \"OO\",{},{}",
+            GEOMETRY_ID + i,
+            MODEL_ID + i
+        );
+        for color_index in materials.iter() {
+            let _ = writeln!(
+                connections,
+                "\tC: \"OO\",{},{}",
+                MATERIAL_ID + *color_index as usize,
+                MODEL_ID + i
+            );
+        }
+    }
+
+    for color_index in used.iter() {
+        let [r, g, b, a] = palette.color(*color_index);
+        let _ = writeln!(
+            objects_section,
+            "\tMaterial: {}, \"Material::Color_{}\", \"\" {{\n\t\tVersion: 102\n\t\tShadingModel: \"lambert\"\n\t\tMultiLayer: 0\n\t\tProperties70:  {{\n\t\t\tP: \"DiffuseColor\", \"Color\", \"\", \"A\",{},{},{}\n\t\t\tP: \"Opacity\", \"double\", \"Number\", \"\",{}\n\t\t}}\n\t}}",
+            MATERIAL_ID + *color_index as usize,
+            color_index,
+            r,
+            g,
+            b,
+            a
+        );
+    }
+
+    let mut out = String::from("; FBX 7.4.0 project file\n");
+    out.push_str("FBXHeaderExtension:  {\n\tFBXHeaderVersion: 1003\n\tFBXVersion: 7400\n\tCreator: \"voxel-editor\"\n}\n");
+    // Y up, one voxel is 10 cm
+    out.push_str("GlobalSettings:  {\n\tVersion: 1000\n\tProperties70:  {\n\t\tP: \"UpAxis\", \"int\", \"Integer\", \"\",1\n\t\tP: \"UpAxisSign\", \"int\", \"Integer\", \"\",1\n\t\tP: \"FrontAxis\", \"int\", \"Integer\", \"\",2\n\t\tP: \"FrontAxisSign\", \"int\", \"Integer\", \"\",1\n\t\tP: \"CoordAxis\", \"int\", \"Integer\", \"\",0\n\t\tP: \"CoordAxisSign\", \"int\", \"Integer\", \"\",1\n\t\tP: \"UnitScaleFactor\", \"double\", \"Number\", \"\",10\n\t}\n}\n");
+    let _ = writeln!(
+        out,
+        "Definitions:  {{\n\tVersion: 100\n\tCount: {}\n\tObjectType: \"Model\" {{\n\t\tCount: {}\n\t}}\n\tObjectType: \"Geometry\" {{\n\t\tCount: {}\n\t}}\n\tObjectType: \"Material\" {{\n\t\tCount: {}\n\t}}\n}}",
+        2 * objects.len() + used.len(),
+        objects.len(),
+        objects.len(),
+        used.len()
+    );
+    out.push_str("Objects:  {\n");
+    out.push_str(&objects_section);
+    out.push_str("}\nConnections:  {\n");
+    out.push_str(&connections);
+    out.push_str("}\n");
+    Ok(out)
+}
+
+pub fn write_fbx(
+    objects: &[ExportObject],
+    palette: &Palette,
+    file_path: &str,
+    progress: &mut Progress,
+) -> io::Result<()> {
+    fs::write(file_path, fbx(objects, palette, progress)?)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::export::export_objects;
+    use crate::selection::SavedMasks;
+    use crate::voxel_manager::VoxelManager;
+
+    #[test]
+    fn objects_are_models_with_per_face_materials() {
+        let mut voxel_manager = VoxelManager::new(8);
+        let red = voxel_manager.palette_mut().index_of([1.0, 0.0, 0.0, 1.0]);
+        let blue = voxel_manager.palette_mut().index_of([0.0, 0.0, 1.0, 1.0]);
+        voxel_manager.set_voxel(0, 0, 0, Some(red));
+        voxel_manager.set_voxel(1, 0, 0, Some(blue));
+        voxel_manager.set_voxel(5, 5, 5, Some(red));
+
+        let objects = export_objects(&[&voxel_manager], &SavedMasks::default(), [0; 3]);
+        let document = fbx(&objects, voxel_manager.palette(), &mut |_| true).unwrap();
+        assert_eq!(document.matches("\tModel: ").count(), 2);
+        assert_eq!(document.matches("\tMaterial: ").count(), 2);
+        assert!(document.contains("PolygonVertexIndex: *48 {\n\t\t\ta: 0,1,2,-4,4,"));
+        // Both colors of the first object are connected to it, the red one first
+        assert!(document.contains("\tC: \"OO\",3000000,2000000\n\tC: \"OO\",3000001,2000000\n"));
+        assert!(document.contains("Materials: *12 {\n\t\t\t\ta: 0,0,0,0,0,0,1,1,1,1,1,1\n"));
+    }
+}
@@ -0,0 +1,114 @@
+/// Characters the bitmap fonts have glyphs for, lowercase letters use the uppercase glyphs
+const CHARS: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789.,!?-:'";
+
+// Glyph rows from the top, the highest used bit is the leftmost column
+#[rustfmt::skip]
+const TINY: [[u8; 5]; 43] = [
+    [2, 5, 7, 5, 5], [6, 5, 6, 5, 6], [3, 4, 4, 4, 3], [6, 5, 5, 5, 6], [7, 4, 6, 4, 7],
+    [7, 4, 6, 4, 4], [3, 4, 5, 5, 3], [5, 5, 7, 5, 5], [7, 2, 2, 2, 7], [1, 1, 1, 5, 2],
+    [5, 5, 6, 5, 5], [4, 4, 4, 4, 7], [5, 7, 7, 5, 5], [6, 5, 5, 5, 5], [2, 5, 5, 5, 2],
+    [6, 5, 6, 4, 4], [2, 5, 5, 6, 3], [6, 5, 6, 5, 5], [3, 4, 2, 1, 6], [7, 2, 2, 2, 2],
+    [5, 5, 5, 5, 7], [5, 5, 5, 5, 2], [5, 5, 7, 7, 5], [5, 5, 2, 5, 5], [5, 5, 2, 2, 2],
+    [7, 1, 2, 4, 7],
+    [7, 5, 5, 5, 7], [2, 6, 2, 2, 7], [6, 1, 2, 4, 7], [6, 1, 2, 1, 6], [5, 5, 7, 1, 1],
+    [7, 4, 6, 1, 6], [3, 4, 7, 5, 7], [7, 1, 1, 2, 2], [7, 5, 7, 5, 7], [7, 5, 7, 1, 6],
+    [0, 0, 0, 0, 2], [0, 0, 0, 2, 4], [2, 2, 2, 0, 2], [6, 1, 2, 0, 2], [0, 0, 7, 0, 0],
+    [0, 2, 0, 2, 0], [2, 2, 0, 0, 0],
+];
+
+#[rustfmt::skip]
+const CLASSIC: [[u8; 7]; 43] = [
+    [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11], [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+    [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E], [0x1E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x1E],
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F], [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+    [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F], [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+    [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E], [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+    [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11], [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+    [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11], [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+    [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E], [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+    [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D], [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+    [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E], [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E], [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+    [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A], [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+    [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04], [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+    [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E], [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+    [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F], [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+    [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02], [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+    [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E], [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+    [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E], [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C], [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08],
+    [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04], [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04],
+    [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00], [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+    [0x0C, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00],
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FontKind {
+    /// 3x5 pixel glyphs for small labels
+    Tiny,
+    /// 5x7 pixel glyphs
+    Classic,
+}
+
+impl FontKind {
+    pub const ALL: [FontKind; 2] = [FontKind::Tiny, FontKind::Classic];
+
+    fn size(self) -> (usize, usize) {
+        match self {
+            FontKind::Tiny => (3, 5),
+            FontKind::Classic => (5, 7),
+        }
+    }
+
+    /// Rows of the glyph from the top, None for characters without a glyph
+    fn glyph(self, c: char) -> Option<&'static [u8]> {
+        let index = CHARS.find(c.to_ascii_uppercase())?;
+        Some(match self {
+            FontKind::Tiny => &TINY[index],
+            FontKind::Classic => &CLASSIC[index],
+        })
+    }
+
+    /// Pixels of the text as (column, row) pairs, rows counting up from the baseline.
+    /// Characters without a glyph are left blank like spaces.
+    pub fn rasterize(self, text: &str) -> Vec<[usize; 2]> {
+        let (width, height) = self.size();
+        let mut pixels = Vec::new();
+        for (i, c) in text.chars().enumerate() {
+            let left = i * (width + 1);
+            if let Some(rows) = self.glyph(c) {
+                for (row, bits) in rows.iter().enumerate() {
+                    for column in 0..width {
+                        if bits & (1 << (width - 1 - column)) != 0 {
+                            pixels.push([left + column, height - 1 - row]);
+                        }
+                    }
+                }
+            }
+        }
+        pixels
+    }
+}
+
+impl Default for FontKind {
+    fn default() -> FontKind {
+        FontKind::Classic
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn rasterize_places_glyphs_side_by_side() {
+        let pixels = FontKind::Tiny.rasterize("i-");
+        // The top bar of the I and the dash of the next glyph in the middle row
+        assert!(pixels.contains(&[0, 4]));
+        assert!(pixels.contains(&[2, 0]));
+        assert!(pixels.contains(&[4, 2]));
+        assert!(!pixels.contains(&[3, 2]));
+        assert_eq!(pixels.len(), 9 + 3);
+    }
+}
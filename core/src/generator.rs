@@ -0,0 +1,402 @@
+use crate::font::FontKind;
+use crate::geometry::{brush_cells, grid_line};
+use crate::transform::Axis;
+use crate::voxel_manager::VoxelManager;
+use std::collections::HashSet;
+
+/// Options of the stairs tool
+#[derive(Debug, Clone, Copy)]
+pub struct StairsOptions {
+    /// Cells every step reaches along the run, one cell deep steps make a ramp
+    pub step_depth: usize,
+    /// Adds a one voxel rail above both side edges of the stairs
+    pub railing: bool,
+}
+
+impl Default for StairsOptions {
+    fn default() -> StairsOptions {
+        StairsOptions {
+            step_depth: 1,
+            railing: false,
+        }
+    }
+}
+
+/// Fills the stepped volume of the stairs climbing from the start cell to the end cell.
+/// The stairs run along the horizontal axis the points are farther apart on and span the
+/// other one, the steps rise evenly to reach the height of the higher point.
+pub fn stairs(
+    voxel_manager: &mut VoxelManager,
+    start: [i32; 3],
+    end: [i32; 3],
+    options: StairsOptions,
+    color: [f32; 4],
+) {
+    let (run, span) = if (end[0] - start[0]).abs() >= (end[2] - start[2]).abs() {
+        (0, 2)
+    } else {
+        (2, 0)
+    };
+    // Climb from the lower point towards the higher one
+    let (low, high) = if start[1] <= end[1] {
+        (start, end)
+    } else {
+        (end, start)
+    };
+    let length = (high[run] - low[run]).abs() + 1;
+    let direction = if high[run] >= low[run] { 1 } else { -1 };
+    let height = high[1] - low[1] + 1;
+    let depth = options.step_depth.max(1) as i32;
+    let step_count = (length + depth - 1) / depth;
+    let (span_min, span_max) = (low[span].min(high[span]), low[span].max(high[span]));
+
+    for t in 0..length {
+        let step = t / depth;
+        let step_height = ((step + 1) * height + step_count - 1) / step_count;
+        for s in span_min..=span_max {
+            let mut cell = low;
+            cell[run] = low[run] + t * direction;
+            cell[span] = s;
+            for y in 0..step_height {
+                cell[1] = low[1] + y;
+                voxel_manager.paint_cell(cell, color, true);
+            }
+            if options.railing && (s == span_min || s == span_max) {
+                cell[1] = low[1] + step_height;
+                voxel_manager.paint_cell(cell, color, true);
+            }
+        }
+    }
+}
+
+/// Options of the text tool
+#[derive(Debug, Clone)]
+pub struct TextOptions {
+    pub text: String,
+    pub font: FontKind,
+    /// Voxels per font pixel along the plane
+    pub scale: usize,
+    /// Layers of voxels along the plane normal
+    pub depth: usize,
+}
+
+impl Default for TextOptions {
+    fn default() -> TextOptions {
+        TextOptions {
+            text: String::new(),
+            font: FontKind::default(),
+            scale: 1,
+            depth: 1,
+        }
+    }
+}
+
+/// Fills the rasterized text on the plane with the given normal,
+/// the bottom left corner of the first glyph on the origin cell
+pub fn text(
+    voxel_manager: &mut VoxelManager,
+    origin: [i32; 3],
+    plane_normal: Axis,
+    options: &TextOptions,
+    color: [f32; 4],
+) {
+    let (right, up) = plane_normal.plane_axes();
+    let scale = options.scale.max(1) as i32;
+    for [column, row] in options.font.rasterize(&options.text) {
+        for i in 0..scale {
+            for j in 0..scale {
+                for layer in 0..options.depth.max(1) as i32 {
+                    let mut cell = origin;
+                    cell[right.index()] += column as i32 * scale + i;
+                    cell[up.index()] += row as i32 * scale + j;
+                    cell[plane_normal.index()] += layer;
+                    voxel_manager.paint_cell(cell, color, true);
+                }
+            }
+        }
+    }
+}
+
+/// Cross section of the brush swept along curves
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BrushProfile {
+    Round,
+    Square,
+}
+
+impl BrushProfile {
+    pub const ALL: [BrushProfile; 2] = [BrushProfile::Round, BrushProfile::Square];
+
+    fn cells(self, center: [i32; 3], radius: i32) -> Vec<[i32; 3]> {
+        match self {
+            BrushProfile::Round => brush_cells(center, radius),
+            BrushProfile::Square => {
+                let mut cells = Vec::new();
+                for x in -radius..=radius {
+                    for y in -radius..=radius {
+                        for z in -radius..=radius {
+                            cells.push([center[0] + x, center[1] + y, center[2] + z]);
+                        }
+                    }
+                }
+                cells
+            }
+        }
+    }
+}
+
+impl Default for BrushProfile {
+    fn default() -> BrushProfile {
+        BrushProfile::Round
+    }
+}
+
+/// Cells of the Catmull-Rom spline passing through the control points, without gaps
+pub fn curve_cells(points: &[[i32; 3]]) -> Vec<[i32; 3]> {
+    let point = |i: usize| {
+        let [x, y, z] = points[i.min(points.len() - 1)];
+        [x as f32, y as f32, z as f32]
+    };
+    let mut cells: Vec<[i32; 3]> = points.iter().take(1).cloned().collect();
+    for i in 1..points.len() {
+        // The end points are repeated to get the tangents of the first and last segment
+        let (p0, p1, p2, p3) = (point(i.max(2) - 2), point(i - 1), point(i), point(i + 1));
+        let steps = grid_line(points[i - 1], points[i]).len() * 2;
+        for step in 1..=steps {
+            let t = step as f32 / steps as f32;
+            let (t2, t3) = (t * t, t * t * t);
+            let mut cell = [0; 3];
+            for axis in 0..3 {
+                let value = 0.5
+                    * (2.0 * p1[axis]
+                        + (p2[axis] - p0[axis]) * t
+                        + (2.0 * p0[axis] - 5.0 * p1[axis] + 4.0 * p2[axis] - p3[axis]) * t2
+                        + (3.0 * p1[axis] - p0[axis] - 3.0 * p2[axis] + p3[axis]) * t3);
+                cell[axis] = value.round() as i32;
+            }
+            let last = *cells.last().unwrap();
+            if cell != last {
+                cells.extend(grid_line(last, cell).into_iter().skip(1));
+            }
+        }
+    }
+    cells
+}
+
+/// Voxelizes the brush swept along the curve through the control points
+pub fn sweep(
+    voxel_manager: &mut VoxelManager,
+    points: &[[i32; 3]],
+    profile: BrushProfile,
+    radius: usize,
+    color: [f32; 4],
+) {
+    for cell in curve_cells(points) {
+        for brush_cell in profile.cells(cell, radius as i32) {
+            voxel_manager.paint_cell(brush_cell, color, true);
+        }
+    }
+}
+
+/// 2D primitives drawn on the active plane
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShapeKind {
+    Rectangle,
+    Ellipse,
+    /// Corners placed by clicking, Enter closes the polygon
+    Polygon,
+}
+
+impl ShapeKind {
+    pub const ALL: [ShapeKind; 3] = [ShapeKind::Rectangle, ShapeKind::Ellipse, ShapeKind::Polygon];
+}
+
+impl Default for ShapeKind {
+    fn default() -> ShapeKind {
+        ShapeKind::Rectangle
+    }
+}
+
+/// The filled cells that have an empty neighbour in the plane
+fn outline(cells: Vec<[i32; 2]>) -> Vec<[i32; 2]> {
+    let filled: HashSet<[i32; 2]> = cells.iter().cloned().collect();
+    cells
+        .into_iter()
+        .filter(|[i, j]| {
+            [[1, 0], [-1, 0], [0, 1], [0, -1]]
+                .iter()
+                .any(|[di, dj]| !filled.contains(&[i + di, j + dj]))
+        })
+        .collect()
+}
+
+/// Cells of the rectangle or the inscribed ellipse between the corners, both included
+pub fn box_shape_cells(
+    kind: ShapeKind,
+    min: [i32; 2],
+    max: [i32; 2],
+    outlined: bool,
+) -> Vec<[i32; 2]> {
+    let center = [
+        (min[0] + max[0]) as f32 / 2.0,
+        (min[1] + max[1]) as f32 / 2.0,
+    ];
+    let radius = [
+        (max[0] - min[0] + 1) as f32 / 2.0,
+        (max[1] - min[1] + 1) as f32 / 2.0,
+    ];
+    let mut cells = Vec::new();
+    for i in min[0]..=max[0] {
+        for j in min[1]..=max[1] {
+            let (di, dj) = (
+                (i as f32 - center[0]) / radius[0],
+                (j as f32 - center[1]) / radius[1],
+            );
+            if kind != ShapeKind::Ellipse || di * di + dj * dj <= 1.0 {
+                cells.push([i, j]);
+            }
+        }
+    }
+    if outlined {
+        outline(cells)
+    } else {
+        cells
+    }
+}
+
+/// Cells of the closed polygon through the corners, the filled inside is decided by the
+/// even-odd rule on the cell centers
+pub fn polygon_cells(corners: &[[i32; 2]], outlined: bool) -> Vec<[i32; 2]> {
+    let mut cells = HashSet::new();
+    for (k, from) in corners.iter().enumerate() {
+        let to = corners[(k + 1) % corners.len()];
+        for [i, _, j] in grid_line([from[0], 0, from[1]], [to[0], 0, to[1]]) {
+            cells.insert([i, j]);
+        }
+    }
+    if !outlined && corners.len() > 2 {
+        let min_i = corners.iter().map(|c| c[0]).min().unwrap();
+        let max_i = corners.iter().map(|c| c[0]).max().unwrap();
+        let min_j = corners.iter().map(|c| c[1]).min().unwrap();
+        let max_j = corners.iter().map(|c| c[1]).max().unwrap();
+        for i in min_i..=max_i {
+            for j in min_j..=max_j {
+                let (x, y) = (i as f32, j as f32);
+                let mut inside = false;
+                for (k, a) in corners.iter().enumerate() {
+                    let b = corners[(k + 1) % corners.len()];
+                    let (ax, ay, bx, by) = (a[0] as f32, a[1] as f32, b[0] as f32, b[1] as f32);
+                    if (ay > y) != (by > y) && x < ax + (y - ay) / (by - ay) * (bx - ax) {
+                        inside = !inside;
+                    }
+                }
+                if inside {
+                    cells.insert([i, j]);
+                }
+            }
+        }
+    }
+    cells.into_iter().collect()
+}
+
+/// Fills the cells of a 2D shape into the layer of the plane with the given normal
+/// that contains the origin, shape coordinates being offsets along the plane's right and up
+/// axes. Returns the filled cells.
+pub fn fill_plane(
+    voxel_manager: &mut VoxelManager,
+    origin: [i32; 3],
+    plane_normal: Axis,
+    cells: &[[i32; 2]],
+    color: [f32; 4],
+) -> Vec<[usize; 3]> {
+    let (right, up) = plane_normal.plane_axes();
+    let extent = voxel_manager.extent() as i32;
+    let mut filled = Vec::new();
+    for [i, j] in cells.iter() {
+        let mut cell = origin;
+        cell[right.index()] += i;
+        cell[up.index()] += j;
+        if cell.iter().all(|c| *c >= 0 && *c < extent) {
+            voxel_manager.paint_cell(cell, color, true);
+            filled.push([cell[0] as usize, cell[1] as usize, cell[2] as usize]);
+        }
+    }
+    filled
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn stairs_rise_by_steps_to_the_end() {
+        let mut voxel_manager = VoxelManager::new(8);
+        let options = StairsOptions {
+            step_depth: 2,
+            railing: false,
+        };
+        stairs(&mut voxel_manager, [0, 0, 0], [3, 1, 1], options, [1.0; 4]);
+
+        let column = |voxel_manager: &VoxelManager, x, z| {
+            (0..8)
+                .filter(|y| voxel_manager.voxel(x, *y, z).is_some())
+                .count()
+        };
+        assert_eq!(column(&voxel_manager, 0, 0), 1);
+        assert_eq!(column(&voxel_manager, 1, 1), 1);
+        assert_eq!(column(&voxel_manager, 2, 0), 2);
+        assert_eq!(column(&voxel_manager, 3, 1), 2);
+        assert_eq!(column(&voxel_manager, 4, 0), 0);
+    }
+
+    #[test]
+    fn curve_passes_through_the_control_points() {
+        let points = [[0, 0, 0], [6, 3, 0], [10, 0, 4]];
+        let cells = curve_cells(&points);
+        for point in points.iter() {
+            assert!(cells.contains(point));
+        }
+        for pair in cells.windows(2) {
+            for axis in 0..3 {
+                assert!((pair[1][axis] - pair[0][axis]).abs() <= 1);
+            }
+        }
+    }
+
+    #[test]
+    fn shapes_fill_and_outline() {
+        let rectangle = box_shape_cells(ShapeKind::Rectangle, [0, 0], [3, 2], false);
+        assert_eq!(rectangle.len(), 12);
+        let outlined = box_shape_cells(ShapeKind::Rectangle, [0, 0], [3, 2], true);
+        assert_eq!(outlined.len(), 10);
+
+        let ellipse = box_shape_cells(ShapeKind::Ellipse, [0, 0], [6, 6], false);
+        assert!(ellipse.contains(&[3, 3]) && ellipse.contains(&[0, 3]));
+        assert!(!ellipse.contains(&[0, 0]));
+
+        let triangle = polygon_cells(&[[0, 0], [6, 0], [0, 6]], false);
+        assert!(triangle.contains(&[1, 1]) && triangle.contains(&[3, 3]));
+        assert!(!triangle.contains(&[4, 4]));
+        let edges = polygon_cells(&[[0, 0], [6, 0], [0, 6]], true);
+        assert!(!edges.contains(&[1, 1]));
+    }
+
+    #[test]
+    fn text_is_scaled_and_extruded() {
+        let mut voxel_manager = VoxelManager::new(16);
+        let options = TextOptions {
+            text: "-".to_owned(),
+            font: FontKind::Tiny,
+            scale: 2,
+            depth: 3,
+        };
+        text(&mut voxel_manager, [1, 0, 1], Axis::Z, &options, [1.0; 4]);
+
+        // The dash: 3 pixels of 2x2 voxels, 3 layers deep
+        assert_eq!(voxel_manager.voxel(1, 4, 1), Some(0));
+        assert_eq!(voxel_manager.voxel(6, 5, 3), Some(0));
+        assert_eq!(voxel_manager.voxel(7, 4, 1), None);
+        assert_eq!(voxel_manager.voxel(1, 4, 4), None);
+    }
+}
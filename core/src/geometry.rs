@@ -0,0 +1,1246 @@
+use crate::transform::Axis;
+use crate::vertex::*;
+use cgmath::{InnerSpace, Matrix, Matrix4, Transform, Vector3, Vector4};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+pub const EPSYLON: f32 = 0.000001;
+
+/// Tolerances of the intersection tests. Both are relative, so they hold for long and short
+/// rays and for small and large scenes alike.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tolerance {
+    /// Smallest cosine between a ray and a plane that still counts as crossing it, hits at
+    /// shallower angles are too far away to place anything precisely
+    pub grazing: f32,
+    /// How far a point may be off a boundary and still count as on it, as a fraction of the
+    /// scene size
+    pub distance: f32,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Tolerance {
+            grazing: EPSYLON,
+            distance: 0.00001,
+        }
+    }
+}
+
+impl Tolerance {
+    /// The distance tolerance in scene units, for a scene of the size
+    pub fn margin(&self, scene_size: f32) -> f32 {
+        self.distance * scene_size.abs().max(1.0)
+    }
+}
+
+pub struct Plane {
+    point: Vector3<f32>,
+    pub normal: Vector3<f32>,
+    pub left: Vector3<f32>,
+    pub down: Vector3<f32>,
+    #[cfg(feature = "debug_ray")]
+    pub name: &'static str,
+}
+
+pub const XY_PLANE: Plane = Plane {
+    point: Vector3 {
+        x: 0.5,
+        y: 0.5,
+        z: 0.0,
+    },
+    normal: Vector3 {
+        x: 0.0,
+        y: 0.0,
+        z: 1.0,
+    },
+    left: Vector3 {
+        x: 1.0,
+        y: 0.0,
+        z: 0.0,
+    },
+    down: Vector3 {
+        x: 0.0,
+        y: -1.0,
+        z: 0.0,
+    },
+    #[cfg(feature = "debug_ray")]
+    name: "XY",
+};
+
+pub const YZ_PLANE: Plane = Plane {
+    point: Vector3 {
+        x: 0.0,
+        y: 0.5,
+        z: 0.5,
+    },
+    normal: Vector3 {
+        x: 1.0,
+        y: 0.0,
+        z: 0.0,
+    },
+    left: Vector3 {
+        x: 0.0,
+        y: 0.0,
+        z: -1.0,
+    },
+    down: Vector3 {
+        x: 0.0,
+        y: -1.0,
+        z: 0.0,
+    },
+    #[cfg(feature = "debug_ray")]
+    name: "YZ",
+};
+
+pub const XZ_PLANE: Plane = Plane {
+    point: Vector3 {
+        x: 0.5,
+        y: 0.0,
+        z: 0.5,
+    },
+    normal: Vector3 {
+        x: 0.0,
+        y: 1.0,
+        z: 0.0,
+    },
+    left: Vector3 {
+        x: 0.0,
+        y: 0.0,
+        z: -1.0,
+    },
+    down: Vector3 {
+        x: 1.0,
+        y: 0.0,
+        z: 0.0,
+    },
+    #[cfg(feature = "debug_ray")]
+    name: "XZ",
+};
+
+impl Plane {
+    /// The same plane moved along its normal
+    pub fn shifted(&self, distance: f32) -> Plane {
+        Plane {
+            point: self.point + self.normal * distance,
+            normal: self.normal,
+            left: self.left,
+            down: self.down,
+            #[cfg(feature = "debug_ray")]
+            name: self.name,
+        }
+    }
+
+    /// Moves the point onto the plane along the normal, a point off by rounding lands exactly
+    /// on it
+    pub fn project(&self, point: Vector3<f32>) -> Vector3<f32> {
+        point - self.normal * (point - self.point).dot(self.normal)
+    }
+}
+
+#[derive(Debug)]
+pub struct Ray {
+    pub origin: Vector3<f32>,
+    pub end: Vector3<f32>,
+    vector: Vector3<f32>,
+    pub tolerance: Tolerance,
+}
+
+impl Ray {
+    pub fn new(origin: Vector3<f32>, end: Vector3<f32>) -> Self {
+        Ray {
+            origin,
+            vector: origin - end,
+            end,
+            tolerance: Tolerance::default(),
+        }
+    }
+
+    pub fn with_tolerance(mut self, tolerance: Tolerance) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// The point where the ray hits the plane and its distance from the ray origin, so hits on
+    /// several planes can be sorted. Only hits ahead of the origin count, in the direction of
+    /// the end.
+    pub fn plane_intersection(&self, plane: &Plane) -> Option<(Vector3<f32>, f32)> {
+        // The vector points from the end to the origin, so the hit is at origin - vector * t
+        let dist_square = self.vector.dot(plane.normal);
+        // Relative to the length of the ray, so short rays aren't taken for parallel ones
+        if dist_square.abs() > self.tolerance.grazing * self.vector.magnitude() {
+            let diff = self.origin - plane.point;
+            let dist_square2 = diff.dot(plane.normal) / dist_square;
+            if dist_square2 >= 0.0 {
+                return Some((
+                    self.origin - self.vector * dist_square2,
+                    self.vector.magnitude() * dist_square2,
+                ));
+            }
+        }
+        None
+    }
+
+    pub fn box_intersection(&self, bbox: &BoundingBox, dist: &mut f32) -> bool {
+        *dist = BoundingBox::manhattan_distance(&self.origin, &bbox.corner);
+        self.slab_distance(bbox.corner, bbox.vec_max()).is_some()
+    }
+
+    /// Distance from the ray origin to where the ray enters the box, zero when the origin is
+    /// inside
+    pub fn box_distance(&self, bbox: &BoundingBox) -> Option<f32> {
+        self.slab_distance(bbox.corner, bbox.vec_max())
+    }
+
+    /// Coordinate along the line through the point with the unit direction where the ray passes
+    /// closest to it, None when the ray runs along the line
+    pub fn line_coordinate(&self, point: Vector3<f32>, direction: Vector3<f32>) -> Option<f32> {
+        let ray_direction = self.end - self.origin;
+        let to_ray = self.origin - point;
+        let a = ray_direction.dot(ray_direction);
+        let b = ray_direction.dot(direction);
+        let denominator = a - b * b;
+        if denominator <= self.tolerance.grazing * a {
+            return None;
+        }
+        Some((a * direction.dot(to_ray) - b * ray_direction.dot(to_ray)) / denominator)
+    }
+
+    /// Distance from the ray origin to where the ray enters the box between two opposite
+    /// corners, zero when the origin is inside
+    fn slab_distance(&self, corner: Vector3<f32>, opposite: Vector3<f32>) -> Option<f32> {
+        let mut tmin = f32::NEG_INFINITY;
+        let mut tmax = f32::INFINITY;
+
+        let inv_dir = 1.0 / -self.vector;
+
+        for i in 0..3 {
+            let t1 = (corner[i] - self.origin[i]) * inv_dir[i];
+            let t2 = (opposite[i] - self.origin[i]) * inv_dir[i];
+
+            tmin = tmin.max(t1.min(t2));
+            tmax = tmax.min(t1.max(t2));
+        }
+
+        if tmax >= tmin.max(0.0) {
+            Some(tmin.max(0.0) * self.vector.magnitude())
+        } else {
+            None
+        }
+    }
+
+    /// Distance from the ray origin to where the ray hits the triangle, from either side.
+    /// Möller–Trumbore, without the plane of the triangle.
+    pub fn triangle_intersection(&self, triangle: &[Vector3<f32>; 3]) -> Option<f32> {
+        let direction = -self.vector;
+        let edge1 = triangle[1] - triangle[0];
+        let edge2 = triangle[2] - triangle[0];
+        let p = direction.cross(edge2);
+        let det = edge1.dot(p);
+        // The cosine between the ray and the triangle, degenerate triangles are always missed
+        let scale = direction.magnitude() * edge1.cross(edge2).magnitude();
+        if det.abs() <= self.tolerance.grazing * scale {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+        let s = self.origin - triangle[0];
+        let u = s.dot(p) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = s.cross(edge1);
+        let v = direction.dot(q) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let t = edge2.dot(q) * inv_det;
+        if t < 0.0 {
+            return None;
+        }
+        Some(t * direction.magnitude())
+    }
+
+    pub fn unproject(
+        winx: f32,
+        winy: f32,
+        winz: f32,
+        model_view: Matrix4<f32>,
+        projection: Matrix4<f32>,
+        window_size: [u32; 2],
+    ) -> Vector3<f32> {
+        let [width, height] = [window_size[0] as f32, window_size[1] as f32];
+        let matrix = (projection * model_view).inverse_transform().unwrap();
+        let in_vec = Vector4::new(
+            (winx / width) * 2.0 - 1.0,
+            ((height - winy) / height) * 2.0 - 1.0,
+            winz,
+            1.0,
+        );
+        let mut out = matrix * in_vec;
+        out.w = 1.0 / out.w;
+        Vector3::new(out.x * out.w, out.y * out.w, out.z * out.w)
+    }
+
+    /// Window position in pixels and depth of the point, the inverse of unproject
+    pub fn project(
+        point: Vector3<f32>,
+        model_view: Matrix4<f32>,
+        projection: Matrix4<f32>,
+        window_size: [u32; 2],
+    ) -> Vector3<f32> {
+        let [width, height] = [window_size[0] as f32, window_size[1] as f32];
+        let clip = projection * model_view * point.extend(1.0);
+        let ndc = clip.truncate() / clip.w;
+        Vector3::new(
+            (ndc.x + 1.0) / 2.0 * width,
+            height - (ndc.y + 1.0) / 2.0 * height,
+            ndc.z,
+        )
+    }
+
+    /// The ray through the cursor position in pixels, from just in front of the camera to the far
+    /// plane. window_size is the width and height in pixels. Every tool picks with this, so they
+    /// all agree on the window depths the ray runs between.
+    pub fn from_screen(
+        cursor_pos: [f32; 2],
+        model_view: Matrix4<f32>,
+        projection: Matrix4<f32>,
+        window_size: [u32; 2],
+    ) -> Self {
+        let [x, y] = cursor_pos;
+        let origin = Self::unproject(x, y, 0.1, model_view, projection, window_size);
+        let end = Self::unproject(x, y, 1.0, model_view, projection, window_size);
+        Ray::new(origin, end)
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BoundingBox {
+    #[cfg_attr(feature = "serde", serde(with = "vector3_serde"))]
+    pub corner: Vector3<f32>,
+    #[cfg_attr(feature = "serde", serde(with = "vector3_serde"))]
+    pub extent: Vector3<f32>,
+    pub color: [f32; 4],
+}
+
+/// Keeps a drag on a single axis
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AxisLock {
+    Axis(Axis),
+    /// The axis the drag has moved the farthest along
+    Dominant,
+}
+
+impl BoundingBox {
+    fn vec_max(&self) -> Vector3<f32> {
+        self.corner + self.extent
+    }
+
+    /// The component-wise smallest corner, whatever the signs of the extent are
+    pub fn min(&self) -> Vector3<f32> {
+        Vector3::new(
+            self.corner.x + self.extent.x.min(0.0),
+            self.corner.y + self.extent.y.min(0.0),
+            self.corner.z + self.extent.z.min(0.0),
+        )
+    }
+
+    /// The component-wise largest corner
+    pub fn max(&self) -> Vector3<f32> {
+        Vector3::new(
+            self.corner.x + self.extent.x.max(0.0),
+            self.corner.y + self.extent.y.max(0.0),
+            self.corner.z + self.extent.z.max(0.0),
+        )
+    }
+
+    /// Moves the end box of a drag back in line with the start box on every axis but the
+    /// locked one
+    pub fn locked_to(&self, start: &BoundingBox, lock: AxisLock) -> BoundingBox {
+        let moved = self.min() - start.min();
+        let free = match lock {
+            AxisLock::Axis(axis) => axis.index(),
+            AxisLock::Dominant => (1..3).fold(0, |dominant, i| {
+                if moved[i].abs() > moved[dominant].abs() {
+                    i
+                } else {
+                    dominant
+                }
+            }),
+        };
+        let mut locked = *self;
+        for i in (0..3).filter(|i| *i != free) {
+            locked.corner[i] -= moved[i];
+        }
+        locked
+    }
+
+    pub fn corner_points(&self) -> [Vector3<f32>; 8] {
+        [
+            self.corner,
+            self.corner + Vector3::new(self.extent.x, 0.0, 0.0),
+            self.corner + Vector3::new(self.extent.x, self.extent.y, 0.0),
+            self.corner + Vector3::new(0.0, self.extent.y, 0.0),
+            self.corner + Vector3::new(0.0, 0.0, self.extent.z),
+            self.corner + Vector3::new(self.extent.x, 0.0, self.extent.z),
+            self.corner + Vector3::new(self.extent.x, self.extent.y, self.extent.z),
+            self.corner + Vector3::new(0.0, self.extent.y, self.extent.z),
+        ]
+    }
+
+    fn manhattan_distance(start: &Vector3<f32>, end: &Vector3<f32>) -> f32 {
+        (start.x - end.x).abs() + (start.y - end.y).abs() + (start.z - end.z).abs()
+    }
+
+    fn from_corner_points(origin: Vector3<f32>, end: Vector3<f32>, color: [f32; 4]) -> Self {
+        BoundingBox {
+            corner: origin,
+            extent: end - origin,
+            color,
+        }
+    }
+
+    /// The smallest box around both boxes, with a positive extent whatever theirs are. Picking
+    /// the farthest pair of corners missed the sides of a box wider than the other one.
+    pub fn containing_box(&self, other: &Self) -> Self {
+        let [min, other_min] = [self.min(), other.min()];
+        let [max, other_max] = [self.max(), other.max()];
+        Self::from_corner_points(
+            Vector3::new(
+                min.x.min(other_min.x),
+                min.y.min(other_min.y),
+                min.z.min(other_min.z),
+            ),
+            Vector3::new(
+                max.x.max(other_max.x),
+                max.y.max(other_max.y),
+                max.z.max(other_max.z),
+            ),
+            self.color,
+        )
+    }
+
+    pub fn new(corner: Vector3<f32>, extent: Vector3<f32>, color: [f32; 4]) -> Self {
+        BoundingBox {
+            corner,
+            extent,
+            color,
+        }
+    }
+
+    /// Moves the corner to the minimum, so the extent becomes positive
+    pub fn rearrange(&mut self) {
+        *self = self.canonicalized();
+    }
+
+    /// The same box with its corner at the minimum, so its extent is positive
+    pub fn canonicalized(&self) -> Self {
+        Self::from_corner_points(self.min(), self.max(), self.color)
+    }
+
+    /// The cells the box covers, its corners rounded to the nearest cell boundary
+    pub fn cell_box(&self) -> CellBox {
+        let [min, max] = [self.min(), self.max()];
+        CellBox {
+            min: [
+                min.x.round() as i32,
+                min.y.round() as i32,
+                min.z.round() as i32,
+            ],
+            max: [
+                max.x.round() as i32,
+                max.y.round() as i32,
+                max.z.round() as i32,
+            ],
+        }
+    }
+
+    pub fn center(&self) -> Vector3<f32> {
+        self.corner + self.extent / 2.0
+    }
+
+    pub fn volume(&self) -> f32 {
+        (self.extent.x * self.extent.y * self.extent.z).abs()
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        let [x, y, z] = [
+            self.extent.x.abs(),
+            self.extent.y.abs(),
+            self.extent.z.abs(),
+        ];
+        2.0 * (x * y + y * z + z * x)
+    }
+
+    pub fn vertices(&self) -> Vec<Vertex> {
+        let corner_points = self.corner_points();
+        let color = self.color;
+
+        // back
+        let corner_indices = [
+            0, 3, 2, 1, // back
+            1, 5, 4, 0, // bottom
+            2, 6, 5, 1, // right
+            3, 7, 6, 2, // top
+            3, 0, 4, 7, // left
+            4, 5, 6, 7, // front
+        ];
+
+        corner_indices
+            .iter()
+            .map(|i| vertex(corner_points[*i].into(), color))
+            .collect()
+    }
+
+    pub fn voxel_vertices(&self) -> Vec<VoxelVertex> {
+        let mut vertex_data = Vec::new();
+        let corner_points = self.corner_points();
+        // back
+        /*0*/
+        vertex_data.push(voxel_vertex(corner_points[0].into(), [0.0, 0.0, -1.0]));
+        /*1*/
+        vertex_data.push(voxel_vertex(corner_points[3].into(), [0.0, 0.0, -1.0]));
+        /*2*/
+        vertex_data.push(voxel_vertex(corner_points[2].into(), [0.0, 0.0, -1.0]));
+        /*3*/
+        vertex_data.push(voxel_vertex(corner_points[1].into(), [0.0, 0.0, -1.0]));
+
+        // bottom
+        /*4*/
+        vertex_data.push(voxel_vertex(corner_points[1].into(), [0.0, -1.0, 0.0]));
+        /*5*/
+        vertex_data.push(voxel_vertex(corner_points[5].into(), [0.0, -1.0, 0.0]));
+        /*6*/
+        vertex_data.push(voxel_vertex(corner_points[4].into(), [0.0, -1.0, 0.0]));
+        /*7*/
+        vertex_data.push(voxel_vertex(corner_points[0].into(), [0.0, -1.0, 0.0]));
+
+        // right
+        /*9*/
+        vertex_data.push(voxel_vertex(corner_points[2].into(), [1.0, 0.0, 0.0]));
+        /*8*/
+        vertex_data.push(voxel_vertex(corner_points[6].into(), [1.0, 0.0, 0.0]));
+        /*10*/
+        vertex_data.push(voxel_vertex(corner_points[5].into(), [1.0, 0.0, 0.0]));
+        /*11*/
+        vertex_data.push(voxel_vertex(corner_points[1].into(), [1.0, 0.0, 0.0]));
+
+        // top
+        /*12*/
+        vertex_data.push(voxel_vertex(corner_points[3].into(), [0.0, 1.0, 0.0]));
+        /*13*/
+        vertex_data.push(voxel_vertex(corner_points[7].into(), [0.0, 1.0, 0.0]));
+        /*14*/
+        vertex_data.push(voxel_vertex(corner_points[6].into(), [0.0, 1.0, 0.0]));
+        /*15*/
+        vertex_data.push(voxel_vertex(corner_points[2].into(), [0.0, 1.0, 0.0]));
+
+        // left
+        /*16*/
+        vertex_data.push(voxel_vertex(corner_points[3].into(), [-1.0, 0.0, 0.0]));
+        /*17*/
+        vertex_data.push(voxel_vertex(corner_points[0].into(), [-1.0, 0.0, 0.0]));
+        /*18*/
+        vertex_data.push(voxel_vertex(corner_points[4].into(), [-1.0, 0.0, 0.0]));
+        /*19*/
+        vertex_data.push(voxel_vertex(corner_points[7].into(), [-1.0, 0.0, 0.0]));
+
+        // front
+        /*20*/
+        vertex_data.push(voxel_vertex(corner_points[4].into(), [0.0, 0.0, 1.0]));
+        /*21*/
+        vertex_data.push(voxel_vertex(corner_points[5].into(), [0.0, 0.0, 1.0]));
+        /*22*/
+        vertex_data.push(voxel_vertex(corner_points[6].into(), [0.0, 0.0, 1.0]));
+        /*23*/
+        vertex_data.push(voxel_vertex(corner_points[7].into(), [0.0, 0.0, 1.0]));
+
+        vertex_data
+    }
+}
+
+/// Cells of a box in integer grid coordinates, from min included to max excluded. Edits go
+/// through these, so corners off by rounding can't move or resize a box by a cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CellBox {
+    pub min: [i32; 3],
+    pub max: [i32; 3],
+}
+
+impl CellBox {
+    pub fn is_empty(&self) -> bool {
+        (0..3).any(|i| self.min[i] >= self.max[i])
+    }
+
+    /// The part of the box inside a grid of the extent, None when nothing is left
+    pub fn clipped(&self, extent: usize) -> Option<CellBox> {
+        let mut clipped = *self;
+        for i in 0..3 {
+            clipped.min[i] = clipped.min[i].max(0);
+            clipped.max[i] = clipped.max[i].min(extent as i32);
+        }
+        if clipped.is_empty() {
+            None
+        } else {
+            Some(clipped)
+        }
+    }
+
+    /// Every cell of the box that is inside a grid of the extent
+    pub fn grid_cells(&self, extent: usize) -> Vec<[usize; 3]> {
+        let clipped = match self.clipped(extent) {
+            Some(clipped) => clipped,
+            None => return Vec::new(),
+        };
+        let [min, max] = [clipped.min, clipped.max];
+        let mut cells = Vec::new();
+        for x in min[0]..max[0] {
+            for y in min[1]..max[1] {
+                for z in min[2]..max[2] {
+                    cells.push([x as usize, y as usize, z as usize]);
+                }
+            }
+        }
+        cells
+    }
+
+    /// The box to render, only here the cells turn into floating point coordinates
+    pub fn to_bounding_box(&self, color: [f32; 4]) -> BoundingBox {
+        let [min, max] = [self.min, self.max];
+        BoundingBox::new(
+            Vector3::new(min[0] as f32, min[1] as f32, min[2] as f32),
+            Vector3::new(
+                (max[0] - min[0]) as f32,
+                (max[1] - min[1]) as f32,
+                (max[2] - min[2]) as f32,
+            ),
+            color,
+        )
+    }
+}
+
+/// The volume a camera sees, bounded by six planes facing inwards
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    // The normal in xyz, pointing inside, and the offset in w, normalized
+    planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+    /// Extracts the planes from a projection times model view matrix with the OpenGL clip
+    /// space of cgmath, where everything visible is between -w and w on every axis
+    pub fn from_matrix(view_projection: Matrix4<f32>) -> Self {
+        let [x, y, z, w] = [
+            view_projection.row(0),
+            view_projection.row(1),
+            view_projection.row(2),
+            view_projection.row(3),
+        ];
+        let mut planes = [w + x, w - x, w + y, w - y, w + z, w - z];
+        for plane in planes.iter_mut() {
+            let length = plane.truncate().magnitude();
+            if length > EPSYLON {
+                *plane /= length;
+            }
+        }
+        Frustum { planes }
+    }
+
+    fn distances<'a>(&'a self, point: Vector3<f32>) -> impl Iterator<Item = f32> + 'a {
+        self.planes
+            .iter()
+            .map(move |plane| plane.truncate().dot(point) + plane.w)
+    }
+
+    pub fn contains_point(&self, point: Vector3<f32>) -> bool {
+        self.distances(point).all(|distance| distance >= 0.0)
+    }
+
+    /// Whether any of the box can be inside. Boxes near a corner of the frustum can pass without
+    /// being seen, which is fine for culling and picking candidates.
+    pub fn intersects_box(&self, bbox: &BoundingBox) -> bool {
+        let [min, max] = [bbox.min(), bbox.max()];
+        self.planes.iter().all(|plane| {
+            // The corner farthest along the plane normal
+            let farthest = Vector3::new(
+                if plane.x >= 0.0 { max.x } else { min.x },
+                if plane.y >= 0.0 { max.y } else { min.y },
+                if plane.z >= 0.0 { max.z } else { min.z },
+            );
+            plane.truncate().dot(farthest) + plane.w >= 0.0
+        })
+    }
+}
+
+// Triangles a leaf of the tree holds at most
+const BVH_LEAF_SIZE: usize = 4;
+
+struct BvhTriangle {
+    // Index of the triangle in the mesh
+    index: usize,
+    corners: [Vector3<f32>; 3],
+}
+
+enum BvhContent {
+    /// Range of the triangles
+    Leaf(usize, usize),
+    /// Indices of the two child nodes
+    Inner(usize, usize),
+}
+
+struct BvhNode {
+    min: Vector3<f32>,
+    max: Vector3<f32>,
+    content: BvhContent,
+}
+
+/// The triangles of a mesh sorted into a tree of boxes, so a ray only tests the triangles in
+/// the boxes it passes through
+pub struct Bvh {
+    triangles: Vec<BvhTriangle>,
+    nodes: Vec<BvhNode>,
+}
+
+impl Bvh {
+    /// Builds the tree over an indexed triangle mesh, as the exporters write them
+    pub fn new(positions: &[[f32; 3]], indices: &[u32]) -> Self {
+        let triangles = indices
+            .chunks_exact(3)
+            .enumerate()
+            .map(|(index, corners)| BvhTriangle {
+                index,
+                corners: [
+                    Vector3::from(positions[corners[0] as usize]),
+                    Vector3::from(positions[corners[1] as usize]),
+                    Vector3::from(positions[corners[2] as usize]),
+                ],
+            })
+            .collect::<Vec<_>>();
+        let mut bvh = Bvh {
+            triangles,
+            nodes: Vec::new(),
+        };
+        if !bvh.triangles.is_empty() {
+            bvh.build(0, bvh.triangles.len());
+        }
+        bvh
+    }
+
+    /// Adds the node of the triangles in the range and returns its index, ranges larger than a
+    /// leaf are split in half along the longest side of their box
+    fn build(&mut self, start: usize, end: usize) -> usize {
+        let mut min = Vector3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY);
+        let mut max = Vector3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY);
+        for triangle in self.triangles[start..end].iter() {
+            for corner in triangle.corners.iter() {
+                for i in 0..3 {
+                    min[i] = min[i].min(corner[i]);
+                    max[i] = max[i].max(corner[i]);
+                }
+            }
+        }
+        let node = self.nodes.len();
+        self.nodes.push(BvhNode {
+            min,
+            max,
+            content: BvhContent::Leaf(start, end),
+        });
+        if end - start <= BVH_LEAF_SIZE {
+            return node;
+        }
+
+        let size = max - min;
+        let axis = if size.x >= size.y && size.x >= size.z {
+            0
+        } else if size.y >= size.z {
+            1
+        } else {
+            2
+        };
+        let centroid = |triangle: &BvhTriangle| {
+            triangle
+                .corners
+                .iter()
+                .map(|corner| corner[axis])
+                .sum::<f32>()
+        };
+        self.triangles[start..end].sort_by(|a, b| {
+            centroid(a)
+                .partial_cmp(&centroid(b))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        let middle = (start + end) / 2;
+        let left = self.build(start, middle);
+        let right = self.build(middle, end);
+        self.nodes[node].content = BvhContent::Inner(left, right);
+        node
+    }
+
+    /// The mesh index of the nearest triangle the ray hits and its distance from the ray origin
+    pub fn intersection(&self, ray: &Ray) -> Option<(usize, f32)> {
+        let mut nearest: Option<(usize, f32)> = None;
+        let mut stack = if self.nodes.is_empty() {
+            Vec::new()
+        } else {
+            vec![0]
+        };
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+            match ray.slab_distance(node.min, node.max) {
+                Some(entry) if nearest.map_or(true, |(_, distance)| entry < distance) => (),
+                _ => continue,
+            }
+            match node.content {
+                BvhContent::Leaf(start, end) => {
+                    for triangle in self.triangles[start..end].iter() {
+                        if let Some(distance) = ray.triangle_intersection(&triangle.corners) {
+                            if nearest.map_or(true, |(_, nearest)| distance < nearest) {
+                                nearest = Some((triangle.index, distance));
+                            }
+                        }
+                    }
+                }
+                BvhContent::Inner(left, right) => {
+                    stack.push(left);
+                    stack.push(right);
+                }
+            }
+        }
+        nearest
+    }
+}
+
+/// Vectors are written as [x, y, z] arrays, cgmath's own serde support isn't enabled
+#[cfg(feature = "serde")]
+mod vector3_serde {
+    use cgmath::Vector3;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        vector: &Vector3<f32>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        [vector.x, vector.y, vector.z].serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vector3<f32>, D::Error> {
+        <[f32; 3]>::deserialize(deserializer).map(Vector3::from)
+    }
+}
+
+/// Cells of a sphere shaped brush around the center cell
+pub fn brush_cells(center: [i32; 3], radius: i32) -> Vec<[i32; 3]> {
+    let mut cells = Vec::new();
+    for x in -radius..=radius {
+        for y in -radius..=radius {
+            for z in -radius..=radius {
+                if x * x + y * y + z * z <= radius * radius {
+                    cells.push([center[0] + x, center[1] + y, center[2] + z]);
+                }
+            }
+        }
+    }
+    cells
+}
+
+/// Grid cells on the line between two cells, both ends included and without gaps
+pub fn grid_line(from: [i32; 3], to: [i32; 3]) -> Vec<[i32; 3]> {
+    let delta = [to[0] - from[0], to[1] - from[1], to[2] - from[2]];
+    let steps = delta.iter().map(|d| d.abs()).max().unwrap_or(0);
+    if steps == 0 {
+        return vec![from];
+    }
+    (0..=steps)
+        .map(|step| {
+            let t = step as f32 / steps as f32;
+            [
+                from[0] + (delta[0] as f32 * t).round() as i32,
+                from[1] + (delta[1] as f32 * t).round() as i32,
+                from[2] + (delta[2] as f32 * t).round() as i32,
+            ]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::voxel_manager::VoxelManager;
+    use cgmath::{Deg, Point3};
+
+    // Number of generated cases every property is checked on
+    const CASES: usize = 500;
+
+    /// Xorshift, so the generated cases are the same on every run and a failure reproduces
+    struct Cases(u32);
+
+    impl Cases {
+        fn range(&mut self, min: f32, max: f32) -> f32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 17;
+            self.0 ^= self.0 << 5;
+            min + (max - min) * (self.0 as f32 / u32::MAX as f32)
+        }
+
+        fn vector(&mut self, min: f32, max: f32) -> Vector3<f32> {
+            Vector3::new(
+                self.range(min, max),
+                self.range(min, max),
+                self.range(min, max),
+            )
+        }
+
+        /// Boxes with extents of either sign, as the cursor makes on the ground planes
+        fn bounding_box(&mut self) -> BoundingBox {
+            BoundingBox::new(self.vector(-20.0, 20.0), self.vector(-5.0, 5.0), [1.0; 4])
+        }
+    }
+
+    fn assert_close(a: Vector3<f32>, b: Vector3<f32>, tolerance: f32) {
+        assert!(
+            (a - b).magnitude() <= tolerance,
+            "{:?} and {:?} differ by more than {}",
+            a,
+            b,
+            tolerance
+        );
+    }
+
+    #[test]
+    fn plane_hits_are_on_the_plane_and_the_ray() {
+        let mut cases = Cases(0x1234_5678);
+        for _ in 0..CASES {
+            let origin = cases.vector(-50.0, 50.0);
+            let end = cases.vector(-50.0, 50.0);
+            let ray = Ray::new(origin, end);
+            let layer = cases.range(-10.0, 10.0);
+            for plane in [XY_PLANE, YZ_PLANE, XZ_PLANE].iter() {
+                let plane = plane.shifted(layer);
+                let towards = (end - origin).dot(plane.normal);
+                let ahead = (plane.point - origin).dot(plane.normal);
+                match ray.plane_intersection(&plane) {
+                    Some((point, distance)) => {
+                        assert!((point - plane.point).dot(plane.normal).abs() < 0.001);
+                        assert!((distance - (point - origin).magnitude()).abs() < 0.001);
+                        assert_close(
+                            point,
+                            origin + (end - origin).normalize() * distance,
+                            0.001 * distance.max(1.0),
+                        );
+                        assert!(towards * ahead > 0.0);
+                    }
+                    // Missed only when the plane is behind or the ray runs along it
+                    None => assert!(towards * ahead <= 0.01 * (end - origin).magnitude()),
+                }
+            }
+        }
+        let along = Ray::new(Vector3::new(0.0, 0.0, 1.0), Vector3::new(5.0, 3.0, 1.0));
+        assert_eq!(along.plane_intersection(&XY_PLANE), None);
+        let short = Ray::new(
+            Vector3::new(0.0, 0.0, 0.001),
+            Vector3::new(0.0, 0.0, 0.0005),
+        );
+        assert!(short.plane_intersection(&XY_PLANE).is_some());
+        let grazing = Ray::new(Vector3::new(0.0, 0.0, 1.0), Vector3::new(100.0, 0.0, 0.99));
+        assert!(grazing.plane_intersection(&XY_PLANE).is_some());
+        let strict = Tolerance {
+            grazing: 0.001,
+            ..Tolerance::default()
+        };
+        assert_eq!(
+            grazing.with_tolerance(strict).plane_intersection(&XY_PLANE),
+            None
+        );
+    }
+
+    #[test]
+    fn bvh_finds_the_nearest_triangle() {
+        let triangle = [
+            Vector3::new(0.0, 0.0, 2.0),
+            Vector3::new(4.0, 0.0, 2.0),
+            Vector3::new(0.0, 4.0, 2.0),
+        ];
+        let ray = Ray::new(Vector3::new(1.0, 1.0, 0.0), Vector3::new(1.0, 1.0, 10.0));
+        assert_eq!(ray.triangle_intersection(&triangle), Some(2.0));
+        let back = Ray::new(Vector3::new(1.0, 1.0, 0.0), Vector3::new(1.0, 1.0, -10.0));
+        assert_eq!(back.triangle_intersection(&triangle), None);
+        let beside = Ray::new(Vector3::new(3.0, 3.0, 0.0), Vector3::new(3.0, 3.0, 10.0));
+        assert_eq!(beside.triangle_intersection(&triangle), None);
+
+        let mut scene = VoxelManager::new(8);
+        for (corner, extent) in [
+            ([0.0, 0.0, 0.0], [8.0, 1.0, 8.0]),
+            ([2.0, 1.0, 3.0], [2.0, 4.0, 1.0]),
+        ]
+        .iter()
+        {
+            scene.add_box(BoundingBox::new(
+                Vector3::from(*corner),
+                Vector3::from(*extent),
+                [1.0; 4],
+            ));
+        }
+        let (vertices, indices) = scene.vertices();
+        let positions = vertices.iter().map(|vertex| vertex.pos).collect::<Vec<_>>();
+        let bvh = Bvh::new(&positions, &indices);
+        let mut cases = Cases(0x0bad_5eed);
+        for _ in 0..CASES {
+            let ray = Ray::new(cases.vector(-10.0, 18.0), cases.vector(2.0, 6.0));
+            let brute_force = indices
+                .chunks_exact(3)
+                .filter_map(|corners| {
+                    let corners = [
+                        Vector3::from(positions[corners[0] as usize]),
+                        Vector3::from(positions[corners[1] as usize]),
+                        Vector3::from(positions[corners[2] as usize]),
+                    ];
+                    ray.triangle_intersection(&corners)
+                })
+                .fold(None, |nearest: Option<f32>, distance| {
+                    Some(nearest.map_or(distance, |nearest| nearest.min(distance)))
+                });
+            assert_eq!(
+                bvh.intersection(&ray).map(|(_, distance)| distance),
+                brute_force
+            );
+        }
+        assert_eq!(Bvh::new(&[], &[]).intersection(&ray), None);
+    }
+
+    #[test]
+    fn unproject_inverts_project() {
+        let mut cases = Cases(0x9e37_79b9);
+        for _ in 0..CASES {
+            let window_size = [
+                cases.range(100.0, 2000.0) as u32,
+                cases.range(100.0, 2000.0) as u32,
+            ];
+            let eye = cases.vector(-100.0, 100.0);
+            let model_view = Matrix4::look_at(
+                Point3::new(eye.x, eye.y, eye.z),
+                Point3::new(16.0, 16.0, 16.0),
+                Vector3::unit_y(),
+            );
+            let aspect = window_size[0] as f32 / window_size[1] as f32;
+            let projection = cgmath::perspective(Deg(cases.range(30.0, 90.0)), aspect, 0.1, 320.0);
+            let window = Vector3::new(
+                cases.range(0.0, window_size[0] as f32),
+                cases.range(0.0, window_size[1] as f32),
+                cases.range(0.1, 0.9),
+            );
+            let point = Ray::unproject(
+                window.x,
+                window.y,
+                window.z,
+                model_view,
+                projection,
+                window_size,
+            );
+            let projected = Ray::project(point, model_view, projection, window_size);
+            // Single precision loses a fraction of a pixel, the cursor still lands on its pixel
+            assert_close(projected, window, 0.5);
+
+            let ray = Ray::from_screen([window.x, window.y], model_view, projection, window_size);
+            for point in [ray.origin, ray.end].iter() {
+                let projected = Ray::project(*point, model_view, projection, window_size);
+                assert_close(
+                    Vector3::new(projected.x, projected.y, window.z),
+                    window,
+                    0.5,
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rearranged_boxes_cover_the_same_cells() {
+        let mut cases = Cases(0x2545_f491);
+        for _ in 0..CASES {
+            let bbox = cases.bounding_box();
+            let mut rearranged = bbox;
+            rearranged.rearrange();
+            assert!(rearranged.extent.x >= 0.0);
+            assert!(rearranged.extent.y >= 0.0);
+            assert!(rearranged.extent.z >= 0.0);
+            assert_close(rearranged.min(), bbox.min(), 0.0001);
+            assert_close(rearranged.max(), bbox.max(), 0.0001);
+            let mut twice = rearranged;
+            twice.rearrange();
+            assert_eq!(twice.corner, rearranged.corner);
+        }
+    }
+
+    #[test]
+    fn containing_boxes_are_the_tightest_around_both() {
+        let mut cases = Cases(0x6a09_e667);
+        for _ in 0..CASES {
+            let [a, b] = [cases.bounding_box(), cases.bounding_box()];
+            let containing = a.containing_box(&b);
+            assert!(containing.extent.x >= 0.0);
+            assert!(containing.extent.y >= 0.0);
+            assert!(containing.extent.z >= 0.0);
+            for point in a.corner_points().iter().chain(b.corner_points().iter()) {
+                for i in 0..3 {
+                    assert!(point[i] >= containing.corner[i] - 0.0001);
+                    assert!(point[i] <= containing.vec_max()[i] + 0.0001);
+                }
+            }
+            for i in 0..3 {
+                let touched =
+                    |bbox: &BoundingBox| (bbox.min()[i] - containing.corner[i]).abs() < 0.0001;
+                assert!(touched(&a) || touched(&b));
+            }
+            assert_close(b.containing_box(&a).corner, containing.corner, 0.0001);
+        }
+        // Flat boxes are canonical too, and keep their measures
+        let flat = BoundingBox::new(
+            Vector3::new(4.0, 2.0, 1.0),
+            Vector3::new(-2.0, 0.0, -3.0),
+            [1.0; 4],
+        )
+        .canonicalized();
+        assert_eq!(flat.corner, Vector3::new(2.0, 2.0, -2.0));
+        assert_eq!(flat.extent, Vector3::new(2.0, 0.0, 3.0));
+        assert_eq!(flat.center(), Vector3::new(3.0, 2.0, -0.5));
+        assert_eq!(flat.volume(), 0.0);
+        assert_eq!(flat.surface_area(), 12.0);
+        // A box inside a wider one, which the farthest pair of corners used to cut off
+        let wide = BoundingBox::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(3.0, 1.0, 1.0),
+            [1.0; 4],
+        );
+        let inner = BoundingBox::new(
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(1.0, 1.0, 1.0),
+            [1.0; 4],
+        );
+        assert_eq!(
+            inner.containing_box(&wide).extent,
+            Vector3::new(3.0, 1.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn cell_boxes_are_not_moved_by_rounding() {
+        let drifted = BoundingBox::new(
+            Vector3::new(2.999_999_8, 31.000_002, -1.000_000_1),
+            Vector3::new(2.000_000_5, -1.000_000_2, 3.0),
+            [1.0; 4],
+        );
+        let cells = drifted.cell_box();
+        assert_eq!(
+            cells,
+            CellBox {
+                min: [3, 30, -1],
+                max: [5, 31, 2],
+            }
+        );
+        let clipped = cells.clipped(32).unwrap();
+        assert_eq!(clipped.min, [3, 30, 0]);
+        assert_eq!(cells.grid_cells(32).len(), 4);
+        assert_eq!(clipped.to_bounding_box([1.0; 4]).cell_box(), clipped);
+        assert_eq!(cells.clipped(2), None);
+    }
+
+    #[test]
+    fn frustum_keeps_what_the_camera_sees() {
+        let model_view = Matrix4::look_at(
+            Point3::new(16.0, 16.0, 60.0),
+            Point3::new(16.0, 16.0, 16.0),
+            Vector3::unit_y(),
+        );
+        let projection = cgmath::perspective(Deg(45.0), 1.5, 0.1, 320.0);
+        let frustum = Frustum::from_matrix(projection * model_view);
+        let cell = |x: f32, y: f32, z: f32| {
+            BoundingBox::new(Vector3::new(x, y, z), Vector3::new(1.0, 1.0, 1.0), [1.0; 4])
+        };
+        assert!(frustum.contains_point(Vector3::new(16.0, 16.0, 16.0)));
+        assert!(frustum.intersects_box(&cell(16.0, 16.0, 0.0)));
+        // Behind the camera, past the far plane and off to the side
+        assert!(!frustum.intersects_box(&cell(16.0, 16.0, 70.0)));
+        assert!(!frustum.intersects_box(&cell(16.0, 16.0, -300.0)));
+        assert!(!frustum.intersects_box(&cell(100.0, 16.0, 16.0)));
+        // A box reaching into the view from outside
+        let wide = BoundingBox::new(
+            Vector3::new(-100.0, 15.0, 15.0),
+            Vector3::new(200.0, 1.0, 1.0),
+            [1.0; 4],
+        );
+        assert!(!frustum.contains_point(wide.corner));
+        assert!(frustum.intersects_box(&wide));
+
+        let mut cases = Cases(0x3c6e_f372);
+        for _ in 0..CASES {
+            let bbox = cases.bounding_box();
+            if frustum.contains_point(bbox.center()) {
+                assert!(frustum.intersects_box(&bbox));
+            }
+        }
+    }
+
+    #[test]
+    fn ray_box_intersection() {
+        let black = [0.0, 0.0, 0.0, 1.0];
+        let origin = Vector3::new(0.0, 0.0, 0.0);
+        let ray_dir = Vector3::new(1.0, 0.0, 0.0);
+        let box_extent = Vector3::new(1.0, 1.0, 1.0);
+        let box_corner = Vector3::new(1.0, -0.5, -0.5);
+
+        let ray = Ray::new(origin, ray_dir);
+        let bb = BoundingBox::new(box_corner, box_extent, black);
+
+        let expected_dist = box_corner.x.abs() + box_corner.y.abs() + box_corner.z.abs(); // Manhattan distance
+        let mut dist = 0.0;
+
+        assert!(ray.box_intersection(&bb, &mut dist));
+        assert_eq!(dist, expected_dist);
+    }
+
+    #[test]
+    fn locked_drags_stay_on_one_axis() {
+        let white = [1.0; 4];
+        let start = BoundingBox::new(
+            Vector3::new(2.0, 3.0, 4.0),
+            Vector3::new(1.0, 1.0, 1.0),
+            white,
+        );
+        // Boxes on the ground planes can have negative extents
+        let end = BoundingBox::new(
+            Vector3::new(7.0, 6.0, 5.0),
+            Vector3::new(1.0, -1.0, 1.0),
+            white,
+        );
+        let locked = end.locked_to(&start, AxisLock::Axis(Axis::Y));
+        assert_eq!(locked.corner, Vector3::new(2.0, 6.0, 4.0));
+        let locked = end.locked_to(&start, AxisLock::Dominant);
+        assert_eq!(locked.corner, Vector3::new(7.0, 4.0, 4.0));
+    }
+
+    #[test]
+    fn ray_plane_intersection() {
+        let ray_origin = Vector3::new(0.5, 0.5, 10.0);
+        let ray_end = Vector3::new(0.5, 0.5, -50.0);
+        let ray = Ray::new(ray_origin, ray_end);
+        let intersection = ray.plane_intersection(&XY_PLANE);
+        let expected_intersection = Some((Vector3::new(0.5, 0.5, 0.0), 10.0));
+        assert_eq!(intersection, expected_intersection);
+    }
+
+    #[test]
+    fn grid_line_has_no_gaps() {
+        let line = grid_line([0, 0, 0], [5, -2, 3]);
+        assert_eq!(line.first(), Some(&[0, 0, 0]));
+        assert_eq!(line.last(), Some(&[5, -2, 3]));
+        for pair in line.windows(2) {
+            for axis in 0..3 {
+                assert!((pair[1][axis] - pair[0][axis]).abs() <= 1);
+            }
+        }
+        assert_eq!(grid_line([1, 2, 3], [1, 2, 3]), vec![[1, 2, 3]]);
+    }
+}
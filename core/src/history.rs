@@ -0,0 +1,220 @@
+use crate::project;
+use crate::voxel_manager::VoxelManager;
+use std::io;
+
+/// Most entries the history keeps, older ones are dropped first
+const MAX_ENTRIES: usize = 500;
+/// Most bytes of compressed scenes the history keeps
+const MAX_BYTES: usize = 256 * 1024 * 1024;
+
+/// A recorded state of the scene, stored as compressed project data
+struct Entry {
+    label: String,
+    parent: Option<usize>,
+    // The child continuing this entry's line, later children start branches
+    first_child: Option<usize>,
+    // Number of branches this entry is nested in
+    depth: usize,
+    state: Vec<u8>,
+}
+
+/// A line of the history list as shown to the user
+#[derive(Debug, Clone, PartialEq)]
+pub struct Item {
+    pub label: String,
+    /// Number of branches this entry is nested in
+    pub depth: usize,
+    pub current: bool,
+}
+
+/// Tree of scene states. Recording after jumping back starts a new branch instead of
+/// discarding the states that were undone.
+pub struct History {
+    entries: Vec<Entry>,
+    current: usize,
+    // Size of the states of all entries
+    bytes: usize,
+}
+
+fn snapshot(voxel_manager: &VoxelManager) -> Vec<u8> {
+    let mut state = Vec::new();
+    project::write_project(voxel_manager, &mut state, &mut |_| true)
+        .expect("writing to memory can't fail");
+    state
+}
+
+impl History {
+    pub fn new(voxel_manager: &VoxelManager, label: &str) -> Self {
+        let state = snapshot(voxel_manager);
+        History {
+            bytes: state.len(),
+            entries: vec![Entry {
+                label: label.to_owned(),
+                parent: None,
+                first_child: None,
+                depth: 0,
+                state,
+            }],
+            current: 0,
+        }
+    }
+
+    /// Records the scene as a child of the current entry and makes it current, dropping the
+    /// oldest entries beyond the limits. Returns false without recording when the scene didn't
+    /// change.
+    pub fn record(&mut self, label: &str, voxel_manager: &VoxelManager) -> bool {
+        let state = snapshot(voxel_manager);
+        if state == self.entries[self.current].state {
+            return false;
+        }
+        let index = self.entries.len();
+        let parent = &mut self.entries[self.current];
+        let depth = match parent.first_child {
+            Some(_) => parent.depth + 1,
+            None => {
+                parent.first_child = Some(index);
+                parent.depth
+            }
+        };
+        self.bytes += state.len();
+        self.entries.push(Entry {
+            label: label.to_owned(),
+            parent: Some(self.current),
+            first_child: None,
+            depth,
+            state,
+        });
+        self.current = index;
+        while (self.entries.len() > MAX_ENTRIES || self.bytes > MAX_BYTES) && self.current > 0 {
+            self.drop_oldest();
+        }
+        true
+    }
+
+    /// Drops the first entry, its children become the first entries of their lines
+    fn drop_oldest(&mut self) {
+        let dropped = self.entries.remove(0);
+        self.bytes -= dropped.state.len();
+        let shift = |index: Option<usize>| index.and_then(|index| index.checked_sub(1));
+        for entry in self.entries.iter_mut() {
+            entry.parent = shift(entry.parent);
+            entry.first_child = shift(entry.first_child);
+        }
+        self.current -= 1;
+    }
+
+    /// Restores the scene of an entry and makes it current
+    pub fn jump(&mut self, index: usize) -> io::Result<VoxelManager> {
+        let entry = self
+            .entries
+            .get(index)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no such history entry"))?;
+        let voxel_manager = project::read_project(&mut entry.state.as_slice(), &mut |_| true)?;
+        self.current = index;
+        Ok(voxel_manager)
+    }
+
+    /// Steps back to the parent of the current entry
+    pub fn undo(&mut self) -> Option<io::Result<VoxelManager>> {
+        let parent = self.entries[self.current].parent?;
+        Some(self.jump(parent))
+    }
+
+    /// Steps forward to the most recent child of the current entry
+    pub fn redo(&mut self) -> Option<io::Result<VoxelManager>> {
+        let child = (0..self.entries.len())
+            .rev()
+            .find(|index| self.entries[*index].parent == Some(self.current))?;
+        Some(self.jump(child))
+    }
+
+    /// Entries in the order they were recorded
+    pub fn items(&self) -> Vec<Item> {
+        self.entries
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| Item {
+                label: entry.label.clone(),
+                depth: entry.depth,
+                current: index == self.current,
+            })
+            .collect()
+    }
+
+    /// Drops every entry that isn't on the path from the first to the current entry
+    pub fn prune_branches(&mut self) {
+        let mut path = vec![self.current];
+        while let Some(parent) = self.entries[*path.last().unwrap()].parent {
+            path.push(parent);
+        }
+        path.reverse();
+
+        let mut entries = std::mem::replace(&mut self.entries, Vec::new())
+            .into_iter()
+            .map(Some)
+            .collect::<Vec<_>>();
+        self.entries = path
+            .iter()
+            .enumerate()
+            .map(|(position, index)| {
+                let mut entry = entries[*index].take().unwrap();
+                entry.parent = position.checked_sub(1);
+                entry.first_child = Some(position + 1).filter(|child| *child < path.len());
+                entry.depth = 0;
+                entry
+            })
+            .collect();
+        self.current = self.entries.len() - 1;
+        self.bytes = self.entries.iter().map(|entry| entry.state.len()).sum();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn branches_are_kept_until_pruned() {
+        let mut scene = VoxelManager::new(16);
+        scene.palette_mut().index_of([1.0, 0.0, 0.0, 1.0]);
+        let mut history = History::new(&scene, "Open");
+        scene.set_voxel(1, 1, 1, Some(0));
+        assert!(history.record("Add", &scene));
+        assert!(!history.record("Nothing", &scene));
+
+        let mut scene = history.undo().unwrap().unwrap();
+        assert_eq!(scene.voxel(1, 1, 1), None);
+        scene.set_voxel(2, 2, 2, Some(0));
+        assert!(history.record("Paint", &scene));
+
+        let depths = history
+            .items()
+            .iter()
+            .map(|item| item.depth)
+            .collect::<Vec<_>>();
+        assert_eq!(depths, vec![0, 0, 1]);
+        let added = history.jump(1).unwrap();
+        assert_eq!(added.voxel(1, 1, 1), Some(0));
+
+        history.jump(2).unwrap();
+        history.prune_branches();
+        let labels = history
+            .items()
+            .into_iter()
+            .map(|item| item.label)
+            .collect::<Vec<_>>();
+        assert_eq!(labels, vec!["Open", "Paint"]);
+        assert!(history.redo().is_none());
+        assert_eq!(history.undo().unwrap().unwrap().voxel(2, 2, 2), None);
+
+        // The oldest entries make room for new ones
+        for i in 0..MAX_ENTRIES {
+            scene.set_voxel(i % 16, i / 16 % 16, 5 + i / 256, Some(0));
+            assert!(history.record("Paint", &scene));
+        }
+        assert_eq!(history.items().len(), MAX_ENTRIES);
+        assert!(history.items().last().unwrap().current);
+        assert!(history.undo().is_some());
+    }
+}
@@ -0,0 +1,409 @@
+//! PNG encoding of RGBA images and GIF encoding of animations. PNG pixels are stored without
+//! compression, which keeps the encoder small and is fast enough for screenshots.
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+// Largest block of a stored deflate stream
+const STORED_BLOCK_SIZE: usize = 65_535;
+// GIF palette entry of transparent pixels, the other 255 hold the animation's colors
+const GIF_TRANSPARENT: u8 = 255;
+const GIF_MAX_CODE: u16 = 4096;
+
+pub(crate) fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in bytes {
+        a = (a + *byte as u32) % 65_521;
+        b = (b + a) % 65_521;
+    }
+    (b << 16) | a
+}
+
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// A zlib stream of stored deflate blocks
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let blocks = data.chunks(STORED_BLOCK_SIZE).collect::<Vec<_>>();
+    if blocks.is_empty() {
+        out.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    for (i, block) in blocks.iter().enumerate() {
+        out.push((i + 1 == blocks.len()) as u8);
+        out.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+/// Encodes 8 bit RGBA pixels, rows from top to bottom
+pub fn png(width: u32, height: u32, rgba: &[u8]) -> Vec<u8> {
+    let row_size = width as usize * 4;
+    assert_eq!(rgba.len(), row_size * height as usize);
+    let mut out = b"\x89PNG\r\n\x1a\n".to_vec();
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // 8 bits per channel, RGBA, default compression, filtering and no interlacing
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+    write_chunk(&mut out, b"IHDR", &header);
+
+    // Every row starts with its filter type, none
+    let mut scanlines = Vec::with_capacity((row_size + 1) * height as usize);
+    for row in rgba.chunks(row_size.max(1)).take(height as usize) {
+        scanlines.push(0);
+        scanlines.extend_from_slice(row);
+    }
+    write_chunk(&mut out, b"IDAT", &zlib_stored(&scanlines));
+    write_chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+pub fn write_png(file_path: &str, width: u32, height: u32, rgba: &[u8]) -> std::io::Result<()> {
+    std::fs::write(file_path, png(width, height, rgba))
+}
+
+/// Colors reduced to 5 bits per channel, the buckets the GIF palette is picked from
+fn bucket(pixel: &[u8]) -> usize {
+    ((pixel[0] as usize >> 3) << 10) | ((pixel[1] as usize >> 3) << 5) | (pixel[2] as usize >> 3)
+}
+
+/// The most common colors of the frame, up to one less than the GIF palette holds
+fn gif_palette(frame: &[u8]) -> Vec<[u8; 3]> {
+    let mut sums = vec![(0u64, [0u64; 3]); 1 << 15];
+    for pixel in frame.chunks(4) {
+        if pixel[3] >= 128 {
+            let (count, sum) = &mut sums[bucket(pixel)];
+            *count += 1;
+            for c in 0..3 {
+                sum[c] += pixel[c] as u64;
+            }
+        }
+    }
+    let mut used: Vec<usize> = (0..sums.len()).filter(|b| sums[*b].0 > 0).collect();
+    used.sort_by_key(|b| std::cmp::Reverse(sums[*b].0));
+    used.iter()
+        .take(GIF_TRANSPARENT as usize)
+        .map(|b| {
+            let (count, sum) = sums[*b];
+            [
+                (sum[0] / count) as u8,
+                (sum[1] / count) as u8,
+                (sum[2] / count) as u8,
+            ]
+        })
+        .collect()
+}
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    bits: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u32) {
+        self.bits |= (code as u32) << self.count;
+        self.count += size;
+        while self.count >= 8 {
+            self.bytes.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.bits as u8);
+        }
+        self.bytes
+    }
+}
+
+/// Variable code size LZW compression as GIF uses it
+fn lzw(indices: &[u8], min_code_size: u32) -> Vec<u8> {
+    let clear = 1u16 << min_code_size;
+    let end = clear + 1;
+    let mut writer = BitWriter {
+        bytes: Vec::new(),
+        bits: 0,
+        count: 0,
+    };
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut code_size = min_code_size + 1;
+    let mut next = end + 1;
+    writer.write(clear, code_size);
+    let mut prefix: Option<u16> = None;
+    for index in indices {
+        let current = match prefix {
+            None => {
+                prefix = Some(*index as u16);
+                continue;
+            }
+            Some(current) => current,
+        };
+        if let Some(code) = table.get(&(current, *index)) {
+            prefix = Some(*code);
+            continue;
+        }
+        writer.write(current, code_size);
+        if next < GIF_MAX_CODE {
+            table.insert((current, *index), next);
+            next += 1;
+            if next > (1 << code_size) && code_size < 12 {
+                code_size += 1;
+            }
+        } else {
+            // The table is full, start over
+            writer.write(clear, code_size);
+            table.clear();
+            code_size = min_code_size + 1;
+            next = end + 1;
+        }
+        prefix = Some(*index as u16);
+    }
+    if let Some(current) = prefix {
+        writer.write(current, code_size);
+    }
+    writer.write(end, code_size);
+    writer.finish()
+}
+
+/// Writes 8 bit RGBA frames into a looping GIF one at a time, so an animation doesn't have to be
+/// kept in memory. Pixels under half opacity are transparent. The palette is picked from the
+/// first frame and shared by all of them so it doesn't flicker, turntables show the same voxels
+/// in every frame.
+pub struct GifWriter<W: Write> {
+    writer: W,
+    width: u32,
+    height: u32,
+    delay: u16,
+    palette: Vec<[u8; 3]>,
+    // Palette entry of every color bucket, found when a frame first uses the bucket
+    indices: Vec<Option<u8>>,
+}
+
+impl<W: Write> GifWriter<W> {
+    pub fn new(writer: W, width: u32, height: u32, fps: f32) -> Self {
+        GifWriter {
+            writer,
+            width,
+            height,
+            delay: (100.0 / fps).round().max(1.0) as u16,
+            palette: Vec::new(),
+            indices: vec![None; 1 << 15],
+        }
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        let mut out = b"GIF89a".to_vec();
+        out.extend_from_slice(&(self.width as u16).to_le_bytes());
+        out.extend_from_slice(&(self.height as u16).to_le_bytes());
+        // A global table of 256 colors with 8 bits per channel
+        out.extend_from_slice(&[0xF7, GIF_TRANSPARENT, 0]);
+        for i in 0..256 {
+            out.extend_from_slice(self.palette.get(i).unwrap_or(&[0, 0, 0]));
+        }
+        // Loop forever
+        out.extend_from_slice(b"\x21\xFF\x0BNETSCAPE2.0\x03\x01\x00\x00\x00");
+        self.writer.write_all(&out)
+    }
+
+    /// The palette entry nearest to the color of the bucket
+    fn index(&mut self, pixel: &[u8]) -> u8 {
+        let b = bucket(pixel);
+        if let Some(index) = self.indices[b] {
+            return index;
+        }
+        let distance = |entry: &[u8; 3]| -> u64 {
+            (0..3)
+                .map(|c| (entry[c] as i64 - pixel[c] as i64).pow(2) as u64)
+                .sum()
+        };
+        let index = (0..self.palette.len())
+            .min_by_key(|i| distance(&self.palette[*i]))
+            .unwrap_or(0) as u8;
+        self.indices[b] = Some(index);
+        index
+    }
+
+    pub fn add_frame(&mut self, rgba: &[u8]) -> io::Result<()> {
+        if self.palette.is_empty() {
+            self.palette = gif_palette(rgba);
+            self.write_header()?;
+        }
+        // Each frame is cleared to transparent before the next one is drawn
+        let mut out = vec![0x21, 0xF9, 0x04, 0x09];
+        out.extend_from_slice(&self.delay.to_le_bytes());
+        out.extend_from_slice(&[GIF_TRANSPARENT, 0]);
+
+        out.push(0x2C);
+        out.extend_from_slice(&[0; 4]);
+        out.extend_from_slice(&(self.width as u16).to_le_bytes());
+        out.extend_from_slice(&(self.height as u16).to_le_bytes());
+        out.push(0);
+
+        let pixels: Vec<u8> = rgba
+            .chunks(4)
+            .map(|pixel| {
+                if pixel[3] >= 128 {
+                    self.index(pixel)
+                } else {
+                    GIF_TRANSPARENT
+                }
+            })
+            .collect();
+        out.push(8);
+        for block in lzw(&pixels, 8).chunks(255) {
+            out.push(block.len() as u8);
+            out.extend_from_slice(block);
+        }
+        out.push(0);
+        self.writer.write_all(&out)
+    }
+
+    /// Ends the animation and returns the writer
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.palette.is_empty() {
+            self.write_header()?;
+        }
+        self.writer.write_all(&[0x3B])?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Encodes the frames into a looping GIF like GifWriter
+pub fn gif(width: u32, height: u32, frames: &[Vec<u8>], fps: f32) -> Vec<u8> {
+    let mut writer = GifWriter::new(Vec::new(), width, height, fps);
+    for frame in frames {
+        writer
+            .add_frame(frame)
+            .expect("writing to memory can't fail");
+    }
+    writer.finish().expect("writing to memory can't fail")
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn png_chunks_and_checksums() {
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+
+        let pixels = [255, 0, 0, 255, 0, 0, 255, 128];
+        let bytes = png(2, 1, &pixels);
+        assert!(bytes.starts_with(b"\x89PNG\r\n\x1a\n"));
+        assert_eq!(&bytes[12..16], b"IHDR");
+        assert!(bytes.ends_with(&[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82]));
+        // The single row is stored as is behind its filter byte
+        let scanline = [0, 255, 0, 0, 255, 0, 0, 255, 128];
+        assert!(bytes.windows(scanline.len()).any(|w| w == scanline));
+    }
+
+    /// Reads back what lzw wrote
+    fn unlzw(bytes: &[u8], min_code_size: u32) -> Vec<u8> {
+        let clear = 1usize << min_code_size;
+        let mut table: Vec<Vec<u8>> = Vec::new();
+        let mut out = Vec::new();
+        let (mut bits, mut count, mut at) = (0u32, 0u32, 0);
+        let mut code_size = min_code_size + 1;
+        let mut previous: Option<usize> = None;
+        loop {
+            while count < code_size {
+                bits |= (bytes[at] as u32) << count;
+                at += 1;
+                count += 8;
+            }
+            let code = (bits & ((1 << code_size) - 1)) as usize;
+            bits >>= code_size;
+            count -= code_size;
+            if code == clear {
+                table = (0..clear).map(|i| vec![i as u8]).collect();
+                table.push(Vec::new());
+                table.push(Vec::new());
+                code_size = min_code_size + 1;
+                previous = None;
+                continue;
+            }
+            if code == clear + 1 {
+                return out;
+            }
+            let entry = match (table.get(code), previous) {
+                (Some(entry), _) => entry.clone(),
+                (None, Some(previous)) => {
+                    let mut entry = table[previous].clone();
+                    entry.push(table[previous][0]);
+                    entry
+                }
+                (None, None) => panic!("unknown first code"),
+            };
+            if let Some(previous) = previous {
+                if table.len() < 4096 {
+                    let mut added = table[previous].clone();
+                    added.push(entry[0]);
+                    table.push(added);
+                    if table.len() == 1 << code_size && code_size < 12 {
+                        code_size += 1;
+                    }
+                }
+            }
+            out.extend_from_slice(&entry);
+            previous = Some(code);
+        }
+    }
+
+    #[test]
+    fn lzw_roundtrips_past_a_full_table() {
+        let indices: Vec<u8> = (0..20_000u32)
+            .map(|i| ((i * 7 + i / 13) % 251) as u8)
+            .collect();
+        assert_eq!(unlzw(&lzw(&indices, 8), 8), indices);
+        assert_eq!(unlzw(&lzw(&[7; 1000], 8), 8), vec![7; 1000]);
+    }
+
+    #[test]
+    fn gif_frames_share_a_palette_with_a_transparent_entry() {
+        let frame = vec![200, 10, 10, 255, 0, 0, 0, 0];
+        let bytes = gif(2, 1, &[frame.clone(), frame], 10.0);
+        assert!(bytes.starts_with(b"GIF89a"));
+        assert_eq!(bytes.last(), Some(&0x3B));
+        // The red pixel is the first palette color
+        assert_eq!(&bytes[13..16], &[200, 10, 10]);
+        let controls = bytes.windows(4).filter(|w| w == &[0x21, 0xF9, 0x04, 0x09]);
+        assert_eq!(controls.count(), 2);
+        // Colors the first frame doesn't have map to their nearest palette entry
+        let mut writer = GifWriter::new(Vec::new(), 2, 1, 10.0);
+        writer
+            .add_frame(&[200, 10, 10, 255, 0, 0, 200, 255])
+            .unwrap();
+        let index = writer.index(&[190, 20, 10, 255]) as usize;
+        assert_eq!(writer.palette[index], [200, 10, 10]);
+    }
+}
@@ -0,0 +1,189 @@
+use crate::transform::{center2, rotate_quarter, Axis};
+use crate::voxel_manager::VoxelManager;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+
+/// A linked copy of a saved mask. It only holds where the copy goes, its voxels are copied again
+/// from the cells of the mask whenever the scene changes, so editing the mask edits every copy.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Instance {
+    /// Name of the saved mask the voxels come from
+    pub source: String,
+    pub offset: [i64; 3],
+    /// Quarter turns around the vertical axis through the center of the source, before moving
+    pub turns: usize,
+}
+
+impl Instance {
+    /// The cells of the copy the source cells land on, in their order, None where a cell would
+    /// leave the grid
+    pub fn targets(&self, cells: &[[usize; 3]], extent: usize) -> Vec<Option<[usize; 3]>> {
+        place(cells, self.offset, self.turns, extent)
+    }
+
+    /// Lowest corner of the bounds of the copy of the source cells, cells leaving the grid
+    /// included
+    pub fn corner(&self, cells: &[[usize; 3]]) -> [i64; 3] {
+        let center2 = center2(cells);
+        let mut corner = [i64::MAX; 3];
+        for cell in cells {
+            let cell = [cell[0] as i64, cell[1] as i64, cell[2] as i64];
+            let turned = rotate_quarter(cell, center2, Axis::Y, self.turns);
+            for i in 0..3 {
+                corner[i] = corner[i].min(turned[i] + self.offset[i]);
+            }
+        }
+        corner
+    }
+}
+
+/// The cells turned by quarter turns around the vertical axis through their center and moved by
+/// the offset, None where a cell would leave the grid
+pub(crate) fn place(
+    cells: &[[usize; 3]],
+    offset: [i64; 3],
+    turns: usize,
+    extent: usize,
+) -> Vec<Option<[usize; 3]>> {
+    let center2 = center2(cells);
+    cells
+        .iter()
+        .map(|cell| {
+            let cell = [cell[0] as i64, cell[1] as i64, cell[2] as i64];
+            let turned = rotate_quarter(cell, center2, Axis::Y, turns);
+            let mut target = [0; 3];
+            for i in 0..3 {
+                let c = turned[i] + offset[i];
+                if c < 0 || c >= extent as i64 {
+                    return None;
+                }
+                target[i] = c as usize;
+            }
+            Some(target)
+        })
+        .collect()
+}
+
+/// Writes the values onto their targets and empties the previously stamped cells that aren't
+/// covered any more. Cells already holding the right voxel are left alone. Returns the stamped
+/// cells.
+pub(crate) fn write_stamp(
+    voxel_manager: &mut VoxelManager,
+    targets: Vec<Option<[usize; 3]>>,
+    values: Vec<Option<u8>>,
+    covered: &HashSet<[usize; 3]>,
+    previous: &[[usize; 3]],
+) -> Vec<[usize; 3]> {
+    let stale: Vec<[usize; 3]> = previous
+        .iter()
+        .filter(|cell| !covered.contains(*cell))
+        .copied()
+        .collect();
+    voxel_manager.erase_cells(&stale);
+    let mut stamped = Vec::with_capacity(targets.len());
+    for (target, value) in targets.into_iter().zip(values) {
+        if let Some([x, y, z]) = target {
+            if voxel_manager.voxel(x, y, z) != value {
+                voxel_manager.set_voxel(x, y, z, value);
+            }
+            stamped.push([x, y, z]);
+        }
+    }
+    stamped
+}
+
+/// Written as `x y z turns source`, the name last as it may contain spaces
+impl fmt::Display for Instance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let [x, y, z] = self.offset;
+        write!(f, "{} {} {} {} {}", x, y, z, self.turns, self.source)
+    }
+}
+
+impl FromStr for Instance {
+    type Err = ();
+
+    fn from_str(text: &str) -> Result<Self, ()> {
+        let mut parts = text.trim().splitn(5, ' ');
+        let mut number = || parts.next().ok_or(())?.parse::<i64>().map_err(|_| ());
+        let offset = [number()?, number()?, number()?];
+        let turns = number()?;
+        let source = parts.next().ok_or(())?.trim();
+        if turns < 0 || source.is_empty() {
+            return Err(());
+        }
+        Ok(Instance {
+            source: source.to_owned(),
+            offset,
+            turns: turns as usize,
+        })
+    }
+}
+
+/// Copies the voxels of the source cells onto the cells of the instance, and empties the cells
+/// it was stamped on before that neither the copy nor the source covers now. Cells already
+/// holding the right voxel are left alone, source cells outside the grid are skipped. Returns
+/// the stamped cells.
+pub fn stamp(
+    voxel_manager: &mut VoxelManager,
+    cells: &[[usize; 3]],
+    instance: &Instance,
+    previous: &[[usize; 3]],
+) -> Vec<[usize; 3]> {
+    let extent = voxel_manager.extent();
+    let cells: Vec<[usize; 3]> = cells
+        .iter()
+        .filter(|cell| cell.iter().all(|c| *c < extent))
+        .copied()
+        .collect();
+    let values: Vec<Option<u8>> = cells
+        .iter()
+        .map(|[x, y, z]| voxel_manager.voxel(*x, *y, *z))
+        .collect();
+    let targets = instance.targets(&cells, extent);
+    let covered: HashSet<[usize; 3]> = cells
+        .iter()
+        .copied()
+        .chain(targets.iter().flatten().copied())
+        .collect();
+    write_stamp(voxel_manager, targets, values, &covered, previous)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn instances_follow_their_source() {
+        let mut voxel_manager = VoxelManager::new(16);
+        // A trunk with a branch towards +x
+        let cells = [[2, 0, 2], [2, 1, 2], [3, 1, 2]];
+        for [x, y, z] in cells.iter() {
+            voxel_manager.set_voxel(*x, *y, *z, Some(1));
+        }
+        let instance: Instance = "8 0 0 1 old tree".parse().unwrap();
+        assert_eq!(instance.source, "old tree");
+        assert_eq!(
+            instance.to_string().parse::<Instance>(),
+            Ok(instance.clone())
+        );
+        assert!("8 0 0 -1 tree".parse::<Instance>().is_err());
+
+        let stamped = stamp(&mut voxel_manager, &cells, &instance, &[]);
+        assert_eq!(stamped.len(), 3);
+        // Turned a quarter, the branch points towards -z
+        assert_eq!(voxel_manager.voxel(10, 1, 1), Some(1));
+        assert_eq!(instance.corner(&cells), [10, 0, 1]);
+
+        // Removing the branch from the source removes it from the copy
+        voxel_manager.set_voxel(3, 1, 2, None);
+        stamp(&mut voxel_manager, &cells, &instance, &stamped);
+        assert_eq!(voxel_manager.voxel(10, 1, 1), None);
+        assert_eq!(voxel_manager.voxel(10, 1, 2), Some(1));
+    }
+}
@@ -0,0 +1,232 @@
+use crate::project::{
+    invalid_data, read_chunk, read_palette, read_u32, write_chunk, write_palette, write_u32,
+};
+use crate::voxel_manager::VoxelManager;
+use std::fs::{self, File, OpenOptions};
+use std::io::prelude::*;
+use std::io::{self, BufReader};
+
+const MAGIC: &[u8; 4] = b"VXJL";
+// Version 2 headers hold the checksum of the project the records apply to
+const VERSION: u32 = 2;
+const HEADER_LEN: u64 = 16;
+
+/// The journal of a project is kept next to it
+pub fn path(project_path: &str) -> String {
+    format!("{}.journal", project_path)
+}
+
+/// FNV-1a, enough to tell a record that was cut off by a crash from a complete one, and the
+/// project a journal was started on from a newer save
+pub(crate) fn checksum(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c_9dc5, |hash, byte| {
+        (hash ^ *byte as u32).wrapping_mul(0x0100_0193)
+    })
+}
+
+/// Encodes the palette and the chunks changed since the last record, None when nothing changed.
+/// Chunks are stored whole, so replaying a record restores them regardless of their contents.
+pub fn record(voxel_manager: &VoxelManager) -> Option<Vec<u8>> {
+    let chunks = voxel_manager.unjournaled_chunks();
+    if chunks.is_empty() {
+        return None;
+    }
+    let mut record = Vec::new();
+    write_palette(voxel_manager, &mut record).expect("writing to memory can't fail");
+    write_u32(&mut record, chunks.len() as u32).expect("writing to memory can't fail");
+    for chunk in chunks {
+        write_u32(&mut record, chunk as u32).expect("writing to memory can't fail");
+        write_chunk(voxel_manager, chunk, &mut record).expect("writing to memory can't fail");
+    }
+    Some(record)
+}
+
+/// Appends a record to the journal of the project and waits until it's on disk. A new journal
+/// is tagged with the checksum of the project file as it is now.
+pub fn append(project_path: &str, extent: usize, record: &[u8]) -> io::Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path(project_path))?;
+    let mut bytes = Vec::with_capacity(record.len() + 24);
+    if file.metadata()?.len() == 0 {
+        bytes.extend_from_slice(MAGIC);
+        write_u32(&mut bytes, VERSION)?;
+        write_u32(&mut bytes, extent as u32)?;
+        write_u32(&mut bytes, checksum(&fs::read(project_path)?))?;
+    }
+    write_u32(&mut bytes, record.len() as u32)?;
+    bytes.extend_from_slice(record);
+    write_u32(&mut bytes, checksum(record))?;
+    file.write_all(&bytes)?;
+    file.sync_data()
+}
+
+fn apply(voxel_manager: &mut VoxelManager, mut record: &[u8]) -> io::Result<()> {
+    // Records hold the colors alone, the emissive entries stay those of the project
+    let mut palette = read_palette(&mut record)?;
+    for index in voxel_manager.palette().emissive() {
+        palette.set_emissive(index, true);
+    }
+    voxel_manager.set_palette(palette);
+    for _ in 0..read_u32(&mut record)? {
+        let chunk = read_u32(&mut record)? as usize;
+        if chunk >= voxel_manager.chunk_count() {
+            return Err(invalid_data("journal chunk is outside the grid"));
+        }
+        read_chunk(voxel_manager, chunk, &mut record)?;
+    }
+    Ok(())
+}
+
+/// Reads the next complete record, None at the end of the journal or at a cut off record
+fn next_record<R: Read>(reader: &mut R) -> Option<Vec<u8>> {
+    let len = read_u32(reader).ok()? as u64;
+    let mut record = Vec::new();
+    reader.take(len).read_to_end(&mut record).ok()?;
+    if record.len() as u64 != len || read_u32(reader).ok()? != checksum(&record) {
+        return None;
+    }
+    Some(record)
+}
+
+/// Opens the journal of the project past its header, None when there is no journal for the
+/// project with the checksum: none was written, or the crash came before the header was
+/// complete, or a newer save replaced the project before the journal was removed
+fn open(project_path: &str, base: u32, extent: usize) -> io::Result<Option<BufReader<File>>> {
+    let file = match File::open(path(project_path)) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    if file.metadata()?.len() < HEADER_LEN {
+        return Ok(None);
+    }
+    let mut reader = BufReader::new(file);
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid_data("not a voxel-editor journal"));
+    }
+    if read_u32(&mut reader)? != VERSION {
+        return Err(invalid_data("unsupported journal version"));
+    }
+    if read_u32(&mut reader)? as usize != extent {
+        return Err(invalid_data("the journal has another grid extent"));
+    }
+    if read_u32(&mut reader)? != base {
+        return Ok(None);
+    }
+    Ok(Some(reader))
+}
+
+/// Applies the journal of the project to the scene loaded from the project file with the
+/// checksum, and returns the number of records replayed. Stops at a record cut off by a crash
+/// and leaves the file alone, the editor may be appending to it.
+pub fn replay(
+    project_path: &str,
+    base: u32,
+    voxel_manager: &mut VoxelManager,
+) -> io::Result<usize> {
+    let mut reader = match open(project_path, base, voxel_manager.extent())? {
+        Some(reader) => reader,
+        None => return Ok(0),
+    };
+    let mut replayed = 0;
+    while let Some(record) = next_record(&mut reader) {
+        apply(voxel_manager, &record)?;
+        replayed += 1;
+    }
+    Ok(replayed)
+}
+
+/// Readies the journal for appending before the project is edited: drops a record cut off by a
+/// crash, and the whole journal when it belongs to an older save of the project
+pub fn repair(project_path: &str, extent: usize) -> io::Result<()> {
+    let base = checksum(&fs::read(project_path)?);
+    let mut reader = match open(project_path, base, extent)? {
+        Some(reader) => reader,
+        None => return discard(project_path),
+    };
+    let mut valid_len = HEADER_LEN;
+    while let Some(record) = next_record(&mut reader) {
+        valid_len += record.len() as u64 + 8;
+    }
+    if valid_len < reader.get_ref().metadata()?.len() {
+        OpenOptions::new()
+            .write(true)
+            .open(path(project_path))?
+            .set_len(valid_len)?;
+    }
+    Ok(())
+}
+
+/// Removes the journal, once a complete save made it obsolete
+pub fn discard(project_path: &str) -> io::Result<()> {
+    match fs::remove_file(path(project_path)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::project;
+
+    #[test]
+    fn journal_replays_up_to_a_cut_off_record() {
+        let project_path = std::env::temp_dir()
+            .join(format!("journal_test_{}.vxe", std::process::id()))
+            .to_string_lossy()
+            .into_owned();
+        let mut scene = VoxelManager::new(16);
+        let red = scene.palette_mut().index_of([1.0, 0.0, 0.0, 1.0]);
+        scene.set_voxel(1, 1, 1, Some(red));
+        project::save_project(&scene, &project_path, &mut |_| true).unwrap();
+        assert!(record(&VoxelManager::new(16)).is_none());
+
+        scene.clear_unjournaled_chunks();
+        scene.set_voxel(1, 1, 1, None);
+        scene.set_voxel(9, 9, 9, Some(red));
+        append(&project_path, 16, &record(&scene).unwrap()).unwrap();
+        scene.clear_unjournaled_chunks();
+        let full_len = fs::metadata(path(&project_path)).unwrap().len();
+        scene.set_voxel(2, 2, 2, Some(red));
+        append(&project_path, 16, &record(&scene).unwrap()).unwrap();
+        // A crash in the middle of the second append
+        OpenOptions::new()
+            .write(true)
+            .open(path(&project_path))
+            .unwrap()
+            .set_len(full_len + 10)
+            .unwrap();
+
+        // Loading the project replays the journal without touching it
+        let loaded = project::load_project(&project_path, &mut |_| true).unwrap();
+        assert_eq!(loaded.voxel(1, 1, 1), None);
+        assert_eq!(loaded.voxel(9, 9, 9), Some(red));
+        assert_eq!(loaded.voxel(2, 2, 2), None);
+        assert!(fs::metadata(path(&project_path)).unwrap().len() > full_len);
+        repair(&project_path, 16).unwrap();
+        assert_eq!(fs::metadata(path(&project_path)).unwrap().len(), full_len);
+
+        project::save_project(&loaded, &project_path, &mut |_| true).unwrap();
+        assert!(!std::path::Path::new(&path(&project_path)).exists());
+
+        // A journal left behind by a crash after the save renamed the project is ignored
+        append(&project_path, 16, &record(&scene).unwrap()).unwrap();
+        let journal = fs::read(path(&project_path)).unwrap();
+        let mut newer = loaded.clone();
+        newer.set_voxel(4, 4, 4, Some(red));
+        project::save_project(&newer, &project_path, &mut |_| true).unwrap();
+        fs::write(path(&project_path), journal).unwrap();
+        let reloaded = project::load_project(&project_path, &mut |_| true).unwrap();
+        assert_eq!(reloaded.voxel(2, 2, 2), None);
+        assert!(reloaded.voxel(4, 4, 4).is_some());
+        repair(&project_path, 16).unwrap();
+        assert!(!std::path::Path::new(&path(&project_path)).exists());
+        fs::remove_file(&project_path).unwrap();
+    }
+}
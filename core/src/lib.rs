@@ -0,0 +1,91 @@
+//! The voxel scene model of voxel-editor, without any windowing or GPU dependency, for loading,
+//! editing and meshing scenes from other Rust projects.
+//!
+//! ```
+//! use voxel_editor_core::project;
+//! use voxel_editor_core::voxel_manager::VoxelManager;
+//!
+//! let mut scene = VoxelManager::new(16);
+//! let red = scene.palette_mut().index_of([1.0, 0.0, 0.0, 1.0]);
+//! scene.set_voxel(1, 2, 3, Some(red));
+//!
+//! let (vertices, indices) = scene.vertices();
+//! assert_eq!((vertices.len(), indices.len()), (24, 36));
+//!
+//! let mut bytes = Vec::new();
+//! project::write_project(&scene, &mut bytes, &mut |_| true).unwrap();
+//! let loaded = project::read_project(&mut bytes.as_slice(), &mut |_| true).unwrap();
+//! assert_eq!(loaded.voxel(1, 2, 3), Some(red));
+//! ```
+
+/// Statistics, connected objects and validation of scenes
+pub mod analysis;
+/// Asset loader for Bevy games
+#[cfg(feature = "bevy")]
+pub mod bevy_loader;
+/// Voxel and triangle limits of projects
+pub mod budget;
+/// Collada .dae export
+pub mod collada;
+/// Named colors of the viewport
+pub mod color;
+/// Wavefront .obj export and export presets
+pub mod export;
+/// ASCII FBX export
+pub mod fbx;
+/// Bitmap fonts of the text generator
+pub mod font;
+/// Procedural stairs, text, swept curves and plane shapes
+pub mod generator;
+/// Rays, planes and boxes in grid space
+pub mod geometry;
+/// Branching undo history of scene states
+pub mod history;
+/// PNG and GIF encoding of screenshots and turntables
+pub mod image;
+/// Linked copies of saved masks
+pub mod instance;
+/// Append-only journal of the changes since the last complete save
+pub mod journal;
+/// Named cells with notes, saved in the project file
+pub mod marker;
+/// The scene's color palette
+pub mod palette;
+/// Sorting and merging the palette entries of a scene
+pub mod palette_tools;
+/// Other projects placed into a scene, stamped again when their files change
+pub mod prefab;
+/// Reading and writing .vxe project files
+pub mod project;
+/// Reducing the colors of a scene
+pub mod quantize;
+/// Recording sessions of edits and playing them back
+pub mod recording;
+/// Sets of grid cells
+pub mod selection;
+/// `key = value` settings files
+pub mod settings;
+/// Baking lighting into voxel colors
+pub mod shading;
+/// Named checkpoints of the scene kept in the project file
+pub mod snapshot;
+/// Finer grids embedded in the scene
+pub mod subgrid;
+/// Progress reporting and cancellation of long running operations
+pub mod task;
+/// Color themes of the viewport
+pub mod theme;
+/// Moving, copying and mirroring groups of cells
+pub mod transform;
+/// USD and USDZ export
+pub mod usd;
+/// OpenVDB volume export
+pub mod vdb;
+/// Vertex formats of the meshes
+pub mod vertex;
+/// Camera positions around a scene
+pub mod view;
+/// Import of raw and NRRD volumes
+pub mod volume;
+/// The voxel grid itself and its meshing
+pub mod voxel_manager;
@@ -0,0 +1,105 @@
+use crate::project::{invalid_data, read_bytes, read_u32, write_bytes, write_u32};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+
+/// A named cell of the scene with a note, for leaving reminders in big scenes
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Marker {
+    pub cell: [usize; 3],
+    pub note: String,
+}
+
+/// The markers of the scene by name, saved inside the project file
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Markers {
+    markers: BTreeMap<String, Marker>,
+}
+
+impl Markers {
+    /// Places the marker under the name, replacing an older marker of the same name.
+    /// Returns false for empty names.
+    pub fn insert(&mut self, name: &str, marker: Marker) -> bool {
+        let name = name.trim();
+        if name.is_empty() {
+            return false;
+        }
+        self.markers.insert(name.to_owned(), marker);
+        true
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Marker> {
+        self.markers.get(name.trim())
+    }
+
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.markers.remove(name.trim()).is_some()
+    }
+
+    /// The markers in the order of their names
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Marker)> {
+        self.markers
+            .iter()
+            .map(|(name, marker)| (name.as_str(), marker))
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        write_u32(writer, self.markers.len() as u32)?;
+        for (name, marker) in self.markers.iter() {
+            write_bytes(writer, name.as_bytes())?;
+            for c in marker.cell.iter() {
+                write_u32(writer, *c as u32)?;
+            }
+            write_bytes(writer, marker.note.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    pub fn read<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let text = |bytes: Vec<u8>| {
+            String::from_utf8(bytes).map_err(|_| invalid_data("marker text isn't UTF-8"))
+        };
+        let mut markers = BTreeMap::new();
+        for _ in 0..read_u32(reader)? {
+            let name = text(read_bytes(reader)?)?;
+            let mut cell = [0; 3];
+            for c in cell.iter_mut() {
+                *c = read_u32(reader)? as usize;
+            }
+            let note = text(read_bytes(reader)?)?;
+            markers.insert(name, Marker { cell, note });
+        }
+        Ok(Markers { markers })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::project::{self, ProjectExtras};
+    use crate::voxel_manager::VoxelManager;
+
+    #[test]
+    fn markers_are_saved_with_the_project() {
+        let mut extras = ProjectExtras::default();
+        let marker = Marker {
+            cell: [3, 0, 12],
+            note: "TODO: door frame\nneeds a lintel".to_owned(),
+        };
+        assert!(extras.markers.insert(" east gate ", marker.clone()));
+        assert!(!extras.markers.insert(" ", marker.clone()));
+
+        let scene = VoxelManager::new(16);
+        let mut bytes = Vec::new();
+        project::write_project_with_extras(&scene, &extras, &mut bytes, &mut |_| true).unwrap();
+        let (_, loaded) =
+            project::read_project_with_extras(&mut bytes.as_slice(), &mut |_| true).unwrap();
+        assert_eq!(loaded.markers, extras.markers);
+        assert_eq!(loaded.markers.get("east gate"), Some(&marker));
+        assert_eq!(loaded.markers.iter().count(), 1);
+    }
+}
@@ -0,0 +1,222 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// Maximum number of colors a scene can use, matching the .vox data model
+pub const PALETTE_SIZE: usize = 256;
+
+/// The colors are sRGB encoded, the way the color picker shows them and other tools store
+/// them. The shaders and anything blending colors convert them to linear light first.
+/// Emissive entries are shown unlit and feed the bloom.
+///
+/// Serialized as the list of its colors, without the emissive flags
+#[derive(Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(from = "Vec<[f32; 4]>", into = "Vec<[f32; 4]>")
+)]
+pub struct Palette {
+    colors: Vec<[f32; 4]>,
+    dirty: bool,
+    // Entries no voxel uses, given new colors once the palette is full
+    unused: Vec<u8>,
+    // Whether a color was replaced by the closest entry of the full palette
+    snapped: bool,
+    emissive: BTreeSet<u8>,
+}
+
+impl Palette {
+    pub fn new() -> Self {
+        Palette {
+            colors: Vec::new(),
+            dirty: true,
+            unused: Vec::new(),
+            snapped: false,
+            emissive: BTreeSet::new(),
+        }
+    }
+
+    pub fn from_colors(mut colors: Vec<[f32; 4]>) -> Self {
+        colors.truncate(PALETTE_SIZE);
+        Palette {
+            colors,
+            dirty: true,
+            unused: Vec::new(),
+            snapped: false,
+            emissive: BTreeSet::new(),
+        }
+    }
+
+    /// Returns the index of the given color, inserting it if the palette doesn't contain it yet.
+    /// When the palette is full the color replaces an unused entry, or without one the index of
+    /// the closest color is returned.
+    pub fn index_of(&mut self, color: [f32; 4]) -> u8 {
+        if let Some(idx) = self.colors.iter().position(|c| *c == color) {
+            self.unused.retain(|unused| *unused as usize != idx);
+            return idx as u8;
+        }
+        if self.colors.len() < PALETTE_SIZE {
+            self.colors.push(color);
+            self.dirty = true;
+            return (self.colors.len() - 1) as u8;
+        }
+        while let Some(idx) = self.unused.pop() {
+            if let Some(entry) = self.colors.get_mut(idx as usize) {
+                *entry = color;
+                self.emissive.remove(&idx);
+                self.dirty = true;
+                return idx;
+            }
+        }
+        self.snapped = true;
+        self.closest(color)
+    }
+
+    /// Sets the entries no voxel uses, index_of reuses them once the palette is full
+    pub fn set_unused(&mut self, entries: Vec<u8>) {
+        self.unused = entries;
+    }
+
+    /// Returns true if a color was snapped to the closest entry since the last call
+    pub fn take_snapped(&mut self) -> bool {
+        std::mem::replace(&mut self.snapped, false)
+    }
+
+    /// The index of the entry nearest to the color
+    pub fn closest(&self, color: [f32; 4]) -> u8 {
+        let mut closest = 0;
+        let mut closest_dist = f32::INFINITY;
+        for (idx, c) in self.colors.iter().enumerate() {
+            let dist = (0..4).map(|i| (c[i] - color[i]).powi(2)).sum::<f32>();
+            if dist < closest_dist {
+                closest = idx;
+                closest_dist = dist;
+            }
+        }
+        closest as u8
+    }
+
+    pub fn color(&self, index: u8) -> [f32; 4] {
+        self.colors[index as usize]
+    }
+
+    pub fn colors(&self) -> &[[f32; 4]] {
+        &self.colors
+    }
+
+    /// The palette padded to PALETTE_SIZE entries, as expected by the voxel shader
+    pub fn raw(&self) -> Vec<[f32; 4]> {
+        let mut raw = self.colors.clone();
+        raw.resize(PALETTE_SIZE, [0.0; 4]);
+        raw
+    }
+
+    /// Flags an entry as glowing or takes the flag away, indices past the colors are ignored
+    pub fn set_emissive(&mut self, index: u8, emissive: bool) {
+        if index as usize >= self.colors.len() {
+            return;
+        }
+        let changed = if emissive {
+            self.emissive.insert(index)
+        } else {
+            self.emissive.remove(&index)
+        };
+        self.dirty |= changed;
+    }
+
+    pub fn is_emissive(&self, index: u8) -> bool {
+        self.emissive.contains(&index)
+    }
+
+    /// Indices of the emissive entries in ascending order
+    pub fn emissive(&self) -> impl Iterator<Item = u8> + '_ {
+        self.emissive.iter().copied()
+    }
+
+    /// One for the emissive entries and zero for the others, padded like raw
+    pub fn raw_emission(&self) -> Vec<[f32; 4]> {
+        let mut raw = vec![[0.0; 4]; PALETTE_SIZE];
+        for index in self.emissive.iter() {
+            raw[*index as usize] = [1.0; 4];
+        }
+        raw
+    }
+
+    /// Returns true if the palette changed since the last call
+    pub fn take_dirty(&mut self) -> bool {
+        std::mem::replace(&mut self.dirty, false)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<Vec<[f32; 4]>> for Palette {
+    fn from(colors: Vec<[f32; 4]>) -> Self {
+        Palette::from_colors(colors)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<Palette> for Vec<[f32; 4]> {
+    fn from(palette: Palette) -> Self {
+        palette.colors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn reuses_existing_colors() {
+        let mut palette = Palette::new();
+        let red = palette.index_of([1.0, 0.0, 0.0, 1.0]);
+        let blue = palette.index_of([0.0, 0.0, 1.0, 1.0]);
+        assert_ne!(red, blue);
+        assert_eq!(palette.index_of([1.0, 0.0, 0.0, 1.0]), red);
+        assert_eq!(palette.index_of([0.0, 1.0, 0.0, 1.0]), 2);
+    }
+
+    #[test]
+    fn full_palette_returns_closest_color() {
+        let mut palette = Palette::new();
+        for i in 0..PALETTE_SIZE {
+            palette.index_of([i as f32 / PALETTE_SIZE as f32, 0.0, 0.0, 1.0]);
+        }
+        let idx = palette.index_of([0.0, 0.0, 0.001, 1.0]);
+        assert_eq!(idx, 0);
+        assert_eq!(palette.color(idx), [0.0, 0.0, 0.0, 1.0]);
+        assert!(palette.take_snapped());
+
+        // Unused entries make room, unless the color got used again
+        palette.set_unused(vec![3, 7]);
+        assert_eq!(
+            palette.index_of([7.0 / PALETTE_SIZE as f32, 0.0, 0.0, 1.0]),
+            7
+        );
+        assert_eq!(palette.index_of([0.0, 1.0, 0.0, 1.0]), 3);
+        assert_eq!(palette.color(3), [0.0, 1.0, 0.0, 1.0]);
+        assert!(!palette.take_snapped());
+        palette.index_of([0.0, 0.0, 1.0, 1.0]);
+        assert!(palette.take_snapped());
+    }
+
+    #[test]
+    fn reused_entries_stop_glowing() {
+        let mut palette = Palette::new();
+        for i in 0..PALETTE_SIZE {
+            palette.index_of([i as f32 / PALETTE_SIZE as f32, 0.0, 0.0, 1.0]);
+        }
+        palette.set_emissive(3, true);
+        palette.set_emissive(5, true);
+        assert_eq!(palette.emissive().collect::<Vec<_>>(), vec![3, 5]);
+        assert_eq!(palette.raw_emission()[3], [1.0; 4]);
+        assert_eq!(palette.raw_emission()[4], [0.0; 4]);
+
+        palette.set_unused(vec![3]);
+        assert_eq!(palette.index_of([0.0, 1.0, 0.0, 1.0]), 3);
+        assert!(!palette.is_emissive(3));
+        assert!(palette.is_emissive(5));
+    }
+}
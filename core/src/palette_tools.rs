@@ -0,0 +1,310 @@
+use crate::color::{from_linear, to_linear};
+use crate::palette::Palette;
+use crate::shading::{from_hsv, to_hsv};
+use crate::voxel_manager::VoxelManager;
+use std::cmp::Ordering;
+
+/// What the palette entries are sorted by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaletteOrder {
+    /// Grays first from dark to bright, then the colors around the color wheel
+    Hue,
+    /// From dark to bright
+    Luminance,
+}
+
+impl PaletteOrder {
+    pub const ALL: [PaletteOrder; 2] = [PaletteOrder::Hue, PaletteOrder::Luminance];
+}
+
+impl std::fmt::Display for PaletteOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// The color space ramps are blended in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RampSpace {
+    /// Perceptually even steps of lightness
+    Oklab,
+    /// Around the color wheel along the shorter way
+    Hsv,
+}
+
+impl RampSpace {
+    pub const ALL: [RampSpace; 2] = [RampSpace::Oklab, RampSpace::Hsv];
+}
+
+impl std::fmt::Display for RampSpace {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// Lightness and the two color axes of the sRGB color
+fn to_oklab(color: [f32; 4]) -> [f32; 3] {
+    let [r, g, b] = [
+        to_linear(color[0]),
+        to_linear(color[1]),
+        to_linear(color[2]),
+    ];
+    let l = (0.412_221_46 * r + 0.536_332_55 * g + 0.051_445_995 * b).cbrt();
+    let m = (0.211_903_5 * r + 0.680_699_5 * g + 0.107_396_96 * b).cbrt();
+    let s = (0.088_302_46 * r + 0.281_718_85 * g + 0.629_978_7 * b).cbrt();
+    [
+        0.210_454_26 * l + 0.793_617_8 * m - 0.004_072_047 * s,
+        1.977_998_5 * l - 2.428_592_2 * m + 0.450_593_7 * s,
+        0.025_904_037 * l + 0.782_771_77 * m - 0.808_675_77 * s,
+    ]
+}
+
+fn from_oklab([lightness, a, b]: [f32; 3], alpha: f32) -> [f32; 4] {
+    let l = (lightness + 0.396_337_78 * a + 0.215_803_76 * b).powi(3);
+    let m = (lightness - 0.105_561_346 * a - 0.063_854_17 * b).powi(3);
+    let s = (lightness - 0.089_484_18 * a - 1.291_485_5 * b).powi(3);
+    [
+        from_linear(4.076_741_7 * l - 3.307_711_6 * m + 0.230_969_94 * s),
+        from_linear(-1.268_438 * l + 2.609_757_4 * m - 0.341_319_38 * s),
+        from_linear(-0.004_196_086_3 * l - 0.703_418_6 * m + 1.707_614_7 * s),
+        alpha,
+    ]
+}
+
+/// The color f of the way from one color to the other
+fn blend(from: [f32; 4], to: [f32; 4], f: f32, space: RampSpace) -> [f32; 4] {
+    let lerp = |a: f32, b: f32| a + (b - a) * f;
+    let alpha = lerp(from[3], to[3]);
+    match space {
+        RampSpace::Oklab => {
+            let (a, b) = (to_oklab(from), to_oklab(to));
+            from_oklab(
+                [lerp(a[0], b[0]), lerp(a[1], b[1]), lerp(a[2], b[2])],
+                alpha,
+            )
+        }
+        RampSpace::Hsv => {
+            let (mut a, b) = (to_hsv(from), to_hsv(to));
+            // Grays take the hue of the other color instead of turning through red
+            if a[1] == 0.0 {
+                a[0] = b[0];
+            }
+            let b_hue = if b[1] == 0.0 { a[0] } else { b[0] };
+            let turn = (b_hue - a[0] + 540.0).rem_euclid(360.0) - 180.0;
+            from_hsv([a[0] + turn * f, lerp(a[1], b[1]), lerp(a[2], b[2])], alpha)
+        }
+    }
+}
+
+/// Steps colors evenly spread from the first key color through the others to the last one,
+/// the key colors themselves among them when the steps land on them
+pub fn ramp(keys: &[[f32; 4]], steps: usize, space: RampSpace) -> Vec<[f32; 4]> {
+    match (keys.len(), steps) {
+        (0, _) | (_, 0) => return Vec::new(),
+        (1, _) | (_, 1) => return vec![keys[0]; steps.min(1)],
+        _ => (),
+    }
+    let segments = (keys.len() - 1) as f32;
+    (0..steps)
+        .map(|step| {
+            let t = step as f32 / (steps - 1) as f32 * segments;
+            let segment = (t as usize).min(keys.len() - 2);
+            blend(keys[segment], keys[segment + 1], t - segment as f32, space)
+        })
+        .collect()
+}
+
+/// Colors that go with a color, picked by turning its hue
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Harmony {
+    /// The opposite hue
+    Complementary,
+    /// The neighbouring hues on both sides
+    Analogous,
+    /// The hues a third of the way around
+    Triadic,
+    /// The two hues next to the opposite one
+    SplitComplementary,
+}
+
+impl Harmony {
+    pub const ALL: [Harmony; 4] = [
+        Harmony::Complementary,
+        Harmony::Analogous,
+        Harmony::Triadic,
+        Harmony::SplitComplementary,
+    ];
+
+    /// Degrees the hue is turned by for each of the suggested colors
+    fn turns(self) -> &'static [f32] {
+        match self {
+            Harmony::Complementary => &[180.0],
+            Harmony::Analogous => &[-30.0, 30.0],
+            Harmony::Triadic => &[120.0, 240.0],
+            Harmony::SplitComplementary => &[150.0, 210.0],
+        }
+    }
+
+    /// The suggested colors for the color, keeping its saturation, value and alpha. Grays have
+    /// no hue to turn and get no suggestions.
+    pub fn colors(self, color: [f32; 4]) -> Vec<[f32; 4]> {
+        let [hue, saturation, value] = to_hsv(color);
+        if saturation == 0.0 {
+            return Vec::new();
+        }
+        self.turns()
+            .iter()
+            .map(|turn| from_hsv([hue + turn, saturation, value], color[3]))
+            .collect()
+    }
+}
+
+impl std::fmt::Display for Harmony {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+fn luminance(color: [f32; 4]) -> f32 {
+    0.2126 * color[0] + 0.7152 * color[1] + 0.0722 * color[2]
+}
+
+/// Distance of the colors in RGBA, like the palette picks the closest color by
+fn distance(a: [f32; 4], b: [f32; 4]) -> f32 {
+    (0..4).map(|i| (a[i] - b[i]).powi(2)).sum::<f32>().sqrt()
+}
+
+/// Sorts the palette entries and renumbers the voxels so they keep their colors
+pub fn sort_palette(voxel_manager: &mut VoxelManager, order: PaletteOrder) {
+    let colors = voxel_manager.palette().colors().to_vec();
+    let key = |color: [f32; 4]| match order {
+        PaletteOrder::Hue => {
+            let [hue, saturation, value] = to_hsv(color);
+            let hue = if saturation == 0.0 { -1.0 } else { hue };
+            (hue, value)
+        }
+        PaletteOrder::Luminance => (luminance(color), 0.0),
+    };
+    let mut sorted: Vec<usize> = (0..colors.len()).collect();
+    sorted.sort_by(|a, b| {
+        key(colors[*a])
+            .partial_cmp(&key(colors[*b]))
+            .unwrap_or(Ordering::Equal)
+    });
+    let mut map = vec![0; colors.len()];
+    for (new, old) in sorted.iter().enumerate() {
+        map[*old] = new as u8;
+    }
+    let palette = Palette::from_colors(sorted.iter().map(|i| colors[*i]).collect());
+    voxel_manager.remap_colors(palette, &map);
+}
+
+/// Merges every palette entry into the first earlier entry at most tolerance away from it,
+/// moving its voxels over. Returns the number of merged entries.
+pub fn merge_duplicates(voxel_manager: &mut VoxelManager, tolerance: f32) -> usize {
+    let colors = voxel_manager.palette().colors().to_vec();
+    let mut kept: Vec<[f32; 4]> = Vec::new();
+    let mut map = Vec::with_capacity(colors.len());
+    for color in colors.iter() {
+        match kept
+            .iter()
+            .position(|kept| distance(*kept, *color) <= tolerance)
+        {
+            Some(index) => map.push(index as u8),
+            None => {
+                map.push(kept.len() as u8);
+                kept.push(*color);
+            }
+        }
+    }
+    let merged = colors.len() - kept.len();
+    if merged > 0 {
+        voxel_manager.remap_colors(Palette::from_colors(kept), &map);
+    }
+    merged
+}
+
+/// The palette entries no voxel uses
+pub fn unused_entries(voxel_manager: &VoxelManager) -> Vec<u8> {
+    let mut used = vec![false; voxel_manager.palette().colors().len()];
+    let extent = voxel_manager.extent();
+    for x in 0..extent {
+        for y in 0..extent {
+            for z in 0..extent {
+                if let Some(index) = voxel_manager.voxel(x, y, z) {
+                    used[index as usize] = true;
+                }
+            }
+        }
+    }
+    (0..used.len())
+        .filter(|i| !used[*i])
+        .map(|i| i as u8)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn merged_and_sorted_palettes_keep_the_voxel_colors() {
+        let mut scene = VoxelManager::new(8);
+        let white = scene.palette_mut().index_of([1.0, 1.0, 1.0, 1.0]);
+        let red = scene.palette_mut().index_of([1.0, 0.0, 0.0, 1.0]);
+        let almost_red = scene.palette_mut().index_of([0.98, 0.01, 0.0, 1.0]);
+        scene.palette_mut().index_of([0.0, 0.0, 1.0, 1.0]);
+        let black = scene.palette_mut().index_of([0.0, 0.0, 0.0, 1.0]);
+        scene.set_voxel(0, 0, 0, Some(white));
+        scene.set_voxel(1, 0, 0, Some(red));
+        scene.set_voxel(2, 0, 0, Some(almost_red));
+        scene.set_voxel(3, 0, 0, Some(black));
+        assert_eq!(unused_entries(&scene), vec![3]);
+
+        assert_eq!(merge_duplicates(&mut scene, 0.05), 1);
+        assert_eq!(scene.palette().colors().len(), 4);
+        assert_eq!(scene.voxel(1, 0, 0), scene.voxel(2, 0, 0));
+
+        let color = |scene: &VoxelManager, x| scene.palette().color(scene.voxel(x, 0, 0).unwrap());
+        sort_palette(&mut scene, PaletteOrder::Luminance);
+        assert_eq!(scene.palette().colors()[0], [0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(color(&scene, 0), [1.0, 1.0, 1.0, 1.0]);
+        assert_eq!(color(&scene, 2), [1.0, 0.0, 0.0, 1.0]);
+
+        sort_palette(&mut scene, PaletteOrder::Hue);
+        // The grays come first
+        assert_eq!(
+            &scene.palette().colors()[..2],
+            &[[0.0, 0.0, 0.0, 1.0], [1.0; 4]]
+        );
+        assert_eq!(color(&scene, 3), [0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(unused_entries(&scene), vec![3]);
+    }
+
+    #[test]
+    fn ramps_run_through_the_key_colors() {
+        let keys = [
+            [0.0, 0.0, 0.0, 1.0],
+            [1.0, 0.0, 0.0, 1.0],
+            [1.0, 1.0, 1.0, 1.0],
+        ];
+        for space in RampSpace::ALL.iter() {
+            let colors = ramp(&keys, 5, *space);
+            assert_eq!(colors.len(), 5);
+            for (color, key) in [colors[0], colors[2], colors[4]].iter().zip(keys.iter()) {
+                assert!(
+                    (0..4).all(|i| (color[i] - key[i]).abs() < 1e-3),
+                    "{:?}",
+                    color
+                );
+            }
+            assert_eq!(
+                Harmony::Triadic.colors([1.0, 0.0, 0.0, 1.0]),
+                vec![[0.0, 1.0, 0.0, 1.0], [0.0, 0.0, 1.0, 1.0]]
+            );
+            // A dark red between black and red
+            assert!(colors[1][0] > 0.0 && colors[1][0] < 1.0 && colors[1][1] < colors[1][0]);
+        }
+    }
+}
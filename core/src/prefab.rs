@@ -0,0 +1,300 @@
+use crate::analysis;
+use crate::instance::{place, write_stamp};
+use crate::journal;
+use crate::project;
+use crate::selection::Selection;
+use crate::settings::Settings;
+use crate::voxel_manager::VoxelManager;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::SystemTime;
+
+/// Another saved project placed into the scene. Its voxels are stamped again whenever the file
+/// changes, so a library of component projects can be assembled into bigger scenes.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Prefab {
+    /// Project file of the voxels, relative paths start next to the project the prefab is in
+    pub path: String,
+    pub offset: [i64; 3],
+    /// Quarter turns around the vertical axis through the center of the voxels, before moving
+    pub turns: usize,
+}
+
+impl Prefab {
+    /// The project file of the prefab, for a prefab inside the project at the path
+    pub fn source_path(&self, project_path: &str) -> PathBuf {
+        let path = Path::new(&self.path);
+        match Path::new(project_path).parent() {
+            Some(dir) if path.is_relative() => dir.join(path),
+            _ => path.to_path_buf(),
+        }
+    }
+}
+
+/// Written as `x y z turns path`, the path last as it may contain spaces
+impl fmt::Display for Prefab {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let [x, y, z] = self.offset;
+        write!(f, "{} {} {} {} {}", x, y, z, self.turns, self.path)
+    }
+}
+
+impl FromStr for Prefab {
+    type Err = ();
+
+    fn from_str(text: &str) -> Result<Self, ()> {
+        let mut parts = text.trim().splitn(5, ' ');
+        let mut number = || parts.next().ok_or(())?.parse::<i64>().map_err(|_| ());
+        let offset = [number()?, number()?, number()?];
+        let turns = number()?;
+        let path = parts.next().ok_or(())?.trim();
+        if turns < 0 || path.is_empty() {
+            return Err(());
+        }
+        Ok(Prefab {
+            path: path.to_owned(),
+            offset,
+            turns: turns as usize,
+        })
+    }
+}
+
+/// The prefabs of a project with the cells each was last stamped on, kept in a sidecar file of
+/// the project so the voxels of an older version are removed after reopening it
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Prefabs {
+    prefabs: Vec<Prefab>,
+    stamped: Vec<Vec<[usize; 3]>>,
+}
+
+impl Prefabs {
+    /// The sidecar file a project's prefabs are kept in
+    pub fn path(project_path: &str) -> PathBuf {
+        PathBuf::from(format!("{}.prefabs", project_path))
+    }
+
+    /// Reads the prefabs of the project, none when it has no sidecar file yet. Stamped cells
+    /// outside a grid of the extent are dropped.
+    pub fn load(project_path: &str, extent: usize) -> Self {
+        let settings = match Settings::read(&Prefabs::path(project_path)) {
+            Ok(settings) => settings,
+            Err(_) => return Prefabs::default(),
+        };
+        let mut prefabs: Vec<(usize, Prefab)> = settings
+            .keys()
+            .filter(|key| key.starts_with("prefab."))
+            .filter_map(|key| Some((key["prefab.".len()..].parse().ok()?, settings.get(key)?)))
+            .collect();
+        prefabs.sort_by_key(|(n, _)| *n);
+        let stamped = prefabs
+            .iter()
+            .map(|(n, _)| {
+                settings
+                    .get::<Selection>(&format!("stamped.{}", n))
+                    .map_or_else(Vec::new, |selection| selection.within(extent).cells())
+            })
+            .collect();
+        Prefabs {
+            prefabs: prefabs.into_iter().map(|(_, prefab)| prefab).collect(),
+            stamped,
+        }
+    }
+
+    pub fn save(&self, project_path: &str) -> io::Result<()> {
+        let mut settings = Settings::default();
+        for (n, (prefab, stamped)) in self.prefabs.iter().zip(&self.stamped).enumerate() {
+            settings.set(&format!("prefab.{}", n + 1), prefab);
+            settings.set(
+                &format!("stamped.{}", n + 1),
+                Selection::from_cells(stamped.clone()),
+            );
+        }
+        settings.write(&Prefabs::path(project_path))
+    }
+
+    /// Adds a prefab that isn't stamped yet, returns its index
+    pub fn add(&mut self, prefab: Prefab) -> usize {
+        self.prefabs.push(prefab);
+        self.stamped.push(Vec::new());
+        self.prefabs.len() - 1
+    }
+
+    /// Drops the prefabs of the project file, their voxels become plain voxels of the scene.
+    /// Returns the number of dropped prefabs.
+    pub fn unlink(&mut self, path: &str) -> usize {
+        let count = self.prefabs.len();
+        let mut stamped = std::mem::take(&mut self.stamped).into_iter();
+        let mut kept = Vec::new();
+        self.prefabs.retain(|prefab| {
+            let cells = stamped.next().unwrap_or_default();
+            let keep = prefab.path != path.trim();
+            if keep {
+                kept.push(cells);
+            }
+            keep
+        });
+        self.stamped = kept;
+        count - self.prefabs.len()
+    }
+
+    pub fn prefabs(&self) -> &[Prefab] {
+        &self.prefabs
+    }
+
+    /// Stamps the voxels of the source project as the prefab at the index, replacing the voxels
+    /// it was stamped with before
+    pub fn restamp(
+        &mut self,
+        index: usize,
+        voxel_manager: &mut VoxelManager,
+        source: &VoxelManager,
+    ) {
+        if let Some(prefab) = self.prefabs.get(index) {
+            self.stamped[index] = stamp(voxel_manager, source, prefab, &self.stamped[index]);
+        }
+    }
+}
+
+/// Copies the voxels of the source project onto the cells of the prefab, with their colors
+/// added to the palette of the scene, and empties the cells it was stamped on before that the
+/// copy doesn't cover now. Returns the stamped cells.
+pub fn stamp(
+    voxel_manager: &mut VoxelManager,
+    source: &VoxelManager,
+    prefab: &Prefab,
+    previous: &[[usize; 3]],
+) -> Vec<[usize; 3]> {
+    let (cells, indices): (Vec<[usize; 3]>, Vec<u8>) =
+        analysis::filled_cells(source).into_iter().unzip();
+    let values = indices
+        .into_iter()
+        .map(|index| {
+            let color = source.palette().color(index);
+            Some(voxel_manager.palette_mut().index_of(color))
+        })
+        .collect();
+    let targets = place(&cells, prefab.offset, prefab.turns, voxel_manager.extent());
+    let covered: HashSet<[usize; 3]> = targets.iter().flatten().copied().collect();
+    write_stamp(voxel_manager, targets, values, &covered, previous)
+}
+
+/// Loads the project with its own prefabs stamped into it. Fails when a prefab includes one of
+/// the projects it is included by.
+pub fn load_composed(project_path: &Path, including: &[PathBuf]) -> io::Result<VoxelManager> {
+    let canonical = fs::canonicalize(project_path)?;
+    if including.contains(&canonical) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} includes itself as a prefab", project_path.display()),
+        ));
+    }
+    let project_path = project_path.to_string_lossy();
+    let mut voxel_manager = project::load_project(&project_path, &mut |_| true)?;
+    let mut including = including.to_vec();
+    including.push(canonical);
+    let prefabs = Prefabs::load(&project_path, voxel_manager.extent());
+    // The saved project holds the prefabs as they were last stamped, move them to where their
+    // files put them now
+    for (prefab, stamped) in prefabs.prefabs().iter().zip(&prefabs.stamped) {
+        let source = load_composed(&prefab.source_path(&project_path), &including)?;
+        stamp(&mut voxel_manager, &source, prefab, stamped);
+    }
+    Ok(voxel_manager)
+}
+
+/// Latest modification time of the project, its prefabs sidecar and the projects of its prefabs,
+/// None when the project file is missing
+pub fn modified(project_path: &Path) -> Option<SystemTime> {
+    modified_within(project_path, &mut Vec::new())
+}
+
+fn modified_within(project_path: &Path, visited: &mut Vec<PathBuf>) -> Option<SystemTime> {
+    let canonical = fs::canonicalize(project_path).ok()?;
+    if visited.contains(&canonical) {
+        return None;
+    }
+    visited.push(canonical);
+    let time = |path: &Path| fs::metadata(path).and_then(|meta| meta.modified()).ok();
+    let mut latest = time(project_path)?;
+    let project_path = project_path.to_string_lossy();
+    let journal_path = PathBuf::from(journal::path(&project_path));
+    for sidecar in [Prefabs::path(&project_path), journal_path].iter() {
+        if let Some(sidecar) = time(sidecar) {
+            latest = latest.max(sidecar);
+        }
+    }
+    // Only the paths of the prefabs matter, not the cells they were stamped on
+    for prefab in Prefabs::load(&project_path, 0).prefabs() {
+        if let Some(nested) = modified_within(&prefab.source_path(&project_path), visited) {
+            latest = latest.max(nested);
+        }
+    }
+    Some(latest)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn prefabs_nest_and_follow_their_files() {
+        let dir = std::env::temp_dir().join(format!("prefab_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let wheel_path = dir.join("wheel.vxe").to_string_lossy().into_owned();
+        let cart_path = dir.join("cart.vxe").to_string_lossy().into_owned();
+
+        let mut wheel = VoxelManager::new(8);
+        let black = wheel.palette_mut().index_of([0.0, 0.0, 0.0, 1.0]);
+        wheel.set_voxel(0, 0, 0, Some(black));
+        project::save_project(&wheel, &wheel_path, &mut |_| true).unwrap();
+
+        // The cart holds the wheel, by a path relative to the cart, and is saved with it stamped
+        let mut cart = VoxelManager::new(8);
+        let mut cart_prefabs = Prefabs::default();
+        let wheel_index = cart_prefabs.add("2 0 0 0 wheel.vxe".parse().unwrap());
+        cart_prefabs.restamp(wheel_index, &mut cart, &wheel);
+        project::save_project(&cart, &cart_path, &mut |_| true).unwrap();
+        cart_prefabs.save(&cart_path).unwrap();
+
+        let mut scene = VoxelManager::new(16);
+        let mut prefabs = Prefabs::default();
+        let index = prefabs.add(Prefab {
+            path: cart_path.clone(),
+            offset: [5, 0, 0],
+            turns: 0,
+        });
+        let cart = load_composed(Path::new(&cart_path), &[]).unwrap();
+        prefabs.restamp(index, &mut scene, &cart);
+        let black = scene.palette_mut().index_of([0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(scene.voxel(7, 0, 0), Some(black));
+
+        // Moving the wheel in its file moves it in the scene once restamped
+        let mut wheel = VoxelManager::new(8);
+        let black = wheel.palette_mut().index_of([0.0, 0.0, 0.0, 1.0]);
+        wheel.set_voxel(0, 1, 0, Some(black));
+        project::save_project(&wheel, &wheel_path, &mut |_| true).unwrap();
+        let cart = load_composed(Path::new(&cart_path), &[]).unwrap();
+        prefabs.restamp(index, &mut scene, &cart);
+        assert_eq!(scene.voxel(7, 0, 0), None);
+        assert!(scene.voxel(7, 1, 0).is_some());
+
+        // A cart holding itself
+        cart_prefabs.add("0 0 0 0 cart.vxe".parse().unwrap());
+        cart_prefabs.save(&cart_path).unwrap();
+        let err = load_composed(Path::new(&cart_path), &[]).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        assert_eq!(prefabs.unlink(&cart_path), 1);
+        assert!(prefabs.prefabs().is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
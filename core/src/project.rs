@@ -0,0 +1,546 @@
+use crate::journal;
+use crate::marker::Markers;
+use crate::palette::{Palette, PALETTE_SIZE};
+use crate::snapshot::Snapshots;
+use crate::subgrid::{self, SubGrid};
+use crate::task::{cancelled, Progress};
+use crate::voxel_manager::{VoxelManager, MAX_EXTENT};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::io::{self, BufWriter};
+
+const MAGIC: &[u8; 4] = b"VXED";
+// Version 6 ends with the snapshots, the markers, the size of a voxel, the sub-grids and the
+// emissive palette entries, version 5 without the emissive entries, version 4 without the
+// sub-grids, version 3 without the size either, version 2 with the
+// snapshots only and version 1 projects are read without any of them
+const VERSION: u32 = 6;
+// Run value of empty cells, filled cells are stored as palette index + 1
+const EMPTY: u16 = 0;
+
+pub(crate) fn write_u16<W: Write>(writer: &mut W, value: u16) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+pub(crate) fn write_u32<W: Write>(writer: &mut W, value: u32) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+fn write_f32<W: Write>(writer: &mut W, value: f32) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+pub(crate) fn read_u16<R: Read>(reader: &mut R) -> io::Result<u16> {
+    let mut bytes = [0; 2];
+    reader.read_exact(&mut bytes)?;
+    Ok(u16::from_le_bytes(bytes))
+}
+
+pub(crate) fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_f32<R: Read>(reader: &mut R) -> io::Result<f32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(f32::from_le_bytes(bytes))
+}
+
+pub(crate) fn invalid_data(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason)
+}
+
+/// Writes the length of the bytes followed by the bytes
+pub(crate) fn write_bytes<W: Write>(writer: &mut W, bytes: &[u8]) -> io::Result<()> {
+    write_u32(writer, bytes.len() as u32)?;
+    writer.write_all(bytes)
+}
+
+pub(crate) fn read_bytes<R: Read>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let len = read_u32(reader)?;
+    let mut bytes = Vec::new();
+    reader.take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != len as usize {
+        return Err(invalid_data("truncated project"));
+    }
+    Ok(bytes)
+}
+
+/// What a project file keeps besides the scene
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ProjectExtras {
+    pub snapshots: Snapshots,
+    pub markers: Markers,
+    pub sub_grids: Vec<SubGrid>,
+}
+
+/// Run-length encodes the cells of a chunk as (run length, value) pairs
+pub(crate) fn encode_chunk(voxel_manager: &VoxelManager, chunk: usize) -> Vec<(u16, u16)> {
+    let mut runs: Vec<(u16, u16)> = Vec::new();
+    for [x, y, z] in voxel_manager.chunk_cells(chunk) {
+        let value = voxel_manager
+            .voxel(x, y, z)
+            .map_or(EMPTY, |idx| idx as u16 + 1);
+        match runs.last_mut() {
+            Some((len, last)) if *last == value && *len < u16::MAX => *len += 1,
+            _ => runs.push((1, value)),
+        }
+    }
+    runs
+}
+
+pub(crate) fn write_palette<W: Write>(
+    voxel_manager: &VoxelManager,
+    writer: &mut W,
+) -> io::Result<()> {
+    let colors = voxel_manager.palette().colors();
+    write_u32(writer, colors.len() as u32)?;
+    for color in colors.iter() {
+        for channel in color.iter() {
+            write_f32(writer, *channel)?;
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn read_palette<R: Read>(reader: &mut R) -> io::Result<Palette> {
+    let color_count = read_u32(reader)? as usize;
+    if color_count > PALETTE_SIZE {
+        return Err(invalid_data("palette has more than 256 colors"));
+    }
+    let mut colors = Vec::with_capacity(color_count);
+    for _ in 0..color_count {
+        let mut color = [0.0; 4];
+        for channel in color.iter_mut() {
+            *channel = read_f32(reader)?;
+        }
+        colors.push(color);
+    }
+    Ok(Palette::from_colors(colors))
+}
+
+pub(crate) fn write_chunk<W: Write>(
+    voxel_manager: &VoxelManager,
+    chunk: usize,
+    writer: &mut W,
+) -> io::Result<()> {
+    write_runs(&encode_chunk(voxel_manager, chunk), writer)
+}
+
+pub(crate) fn write_runs<W: Write>(runs: &[(u16, u16)], writer: &mut W) -> io::Result<()> {
+    write_u32(writer, runs.len() as u32)?;
+    for (len, value) in runs {
+        write_u16(writer, *len)?;
+        write_u16(writer, *value)?;
+    }
+    Ok(())
+}
+
+/// Overwrites every cell of the chunk, empty runs clear the cells. Runs of colors the palette
+/// of the scene doesn't have are invalid, so the palette has to be read first.
+pub(crate) fn read_chunk<R: Read>(
+    voxel_manager: &mut VoxelManager,
+    chunk: usize,
+    reader: &mut R,
+) -> io::Result<()> {
+    let cells = voxel_manager.chunk_cells(chunk);
+    let mut cell = 0;
+    for _ in 0..read_u32(reader)? {
+        let len = read_u16(reader)? as usize;
+        let value = read_u16(reader)?;
+        if cell + len > cells.len() {
+            return Err(invalid_data("run exceeds chunk size"));
+        }
+        let color_index = if value == EMPTY {
+            None
+        } else if (value as usize - 1) < voxel_manager.palette().colors().len() {
+            Some((value - 1) as u8)
+        } else {
+            return Err(invalid_data("color index is not in the palette"));
+        };
+        for [x, y, z] in cells[cell..cell + len].iter() {
+            voxel_manager.set_voxel(*x, *y, *z, color_index);
+        }
+        cell += len;
+    }
+    Ok(())
+}
+
+/// Writes the project, calling progress with the finished fraction after every chunk.
+/// Returning false from progress cancels the write.
+pub fn write_project<W: Write>(
+    voxel_manager: &VoxelManager,
+    writer: &mut W,
+    progress: &mut Progress,
+) -> io::Result<()> {
+    write_project_with_extras(voxel_manager, &ProjectExtras::default(), writer, progress)
+}
+
+/// Writes the project like write_project followed by its snapshots, markers and sub-grids
+pub fn write_project_with_extras<W: Write>(
+    voxel_manager: &VoxelManager,
+    extras: &ProjectExtras,
+    writer: &mut W,
+    progress: &mut Progress,
+) -> io::Result<()> {
+    writer.write_all(MAGIC)?;
+    write_u32(writer, VERSION)?;
+    write_u32(writer, voxel_manager.extent() as u32)?;
+    write_palette(voxel_manager, writer)?;
+
+    write_u32(writer, voxel_manager.chunk_count() as u32)?;
+    for chunk in 0..voxel_manager.chunk_count() {
+        if !progress(chunk as f32 / voxel_manager.chunk_count() as f32) {
+            return Err(cancelled());
+        }
+        write_chunk(voxel_manager, chunk, writer)?;
+    }
+    extras.snapshots.write(writer)?;
+    extras.markers.write(writer)?;
+    for size in voxel_manager.voxel_scale().iter() {
+        write_f32(writer, *size)?;
+    }
+    subgrid::write_sub_grids(&extras.sub_grids, writer)?;
+    let emissive: Vec<u8> = voxel_manager.palette().emissive().collect();
+    write_u32(writer, emissive.len() as u32)?;
+    writer.write_all(&emissive)?;
+    Ok(())
+}
+
+/// Reads a project, reporting progress and honoring cancellation like write_project
+pub fn read_project<R: Read>(reader: &mut R, progress: &mut Progress) -> io::Result<VoxelManager> {
+    read_project_with_extras(reader, progress).map(|(voxel_manager, _)| voxel_manager)
+}
+
+/// Reads a project with its snapshots, markers and sub-grids
+pub fn read_project_with_extras<R: Read>(
+    reader: &mut R,
+    progress: &mut Progress,
+) -> io::Result<(VoxelManager, ProjectExtras)> {
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid_data("not a voxel-editor project"));
+    }
+    let version = read_u32(reader)?;
+    if version == 0 || version > VERSION {
+        return Err(invalid_data("unsupported project version"));
+    }
+    let extent = read_u32(reader)? as usize;
+    if extent == 0 || extent > MAX_EXTENT {
+        return Err(invalid_data("grid extent is out of range"));
+    }
+    let mut voxel_manager = VoxelManager::new(extent);
+
+    voxel_manager.set_palette(read_palette(reader)?);
+
+    if read_u32(reader)? as usize != voxel_manager.chunk_count() {
+        return Err(invalid_data("chunk count doesn't match the grid extent"));
+    }
+    for chunk in 0..voxel_manager.chunk_count() {
+        if !progress(chunk as f32 / voxel_manager.chunk_count() as f32) {
+            return Err(cancelled());
+        }
+        read_chunk(&mut voxel_manager, chunk, reader)?;
+    }
+    voxel_manager.mark_all_dirty();
+    let snapshots = if version >= 2 {
+        Snapshots::read(reader)?
+    } else {
+        Snapshots::default()
+    };
+    let markers = if version >= 3 {
+        Markers::read(reader)?
+    } else {
+        Markers::default()
+    };
+    if version >= 4 {
+        let mut voxel_scale = [1.0; 3];
+        for size in voxel_scale.iter_mut() {
+            *size = read_f32(reader)?;
+        }
+        if voxel_scale
+            .iter()
+            .any(|size| !size.is_finite() || *size <= 0.0)
+        {
+            return Err(invalid_data("voxel size isn't positive"));
+        }
+        voxel_manager.set_voxel_scale(voxel_scale);
+    }
+    let sub_grids = if version >= 5 {
+        subgrid::read_sub_grids(reader, extent)?
+    } else {
+        Vec::new()
+    };
+    if version >= 6 {
+        let count = read_u32(reader)? as usize;
+        if count > PALETTE_SIZE {
+            return Err(invalid_data("more emissive entries than palette colors"));
+        }
+        let mut emissive = vec![0; count];
+        reader.read_exact(&mut emissive)?;
+        for index in emissive {
+            if index as usize >= voxel_manager.palette().colors().len() {
+                return Err(invalid_data("emissive entry is not in the palette"));
+            }
+            voxel_manager.palette_mut().set_emissive(index, true);
+        }
+    }
+    Ok((
+        voxel_manager,
+        ProjectExtras {
+            snapshots,
+            markers,
+            sub_grids,
+        },
+    ))
+}
+
+/// Saves into a temporary file first so a failed or cancelled save keeps the previous project.
+/// A complete save replaces the project's journal. The saved project has no snapshots, markers
+/// or sub-grids, save_project_with_extras keeps those of a loaded project.
+pub fn save_project(
+    voxel_manager: &VoxelManager,
+    file_path: &str,
+    progress: &mut Progress,
+) -> io::Result<()> {
+    save_project_with_extras(
+        voxel_manager,
+        &ProjectExtras::default(),
+        file_path,
+        progress,
+    )
+}
+
+pub fn save_project_with_extras(
+    voxel_manager: &VoxelManager,
+    extras: &ProjectExtras,
+    file_path: &str,
+    progress: &mut Progress,
+) -> io::Result<()> {
+    let tmp_path = format!("{}.tmp", file_path);
+    let result = File::create(&tmp_path).and_then(|file| {
+        let mut buffer = BufWriter::new(file);
+        write_project_with_extras(voxel_manager, extras, &mut buffer, progress)?;
+        buffer.flush()
+    });
+    match result {
+        // A journal left behind by a crash in between is tagged with the older project, so its
+        // records aren't replayed over this one
+        Ok(_) => fs::rename(&tmp_path, file_path).and_then(|_| journal::discard(file_path)),
+        Err(e) => {
+            let _ = fs::remove_file(&tmp_path);
+            Err(e)
+        }
+    }
+}
+
+/// Loads the scene of a project with the edits of its journal replayed onto it,
+/// load_project_with_extras also reads what is saved with it
+pub fn load_project(file_path: &str, progress: &mut Progress) -> io::Result<VoxelManager> {
+    load_project_with_extras(file_path, progress).map(|(voxel_manager, _)| voxel_manager)
+}
+
+pub fn load_project_with_extras(
+    file_path: &str,
+    progress: &mut Progress,
+) -> io::Result<(VoxelManager, ProjectExtras)> {
+    let bytes = fs::read(file_path)?;
+    let (mut voxel_manager, extras) = read_project_with_extras(&mut bytes.as_slice(), progress)?;
+    journal::replay(file_path, journal::checksum(&bytes), &mut voxel_manager)?;
+    Ok((voxel_manager, extras))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::geometry::BoundingBox;
+    use cgmath::Vector3;
+
+    #[test]
+    fn project_roundtrip() {
+        let mut voxel_manager = VoxelManager::new(16);
+        voxel_manager.add_box(BoundingBox::new(
+            Vector3::new(2.0, 3.0, 4.0),
+            Vector3::new(10.0, 2.0, 5.0),
+            [1.0, 0.0, 0.0, 1.0],
+        ));
+        voxel_manager.set_voxel(15, 15, 15, Some(0));
+        voxel_manager.set_emissive(0, true);
+
+        let mut bytes = Vec::new();
+        write_project(&voxel_manager, &mut bytes, &mut |_| true).unwrap();
+        let loaded = read_project(&mut bytes.as_slice(), &mut |_| true).unwrap();
+
+        assert_eq!(loaded.palette().colors(), voxel_manager.palette().colors());
+        assert!(loaded.palette().is_emissive(0));
+        for x in 0..16 {
+            for y in 0..16 {
+                for z in 0..16 {
+                    assert_eq!(loaded.voxel(x, y, z), voxel_manager.voxel(x, y, z));
+                }
+            }
+        }
+        // The run-length encoded grid is smaller than storing one byte per cell
+        assert!(bytes.len() < 16 * 16 * 16);
+    }
+
+    #[test]
+    fn version_1_projects_have_no_snapshots() {
+        let mut voxel_manager = VoxelManager::new(16);
+        let red = voxel_manager.palette_mut().index_of([1.0, 0.0, 0.0, 1.0]);
+        voxel_manager.set_voxel(1, 2, 3, Some(red));
+        let mut bytes = Vec::new();
+        write_project(&voxel_manager, &mut bytes, &mut |_| true).unwrap();
+        // Version 1 ended after the chunks, where later versions write the snapshot and marker
+        // counts, the size of a voxel and the sub-grid count
+        bytes[4..8].copy_from_slice(&1u32.to_le_bytes());
+        bytes.truncate(bytes.len() - 24);
+
+        let (loaded, extras) =
+            read_project_with_extras(&mut bytes.as_slice(), &mut |_| true).unwrap();
+        assert_eq!(loaded.voxel(1, 2, 3), Some(0));
+        assert_eq!(extras.snapshots.names().count(), 0);
+    }
+
+    #[test]
+    fn voxel_size_is_saved_and_stretches_the_meshes() {
+        let mut voxel_manager = VoxelManager::new(16);
+        let red = voxel_manager.palette_mut().index_of([1.0, 0.0, 0.0, 1.0]);
+        voxel_manager.set_voxel(1, 2, 3, Some(red));
+        voxel_manager.set_voxel_scale([1.0, 2.0, 0.5]);
+        let mut bytes = Vec::new();
+        write_project(&voxel_manager, &mut bytes, &mut |_| true).unwrap();
+        let loaded = read_project(&mut bytes.as_slice(), &mut |_| true).unwrap();
+        assert_eq!(loaded.voxel_scale(), [1.0, 2.0, 0.5]);
+
+        let (vertices, _) = loaded.vertices();
+        let highest = |axis: usize| {
+            vertices
+                .iter()
+                .map(|vertex| vertex.pos[axis])
+                .fold(f32::MIN, f32::max)
+        };
+        assert_eq!([highest(0), highest(1), highest(2)], [2.0, 6.0, 2.0]);
+    }
+
+    #[test]
+    fn corrupt_projects_are_rejected() {
+        let mut voxel_manager = VoxelManager::new(16);
+        let red = voxel_manager.palette_mut().index_of([1.0, 0.0, 0.0, 1.0]);
+        voxel_manager.set_voxel(0, 0, 0, Some(red));
+        let mut bytes = Vec::new();
+        write_project(&voxel_manager, &mut bytes, &mut |_| true).unwrap();
+        let read = |bytes: &[u8]| read_project(&mut &bytes[..], &mut |_| true).map(|_| ());
+
+        // The first run of the first chunk is the red voxel, its value follows the header, the
+        // palette of one color, the chunk and run counts and the run length
+        let run_value = 12 + 4 + 16 + 4 + 4 + 2;
+        assert_eq!(bytes[run_value..run_value + 2], [1, 0]);
+        let mut unknown_color = bytes.clone();
+        unknown_color[run_value] = 2;
+        assert_eq!(
+            read(&unknown_color).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+
+        let mut huge_grid = bytes.clone();
+        huge_grid[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(read(&huge_grid).is_err());
+        let mut huge_palette = bytes;
+        huge_palette[12..16].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(read(&huge_palette).is_err());
+    }
+
+    #[test]
+    fn cancelled_write_is_interrupted() {
+        let voxel_manager = VoxelManager::new(16);
+        let mut bytes = Vec::new();
+        let err = write_project(&voxel_manager, &mut bytes, &mut |_| false).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Interrupted);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn project_types_roundtrip_through_json() {
+        use crate::budget::Budget;
+        use crate::instance::Instance;
+        use crate::marker::Marker;
+        use crate::prefab::Prefab;
+        use crate::selection::{SavedMasks, Selection};
+        use crate::transform::ObjectPivot;
+        use crate::view::CameraView;
+
+        fn roundtrip<T: Serialize + for<'de> Deserialize<'de>>(value: &T) -> T {
+            serde_json::from_str(&serde_json::to_string(value).unwrap()).unwrap()
+        }
+
+        let mut scene = VoxelManager::new(16);
+        let red = scene.palette_mut().index_of([1.0, 0.0, 0.0, 1.0]);
+        scene.set_voxel(1, 1, 1, Some(red));
+        scene.set_origin([-8.0, 0.0, -8.0]);
+        let mut extras = ProjectExtras::default();
+        extras.snapshots.insert("first", &scene);
+        extras.markers.insert(
+            "door",
+            Marker {
+                cell: [3, 0, 4],
+                note: "make it wider".to_owned(),
+            },
+        );
+        extras
+            .sub_grids
+            .push(SubGrid::refine(&mut scene, [0, 0, 0], 2).unwrap());
+
+        let loaded = roundtrip(&scene);
+        assert_eq!(loaded.origin(), [-8.0, 0.0, -8.0]);
+        let loaded = roundtrip(&extras);
+        assert_eq!(loaded.markers, extras.markers);
+        let snapshot = loaded.snapshots.restore("first").unwrap().unwrap();
+        assert_eq!(snapshot.voxel(1, 1, 1), Some(red));
+        let sub_grid = &loaded.sub_grids[0];
+        assert_eq!((sub_grid.corner, sub_grid.density), ([0, 0, 0], 2));
+        assert_eq!(sub_grid.scene.voxel(2, 3, 3), Some(red));
+
+        let mut masks = SavedMasks::default();
+        masks.insert("tree", Selection::from_cells(vec![[1, 1, 1]]));
+        masks.set_pivot("tree", Some(ObjectPivot::Cell([1, 0, 1])));
+        let instance = Instance {
+            source: "tree".to_owned(),
+            offset: [4, 0, 0],
+            turns: 1,
+        };
+        masks.add_instance(instance.clone());
+        let loaded = roundtrip(&masks);
+        assert_eq!(loaded.get("tree").unwrap().cells(), vec![[1, 1, 1]]);
+        assert_eq!(loaded.pivot("tree"), Some(ObjectPivot::Cell([1, 0, 1])));
+        assert_eq!(loaded.instances(), &[instance][..]);
+
+        let prefab = Prefab {
+            path: "parts/door.vxe".to_owned(),
+            offset: [0, 0, -2],
+            turns: 3,
+        };
+        assert_eq!(roundtrip(&prefab), prefab);
+        let budget = Budget {
+            voxels: Some(5000),
+            object_triangles: Some(200),
+            ..Budget::default()
+        };
+        assert_eq!(roundtrip(&budget), budget);
+        let view = CameraView {
+            target: [8.0, 0.0, 8.0],
+            yaw: 0.5,
+            pitch: -0.25,
+            distance: 32.0,
+            fov: 45.0,
+        };
+        assert_eq!(roundtrip(&view), view);
+    }
+}
@@ -0,0 +1,197 @@
+use crate::color::{linear, srgb};
+use crate::palette::Palette;
+use crate::voxel_manager::VoxelManager;
+use std::collections::BTreeMap;
+
+/// Splits the colors, weighted by how many voxels use them, into at most count boxes and
+/// returns the weighted average color of every box, averaged in linear light. The box with the widest channel is cut at
+/// its median along that channel until there are count boxes or none can be cut further.
+pub fn median_cut(colors: &[([f32; 4], usize)], count: usize) -> Vec<[f32; 4]> {
+    let mut boxes: Vec<Vec<([f32; 4], usize)>> = Vec::new();
+    if !colors.is_empty() && count > 0 {
+        boxes.push(colors.to_vec());
+    }
+    while boxes.len() < count {
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, colors)| colors.len() > 1)
+            .map(|(i, colors)| {
+                let (channel, range) = widest_channel(colors);
+                (i, channel, range)
+            })
+            .filter(|(_, _, range)| *range > 0.0)
+            .max_by(|a, b| a.2.partial_cmp(&b.2).unwrap_or(std::cmp::Ordering::Equal));
+        let (i, channel, _) = match widest {
+            Some(widest) => widest,
+            None => break,
+        };
+        let mut cut = boxes.swap_remove(i);
+        cut.sort_by(|a, b| {
+            a.0[channel]
+                .partial_cmp(&b.0[channel])
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        let half = cut.iter().map(|(_, weight)| weight).sum::<usize>() / 2;
+        let mut weight = 0;
+        let mut split = 1;
+        for (j, (_, w)) in cut.iter().enumerate() {
+            weight += w;
+            if weight > half {
+                split = j;
+                break;
+            }
+        }
+        let upper = cut.split_off(split.max(1).min(cut.len() - 1));
+        boxes.push(cut);
+        boxes.push(upper);
+    }
+    boxes.iter().map(|colors| average(colors)).collect()
+}
+
+/// The channel the colors differ the most in and how much
+fn widest_channel(colors: &[([f32; 4], usize)]) -> (usize, f32) {
+    (0..4)
+        .map(|channel| {
+            let values = colors.iter().map(|(color, _)| color[channel]);
+            let min = values.clone().fold(f32::INFINITY, f32::min);
+            let max = values.fold(f32::NEG_INFINITY, f32::max);
+            (channel, max - min)
+        })
+        .fold((0, f32::NEG_INFINITY), |widest, channel| {
+            if channel.1 > widest.1 {
+                channel
+            } else {
+                widest
+            }
+        })
+}
+
+fn average(colors: &[([f32; 4], usize)]) -> [f32; 4] {
+    let total = colors
+        .iter()
+        .map(|(_, weight)| weight)
+        .sum::<usize>()
+        .max(1) as f32;
+    let mut average = [0.0; 4];
+    for (color, weight) in colors {
+        let color = linear(*color);
+        for i in 0..4 {
+            average[i] += color[i] * *weight as f32 / total;
+        }
+    }
+    srgb(average)
+}
+
+/// Replaces the palette of the scene with at most count colors picked by median cut and moves
+/// every voxel to the closest of them. Dithering spreads the error of each voxel onto the next
+/// voxels of its layer like Floyd-Steinberg does on images, so gradients keep their look. The
+/// error is spread in linear light, so the dithered voxels blend into the wanted brightness.
+pub fn quantize(voxel_manager: &mut VoxelManager, count: usize, dither: bool) {
+    let extent = voxel_manager.extent();
+    let mut usage: BTreeMap<u8, usize> = BTreeMap::new();
+    for x in 0..extent {
+        for y in 0..extent {
+            for z in 0..extent {
+                if let Some(index) = voxel_manager.voxel(x, y, z) {
+                    *usage.entry(index).or_insert(0) += 1;
+                }
+            }
+        }
+    }
+    if usage.len() <= count {
+        return;
+    }
+    let old = voxel_manager.palette().clone();
+    let colors: Vec<([f32; 4], usize)> = usage
+        .iter()
+        .map(|(index, uses)| (old.color(*index), *uses))
+        .collect();
+    let palette = Palette::from_colors(median_cut(&colors, count));
+    let mut errors = vec![[0.0f32; 3]; extent * extent * extent];
+    let at = |x: usize, y: usize, z: usize| (y * extent + z) * extent + x;
+    let mut values = Vec::new();
+    for y in 0..extent {
+        for z in 0..extent {
+            for x in 0..extent {
+                let index = match voxel_manager.voxel(x, y, z) {
+                    Some(index) => index,
+                    None => continue,
+                };
+                let mut wanted = linear(old.color(index));
+                let error = errors[at(x, y, z)];
+                for i in 0..3 {
+                    wanted[i] = (wanted[i] + error[i]).max(0.0).min(1.0);
+                }
+                let closest = palette.closest(srgb(wanted));
+                values.push(([x, y, z], closest));
+                if !dither {
+                    continue;
+                }
+                let chosen = linear(palette.color(closest));
+                // The following cells of the layer, the row after this one starts at z + 1
+                let spread: [(i64, usize, f32); 4] = [
+                    (1, 0, 7.0 / 16.0),
+                    (-1, 1, 3.0 / 16.0),
+                    (0, 1, 5.0 / 16.0),
+                    (1, 1, 1.0 / 16.0),
+                ];
+                for (dx, dz, share) in spread.iter() {
+                    let nx = x as i64 + dx;
+                    let nz = z + dz;
+                    if nx < 0 || nx >= extent as i64 || nz >= extent {
+                        continue;
+                    }
+                    let nx = nx as usize;
+                    if voxel_manager.voxel(nx, y, nz).is_none() {
+                        continue;
+                    }
+                    let neighbour = &mut errors[at(nx, y, nz)];
+                    for i in 0..3 {
+                        neighbour[i] += (wanted[i] - chosen[i]) * share;
+                    }
+                }
+            }
+        }
+    }
+    voxel_manager.set_palette(palette);
+    for ([x, y, z], value) in values {
+        voxel_manager.set_voxel(x, y, z, Some(value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn gradients_are_quantized_and_dithered() {
+        let mut scene = VoxelManager::new(16);
+        for x in 0..16 {
+            let shade = x as f32 / 15.0;
+            let index = scene.palette_mut().index_of([shade, shade, shade, 1.0]);
+            for z in 0..16 {
+                scene.set_voxel(x, 0, z, Some(index));
+            }
+        }
+        let mut plain = scene.clone();
+        quantize(&mut plain, 4, false);
+        assert_eq!(plain.palette().colors().len(), 4);
+        // Every column keeps a single color without dithering
+        let column = |scene: &VoxelManager, x| {
+            let mut colors: Vec<u8> = (0..16).filter_map(|z| scene.voxel(x, 0, z)).collect();
+            colors.dedup();
+            colors.len()
+        };
+        assert!((0..16).all(|x| column(&plain, x) == 1));
+        // The darkest and the brightest voxel stay apart
+        let shade = |scene: &VoxelManager, x| scene.palette().color(scene.voxel(x, 0, 0).unwrap());
+        assert!(shade(&plain, 0)[0] < shade(&plain, 15)[0]);
+
+        quantize(&mut scene, 4, true);
+        assert_eq!(scene.palette().colors().len(), 4);
+        assert!((0..16).any(|x| column(&scene, x) > 1));
+        assert_eq!(scene.voxel(0, 1, 0), None);
+    }
+}
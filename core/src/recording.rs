@@ -0,0 +1,206 @@
+use crate::project::{
+    encode_chunk, invalid_data, read_chunk, read_palette, read_u32, write_palette, write_runs,
+    write_u32,
+};
+use crate::voxel_manager::VoxelManager;
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::{self, BufReader, BufWriter};
+
+const MAGIC: &[u8; 4] = b"VXRC";
+const VERSION: u32 = 1;
+
+/// Writes the changes of the scene to a session file as they happen. Every frame holds the
+/// palette and the chunks that differ from the previous frame, the first one holds all of them.
+/// Only the chunks the scene marks as unrecorded are encoded again, the caller clears the marks
+/// with clear_unrecorded_chunks once a frame is captured.
+pub struct Recorder {
+    writer: BufWriter<File>,
+    // Encoded chunks of the last frame
+    chunks: Vec<Vec<(u16, u16)>>,
+}
+
+impl Recorder {
+    /// Starts the recording with the scene as it is now
+    pub fn create(file_path: &str, voxel_manager: &VoxelManager) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(file_path)?);
+        writer.write_all(MAGIC)?;
+        write_u32(&mut writer, VERSION)?;
+        write_u32(&mut writer, voxel_manager.extent() as u32)?;
+        let mut recorder = Recorder {
+            writer,
+            chunks: vec![Vec::new(); voxel_manager.chunk_count()],
+        };
+        recorder.capture_chunks(voxel_manager, 0..voxel_manager.chunk_count(), 0)?;
+        Ok(recorder)
+    }
+
+    /// Adds a frame at the time in milliseconds since the start, when the scene changed.
+    /// Frames are flushed right away, so a crash only loses the frame being written.
+    pub fn capture(&mut self, voxel_manager: &VoxelManager, time: u32) -> io::Result<bool> {
+        let chunks = voxel_manager.unrecorded_chunks();
+        self.capture_chunks(voxel_manager, chunks.into_iter(), time)
+    }
+
+    fn capture_chunks(
+        &mut self,
+        voxel_manager: &VoxelManager,
+        chunks: impl Iterator<Item = usize>,
+        time: u32,
+    ) -> io::Result<bool> {
+        if voxel_manager.chunk_count() != self.chunks.len() {
+            return Err(invalid_data("the scene has another grid extent"));
+        }
+        // Chunks edited back to how they were aren't written again
+        let mut changed = Vec::new();
+        for chunk in chunks {
+            let runs = encode_chunk(voxel_manager, chunk);
+            if runs != self.chunks[chunk] {
+                self.chunks[chunk] = runs;
+                changed.push(chunk);
+            }
+        }
+        if changed.is_empty() {
+            return Ok(false);
+        }
+
+        let mut frame = Vec::new();
+        write_palette(voxel_manager, &mut frame)?;
+        write_u32(&mut frame, changed.len() as u32)?;
+        for chunk in changed {
+            write_u32(&mut frame, chunk as u32)?;
+            write_runs(&self.chunks[chunk], &mut frame)?;
+        }
+        write_u32(&mut self.writer, time)?;
+        write_u32(&mut self.writer, frame.len() as u32)?;
+        self.writer.write_all(&frame)?;
+        self.writer.flush()?;
+        Ok(true)
+    }
+}
+
+struct Frame {
+    time: u32,
+    data: Vec<u8>,
+}
+
+/// A recorded session played back onto a scene
+pub struct Replay {
+    extent: usize,
+    frames: Vec<Frame>,
+    next: usize,
+}
+
+impl Replay {
+    /// Reads every complete frame, a frame cut off at the end of the file is left out
+    pub fn load(file_path: &str) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(file_path)?);
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("not a voxel-editor session recording"));
+        }
+        if read_u32(&mut reader)? != VERSION {
+            return Err(invalid_data("unsupported recording version"));
+        }
+        let extent = read_u32(&mut reader)? as usize;
+
+        let mut frames = Vec::new();
+        while let Ok(time) = read_u32(&mut reader) {
+            let len = match read_u32(&mut reader) {
+                Ok(len) => len as u64,
+                Err(_) => break,
+            };
+            let mut data = Vec::new();
+            (&mut reader).take(len).read_to_end(&mut data)?;
+            if data.len() as u64 != len {
+                break;
+            }
+            frames.push(Frame { time, data });
+        }
+        if frames.is_empty() {
+            return Err(invalid_data("the recording has no frames"));
+        }
+        Ok(Replay {
+            extent,
+            frames,
+            next: 0,
+        })
+    }
+
+    /// An empty scene of the recorded size to play the session onto
+    pub fn scene(&self) -> VoxelManager {
+        VoxelManager::new(self.extent)
+    }
+
+    /// Length of the session in milliseconds
+    pub fn duration(&self) -> u32 {
+        self.frames.last().map_or(0, |frame| frame.time)
+    }
+
+    pub fn finished(&self) -> bool {
+        self.next == self.frames.len()
+    }
+
+    /// Applies the frames recorded up to the time in milliseconds, returns whether any were
+    pub fn advance(&mut self, voxel_manager: &mut VoxelManager, time: u32) -> io::Result<bool> {
+        if voxel_manager.extent() != self.extent {
+            return Err(invalid_data("the scene has another grid extent"));
+        }
+        let start = self.next;
+        while let Some(frame) = self.frames.get(self.next) {
+            if frame.time > time {
+                break;
+            }
+            let mut data = frame.data.as_slice();
+            voxel_manager.set_palette(read_palette(&mut data)?);
+            for _ in 0..read_u32(&mut data)? {
+                let chunk = read_u32(&mut data)? as usize;
+                if chunk >= voxel_manager.chunk_count() {
+                    return Err(invalid_data("recorded chunk is outside the grid"));
+                }
+                read_chunk(voxel_manager, chunk, &mut data)?;
+            }
+            self.next += 1;
+        }
+        Ok(self.next > start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn replay_reaches_the_recorded_states_in_time() {
+        let file_path = std::env::temp_dir()
+            .join(format!("recording_test_{}.vxr", std::process::id()))
+            .to_string_lossy()
+            .into_owned();
+        let mut scene = VoxelManager::new(16);
+        let red = scene.palette_mut().index_of([1.0, 0.0, 0.0, 1.0]);
+        scene.set_voxel(1, 1, 1, Some(red));
+        let mut recorder = Recorder::create(&file_path, &scene).unwrap();
+        scene.clear_unrecorded_chunks();
+        assert!(!recorder.capture(&scene, 100).unwrap());
+        scene.set_voxel(1, 1, 1, None);
+        scene.set_voxel(12, 12, 12, Some(red));
+        assert_eq!(scene.unrecorded_chunks().len(), 2);
+        assert!(recorder.capture(&scene, 500).unwrap());
+        scene.clear_unrecorded_chunks();
+        drop(recorder);
+
+        let mut replay = Replay::load(&file_path).unwrap();
+        assert_eq!(replay.duration(), 500);
+        let mut played = replay.scene();
+        assert!(replay.advance(&mut played, 250).unwrap());
+        assert_eq!(played.voxel(1, 1, 1), Some(red));
+        assert!(!replay.finished());
+        assert!(replay.advance(&mut played, 500).unwrap());
+        assert_eq!(played.voxel(1, 1, 1), None);
+        assert_eq!(played.voxel(12, 12, 12), Some(red));
+        assert!(replay.finished());
+        std::fs::remove_file(&file_path).unwrap();
+    }
+}
@@ -0,0 +1,578 @@
+use crate::analysis::{self, FACE_OFFSETS};
+use crate::geometry::{BoundingBox, Frustum, Ray};
+use crate::instance::Instance;
+use crate::settings::Settings;
+use crate::transform::ObjectPivot;
+use crate::voxel_manager::VoxelManager;
+use cgmath::{Matrix4, Vector3};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// A set of grid cells the next operation works on
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Selection {
+    cells: HashSet<[usize; 3]>,
+}
+
+impl Selection {
+    pub fn from_cells(cells: Vec<[usize; 3]>) -> Self {
+        Selection {
+            cells: cells.into_iter().collect(),
+        }
+    }
+
+    pub fn cells(&self) -> Vec<[usize; 3]> {
+        self.cells.iter().cloned().collect()
+    }
+
+    pub fn contains(&self, cell: [usize; 3]) -> bool {
+        self.cells.contains(&cell)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /// The selected cells inside a grid of the extent
+    pub fn within(&self, extent: usize) -> Selection {
+        let cells = self
+            .cells
+            .iter()
+            .filter(|cell| cell.iter().all(|c| *c < extent))
+            .cloned()
+            .collect();
+        Selection { cells }
+    }
+
+    pub fn combine(&self, other: &Selection, combine: Combine) -> Selection {
+        let cells = match combine {
+            Combine::Replace => other.cells.clone(),
+            Combine::Union => self.cells.union(&other.cells).cloned().collect(),
+            Combine::Intersect => self.cells.intersection(&other.cells).cloned().collect(),
+            Combine::Subtract => self.cells.difference(&other.cells).cloned().collect(),
+        };
+        Selection { cells }
+    }
+}
+
+/// Written as space separated `x,y,z` cells in grid order
+impl fmt::Display for Selection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut cells = self.cells();
+        cells.sort_unstable();
+        let cells: Vec<String> = cells
+            .iter()
+            .map(|[x, y, z]| format!("{},{},{}", x, y, z))
+            .collect();
+        write!(f, "{}", cells.join(" "))
+    }
+}
+
+impl FromStr for Selection {
+    type Err = ();
+
+    fn from_str(text: &str) -> Result<Self, ()> {
+        let cells = text
+            .split_whitespace()
+            .map(|cell| {
+                let coords: Vec<usize> = cell
+                    .split(',')
+                    .map(|c| c.parse().map_err(|_| ()))
+                    .collect::<Result<_, ()>>()?;
+                match coords.as_slice() {
+                    &[x, y, z] => Ok([x, y, z]),
+                    _ => Err(()),
+                }
+            })
+            .collect::<Result<_, ()>>()?;
+        Ok(Selection { cells })
+    }
+}
+
+/// Part of the window a marquee encloses, in pixels from the top left corner
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScreenRegion {
+    /// Between two opposite corners
+    Rectangle([f32; 2], [f32; 2]),
+    /// Inside the closed freehand path
+    Lasso(Vec<[f32; 2]>),
+}
+
+impl ScreenRegion {
+    pub fn contains(&self, [x, y]: [f32; 2]) -> bool {
+        match self {
+            ScreenRegion::Rectangle(a, b) => {
+                x >= a[0].min(b[0])
+                    && x <= a[0].max(b[0])
+                    && y >= a[1].min(b[1])
+                    && y <= a[1].max(b[1])
+            }
+            ScreenRegion::Lasso(path) => {
+                // Even-odd rule, a ray to the right crosses the path an odd number of times
+                let mut inside = false;
+                for (i, a) in path.iter().enumerate() {
+                    let b = path[(i + 1) % path.len()];
+                    if (a[1] > y) != (b[1] > y)
+                        && x < a[0] + (y - a[1]) / (b[1] - a[1]) * (b[0] - a[0])
+                    {
+                        inside = !inside;
+                    }
+                }
+                inside
+            }
+        }
+    }
+}
+
+/// Whether a filled cell lies between the center of the cell and the point. Steps from cell to
+/// cell along the line, past the grid nothing blocks the view.
+fn occluded(voxel_manager: &VoxelManager, cell: [usize; 3], towards: Vector3<f32>) -> bool {
+    let extent = voxel_manager.extent() as i64;
+    let mut current = [cell[0] as i64, cell[1] as i64, cell[2] as i64];
+    let start = Vector3::new(
+        cell[0] as f32 + 0.5,
+        cell[1] as f32 + 0.5,
+        cell[2] as f32 + 0.5,
+    );
+    let direction = towards - start;
+    let mut step = [0; 3];
+    // Distance along the line to the next cell boundary on each axis and between boundaries
+    let mut next = [f32::INFINITY; 3];
+    let mut delta = [f32::INFINITY; 3];
+    for i in 0..3 {
+        if direction[i] > 0.0 {
+            step[i] = 1;
+            next[i] = 0.5 / direction[i];
+            delta[i] = 1.0 / direction[i];
+        } else if direction[i] < 0.0 {
+            step[i] = -1;
+            next[i] = -0.5 / direction[i];
+            delta[i] = -1.0 / direction[i];
+        }
+    }
+    loop {
+        let i = if next[0] <= next[1] && next[0] <= next[2] {
+            0
+        } else if next[1] <= next[2] {
+            1
+        } else {
+            2
+        };
+        if next[i] > 1.0 {
+            return false;
+        }
+        current[i] += step[i];
+        if current[i] < 0 || current[i] >= extent {
+            return false;
+        }
+        next[i] += delta[i];
+        let [x, y, z] = [
+            current[0] as usize,
+            current[1] as usize,
+            current[2] as usize,
+        ];
+        if voxel_manager.voxel(x, y, z).is_some() {
+            return true;
+        }
+    }
+}
+
+/// The filled cells seen in the region of the window, whose centers project into it and aren't
+/// hidden behind other voxels. The matrices are the camera's, as for Ray::unproject.
+pub fn cells_in_region(
+    voxel_manager: &VoxelManager,
+    region: &ScreenRegion,
+    model_view: Matrix4<f32>,
+    projection: Matrix4<f32>,
+    window_size: [u32; 2],
+) -> Vec<[usize; 3]> {
+    // Chunks out of view are skipped whole, the frustum also drops cells behind the camera,
+    // which the projection would mirror into the window
+    let frustum = Frustum::from_matrix(projection * model_view);
+    let mut found = Vec::new();
+    for chunk in 0..voxel_manager.chunk_count() {
+        let cells = voxel_manager.chunk_cells(chunk);
+        let (min, max) = match (cells.first(), cells.last()) {
+            (Some(min), Some(max)) => (*min, *max),
+            _ => continue,
+        };
+        let corner = Vector3::new(min[0] as f32, min[1] as f32, min[2] as f32);
+        let end =
+            Vector3::new(max[0] as f32, max[1] as f32, max[2] as f32) + Vector3::new(1.0, 1.0, 1.0);
+        if !frustum.intersects_box(&BoundingBox::new(corner, end - corner, [0.0; 4])) {
+            continue;
+        }
+        for cell in cells {
+            if voxel_manager.voxel(cell[0], cell[1], cell[2]).is_none() {
+                continue;
+            }
+            let center = Vector3::new(
+                cell[0] as f32 + 0.5,
+                cell[1] as f32 + 0.5,
+                cell[2] as f32 + 0.5,
+            );
+            if !frustum.contains_point(center) {
+                continue;
+            }
+            let screen = Ray::project(center, model_view, projection, window_size);
+            if !region.contains([screen.x, screen.y]) {
+                continue;
+            }
+            let ray = Ray::from_screen([screen.x, screen.y], model_view, projection, window_size);
+            if !occluded(voxel_manager, cell, ray.origin) {
+                found.push(cell);
+            }
+        }
+    }
+    found
+}
+
+/// The selected cells with a weight of 1 and the filled cells around them with weights fading
+/// smoothly to 0 at the radius, by their distance to the closest selected cell. Operations scale
+/// their effect by the weights for soft edges.
+pub fn soft_weights(
+    voxel_manager: &VoxelManager,
+    selection: &Selection,
+    radius: f32,
+) -> Vec<([usize; 3], f32)> {
+    let core = selection.cells();
+    let mut weights: Vec<([usize; 3], f32)> = core.iter().map(|cell| (*cell, 1.0)).collect();
+    if radius <= 0.0 {
+        return weights;
+    }
+    // Grows outwards from the selection through empty and filled cells alike, every reached cell
+    // keeps the selected cell it was reached from as its closest one. Cells at the radius stop
+    // the growth, so only the cells around the selection are visited.
+    let extent = voxel_manager.extent();
+    let mut closest: HashMap<[usize; 3], [usize; 3]> =
+        core.iter().map(|cell| (*cell, *cell)).collect();
+    let mut queue: VecDeque<[usize; 3]> = core.iter().cloned().collect();
+    while let Some(cell) = queue.pop_front() {
+        let selected = closest[&cell];
+        for offset in FACE_OFFSETS.iter() {
+            let next = match analysis::neighbour(extent, cell, *offset) {
+                Some(next) if !closest.contains_key(&next) => next,
+                _ => continue,
+            };
+            let distance2: f32 = (0..3)
+                .map(|i| (next[i] as f32 - selected[i] as f32).powi(2))
+                .sum();
+            let t = 1.0 - distance2.sqrt() / radius;
+            if t <= 0.0 {
+                continue;
+            }
+            closest.insert(next, selected);
+            queue.push_back(next);
+            if voxel_manager.voxel(next[0], next[1], next[2]).is_some() {
+                weights.push((next, t * t * (3.0 - 2.0 * t)));
+            }
+        }
+    }
+    weights
+}
+
+/// How a recalled mask is merged into the current selection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Combine {
+    Replace,
+    Union,
+    Intersect,
+    Subtract,
+}
+
+impl Combine {
+    pub const ALL: [Combine; 4] = [
+        Combine::Replace,
+        Combine::Union,
+        Combine::Intersect,
+        Combine::Subtract,
+    ];
+}
+
+impl Default for Combine {
+    fn default() -> Combine {
+        Combine::Replace
+    }
+}
+
+/// Selections saved under a name, kept in a sidecar file of the project. They are the named
+/// objects of the scene, can have a pivot to be turned around and exported at, and linked
+/// copies.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SavedMasks {
+    masks: BTreeMap<String, Selection>,
+    pivots: BTreeMap<String, ObjectPivot>,
+    instances: Vec<Instance>,
+}
+
+impl SavedMasks {
+    /// The sidecar file a project's masks are kept in
+    pub fn path(project_path: &str) -> PathBuf {
+        PathBuf::from(format!("{}.masks", project_path))
+    }
+
+    /// Reads the masks of the project, none when it has no sidecar file yet. Cells outside a
+    /// grid of the extent are dropped.
+    pub fn load(project_path: &str, extent: usize) -> Self {
+        let settings = match Settings::read(&SavedMasks::path(project_path)) {
+            Ok(settings) => settings,
+            Err(_) => return SavedMasks::default(),
+        };
+        let masks = settings
+            .keys()
+            .filter(|key| key.starts_with("mask."))
+            .filter_map(|key| {
+                let mask: Selection = settings.get(key)?;
+                Some((key["mask.".len()..].to_owned(), mask.within(extent)))
+            })
+            .collect();
+        let pivots = settings
+            .keys()
+            .filter(|key| key.starts_with("pivot."))
+            .filter_map(|key| {
+                let pivot: ObjectPivot = settings.get(key)?;
+                match pivot {
+                    ObjectPivot::Cell(cell) if cell.iter().any(|c| *c >= extent) => None,
+                    _ => Some((key["pivot.".len()..].to_owned(), pivot)),
+                }
+            })
+            .collect();
+        let mut instances: Vec<(usize, Instance)> = settings
+            .keys()
+            .filter(|key| key.starts_with("instance."))
+            .filter_map(|key| Some((key["instance.".len()..].parse().ok()?, settings.get(key)?)))
+            .collect();
+        instances.sort_by_key(|(n, _)| *n);
+        SavedMasks {
+            masks,
+            pivots,
+            instances: instances
+                .into_iter()
+                .map(|(_, instance)| instance)
+                .collect(),
+        }
+    }
+
+    pub fn save(&self, project_path: &str) -> io::Result<()> {
+        let mut settings = Settings::default();
+        for (name, selection) in self.masks.iter() {
+            settings.set(&format!("mask.{}", name), selection);
+        }
+        // Cells are written like a selection of the one cell
+        for (name, pivot) in self.pivots.iter() {
+            settings.set(&format!("pivot.{}", name), pivot);
+        }
+        for (n, instance) in self.instances.iter().enumerate() {
+            settings.set(&format!("instance.{}", n + 1), instance);
+        }
+        settings.write(&SavedMasks::path(project_path))
+    }
+
+    /// Saves the selection under the name, replacing the mask saved with it before.
+    /// Names have to fit into a `key = value` line.
+    pub fn insert(&mut self, name: &str, selection: Selection) -> bool {
+        let name = name.trim();
+        if name.is_empty() || name.contains('=') || name.contains('\n') {
+            return false;
+        }
+        self.masks.insert(name.to_owned(), selection);
+        true
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Selection> {
+        self.masks.get(name.trim())
+    }
+
+    /// Removes the mask with its pivot and instances, the voxels of the instances stay
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.pivots.remove(name.trim());
+        self.unlink(name);
+        self.masks.remove(name.trim()).is_some()
+    }
+
+    /// Adds a linked copy of a mask, false when there is no mask by its source name
+    pub fn add_instance(&mut self, instance: Instance) -> bool {
+        if !self.masks.contains_key(instance.source.trim()) {
+            return false;
+        }
+        self.instances.push(instance);
+        true
+    }
+
+    /// Drops the instances of the mask, their voxels become plain voxels of the scene.
+    /// Returns the number of dropped instances.
+    pub fn unlink(&mut self, name: &str) -> usize {
+        let count = self.instances.len();
+        self.instances
+            .retain(|instance| instance.source.trim() != name.trim());
+        count - self.instances.len()
+    }
+
+    pub fn instances(&self) -> &[Instance] {
+        &self.instances
+    }
+
+    /// Sets the pivot of the named mask, None clears it. False when there is no mask with the
+    /// name.
+    pub fn set_pivot(&mut self, name: &str, pivot: Option<ObjectPivot>) -> bool {
+        let name = name.trim();
+        if !self.masks.contains_key(name) {
+            return false;
+        }
+        match pivot {
+            Some(pivot) => self.pivots.insert(name.to_owned(), pivot),
+            None => self.pivots.remove(name),
+        };
+        true
+    }
+
+    pub fn pivot(&self, name: &str) -> Option<ObjectPivot> {
+        self.pivots.get(name.trim()).copied()
+    }
+
+    /// The cell the named mask turns around, masks with a pivot of their bounds turn around
+    /// their center
+    pub fn pivot_cell(&self, name: &str) -> Option<[usize; 3]> {
+        match self.pivot(name)? {
+            ObjectPivot::Cell(cell) => Some(cell),
+            ObjectPivot::Bounds(_) => None,
+        }
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.masks.keys().map(String::as_str)
+    }
+}
+
+/// Which cells the editing tools can change while masking with the selection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaskMode {
+    Off,
+    /// Only the selected cells
+    Inside,
+    /// Every cell but the selected ones, keeping finished parts safe
+    Outside,
+}
+
+impl MaskMode {
+    pub const ALL: [MaskMode; 3] = [MaskMode::Off, MaskMode::Inside, MaskMode::Outside];
+}
+
+impl Default for MaskMode {
+    fn default() -> MaskMode {
+        MaskMode::Off
+    }
+}
+
+/// Limits edits to the cells of a selection, or keeps edits away from them
+#[derive(Debug, Clone)]
+pub struct Mask {
+    selection: Selection,
+    inside: bool,
+}
+
+impl Mask {
+    /// The mask of the selection, None when the mode doesn't mask anything
+    pub fn new(selection: Selection, mode: MaskMode) -> Option<Self> {
+        match mode {
+            MaskMode::Off => None,
+            MaskMode::Inside => Some(Mask {
+                selection,
+                inside: true,
+            }),
+            MaskMode::Outside => Some(Mask {
+                selection,
+                inside: false,
+            }),
+        }
+    }
+
+    pub fn allows(&self, cell: [usize; 3]) -> bool {
+        self.selection.contains(cell) == self.inside
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn marquees_select_the_visible_voxels_inside() {
+        let mut scene = VoxelManager::new(8);
+        // A voxel left of the middle in front of another one, and one to the right
+        scene.set_voxel(2, 4, 6, Some(0));
+        scene.set_voxel(2, 4, 1, Some(0));
+        scene.set_voxel(6, 4, 4, Some(0));
+        let model_view = Matrix4::look_at(
+            cgmath::Point3::new(4.0, 4.5, 30.0),
+            cgmath::Point3::new(4.0, 4.5, 0.0),
+            Vector3::unit_y(),
+        );
+        let projection = cgmath::perspective(cgmath::Deg(45.0), 1.0, 0.1, 320.0);
+        let window_size = [400, 400];
+        let select = |region: &ScreenRegion| {
+            let mut cells = cells_in_region(&scene, region, model_view, projection, window_size);
+            cells.sort_unstable();
+            cells
+        };
+
+        let everything = ScreenRegion::Rectangle([0.0, 0.0], [400.0, 400.0]);
+        assert_eq!(select(&everything), vec![[2, 4, 6], [6, 4, 4]]);
+        let left = ScreenRegion::Lasso(vec![[0.0, 0.0], [190.0, 0.0], [190.0, 400.0]]);
+        assert_eq!(select(&left), vec![[2, 4, 6]]);
+        assert!(!left.contains([300.0, 300.0]));
+        let right = ScreenRegion::Rectangle([400.0, 100.0], [210.0, 300.0]);
+        assert_eq!(select(&right), vec![[6, 4, 4]]);
+    }
+
+    #[test]
+    fn masks_combine_and_roundtrip_through_text() {
+        let body = Selection::from_cells(vec![[0, 0, 0], [1, 0, 0], [2, 0, 0]]);
+        let arm = Selection::from_cells(vec![[2, 0, 0], [2, 1, 0]]);
+        let sorted = |selection: Selection| {
+            let mut cells = selection.cells();
+            cells.sort_unstable();
+            cells
+        };
+        assert_eq!(sorted(body.combine(&arm, Combine::Union)).len(), 4);
+        assert_eq!(
+            sorted(body.combine(&arm, Combine::Intersect)),
+            vec![[2, 0, 0]]
+        );
+        assert_eq!(
+            sorted(body.combine(&arm, Combine::Subtract)),
+            vec![[0, 0, 0], [1, 0, 0]]
+        );
+        // Masks read from files are cut to the grid
+        assert_eq!(sorted(body.within(2)), vec![[0, 0, 0], [1, 0, 0]]);
+
+        assert_eq!(arm.to_string(), "2,0,0 2,1,0");
+        assert_eq!(sorted("2,1,0 2,0,0".parse().unwrap()), sorted(arm));
+        assert!("2,1".parse::<Selection>().is_err());
+
+        let mut masks = SavedMasks::default();
+        assert!(!masks.insert("a = b", body.clone()));
+        assert!(masks.insert(" body ", body));
+        assert_eq!(masks.names().collect::<Vec<_>>(), vec!["body"]);
+        assert!(!masks.set_pivot("arm", Some(ObjectPivot::Cell([2, 0, 0]))));
+        assert!(masks.set_pivot("body", Some(ObjectPivot::Cell([1, 0, 0]))));
+        assert_eq!(masks.pivot_cell(" body"), Some([1, 0, 0]));
+        assert_eq!("1 0 0".parse(), Ok(ObjectPivot::Cell([1, 0, 0])));
+        assert_eq!(
+            "BottomCenter".parse::<ObjectPivot>().unwrap().to_string(),
+            "BottomCenter"
+        );
+        masks.set_pivot("body", "Center".parse().ok());
+        assert_eq!(masks.pivot_cell("body"), None);
+        assert!(masks.remove("body"));
+        assert_eq!(masks.pivot("body"), None);
+    }
+}
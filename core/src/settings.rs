@@ -0,0 +1,117 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+const FILE_NAME: &str = "settings.cfg";
+
+/// Editor settings persisted between sessions as `key = value` lines
+/// in the user's config directory
+#[derive(Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(transparent))]
+pub struct Settings {
+    values: BTreeMap<String, String>,
+}
+
+impl Settings {
+    /// Loads the settings, a missing or unreadable file gives the defaults
+    pub fn load() -> Self {
+        settings_path()
+            .and_then(|path| Settings::read(&path).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = settings_path()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no config directory"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        self.write(&path)
+    }
+
+    /// Reads `key = value` lines from any file, like the sidecar files of projects
+    pub fn read(path: &Path) -> io::Result<Self> {
+        Ok(Settings {
+            values: parse(&fs::read_to_string(path)?),
+        })
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, format(&self.values))
+    }
+
+    pub fn get<T: FromStr>(&self, key: &str) -> Option<T> {
+        self.values.get(key).and_then(|value| value.parse().ok())
+    }
+
+    pub fn set<T: ToString>(&mut self, key: &str, value: T) {
+        self.values.insert(key.to_owned(), value.to_string());
+    }
+
+    pub fn remove(&mut self, key: &str) {
+        self.values.remove(key);
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.values.keys().map(String::as_str)
+    }
+}
+
+fn config_dir() -> Option<PathBuf> {
+    let env_path = |name| std::env::var_os(name).map(PathBuf::from);
+    if cfg!(windows) {
+        env_path("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        env_path("HOME").map(|home| home.join("Library").join("Application Support"))
+    } else {
+        env_path("XDG_CONFIG_HOME").or_else(|| env_path("HOME").map(|home| home.join(".config")))
+    }
+}
+
+fn settings_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("voxel-editor").join(FILE_NAME))
+}
+
+fn parse(text: &str) -> BTreeMap<String, String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut parts = line.splitn(2, '=');
+            let key = parts.next()?.trim();
+            let value = parts.next()?.trim();
+            Some((key.to_owned(), value.to_owned()))
+        })
+        .collect()
+}
+
+fn format(values: &BTreeMap<String, String>) -> String {
+    values
+        .iter()
+        .map(|(key, value)| format!("{} = {}\n", key, value))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn settings_roundtrip() {
+        let mut settings = Settings::default();
+        settings.set("window.width", 1280);
+        settings.set("window.maximized", true);
+        let parsed = Settings {
+            values: parse(&format!("# comment\n\n{}", format(&settings.values))),
+        };
+        assert_eq!(parsed.get::<u32>("window.width"), Some(1280));
+        assert_eq!(parsed.get::<bool>("window.maximized"), Some(true));
+        assert_eq!(parsed.get::<u32>("window.maximized"), None);
+        assert_eq!(parsed.get::<i32>("window.x"), None);
+    }
+}
@@ -0,0 +1,278 @@
+use crate::analysis::{neighbour, FACE_OFFSETS};
+use crate::color::quantized;
+use crate::voxel_manager::VoxelManager;
+use cgmath::{InnerSpace, Vector3};
+
+fn scaled(color: [f32; 4], factor: f32) -> [f32; 4] {
+    [
+        quantized(color[0] * factor),
+        quantized(color[1] * factor),
+        quantized(color[2] * factor),
+        color[3],
+    ]
+}
+
+/// Hue in degrees, saturation and value of the color
+pub(crate) fn to_hsv([r, g, b, _]: [f32; 4]) -> [f32; 3] {
+    let max = r.max(g).max(b);
+    let range = max - r.min(g).min(b);
+    let hue = if range == 0.0 {
+        0.0
+    } else if max == r {
+        60.0 * ((g - b) / range).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / range + 2.0)
+    } else {
+        60.0 * ((r - g) / range + 4.0)
+    };
+    let saturation = if max == 0.0 { 0.0 } else { range / max };
+    [hue, saturation, max]
+}
+
+pub(crate) fn from_hsv([hue, saturation, value]: [f32; 3], alpha: f32) -> [f32; 4] {
+    let chroma = value * saturation;
+    let sector = hue.rem_euclid(360.0) / 60.0;
+    let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+    let [r, g, b] = match sector as usize {
+        0 => [chroma, x, 0.0],
+        1 => [x, chroma, 0.0],
+        2 => [0.0, chroma, x],
+        3 => [0.0, x, chroma],
+        4 => [x, 0.0, chroma],
+        _ => [chroma, 0.0, x],
+    };
+    let m = value - chroma;
+    [r + m, g + m, b + m, alpha]
+}
+
+/// Average normal of the faces of the cell that aren't covered by a neighbour, zero when the
+/// exposed faces cancel out
+fn exposed_normal(voxel_manager: &VoxelManager, cell: [usize; 3]) -> Vector3<f32> {
+    let extent = voxel_manager.extent();
+    let mut normal = Vector3::new(0.0, 0.0, 0.0);
+    for offset in FACE_OFFSETS.iter() {
+        let covered = neighbour(extent, cell, *offset)
+            .map_or(false, |[x, y, z]| voxel_manager.voxel(x, y, z).is_some());
+        if !covered {
+            normal += Vector3::new(offset[0] as f32, offset[1] as f32, offset[2] as f32);
+        }
+    }
+    if normal.magnitude2() > 0.0 {
+        normal.normalize()
+    } else {
+        normal
+    }
+}
+
+/// Bakes directional shading into the colors of the cells, brightening cells that face the
+/// light and darkening the ones facing away, by up to strength.
+pub fn shade_by_orientation(
+    voxel_manager: &mut VoxelManager,
+    cells: &[[usize; 3]],
+    light: Vector3<f32>,
+    strength: f32,
+) {
+    if light.magnitude2() == 0.0 {
+        return;
+    }
+    let light = light.normalize();
+    let shaded: Vec<([usize; 3], [f32; 4])> = cells
+        .iter()
+        .filter_map(|cell| {
+            let index = voxel_manager.voxel(cell[0], cell[1], cell[2])?;
+            let facing = exposed_normal(voxel_manager, *cell).dot(light);
+            let color = voxel_manager.palette().color(index);
+            Some((*cell, scaled(color, 1.0 + strength * facing)))
+        })
+        .collect();
+    for ([x, y, z], color) in shaded {
+        let index = voxel_manager.palette_mut().index_of(color);
+        voxel_manager.set_voxel(x, y, z, Some(index));
+    }
+}
+
+/// Shifts the hue by degrees and the saturation and value by the given amounts in the colors of
+/// the cells, each scaled by the weight of the cell as from selection::soft_weights.
+pub fn shift_hsv(
+    voxel_manager: &mut VoxelManager,
+    weighted_cells: &[([usize; 3], f32)],
+    shift: [f32; 3],
+) {
+    let shifted: Vec<([usize; 3], [f32; 4])> = weighted_cells
+        .iter()
+        .filter_map(|(cell, weight)| {
+            let index = voxel_manager.voxel(cell[0], cell[1], cell[2])?;
+            let color = voxel_manager.palette().color(index);
+            let [hue, saturation, value] = to_hsv(color);
+            let [r, g, b, a] = from_hsv(
+                [
+                    hue + shift[0] * weight,
+                    (saturation + shift[1] * weight).max(0.0).min(1.0),
+                    (value + shift[2] * weight).max(0.0).min(1.0),
+                ],
+                color[3],
+            );
+            Some((*cell, [quantized(r), quantized(g), quantized(b), a]))
+        })
+        .collect();
+    for ([x, y, z], color) in shifted {
+        let index = voxel_manager.palette_mut().index_of(color);
+        voxel_manager.set_voxel(x, y, z, Some(index));
+    }
+}
+
+/// Share of the cells around the open side of the exposed faces that are filled,
+/// None for cells without exposed faces
+fn occlusion(voxel_manager: &VoxelManager, cell: [usize; 3]) -> Option<f32> {
+    let extent = voxel_manager.extent();
+    let filled = |cell: Option<[usize; 3]>| {
+        cell.map_or(false, |[x, y, z]| voxel_manager.voxel(x, y, z).is_some())
+    };
+    let mut total = 0.0;
+    let mut faces = 0;
+    for offset in FACE_OFFSETS.iter() {
+        if filled(neighbour(extent, cell, *offset)) {
+            continue;
+        }
+        // The ring of cells around the empty cell in front of the face
+        let axis = offset.iter().position(|o| *o != 0).unwrap();
+        let mut covered = 0;
+        for du in -1..=1 {
+            for dv in -1..=1 {
+                if du == 0 && dv == 0 {
+                    continue;
+                }
+                let mut around = *offset;
+                around[(axis + 1) % 3] = du;
+                around[(axis + 2) % 3] = dv;
+                if filled(neighbour(extent, cell, around)) {
+                    covered += 1;
+                }
+            }
+        }
+        total += covered as f32 / 8.0;
+        faces += 1;
+    }
+    if faces > 0 {
+        Some(total / faces as f32)
+    } else {
+        None
+    }
+}
+
+/// A copy of the scene with the colors of the cells darkened by their ambient occlusion, by up
+/// to strength. The scene itself is left as it is, so the bake can be compared with it and
+/// thrown away.
+pub fn bake_ambient_occlusion(
+    voxel_manager: &VoxelManager,
+    cells: &[[usize; 3]],
+    strength: f32,
+) -> VoxelManager {
+    let mut baked = voxel_manager.clone();
+    for cell in cells {
+        let [x, y, z] = *cell;
+        let index = match voxel_manager.voxel(x, y, z) {
+            Some(index) => index,
+            None => continue,
+        };
+        if let Some(occlusion) = occlusion(voxel_manager, *cell) {
+            let color = scaled(
+                voxel_manager.palette().color(index),
+                1.0 - strength * occlusion,
+            );
+            let baked_index = baked.palette_mut().index_of(color);
+            baked.set_voxel(x, y, z, Some(baked_index));
+        }
+    }
+    baked
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::selection::{soft_weights, Selection};
+
+    #[test]
+    fn faces_towards_the_light_get_brighter() {
+        let mut voxel_manager = VoxelManager::new(8);
+        let gray = voxel_manager.palette_mut().index_of([0.5, 0.5, 0.5, 1.0]);
+        let mut cells = Vec::new();
+        for x in 0..3 {
+            for y in 0..2 {
+                for z in 0..3 {
+                    voxel_manager.set_voxel(x, y, z, Some(gray));
+                    cells.push([x, y, z]);
+                }
+            }
+        }
+        shade_by_orientation(&mut voxel_manager, &cells, Vector3::unit_y(), 0.5);
+
+        let color = |x, y, z| {
+            let index = voxel_manager.voxel(x, y, z).unwrap();
+            voxel_manager.palette().color(index)
+        };
+        assert_eq!(color(1, 1, 1), [0.75, 0.75, 0.75, 1.0]);
+        assert_eq!(color(1, 0, 1), [0.25, 0.25, 0.25, 1.0]);
+    }
+
+    #[test]
+    fn corners_get_darker_in_a_copy_of_the_scene() {
+        let mut voxel_manager = VoxelManager::new(8);
+        let white = voxel_manager.palette_mut().index_of([1.0; 4]);
+        let mut cells = Vec::new();
+        // A floor with a wall along its x = 0 edge
+        for x in 0..5 {
+            for z in 0..5 {
+                voxel_manager.set_voxel(x, 0, z, Some(white));
+                cells.push([x, 0, z]);
+            }
+        }
+        for z in 0..5 {
+            voxel_manager.set_voxel(0, 1, z, Some(white));
+            cells.push([0, 1, z]);
+        }
+        let baked = bake_ambient_occlusion(&voxel_manager, &cells, 1.0);
+
+        let brightness = |voxel_manager: &VoxelManager, x, y, z| {
+            let index = voxel_manager.voxel(x, y, z).unwrap();
+            voxel_manager.palette().color(index)[0]
+        };
+        assert!(brightness(&baked, 1, 0, 2) < brightness(&baked, 3, 0, 2));
+        assert!(brightness(&baked, 0, 1, 2) < 1.0);
+        assert!(cells
+            .iter()
+            .all(|[x, y, z]| voxel_manager.voxel(*x, *y, *z) == Some(white)));
+    }
+
+    #[test]
+    fn soft_hsv_shifts_fade_out_around_the_selection() {
+        let mut voxel_manager = VoxelManager::new(8);
+        let red = voxel_manager.palette_mut().index_of([1.0, 0.0, 0.0, 1.0]);
+        for x in 0..6 {
+            voxel_manager.set_voxel(x, 0, 0, Some(red));
+        }
+        let selection = Selection::from_cells(vec![[0, 0, 0]]);
+        let weights = soft_weights(&voxel_manager, &selection, 4.0);
+        let weight = |x| {
+            weights
+                .iter()
+                .find(|(cell, _)| *cell == [x, 0, 0])
+                .map(|w| w.1)
+        };
+        assert_eq!(weight(0), Some(1.0));
+        assert!(weight(1).unwrap() > weight(2).unwrap());
+        assert_eq!(weight(4), None);
+
+        shift_hsv(&mut voxel_manager, &weights, [120.0, 0.0, 0.0]);
+        let color = |x| {
+            let index = voxel_manager.voxel(x, 0, 0).unwrap();
+            voxel_manager.palette().color(index)
+        };
+        assert_eq!(color(0), [0.0, 1.0, 0.0, 1.0]);
+        // Part of the way from red towards green
+        assert!(color(1)[0] < color(2)[0]);
+        assert!(color(3)[1] > 0.0 && color(3)[1] < 1.0);
+        assert_eq!(color(5), [1.0, 0.0, 0.0, 1.0]);
+    }
+}
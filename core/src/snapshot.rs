@@ -0,0 +1,186 @@
+use crate::project::{self, invalid_data, read_bytes, write_bytes};
+use crate::voxel_manager::VoxelManager;
+#[cfg(feature = "serde")]
+use serde::{ser::SerializeMap, Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+
+/// Cells that differ between a snapshot and the scene
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SnapshotDiff {
+    /// Filled in the scene but empty in the snapshot
+    pub added: Vec<[usize; 3]>,
+    /// Filled in the snapshot but empty in the scene
+    pub removed: Vec<[usize; 3]>,
+    /// Filled in both with different colors
+    pub recolored: Vec<[usize; 3]>,
+}
+
+impl SnapshotDiff {
+    /// Compares the colors instead of the palette indices, the palettes may be ordered differently
+    pub fn between(snapshot: &VoxelManager, scene: &VoxelManager) -> io::Result<Self> {
+        if snapshot.extent() != scene.extent() {
+            return Err(invalid_data("the snapshot has another grid extent"));
+        }
+        let mut diff = SnapshotDiff::default();
+        let extent = scene.extent();
+        for x in 0..extent {
+            for y in 0..extent {
+                for z in 0..extent {
+                    let before = snapshot
+                        .voxel(x, y, z)
+                        .map(|index| snapshot.palette().color(index));
+                    let after = scene
+                        .voxel(x, y, z)
+                        .map(|index| scene.palette().color(index));
+                    match (before, after) {
+                        (None, Some(_)) => diff.added.push([x, y, z]),
+                        (Some(_), None) => diff.removed.push([x, y, z]),
+                        (Some(before), Some(after)) if before != after => {
+                            diff.recolored.push([x, y, z])
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+        Ok(diff)
+    }
+
+    pub fn cells(&self) -> Vec<[usize; 3]> {
+        self.added
+            .iter()
+            .chain(self.removed.iter())
+            .chain(self.recolored.iter())
+            .cloned()
+            .collect()
+    }
+}
+
+/// Named states of the scene saved inside the project file. Serialized as a map of the names
+/// to the scenes.
+#[derive(Debug, Clone, Default)]
+pub struct Snapshots {
+    // Scenes in the project format, without snapshots of their own
+    snapshots: BTreeMap<String, Vec<u8>>,
+}
+
+impl Snapshots {
+    /// Keeps the scene under the name, replacing an older snapshot of the same name.
+    /// Returns false for empty names.
+    pub fn insert(&mut self, name: &str, voxel_manager: &VoxelManager) -> bool {
+        let name = name.trim();
+        if name.is_empty() {
+            return false;
+        }
+        let mut state = Vec::new();
+        project::write_project(voxel_manager, &mut state, &mut |_| true)
+            .expect("writing to memory can't fail");
+        self.snapshots.insert(name.to_owned(), state);
+        true
+    }
+
+    /// The scene of the snapshot, None when there is no snapshot of the name
+    pub fn restore(&self, name: &str) -> Option<io::Result<VoxelManager>> {
+        let state = self.snapshots.get(name.trim())?;
+        Some(project::read_project(&mut state.as_slice(), &mut |_| true))
+    }
+
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.snapshots.remove(name.trim()).is_some()
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.snapshots.keys().map(String::as_str)
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&(self.snapshots.len() as u32).to_le_bytes())?;
+        for (name, state) in self.snapshots.iter() {
+            write_bytes(writer, name.as_bytes())?;
+            write_bytes(writer, state)?;
+        }
+        Ok(())
+    }
+
+    pub fn read<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let mut count = [0; 4];
+        reader.read_exact(&mut count)?;
+        let mut snapshots = BTreeMap::new();
+        for _ in 0..u32::from_le_bytes(count) {
+            let name = String::from_utf8(read_bytes(reader)?)
+                .map_err(|_| invalid_data("snapshot name isn't UTF-8"))?;
+            snapshots.insert(name, read_bytes(reader)?);
+        }
+        Ok(Snapshots { snapshots })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for Snapshots {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::Error;
+        let mut map = serializer.serialize_map(Some(self.snapshots.len()))?;
+        for name in self.names() {
+            let scene = self.restore(name).expect("the name is a snapshot");
+            map.serialize_entry(name, &scene.map_err(S::Error::custom)?)?;
+        }
+        map.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Snapshots {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let scenes = BTreeMap::<String, VoxelManager>::deserialize(deserializer)?;
+        let mut snapshots = Snapshots::default();
+        for (name, scene) in scenes.iter() {
+            snapshots.insert(name, scene);
+        }
+        Ok(snapshots)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn snapshots_are_saved_with_the_project() {
+        let mut scene = VoxelManager::new(16);
+        let red = scene.palette_mut().index_of([1.0, 0.0, 0.0, 1.0]);
+        scene.set_voxel(1, 1, 1, Some(red));
+        scene.set_voxel(2, 2, 2, Some(red));
+        let mut snapshots = Snapshots::default();
+        assert!(snapshots.insert(" before roof redesign ", &scene));
+        assert!(!snapshots.insert("  ", &scene));
+
+        let blue = scene.palette_mut().index_of([0.0, 0.0, 1.0, 1.0]);
+        scene.set_voxel(1, 1, 1, None);
+        scene.set_voxel(2, 2, 2, Some(blue));
+        scene.set_voxel(3, 3, 3, Some(red));
+
+        let mut bytes = Vec::new();
+        let extras = project::ProjectExtras {
+            snapshots,
+            ..Default::default()
+        };
+        project::write_project_with_extras(&scene, &extras, &mut bytes, &mut |_| true).unwrap();
+        let (loaded, extras) =
+            project::read_project_with_extras(&mut bytes.as_slice(), &mut |_| true).unwrap();
+        let snapshots = extras.snapshots;
+        assert_eq!(
+            snapshots.names().collect::<Vec<_>>(),
+            vec!["before roof redesign"]
+        );
+
+        let before = snapshots.restore("before roof redesign").unwrap().unwrap();
+        assert_eq!(before.voxel(1, 1, 1), Some(red));
+        let diff = SnapshotDiff::between(&before, &loaded).unwrap();
+        assert_eq!(diff.added, vec![[3, 3, 3]]);
+        assert_eq!(diff.removed, vec![[1, 1, 1]]);
+        assert_eq!(diff.recolored, vec![[2, 2, 2]]);
+    }
+}
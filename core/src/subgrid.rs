@@ -0,0 +1,234 @@
+use crate::project::{self, invalid_data, read_bytes, read_u32, write_bytes, write_u32};
+use crate::voxel_manager::VoxelManager;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Read, Write};
+
+/// A finer grid embedded in a cube of cells of the scene, for detailed props in a coarse
+/// environment. The sub-grid has the extent of the scene and covers `extent / density` cells
+/// of it along each axis.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SubGrid {
+    /// The cell of the scene its grid starts at
+    pub corner: [usize; 3],
+    /// Cells of the sub-grid along each axis of a cell of the scene
+    pub density: usize,
+    pub scene: VoxelManager,
+}
+
+impl fmt::Debug for SubGrid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SubGrid")
+            .field("corner", &self.corner)
+            .field("density", &self.density)
+            .finish()
+    }
+}
+
+impl SubGrid {
+    /// Moves the voxels of the cube of cells starting at the corner into a new sub-grid, every
+    /// voxel filling density cells along each axis. None when the density doesn't divide the
+    /// extent of the scene or the cube doesn't fit into it.
+    pub fn refine(parent: &mut VoxelManager, corner: [usize; 3], density: usize) -> Option<Self> {
+        let extent = parent.extent();
+        if density < 2 || extent % density != 0 {
+            return None;
+        }
+        let cells = extent / density;
+        if corner.iter().any(|c| c + cells > extent) {
+            return None;
+        }
+        let mut scene = VoxelManager::new(extent);
+        scene.set_palette(parent.palette().clone());
+        for [x, y, z] in cube(corner, cells) {
+            let value = match parent.voxel(x, y, z) {
+                Some(value) => value,
+                None => continue,
+            };
+            let fine = [x - corner[0], y - corner[1], z - corner[2]];
+            for [fx, fy, fz] in cube(
+                [fine[0] * density, fine[1] * density, fine[2] * density],
+                density,
+            ) {
+                scene.set_voxel(fx, fy, fz, Some(value));
+            }
+            parent.set_voxel(x, y, z, None);
+        }
+        Some(SubGrid {
+            corner,
+            density,
+            scene,
+        })
+    }
+
+    /// Cells of the scene it covers along each axis
+    pub fn cells(&self) -> usize {
+        self.scene.extent() / self.density
+    }
+
+    pub fn covers(&self, cell: [usize; 3]) -> bool {
+        (0..3).all(|i| cell[i] >= self.corner[i] && cell[i] < self.corner[i] + self.cells())
+    }
+
+    /// Whether it shares a cell with the cube of cells starting at the corner
+    pub fn overlaps(&self, corner: [usize; 3], cells: usize) -> bool {
+        (0..3).all(|i| {
+            corner[i] < self.corner[i] + self.cells() && self.corner[i] < corner[i] + cells
+        })
+    }
+
+    /// The sub-grid's scene with the meshes in place among the meshes of a scene whose grid
+    /// corner is at grid_corner and whose voxels have the given size
+    pub fn placed(&self, grid_corner: [f32; 3], voxel_scale: [f32; 3]) -> VoxelManager {
+        let mut placed = self.scene.clone();
+        let mut scale = [1.0; 3];
+        let mut origin = [0.0; 3];
+        for i in 0..3 {
+            scale[i] = voxel_scale[i] / self.density as f32;
+            origin[i] = grid_corner[i] + self.corner[i] as f32 * voxel_scale[i];
+        }
+        placed.set_voxel_scale(scale);
+        placed.set_origin(origin);
+        placed
+    }
+
+    /// Puts the voxels back into the cells of the parent it covers. A cell is filled with the
+    /// most common color when at least half of its finer cells are filled.
+    pub fn flatten(&self, parent: &mut VoxelManager) {
+        let density = self.density;
+        for [x, y, z] in cube(self.corner, self.cells()) {
+            let fine = [
+                (x - self.corner[0]) * density,
+                (y - self.corner[1]) * density,
+                (z - self.corner[2]) * density,
+            ];
+            let mut counts: BTreeMap<u8, usize> = BTreeMap::new();
+            for [fx, fy, fz] in cube(fine, density) {
+                if let Some(value) = self.scene.voxel(fx, fy, fz) {
+                    *counts.entry(value).or_insert(0) += 1;
+                }
+            }
+            let filled: usize = counts.values().sum();
+            let value = if filled * 2 >= density * density * density {
+                // The lowest index wins ties
+                let (value, _) = counts.iter().fold((0, 0), |best, (value, count)| {
+                    if *count > best.1 {
+                        (*value, *count)
+                    } else {
+                        best
+                    }
+                });
+                let color = self.scene.palette().color(value);
+                Some(parent.palette_mut().index_of(color))
+            } else {
+                None
+            };
+            parent.set_voxel(x, y, z, value);
+        }
+    }
+}
+
+/// The index of the sub-grid covering the cell of the scene
+pub fn find(sub_grids: &[SubGrid], cell: [usize; 3]) -> Option<usize> {
+    sub_grids.iter().position(|sub_grid| sub_grid.covers(cell))
+}
+
+/// The cells of the cube from the corner spanning size cells along each axis
+fn cube(corner: [usize; 3], size: usize) -> impl Iterator<Item = [usize; 3]> {
+    (0..size).flat_map(move |x| {
+        (0..size).flat_map(move |y| {
+            (0..size).map(move |z| [corner[0] + x, corner[1] + y, corner[2] + z])
+        })
+    })
+}
+
+pub fn write_sub_grids<W: Write>(sub_grids: &[SubGrid], writer: &mut W) -> io::Result<()> {
+    write_u32(writer, sub_grids.len() as u32)?;
+    for sub_grid in sub_grids.iter() {
+        for c in sub_grid.corner.iter() {
+            write_u32(writer, *c as u32)?;
+        }
+        write_u32(writer, sub_grid.density as u32)?;
+        let mut bytes = Vec::new();
+        project::write_project(&sub_grid.scene, &mut bytes, &mut |_| true)?;
+        write_bytes(writer, &bytes)?;
+    }
+    Ok(())
+}
+
+/// Reads the sub-grids of a scene of the given extent
+pub fn read_sub_grids<R: Read>(reader: &mut R, extent: usize) -> io::Result<Vec<SubGrid>> {
+    let mut sub_grids = Vec::new();
+    for _ in 0..read_u32(reader)? {
+        let mut corner = [0; 3];
+        for c in corner.iter_mut() {
+            *c = read_u32(reader)? as usize;
+        }
+        let density = read_u32(reader)? as usize;
+        let bytes = read_bytes(reader)?;
+        let scene = project::read_project(&mut bytes.as_slice(), &mut |_| true)?;
+        if density < 2 || scene.extent() != extent || extent % density != 0 {
+            return Err(invalid_data("sub-grid doesn't match the scene"));
+        }
+        if corner.iter().any(|c| c + extent / density > extent) {
+            return Err(invalid_data("sub-grid is outside of the scene"));
+        }
+        sub_grids.push(SubGrid {
+            corner,
+            density,
+            scene,
+        });
+    }
+    Ok(sub_grids)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::project::ProjectExtras;
+
+    #[test]
+    fn refined_cells_are_saved_and_flatten_back() {
+        let mut scene = VoxelManager::new(8);
+        let red = scene.palette_mut().index_of([1.0, 0.0, 0.0, 1.0]);
+        scene.set_voxel(2, 2, 2, Some(red));
+        scene.set_voxel(7, 7, 7, Some(red));
+        assert!(SubGrid::refine(&mut scene, [7, 0, 0], 4).is_none());
+        assert!(SubGrid::refine(&mut scene, [2, 2, 2], 3).is_none());
+
+        let mut sub_grid = SubGrid::refine(&mut scene, [2, 2, 2], 4).unwrap();
+        assert_eq!(sub_grid.cells(), 2);
+        assert_eq!(scene.voxel(2, 2, 2), None);
+        assert_eq!(sub_grid.scene.voxel(3, 3, 3), Some(red));
+        assert_eq!(sub_grid.scene.voxel(4, 0, 0), None);
+        assert!(sub_grid.covers([3, 3, 3]) && !sub_grid.covers([4, 2, 2]));
+        assert!(sub_grid.overlaps([3, 0, 0], 4) && !sub_grid.overlaps([4, 0, 0], 4));
+
+        let placed = sub_grid.placed([0.0; 3], [1.0, 2.0, 1.0]);
+        assert_eq!(placed.voxel_scale(), [0.25, 0.5, 0.25]);
+        assert_eq!(placed.origin(), [2.0, 4.0, 2.0]);
+
+        // Detail carved into the finer cells doesn't fill the coarse cell it is in
+        sub_grid.scene.set_voxel(4, 4, 4, Some(red));
+        for x in 0..4 {
+            sub_grid.scene.set_voxel(x, 0, 0, None);
+        }
+        let mut extras = ProjectExtras::default();
+        extras.sub_grids.push(sub_grid);
+        let mut bytes = Vec::new();
+        project::write_project_with_extras(&scene, &extras, &mut bytes, &mut |_| true).unwrap();
+        let (mut loaded, extras) =
+            project::read_project_with_extras(&mut bytes.as_slice(), &mut |_| true).unwrap();
+        assert_eq!(extras.sub_grids.len(), 1);
+        assert_eq!(extras.sub_grids[0].corner, [2, 2, 2]);
+
+        extras.sub_grids[0].flatten(&mut loaded);
+        assert_eq!(loaded.voxel(2, 2, 2), Some(red));
+        assert_eq!(loaded.voxel(3, 3, 3), None);
+        assert_eq!(loaded.voxel(7, 7, 7), Some(red));
+    }
+}
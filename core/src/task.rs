@@ -0,0 +1,95 @@
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+
+/// Called by long running operations with the finished fraction of their work,
+/// returns false once the operation should stop
+pub type Progress<'a> = dyn FnMut(f32) -> bool + 'a;
+
+// Smallest progress step forwarded to the UI, so tight loops don't flood the channel
+const PROGRESS_STEP: f32 = 0.01;
+
+/// The error an operation returns when it stops because it was cancelled
+pub fn cancelled() -> io::Error {
+    io::Error::new(io::ErrorKind::Interrupted, "cancelled")
+}
+
+pub enum TaskEvent<T> {
+    Progress(f32),
+    Finished(io::Result<T>),
+}
+
+/// A long running operation executed on a worker thread
+pub struct Task<T> {
+    name: &'static str,
+    events: Receiver<TaskEvent<T>>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl<T: Send + 'static> Task<T> {
+    pub fn spawn<F>(name: &'static str, work: F) -> Self
+    where
+        F: FnOnce(&mut Progress) -> io::Result<T> + Send + 'static,
+    {
+        let (sender, events) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let cancel_flag = cancel.clone();
+        thread::spawn(move || {
+            let progress_sender = sender.clone();
+            let mut reported = 0.0;
+            let mut progress = |fraction: f32| {
+                if fraction - reported >= PROGRESS_STEP {
+                    reported = fraction;
+                    let _ = progress_sender.send(TaskEvent::Progress(fraction));
+                }
+                !cancel_flag.load(Ordering::Relaxed)
+            };
+            let result = work(&mut progress);
+            let _ = sender.send(TaskEvent::Finished(result));
+        });
+        Task {
+            name,
+            events,
+            cancel,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns the events sent by the worker since the last poll
+    pub fn poll(&self) -> Vec<TaskEvent<T>> {
+        self.events.try_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn cancelled_task_finishes_with_interrupted() {
+        let task = Task::spawn("test", |progress| {
+            let mut step = 0;
+            while progress(step as f32 / 1000.0) {
+                step += 1;
+            }
+            Err::<(), _>(cancelled())
+        });
+        task.cancel();
+        let result = loop {
+            if let Ok(TaskEvent::Finished(result)) = task.events.recv() {
+                break result;
+            }
+        };
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::Interrupted);
+    }
+}
@@ -0,0 +1,113 @@
+use crate::color::*;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Colors of the viewport helpers: the axes, the grid and the edit cursor
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Theme {
+    /// Colors of the x, y and z axes
+    pub axes: [[f32; 4]; 3],
+    pub grid: [f32; 4],
+    /// Highlight of the voxels about to be erased
+    pub erase_highlight: [f32; 4],
+    pub selection: [f32; 4],
+    /// Opacity of the preview voxel shown in the draw color
+    pub ghost_alpha: f32,
+}
+
+impl Theme {
+    /// Translucent version of a draw color for previewing placement
+    pub fn ghost(&self, color: [f32; 4]) -> [f32; 4] {
+        [color[0], color[1], color[2], self.ghost_alpha]
+    }
+}
+
+/// The selectable themes. The color-blind safe presets use the Okabe-Ito colors,
+/// which stay distinguishable with the common kinds of color vision deficiency.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ThemeKind {
+    Default,
+    RedGreenSafe,
+    BlueYellowSafe,
+    HighContrast,
+}
+
+impl ThemeKind {
+    pub const ALL: [ThemeKind; 4] = [
+        ThemeKind::Default,
+        ThemeKind::RedGreenSafe,
+        ThemeKind::BlueYellowSafe,
+        ThemeKind::HighContrast,
+    ];
+
+    pub fn theme(self) -> Theme {
+        match self {
+            ThemeKind::Default => Theme {
+                axes: [RED, GREEN, BLUE],
+                grid: WHITE,
+                erase_highlight: HALF_ALPHA_RED,
+                selection: [1.0, 0.85, 0.0, 0.35],
+                ghost_alpha: 0.4,
+            },
+            // Vermillion, yellow and blue don't get confused with protanopia and deuteranopia
+            ThemeKind::RedGreenSafe => Theme {
+                axes: [
+                    [0.84, 0.37, 0.0, 1.0],
+                    [0.94, 0.89, 0.26, 1.0],
+                    [0.0, 0.45, 0.7, 1.0],
+                ],
+                grid: [0.7, 0.7, 0.7, 1.0],
+                erase_highlight: [0.8, 0.47, 0.65, 0.4],
+                selection: [0.34, 0.71, 0.91, 0.4],
+                ghost_alpha: 0.4,
+            },
+            // Vermillion, bluish green and reddish purple stay apart with tritanopia
+            ThemeKind::BlueYellowSafe => Theme {
+                axes: [
+                    [0.84, 0.37, 0.0, 1.0],
+                    [0.0, 0.62, 0.45, 1.0],
+                    [0.8, 0.47, 0.65, 1.0],
+                ],
+                grid: [0.7, 0.7, 0.7, 1.0],
+                erase_highlight: [0.84, 0.37, 0.0, 0.4],
+                selection: [0.94, 0.89, 0.26, 0.4],
+                ghost_alpha: 0.4,
+            },
+            // Tells the cursor apart by brightness alone
+            ThemeKind::HighContrast => Theme {
+                axes: [RED, [0.0, 1.0, 1.0, 1.0], [1.0, 1.0, 0.0, 1.0]],
+                grid: [0.5, 0.5, 0.5, 1.0],
+                erase_highlight: [1.0, 1.0, 1.0, 0.6],
+                selection: [1.0, 0.0, 1.0, 0.5],
+                ghost_alpha: 0.7,
+            },
+        }
+    }
+}
+
+impl Default for ThemeKind {
+    fn default() -> ThemeKind {
+        ThemeKind::Default
+    }
+}
+
+impl std::fmt::Display for ThemeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl FromStr for ThemeKind {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Self, ()> {
+        ThemeKind::ALL
+            .iter()
+            .find(|kind| kind.to_string() == name)
+            .copied()
+            .ok_or(())
+    }
+}
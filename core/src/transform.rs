@@ -0,0 +1,803 @@
+use crate::analysis::neighbour;
+use crate::voxel_manager::VoxelManager;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::f32::consts::PI;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+impl Axis {
+    pub const ALL: [Axis; 3] = [Axis::X, Axis::Y, Axis::Z];
+
+    pub fn index(self) -> usize {
+        match self {
+            Axis::X => 0,
+            Axis::Y => 1,
+            Axis::Z => 2,
+        }
+    }
+
+    /// The right and up axes of the plane with this normal, as seen from the camera's side
+    pub fn plane_axes(self) -> (Axis, Axis) {
+        match self {
+            Axis::X => (Axis::Z, Axis::Y),
+            Axis::Y => (Axis::X, Axis::Z),
+            Axis::Z => (Axis::X, Axis::Y),
+        }
+    }
+}
+
+impl Default for Axis {
+    fn default() -> Axis {
+        Axis::X
+    }
+}
+
+/// Point of the bounds of some voxels that is placed at the origin, as engines expect it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Pivot {
+    /// The lowest corner
+    Corner,
+    Center,
+    /// The center of the bottom face
+    BottomCenter,
+}
+
+impl Pivot {
+    pub const ALL: [Pivot; 3] = [Pivot::Corner, Pivot::Center, Pivot::BottomCenter];
+
+    /// The pivot of the bounds from the lowest to the highest of the cells
+    pub fn point(self, min: [usize; 3], max: [usize; 3]) -> [f32; 3] {
+        let mut point = [0.0; 3];
+        for i in 0..3 {
+            point[i] = match (self, i) {
+                (Pivot::Corner, _) | (Pivot::BottomCenter, 1) => min[i] as f32,
+                _ => (min[i] + max[i] + 1) as f32 / 2.0,
+            };
+        }
+        point
+    }
+
+    /// Where the pivot goes in a grid: its corner, center or the center of its floor. The
+    /// editor's grid starts at the origin, so this is the origin of a scene standing on it.
+    pub fn grid_point(self, extent: usize) -> [f32; 3] {
+        self.point([0; 3], [extent.saturating_sub(1); 3])
+    }
+}
+
+impl fmt::Display for Pivot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl FromStr for Pivot {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Self, ()> {
+        Pivot::ALL
+            .iter()
+            .find(|pivot| pivot.to_string() == name)
+            .copied()
+            .ok_or(())
+    }
+}
+
+/// The pivot of a saved mask: a cell of the grid, which rotations and radial arrays of the mask
+/// turn around, or a point of the bounds of its voxels. Exports place the mask's node there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ObjectPivot {
+    Cell([usize; 3]),
+    Bounds(Pivot),
+}
+
+impl ObjectPivot {
+    /// The pivot in the grid for the cells of the object, the center of a pivot cell. None for
+    /// the bounds of no cells.
+    pub fn point(self, cells: &[[usize; 3]]) -> Option<[f32; 3]> {
+        match self {
+            ObjectPivot::Cell([x, y, z]) => Some([x as f32 + 0.5, y as f32 + 0.5, z as f32 + 0.5]),
+            ObjectPivot::Bounds(pivot) => {
+                let (min, max) = bounds(cells)?;
+                Some(pivot.point(min, max))
+            }
+        }
+    }
+}
+
+/// Written as the name of the point of the bounds, or as `x,y,z` for a cell
+impl fmt::Display for ObjectPivot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjectPivot::Cell([x, y, z]) => write!(f, "{},{},{}", x, y, z),
+            ObjectPivot::Bounds(pivot) => write!(f, "{}", pivot),
+        }
+    }
+}
+
+/// Also reads cells with their coordinates separated by spaces
+impl FromStr for ObjectPivot {
+    type Err = ();
+
+    fn from_str(text: &str) -> Result<Self, ()> {
+        let text = text.trim();
+        if let Ok(pivot) = text.parse() {
+            return Ok(ObjectPivot::Bounds(pivot));
+        }
+        let coords: Vec<usize> = text
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|c| !c.is_empty())
+            .map(|c| c.parse().map_err(|_| ()))
+            .collect::<Result<_, ()>>()?;
+        match coords[..] {
+            [x, y, z] => Ok(ObjectPivot::Cell([x, y, z])),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Lowest and highest of the cells
+pub(crate) fn bounds(cells: &[[usize; 3]]) -> Option<([usize; 3], [usize; 3])> {
+    let mut min = [0; 3];
+    let mut max = [0; 3];
+    for i in 0..3 {
+        min[i] = cells.iter().map(|cell| cell[i]).min()?;
+        max[i] = cells.iter().map(|cell| cell[i]).max()?;
+    }
+    Some((min, max))
+}
+
+/// Moves the voxels of the cells by the offset, overwriting the voxels at the destination.
+/// Returns the moved cells, or None without changing anything if a cell would leave the grid.
+pub fn translate(
+    voxel_manager: &mut VoxelManager,
+    cells: &[[usize; 3]],
+    offset: [i64; 3],
+) -> Option<Vec<[usize; 3]>> {
+    let extent = voxel_manager.extent();
+    let moved = cells
+        .iter()
+        .map(|cell| neighbour(extent, *cell, offset))
+        .collect::<Option<Vec<_>>>()?;
+    let values: Vec<Option<u8>> = cells
+        .iter()
+        .map(|[x, y, z]| voxel_manager.voxel(*x, *y, *z))
+        .collect();
+    voxel_manager.erase_cells(cells);
+    for ([x, y, z], value) in moved.iter().zip(values) {
+        if value.is_some() {
+            voxel_manager.set_voxel(*x, *y, *z, value);
+        }
+    }
+    Some(moved)
+}
+
+/// Moves the voxels of the cells by the offset scaled by their weights, as from
+/// selection::soft_weights, rounded to whole cells. Heavier cells are written last and win where
+/// voxels land on the same cell. Returns where the cells of full weight moved to, or None without
+/// changing anything if a cell would leave the grid.
+pub fn translate_weighted(
+    voxel_manager: &mut VoxelManager,
+    weighted_cells: &[([usize; 3], f32)],
+    offset: [i64; 3],
+) -> Option<Vec<[usize; 3]>> {
+    let mut weighted_cells = weighted_cells.to_vec();
+    weighted_cells.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+    let cells: Vec<[usize; 3]> = weighted_cells.iter().map(|(cell, _)| *cell).collect();
+    let targets: Vec<[i64; 3]> = weighted_cells
+        .iter()
+        .map(|(cell, weight)| {
+            let mut target = [0; 3];
+            for i in 0..3 {
+                target[i] = cell[i] as i64 + (offset[i] as f32 * weight).round() as i64;
+            }
+            target
+        })
+        .collect();
+    let moved = rewrite_cells(voxel_manager, &cells, &cells, &targets)?;
+    Some(
+        moved
+            .into_iter()
+            .zip(weighted_cells)
+            .filter(|(_, (_, weight))| *weight >= 1.0)
+            .map(|(target, _)| target)
+            .collect(),
+    )
+}
+
+/// Moves the voxels of the cells so the pivot of their bounds lands on the same point of the
+/// grid, rounded down to whole cells. Returns the moved cells, None without changing anything
+/// without cells.
+pub fn recenter(
+    voxel_manager: &mut VoxelManager,
+    cells: &[[usize; 3]],
+    pivot: Pivot,
+) -> Option<Vec<[usize; 3]>> {
+    let mut min = [0; 3];
+    let mut max = [0; 3];
+    for i in 0..3 {
+        min[i] = cells.iter().map(|cell| cell[i]).min()?;
+        max[i] = cells.iter().map(|cell| cell[i]).max()?;
+    }
+    let from = pivot.point(min, max);
+    let to = pivot.grid_point(voxel_manager.extent());
+    let mut offset = [0; 3];
+    for i in 0..3 {
+        offset[i] = (to[i] - from[i]).floor() as i64;
+    }
+    translate(voxel_manager, cells, offset)
+}
+
+/// Turns the cell a quarter turn counterclockwise around the axis the given number of times.
+/// The center is passed doubled so it can fall between cells, odd results are rounded down.
+pub(crate) fn rotate_quarter(
+    cell: [i64; 3],
+    center2: [i64; 3],
+    axis: Axis,
+    turns: usize,
+) -> [i64; 3] {
+    let (a, b) = match axis {
+        Axis::X => (1, 2),
+        Axis::Y => (2, 0),
+        Axis::Z => (0, 1),
+    };
+    let mut d = [0; 3];
+    for i in 0..3 {
+        d[i] = 2 * cell[i] - center2[i];
+    }
+    for _ in 0..turns % 4 {
+        let (da, db) = (d[a], d[b]);
+        d[a] = -db;
+        d[b] = da;
+    }
+    let mut rotated = [0; 3];
+    for i in 0..3 {
+        rotated[i] = (d[i] + center2[i]).div_euclid(2);
+    }
+    rotated
+}
+
+/// The center of the cell, doubled like the centers of bounds
+fn pivot_center2(pivot: [usize; 3]) -> [i64; 3] {
+    [
+        2 * pivot[0] as i64,
+        2 * pivot[1] as i64,
+        2 * pivot[2] as i64,
+    ]
+}
+
+/// The center of the bounds of the cells, doubled so it can fall between cells
+pub(crate) fn center2(cells: &[[usize; 3]]) -> [i64; 3] {
+    let mut center2 = [0; 3];
+    for i in 0..3 {
+        let min = cells.iter().map(|cell| cell[i]).min().unwrap_or(0) as i64;
+        let max = cells.iter().map(|cell| cell[i]).max().unwrap_or(0) as i64;
+        center2[i] = min + max;
+    }
+    center2
+}
+
+/// Empties the cells and copies the voxel of every source to its target, the sources are read
+/// before anything is written. Returns the targets, or None without changing anything if a
+/// target is outside the grid.
+fn rewrite_cells(
+    voxel_manager: &mut VoxelManager,
+    cells: &[[usize; 3]],
+    sources: &[[usize; 3]],
+    targets: &[[i64; 3]],
+) -> Option<Vec<[usize; 3]>> {
+    let extent = voxel_manager.extent() as i64;
+    if targets.iter().flatten().any(|c| *c < 0 || *c >= extent) {
+        return None;
+    }
+    let values: Vec<Option<u8>> = sources
+        .iter()
+        .map(|[x, y, z]| voxel_manager.voxel(*x, *y, *z))
+        .collect();
+    voxel_manager.erase_cells(cells);
+    let mut written = Vec::with_capacity(targets.len());
+    for (target, value) in targets.iter().zip(values) {
+        let [x, y, z] = [target[0] as usize, target[1] as usize, target[2] as usize];
+        if value.is_some() {
+            voxel_manager.set_voxel(x, y, z, value);
+        }
+        written.push([x, y, z]);
+    }
+    Some(written)
+}
+
+/// Turns the voxels of the cells the given number of quarter turns around the axis through
+/// their center, or through the pivot cell when given. Returns the turned cells, or None
+/// without changing anything if a cell would leave the grid.
+pub fn rotate(
+    voxel_manager: &mut VoxelManager,
+    cells: &[[usize; 3]],
+    axis: Axis,
+    turns: usize,
+    pivot: Option<[usize; 3]>,
+) -> Option<Vec<[usize; 3]>> {
+    let center2 = pivot.map_or_else(|| center2(cells), pivot_center2);
+    let targets: Vec<[i64; 3]> = cells
+        .iter()
+        .map(|cell| {
+            let cell = [cell[0] as i64, cell[1] as i64, cell[2] as i64];
+            rotate_quarter(cell, center2, axis, turns)
+        })
+        .collect();
+    rewrite_cells(voxel_manager, cells, cells, &targets)
+}
+
+/// Stretches or squeezes the voxels of the cells along the axis until their bounds are length
+/// cells long, starting at the same side. Every layer of the result copies the source layer
+/// it falls on. Returns the resulting cells, or None without changing anything if the length
+/// is zero or a cell would leave the grid.
+pub fn scale(
+    voxel_manager: &mut VoxelManager,
+    cells: &[[usize; 3]],
+    axis: Axis,
+    length: usize,
+) -> Option<Vec<[usize; 3]>> {
+    let i = axis.index();
+    let min = cells.iter().map(|cell| cell[i]).min()?;
+    let max = cells.iter().map(|cell| cell[i]).max()?;
+    if length == 0 {
+        return None;
+    }
+    let source_length = max - min + 1;
+    let mut sources = Vec::new();
+    let mut targets = Vec::new();
+    for layer in 0..length {
+        let source_layer = min + layer * source_length / length;
+        for cell in cells.iter().filter(|cell| cell[i] == source_layer) {
+            let mut target = [cell[0] as i64, cell[1] as i64, cell[2] as i64];
+            target[i] = (min + layer) as i64;
+            sources.push(*cell);
+            targets.push(target);
+        }
+    }
+    rewrite_cells(voxel_manager, cells, &sources, &targets)
+}
+
+/// Edits replicated count times around the axis through the grid center while it is set
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RadialSymmetry {
+    pub axis: Axis,
+    pub count: usize,
+}
+
+impl RadialSymmetry {
+    /// The cell followed by its distinct turned copies inside the grid. Quarter turns are exact,
+    /// other angles round to the nearest cell.
+    pub fn images(&self, cell: [usize; 3], extent: usize) -> Vec<[usize; 3]> {
+        let (a, b) = match self.axis {
+            Axis::X => (1, 2),
+            Axis::Y => (2, 0),
+            Axis::Z => (0, 1),
+        };
+        let center = (extent - 1) as f32 / 2.0;
+        let mut images = vec![cell];
+        for copy in 1..self.count {
+            let image = if (copy * 4) % self.count == 0 {
+                let center2 = [(extent - 1) as i64; 3];
+                let turned = [cell[0] as i64, cell[1] as i64, cell[2] as i64];
+                let [x, y, z] = rotate_quarter(turned, center2, self.axis, copy * 4 / self.count);
+                [x as usize, y as usize, z as usize]
+            } else {
+                let (sin, cos) = (2.0 * PI * copy as f32 / self.count as f32).sin_cos();
+                let (da, db) = (cell[a] as f32 - center, cell[b] as f32 - center);
+                let (ta, tb) = (
+                    (center + da * cos - db * sin).round(),
+                    (center + da * sin + db * cos).round(),
+                );
+                if ta < 0.0 || tb < 0.0 || ta >= extent as f32 || tb >= extent as f32 {
+                    continue;
+                }
+                let mut turned = cell;
+                turned[a] = ta as usize;
+                turned[b] = tb as usize;
+                turned
+            };
+            if !images.contains(&image) {
+                images.push(image);
+            }
+        }
+        images
+    }
+}
+
+/// Copies the voxels of the cells count times, every copy moved further by the offset and
+/// optionally turned another quarter around the axis through the center of the cells.
+/// Copied cells outside the grid are dropped. Returns the cells written by the copies.
+pub fn array(
+    voxel_manager: &mut VoxelManager,
+    cells: &[[usize; 3]],
+    count: usize,
+    offset: [i64; 3],
+    rotation: Option<Axis>,
+) -> Vec<[usize; 3]> {
+    let extent = voxel_manager.extent() as i64;
+    let center2 = center2(cells);
+    let values: Vec<Option<u8>> = cells
+        .iter()
+        .map(|[x, y, z]| voxel_manager.voxel(*x, *y, *z))
+        .collect();
+    let mut copied = Vec::new();
+    for copy in 1..=count {
+        for (cell, value) in cells.iter().zip(values.iter()) {
+            let mut target = [cell[0] as i64, cell[1] as i64, cell[2] as i64];
+            if let Some(axis) = rotation {
+                target = rotate_quarter(target, center2, axis, copy);
+            }
+            for i in 0..3 {
+                target[i] += offset[i] * copy as i64;
+            }
+            if value.is_none() || target.iter().any(|c| *c < 0 || *c >= extent) {
+                continue;
+            }
+            let [x, y, z] = [target[0] as usize, target[1] as usize, target[2] as usize];
+            voxel_manager.set_voxel(x, y, z, *value);
+            copied.push([x, y, z]);
+        }
+    }
+    copied
+}
+
+/// Places count evenly spaced instances of the cells around the axis through the grid center,
+/// or through the pivot cell when given, the cells themselves being the first one. Copies at
+/// multiples of a quarter turn are exact, the others are resampled by taking the nearest
+/// source cell of every covered grid cell. Returns the cells written by the copies.
+pub fn radial_array(
+    voxel_manager: &mut VoxelManager,
+    cells: &[[usize; 3]],
+    count: usize,
+    axis: Axis,
+    pivot: Option<[usize; 3]>,
+) -> Vec<[usize; 3]> {
+    let center2 = radial_center2(voxel_manager.extent(), pivot);
+    radial_array_around(voxel_manager, cells, count, axis, center2)
+}
+
+/// The doubled center radial_array turns around, the grid center or the pivot cell
+pub fn radial_center2(extent: usize, pivot: Option<[usize; 3]>) -> [i64; 3] {
+    pivot.map_or([(extent - 1) as i64; 3], pivot_center2)
+}
+
+/// Like radial_array, around the axis through the doubled center
+pub fn radial_array_around(
+    voxel_manager: &mut VoxelManager,
+    cells: &[[usize; 3]],
+    count: usize,
+    axis: Axis,
+    center2: [i64; 3],
+) -> Vec<[usize; 3]> {
+    let extent = voxel_manager.extent();
+    let (a, b) = match axis {
+        Axis::X => (1, 2),
+        Axis::Y => (2, 0),
+        Axis::Z => (0, 1),
+    };
+    let source: HashMap<[usize; 3], u8> = cells
+        .iter()
+        .filter_map(|[x, y, z]| Some(([*x, *y, *z], voxel_manager.voxel(*x, *y, *z)?)))
+        .collect();
+    let (center_a, center_b) = (center2[a] as f32 / 2.0, center2[b] as f32 / 2.0);
+    let mut copied = Vec::new();
+    for copy in 1..count {
+        if (copy * 4) % count == 0 {
+            let turns = copy * 4 / count;
+            for (cell, value) in source.iter() {
+                let cell = [cell[0] as i64, cell[1] as i64, cell[2] as i64];
+                let turned = rotate_quarter(cell, center2, axis, turns);
+                // Around a pivot off the grid center copies can leave the grid
+                if turned.iter().any(|c| *c < 0 || *c >= extent as i64) {
+                    continue;
+                }
+                let target = [turned[0] as usize, turned[1] as usize, turned[2] as usize];
+                voxel_manager.set_voxel(target[0], target[1], target[2], Some(*value));
+                copied.push(target);
+            }
+            continue;
+        }
+        let (sin, cos) = (2.0 * PI * copy as f32 / count as f32).sin_cos();
+        // Turns the cell by the angle with the given sine around the axis
+        let turn = |cell: [usize; 3], sin: f32| {
+            let (da, db) = (cell[a] as f32 - center_a, cell[b] as f32 - center_b);
+            let (ta, tb) = (
+                (center_a + da * cos - db * sin).round(),
+                (center_b + da * sin + db * cos).round(),
+            );
+            if ta < 0.0 || tb < 0.0 || ta >= extent as f32 || tb >= extent as f32 {
+                return None;
+            }
+            let mut turned = cell;
+            turned[a] = ta as usize;
+            turned[b] = tb as usize;
+            Some(turned)
+        };
+        // Every source cell lands somewhere, even when no grid cell samples it
+        let mut written = HashSet::new();
+        for (cell, value) in source.iter() {
+            if let Some(target) = turn(*cell, sin) {
+                voxel_manager.set_voxel(target[0], target[1], target[2], Some(*value));
+                written.insert(target);
+            }
+        }
+        // Rotated cells stay within the radius of the farthest source cell around the axis
+        let radius = source
+            .keys()
+            .map(|cell| {
+                let (da, db) = (cell[a] as f32 - center_a, cell[b] as f32 - center_b);
+                (da * da + db * db).sqrt()
+            })
+            .fold(0.0, f32::max)
+            .ceil() as usize
+            + 1;
+        let range = |center: f32| {
+            (center as usize).saturating_sub(radius)
+                ..=(center as usize + radius + 1).min(extent - 1)
+        };
+        let (along_min, along_max) = source.keys().fold((extent, 0), |(lo, hi), cell| {
+            (lo.min(cell[axis.index()]), hi.max(cell[axis.index()]))
+        });
+        for i in range(center_a) {
+            for j in range(center_b) {
+                for k in along_min..=along_max {
+                    let mut target = [0; 3];
+                    target[a] = i;
+                    target[b] = j;
+                    target[axis.index()] = k;
+                    // Turn the target back onto the source instance
+                    let value = turn(target, -sin).and_then(|from| source.get(&from));
+                    if let Some(value) = value {
+                        if written.insert(target) {
+                            voxel_manager.set_voxel(target[0], target[1], target[2], Some(*value));
+                        }
+                    }
+                }
+            }
+        }
+        copied.extend(written);
+    }
+    copied
+}
+
+/// Revolves the cells around the axis through the grid center, every cell sweeping the ring
+/// of its distance from the axis. A profile drawn on one side of the axis becomes a solid.
+/// Returns the cells of the solid.
+pub fn revolve(
+    voxel_manager: &mut VoxelManager,
+    cells: &[[usize; 3]],
+    axis: Axis,
+) -> Vec<[usize; 3]> {
+    let extent = voxel_manager.extent();
+    let (a, b) = match axis {
+        Axis::X => (1, 2),
+        Axis::Y => (2, 0),
+        Axis::Z => (0, 1),
+    };
+    let center = (extent - 1) as f32 / 2.0;
+    let ring = |cell: &[usize; 3]| {
+        let (da, db) = (cell[a] as f32 - center, cell[b] as f32 - center);
+        (da * da + db * db).sqrt().round() as usize
+    };
+    let mut profile = HashMap::new();
+    for cell in cells.iter() {
+        if let Some(value) = voxel_manager.voxel(cell[0], cell[1], cell[2]) {
+            profile.insert((cell[axis.index()], ring(cell)), value);
+        }
+    }
+    let mut solid = Vec::new();
+    for x in 0..extent {
+        for y in 0..extent {
+            for z in 0..extent {
+                let cell = [x, y, z];
+                if let Some(value) = profile.get(&(cell[axis.index()], ring(&cell))) {
+                    voxel_manager.set_voxel(x, y, z, Some(*value));
+                    solid.push(cell);
+                }
+            }
+        }
+    }
+    solid
+}
+
+/// Reflects one half of the grid over the mirror plane through the grid center,
+/// replacing the other half. keep_positive selects the half with the higher coordinates.
+pub fn symmetrize(voxel_manager: &mut VoxelManager, axis: Axis, keep_positive: bool) {
+    let extent = voxel_manager.extent();
+    let axis = axis.index();
+    for x in 0..extent {
+        for y in 0..extent {
+            for z in 0..extent {
+                let target = [x, y, z];
+                let mut source = target;
+                source[axis] = extent - 1 - target[axis];
+                // Cells of the kept half and the ones on an odd sized grid's mirror plane stay
+                let replaced = if keep_positive {
+                    target[axis] < source[axis]
+                } else {
+                    target[axis] > source[axis]
+                };
+                if replaced {
+                    let value = voxel_manager.voxel(source[0], source[1], source[2]);
+                    voxel_manager.set_voxel(x, y, z, value);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn symmetrize_replaces_the_other_half() {
+        let mut voxel_manager = VoxelManager::new(8);
+        voxel_manager.set_voxel(6, 1, 2, Some(0));
+        voxel_manager.set_voxel(0, 4, 4, Some(0));
+
+        symmetrize(&mut voxel_manager, Axis::X, true);
+        assert_eq!(voxel_manager.voxel(1, 1, 2), Some(0));
+        assert_eq!(voxel_manager.voxel(6, 1, 2), Some(0));
+        // The voxel on the replaced side is gone
+        assert_eq!(voxel_manager.voxel(0, 4, 4), None);
+        assert_eq!(voxel_manager.voxel(7, 4, 4), None);
+    }
+
+    #[test]
+    fn array_copies_with_offset_and_rotation() {
+        let mut voxel_manager = VoxelManager::new(8);
+        voxel_manager.set_voxel(0, 0, 0, Some(0));
+        voxel_manager.set_voxel(1, 0, 0, Some(1));
+        let cells = [[0, 0, 0], [1, 0, 0]];
+
+        // The third copy falls outside the grid
+        let copied = array(&mut voxel_manager, &cells, 3, [3, 0, 0], None);
+        assert_eq!(copied.len(), 4);
+        assert_eq!(voxel_manager.voxel(3, 0, 0), Some(0));
+        assert_eq!(voxel_manager.voxel(7, 0, 0), Some(1));
+
+        // A quarter turn around Y lays the bar along Z
+        let copied = array(&mut voxel_manager, &cells, 1, [0, 0, 3], Some(Axis::Y));
+        assert_eq!(copied, vec![[0, 0, 3], [0, 0, 2]]);
+        assert_eq!(voxel_manager.voxel(0, 0, 2), Some(1));
+    }
+
+    #[test]
+    fn rotate_and_scale_keep_the_selection_in_the_grid() {
+        let mut voxel_manager = VoxelManager::new(8);
+        voxel_manager.set_voxel(2, 0, 2, Some(0));
+        voxel_manager.set_voxel(3, 0, 2, Some(1));
+        voxel_manager.set_voxel(4, 0, 2, Some(2));
+        let cells = [[2, 0, 2], [3, 0, 2], [4, 0, 2]];
+
+        // Standing the bar up around Z would take it below the grid
+        assert_eq!(rotate(&mut voxel_manager, &cells, Axis::Z, 1, None), None);
+        assert_eq!(voxel_manager.voxel(2, 0, 2), Some(0));
+        let turned = rotate(&mut voxel_manager, &cells, Axis::Y, 1, None).unwrap();
+        assert_eq!(turned, vec![[3, 0, 3], [3, 0, 2], [3, 0, 1]]);
+        assert_eq!(voxel_manager.voxel(3, 0, 3), Some(0));
+        assert_eq!(voxel_manager.voxel(2, 0, 2), None);
+
+        let stretched = scale(&mut voxel_manager, &turned, Axis::Z, 6).unwrap();
+        assert_eq!(stretched.len(), 6);
+        assert_eq!(voxel_manager.voxel(3, 0, 1), Some(2));
+        assert_eq!(voxel_manager.voxel(3, 0, 2), Some(2));
+        assert_eq!(voxel_manager.voxel(3, 0, 6), Some(0));
+        assert_eq!(scale(&mut voxel_manager, &stretched, Axis::Z, 8), None);
+        let squeezed = scale(&mut voxel_manager, &stretched, Axis::Z, 1).unwrap();
+        assert_eq!(squeezed, vec![[3, 0, 1]]);
+        assert_eq!(voxel_manager.voxel(3, 0, 6), None);
+    }
+
+    #[test]
+    fn recentering_moves_the_pivot_onto_the_grid() {
+        let mut voxel_manager = VoxelManager::new(8);
+        let cells = [[0, 3, 0], [1, 3, 0], [1, 4, 0]];
+        for [x, y, z] in cells.iter() {
+            voxel_manager.set_voxel(*x, *y, *z, Some(0));
+        }
+        let mut moved = recenter(&mut voxel_manager, &cells, Pivot::BottomCenter).unwrap();
+        moved.sort_unstable();
+        assert_eq!(moved, vec![[3, 0, 3], [4, 0, 3], [4, 1, 3]]);
+        assert_eq!(voxel_manager.voxel(0, 3, 0), None);
+        assert_eq!(recenter(&mut voxel_manager, &[], Pivot::Center), None);
+    }
+
+    #[test]
+    fn radial_symmetry_turns_edits_around_the_axis() {
+        let mut voxel_manager = VoxelManager::new(8);
+        voxel_manager.set_symmetry(Some(RadialSymmetry {
+            axis: Axis::Y,
+            count: 4,
+        }));
+        voxel_manager.set_voxel(7, 2, 3, Some(0));
+        voxel_manager.set_symmetry(None);
+        for [x, y, z] in [[7, 2, 3], [3, 2, 0], [0, 2, 4], [4, 2, 7]].iter() {
+            assert_eq!(voxel_manager.voxel(*x, *y, *z), Some(0));
+        }
+
+        // Cells on the axis have no other images
+        let half = RadialSymmetry {
+            axis: Axis::Z,
+            count: 2,
+        };
+        assert_eq!(half.images([1, 6, 5], 8), vec![[1, 6, 5], [6, 1, 5]]);
+        assert_eq!(half.images([4, 4, 0], 9), vec![[4, 4, 0]]);
+    }
+
+    #[test]
+    fn radial_array_spaces_the_copies_around_the_axis() {
+        let mut voxel_manager = VoxelManager::new(8);
+        voxel_manager.set_voxel(7, 2, 3, Some(0));
+
+        let copied = radial_array(&mut voxel_manager, &[[7, 2, 3]], 4, Axis::Y, None);
+        assert_eq!(copied.len(), 3);
+        for [x, y, z] in [[3, 2, 0], [0, 2, 4], [4, 2, 7]].iter() {
+            assert_eq!(voxel_manager.voxel(*x, *y, *z), Some(0));
+        }
+
+        // Copies of other angles are resampled onto the grid
+        let copied = radial_array(&mut voxel_manager, &[[7, 2, 3]], 8, Axis::Y, None);
+        assert!(copied.len() >= 7);
+        assert!(copied.iter().all(|cell| cell[1] == 2));
+
+        // Around a pivot next to the cell instead of the grid center
+        let mut voxel_manager = VoxelManager::new(8);
+        voxel_manager.set_voxel(7, 2, 3, Some(0));
+        let mut copied = radial_array(
+            &mut voxel_manager,
+            &[[7, 2, 3]],
+            4,
+            Axis::Y,
+            Some([5, 2, 3]),
+        );
+        copied.sort_unstable();
+        assert_eq!(copied, vec![[3, 2, 3], [5, 2, 1], [5, 2, 5]]);
+    }
+
+    #[test]
+    fn revolve_sweeps_rings_around_the_axis() {
+        let mut voxel_manager = VoxelManager::new(9);
+        // A profile reaching 2 and 3 cells from the axis through the center column
+        voxel_manager.set_voxel(6, 1, 4, Some(0));
+        voxel_manager.set_voxel(7, 2, 4, Some(1));
+
+        let solid = revolve(&mut voxel_manager, &[[6, 1, 4], [7, 2, 4]], Axis::Y);
+        assert!(solid.contains(&[2, 1, 4]));
+        assert!(solid.contains(&[4, 1, 6]));
+        assert_eq!(voxel_manager.voxel(4, 2, 1), Some(1));
+        assert_eq!(voxel_manager.voxel(4, 1, 4), None);
+        assert!(solid.iter().all(|cell| cell[1] == 1 || cell[1] == 2));
+    }
+
+    #[test]
+    fn translate_moves_cells_inside_the_grid() {
+        let mut voxel_manager = VoxelManager::new(4);
+        voxel_manager.set_voxel(0, 0, 0, Some(0));
+        voxel_manager.set_voxel(1, 0, 0, Some(1));
+        let cells = [[0, 0, 0], [1, 0, 0]];
+
+        let moved = translate(&mut voxel_manager, &cells, [1, 0, 0]).unwrap();
+        assert_eq!(moved, vec![[1, 0, 0], [2, 0, 0]]);
+        assert_eq!(voxel_manager.voxel(0, 0, 0), None);
+        assert_eq!(voxel_manager.voxel(1, 0, 0), Some(0));
+        assert_eq!(voxel_manager.voxel(2, 0, 0), Some(1));
+
+        assert!(translate(&mut voxel_manager, &moved, [0, -1, 0]).is_none());
+        assert_eq!(voxel_manager.voxel(2, 0, 0), Some(1));
+    }
+}
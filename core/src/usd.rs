@@ -0,0 +1,224 @@
+//! USD export, as a text .usda layer or packaged into a .usdz archive for AR Quick Look.
+//! Every object becomes an Xform holding a mesh for each of its colors, translated to the pivot
+//! of saved masks that have one. The colors are UsdPreviewSurface materials shared by all
+//! objects.
+use crate::export::ExportObject;
+use crate::image::crc32;
+use crate::palette::Palette;
+use crate::task::{cancelled, Progress};
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// Name of the layer inside .usdz archives
+const USDZ_LAYER: &str = "scene.usda";
+// Archive members have to start on 64 byte boundaries
+const USDZ_ALIGNMENT: usize = 64;
+
+fn write_array<T: std::fmt::Display>(out: &mut String, values: impl Iterator<Item = T>) {
+    out.push('[');
+    for (i, value) in values.enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        let _ = write!(out, "{}", value);
+    }
+    out.push(']');
+}
+
+fn tuple(v: [f32; 3]) -> String {
+    format!("({}, {}, {})", v[0], v[1], v[2])
+}
+
+/// The objects as a .usda layer, their colors are entries of the palette
+pub fn usda(
+    objects: &[ExportObject],
+    palette: &Palette,
+    progress: &mut Progress,
+) -> io::Result<String> {
+    let mut out = String::from(
+        "#usda 1.0\n(\n    defaultPrim = \"Scene\"\n    metersPerUnit = 0.1\n    upAxis = \"Y\"\n)\n\n",
+    );
+    out.push_str("def Xform \"Scene\"\n{\n");
+
+    let mut used = BTreeSet::new();
+    let mut meshes = String::new();
+    for (i, object) in objects.iter().enumerate() {
+        if !progress(i as f32 / objects.len() as f32) {
+            return Err(cancelled());
+        }
+        let _ = write!(
+            meshes,
+            "    def Xform \"{}\"\n    {{\n",
+            object.node_name(i + 1)
+        );
+        let (translation, origin) = object.transform();
+        if object.pivot.is_some() {
+            let [x, y, z] = translation;
+            let _ = write!(
+                meshes,
+                "        double3 xformOp:translate = ({}, {}, {})\n        uniform token[] xformOpOrder = [\"xformOp:translate\"]\n",
+                x, y, z
+            );
+        }
+        for (color_index, vertices, _) in object.part.cells_by_color(&object.cells, origin) {
+            used.insert(color_index);
+            // Faces are quads of 4 consecutive vertices
+            let _ = write!(
+                meshes,
+                "        def Mesh \"Color_{}\" (\n            prepend apiSchemas = [\"MaterialBindingAPI\"]\n        )\n        {{\n",
+                color_index
+            );
+            meshes.push_str("            int[] faceVertexCounts = ");
+            write_array(&mut meshes, (0..vertices.len() / 4).map(|_| 4));
+            meshes.push_str("\n            int[] faceVertexIndices = ");
+            write_array(&mut meshes, 0..vertices.len());
+            meshes.push_str("\n            normal3f[] normals = ");
+            write_array(&mut meshes, vertices.iter().map(|v| tuple(v.normal)));
+            meshes.push_str(" (\n                interpolation = \"vertex\"\n            )\n");
+            meshes.push_str("            point3f[] points = ");
+            write_array(&mut meshes, vertices.iter().map(|v| tuple(v.pos)));
+            let _ = write!(
+                meshes,
+                "\n            uniform token subdivisionScheme = \"none\"\n            rel material:binding = </Scene/Materials/Color_{}>\n        }}\n",
+                color_index
+            );
+        }
+        meshes.push_str("    }\n");
+    }
+
+    out.push_str("    def Scope \"Materials\"\n    {\n");
+    for color_index in used {
+        let [r, g, b, a] = palette.color(color_index);
+        let _ = write!(
+            out,
+            "        def Material \"Color_{0}\"\n        {{\n            token outputs:surface.connect = </Scene/Materials/Color_{0}/Surface.outputs:surface>\n\n            def Shader \"Surface\"\n            {{\n                uniform token info:id = \"UsdPreviewSurface\"\n                color3f inputs:diffuseColor = {1}\n                float inputs:opacity = {2}\n                float inputs:roughness = 0.8\n                token outputs:surface\n            }}\n        }}\n",
+            color_index,
+            tuple([r, g, b]),
+            a
+        );
+    }
+    out.push_str("    }\n");
+    out.push_str(&meshes);
+    out.push_str("}\n");
+    Ok(out)
+}
+
+/// An uncompressed zip archive with every member aligned to 64 bytes, as .usdz requires
+fn usdz(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut archive = Vec::new();
+    let mut central = Vec::new();
+    for (name, data) in files {
+        let offset = archive.len();
+        let crc = crc32(data);
+        // Pad the extra field so the data starts aligned
+        let header_size = 30 + name.len() + 4;
+        let padding = (USDZ_ALIGNMENT - (offset + header_size) % USDZ_ALIGNMENT) % USDZ_ALIGNMENT;
+        let extra_len = 4 + padding;
+
+        let mut common = Vec::new();
+        common.extend_from_slice(&20u16.to_le_bytes()); // version needed
+        common.extend_from_slice(&0u16.to_le_bytes()); // flags
+        common.extend_from_slice(&0u16.to_le_bytes()); // stored
+        common.extend_from_slice(&0u32.to_le_bytes()); // time and date
+        common.extend_from_slice(&crc.to_le_bytes());
+        common.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        common.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        common.extend_from_slice(&(name.len() as u16).to_le_bytes());
+
+        archive.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        archive.extend_from_slice(&common);
+        archive.extend_from_slice(&(extra_len as u16).to_le_bytes());
+        archive.extend_from_slice(name.as_bytes());
+        archive.extend_from_slice(&0x1986u16.to_le_bytes()); // padding extra field
+        archive.extend_from_slice(&(padding as u16).to_le_bytes());
+        archive.extend(std::iter::repeat(0).take(padding));
+        archive.extend_from_slice(data);
+
+        central.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        central.extend_from_slice(&20u16.to_le_bytes()); // version made by
+        central.extend_from_slice(&common);
+        central.extend_from_slice(&0u16.to_le_bytes()); // extra field
+        central.extend_from_slice(&0u16.to_le_bytes()); // comment
+        central.extend_from_slice(&0u16.to_le_bytes()); // disk
+        central.extend_from_slice(&0u16.to_le_bytes()); // internal attributes
+        central.extend_from_slice(&0u32.to_le_bytes()); // external attributes
+        central.extend_from_slice(&(offset as u32).to_le_bytes());
+        central.extend_from_slice(name.as_bytes());
+    }
+    let central_offset = archive.len();
+    archive.extend_from_slice(&central);
+    archive.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+    archive.extend_from_slice(&0u32.to_le_bytes()); // disk numbers
+    archive.extend_from_slice(&(files.len() as u16).to_le_bytes());
+    archive.extend_from_slice(&(files.len() as u16).to_le_bytes());
+    archive.extend_from_slice(&(central.len() as u32).to_le_bytes());
+    archive.extend_from_slice(&(central_offset as u32).to_le_bytes());
+    archive.extend_from_slice(&0u16.to_le_bytes()); // comment
+    archive
+}
+
+/// Writes a .usdz archive, or a plain .usda layer for any other extension
+pub fn write_usd(
+    objects: &[ExportObject],
+    palette: &Palette,
+    file_path: &str,
+    progress: &mut Progress,
+) -> io::Result<()> {
+    let layer = usda(objects, palette, progress)?;
+    let packaged = Path::new(file_path)
+        .extension()
+        .map_or(false, |ext| ext.eq_ignore_ascii_case("usdz"));
+    if packaged {
+        fs::write(file_path, usdz(&[(USDZ_LAYER, layer.as_bytes())]))
+    } else {
+        fs::write(file_path, layer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::export::export_objects;
+    use crate::selection::SavedMasks;
+    use crate::voxel_manager::VoxelManager;
+
+    #[test]
+    fn objects_get_a_mesh_per_color() {
+        let mut voxel_manager = VoxelManager::new(8);
+        let red = voxel_manager.palette_mut().index_of([1.0, 0.0, 0.0, 1.0]);
+        let blue = voxel_manager.palette_mut().index_of([0.0, 0.0, 1.0, 1.0]);
+        voxel_manager.set_voxel(0, 0, 0, Some(red));
+        voxel_manager.set_voxel(1, 0, 0, Some(blue));
+        voxel_manager.set_voxel(5, 5, 5, Some(red));
+
+        let objects = export_objects(&[&voxel_manager], &SavedMasks::default(), [0; 3]);
+        let layer = usda(&objects, voxel_manager.palette(), &mut |_| true).unwrap();
+        assert!(layer.starts_with("#usda 1.0"));
+        assert_eq!(layer.matches("def Xform \"Object_").count(), 2);
+        assert_eq!(layer.matches("def Mesh").count(), 3);
+        assert_eq!(layer.matches("def Material").count(), 2);
+        assert!(layer.contains("color3f inputs:diffuseColor = (1, 0, 0)"));
+        assert!(layer.contains("rel material:binding = </Scene/Materials/Color_1>"));
+    }
+
+    #[test]
+    fn usdz_members_are_stored_and_aligned() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        let archive = usdz(&[("a.usda", b"#usda 1.0\n"), ("b.png", b"image")]);
+        let first = archive
+            .windows(10)
+            .position(|w| w == b"#usda 1.0\n")
+            .unwrap();
+        assert_eq!(first % USDZ_ALIGNMENT, 0);
+        let second = archive.windows(5).position(|w| w == b"image").unwrap();
+        assert_eq!(second % USDZ_ALIGNMENT, 0);
+        assert_eq!(
+            &archive[archive.len() - 22..archive.len() - 18],
+            b"PK\x05\x06"
+        );
+    }
+}
@@ -0,0 +1,335 @@
+//! OpenVDB export, for using scenes as volumes in offline renderers. Filled cells become active
+//! voxels of a `density` float grid holding the color's alpha and a `Cd` color grid, both
+//! stored uncompressed in the standard 5-4-3 tree.
+use crate::analysis;
+use crate::task::{cancelled, Progress};
+use crate::voxel_manager::VoxelManager;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const VDB_MAGIC: i64 = 0x5644_4220;
+const FILE_VERSION: u32 = 224;
+const LIBRARY_VERSION: [u32; 2] = [9, 0];
+// Per node flag of the stored values: no selection mask, every value is written
+const NO_MASK_AND_ALL_VALS: u8 = 6;
+
+// Log2 of the node sizes along an axis, from the leaves up
+const LEAF_LOG2: usize = 3;
+const INTERNAL1_LOG2: usize = 4;
+const INTERNAL2_LOG2: usize = 5;
+// Cells an internal node of the upper level spans along an axis
+const INTERNAL2_DIM: usize = 1 << (LEAF_LOG2 + INTERNAL1_LOG2 + INTERNAL2_LOG2);
+
+/// Offset of a cell in a node whose children span `1 << child_log2` cells
+fn offset([x, y, z]: [usize; 3], log2: usize, child_log2: usize) -> usize {
+    let local = |v: usize| (v & ((1 << (log2 + child_log2)) - 1)) >> child_log2;
+    (local(x) << (2 * log2)) | (local(y) << log2) | local(z)
+}
+
+type Leaf = Vec<Option<u8>>;
+
+/// Filled cells by the offsets of their lower internal node and leaf
+fn tree(cells: &[([usize; 3], u8)]) -> BTreeMap<usize, BTreeMap<usize, Leaf>> {
+    let mut nodes: BTreeMap<usize, BTreeMap<usize, Leaf>> = BTreeMap::new();
+    for (cell, color_index) in cells {
+        let leaf = nodes
+            .entry(offset(*cell, INTERNAL2_LOG2, LEAF_LOG2 + INTERNAL1_LOG2))
+            .or_default()
+            .entry(offset(*cell, INTERNAL1_LOG2, LEAF_LOG2))
+            .or_insert_with(|| vec![None; 1 << (3 * LEAF_LOG2)]);
+        leaf[offset(*cell, LEAF_LOG2, 0)] = Some(*color_index);
+    }
+    nodes
+}
+
+fn write_string(out: &mut Vec<u8>, value: &str) {
+    out.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    out.extend_from_slice(value.as_bytes());
+}
+
+fn write_vec3d(out: &mut Vec<u8>, value: [f64; 3]) {
+    for c in value.iter() {
+        out.extend_from_slice(&c.to_le_bytes());
+    }
+}
+
+fn write_mask(out: &mut Vec<u8>, bits: usize, on: impl Fn(usize) -> bool) {
+    for word in 0..bits / 64 {
+        let value = (0..64).fold(0u64, |value, bit| {
+            value | ((on(word * 64 + bit) as u64) << bit)
+        });
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+/// Writes the values of a node, with the flag newer files put in front of them
+fn write_values(out: &mut Vec<u8>, values: impl Iterator<Item = Vec<u8>>) {
+    out.push(NO_MASK_AND_ALL_VALS);
+    for value in values {
+        out.extend_from_slice(&value);
+    }
+}
+
+struct Grid<'a> {
+    name: &'a str,
+    tree_type: &'a str,
+    metadata: &'a [(&'a str, &'a str)],
+    background: Vec<u8>,
+    /// Value of a voxel of the given color
+    value: &'a dyn Fn(u8) -> Vec<u8>,
+}
+
+fn write_grid(
+    out: &mut Vec<u8>,
+    grid: &Grid,
+    nodes: &BTreeMap<usize, BTreeMap<usize, Leaf>>,
+    origin: [f32; 3],
+    voxel_scale: [f32; 3],
+) {
+    write_string(out, grid.name);
+    write_string(out, grid.tree_type);
+    write_string(out, "");
+    // Grid, buffer and end positions are patched in once they're known
+    let positions = out.len();
+    out.extend_from_slice(&[0; 24]);
+    let grid_pos = out.len();
+
+    out.extend_from_slice(&0u32.to_le_bytes()); // not compressed
+    out.extend_from_slice(&(grid.metadata.len() as u32 + 1).to_le_bytes());
+    for (name, value) in [("name", grid.name)].iter().chain(grid.metadata.iter()) {
+        write_string(out, name);
+        write_string(out, "string");
+        write_string(out, value);
+    }
+    // One index unit is one cell as large as a voxel, with voxel centers in the middle of the
+    // cells
+    let scale = |i: usize| voxel_scale[i] as f64;
+    let per_axis = |f: fn(f64) -> f64| [f(scale(0)), f(scale(1)), f(scale(2))];
+    if voxel_scale[0] == voxel_scale[1] && voxel_scale[1] == voxel_scale[2] {
+        write_string(out, "UniformScaleTranslateMap");
+    } else {
+        write_string(out, "ScaleTranslateMap");
+    }
+    let center = |i: usize| origin[i] as f64 + 0.5 * scale(i);
+    write_vec3d(out, [center(0), center(1), center(2)]); // translation
+    write_vec3d(out, per_axis(|s| s)); // scale
+    write_vec3d(out, per_axis(|s| s)); // voxel size
+    write_vec3d(out, per_axis(|s| 1.0 / s)); // inverse scale
+    write_vec3d(out, per_axis(|s| 1.0 / (s * s))); // inverse scale squared
+    write_vec3d(out, per_axis(|s| 0.5 / s)); // inverse twice the scale
+
+    // Topology: the root holds a single upper internal node at the origin
+    out.extend_from_slice(&1u32.to_le_bytes()); // buffer count
+    out.extend_from_slice(&grid.background);
+    out.extend_from_slice(&0u32.to_le_bytes()); // tiles
+    out.extend_from_slice(&(!nodes.is_empty() as u32).to_le_bytes());
+    if !nodes.is_empty() {
+        out.extend_from_slice(&[0; 12]);
+        let size = 1 << (3 * INTERNAL2_LOG2);
+        write_mask(out, size, |i| nodes.contains_key(&i));
+        write_mask(out, size, |_| false);
+        let zero = vec![0; grid.background.len()];
+        write_values(
+            out,
+            (0..size).map(|i| {
+                if nodes.contains_key(&i) {
+                    zero.clone()
+                } else {
+                    grid.background.clone()
+                }
+            }),
+        );
+        for leaves in nodes.values() {
+            let size = 1 << (3 * INTERNAL1_LOG2);
+            write_mask(out, size, |i| leaves.contains_key(&i));
+            write_mask(out, size, |_| false);
+            write_values(
+                out,
+                (0..size).map(|i| {
+                    if leaves.contains_key(&i) {
+                        zero.clone()
+                    } else {
+                        grid.background.clone()
+                    }
+                }),
+            );
+            for leaf in leaves.values() {
+                write_mask(out, leaf.len(), |i| leaf[i].is_some());
+            }
+        }
+    }
+
+    let block_pos = out.len();
+    for leaf in nodes.values().flat_map(|leaves| leaves.values()) {
+        write_mask(out, leaf.len(), |i| leaf[i].is_some());
+        write_values(
+            out,
+            leaf.iter().map(|voxel| match voxel {
+                Some(color_index) => (grid.value)(*color_index),
+                None => grid.background.clone(),
+            }),
+        );
+    }
+    let end_pos = out.len();
+    for (i, pos) in [grid_pos, block_pos, end_pos].iter().enumerate() {
+        out[positions + i * 8..positions + i * 8 + 8].copy_from_slice(&(*pos as i64).to_le_bytes());
+    }
+}
+
+/// A random version 4 UUID, every file gets its own
+fn uuid() -> String {
+    let mut state = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_nanos() as u64)
+        ^ ((std::process::id() as u64) << 32)
+        | 1;
+    let mut bytes = [0u8; 16];
+    for byte in bytes.iter_mut() {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        *byte = state as u8;
+    }
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+/// The scene as a .vdb file
+pub fn vdb(voxel_manager: &VoxelManager, progress: &mut Progress) -> io::Result<Vec<u8>> {
+    if voxel_manager.extent() > INTERNAL2_DIM {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the grid is too large for a single VDB root child",
+        ));
+    }
+    let cells = analysis::filled_cells(voxel_manager);
+    if !progress(0.5) {
+        return Err(cancelled());
+    }
+    let nodes = tree(&cells);
+    let palette = voxel_manager.palette();
+
+    let mut out = Vec::new();
+    out.extend_from_slice(&VDB_MAGIC.to_le_bytes());
+    out.extend_from_slice(&FILE_VERSION.to_le_bytes());
+    for version in LIBRARY_VERSION.iter() {
+        out.extend_from_slice(&version.to_le_bytes());
+    }
+    out.push(1); // has grid offsets
+    out.extend_from_slice(uuid().as_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes()); // file metadata
+    out.extend_from_slice(&2u32.to_le_bytes());
+
+    let density = |color_index| palette.color(color_index)[3].to_le_bytes().to_vec();
+    write_grid(
+        &mut out,
+        &Grid {
+            name: "density",
+            tree_type: "Tree_float_5_4_3",
+            metadata: &[("class", "fog volume")],
+            background: 0f32.to_le_bytes().to_vec(),
+            value: &density,
+        },
+        &nodes,
+        voxel_manager.origin(),
+        voxel_manager.voxel_scale(),
+    );
+    let color = |color_index| {
+        let [r, g, b, _] = palette.color(color_index);
+        [r, g, b]
+            .iter()
+            .flat_map(|c| c.to_le_bytes().to_vec())
+            .collect()
+    };
+    write_grid(
+        &mut out,
+        &Grid {
+            name: "Cd",
+            tree_type: "Tree_vec3s_5_4_3",
+            metadata: &[("vector_type", "invariant")],
+            background: vec![0; 12],
+            value: &color,
+        },
+        &nodes,
+        voxel_manager.origin(),
+        voxel_manager.voxel_scale(),
+    );
+    Ok(out)
+}
+
+pub fn write_vdb(
+    voxel_manager: &VoxelManager,
+    file_path: &str,
+    progress: &mut Progress,
+) -> io::Result<()> {
+    fs::write(file_path, vdb(voxel_manager, progress)?)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn read_u32(bytes: &[u8], at: usize) -> usize {
+        u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]]) as usize
+    }
+
+    fn read_i64(bytes: &[u8], at: usize) -> usize {
+        let mut word = [0; 8];
+        word.copy_from_slice(&bytes[at..at + 8]);
+        i64::from_le_bytes(word) as usize
+    }
+
+    #[test]
+    fn grids_are_laid_out_back_to_back() {
+        let mut voxel_manager = VoxelManager::new(16);
+        let red = voxel_manager.palette_mut().index_of([1.0, 0.0, 0.0, 1.0]);
+        voxel_manager.set_voxel(0, 0, 0, Some(red));
+        voxel_manager.set_voxel(9, 0, 0, Some(red));
+        let bytes = vdb(&voxel_manager, &mut |_| true).unwrap();
+
+        assert_eq!(read_u32(&bytes, 0), 0x5644_4220);
+        assert_eq!(read_u32(&bytes, 8), 224);
+        // Header, UUID, empty file metadata and the grid count
+        let mut at = 8 + 4 + 8 + 1 + 36 + 4;
+        assert_eq!(read_u32(&bytes, at), 2);
+        at += 4;
+        let mut names = Vec::new();
+        for _ in 0..2 {
+            let mut strings = Vec::new();
+            for _ in 0..3 {
+                let len = read_u32(&bytes, at);
+                strings.push(String::from_utf8(bytes[at + 4..at + 4 + len].to_vec()).unwrap());
+                at += 4 + len;
+            }
+            assert_eq!(read_i64(&bytes, at), at + 24);
+            let (block_pos, end_pos) = (read_i64(&bytes, at + 8), read_i64(&bytes, at + 16));
+            // Two leaves, each a value mask, the flag and 512 values
+            let value_size = if strings[0] == "density" { 4 } else { 12 };
+            assert_eq!(end_pos - block_pos, 2 * (64 + 1 + 512 * value_size));
+            names.push(strings[0].clone());
+            at = end_pos;
+        }
+        assert_eq!(names, vec!["density", "Cd"]);
+        assert_eq!(at, bytes.len());
+    }
+
+    #[test]
+    fn offsets_put_z_fastest() {
+        assert_eq!(offset([0, 0, 1], LEAF_LOG2, 0), 1);
+        assert_eq!(offset([1, 0, 0], LEAF_LOG2, 0), 64);
+        assert_eq!(offset([9, 0, 0], LEAF_LOG2, 0), 64);
+        assert_eq!(offset([9, 0, 0], INTERNAL1_LOG2, LEAF_LOG2), 256);
+    }
+}
@@ -1,4 +1,5 @@
 use crate::color::*;
+use crate::theme::Theme;
 use bytemuck::{Pod, Zeroable};
 
 #[derive(Clone, Copy)]
@@ -16,7 +17,7 @@ pub struct VoxelVertex {
 #[derive(Clone, Copy)]
 pub struct VoxelInstance {
     _offset: [f32; 3],
-    _col: [f32; 4],
+    _color_index: u32,
 }
 
 unsafe impl Pod for Vertex {}
@@ -28,10 +29,6 @@ unsafe impl Zeroable for VoxelInstance {}
 unsafe impl Pod for VoxelVertex {}
 unsafe impl Zeroable for VoxelVertex {}
 
-pub fn white_vertex(pos: [f32; 3]) -> Vertex {
-    vertex(pos, [1.0; 4])
-}
-
 pub fn vertex(pos: [f32; 3], col: [f32; 4]) -> Vertex {
     Vertex {
         _pos: [pos[0], pos[1], pos[2]],
@@ -39,10 +36,10 @@ pub fn vertex(pos: [f32; 3], col: [f32; 4]) -> Vertex {
     }
 }
 
-pub fn instance(offset: [f32; 3], col: [f32; 4]) -> VoxelInstance {
+pub fn instance(offset: [f32; 3], color_index: u8) -> VoxelInstance {
     VoxelInstance {
         _offset: [offset[0], offset[1], offset[2]],
-        _col: col,
+        _color_index: color_index as u32,
     }
 }
 
@@ -53,61 +50,62 @@ pub fn voxel_vertex(pos: [f32; 3], normal: [f32; 3]) -> VoxelVertex {
     }
 }
 
-pub fn generate_mesh_vertices(meshes: u16) -> (Vec<Vertex>, Vec<u16>) {
+pub fn generate_mesh_vertices(meshes: u16, theme: &Theme) -> (Vec<Vertex>, Vec<u16>) {
     let mut vertex_data = Vec::new();
     let mut index_data: Vec<u16> = Vec::new();
     let mesh_count = meshes as f32;
+    let grid_vertex = |pos| vertex(pos, theme.grid);
 
     // X axis
-    vertex_data.push(vertex([0.0, 0.0, 0.0], RED));
+    vertex_data.push(vertex([0.0, 0.0, 0.0], theme.axes[0]));
     index_data.push((vertex_data.len() - 1) as u16);
-    vertex_data.push(vertex([mesh_count, 0.0, 0.0], RED));
+    vertex_data.push(vertex([mesh_count, 0.0, 0.0], theme.axes[0]));
     index_data.push((vertex_data.len() - 1) as u16);
 
     // Y axis
-    vertex_data.push(vertex([0.0, 0.0, 0.0], GREEN));
+    vertex_data.push(vertex([0.0, 0.0, 0.0], theme.axes[1]));
     index_data.push((vertex_data.len() - 1) as u16);
-    vertex_data.push(vertex([0.0, mesh_count, 0.0], GREEN));
+    vertex_data.push(vertex([0.0, mesh_count, 0.0], theme.axes[1]));
     index_data.push((vertex_data.len() - 1) as u16);
 
     // Z axis
-    vertex_data.push(vertex([0.0, 0.0, 0.0], BLUE));
+    vertex_data.push(vertex([0.0, 0.0, 0.0], theme.axes[2]));
     index_data.push((vertex_data.len() - 1) as u16);
-    vertex_data.push(vertex([0.0, 0.0, mesh_count], BLUE));
+    vertex_data.push(vertex([0.0, 0.0, mesh_count], theme.axes[2]));
     index_data.push((vertex_data.len() - 1) as u16);
 
     for i in 1..(meshes + 1) {
         // back
-        vertex_data.push(white_vertex([0.0, 0.0 + i as f32, 0.0]));
+        vertex_data.push(grid_vertex([0.0, 0.0 + i as f32, 0.0]));
         index_data.push((vertex_data.len() - 1) as u16);
-        vertex_data.push(white_vertex([mesh_count, 0.0 + i as f32, 0.0]));
+        vertex_data.push(grid_vertex([mesh_count, 0.0 + i as f32, 0.0]));
         index_data.push((vertex_data.len() - 1) as u16);
 
-        vertex_data.push(white_vertex([0.0 + i as f32, 0.0, 0.0]));
+        vertex_data.push(grid_vertex([0.0 + i as f32, 0.0, 0.0]));
         index_data.push((vertex_data.len() - 1) as u16);
-        vertex_data.push(white_vertex([0.0 + i as f32, mesh_count, 0.0]));
+        vertex_data.push(grid_vertex([0.0 + i as f32, mesh_count, 0.0]));
         index_data.push((vertex_data.len() - 1) as u16);
 
         // left
-        vertex_data.push(white_vertex([0.0, 0.0 + i as f32, 0.0]));
+        vertex_data.push(grid_vertex([0.0, 0.0 + i as f32, 0.0]));
         index_data.push((vertex_data.len() - 1) as u16);
-        vertex_data.push(white_vertex([0.0, 0.0 + i as f32, mesh_count]));
+        vertex_data.push(grid_vertex([0.0, 0.0 + i as f32, mesh_count]));
         index_data.push((vertex_data.len() - 1) as u16);
 
-        vertex_data.push(white_vertex([0.0, 0.0, 0.0 + i as f32]));
+        vertex_data.push(grid_vertex([0.0, 0.0, 0.0 + i as f32]));
         index_data.push((vertex_data.len() - 1) as u16);
-        vertex_data.push(white_vertex([0.0, mesh_count, 0.0 + i as f32]));
+        vertex_data.push(grid_vertex([0.0, mesh_count, 0.0 + i as f32]));
         index_data.push((vertex_data.len() - 1) as u16);
 
         // bottom
-        vertex_data.push(white_vertex([0.0 + i as f32, 0.0, 0.0]));
+        vertex_data.push(grid_vertex([0.0 + i as f32, 0.0, 0.0]));
         index_data.push((vertex_data.len() - 1) as u16);
-        vertex_data.push(white_vertex([0.0 + i as f32, 0.0, mesh_count]));
+        vertex_data.push(grid_vertex([0.0 + i as f32, 0.0, mesh_count]));
         index_data.push((vertex_data.len() - 1) as u16);
 
-        vertex_data.push(white_vertex([0.0, 0.0, 0.0 + i as f32]));
+        vertex_data.push(grid_vertex([0.0, 0.0, 0.0 + i as f32]));
         index_data.push((vertex_data.len() - 1) as u16);
-        vertex_data.push(white_vertex([mesh_count, 0.0, 0.0 + i as f32]));
+        vertex_data.push(grid_vertex([mesh_count, 0.0, 0.0 + i as f32]));
         index_data.push((vertex_data.len() - 1) as u16);
     }
 
@@ -0,0 +1,18 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Where an orbiting camera looks at the scene from
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CameraView {
+    /// The point in the grid the camera orbits around
+    pub target: [f32; 3],
+    /// Angle around the vertical axis in radians
+    pub yaw: f32,
+    /// Angle above the ground plane in radians
+    pub pitch: f32,
+    /// Distance of the eye from the target in the world
+    pub distance: f32,
+    /// Vertical field of view in degrees
+    pub fov: f32,
+}
@@ -0,0 +1,456 @@
+//! Import of volumetric data, raw 3D sample arrays and NRRD files, as voxels. Samples above a
+//! threshold are filled, their color comes from a transfer function quantized into the palette.
+use crate::quantize;
+use crate::task::{cancelled, Progress};
+use crate::voxel_manager::VoxelManager;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+// Colors a transfer function is quantized into
+const VOLUME_LEVELS: usize = 32;
+// Most samples along an axis of a volume read from a file
+const MAX_VOLUME_SIZE: usize = 4096;
+
+fn invalid_data(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleType {
+    U8,
+    I16,
+    U16,
+    F32,
+}
+
+impl SampleType {
+    pub fn size(self) -> usize {
+        match self {
+            SampleType::U8 => 1,
+            SampleType::I16 | SampleType::U16 => 2,
+            SampleType::F32 => 4,
+        }
+    }
+}
+
+impl FromStr for SampleType {
+    type Err = ();
+
+    /// Accepts the type names of NRRD headers and raw file names
+    fn from_str(name: &str) -> Result<Self, ()> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "uchar" | "unsigned char" | "uint8" | "uint8_t" | "u8" => Ok(SampleType::U8),
+            "short" | "short int" | "signed short" | "signed short int" | "int16" | "int16_t"
+            | "i16" => Ok(SampleType::I16),
+            "ushort" | "unsigned short" | "unsigned short int" | "uint16" | "uint16_t" | "u16" => {
+                Ok(SampleType::U16)
+            }
+            "float" | "float32" | "f32" => Ok(SampleType::F32),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Size and sample type of a headerless raw volume
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RawLayout {
+    pub size: [usize; 3],
+    pub sample_type: SampleType,
+}
+
+fn parse_size(text: &str) -> Option<[usize; 3]> {
+    let mut parts = text.split('x').map(|part| part.parse::<usize>().ok());
+    match (parts.next()??, parts.next()??, parts.next()??, parts.next()) {
+        (x, y, z, None) if [x, y, z].iter().all(|s| *s > 0 && *s <= MAX_VOLUME_SIZE) => {
+            Some([x, y, z])
+        }
+        _ => None,
+    }
+}
+
+/// Reads the three sample distances of NRRD spacings, None when one isn't a positive number
+fn parse_spacing(text: &str) -> Option<[f32; 3]> {
+    let mut parts = text.split_whitespace().map(|part| part.parse::<f32>().ok());
+    let spacing = [parts.next()??, parts.next()??, parts.next()??];
+    if parts.next().is_some() || spacing.iter().any(|s| !s.is_finite() || *s <= 0.0) {
+        return None;
+    }
+    Some(spacing)
+}
+
+impl RawLayout {
+    /// Reads the layout from names like `foot_256x256x256_uint8.raw`
+    pub fn from_file_name(file_path: &str) -> Option<Self> {
+        let stem = Path::new(file_path).file_stem()?.to_str()?;
+        let parts: Vec<&str> = stem.split(|c| c == '_' || c == '.' || c == '-').collect();
+        Some(RawLayout {
+            size: parts.iter().find_map(|part| parse_size(part))?,
+            sample_type: parts.iter().find_map(|part| part.parse().ok())?,
+        })
+    }
+}
+
+impl FromStr for RawLayout {
+    type Err = ();
+
+    /// Parses `<x>x<y>x<z> <type>`, e.g. `256x256x128 uint16`
+    fn from_str(text: &str) -> Result<Self, ()> {
+        let mut parts = text.split_whitespace();
+        let size = parse_size(parts.next().ok_or(())?).ok_or(())?;
+        let sample_type = parts.next().ok_or(())?.parse()?;
+        if parts.next().is_some() {
+            return Err(());
+        }
+        Ok(RawLayout { size, sample_type })
+    }
+}
+
+/// Samples of a volume with x varying fastest, then y, then z
+#[derive(Debug, Clone, PartialEq)]
+pub struct Volume {
+    pub size: [usize; 3],
+    pub samples: Vec<f32>,
+    /// Distance between two samples along each axis, scans are often farther apart across
+    /// their slices
+    pub spacing: [f32; 3],
+}
+
+impl Volume {
+    pub fn from_raw(bytes: &[u8], layout: RawLayout, big_endian: bool) -> io::Result<Self> {
+        let [x, y, z] = layout.size;
+        let sample_size = layout.sample_type.size();
+        let len = x
+            .checked_mul(y)
+            .and_then(|len| len.checked_mul(z))
+            .and_then(|len| len.checked_mul(sample_size))
+            .ok_or_else(|| invalid_data("volume is too large"))?;
+        if bytes.len() < len {
+            return Err(invalid_data("volume data is shorter than its size"));
+        }
+        let samples = bytes[..len]
+            .chunks_exact(sample_size)
+            .map(|sample| {
+                let mut word = [0; 4];
+                word[..sample_size].copy_from_slice(sample);
+                if big_endian {
+                    word[..sample_size].reverse();
+                }
+                match layout.sample_type {
+                    SampleType::U8 => word[0] as f32,
+                    SampleType::I16 => i16::from_le_bytes([word[0], word[1]]) as f32,
+                    SampleType::U16 => u16::from_le_bytes([word[0], word[1]]) as f32,
+                    SampleType::F32 => f32::from_le_bytes(word),
+                }
+            })
+            .collect();
+        Ok(Volume {
+            size: layout.size,
+            samples,
+            spacing: [1.0; 3],
+        })
+    }
+
+    pub fn sample(&self, x: usize, y: usize, z: usize) -> f32 {
+        self.samples[x + self.size[0] * (y + self.size[1] * z)]
+    }
+
+    /// Smallest and largest sample
+    pub fn range(&self) -> (f32, f32) {
+        self.samples
+            .iter()
+            .filter(|sample| sample.is_finite())
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), sample| {
+                (min.min(*sample), max.max(*sample))
+            })
+    }
+}
+
+/// Parses a NRRD file, `dir` is where detached data files are looked up
+pub fn read_nrrd(bytes: &[u8], dir: &Path) -> io::Result<Volume> {
+    if !bytes.starts_with(b"NRRD") {
+        return Err(invalid_data("not a NRRD file"));
+    }
+    let mut sample_type = None;
+    let mut size = None;
+    let mut raw = false;
+    let mut big_endian = false;
+    let mut data_file = None;
+    let mut spacing = None;
+    let mut offset = 0;
+    // The header ends at the first empty line
+    for line in bytes.split(|b| *b == b'\n') {
+        offset += line.len() + 1;
+        let line = String::from_utf8_lossy(line);
+        let line = line.trim_end_matches('\r');
+        if line.is_empty() {
+            break;
+        }
+        if line.starts_with("NRRD") || line.starts_with('#') || line.contains(":=") {
+            continue;
+        }
+        let (key, value) = match line.find(": ") {
+            Some(i) => (&line[..i], line[i + 2..].trim()),
+            None => return Err(invalid_data("malformed NRRD header line")),
+        };
+        match key {
+            "type" => {
+                sample_type = Some(
+                    value
+                        .parse()
+                        .map_err(|_| invalid_data("unsupported NRRD sample type"))?,
+                )
+            }
+            "dimension" if value != "3" => {
+                return Err(invalid_data("only 3D volumes are supported"))
+            }
+            "sizes" => size = parse_size(&value.split_whitespace().collect::<Vec<_>>().join("x")),
+            "encoding" => raw = value == "raw",
+            "endian" => big_endian = value == "big",
+            "data file" | "datafile" => data_file = Some(value.to_owned()),
+            "spacings" => spacing = parse_spacing(value),
+            _ => {}
+        }
+    }
+    if !raw {
+        return Err(invalid_data("only raw encoded NRRD files are supported"));
+    }
+    let layout = RawLayout {
+        size: size.ok_or_else(|| invalid_data("NRRD header has no 3D sizes"))?,
+        sample_type: sample_type.ok_or_else(|| invalid_data("NRRD header has no type"))?,
+    };
+    let mut volume = match data_file {
+        Some(name) => Volume::from_raw(&fs::read(dir.join(name))?, layout, big_endian)?,
+        None => Volume::from_raw(bytes.get(offset..).unwrap_or(&[]), layout, big_endian)?,
+    };
+    if let Some(spacing) = spacing {
+        volume.spacing = spacing;
+    }
+    Ok(volume)
+}
+
+/// Color ramps samples are mapped through
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferFunction {
+    Grayscale,
+    Bone,
+    Heat,
+}
+
+impl TransferFunction {
+    pub const ALL: [TransferFunction; 3] = [
+        TransferFunction::Grayscale,
+        TransferFunction::Bone,
+        TransferFunction::Heat,
+    ];
+
+    fn stops(self) -> &'static [(f32, [f32; 3])] {
+        match self {
+            TransferFunction::Grayscale => &[(0.0, [0.1, 0.1, 0.1]), (1.0, [1.0, 1.0, 1.0])],
+            TransferFunction::Bone => &[
+                (0.0, [0.55, 0.2, 0.15]),
+                (0.5, [0.85, 0.65, 0.5]),
+                (1.0, [1.0, 0.98, 0.92]),
+            ],
+            TransferFunction::Heat => &[
+                (0.0, [0.2, 0.0, 0.3]),
+                (0.35, [0.8, 0.1, 0.1]),
+                (0.7, [1.0, 0.7, 0.0]),
+                (1.0, [1.0, 1.0, 0.8]),
+            ],
+        }
+    }
+
+    /// Color of a sample between 0 and 1
+    pub fn color(self, t: f32) -> [f32; 4] {
+        let stops = self.stops();
+        let t = t.max(0.0).min(1.0);
+        let i = stops
+            .iter()
+            .position(|(at, _)| *at >= t)
+            .unwrap_or(stops.len() - 1);
+        if i == 0 {
+            let [r, g, b] = stops[0].1;
+            return [r, g, b, 1.0];
+        }
+        let (from, a) = stops[i - 1];
+        let (to, b) = stops[i];
+        let f = (t - from) / (to - from);
+        [
+            a[0] + (b[0] - a[0]) * f,
+            a[1] + (b[1] - a[1]) * f,
+            a[2] + (b[2] - a[2]) * f,
+            1.0,
+        ]
+    }
+}
+
+impl Default for TransferFunction {
+    fn default() -> TransferFunction {
+        TransferFunction::Grayscale
+    }
+}
+
+impl std::fmt::Display for TransferFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl FromStr for TransferFunction {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Self, ()> {
+        TransferFunction::ALL
+            .iter()
+            .find(|transfer| transfer.to_string() == name)
+            .copied()
+            .ok_or(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VolumeOptions {
+    /// Samples below this fraction of the value range stay empty
+    pub threshold: f32,
+    pub transfer: TransferFunction,
+    /// Layout of raw files, read from the file name when None
+    pub layout: Option<RawLayout>,
+    /// Most colors the voxels are quantized to, None keeps a color for every level
+    pub colors: Option<usize>,
+    /// Dithers the quantized colors
+    pub dither: bool,
+}
+
+/// Voxels of the volume, scaled down to fit the extent. The volume's z axis, the slice
+/// direction of scans, becomes the up axis. The voxels are as long along each axis as the
+/// spacing of the samples, relative to the closest spacing.
+pub fn to_voxels(
+    volume: &Volume,
+    options: &VolumeOptions,
+    extent: usize,
+    progress: &mut Progress,
+) -> io::Result<VoxelManager> {
+    let mut voxel_manager = VoxelManager::new(extent);
+    let [sx, sy, sz] = volume.spacing;
+    let closest = sx.min(sy).min(sz);
+    voxel_manager.set_voxel_scale([sx / closest, sz / closest, sy / closest]);
+    let (min, max) = volume.range();
+    let range = if max > min { max - min } else { 1.0 };
+    let step = (*volume.size.iter().max().unwrap_or(&1) as f32 / extent as f32).max(1.0);
+    let cells = |axis: usize| ((volume.size[axis] as f32 / step).ceil() as usize).min(extent);
+    let sample =
+        |i: usize, axis: usize| (((i as f32 + 0.5) * step) as usize).min(volume.size[axis] - 1);
+
+    let (width, height, depth) = (cells(0), cells(2), cells(1));
+    for y in 0..height {
+        if !progress(y as f32 / height as f32) {
+            return Err(cancelled());
+        }
+        for z in 0..depth {
+            for x in 0..width {
+                let value = volume.sample(sample(x, 0), sample(z, 1), sample(y, 2));
+                let t = (value - min) / range;
+                if t.is_nan() || t < options.threshold {
+                    continue;
+                }
+                let level = ((t * VOLUME_LEVELS as f32) as usize).min(VOLUME_LEVELS - 1);
+                let color = options
+                    .transfer
+                    .color((level as f32 + 0.5) / VOLUME_LEVELS as f32);
+                let color_index = voxel_manager.palette_mut().index_of(color);
+                voxel_manager.set_voxel(x, y, z, Some(color_index));
+            }
+        }
+    }
+    if let Some(colors) = options.colors {
+        quantize::quantize(&mut voxel_manager, colors, options.dither);
+    }
+    Ok(voxel_manager)
+}
+
+/// Reads a .nrrd/.nhdr file or a raw volume and turns it into voxels
+pub fn import_volume(
+    file_path: &str,
+    options: &VolumeOptions,
+    extent: usize,
+    progress: &mut Progress,
+) -> io::Result<VoxelManager> {
+    let path = Path::new(file_path);
+    let bytes = fs::read(path)?;
+    let volume = if bytes.starts_with(b"NRRD") {
+        read_nrrd(&bytes, path.parent().unwrap_or_else(|| Path::new("")))?
+    } else {
+        let layout = options
+            .layout
+            .or_else(|| RawLayout::from_file_name(file_path))
+            .ok_or_else(|| invalid_data("the raw volume's size and type are unknown"))?;
+        Volume::from_raw(&bytes, layout, false)?
+    };
+    to_voxels(&volume, options, extent, progress)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn raw_layouts_are_read_from_file_names() {
+        assert_eq!(
+            RawLayout::from_file_name("data/foot_256x256x128_uint16.raw"),
+            Some(RawLayout {
+                size: [256, 256, 128],
+                sample_type: SampleType::U16,
+            })
+        );
+        assert_eq!(RawLayout::from_file_name("foot.raw"), None);
+        assert_eq!(
+            "4x2x3 float".parse(),
+            Ok(RawLayout {
+                size: [4, 2, 3],
+                sample_type: SampleType::F32,
+            })
+        );
+        assert_eq!("4x2x5000 float".parse::<RawLayout>(), Err(()));
+        let huge = RawLayout {
+            size: [usize::MAX, 2, 1],
+            sample_type: SampleType::U8,
+        };
+        assert!(Volume::from_raw(&[0; 8], huge, false).is_err());
+    }
+
+    #[test]
+    fn nrrd_samples_above_the_threshold_become_voxels() {
+        let mut bytes = b"NRRD0004\n# comment\ntype: short\ndimension: 3\nsizes: 2 1 2\nspacings: 0.5 0.5 1.5\nendian: big\nencoding: raw\n\n".to_vec();
+        for sample in [0i16, 100, 200, 400].iter() {
+            bytes.extend_from_slice(&sample.to_be_bytes());
+        }
+        let volume = read_nrrd(&bytes, Path::new("")).unwrap();
+        assert_eq!(volume.size, [2, 1, 2]);
+        assert_eq!(volume.sample(1, 0, 1), 400.0);
+
+        let options = VolumeOptions {
+            threshold: 0.4,
+            transfer: TransferFunction::Grayscale,
+            layout: None,
+            colors: None,
+            dither: false,
+        };
+        let voxel_manager = to_voxels(&volume, &options, 4, &mut |_| true).unwrap();
+        // The slices are three times as far apart as the samples within them
+        assert_eq!(voxel_manager.voxel_scale(), [1.0, 3.0, 1.0]);
+        // The volume's z axis is the grid's y axis
+        assert_eq!(voxel_manager.voxel(0, 0, 0), None);
+        assert_eq!(voxel_manager.voxel(1, 0, 0), None);
+        assert!(voxel_manager.voxel(0, 1, 0).is_some());
+        assert!(voxel_manager.voxel(1, 1, 0).is_some());
+        // Brighter samples get brighter colors
+        let color = |x| {
+            let index = voxel_manager.voxel(x, 1, 0).unwrap();
+            voxel_manager.palette().color(index)[0]
+        };
+        assert!(color(1) > color(0));
+    }
+}
@@ -0,0 +1,925 @@
+use crate::color::{linear, quantized, srgb};
+use crate::geometry::{BoundingBox, Ray};
+use crate::palette::Palette;
+use crate::selection::Mask;
+use crate::transform::RadialSymmetry;
+use crate::vertex::{instance, VoxelInstance, VoxelVertex};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+#[cfg(feature = "serde")]
+use std::convert::TryFrom;
+
+/// Edge length of the cubic chunks the grid is split into for rendering
+pub const CHUNK_SIZE: usize = 8;
+/// vertices() meshes every visible voxel as 4 vertices for each of its 6 faces
+pub const VERTICES_PER_VOXEL: usize = 24;
+/// Largest extent of a grid read from files and scripts, its cells take a few hundred MB
+pub const MAX_EXTENT: usize = 256;
+
+/// A voxel hit under the cursor and the normal of the face that was hit
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Pick {
+    pub voxel: [usize; 3],
+    pub normal: [i32; 3],
+}
+
+#[derive(Copy, Clone, Default)]
+struct CubeDescriptor {
+    color_index: Option<u8>,
+    neighbours: usize,
+}
+
+impl CubeDescriptor {
+    fn incr(&mut self) {
+        self.neighbours += 1;
+        debug_assert!(self.neighbours < 7);
+    }
+
+    fn decr(&mut self) {
+        if self.neighbours > 0 {
+            self.neighbours -= 1;
+        }
+    }
+
+    fn visible(&self) -> bool {
+        self.neighbours != 6
+    }
+}
+
+/// Serialized as its extent, palette and filled cells
+#[derive(Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(try_from = "SceneData", into = "SceneData")
+)]
+pub struct VoxelManager {
+    boxes: Vec<Vec<Vec<CubeDescriptor>>>,
+    extent: usize,
+    dirty_chunks: Vec<bool>,
+    // Chunks changed since they were last appended to the journal
+    unjournaled_chunks: Vec<bool>,
+    // Chunks changed since the session recording last captured them
+    unrecorded_chunks: Vec<bool>,
+    // Incremented on every change, so views of the scene know when to refresh
+    revision: u64,
+    palette: Palette,
+    // Cells outside of it are left alone by every edit
+    mask: Option<Mask>,
+    // Edits of a cell are repeated on its turned copies
+    symmetry: Option<RadialSymmetry>,
+    // Position of the grid's corner in the meshes, exports move it to center the voxels
+    origin: [f32; 3],
+    // Size of a cell along each axis in the meshes, for voxels that aren't cubes
+    voxel_scale: [f32; 3],
+}
+
+impl VoxelManager {
+    pub fn new(extent: usize) -> Self {
+        let chunks_per_axis = (extent + CHUNK_SIZE - 1) / CHUNK_SIZE;
+        VoxelManager {
+            boxes: vec![vec![vec![Default::default(); extent]; extent]; extent],
+            extent,
+            dirty_chunks: vec![false; chunks_per_axis * chunks_per_axis * chunks_per_axis],
+            unjournaled_chunks: vec![false; chunks_per_axis * chunks_per_axis * chunks_per_axis],
+            unrecorded_chunks: vec![false; chunks_per_axis * chunks_per_axis * chunks_per_axis],
+            revision: 0,
+            palette: Palette::new(),
+            mask: None,
+            symmetry: None,
+            origin: [0.0; 3],
+            voxel_scale: [1.0; 3],
+        }
+    }
+
+    /// Limits the following edits to the cells the mask allows
+    pub fn set_mask(&mut self, mask: Option<Mask>) {
+        self.mask = mask;
+    }
+
+    pub fn take_mask(&mut self) -> Option<Mask> {
+        self.mask.take()
+    }
+
+    /// Repeats the following edits around the symmetry's axis
+    pub fn set_symmetry(&mut self, symmetry: Option<RadialSymmetry>) {
+        self.symmetry = symmetry;
+    }
+
+    /// The cell and the cells its edits are repeated on
+    fn images(&self, x: usize, y: usize, z: usize) -> Vec<[usize; 3]> {
+        match &self.symmetry {
+            Some(symmetry) => symmetry.images([x, y, z], self.extent),
+            None => vec![[x, y, z]],
+        }
+    }
+
+    fn editable(&self, x: usize, y: usize, z: usize) -> bool {
+        self.mask
+            .as_ref()
+            .map_or(true, |mask| mask.allows([x, y, z]))
+    }
+
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    pub fn palette_mut(&mut self) -> &mut Palette {
+        &mut self.palette
+    }
+
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+
+    /// Flags a palette entry as glowing like Palette::set_emissive, as a change of the scene
+    pub fn set_emissive(&mut self, index: u8, emissive: bool) {
+        self.palette.set_emissive(index, emissive);
+        self.revision += 1;
+    }
+
+    pub fn extent(&self) -> usize {
+        self.extent
+    }
+
+    pub fn origin(&self) -> [f32; 3] {
+        self.origin
+    }
+
+    /// Moves the meshes of the voxels by the origin, the cells stay where they are
+    pub fn set_origin(&mut self, origin: [f32; 3]) {
+        self.origin = origin;
+    }
+
+    pub fn voxel_scale(&self) -> [f32; 3] {
+        self.voxel_scale
+    }
+
+    /// Stretches the meshes of the voxels by the size of a cell along each axis, the cells stay
+    /// where they are. Counts as a change of the scene.
+    pub fn set_voxel_scale(&mut self, voxel_scale: [f32; 3]) {
+        self.voxel_scale = voxel_scale;
+        self.revision += 1;
+    }
+
+    /// Where the corner of the cell is in the meshes
+    pub fn mesh_position(&self, [x, y, z]: [usize; 3]) -> cgmath::Vector3<f32> {
+        let [sx, sy, sz] = self.voxel_scale;
+        cgmath::Vector3::new(x as f32 * sx, y as f32 * sy, z as f32 * sz)
+            + cgmath::Vector3::from(self.origin)
+    }
+
+    /// Replaces the palette and renumbers the voxels by the map from the old indices to the new
+    /// ones. Unlike set_voxel it reaches every voxel, whatever the mask and the symmetry.
+    /// Entries the map merges glow when any of them did.
+    pub fn remap_colors(&mut self, mut palette: Palette, map: &[u8]) {
+        for index in self.palette.emissive() {
+            palette.set_emissive(map[index as usize], true);
+        }
+        let extent = self.extent;
+        for x in 0..extent {
+            for y in 0..extent {
+                for z in 0..extent {
+                    let index = match self.boxes[x][y][z].color_index {
+                        Some(index) => index,
+                        None => continue,
+                    };
+                    if map[index as usize] != index {
+                        self.boxes[x][y][z].color_index = Some(map[index as usize]);
+                        self.mark_dirty(x, y, z);
+                    }
+                }
+            }
+        }
+        self.set_palette(palette);
+        self.revision += 1;
+    }
+
+    pub fn voxel(&self, x: usize, y: usize, z: usize) -> Option<u8> {
+        self.boxes[x][y][z].color_index
+    }
+
+    /// Sets or clears a single voxel keeping the neighbour counts up to date
+    pub fn set_voxel(&mut self, x: usize, y: usize, z: usize, color_index: Option<u8>) {
+        for [x, y, z] in self.images(x, y, z) {
+            self.set_cell(x, y, z, color_index);
+        }
+    }
+
+    fn set_cell(&mut self, x: usize, y: usize, z: usize, color_index: Option<u8>) {
+        if !self.editable(x, y, z) {
+            return;
+        }
+        let previous = std::mem::replace(&mut self.boxes[x][y][z].color_index, color_index);
+        if previous == color_index {
+            return;
+        }
+        self.mark_dirty(x, y, z);
+        match (previous, color_index) {
+            (None, Some(_)) => {
+                for [nx, ny, nz] in self.get_neighbour_indices(x, y, z) {
+                    self.boxes[nx][ny][nz].incr();
+                    self.mark_dirty(nx, ny, nz);
+                }
+            }
+            (Some(_), None) => {
+                for [nx, ny, nz] in self.get_neighbour_indices(x, y, z) {
+                    self.boxes[nx][ny][nz].decr();
+                    self.mark_dirty(nx, ny, nz);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Recolors a voxel, or fills it when it's empty and fill_empty is set.
+    /// Cells outside of the grid are ignored.
+    pub fn paint_cell(&mut self, cell: [i32; 3], color: [f32; 4], fill_empty: bool) {
+        let extent = self.extent as i32;
+        if cell.iter().any(|c| *c < 0 || *c >= extent) {
+            return;
+        }
+        let color_index = self.palette.index_of(color);
+        for [x, y, z] in self.images(cell[0] as usize, cell[1] as usize, cell[2] as usize) {
+            if fill_empty || self.boxes[x][y][z].color_index.is_some() {
+                self.set_cell(x, y, z, Some(color_index));
+            }
+        }
+    }
+
+    /// Replaces the color of the filled cells with the quantized average of their filled
+    /// neighbourhood, taken in linear light. Empty cells and cells outside of the grid are
+    /// ignored.
+    pub fn smooth_colors(&mut self, cells: &[[i32; 3]]) {
+        let extent = self.extent as i32;
+        let mut smoothed = Vec::new();
+        for cell in cells.iter() {
+            if cell.iter().any(|c| *c < 0 || *c >= extent) {
+                continue;
+            }
+            for [x, y, z] in self.images(cell[0] as usize, cell[1] as usize, cell[2] as usize) {
+                let mut sum = match self.boxes[x][y][z].color_index {
+                    Some(index) => linear(self.palette.color(index)),
+                    None => continue,
+                };
+                let mut count = 1.0;
+                for [nx, ny, nz] in self.get_neighbour_indices(x, y, z) {
+                    if let Some(index) = self.boxes[nx][ny][nz].color_index {
+                        let color = linear(self.palette.color(index));
+                        for (total, channel) in sum.iter_mut().zip(color.iter()) {
+                            *total += channel;
+                        }
+                        count += 1.0;
+                    }
+                }
+                for total in sum.iter_mut() {
+                    *total /= count;
+                }
+                let mut average = srgb(sum);
+                for channel in average.iter_mut() {
+                    *channel = quantized(*channel);
+                }
+                smoothed.push(([x, y, z], average));
+            }
+        }
+        // Average from the original colors before writing any of them back
+        for ([x, y, z], color) in smoothed {
+            let color_index = self.palette.index_of(color);
+            self.set_cell(x, y, z, Some(color_index));
+        }
+    }
+
+    /// Returns the grid coordinates of the cells inside a chunk
+    pub fn chunk_cells(&self, chunk: usize) -> Vec<[usize; 3]> {
+        let cpa = self.chunks_per_axis();
+        let origin_x = chunk / (cpa * cpa) * CHUNK_SIZE;
+        let origin_y = chunk / cpa % cpa * CHUNK_SIZE;
+        let origin_z = chunk % cpa * CHUNK_SIZE;
+        let mut cells = Vec::new();
+        for x in origin_x..(origin_x + CHUNK_SIZE).min(self.extent) {
+            for y in origin_y..(origin_y + CHUNK_SIZE).min(self.extent) {
+                for z in origin_z..(origin_z + CHUNK_SIZE).min(self.extent) {
+                    cells.push([x, y, z]);
+                }
+            }
+        }
+        cells
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.dirty_chunks.len()
+    }
+
+    /// Approximate number of bytes used by the grid and the palette
+    pub fn memory_usage(&self) -> usize {
+        self.extent * self.extent * self.extent * std::mem::size_of::<CubeDescriptor>()
+            + self.dirty_chunks.len()
+            + self.unjournaled_chunks.len()
+            + self.unrecorded_chunks.len()
+            + self.palette.colors().len() * std::mem::size_of::<[f32; 4]>()
+    }
+
+    fn chunks_per_axis(&self) -> usize {
+        (self.extent + CHUNK_SIZE - 1) / CHUNK_SIZE
+    }
+
+    fn chunk_index(&self, x: usize, y: usize, z: usize) -> usize {
+        let cpa = self.chunks_per_axis();
+        (x / CHUNK_SIZE) * cpa * cpa + (y / CHUNK_SIZE) * cpa + z / CHUNK_SIZE
+    }
+
+    fn mark_dirty(&mut self, x: usize, y: usize, z: usize) {
+        let idx = self.chunk_index(x, y, z);
+        self.dirty_chunks[idx] = true;
+        self.unjournaled_chunks[idx] = true;
+        self.unrecorded_chunks[idx] = true;
+        self.revision += 1;
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn mark_all_dirty(&mut self) {
+        self.revision += 1;
+        for dirty in self.dirty_chunks.iter_mut() {
+            *dirty = true;
+        }
+        for unjournaled in self.unjournaled_chunks.iter_mut() {
+            *unjournaled = true;
+        }
+        for unrecorded in self.unrecorded_chunks.iter_mut() {
+            *unrecorded = true;
+        }
+    }
+
+    /// Returns the indices of the chunks changed since the last call and clears their dirty flag
+    pub fn take_dirty_chunks(&mut self) -> Vec<usize> {
+        let mut dirty = Vec::new();
+        for (idx, flag) in self.dirty_chunks.iter_mut().enumerate() {
+            if *flag {
+                dirty.push(idx);
+                *flag = false;
+            }
+        }
+        dirty
+    }
+
+    /// Indices of the chunks changed since the journal last recorded them
+    pub fn unjournaled_chunks(&self) -> Vec<usize> {
+        (0..self.unjournaled_chunks.len())
+            .filter(|idx| self.unjournaled_chunks[*idx])
+            .collect()
+    }
+
+    /// Marks every chunk as recorded, after appending them to the journal or loading the scene
+    pub fn clear_unjournaled_chunks(&mut self) {
+        for unjournaled in self.unjournaled_chunks.iter_mut() {
+            *unjournaled = false;
+        }
+    }
+
+    /// Indices of the chunks changed since the session recording last captured them
+    pub fn unrecorded_chunks(&self) -> Vec<usize> {
+        (0..self.unrecorded_chunks.len())
+            .filter(|idx| self.unrecorded_chunks[*idx])
+            .collect()
+    }
+
+    /// Marks every chunk as captured, after the session recording wrote them
+    pub fn clear_unrecorded_chunks(&mut self) {
+        for unrecorded in self.unrecorded_chunks.iter_mut() {
+            *unrecorded = false;
+        }
+    }
+
+    pub fn add_box(&mut self, bbox: BoundingBox) {
+        let color_index = self.palette.index_of(bbox.color);
+        for [x, y, z] in bbox.cell_box().grid_cells(self.extent) {
+            for [x, y, z] in self.images(x, y, z) {
+                if !self.editable(x, y, z) {
+                    continue;
+                }
+                self.mark_dirty(x, y, z);
+                if self.boxes[x][y][z]
+                    .color_index
+                    .replace(color_index)
+                    .is_none()
+                {
+                    for [nx, ny, nz] in self.get_neighbour_indices(x, y, z) {
+                        self.boxes[nx][ny][nz].incr();
+                        self.mark_dirty(nx, ny, nz);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn erase_box(&mut self, bbox: BoundingBox) {
+        for [x, y, z] in bbox.cell_box().grid_cells(self.extent) {
+            for [x, y, z] in self.images(x, y, z) {
+                if !self.editable(x, y, z) {
+                    continue;
+                }
+                if self.boxes[x][y][z].color_index.take().is_some() {
+                    self.mark_dirty(x, y, z);
+                    for [nx, ny, nz] in self.get_neighbour_indices(x, y, z) {
+                        self.boxes[nx][ny][nz].decr();
+                        self.mark_dirty(nx, ny, nz);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn erase_cells(&mut self, cells: &[[usize; 3]]) {
+        for [x, y, z] in cells.iter() {
+            self.set_voxel(*x, *y, *z, None);
+        }
+    }
+
+    pub fn refill(&mut self, bbox: BoundingBox) {
+        let color_index = self.palette.index_of(bbox.color);
+        for [x, y, z] in bbox.cell_box().grid_cells(self.extent) {
+            for [x, y, z] in self.images(x, y, z) {
+                if self.boxes[x][y][z].color_index.is_some() && self.editable(x, y, z) {
+                    self.boxes[x][y][z].color_index = Some(color_index);
+                    self.mark_dirty(x, y, z);
+                }
+            }
+        }
+    }
+
+    fn get_neighbour_indices(&self, pos_x: usize, pos_y: usize, pos_z: usize) -> Vec<[usize; 3]> {
+        let mut neighbours = Vec::new();
+        let min_x = pos_x.max(1) - 1;
+        let min_y = pos_y.max(1) - 1;
+        let min_z = pos_z.max(1) - 1;
+
+        let max_x = (pos_x + 1).min(self.extent - 1);
+        let max_y = (pos_y + 1).min(self.extent - 1);
+        let max_z = (pos_z + 1).min(self.extent - 1);
+        for x in min_x..=max_x {
+            if x != pos_x {
+                neighbours.push([x, pos_y, pos_z]);
+            }
+        }
+        for y in min_y..=max_y {
+            if y != pos_y {
+                neighbours.push([pos_x, y, pos_z]);
+            }
+        }
+        for z in min_z..=max_z {
+            if z != pos_z {
+                neighbours.push([pos_x, pos_y, z]);
+            }
+        }
+        debug_assert!(neighbours.len() < 7, "neighbours {:?}", neighbours.len());
+        neighbours
+    }
+
+    fn get_neighbour_boxes(
+        &mut self,
+        pos_x: usize,
+        pos_y: usize,
+        pos_z: usize,
+    ) -> Vec<BoundingBox> {
+        let mut origins = Vec::new();
+        for [nx, ny, nz] in self.get_neighbour_indices(pos_x, pos_y, pos_z) {
+            if self.boxes[nx][ny][nz].color_index.is_none() {
+                origins.push(BoundingBox::new(
+                    cgmath::Vector3::new(nx as f32, ny as f32, nz as f32),
+                    cgmath::Vector3::new(1.0, 1.0, 1.0),
+                    [1.0; 4],
+                ));
+            }
+        }
+        origins
+    }
+
+    fn cell_box(&self, x: usize, y: usize, z: usize) -> Option<BoundingBox> {
+        self.boxes[x][y][z].color_index.map(|color_index| {
+            BoundingBox::new(
+                cgmath::Vector3::new(x as f32, y as f32, z as f32),
+                cgmath::Vector3::new(1.0, 1.0, 1.0),
+                self.palette.color(color_index),
+            )
+        })
+    }
+
+    /// Same as get_intersection_boxes, but for a voxel picked from the id buffer
+    pub fn get_picked_boxes(&self, pick: Pick) -> (Option<BoundingBox>, Option<BoundingBox>) {
+        let [x, y, z] = pick.voxel;
+        if x >= self.extent || y >= self.extent || z >= self.extent {
+            return (None, None);
+        }
+        let erase_box = self.cell_box(x, y, z);
+        let [nx, ny, nz] = [
+            x as i32 + pick.normal[0],
+            y as i32 + pick.normal[1],
+            z as i32 + pick.normal[2],
+        ];
+        let extent = self.extent as i32;
+        let mut draw_box = erase_box;
+        if erase_box.is_some()
+            && (0..extent).contains(&nx)
+            && (0..extent).contains(&ny)
+            && (0..extent).contains(&nz)
+            && self.boxes[nx as usize][ny as usize][nz as usize]
+                .color_index
+                .is_none()
+        {
+            draw_box = Some(BoundingBox::new(
+                cgmath::Vector3::new(nx as f32, ny as f32, nz as f32),
+                cgmath::Vector3::new(1.0, 1.0, 1.0),
+                [1.0; 4],
+            ));
+        }
+        (erase_box, draw_box)
+    }
+
+    pub fn get_intersection_boxes(
+        &mut self,
+        ray: &Ray,
+    ) -> (Option<BoundingBox>, Option<BoundingBox>) {
+        let mut erase_box = None;
+        let mut draw_box = None;
+        let mut closest_distance = 100000.0;
+        let mut distance = 1.0;
+        let mut bbox;
+        for x in 0..self.extent {
+            for y in 0..self.extent {
+                for z in 0..self.extent {
+                    if let Some(color_index) = self.boxes[x][y][z].color_index {
+                        if !self.boxes[x][y][z].visible() {
+                            continue;
+                        }
+                        bbox = BoundingBox::new(
+                            cgmath::Vector3::new(x as f32, y as f32, z as f32),
+                            cgmath::Vector3::new(1.0, 1.0, 1.0),
+                            self.palette.color(color_index),
+                        );
+
+                        if ray.box_intersection(&bbox, &mut distance) {
+                            if distance.abs() < closest_distance {
+                                closest_distance = distance.abs();
+                                erase_box = Some(bbox);
+                                if cfg!(feature = "debug_ray") {
+                                    self.boxes[x][y][z].color_index =
+                                        Some(self.palette.index_of([0.0, 0.0, 1.0, 1.0]));
+                                }
+                            }
+                        } else if cfg!(feature = "debug_ray") {
+                            self.boxes[x][y][z].color_index =
+                                Some(self.palette.index_of([0.0, 1.0, 0.0, 0.1]));
+                        }
+                    }
+                }
+            }
+        }
+        if let Some(bbox) = erase_box {
+            draw_box = erase_box;
+            let boxes = self.get_neighbour_boxes(
+                bbox.corner.x as usize,
+                bbox.corner.y as usize,
+                bbox.corner.z as usize,
+            );
+            for bbox in boxes {
+                if ray.box_intersection(&bbox, &mut distance) {
+                    if distance.abs() < closest_distance {
+                        closest_distance = distance.abs();
+                        draw_box = Some(bbox);
+                    }
+                }
+            }
+        }
+        (erase_box, draw_box)
+    }
+
+    pub fn vertices(&self) -> (Vec<VoxelVertex>, Vec<u32>) {
+        let mut vertex_data = Vec::new();
+        let mut index_data = Vec::new();
+        let mut step;
+        let mut idx;
+        let mut bbox;
+        for x in 0..self.extent {
+            for y in 0..self.extent {
+                for z in 0..self.extent {
+                    if let Some(color_index) = self.boxes[x][y][z].color_index {
+                        if !self.boxes[x][y][z].visible() {
+                            continue;
+                        }
+                        idx = vertex_data.len() as u32;
+                        for i in 0..6 {
+                            step = 4 * i;
+                            index_data.extend_from_slice(&[
+                                idx + step,
+                                idx + 1 + step,
+                                idx + 2 + step,
+                                idx + 2 + step,
+                                idx + 3 + step,
+                                idx + step,
+                            ]);
+                        }
+                        bbox = BoundingBox::new(
+                            self.mesh_position([x, y, z]),
+                            cgmath::Vector3::from(self.voxel_scale),
+                            self.palette.color(color_index),
+                        );
+                        vertex_data.append(&mut bbox.voxel_vertices());
+                    }
+                }
+            }
+        }
+        (vertex_data, index_data)
+    }
+
+    /// Meshes the visible voxels separately for every palette index in use,
+    /// for renderers drawing each color with its own material
+    pub fn vertices_by_color(&self) -> Vec<(u8, Vec<VoxelVertex>, Vec<u32>)> {
+        let extent = self.extent;
+        let cells = (0..extent)
+            .flat_map(move |x| (0..extent).flat_map(move |y| (0..extent).map(move |z| [x, y, z])));
+        self.mesh_by_color(cells, self.origin)
+    }
+
+    /// Meshes the visible voxels of the cells like vertices_by_color(), placed as if the scene
+    /// had the given origin. Meshes a single object without copying it into a scene of its own.
+    pub fn cells_by_color(
+        &self,
+        cells: &[[usize; 3]],
+        origin: [f32; 3],
+    ) -> Vec<(u8, Vec<VoxelVertex>, Vec<u32>)> {
+        self.mesh_by_color(cells.iter().cloned(), origin)
+    }
+
+    fn mesh_by_color(
+        &self,
+        cells: impl Iterator<Item = [usize; 3]>,
+        origin: [f32; 3],
+    ) -> Vec<(u8, Vec<VoxelVertex>, Vec<u32>)> {
+        let [sx, sy, sz] = self.voxel_scale;
+        let mut parts: BTreeMap<u8, (Vec<VoxelVertex>, Vec<u32>)> = BTreeMap::new();
+        for [x, y, z] in cells {
+            let color_index = match self.boxes[x][y][z].color_index {
+                Some(color_index) if self.boxes[x][y][z].visible() => color_index,
+                _ => continue,
+            };
+            let (vertex_data, index_data) = parts.entry(color_index).or_default();
+            let idx = vertex_data.len() as u32;
+            for i in 0..6 {
+                let step = 4 * i;
+                index_data.extend_from_slice(&[
+                    idx + step,
+                    idx + 1 + step,
+                    idx + 2 + step,
+                    idx + 2 + step,
+                    idx + 3 + step,
+                    idx + step,
+                ]);
+            }
+            let bbox = BoundingBox::new(
+                cgmath::Vector3::new(x as f32 * sx, y as f32 * sy, z as f32 * sz)
+                    + cgmath::Vector3::from(origin),
+                cgmath::Vector3::from(self.voxel_scale),
+                self.palette.color(color_index),
+            );
+            vertex_data.append(&mut bbox.voxel_vertices());
+        }
+        parts
+            .into_iter()
+            .map(|(color_index, (vertex_data, index_data))| (color_index, vertex_data, index_data))
+            .collect()
+    }
+
+    /// Palette index of every vertex of vertices()
+    pub fn vertex_color_indices(&self) -> Vec<u8> {
+        self.visible_color_indices()
+            .into_iter()
+            .flat_map(|index| std::iter::repeat(index).take(VERTICES_PER_VOXEL))
+            .collect()
+    }
+
+    /// Color of every vertex of vertices()
+    pub fn vertex_colors(&self) -> Vec<[f32; 4]> {
+        self.vertex_color_indices()
+            .into_iter()
+            .map(|index| self.palette.color(index))
+            .collect()
+    }
+
+    /// Color indices of the visible voxels, in the order vertices() meshes them
+    pub fn visible_color_indices(&self) -> Vec<u8> {
+        let mut indices = Vec::new();
+        for x in 0..self.extent {
+            for y in 0..self.extent {
+                for z in 0..self.extent {
+                    if let Some(color_index) = self.boxes[x][y][z].color_index {
+                        if self.boxes[x][y][z].visible() {
+                            indices.push(color_index);
+                        }
+                    }
+                }
+            }
+        }
+        indices
+    }
+
+    /// Collects the instance data of the visible voxels inside a single chunk
+    pub fn chunk_instance_data(&self, chunk: usize) -> Vec<VoxelInstance> {
+        let mut instance_data = Vec::new();
+        for [x, y, z] in self.chunk_cells(chunk) {
+            if let Some(color_index) = self.boxes[x][y][z].color_index {
+                if self.boxes[x][y][z].visible() {
+                    instance_data.push(instance([x as f32, y as f32, z as f32], color_index));
+                }
+            }
+        }
+        instance_data
+    }
+}
+
+/// The serialized form of a scene, listing only the filled cells
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct SceneData {
+    extent: usize,
+    palette: Palette,
+    voxels: Vec<([usize; 3], u8)>,
+    #[serde(default = "cubic")]
+    voxel_scale: [f32; 3],
+    #[serde(default)]
+    origin: [f32; 3],
+}
+
+#[cfg(feature = "serde")]
+fn cubic() -> [f32; 3] {
+    [1.0; 3]
+}
+
+#[cfg(feature = "serde")]
+impl From<VoxelManager> for SceneData {
+    fn from(voxel_manager: VoxelManager) -> Self {
+        let mut voxels = Vec::new();
+        for x in 0..voxel_manager.extent {
+            for y in 0..voxel_manager.extent {
+                for z in 0..voxel_manager.extent {
+                    if let Some(color_index) = voxel_manager.voxel(x, y, z) {
+                        voxels.push(([x, y, z], color_index));
+                    }
+                }
+            }
+        }
+        SceneData {
+            extent: voxel_manager.extent,
+            palette: voxel_manager.palette,
+            voxels,
+            voxel_scale: voxel_manager.voxel_scale,
+            origin: voxel_manager.origin,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<SceneData> for VoxelManager {
+    type Error = String;
+
+    fn try_from(data: SceneData) -> Result<Self, String> {
+        if data.extent == 0 || data.extent > MAX_EXTENT {
+            return Err(format!(
+                "grid extent {} is not between 1 and {}",
+                data.extent, MAX_EXTENT
+            ));
+        }
+        let mut voxel_manager = VoxelManager::new(data.extent);
+        let color_count = data.palette.colors().len();
+        voxel_manager.set_palette(data.palette);
+        voxel_manager.set_voxel_scale(data.voxel_scale);
+        voxel_manager.set_origin(data.origin);
+        for ([x, y, z], color_index) in data.voxels {
+            if x >= data.extent || y >= data.extent || z >= data.extent {
+                return Err(format!("voxel {:?} is outside the grid", [x, y, z]));
+            }
+            if color_index as usize >= color_count {
+                return Err(format!("color index {} is not in the palette", color_index));
+            }
+            voxel_manager.set_voxel(x, y, z, Some(color_index));
+        }
+        voxel_manager.mark_all_dirty();
+        Ok(voxel_manager)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use cgmath::Vector3;
+
+    #[test]
+    fn vertices_by_color_splits_the_mesh() {
+        let mut voxel_manager = VoxelManager::new(8);
+        let red = voxel_manager.palette_mut().index_of([1.0, 0.0, 0.0, 1.0]);
+        let blue = voxel_manager.palette_mut().index_of([0.0, 0.0, 1.0, 1.0]);
+        voxel_manager.set_voxel(0, 0, 0, Some(blue));
+        voxel_manager.set_voxel(3, 3, 3, Some(red));
+        voxel_manager.set_voxel(5, 5, 5, Some(red));
+
+        let parts = voxel_manager.vertices_by_color();
+        assert_eq!(parts.len(), 2);
+        let (color_index, vertices, indices) = &parts[0];
+        assert_eq!(*color_index, red);
+        assert_eq!((vertices.len(), indices.len()), (48, 72));
+        assert_eq!(indices.iter().max(), Some(&47));
+        assert_eq!(parts[1].1.len(), 24);
+
+        let object = voxel_manager.cells_by_color(&[[3, 3, 3]], [1.0, 0.0, 0.0]);
+        assert_eq!(object.len(), 1);
+        assert_eq!(object[0].1.len(), 24);
+        let lowest = object[0]
+            .1
+            .iter()
+            .map(|v| v.pos[0])
+            .fold(f32::MAX, f32::min);
+        assert_eq!(lowest, 4.0);
+
+        let colors = voxel_manager.vertex_color_indices();
+        assert_eq!(colors.len(), voxel_manager.vertices().0.len());
+        assert_eq!(colors.iter().filter(|&&index| index == red).count(), 48);
+        assert_eq!(voxel_manager.vertex_colors()[0], [0.0, 0.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn masks_keep_edits_away_from_protected_cells() {
+        use crate::selection::{MaskMode, Selection};
+
+        let mut voxel_manager = VoxelManager::new(8);
+        let red = voxel_manager.palette_mut().index_of([1.0, 0.0, 0.0, 1.0]);
+        voxel_manager.set_voxel(0, 0, 0, Some(red));
+        let finished = Selection::from_cells(vec![[0, 0, 0]]);
+        voxel_manager.set_mask(Mask::new(finished, MaskMode::Outside));
+
+        let size = Vector3::new(2.0, 1.0, 1.0);
+        voxel_manager.erase_box(BoundingBox::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            size,
+            [0.0; 4],
+        ));
+        voxel_manager.add_box(BoundingBox::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            size,
+            [0.0, 0.0, 1.0, 1.0],
+        ));
+        assert_eq!(voxel_manager.voxel(0, 0, 0), Some(red));
+        assert!(voxel_manager.voxel(1, 0, 0).is_some());
+
+        voxel_manager.take_mask();
+        voxel_manager.set_voxel(0, 0, 0, None);
+        assert_eq!(voxel_manager.voxel(0, 0, 0), None);
+    }
+
+    #[test]
+    fn smoothing_averages_neighbours_in_linear_light() {
+        let mut voxel_manager = VoxelManager::new(8);
+        let red = voxel_manager.palette_mut().index_of([1.0, 0.0, 0.0, 1.0]);
+        let blue = voxel_manager.palette_mut().index_of([0.0, 0.0, 1.0, 1.0]);
+        voxel_manager.set_voxel(0, 0, 0, Some(red));
+        voxel_manager.set_voxel(1, 0, 0, Some(blue));
+        voxel_manager.set_voxel(2, 0, 0, Some(red));
+
+        voxel_manager.smooth_colors(&[[1, 0, 0], [4, 0, 0], [-1, 0, 0]]);
+        let smoothed = voxel_manager.voxel(1, 0, 0).unwrap();
+        // Two thirds of red's light is 0.835 encoded, not 0.667, and lands on 27 of 32 levels
+        assert_eq!(
+            voxel_manager.palette().color(smoothed),
+            [27.0 / 32.0, 0.0, 20.0 / 32.0, 1.0]
+        );
+        // Only the filled cells of the list change, empty ones stay empty
+        assert_eq!(voxel_manager.voxel(0, 0, 0), Some(red));
+        assert_eq!(voxel_manager.voxel(4, 0, 0), None);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn scenes_roundtrip_through_json() {
+        let mut voxel_manager = VoxelManager::new(8);
+        let red = voxel_manager.palette_mut().index_of([1.0, 0.0, 0.0, 1.0]);
+        voxel_manager.set_voxel(1, 2, 3, Some(red));
+        voxel_manager.set_voxel(7, 7, 7, Some(red));
+
+        let json = serde_json::to_string(&voxel_manager).unwrap();
+        let loaded: VoxelManager = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.extent(), 8);
+        assert_eq!(loaded.palette().colors(), voxel_manager.palette().colors());
+        assert_eq!(loaded.voxel(1, 2, 3), Some(red));
+        assert_eq!(loaded.voxel(7, 7, 7), Some(red));
+        assert_eq!(loaded.voxel(0, 0, 0), None);
+
+        let outside = json.replace("[7,7,7]", "[8,7,7]");
+        assert!(serde_json::from_str::<VoxelManager>(&outside).is_err());
+        let huge = json.replace("\"extent\":8", "\"extent\":100000");
+        assert!(serde_json::from_str::<VoxelManager>(&huge).is_err());
+    }
+}
@@ -0,0 +1,144 @@
+#ifndef VOXEL_EDITOR_H
+#define VOXEL_EDITOR_H
+
+/* Generated by cbindgen from ffi/src/lib.rs, don't edit by hand */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/*
+ Returned when a call succeeded
+ */
+#define VXE_OK 0
+
+/*
+ Returned for null handles, cells outside the grid and invalid paths
+ */
+#define VXE_INVALID_ARGUMENT -1
+
+/*
+ Returned when writing a file failed
+ */
+#define VXE_IO_ERROR -2
+
+/*
+ Returned when the library hit a bug, the handles stay valid
+ */
+#define VXE_INTERNAL_ERROR -3
+
+/*
+ Triangles of the visible voxel faces
+ */
+typedef struct VxeMesh VxeMesh;
+
+/*
+ A voxel grid with its palette
+ */
+typedef struct VxeScene VxeScene;
+
+typedef struct VxeVertex {
+  float position[3];
+  float normal[3];
+  float color[4];
+} VxeVertex;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/*
+ Creates an empty scene of extent^3 cells, null when the extent is zero or above 256
+ */
+VxeScene *vxe_scene_new(uint32_t extent);
+
+/*
+ # Safety
+ scene has to be null or a handle from vxe_scene_new that wasn't freed yet
+ */
+void vxe_scene_free(VxeScene *scene);
+
+/*
+ Number of cells along every axis, zero for a null scene
+
+ # Safety
+ scene has to be null or a live handle
+ */
+uint32_t vxe_scene_extent(const VxeScene *scene);
+
+/*
+ Fills the cell with the RGBA color, adding it to the palette when needed.
+ A null color empties the cell.
+
+ # Safety
+ scene has to be null or a live handle, color null or pointing to 4 floats
+ */
+int vxe_scene_set_voxel(VxeScene *scene, uint32_t x, uint32_t y, uint32_t z, const float *color);
+
+/*
+ Writes the RGBA color of the cell into color, returns 1 for filled cells, 0 for empty ones
+ and VXE_INVALID_ARGUMENT for cells outside the grid
+
+ # Safety
+ scene has to be null or a live handle, color null or pointing to 4 writable floats
+ */
+int vxe_scene_get_voxel(const VxeScene *scene, uint32_t x, uint32_t y, uint32_t z, float *color);
+
+/*
+ Meshes the visible faces of the scene, null for a null scene
+
+ # Safety
+ scene has to be null or a live handle
+ */
+VxeMesh *vxe_scene_mesh(const VxeScene *scene);
+
+/*
+ # Safety
+ mesh has to be null or a handle from vxe_scene_mesh that wasn't freed yet
+ */
+void vxe_mesh_free(VxeMesh *mesh);
+
+/*
+ # Safety
+ mesh has to be null or a live handle
+ */
+uintptr_t vxe_mesh_vertex_count(const VxeMesh *mesh);
+
+/*
+ The vertices of the mesh, valid until the mesh is freed
+
+ # Safety
+ mesh has to be null or a live handle
+ */
+const VxeVertex *vxe_mesh_vertices(const VxeMesh *mesh);
+
+/*
+ # Safety
+ mesh has to be null or a live handle
+ */
+uintptr_t vxe_mesh_index_count(const VxeMesh *mesh);
+
+/*
+ Triangle list indices into the vertices, valid until the mesh is freed
+
+ # Safety
+ mesh has to be null or a live handle
+ */
+const uint32_t *vxe_mesh_indices(const VxeMesh *mesh);
+
+/*
+ Writes the scene as a Wavefront .obj file, with texture_atlas the colors go into a texture
+ next to it
+
+ # Safety
+ scene has to be null or a live handle, path null or a NUL terminated UTF-8 string
+ */
+int vxe_scene_export_obj(const VxeScene *scene, const char *path, bool texture_atlas);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* VOXEL_EDITOR_H */
@@ -0,0 +1,303 @@
+//! C API of the voxel core, for embedding scenes into existing engines and tools.
+//!
+//! Scenes and meshes are opaque handles owned by the caller, every `*_new` or `vxe_scene_mesh`
+//! has to be released with the matching `*_free`. The header is `include/voxel_editor.h`.
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+use voxel_editor_core::export;
+use voxel_editor_core::voxel_manager::{VoxelManager, MAX_EXTENT};
+
+/// Returned when a call succeeded
+pub const VXE_OK: c_int = 0;
+/// Returned for null handles, cells outside the grid and invalid paths
+pub const VXE_INVALID_ARGUMENT: c_int = -1;
+/// Returned when writing a file failed
+pub const VXE_IO_ERROR: c_int = -2;
+/// Returned when the library hit a bug, the handles stay valid
+pub const VXE_INTERNAL_ERROR: c_int = -3;
+
+/// A voxel grid with its palette
+pub struct VxeScene {
+    voxel_manager: VoxelManager,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VxeVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub color: [f32; 4],
+}
+
+/// Triangles of the visible voxel faces
+pub struct VxeMesh {
+    vertices: Vec<VxeVertex>,
+    indices: Vec<u32>,
+}
+
+/// Runs the body of a call, a panic returns the fallback instead of unwinding into the caller
+fn guarded<T>(fallback: T, body: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(body)).unwrap_or(fallback)
+}
+
+fn cell(scene: &VxeScene, x: u32, y: u32, z: u32) -> Option<[usize; 3]> {
+    let extent = scene.voxel_manager.extent();
+    let cell = [x as usize, y as usize, z as usize];
+    if cell.iter().all(|c| *c < extent) {
+        Some(cell)
+    } else {
+        None
+    }
+}
+
+/// Creates an empty scene of extent^3 cells, null when the extent is zero or above 256
+#[no_mangle]
+pub extern "C" fn vxe_scene_new(extent: u32) -> *mut VxeScene {
+    guarded(ptr::null_mut(), || {
+        if extent == 0 || extent as usize > MAX_EXTENT {
+            return ptr::null_mut();
+        }
+        Box::into_raw(Box::new(VxeScene {
+            voxel_manager: VoxelManager::new(extent as usize),
+        }))
+    })
+}
+
+/// # Safety
+/// scene has to be null or a handle from vxe_scene_new that wasn't freed yet
+#[no_mangle]
+pub unsafe extern "C" fn vxe_scene_free(scene: *mut VxeScene) {
+    guarded((), || {
+        if !scene.is_null() {
+            drop(Box::from_raw(scene));
+        }
+    })
+}
+
+/// Number of cells along every axis, zero for a null scene
+///
+/// # Safety
+/// scene has to be null or a live handle
+#[no_mangle]
+pub unsafe extern "C" fn vxe_scene_extent(scene: *const VxeScene) -> u32 {
+    guarded(0, || {
+        scene
+            .as_ref()
+            .map_or(0, |scene| scene.voxel_manager.extent() as u32)
+    })
+}
+
+/// Fills the cell with the RGBA color, adding it to the palette when needed.
+/// A null color empties the cell.
+///
+/// # Safety
+/// scene has to be null or a live handle, color null or pointing to 4 floats
+#[no_mangle]
+pub unsafe extern "C" fn vxe_scene_set_voxel(
+    scene: *mut VxeScene,
+    x: u32,
+    y: u32,
+    z: u32,
+    color: *const f32,
+) -> c_int {
+    guarded(VXE_INTERNAL_ERROR, || {
+        let scene = match scene.as_mut() {
+            Some(scene) => scene,
+            None => return VXE_INVALID_ARGUMENT,
+        };
+        let [x, y, z] = match cell(scene, x, y, z) {
+            Some(cell) => cell,
+            None => return VXE_INVALID_ARGUMENT,
+        };
+        let color_index = if color.is_null() {
+            None
+        } else {
+            let color = slice::from_raw_parts(color, 4);
+            let color = [color[0], color[1], color[2], color[3]];
+            Some(scene.voxel_manager.palette_mut().index_of(color))
+        };
+        scene.voxel_manager.set_voxel(x, y, z, color_index);
+        VXE_OK
+    })
+}
+
+/// Writes the RGBA color of the cell into color, returns 1 for filled cells, 0 for empty ones
+/// and VXE_INVALID_ARGUMENT for cells outside the grid
+///
+/// # Safety
+/// scene has to be null or a live handle, color null or pointing to 4 writable floats
+#[no_mangle]
+pub unsafe extern "C" fn vxe_scene_get_voxel(
+    scene: *const VxeScene,
+    x: u32,
+    y: u32,
+    z: u32,
+    color: *mut f32,
+) -> c_int {
+    guarded(VXE_INTERNAL_ERROR, || {
+        let scene = match scene.as_ref() {
+            Some(scene) => scene,
+            None => return VXE_INVALID_ARGUMENT,
+        };
+        let [x, y, z] = match cell(scene, x, y, z) {
+            Some(cell) => cell,
+            None => return VXE_INVALID_ARGUMENT,
+        };
+        match scene.voxel_manager.voxel(x, y, z) {
+            Some(index) => {
+                if !color.is_null() {
+                    let rgba = scene.voxel_manager.palette().color(index);
+                    slice::from_raw_parts_mut(color, 4).copy_from_slice(&rgba);
+                }
+                1
+            }
+            None => 0,
+        }
+    })
+}
+
+/// Meshes the visible faces of the scene, null for a null scene
+///
+/// # Safety
+/// scene has to be null or a live handle
+#[no_mangle]
+pub unsafe extern "C" fn vxe_scene_mesh(scene: *const VxeScene) -> *mut VxeMesh {
+    guarded(ptr::null_mut(), || {
+        let scene = match scene.as_ref() {
+            Some(scene) => scene,
+            None => return ptr::null_mut(),
+        };
+        let voxel_manager = &scene.voxel_manager;
+        let (vertices, indices) = voxel_manager.vertices();
+        let vertices = vertices
+            .iter()
+            .zip(voxel_manager.vertex_colors())
+            .map(|(vertex, color)| VxeVertex {
+                position: vertex.pos,
+                normal: vertex.normal,
+                color,
+            })
+            .collect();
+        Box::into_raw(Box::new(VxeMesh { vertices, indices }))
+    })
+}
+
+/// # Safety
+/// mesh has to be null or a handle from vxe_scene_mesh that wasn't freed yet
+#[no_mangle]
+pub unsafe extern "C" fn vxe_mesh_free(mesh: *mut VxeMesh) {
+    guarded((), || {
+        if !mesh.is_null() {
+            drop(Box::from_raw(mesh));
+        }
+    })
+}
+
+/// # Safety
+/// mesh has to be null or a live handle
+#[no_mangle]
+pub unsafe extern "C" fn vxe_mesh_vertex_count(mesh: *const VxeMesh) -> usize {
+    guarded(0, || mesh.as_ref().map_or(0, |mesh| mesh.vertices.len()))
+}
+
+/// The vertices of the mesh, valid until the mesh is freed
+///
+/// # Safety
+/// mesh has to be null or a live handle
+#[no_mangle]
+pub unsafe extern "C" fn vxe_mesh_vertices(mesh: *const VxeMesh) -> *const VxeVertex {
+    guarded(ptr::null(), || {
+        mesh.as_ref()
+            .map_or(ptr::null(), |mesh| mesh.vertices.as_ptr())
+    })
+}
+
+/// # Safety
+/// mesh has to be null or a live handle
+#[no_mangle]
+pub unsafe extern "C" fn vxe_mesh_index_count(mesh: *const VxeMesh) -> usize {
+    guarded(0, || mesh.as_ref().map_or(0, |mesh| mesh.indices.len()))
+}
+
+/// Triangle list indices into the vertices, valid until the mesh is freed
+///
+/// # Safety
+/// mesh has to be null or a live handle
+#[no_mangle]
+pub unsafe extern "C" fn vxe_mesh_indices(mesh: *const VxeMesh) -> *const u32 {
+    guarded(ptr::null(), || {
+        mesh.as_ref()
+            .map_or(ptr::null(), |mesh| mesh.indices.as_ptr())
+    })
+}
+
+/// Writes the scene as a Wavefront .obj file, with texture_atlas the colors go into a texture
+/// next to it
+///
+/// # Safety
+/// scene has to be null or a live handle, path null or a NUL terminated UTF-8 string
+#[no_mangle]
+pub unsafe extern "C" fn vxe_scene_export_obj(
+    scene: *const VxeScene,
+    path: *const c_char,
+    texture_atlas: bool,
+) -> c_int {
+    guarded(VXE_INTERNAL_ERROR, || {
+        let scene = match scene.as_ref() {
+            Some(scene) => scene,
+            None => return VXE_INVALID_ARGUMENT,
+        };
+        if path.is_null() {
+            return VXE_INVALID_ARGUMENT;
+        }
+        let path = match CStr::from_ptr(path).to_str() {
+            Ok(path) => path,
+            Err(_) => return VXE_INVALID_ARGUMENT,
+        };
+        match export::write_obj(&scene.voxel_manager, path, texture_atlas, &mut |_| true) {
+            Ok(_) => VXE_OK,
+            Err(_) => VXE_IO_ERROR,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn scenes_are_edited_and_meshed_through_the_c_api() {
+        unsafe {
+            assert!(vxe_scene_new(0).is_null());
+            assert!(vxe_scene_new(u32::MAX).is_null());
+            let scene = vxe_scene_new(4);
+            assert_eq!(vxe_scene_extent(scene), 4);
+            let red = [1.0, 0.0, 0.0, 1.0];
+            assert_eq!(vxe_scene_set_voxel(scene, 1, 2, 3, red.as_ptr()), VXE_OK);
+            assert_eq!(
+                vxe_scene_set_voxel(scene, 4, 0, 0, red.as_ptr()),
+                VXE_INVALID_ARGUMENT
+            );
+
+            let mut color = [0.0; 4];
+            assert_eq!(vxe_scene_get_voxel(scene, 1, 2, 3, color.as_mut_ptr()), 1);
+            assert_eq!(color, red);
+            assert_eq!(vxe_scene_get_voxel(scene, 0, 0, 0, color.as_mut_ptr()), 0);
+
+            let mesh = vxe_scene_mesh(scene);
+            assert_eq!(vxe_mesh_vertex_count(mesh), 24);
+            assert_eq!(vxe_mesh_index_count(mesh), 36);
+            assert_eq!((*vxe_mesh_vertices(mesh)).color, red);
+            vxe_mesh_free(mesh);
+
+            assert_eq!(vxe_scene_set_voxel(scene, 1, 2, 3, ptr::null()), VXE_OK);
+            assert_eq!(vxe_scene_get_voxel(scene, 1, 2, 3, ptr::null_mut()), 0);
+            vxe_scene_free(scene);
+            vxe_scene_free(ptr::null_mut());
+        }
+    }
+}
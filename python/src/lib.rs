@@ -0,0 +1,302 @@
+//! Python module `voxel_editor` wrapping the scene and the procedural generators of the core.
+//!
+//! ```python
+//! import voxel_editor
+//!
+//! scene = voxel_editor.Scene(64)
+//! scene.stairs((0, 0, 0), (12, 6, 3), (0.6, 0.4, 0.2, 1.0), step_depth=2)
+//! scene.text((0, 8, 0), "HELLO", (1.0, 1.0, 1.0, 1.0), plane="z")
+//! scene.export_obj("hello.obj")
+//! ```
+use pyo3::exceptions::ValueError;
+use pyo3::prelude::*;
+use voxel_editor_core::export::{Crop, ExportFormat, ExportPreset};
+use voxel_editor_core::font::FontKind;
+use voxel_editor_core::generator::{self, BrushProfile, StairsOptions, TextOptions};
+use voxel_editor_core::project::{self, ProjectExtras};
+use voxel_editor_core::selection::SavedMasks;
+use voxel_editor_core::transform::Axis;
+use voxel_editor_core::voxel_manager::{VoxelManager, MAX_EXTENT};
+
+type Cell = (i32, i32, i32);
+type Color = (f32, f32, f32, f32);
+
+fn cell((x, y, z): Cell) -> [i32; 3] {
+    [x, y, z]
+}
+
+fn color((r, g, b, a): Color) -> [f32; 4] {
+    [r, g, b, a]
+}
+
+/// Looks up the variant by its lowercase name, like "x" or "round"
+fn variant<T: Copy + std::fmt::Debug>(all: &[T], name: &str) -> PyResult<T> {
+    all.iter()
+        .find(|value| format!("{:?}", value).eq_ignore_ascii_case(name))
+        .copied()
+        .ok_or_else(|| {
+            let names: Vec<String> = all
+                .iter()
+                .map(|value| format!("{:?}", value).to_lowercase())
+                .collect();
+            PyErr::new::<ValueError, _>(format!(
+                "unknown name {:?}, expected one of {}",
+                name,
+                names.join(", ")
+            ))
+        })
+}
+
+/// A voxel grid of extent^3 cells with its palette
+#[pyclass]
+struct Scene {
+    voxel_manager: VoxelManager,
+    /// Snapshots, markers and sub-grids of a loaded project
+    extras: ProjectExtras,
+}
+
+impl Scene {
+    fn checked(&self, x: usize, y: usize, z: usize) -> PyResult<()> {
+        let extent = self.voxel_manager.extent();
+        if x < extent && y < extent && z < extent {
+            Ok(())
+        } else {
+            Err(PyErr::new::<ValueError, _>(format!(
+                "cell ({}, {}, {}) is outside the grid of extent {}",
+                x, y, z, extent
+            )))
+        }
+    }
+}
+
+#[pymethods]
+impl Scene {
+    #[new]
+    fn new(extent: usize) -> PyResult<Self> {
+        if extent == 0 || extent > MAX_EXTENT {
+            return Err(PyErr::new::<ValueError, _>(format!(
+                "extent {} is outside 1 to {}",
+                extent, MAX_EXTENT
+            )));
+        }
+        Ok(Scene {
+            voxel_manager: VoxelManager::new(extent),
+            extras: ProjectExtras::default(),
+        })
+    }
+
+    /// Reads a .vxe project
+    #[staticmethod]
+    fn load(path: &str) -> PyResult<Scene> {
+        let (voxel_manager, extras) = project::load_project_with_extras(path, &mut |_| true)?;
+        Ok(Scene {
+            voxel_manager,
+            extras,
+        })
+    }
+
+    /// Writes the scene as a .vxe project, with the snapshots, markers and sub-grids it was
+    /// loaded with
+    fn save(&self, path: &str) -> PyResult<()> {
+        project::save_project_with_extras(&self.voxel_manager, &self.extras, path, &mut |_| true)?;
+        Ok(())
+    }
+
+    #[getter]
+    fn extent(&self) -> usize {
+        self.voxel_manager.extent()
+    }
+
+    /// Fills the cell with the RGBA color, None empties it
+    fn set_voxel(&mut self, x: usize, y: usize, z: usize, color: Option<Color>) -> PyResult<()> {
+        self.checked(x, y, z)?;
+        let color_index = color.map(|c| self.voxel_manager.palette_mut().index_of(self::color(c)));
+        self.voxel_manager.set_voxel(x, y, z, color_index);
+        Ok(())
+    }
+
+    /// The RGBA color of the cell, None when it's empty
+    fn get_voxel(&self, x: usize, y: usize, z: usize) -> PyResult<Option<Color>> {
+        self.checked(x, y, z)?;
+        Ok(self.voxel_manager.voxel(x, y, z).map(|index| {
+            let [r, g, b, a] = self.voxel_manager.palette().color(index);
+            (r, g, b, a)
+        }))
+    }
+
+    /// The visible faces as (positions, normals, colors, triangle indices)
+    fn mesh(
+        &self,
+    ) -> (
+        Vec<(f32, f32, f32)>,
+        Vec<(f32, f32, f32)>,
+        Vec<Color>,
+        Vec<u32>,
+    ) {
+        let (vertices, indices) = self.voxel_manager.vertices();
+        let positions = vertices
+            .iter()
+            .map(|v| (v.pos[0], v.pos[1], v.pos[2]))
+            .collect();
+        let normals = vertices
+            .iter()
+            .map(|v| (v.normal[0], v.normal[1], v.normal[2]))
+            .collect();
+        let vertex_colors = self
+            .voxel_manager
+            .vertex_colors()
+            .into_iter()
+            .map(|[r, g, b, a]| (r, g, b, a))
+            .collect();
+        (positions, normals, vertex_colors, indices)
+    }
+
+    /// Writes the scene with its sub-grids as a Wavefront .obj file
+    #[args(texture_atlas = false)]
+    fn export_obj(&self, path: &str, texture_atlas: bool) -> PyResult<()> {
+        let preset = ExportPreset {
+            format: ExportFormat::Obj,
+            file_path: path.to_owned(),
+            texture_atlas,
+            template: "{name}_{n}".to_owned(),
+            crop: Crop::Grid,
+            pivot: None,
+        };
+        preset.export_with_sub_grids(
+            &self.voxel_manager,
+            &self.extras.sub_grids,
+            &SavedMasks::default(),
+            &mut |_| true,
+        )?;
+        Ok(())
+    }
+
+    /// Stairs climbing from the start cell to the end cell
+    #[args(step_depth = 1, railing = false)]
+    fn stairs(&mut self, start: Cell, end: Cell, color: Color, step_depth: usize, railing: bool) {
+        let options = StairsOptions {
+            step_depth,
+            railing,
+        };
+        generator::stairs(
+            &mut self.voxel_manager,
+            cell(start),
+            cell(end),
+            options,
+            self::color(color),
+        );
+    }
+
+    /// Text on the plane with the given normal axis ("x", "y" or "z"),
+    /// in the "tiny" or "classic" font
+    #[args(plane = "\"z\"", font = "\"classic\"", scale = 1, depth = 1)]
+    fn text(
+        &mut self,
+        origin: Cell,
+        text: String,
+        color: Color,
+        plane: &str,
+        font: &str,
+        scale: usize,
+        depth: usize,
+    ) -> PyResult<()> {
+        let options = TextOptions {
+            text,
+            font: variant(&FontKind::ALL, font)?,
+            scale,
+            depth,
+        };
+        generator::text(
+            &mut self.voxel_manager,
+            cell(origin),
+            variant(&Axis::ALL, plane)?,
+            &options,
+            self::color(color),
+        );
+        Ok(())
+    }
+
+    /// A "round" or "square" brush swept along the curve through the points
+    #[args(radius = 1, profile = "\"round\"")]
+    fn sweep(
+        &mut self,
+        points: Vec<Cell>,
+        color: Color,
+        radius: usize,
+        profile: &str,
+    ) -> PyResult<()> {
+        let points: Vec<[i32; 3]> = points.into_iter().map(cell).collect();
+        generator::sweep(
+            &mut self.voxel_manager,
+            &points,
+            variant(&BrushProfile::ALL, profile)?,
+            radius,
+            self::color(color),
+        );
+        Ok(())
+    }
+
+    /// Fills a 2D shape of (right, up) offsets into the plane through the origin
+    #[args(plane = "\"z\"")]
+    fn fill_plane(
+        &mut self,
+        origin: Cell,
+        cells: Vec<(i32, i32)>,
+        color: Color,
+        plane: &str,
+    ) -> PyResult<usize> {
+        let cells: Vec<[i32; 2]> = cells.into_iter().map(|(i, j)| [i, j]).collect();
+        let filled = generator::fill_plane(
+            &mut self.voxel_manager,
+            cell(origin),
+            variant(&Axis::ALL, plane)?,
+            &cells,
+            self::color(color),
+        );
+        Ok(filled.len())
+    }
+}
+
+#[pymodule]
+fn voxel_editor(_py: Python, m: &PyModule) -> PyResult<()> {
+    m.add_class::<Scene>()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use pyo3::types::PyDict;
+    use pyo3::wrap_pymodule;
+
+    #[test]
+    fn scenes_are_edited_and_meshed_from_python() {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        let locals = PyDict::new(py);
+        locals
+            .set_item("voxel_editor", wrap_pymodule!(voxel_editor)(py))
+            .unwrap();
+        py.run(
+            r#"
+scene = voxel_editor.Scene(4)
+scene.set_voxel(1, 2, 3, (1.0, 0.0, 0.0, 1.0))
+assert scene.get_voxel(1, 2, 3) == (1.0, 0.0, 0.0, 1.0)
+positions, normals, colors, indices = scene.mesh()
+assert len(positions) == len(colors) == 24
+assert len(indices) == 36
+assert all(color == (1.0, 0.0, 0.0, 1.0) for color in colors)
+for extent in (0, 100000):
+    try:
+        voxel_editor.Scene(extent)
+        assert False
+    except ValueError:
+        pass
+"#,
+            None,
+            Some(locals),
+        )
+        .unwrap();
+    }
+}
@@ -0,0 +1,26 @@
+#version 450
+
+layout(location = 0) in vec2 fragUv;
+
+layout(location = 0) out vec4 outGlow;
+
+layout(set = 0, binding = 0) uniform texture2D t_Glow;
+layout(set = 0, binding = 1) uniform sampler s_Glow;
+
+layout(set = 0, binding = 2) uniform BlurUBO {
+    // Distance between the taps in texture coordinates, along the blurred direction
+    vec4 step;
+} blur;
+
+// A gaussian over nine taps, the two halves are sampled mirrored
+const float weights[5] = float[](0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+
+void main() {
+    vec4 sum = texture(sampler2D(t_Glow, s_Glow), fragUv) * weights[0];
+    for (int i = 1; i < 5; ++i) {
+        vec2 offset = blur.step.xy * float(i);
+        sum += texture(sampler2D(t_Glow, s_Glow), fragUv + offset) * weights[i];
+        sum += texture(sampler2D(t_Glow, s_Glow), fragUv - offset) * weights[i];
+    }
+    outGlow = sum;
+}
@@ -0,0 +1,53 @@
+#version 450
+
+layout(location = 0) in vec4 fragColor;
+layout(location = 1) in vec3 fragNormal;
+layout(location = 2) in vec3 fragViewVec;
+layout(location = 3) in vec3 fragLightVec;
+layout(location = 4) in vec4 vertPos;
+layout(location = 5) in mat4 fragLightProj;
+layout(location = 9) in vec4 fragTone;
+
+
+layout(location = 0) out vec4 outColor;
+
+layout(set = 0, binding = 2) uniform texture2D t_Shadow;
+layout(set = 0, binding = 3) uniform sampler s_Shadow;
+
+// Same as in voxel.frag
+float ShadowCalculationPcf(vec4 fragPosLightSpace)
+{
+    const vec2 flip_correction = vec2(0.5, -0.5);
+    // compute texture coordinates for shadow lookup
+    vec3 projCoords =
+      vec3(fragPosLightSpace.xy * flip_correction / fragPosLightSpace.w + 0.5,
+           fragPosLightSpace.z / fragPosLightSpace.w);
+
+     if (projCoords.z > 1.0) {
+        return 0.0;
+     }
+    // get depth of current fragment from light's perspective
+    float currentDepth = projCoords.z;
+
+    float shadow = 0.0;
+    float bias = 0.002;
+    vec2 texelSize = 1.0 / textureSize(sampler2D(t_Shadow, s_Shadow), 0);
+    for(int x = -1; x <= 1; ++x)
+    {
+        for(int y = -1; y <= 1; ++y)
+        {
+            float pcfDepth = texture(sampler2D(t_Shadow, s_Shadow), projCoords.xy + vec2(x, y) * texelSize).r;
+            shadow += currentDepth - bias > pcfDepth ? 1.0 : 0.0;
+        }
+    }
+    shadow /= 18.0;
+
+    return shadow;
+}
+
+// The ground only shows the shadow falling on it, as black covering as much as the shadow
+// darkens the voxels
+void main() {
+  float shadow = ShadowCalculationPcf(fragLightProj * vertPos);
+  outColor = vec4(0.0, 0.0, 0.0, shadow);
+}
@@ -0,0 +1,26 @@
+#version 450
+
+layout(location = 0) in vec2 fragUv;
+
+layout(location = 0) out vec4 outColor;
+
+layout(set = 0, binding = 0) uniform texture2D t_Glow;
+layout(set = 0, binding = 1) uniform sampler s_Glow;
+
+layout(set = 0, binding = 2) uniform CompositeUBO {
+    // The strength of the glow in x
+    vec4 strength;
+} composite;
+
+// The glow holds sRGB encoded colors, the framebuffer adds them in linear light
+vec3 ToLinear(vec3 srgb)
+{
+    vec3 low = srgb / 12.92;
+    vec3 high = pow((srgb + 0.055) / 1.055, vec3(2.4));
+    return mix(high, low, lessThanEqual(srgb, vec3(0.04045)));
+}
+
+void main() {
+    vec3 glow = texture(sampler2D(t_Glow, s_Glow), fragUv).rgb;
+    outColor = vec4(ToLinear(glow) * composite.strength.x, 0.0);
+}
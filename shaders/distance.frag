@@ -0,0 +1,9 @@
+#version 450
+
+layout(location = 0) in float fragDistance;
+
+layout(location = 0) out float outDistance;
+
+void main() {
+    outDistance = fragDistance;
+}
@@ -0,0 +1,22 @@
+#version 450
+
+layout(location = 0) in vec3 inPos;
+layout(location = 1) in vec3 inNormal;
+
+layout(location = 2) in vec3 inOffset;
+
+layout(location = 0) out float fragDistance;
+
+layout(set = 0, binding = 0) uniform UBO {
+    mat4 model;
+    mat4 view;
+    mat4 projection;
+} ubo;
+
+void main() {
+    vec4 viewPos = ubo.view * ubo.model * vec4(inPos + inOffset, 1.0);
+    gl_Position = ubo.projection * viewPos;
+
+    // Distance from the eye rather than depth, so the blur doesn't change across the image
+    fragDistance = length(viewPos.xyz);
+}
@@ -0,0 +1,28 @@
+#version 450
+
+layout(location = 0) in vec3 inPos;
+layout(location = 1) in vec3 inNormal;
+
+layout(location = 2) in vec3 inOffset;
+layout(location = 3) in uint inColorIndex;
+
+layout(location = 0) out vec4 fragGlow;
+
+layout(set = 0, binding = 0) uniform UBO {
+    mat4 model;
+    mat4 view;
+    mat4 projection;
+} ubo;
+
+layout(set = 0, binding = 1) uniform PaletteUBO {
+    vec4 colors[256];
+    vec4 emission[256];
+} palette;
+
+void main() {
+    gl_Position = ubo.projection * ubo.view * ubo.model * vec4(inPos + inOffset, 1.0);
+
+    // Other voxels are drawn black, hiding the glow of the emissive ones behind them
+    float emission = palette.emission[inColorIndex].x;
+    fragGlow = vec4(palette.colors[inColorIndex].rgb * emission, emission);
+}
@@ -0,0 +1,10 @@
+#version 450
+
+layout(location = 0) out vec2 fragUv;
+
+void main() {
+    // One triangle covering the screen, textures have their origin at the top left
+    vec2 corner = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2);
+    fragUv = vec2(corner.x, 1.0 - corner.y);
+    gl_Position = vec4(corner * 2.0 - 1.0, 0.0, 1.0);
+}
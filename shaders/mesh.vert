@@ -11,7 +11,15 @@ layout(set = 0, binding = 0) uniform UBO {
     mat4 projection;
 } ubo;
 
-void main() {;
+// Colors are given sRGB encoded, the framebuffer encodes the linear output again
+vec3 ToLinear(vec3 srgb)
+{
+    vec3 low = srgb / 12.92;
+    vec3 high = pow((srgb + 0.055) / 1.055, vec3(2.4));
+    return mix(high, low, lessThanEqual(srgb, vec3(0.04045)));
+}
+
+void main() {
     gl_Position = ubo.projection * ubo.view * ubo.model * vec4(inPos, 1.0);
-    outColor = inColor;
+    outColor = vec4(ToLinear(inColor.rgb), inColor.a);
 }
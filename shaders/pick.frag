@@ -0,0 +1,9 @@
+#version 450
+
+layout(location = 0) flat in uvec2 fragId;
+
+layout(location = 0) out uvec2 outId;
+
+void main() {
+    outId = fragId;
+}
@@ -0,0 +1,32 @@
+#version 450
+
+layout(location = 0) in vec3 inPos;
+layout(location = 1) in vec3 inNormal;
+
+layout(location = 2) in vec3 inOffset;
+
+layout(location = 0) flat out uvec2 fragId;
+
+layout(set = 0, binding = 0) uniform UBO {
+    mat4 model;
+    mat4 view;
+    mat4 projection;
+} ubo;
+
+// Has to match the order of FACE_NORMALS in picking.rs
+uint faceIndex(vec3 normal) {
+    if (normal.x > 0.5) return 0;
+    if (normal.x < -0.5) return 1;
+    if (normal.y > 0.5) return 2;
+    if (normal.y < -0.5) return 3;
+    if (normal.z > 0.5) return 4;
+    return 5;
+}
+
+void main() {
+    gl_Position = ubo.projection * ubo.view * ubo.model * vec4(inPos + inOffset, 1.0);
+
+    // Zero is left for the background
+    uvec3 cell = uvec3(inOffset);
+    fragId = uvec2(1 + cell.x + 1024 * (cell.y + 1024 * cell.z), faceIndex(inNormal));
+}
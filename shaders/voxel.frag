@@ -6,6 +6,9 @@ layout(location = 2) in vec3 fragViewVec;
 layout(location = 3) in vec3 fragLightVec;
 layout(location = 4) in vec4 vertPos;
 layout(location = 5) in mat4 fragLightProj;
+layout(location = 9) in vec4 fragTone;
+layout(location = 10) in vec4 fragLight;
+layout(location = 11) in float fragEmission;
 
 
 layout(location = 0) out vec4 outColor;
@@ -64,18 +67,37 @@ float ShadowCalculationPcf(vec4 fragPosLightSpace)
     return shadow;
 }
 
+// Has to match the order of ToneMapping in light.rs
+vec3 ToneMap(vec3 color, float op)
+{
+    if (op > 1.5) {
+        // Narkowicz's fit of the ACES curve
+        return (color * (2.51 * color + 0.03)) / (color * (2.43 * color + 0.59) + 0.14);
+    }
+    if (op > 0.5) {
+        return color / (1.0 + color);
+    }
+    return color;
+}
+
 void main() {
   vec3 N = normalize(fragNormal);
   vec3 L = normalize(fragLightVec);
   vec3 V = normalize(fragViewVec.xyz);
   vec3 R = reflect(L, N);
 
-  vec3 ambient = fragColor.xyz * 0.2;
-  vec3 diffuse = fragColor.xyz * max(dot(N, L), 0.0);
+  // The light's color in rgb, its ambient share in w
+  vec3 ambient = fragColor.xyz * fragLight.w;
+  vec3 diffuse = fragColor.xyz * fragLight.rgb * max(dot(N, L), 0.0);
   //vec3 specular = pow(max(dot(R, V), 0.0), 16.0) * vec3(1.35);
   vec4 FragPosLightSpace = fragLightProj * vertPos;
   float shadow = ShadowCalculationPcf(FragPosLightSpace);
   vec3 lighting = (ambient + (1.0 - shadow) * (diffuse /*+ specular*/));
+  // Emissive colors shine on their own, unlit and unshadowed
+  lighting = mix(lighting, fragColor.xyz, fragEmission);
+
+  // Lighting stays unbounded until the exposed color is mapped into the displayable range
+  vec3 exposed = ToneMap(lighting * fragTone.x, fragTone.y);
 
-  outColor = vec4(lighting, fragColor.z);
+  outColor = vec4(clamp(exposed, 0.0, 1.0), fragColor.w);
 }
@@ -4,7 +4,7 @@ layout(location = 0) in vec3 inPos;
 layout(location = 1) in vec3 inNormal;
 
 layout(location = 2) in vec3 inOffset;
-layout(location = 3) in vec4 inColor;
+layout(location = 3) in uint inColorIndex;
 
 layout(location = 0) out vec4 fragColor;
 layout(location = 1) out vec3 fragNormal;
@@ -12,6 +12,9 @@ layout(location = 2) out vec3 fragViewVec;
 layout(location = 3) out vec3 fragLightVec;
 layout(location = 4) out vec4 vertPos;
 layout(location = 5) out mat4 fragLightProj;
+layout(location = 9) out vec4 fragTone;
+layout(location = 10) out vec4 fragLight;
+layout(location = 11) out float fragEmission;
 
 layout(set = 0, binding = 0) uniform UBO {
     mat4 model;
@@ -23,17 +26,36 @@ layout(set = 0, binding = 1) uniform LightUBO {
     vec4 direction;
     vec4 color;
     mat4 projection;
+    vec4 tone;
 } light;
 
+layout(set = 0, binding = 4) uniform PaletteUBO {
+    vec4 colors[256];
+    // One for the emissive entries
+    vec4 emission[256];
+} palette;
+
+// Colors are given sRGB encoded, the framebuffer encodes the linear output again
+vec3 ToLinear(vec3 srgb)
+{
+    vec3 low = srgb / 12.92;
+    vec3 high = pow((srgb + 0.055) / 1.055, vec3(2.4));
+    return mix(high, low, lessThanEqual(srgb, vec3(0.04045)));
+}
+
 void main() {
     vec4 pos = vec4(inPos + inOffset, 1.0);
     gl_Position = ubo.projection * ubo.view * ubo.model * pos;
     vec4 worldPos = ubo.model * pos;
 
-    fragColor = inColor;
+    vec4 color = palette.colors[inColorIndex];
+    fragColor = vec4(ToLinear(color.rgb), color.a);
     fragNormal = mat3(ubo.model) * inNormal;
     fragViewVec = (ubo.view * worldPos).xyz;
     fragLightVec = light.direction.xyz;
     vertPos = pos;
     fragLightProj = light.projection;
+    fragTone = light.tone;
+    fragLight = light.color;
+    fragEmission = palette.emission[inColorIndex].x;
 }
@@ -0,0 +1,715 @@
+use crate::screenshot::padded_row;
+use crate::vertex::{VoxelInstance, VoxelVertex};
+use futures::executor::block_on;
+use iced_wgpu::wgpu;
+use std::mem;
+
+const EMISSION_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+const EMISSION_DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+const PIXEL_SIZE: usize = 4;
+/// Blur radius in pixels of the glow around emissive voxels in saved renders
+const BLOOM_RADIUS: usize = 12;
+/// The viewport blurs the glow at this fraction of the window's resolution
+const VIEWPORT_SCALE: u32 = 2;
+
+/// Lets the voxels of emissive palette entries glow
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bloom {
+    /// How much of the blurred glow is added back
+    pub strength: f32,
+}
+
+/// Averages every channel over `2 * radius + 1` values along rows (step 1) or columns
+fn box_blur(values: &mut [f32], width: usize, height: usize, radius: usize, horizontal: bool) {
+    let (lines, len, step, line_step) = if horizontal {
+        (height, width, 1, width)
+    } else {
+        (width, height, width, 1)
+    };
+    let mut line = vec![[0.0f32; 3]; len];
+    for l in 0..lines {
+        for (i, value) in line.iter_mut().enumerate() {
+            let at = (l * line_step + i * step) * 3;
+            value.copy_from_slice(&values[at..at + 3]);
+        }
+        let mut sum = [0.0f32; 3];
+        for value in line.iter().take(radius.min(len)) {
+            for c in 0..3 {
+                sum[c] += value[c];
+            }
+        }
+        let size = (2 * radius + 1) as f32;
+        for i in 0..len {
+            if i + radius < len {
+                for c in 0..3 {
+                    sum[c] += line[i + radius][c];
+                }
+            }
+            if i > radius {
+                for c in 0..3 {
+                    sum[c] -= line[i - radius - 1][c];
+                }
+            }
+            let at = (l * line_step + i * step) * 3;
+            for c in 0..3 {
+                values[at + c] = sum[c] / size;
+            }
+        }
+    }
+}
+
+impl Bloom {
+    /// Adds the blurred emission, the RGBA image the emission pipeline renders, onto the RGBA
+    /// pixels. The glow also covers the transparent background around the voxels.
+    pub fn apply(&self, pixels: &mut [u8], emission: &[u8], width: usize, height: usize) {
+        let mut glow = Vec::with_capacity(width * height * 3);
+        for pixel in emission.chunks(PIXEL_SIZE) {
+            for c in 0..3 {
+                glow.push(pixel[c] as f32 / 255.0);
+            }
+        }
+        // Two box blurs in each direction come close to a gaussian
+        for _ in 0..2 {
+            box_blur(&mut glow, width, height, BLOOM_RADIUS, true);
+            box_blur(&mut glow, width, height, BLOOM_RADIUS, false);
+        }
+        for (pixel, glow) in pixels.chunks_mut(PIXEL_SIZE).zip(glow.chunks(3)) {
+            let alpha = pixel[3] as f32 / 255.0;
+            let added = [
+                glow[0] * self.strength,
+                glow[1] * self.strength,
+                glow[2] * self.strength,
+            ];
+            let added_alpha = added[0].max(added[1]).max(added[2]).min(1.0);
+            let new_alpha = alpha + added_alpha * (1.0 - alpha);
+            if new_alpha <= 0.0 {
+                continue;
+            }
+            for c in 0..3 {
+                // Blend the color over the glow like the background would show through it
+                let color = pixel[c] as f32 / 255.0 * alpha + added[c];
+                pixel[c] = (255.0 * (color / new_alpha).min(1.0)).round() as u8;
+            }
+            pixel[3] = (255.0 * new_alpha).round() as u8;
+        }
+    }
+}
+
+/// Offscreen target the emission pipeline renders the glow of saved renders into
+pub struct EmissionTarget {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    depth_view: wgpu::TextureView,
+    readback_buf: wgpu::Buffer,
+    width: u32,
+    height: u32,
+}
+
+fn create_view(
+    device: &wgpu::Device,
+    width: u32,
+    height: u32,
+    format: wgpu::TextureFormat,
+    usage: wgpu::TextureUsage,
+    label: &'static str,
+) -> (wgpu::Texture, wgpu::TextureView) {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth: 1,
+        },
+        array_layer_count: 1,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage,
+        label: Some(label),
+    });
+    let view = texture.create_default_view();
+    (texture, view)
+}
+
+fn begin_emission_pass<'a>(
+    encoder: &'a mut wgpu::CommandEncoder,
+    view: &'a wgpu::TextureView,
+    depth_view: &'a wgpu::TextureView,
+) -> wgpu::RenderPass<'a> {
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+            attachment: view,
+            resolve_target: None,
+            load_op: wgpu::LoadOp::Clear,
+            store_op: wgpu::StoreOp::Store,
+            clear_color: wgpu::Color::TRANSPARENT,
+        }],
+        depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachmentDescriptor {
+            attachment: depth_view,
+            depth_load_op: wgpu::LoadOp::Clear,
+            depth_store_op: wgpu::StoreOp::Store,
+            stencil_load_op: wgpu::LoadOp::Clear,
+            stencil_store_op: wgpu::StoreOp::Store,
+            clear_depth: 1.0,
+            clear_stencil: 0,
+        }),
+    })
+}
+
+impl EmissionTarget {
+    pub fn new(device: &wgpu::Device, width: u32, height: u32) -> Self {
+        let (texture, view) = create_view(
+            device,
+            width,
+            height,
+            EMISSION_FORMAT,
+            wgpu::TextureUsage::OUTPUT_ATTACHMENT | wgpu::TextureUsage::COPY_SRC,
+            "EmissionBuffer",
+        );
+        let (_, depth_view) = create_view(
+            device,
+            width,
+            height,
+            EMISSION_DEPTH_FORMAT,
+            wgpu::TextureUsage::OUTPUT_ATTACHMENT,
+            "EmissionDepthBuffer",
+        );
+        let readback_buf = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: padded_row(width) as u64 * height as u64,
+            usage: wgpu::BufferUsage::MAP_READ | wgpu::BufferUsage::COPY_DST,
+        });
+        EmissionTarget {
+            texture,
+            view,
+            depth_view,
+            readback_buf,
+            width,
+            height,
+        }
+    }
+
+    pub fn begin_pass<'a>(&'a self, encoder: &'a mut wgpu::CommandEncoder) -> wgpu::RenderPass<'a> {
+        begin_emission_pass(encoder, &self.view, &self.depth_view)
+    }
+
+    /// Copies the emission into the readback buffer
+    pub fn copy(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.copy_texture_to_buffer(
+            wgpu::TextureCopyView {
+                texture: &self.texture,
+                mip_level: 0,
+                array_layer: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            wgpu::BufferCopyView {
+                buffer: &self.readback_buf,
+                offset: 0,
+                bytes_per_row: padded_row(self.width),
+                rows_per_image: self.height,
+            },
+            wgpu::Extent3d {
+                width: self.width,
+                height: self.height,
+                depth: 1,
+            },
+        );
+    }
+
+    /// Waits for the copy submitted after copy and returns the RGBA rows from top to bottom
+    pub fn read(&self, device: &wgpu::Device) -> Option<Vec<u8>> {
+        let padded_row = padded_row(self.width) as usize;
+        let mapping = self
+            .readback_buf
+            .map_read(0, padded_row as u64 * self.height as u64);
+        device.poll(wgpu::Maintain::Wait);
+        let mapping = block_on(mapping).ok()?;
+        let row_size = self.width as usize * PIXEL_SIZE;
+        let mut pixels = Vec::with_capacity(row_size * self.height as usize);
+        for row in mapping.as_slice().chunks(padded_row) {
+            pixels.extend_from_slice(&row[..row_size]);
+        }
+        Some(pixels)
+    }
+}
+
+/// The reduced resolution textures of the viewport glow
+struct GlowTargets {
+    emission_view: wgpu::TextureView,
+    depth_view: wgpu::TextureView,
+    blurred_view: wgpu::TextureView,
+    // The horizontal blur reads the emission into blurred, the vertical one blurs it back
+    horizontal_bind_group: wgpu::BindGroup,
+    vertical_bind_group: wgpu::BindGroup,
+    composite_bind_group: wgpu::BindGroup,
+    width: u32,
+    height: u32,
+}
+
+impl GlowTargets {
+    fn new(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+        strength_buf: &wgpu::Buffer,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let width = (width / VIEWPORT_SCALE).max(1);
+        let height = (height / VIEWPORT_SCALE).max(1);
+        let usage = wgpu::TextureUsage::OUTPUT_ATTACHMENT | wgpu::TextureUsage::SAMPLED;
+        let (_, emission_view) =
+            create_view(device, width, height, EMISSION_FORMAT, usage, "GlowBuffer");
+        let (_, blurred_view) = create_view(
+            device,
+            width,
+            height,
+            EMISSION_FORMAT,
+            usage,
+            "BlurredGlowBuffer",
+        );
+        let (_, depth_view) = create_view(
+            device,
+            width,
+            height,
+            EMISSION_DEPTH_FORMAT,
+            wgpu::TextureUsage::OUTPUT_ATTACHMENT,
+            "GlowDepthBuffer",
+        );
+        let uniform_size = mem::size_of::<[f32; 4]>() as u64;
+        let bind_group = |view: &wgpu::TextureView, buffer: &wgpu::Buffer| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout,
+                bindings: &[
+                    wgpu::Binding {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(view),
+                    },
+                    wgpu::Binding {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(sampler),
+                    },
+                    wgpu::Binding {
+                        binding: 2,
+                        resource: wgpu::BindingResource::Buffer {
+                            buffer,
+                            range: 0..uniform_size,
+                        },
+                    },
+                ],
+                label: None,
+            })
+        };
+        // Taps two texels apart spread the glow further at little loss
+        let horizontal_step = device.create_buffer_with_data(
+            bytemuck::cast_slice(&[2.0 / width as f32, 0.0, 0.0, 0.0]),
+            wgpu::BufferUsage::UNIFORM,
+        );
+        let vertical_step = device.create_buffer_with_data(
+            bytemuck::cast_slice(&[0.0, 2.0 / height as f32, 0.0, 0.0]),
+            wgpu::BufferUsage::UNIFORM,
+        );
+        GlowTargets {
+            horizontal_bind_group: bind_group(&emission_view, &horizontal_step),
+            vertical_bind_group: bind_group(&blurred_view, &vertical_step),
+            composite_bind_group: bind_group(&emission_view, strength_buf),
+            emission_view,
+            depth_view,
+            blurred_view,
+            width,
+            height,
+        }
+    }
+}
+
+/// Renders the voxels with their emission at a fraction of the window's resolution and blurs
+/// it, the composite pipeline adds it onto the frame
+pub struct ViewportBloom {
+    layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    blur_pipeline: wgpu::RenderPipeline,
+    composite_pipeline: wgpu::RenderPipeline,
+    strength_buf: wgpu::Buffer,
+    targets: GlowTargets,
+}
+
+/// Builds a pipeline drawing one triangle over the whole target with the fragment shader
+fn build_fullscreen_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    fs: &[u8],
+    color_state: wgpu::ColorStateDescriptor,
+    sample_count: u32,
+) -> wgpu::RenderPipeline {
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        bind_group_layouts: &[layout],
+    });
+    let vs = include_bytes!("../shaders/fullscreen.vert.spv");
+    let vs_module =
+        device.create_shader_module(&wgpu::read_spirv(std::io::Cursor::new(&vs[..])).unwrap());
+    let fs_module =
+        device.create_shader_module(&wgpu::read_spirv(std::io::Cursor::new(fs)).unwrap());
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        layout: &pipeline_layout,
+        vertex_stage: wgpu::ProgrammableStageDescriptor {
+            module: &vs_module,
+            entry_point: "main",
+        },
+        fragment_stage: Some(wgpu::ProgrammableStageDescriptor {
+            module: &fs_module,
+            entry_point: "main",
+        }),
+        rasterization_state: Some(wgpu::RasterizationStateDescriptor {
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: wgpu::CullMode::None,
+            depth_bias: 0,
+            depth_bias_slope_scale: 0.0,
+            depth_bias_clamp: 0.0,
+        }),
+        primitive_topology: wgpu::PrimitiveTopology::TriangleList,
+        color_states: &[color_state],
+        depth_stencil_state: None,
+        vertex_state: wgpu::VertexStateDescriptor {
+            index_format: wgpu::IndexFormat::Uint16,
+            vertex_buffers: &[],
+        },
+        sample_count,
+        sample_mask: !0,
+        alpha_to_coverage_enabled: false,
+    })
+}
+
+impl ViewportBloom {
+    /// The glow is added onto a framebuffer of the format and sample count
+    pub fn new(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            bindings: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::SampledTexture {
+                        multisampled: false,
+                        component_type: wgpu::TextureComponentType::Float,
+                        dimension: wgpu::TextureViewDimension::D2,
+                    },
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler { comparison: false },
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+                },
+            ],
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            lod_min_clamp: 0.0,
+            lod_max_clamp: 0.0,
+            compare: wgpu::CompareFunction::Undefined,
+        });
+        let blur_pipeline = build_fullscreen_pipeline(
+            device,
+            &layout,
+            include_bytes!("../shaders/blur.frag.spv"),
+            wgpu::ColorStateDescriptor {
+                format: EMISSION_FORMAT,
+                color_blend: wgpu::BlendDescriptor::REPLACE,
+                alpha_blend: wgpu::BlendDescriptor::REPLACE,
+                write_mask: wgpu::ColorWrite::ALL,
+            },
+            1,
+        );
+        // The glow adds light to the frame and keeps its alpha
+        let composite_pipeline = build_fullscreen_pipeline(
+            device,
+            &layout,
+            include_bytes!("../shaders/composite.frag.spv"),
+            wgpu::ColorStateDescriptor {
+                format,
+                color_blend: wgpu::BlendDescriptor {
+                    src_factor: wgpu::BlendFactor::One,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha_blend: wgpu::BlendDescriptor {
+                    src_factor: wgpu::BlendFactor::Zero,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                write_mask: wgpu::ColorWrite::ALL,
+            },
+            sample_count,
+        );
+        let strength_buf = device.create_buffer_with_data(
+            bytemuck::cast_slice(&[1.0f32, 0.0, 0.0, 0.0]),
+            wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        );
+        let targets = GlowTargets::new(device, &layout, &sampler, &strength_buf, width, height);
+        ViewportBloom {
+            layout,
+            sampler,
+            blur_pipeline,
+            composite_pipeline,
+            strength_buf,
+            targets,
+        }
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        self.targets = GlowTargets::new(
+            device,
+            &self.layout,
+            &self.sampler,
+            &self.strength_buf,
+            width,
+            height,
+        );
+    }
+
+    /// The uniform holding the strength of the glow in its first float
+    pub fn strength_buf(&self) -> &wgpu::Buffer {
+        &self.strength_buf
+    }
+
+    /// Estimated memory of the glow and depth targets
+    pub fn memory(&self) -> u64 {
+        (self.targets.width * self.targets.height) as u64 * PIXEL_SIZE as u64 * 3
+    }
+
+    /// The emission pipeline draws the voxels into this pass
+    pub fn begin_emission_pass<'a>(
+        &'a self,
+        encoder: &'a mut wgpu::CommandEncoder,
+    ) -> wgpu::RenderPass<'a> {
+        begin_emission_pass(
+            encoder,
+            &self.targets.emission_view,
+            &self.targets.depth_view,
+        )
+    }
+
+    /// Blurs the emission and adds it onto the framebuffer, keeping what it shows
+    pub fn composite(&self, encoder: &mut wgpu::CommandEncoder, framebuffer: &wgpu::TextureView) {
+        let targets = &self.targets;
+        let passes = [
+            (
+                &targets.blurred_view,
+                &targets.horizontal_bind_group,
+                &self.blur_pipeline,
+                wgpu::LoadOp::Clear,
+            ),
+            (
+                &targets.emission_view,
+                &targets.vertical_bind_group,
+                &self.blur_pipeline,
+                wgpu::LoadOp::Clear,
+            ),
+            (
+                framebuffer,
+                &targets.composite_bind_group,
+                &self.composite_pipeline,
+                wgpu::LoadOp::Load,
+            ),
+        ];
+        for (target, bind_group, pipeline, load_op) in passes.iter() {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                    attachment: target,
+                    resolve_target: None,
+                    load_op: *load_op,
+                    store_op: wgpu::StoreOp::Store,
+                    clear_color: wgpu::Color::TRANSPARENT,
+                }],
+                depth_stencil_attachment: None,
+            });
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(0, bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+    }
+}
+
+pub fn build_emission_pipeline(
+    device: &wgpu::Device,
+    uniform_buf: &wgpu::Buffer,
+    uniform_buf_size: u64,
+    palette_buf: &wgpu::Buffer,
+    palette_buf_size: u64,
+) -> (wgpu::BindGroup, wgpu::RenderPipeline) {
+    let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: None,
+        bindings: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::VERTEX,
+                ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1, // palette
+                visibility: wgpu::ShaderStage::VERTEX,
+                ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+            },
+        ],
+    });
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        bind_group_layouts: &[&bind_group_layout],
+    });
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &bind_group_layout,
+        bindings: &[
+            wgpu::Binding {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer {
+                    buffer: uniform_buf,
+                    range: 0..uniform_buf_size,
+                },
+            },
+            wgpu::Binding {
+                binding: 1,
+                resource: wgpu::BindingResource::Buffer {
+                    buffer: palette_buf,
+                    range: 0..palette_buf_size,
+                },
+            },
+        ],
+        label: None,
+    });
+
+    let vs = include_bytes!("../shaders/emission.vert.spv");
+    let fs = include_bytes!("../shaders/emission.frag.spv");
+
+    let vs_module =
+        device.create_shader_module(&wgpu::read_spirv(std::io::Cursor::new(&vs[..])).unwrap());
+
+    let fs_module =
+        device.create_shader_module(&wgpu::read_spirv(std::io::Cursor::new(&fs[..])).unwrap());
+
+    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        layout: &pipeline_layout,
+        vertex_stage: wgpu::ProgrammableStageDescriptor {
+            module: &vs_module,
+            entry_point: "main",
+        },
+        fragment_stage: Some(wgpu::ProgrammableStageDescriptor {
+            module: &fs_module,
+            entry_point: "main",
+        }),
+        rasterization_state: Some(wgpu::RasterizationStateDescriptor {
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: wgpu::CullMode::Back,
+            depth_bias: 0,
+            depth_bias_slope_scale: 0.0,
+            depth_bias_clamp: 0.0,
+        }),
+        primitive_topology: wgpu::PrimitiveTopology::TriangleList,
+        color_states: &[wgpu::ColorStateDescriptor {
+            format: EMISSION_FORMAT,
+            color_blend: wgpu::BlendDescriptor::REPLACE,
+            alpha_blend: wgpu::BlendDescriptor::REPLACE,
+            write_mask: wgpu::ColorWrite::ALL,
+        }],
+        depth_stencil_state: Some(wgpu::DepthStencilStateDescriptor {
+            format: EMISSION_DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil_front: wgpu::StencilStateFaceDescriptor::IGNORE,
+            stencil_back: wgpu::StencilStateFaceDescriptor::IGNORE,
+            stencil_read_mask: 0,
+            stencil_write_mask: 0,
+        }),
+        vertex_state: wgpu::VertexStateDescriptor {
+            index_format: wgpu::IndexFormat::Uint16,
+            vertex_buffers: &[
+                wgpu::VertexBufferDescriptor {
+                    stride: mem::size_of::<VoxelVertex>() as wgpu::BufferAddress,
+                    step_mode: wgpu::InputStepMode::Vertex,
+                    attributes: &[
+                        // Position
+                        wgpu::VertexAttributeDescriptor {
+                            format: wgpu::VertexFormat::Float3,
+                            offset: 0,
+                            shader_location: 0,
+                        },
+                        // Normal
+                        wgpu::VertexAttributeDescriptor {
+                            format: wgpu::VertexFormat::Float3,
+                            offset: 3 * 4,
+                            shader_location: 1,
+                        },
+                    ],
+                },
+                wgpu::VertexBufferDescriptor {
+                    stride: mem::size_of::<VoxelInstance>() as wgpu::BufferAddress,
+                    step_mode: wgpu::InputStepMode::Instance,
+                    attributes: &[
+                        // Offset
+                        wgpu::VertexAttributeDescriptor {
+                            format: wgpu::VertexFormat::Float3,
+                            offset: 0,
+                            shader_location: 2,
+                        },
+                        // Palette index
+                        wgpu::VertexAttributeDescriptor {
+                            format: wgpu::VertexFormat::Uint,
+                            offset: 3 * 4,
+                            shader_location: 3,
+                        },
+                    ],
+                },
+            ],
+        },
+        sample_count: 1,
+        sample_mask: !0,
+        alpha_to_coverage_enabled: false,
+    });
+
+    (bind_group, pipeline)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn emissive_pixels_glow_onto_the_background() {
+        let bloom = Bloom { strength: 1.0 };
+        // An emissive white square left of a plain one in a transparent image
+        let size = 120;
+        let at = |x: usize, y: usize| (y * size + x) * PIXEL_SIZE;
+        let mut pixels = vec![0; size * size * PIXEL_SIZE];
+        let mut emission = vec![0; size * size * PIXEL_SIZE];
+        for y in 30..50 {
+            for x in 30..50 {
+                pixels[at(x, y)..at(x, y) + PIXEL_SIZE].copy_from_slice(&[255; 4]);
+                emission[at(x, y)..at(x, y) + PIXEL_SIZE].copy_from_slice(&[255; 4]);
+                pixels[at(x + 60, y)..at(x + 60, y) + PIXEL_SIZE].copy_from_slice(&[255; 4]);
+            }
+        }
+        bloom.apply(&mut pixels, &emission, size, size);
+        assert_eq!(&pixels[at(40, 40)..at(40, 40) + PIXEL_SIZE], &[255; 4]);
+        let next = &pixels[at(50, 40)..at(50, 40) + PIXEL_SIZE];
+        assert!(next[3] > 0 && next[3] < 255);
+        assert_eq!(&next[..3], &[255, 255, 255]);
+        assert_eq!(&pixels[at(0, 0)..at(0, 0) + PIXEL_SIZE], &[0; 4]);
+        // The plain square doesn't glow
+        assert_eq!(&pixels[at(110, 40)..at(110, 40) + PIXEL_SIZE], &[0; 4]);
+    }
+}
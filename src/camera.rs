@@ -1,5 +1,6 @@
+use crate::geometry::{Ray, XZ_PLANE};
+use crate::view::CameraView;
 use camera_controllers::{CameraPerspective, Keys, OrbitZoomCamera, OrbitZoomCameraSettings};
-use cgmath::Transform;
 use winit::event;
 
 pub struct CameraWrapper {
@@ -20,6 +21,9 @@ pub struct CameraWrapper {
 
     /// The y postion of the mouse
     y_axis: f32,
+
+    /// Size of a voxel along each axis, the model matrix stretching the grid into the world
+    voxel_scale: [f32; 3],
 }
 
 impl CameraWrapper {
@@ -41,28 +45,182 @@ impl CameraWrapper {
             zoom_button: event::VirtualKeyCode::LControl,
             x_axis: 0.0,
             y_axis: 0.0,
+            voxel_scale: [1.0; 3],
         }
     }
 
+    /// Stretches the grid by the size of a voxel, the camera keeps looking at the same cell
+    pub fn set_voxel_scale(&mut self, voxel_scale: [f32; 3]) {
+        let target = self.target();
+        self.voxel_scale = voxel_scale;
+        self.set_target(target);
+    }
+
+    fn model_mat(&self) -> cgmath::Matrix4<f32> {
+        let [x, y, z] = self.voxel_scale;
+        cgmath::Matrix4::from_nonuniform_scale(x, y, z)
+    }
+
+    /// The point in the world of the point in the grid
+    fn to_world(&self, point: [f32; 3]) -> [f32; 3] {
+        let [x, y, z] = self.voxel_scale;
+        [point[0] * x, point[1] * y, point[2] * z]
+    }
+
     /// Generates the MVP matrix for the current camera setting
     pub fn mvp_matrices(&mut self, aspect_ratio: f32) -> [[[f32; 4]; 4]; 3] {
         self.cam_persp.aspect_ratio = aspect_ratio;
 
-        let model = cgmath::Matrix4::one();
+        let model = self.model_mat();
         let view = self.camera.camera(0.0).orthogonal();
         let proj = self.cam_persp.projection();
 
         [model.into(), view, proj]
     }
 
+    /// Generates the MVP matrix for rendering the region of the window a safe frame of the
+    /// given aspect ratio outlines, narrowing the field of view for frames wider than the window
+    pub fn framed_mvp_matrices(
+        &mut self,
+        aspect_ratio: f32,
+        window_aspect_ratio: f32,
+    ) -> [[[f32; 4]; 4]; 3] {
+        let fov = self.cam_persp.fov;
+        if aspect_ratio > window_aspect_ratio {
+            let half_fov = (0.5 * fov).to_radians().tan() * window_aspect_ratio / aspect_ratio;
+            self.cam_persp.fov = 2.0 * half_fov.atan().to_degrees();
+        }
+        let matrices = self.mvp_matrices(aspect_ratio);
+        self.cam_persp.fov = fov;
+        matrices
+    }
+
+    /// Like framed_mvp_matrices for an eye moved sideways by the offset in the world, to the
+    /// right for positive offsets, and turned to keep looking at the target
+    pub fn eye_mvp_matrices(
+        &mut self,
+        aspect_ratio: f32,
+        window_aspect_ratio: f32,
+        offset: f32,
+    ) -> [[[f32; 4]; 4]; 3] {
+        let mut matrices = self.framed_mvp_matrices(aspect_ratio, window_aspect_ratio);
+        let eye = cgmath::Matrix4::look_at(
+            cgmath::Point3::new(offset, 0.0, 0.0),
+            cgmath::Point3::new(0.0, 0.0, -self.camera.distance),
+            cgmath::Vector3::unit_y(),
+        );
+        let view: cgmath::Matrix4<f32> = matrices[1].into();
+        matrices[1] = (eye * view).into();
+        matrices
+    }
+
+    /// Maps grid space into view space, the grid stretched by the size of a voxel first
     pub fn model_view_mat(&self) -> cgmath::Matrix4<f32> {
-        self.camera.camera(0.0).orthogonal().into()
+        let view: cgmath::Matrix4<f32> = self.camera.camera(0.0).orthogonal().into();
+        view * self.model_mat()
     }
 
     pub fn projection_mat(&self) -> cgmath::Matrix4<f32> {
         self.cam_persp.projection().into()
     }
 
+    /// The ray through the cursor position in pixels, window_size is the width and height
+    pub fn ray_at(&self, cursor_pos: [f32; 2], window_size: [u32; 2]) -> Ray {
+        Ray::from_screen(
+            cursor_pos,
+            self.model_view_mat(),
+            self.projection_mat(),
+            window_size,
+        )
+    }
+
+    /// The point in the grid the camera orbits around
+    pub fn target(&self) -> [f32; 3] {
+        let [x, y, z] = self.voxel_scale;
+        let target = self.camera.target;
+        [target[0] / x, target[1] / y, target[2] / z]
+    }
+
+    /// Moves the point the camera orbits around, the camera keeps its angle and distance
+    pub fn set_target(&mut self, target: [f32; 3]) {
+        self.camera.target = self.to_world(target);
+    }
+
+    /// Where the camera looks at the grid from
+    pub fn view(&self) -> CameraView {
+        CameraView {
+            target: self.target(),
+            yaw: self.camera.yaw,
+            pitch: self.camera.pitch,
+            distance: self.camera.distance,
+            fov: self.cam_persp.fov,
+        }
+    }
+
+    pub fn set_view(&mut self, view: &CameraView) {
+        self.set_target(view.target);
+        self.camera.yaw = view.yaw;
+        self.camera.pitch = view.pitch;
+        self.camera.distance = view.distance;
+        self.cam_persp.fov = view.fov;
+        // Orbiting by nothing turns the camera to the new angles
+        self.orbit(0.0, 0.0);
+    }
+
+    /// Where the eye is and where the corners of the window look at the ground, as x and z
+    /// coordinates of the grid. Corners looking above the horizon reach as far as the camera
+    /// sees.
+    pub fn ground_footprint(&self, window_size: [u32; 2]) -> Vec<[f32; 2]> {
+        let eye = self.camera.camera(0.0).position;
+        let eye = [eye[0] / self.voxel_scale[0], eye[2] / self.voxel_scale[2]];
+        let [width, height] = [window_size[0] as f32, window_size[1] as f32];
+        let corners = [[0.0, 0.0], [width, 0.0], [width, height], [0.0, height]];
+        let mut footprint = vec![eye];
+        for corner in corners.iter() {
+            let ray = self.ray_at(*corner, window_size);
+            let point = match ray.plane_intersection(&XZ_PLANE) {
+                Some((point, _)) => point,
+                None => ray.end,
+            };
+            footprint.push([point.x, point.z]);
+        }
+        footprint
+    }
+
+    /// Distance of the point in the grid from the eye
+    pub fn distance_to(&self, point: [f32; 3]) -> f32 {
+        let point = self.to_world(point);
+        let eye = self.camera.camera(0.0).position;
+        let d = [point[0] - eye[0], point[1] - eye[1], point[2] - eye[2]];
+        (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt()
+    }
+
+    /// Moves the camera as if only the given keys were held during the movement
+    fn control_with(&mut self, keys: Keys, dx: f32, dy: f32) {
+        let held = self.camera.keys;
+        self.camera.keys = keys;
+        self.camera.control_camera(dx, dy);
+        self.camera.keys = held;
+    }
+
+    pub fn orbit(&mut self, dx: f32, dy: f32) {
+        self.control_with(Keys::ORBIT, dx, dy);
+    }
+
+    /// Orbits around the up axis by the angle in radians
+    pub fn turn(&mut self, angle: f32) {
+        let speed = self.camera.settings.orbit_speed;
+        self.orbit(angle / speed, 0.0);
+    }
+
+    pub fn pan(&mut self, dx: f32, dy: f32) {
+        self.control_with(Keys::PAN, dx, dy);
+    }
+
+    pub fn zoom(&mut self, delta: f32) {
+        self.control_with(Keys::ZOOM, 0.0, delta);
+    }
+
     /// Respond to scroll and key press/release events
     pub fn update(&mut self, window_event: &winit::event::WindowEvent) -> bool {
         let mut viewport_changed = false;
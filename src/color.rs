@@ -1,5 +0,0 @@
-pub const RED: [f32; 4] = [1.0, 0.0, 0.0, 1.0];
-pub const HALF_ALPHA_RED: [f32; 4] = [1.0, 0.0, 0.0, 0.2];
-pub const GREEN: [f32; 4] = [0.0, 1.0, 0.0, 1.0];
-pub const BLUE: [f32; 4] = [0.0, 0.0, 1.0, 1.0];
-pub const TRANSPARENT: [f32; 4] = [0.0, 0.0, 0.0, 0.0];
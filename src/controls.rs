@@ -1,26 +1,168 @@
+use crate::analysis::{SceneStats, ValidationReport};
+use crate::bloom::Bloom;
+use crate::budget::{Budget, BudgetWarning};
+use crate::color::parse_hex;
+use crate::depth_of_field::MAX_BLUR_RADIUS;
+use crate::dialog;
+use crate::export::Crop;
+use crate::font::FontKind;
+use crate::generator::{BrushProfile, ShapeKind, StairsOptions, TextOptions};
+use crate::history;
+use crate::light::{LightingPreset, ToneMapping};
+use crate::marker::Marker;
+use crate::palette_tools::{self, Harmony, PaletteOrder, RampSpace};
+use crate::pen::DitherPattern;
+use crate::prefab::Prefab;
+use crate::screenshot::{
+    FrameAspect, Turntable, MAX_SCREENSHOT_SIZE, MAX_TURNTABLE_FPS, MAX_TURNTABLE_FRAMES,
+    MAX_TURNTABLE_PIXELS,
+};
+use crate::selection::{Combine, MaskMode};
+use crate::settings::Settings;
+use crate::snapshot::SnapshotDiff;
+use crate::stats::{Stats, FRAME_HISTORY};
+use crate::stereo::{Stereo, StereoLayout, MAX_STEREO_SEPARATION};
+use crate::theme::ThemeKind;
+use crate::transform::{Axis, ObjectPivot, Pivot, RadialSymmetry};
+use crate::volume::{TransferFunction, VolumeOptions};
 use iced_wgpu::{
     canvas,
     container::{Style, StyleSheet},
     Renderer,
 };
 use iced_winit::{
-    button, mouse, Background, Button, Color, Column, Command, Container, Element, Length, Point,
-    Program, Radio, Rectangle, Size, Text,
+    button, mouse, slider, text_input, Align, Background, Button, Checkbox, Color, Column, Command,
+    Container, Element, Length, Point, Program, ProgressBar, Radio, Rectangle, Row, Size, Slider,
+    Text, TextInput,
 };
 
 use std::cell::Cell;
 
 pub const COLOR_SIZE: f32 = 20.0;
+const GRAPH_HEIGHT: f32 = 60.0;
+// Frame time at the top of the graph unless a slower frame needs more room
+const GRAPH_MIN_SCALE: f32 = 1000.0 / 30.0;
+const FRAME_BUDGET: f32 = 1000.0 / 60.0;
+const MINIMAP_SIZE: f32 = 160.0;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum EditOp {
     Draw,
     Erase,
     Refill,
+    Paint,
+    /// Selects the connected voxels under the cursor
+    Select,
+    /// Fills stairs from the start to the end of the dragged rectangle
+    Stairs,
+    /// Writes the text on the active plane at the clicked cell
+    Text,
+    /// Places the control points of a curve, Enter sweeps the brush along it
+    Curve,
+    /// Draws a 2D shape into one layer of the active plane
+    Shape,
+    /// Paints copies of the voxels around the source anchor, Alt+click sets the anchor
+    Clone,
+    /// Blurs the colors under the brush without changing the shape
+    Smooth,
+    /// Focuses the depth of field of screenshots and turntables on the clicked voxel
+    Focus,
 }
 
 impl EditOp {
-    pub const ALL: [EditOp; 3] = [EditOp::Draw, EditOp::Erase, EditOp::Refill];
+    pub const ALL: [EditOp; 12] = [
+        EditOp::Draw,
+        EditOp::Erase,
+        EditOp::Refill,
+        EditOp::Paint,
+        EditOp::Select,
+        EditOp::Stairs,
+        EditOp::Text,
+        EditOp::Curve,
+        EditOp::Shape,
+        EditOp::Clone,
+        EditOp::Smooth,
+        EditOp::Focus,
+    ];
+}
+
+/// Operations on the selected voxels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectionAction {
+    Delete,
+    /// Deletes everything but the selection
+    Isolate,
+}
+
+/// Operations on the saved masks, by name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MaskAction {
+    /// Saves the selection as the mask
+    Save(String),
+    /// Merges the mask into the selection
+    Recall(String, Combine),
+    Delete(String),
+    /// Sets the pivot of the mask's object, None clears it
+    SetPivot(String, Option<ObjectPivot>),
+    /// Places a linked copy of the mask by the offset and quarter turns around the vertical
+    Instance(String, [i64; 3], usize),
+    /// Turns the linked copies of the mask into plain voxels
+    Unlink(String),
+}
+
+/// Operations on the named snapshots of the project
+#[derive(Debug, Clone, PartialEq)]
+pub enum SnapshotAction {
+    /// Keeps the scene as the snapshot
+    Save(String),
+    /// Replaces the scene with the snapshot
+    Restore(String),
+    /// Selects the cells changed since the snapshot
+    Diff(String),
+    Delete(String),
+}
+
+/// Operations on the named markers of the project
+#[derive(Debug, Clone, PartialEq)]
+pub enum MarkerAction {
+    /// Places a marker with the name and the note on the selection, or on the camera target
+    /// without one
+    Add(String, String),
+    /// Points the camera at the marker
+    Jump(String),
+    Delete(String),
+}
+
+/// Operations on the other projects placed into the scene
+#[derive(Debug, Clone, PartialEq)]
+pub enum PrefabAction {
+    /// Places the project into the scene, linked to its file
+    Link(Prefab),
+    /// Turns the voxels of the prefabs of the project file into plain voxels
+    Unlink(String),
+}
+
+/// Operations on the palette entries of the scene
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PaletteAction {
+    Sort(PaletteOrder),
+    /// Merges entries at most the tolerance apart
+    Merge(f32),
+}
+
+/// Operations on the finer grids embedded in the scene
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubGridAction {
+    /// Moves the cells from the corner of the selection, or from the camera target without
+    /// one, into a sub-grid of the density
+    Refine(usize),
+    /// Edits the sub-grid at the selection or the camera target in place of the scene
+    Enter,
+    /// Goes back to editing the scene around the sub-grid
+    Leave,
+    /// Puts the voxels of the sub-grid at the selection or the camera target back into the
+    /// cells of the scene
+    Flatten,
 }
 
 impl Default for EditOp {
@@ -29,11 +171,179 @@ impl Default for EditOp {
     }
 }
 
+/// What the pen pressure controls while painting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PressureMapping {
+    Off,
+    BrushSize,
+    ValueJitter,
+}
+
+impl PressureMapping {
+    pub const ALL: [PressureMapping; 3] = [
+        PressureMapping::Off,
+        PressureMapping::BrushSize,
+        PressureMapping::ValueJitter,
+    ];
+}
+
+impl Default for PressureMapping {
+    fn default() -> PressureMapping {
+        PressureMapping::BrushSize
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum Message {
     EditChanged(EditOp),
     ExportPressed,
+    UsdExportPressed,
+    FbxExportPressed,
+    ColladaExportPressed,
+    VdbExportPressed,
+    ScreenshotSizeChanged(String),
+    FrameAspectChanged(FrameAspect),
+    SafeFrameToggled(bool),
+    DepthOfFieldToggled(bool),
+    DepthOfFieldBlurChanged(f32),
+    BloomToggled(bool),
+    ShadowCatcherToggled(bool),
+    BloomStrengthChanged(f32),
+    ScreenshotPressed,
+    TurntableTimingChanged(String),
+    TurntablePressed,
+    StereoSeparationChanged(f32),
+    StereoLayoutChanged(StereoLayout),
+    StereoPressed,
+    TextureAtlasToggled(bool),
+    ExportCropChanged(Crop),
+    ExportPivotChanged(Option<Pivot>),
+    BatchExportPressed,
+    ReexportPressed,
+    BatchTemplateChanged(String),
+    SaveProjectPressed,
+    QuickSavePressed,
+    JournalSavesToggled(bool),
+    MacroRecordPressed,
+    MacroPlayPressed,
+    MacroStateChanged(bool, usize),
+    RecordSessionPressed,
+    ReplaySessionPressed,
+    ReplaySpeedChanged(f32),
+    SessionStateChanged(bool, bool),
+    OpenProjectPressed,
+    VolumeLayoutChanged(String),
+    VolumeThresholdChanged(f32),
+    TransferFunctionChanged(TransferFunction),
+    ImportColorsChanged(String),
+    ImportDitherToggled(bool),
+    ImportVolumePressed,
+    CancelPressed,
+    ProgressChanged(Option<(&'static str, f32)>),
+    GpuPickingToggled(bool),
+    WorkingPlaneToggled(bool),
+    SnapToSurfaceToggled(bool),
+    RulersToggled(bool),
+    RulerStepChanged(f32),
+    TransformGizmoToggled(bool),
+    SoftSelectionToggled(bool),
+    SoftRadiusChanged(f32),
+    PressureMappingChanged(PressureMapping),
+    ThemeChanged(ThemeKind),
+    ExposureChanged(f32),
+    ToneMappingChanged(ToneMapping),
+    LightingChanged(LightingPreset),
+    ToggleStats,
+    StatsChanged(Stats),
+    ToggleSceneStats,
+    SceneStatsChanged(SceneStats),
+    ToggleHistory,
+    HistoryChanged(Vec<history::Item>),
+    ToggleMinimap,
+    ToggleMarkers,
+    MarkersChanged(Vec<(String, Marker)>),
+    MarkerNameChanged(String),
+    MarkerNoteChanged(String),
+    MarkerActionPressed(MarkerAction),
+    MinimapColumnsChanged(usize, Vec<Option<(usize, [f32; 4])>>),
+    MinimapFrustumChanged(Vec<[f32; 2]>),
+    MinimapPressed([f32; 2]),
+    HistoryEntryPressed(usize),
+    SelectColorPressed(u8),
+    ClearSelectionPressed,
+    ValidatePressed,
+    SelectionActionPressed(SelectionAction),
+    RecenterPressed(Pivot),
+    MaskModeChanged(MaskMode),
+    MaskNameChanged(String),
+    PivotCellChanged(String),
+    InstancePlacementChanged(String),
+    MaskCombineChanged(Combine),
+    MaskActionPressed(MaskAction),
+    MaskNamesChanged(Vec<String>),
+    SnapshotNameChanged(String),
+    SnapshotActionPressed(SnapshotAction),
+    PrefabChanged(String),
+    PrefabActionPressed(PrefabAction),
+    VoxelScaleChanged(String),
+    SetVoxelScalePressed([f32; 3]),
+    VoxelScaleLoaded([f32; 3]),
+    SubGridDensityChanged(String),
+    SubGridActionPressed(SubGridAction),
+    BudgetChanged(String),
+    SetBudgetPressed(Budget),
+    BudgetLoaded(Budget),
+    BudgetWarningsChanged(Option<Vec<BudgetWarning>>),
+    SnapshotNamesChanged(Vec<String>),
+    SnapshotDiffChanged(String, SnapshotDiff),
+    SymmetryAxisChanged(Axis),
+    KeepPositiveToggled(bool),
+    SymmetrizePressed,
+    ArrayCountChanged(String),
+    ArrayOffsetChanged(String),
+    ArrayRotateToggled(bool),
+    ArrayPressed,
+    RadialCountChanged(String),
+    RadialAxisChanged(Axis),
+    RadialSymmetryToggled(bool),
+    RadialArrayPressed,
+    RevolvePressed,
+    LightDirectionChanged(String),
+    ShadingStrengthChanged(f32),
+    BakeShadingPressed,
+    BakeOcclusionPressed,
+    ToggleBakePressed,
+    DiscardBakePressed,
+    HsvShiftChanged(String),
+    ShiftColorsPressed,
+    EmissiveTogglePressed,
+    PaletteActionPressed(PaletteAction),
+    MergeToleranceChanged(f32),
+    PaletteUsageChanged(usize, Vec<u8>),
+    BoxPositionChanged(String),
+    BoxSizeChanged(String),
+    PlaceBoxPressed,
+    StepDepthChanged(String),
+    RailingToggled(bool),
+    TextChanged(String),
+    FontChanged(FontKind),
+    TextScaleChanged(String),
+    TextDepthChanged(String),
+    CurveRadiusChanged(String),
+    CurveProfileChanged(BrushProfile),
+    ShapeChanged(ShapeKind),
+    OutlineToggled(bool),
+    ValidationChanged(ValidationReport),
+    DitherPatternChanged(DitherPattern),
+    DitherAmountChanged(f32),
     ColorPicked(Color),
+    RampKeysChanged(String),
+    RampStepsChanged(f32),
+    RampSpaceChanged(RampSpace),
+    AddRampPressed(Vec<[f32; 4]>),
+    HarmonyChanged(Harmony),
+    AddHarmonyPressed,
+    DitherColorPicked(Color),
 }
 
 #[derive(Default)]
@@ -89,6 +399,16 @@ impl ColorPicker {
         }
     }
 
+    /// Adds the colors as rows of their own below the others
+    fn add_rows(&mut self, colors: &[[f32; 4]]) {
+        let rows = (self.colors.len() + Self::COLORS_PER_LINE - 1) / Self::COLORS_PER_LINE;
+        self.colors
+            .resize(rows * Self::COLORS_PER_LINE, Color::TRANSPARENT);
+        self.colors
+            .extend(colors.iter().map(|[r, g, b, a]| Color::new(*r, *g, *b, *a)));
+        self.canvas_cache.clear();
+    }
+
     fn draw(&self, frame: &mut canvas::Frame) {
         let box_size = Size {
             width: COLOR_SIZE,
@@ -153,22 +473,859 @@ impl PickedColor {
     }
 }
 
+#[derive(Default)]
+struct FrameGraph {
+    frame_times: Vec<f32>,
+    canvas_cache: canvas::Cache,
+}
+
+impl FrameGraph {
+    fn new(frame_times: Vec<f32>) -> Self {
+        FrameGraph {
+            frame_times,
+            canvas_cache: canvas::Cache::default(),
+        }
+    }
+
+    fn draw(&self, frame: &mut canvas::Frame) {
+        let scale = self
+            .frame_times
+            .iter()
+            .cloned()
+            .fold(GRAPH_MIN_SCALE, f32::max);
+        frame.fill_rectangle(
+            Point::ORIGIN,
+            Size {
+                width: FRAME_HISTORY as f32,
+                height: GRAPH_HEIGHT,
+            },
+            Color::new(0.9, 0.9, 0.9, 1.0),
+        );
+        for (i, frame_time) in self.frame_times.iter().enumerate() {
+            let height = frame_time / scale * GRAPH_HEIGHT;
+            let color = if *frame_time > FRAME_BUDGET {
+                Color::new(0.8, 0.1, 0.1, 1.0)
+            } else {
+                Color::new(0.1, 0.6, 0.1, 1.0)
+            };
+            frame.fill_rectangle(
+                Point {
+                    x: i as f32,
+                    y: GRAPH_HEIGHT - height,
+                },
+                Size { width: 1.0, height },
+                color,
+            );
+        }
+    }
+
+    pub fn view(&mut self) -> Element<Message, Renderer> {
+        canvas::Canvas::new(self)
+            .width(Length::Units(FRAME_HISTORY as u16))
+            .height(Length::Units(GRAPH_HEIGHT as u16))
+            .into()
+    }
+}
+
+/// The scene from above, lighter where it is higher, with the view of the camera over it
+#[derive(Default)]
+struct Minimap {
+    extent: usize,
+    columns: Vec<Option<(usize, [f32; 4])>>,
+    /// The eye and the ground points of the window corners, in x and z of the grid
+    frustum: Vec<[f32; 2]>,
+    canvas_cache: canvas::Cache,
+}
+
+impl Minimap {
+    /// Pixels per cell
+    fn scale(&self) -> f32 {
+        MINIMAP_SIZE / self.extent.max(1) as f32
+    }
+
+    fn set_columns(&mut self, extent: usize, columns: Vec<Option<(usize, [f32; 4])>>) {
+        self.extent = extent;
+        self.columns = columns;
+        self.canvas_cache.clear();
+    }
+
+    fn draw(&self, frame: &mut canvas::Frame) {
+        frame.fill_rectangle(
+            Point::ORIGIN,
+            Size {
+                width: MINIMAP_SIZE,
+                height: MINIMAP_SIZE,
+            },
+            Color::new(0.15, 0.15, 0.15, 1.0),
+        );
+        let scale = self.scale();
+        for (i, column) in self.columns.iter().enumerate() {
+            if let Some((height, [r, g, b, _])) = column {
+                let light = 0.4 + 0.6 * (*height + 1) as f32 / self.extent as f32;
+                frame.fill_rectangle(
+                    Point {
+                        x: (i % self.extent) as f32 * scale,
+                        y: (i / self.extent) as f32 * scale,
+                    },
+                    Size {
+                        width: scale,
+                        height: scale,
+                    },
+                    Color::new(r * light, g * light, b * light, 1.0),
+                );
+            }
+        }
+    }
+
+    /// Lines from the eye to the corners of the view and around them, kept on the map
+    fn draw_frustum(&self, frame: &mut canvas::Frame) {
+        let scale = self.scale();
+        let point = |[x, z]: [f32; 2]| Point {
+            x: (x * scale).max(0.0).min(MINIMAP_SIZE),
+            y: (z * scale).max(0.0).min(MINIMAP_SIZE),
+        };
+        if let Some((eye, corners)) = self.frustum.split_first() {
+            let outline = canvas::Path::new(|builder| {
+                for corner in corners.iter() {
+                    builder.move_to(point(*eye));
+                    builder.line_to(point(*corner));
+                }
+                if let Some(last) = corners.last() {
+                    builder.move_to(point(*last));
+                    for corner in corners.iter() {
+                        builder.line_to(point(*corner));
+                    }
+                }
+            });
+            frame.stroke(
+                &outline,
+                canvas::Stroke::default()
+                    .with_color(Color::new(1.0, 0.8, 0.2, 1.0))
+                    .with_width(1.5),
+            );
+        }
+    }
+
+    pub fn view(&mut self) -> Element<Message, Renderer> {
+        let map = canvas::Canvas::new(self)
+            .width(Length::Units(MINIMAP_SIZE as u16))
+            .height(Length::Units(MINIMAP_SIZE as u16));
+        let panel = Column::new()
+            .spacing(5)
+            .push(Text::new("Map (click to move the camera)").size(16))
+            .push(map);
+        Container::new(panel).padding(10).style(UiStyle {}).into()
+    }
+}
+
+fn megabytes(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f32 / (1024.0 * 1024.0))
+}
+
+#[derive(Default)]
+struct StatsPanel {
+    stats: Stats,
+    frame_graph: FrameGraph,
+}
+
+impl StatsPanel {
+    fn new(stats: Stats) -> Self {
+        StatsPanel {
+            frame_graph: FrameGraph::new(stats.frame_times.clone()),
+            stats,
+        }
+    }
+
+    fn view(&mut self) -> Element<Message, Renderer> {
+        let stats = &self.stats;
+        let frame_time = stats.frame_times.last().cloned().unwrap_or(0.0);
+        let panel = Column::new()
+            .spacing(5)
+            .push(Text::new(format!("Frame time: {:.2} ms", frame_time)).size(16))
+            .push(self.frame_graph.view())
+            .push(Text::new(format!("Draw calls: {}", stats.render.draw_calls)).size(16))
+            .push(Text::new(format!("Triangles: {}", stats.render.triangles)).size(16))
+            .push(
+                Text::new(format!(
+                    "Remesh: {} chunks, {:.3} ms/chunk",
+                    stats.render.remeshed_chunks,
+                    stats.render.remesh_time.as_secs_f32() * 1000.0
+                ))
+                .size(16),
+            )
+            .push(
+                Text::new(format!(
+                    "Scene memory: {}",
+                    megabytes(stats.scene_memory as u64)
+                ))
+                .size(16),
+            )
+            .push(
+                Text::new(format!(
+                    "GPU memory: {}",
+                    megabytes(stats.render.gpu_memory)
+                ))
+                .size(16),
+            );
+
+        Container::new(panel).padding(10).style(UiStyle {}).into()
+    }
+}
+
+/// Voxel counts per color with buttons selecting the voxels of a color
+#[derive(Default)]
+struct ScenePanel {
+    stats: SceneStats,
+    swatches: Vec<PickedColor>,
+    select_buttons: Vec<button::State>,
+    clear_button: button::State,
+}
+
+impl ScenePanel {
+    fn new(stats: SceneStats) -> Self {
+        ScenePanel {
+            swatches: stats
+                .color_counts
+                .iter()
+                .map(|(_, c, _)| PickedColor::new(Color::new(c[0], c[1], c[2], c[3])))
+                .collect(),
+            select_buttons: vec![button::State::default(); stats.color_counts.len()],
+            clear_button: button::State::default(),
+            stats,
+        }
+    }
+
+    fn view(&mut self) -> Element<Message, Renderer> {
+        let stats = &self.stats;
+        let [width, height, depth] = stats.dimensions();
+        let mut panel = Column::new()
+            .spacing(5)
+            .push(Text::new(format!("Voxels: {}", stats.voxel_count)).size(16))
+            .push(Text::new(format!("Components: {}", stats.component_count)).size(16))
+            .push(Text::new(format!("Bounds: {} x {} x {}", width, height, depth)).size(16))
+            .push(
+                Text::new(format!(
+                    "Surface/volume: {:.2} faces per voxel",
+                    stats.surface_to_volume()
+                ))
+                .size(16),
+            );
+        for ((swatch, button), (index, _, count)) in self
+            .swatches
+            .iter_mut()
+            .zip(self.select_buttons.iter_mut())
+            .zip(stats.color_counts.iter())
+        {
+            panel = panel.push(
+                Row::new()
+                    .spacing(5)
+                    .align_items(Align::Center)
+                    .push(swatch.view())
+                    .push(
+                        Button::new(button, Text::new(format!("{}", count)).size(16))
+                            .on_press(Message::SelectColorPressed(*index)),
+                    ),
+            );
+        }
+        panel = panel.push(
+            Button::new(
+                &mut self.clear_button,
+                Text::new("Clear selection").size(16),
+            )
+            .on_press(Message::ClearSelectionPressed),
+        );
+
+        Container::new(panel).padding(10).style(UiStyle {}).into()
+    }
+}
+
+/// Recorded scene states, pressing one restores it
+#[derive(Default)]
+struct HistoryPanel {
+    items: Vec<history::Item>,
+    entry_buttons: Vec<button::State>,
+}
+
+impl HistoryPanel {
+    fn new(items: Vec<history::Item>) -> Self {
+        HistoryPanel {
+            entry_buttons: vec![button::State::default(); items.len()],
+            items,
+        }
+    }
+
+    fn view(&mut self) -> Element<Message, Renderer> {
+        let mut panel = Column::new()
+            .spacing(5)
+            .push(Text::new("History (Ctrl+Z, Ctrl+Shift+Z)").size(16));
+        for (index, (button, item)) in self
+            .entry_buttons
+            .iter_mut()
+            .zip(self.items.iter())
+            .enumerate()
+        {
+            // Branches are indented under the state they were started from
+            let label = format!(
+                "{}{}{}",
+                "  ".repeat(item.depth),
+                if item.current { "> " } else { "" },
+                item.label
+            );
+            panel = panel.push(
+                Button::new(button, Text::new(label).size(16))
+                    .on_press(Message::HistoryEntryPressed(index)),
+            );
+        }
+
+        Container::new(panel).padding(10).style(UiStyle {}).into()
+    }
+}
+
+/// The markers of the project with their notes, pressing one points the camera at it
+#[derive(Default)]
+struct MarkersPanel {
+    markers: Vec<(String, Marker)>,
+    jump_buttons: Vec<button::State>,
+}
+
+impl MarkersPanel {
+    fn new(markers: Vec<(String, Marker)>) -> Self {
+        MarkersPanel {
+            jump_buttons: vec![button::State::default(); markers.len()],
+            markers,
+        }
+    }
+
+    fn view(&mut self) -> Element<Message, Renderer> {
+        let mut panel = Column::new().spacing(5).push(Text::new("Markers").size(16));
+        if self.markers.is_empty() {
+            panel = panel.push(Text::new("None").size(16));
+        }
+        for (button, (name, marker)) in self.jump_buttons.iter_mut().zip(self.markers.iter()) {
+            let [x, y, z] = marker.cell;
+            panel = panel.push(
+                Button::new(
+                    button,
+                    Text::new(format!("{} ({}, {}, {})", name, x, y, z)).size(16),
+                )
+                .on_press(Message::MarkerActionPressed(MarkerAction::Jump(
+                    name.clone(),
+                ))),
+            );
+            if !marker.note.is_empty() {
+                panel = panel.push(Text::new(marker.note.clone()).size(14));
+            }
+        }
+
+        Container::new(panel).padding(10).style(UiStyle {}).into()
+    }
+}
+
 pub struct Controls {
     edit_op: Cell<EditOp>,
     export_button: button::State,
+    usd_export_button: button::State,
+    usd_save_file: Cell<Option<String>>,
+    fbx_export_button: button::State,
+    fbx_save_file: Cell<Option<String>>,
+    collada_export_button: button::State,
+    collada_save_file: Cell<Option<String>>,
+    vdb_export_button: button::State,
+    vdb_save_file: Cell<Option<String>>,
+    screenshot_size_input: text_input::State,
+    screenshot_size: String,
+    safe_frame: bool,
+    depth_of_field: bool,
+    depth_of_field_blur: f32,
+    depth_of_field_slider: slider::State,
+    bloom: bool,
+    bloom_strength: f32,
+    bloom_strength_slider: slider::State,
+    shadow_catcher: bool,
+    screenshot_button: button::State,
+    screenshot_file: Cell<Option<String>>,
+    turntable_timing_input: text_input::State,
+    turntable_timing: String,
+    turntable_button: button::State,
+    turntable_file: Cell<Option<String>>,
+    stereo_separation: f32,
+    stereo_separation_slider: slider::State,
+    stereo_layout: StereoLayout,
+    stereo_button: button::State,
+    stereo_file: Cell<Option<String>>,
+    texture_atlas: bool,
+    export_crop: Crop,
+    export_pivot: Option<Pivot>,
+    quick_save_button: button::State,
+    quick_save_requested: Cell<bool>,
+    journal_saves: bool,
+    macro_recording: bool,
+    macro_steps: usize,
+    macro_record_button: button::State,
+    macro_play_button: button::State,
+    macro_record_requested: Cell<bool>,
+    macro_play_requested: Cell<bool>,
+    // Whether a session is being recorded or replayed, buttons of either stop it
+    recording: bool,
+    replaying: bool,
+    record_button: button::State,
+    replay_button: button::State,
+    record_session: Cell<Option<Option<String>>>,
+    replay_session: Cell<Option<Option<String>>>,
+    replay_speed: f32,
+    replay_speed_slider: slider::State,
+    batch_export_button: button::State,
+    reexport_button: button::State,
+    reexport_requested: Cell<bool>,
+    batch_template_input: text_input::State,
+    batch_template: String,
+    batch_save_file: Cell<Option<String>>,
+    save_project_button: button::State,
+    open_project_button: button::State,
+    volume_layout_input: text_input::State,
+    volume_layout: String,
+    volume_threshold_slider: slider::State,
+    volume_threshold: f32,
+    transfer_function: TransferFunction,
+    // Most colors of imports, empty for no limit
+    import_colors_input: text_input::State,
+    import_colors: String,
+    import_dither: bool,
+    import_volume_button: button::State,
+    import_volume_file: Cell<Option<String>>,
+    cancel_button: button::State,
     color_picker: ColorPicker,
+    // Colors and steps of shading ramps added to the picker
+    ramp_keys_input: text_input::State,
+    ramp_keys: String,
+    ramp_steps_slider: slider::State,
+    ramp_steps: usize,
+    ramp_space: RampSpace,
+    add_ramp_button: button::State,
     picked_color: PickedColor,
+    // Colors going with the draw color, picked from like the color picker
+    harmony: Harmony,
+    harmony_colors: ColorPicker,
+    add_harmony_button: button::State,
+    dither_color: PickedColor,
+    dither_pattern: DitherPattern,
+    dither_slider: slider::State,
+    dither_amount: f32,
     save_file: Cell<Option<String>>,
+    save_project_file: Cell<Option<String>>,
+    open_project_file: Cell<Option<String>>,
+    progress: Option<(&'static str, f32)>,
+    cancel_requested: Cell<bool>,
+    show_stats: bool,
+    stats_panel: StatsPanel,
+    show_scene_stats: bool,
+    scene_panel: ScenePanel,
+    show_history: bool,
+    history_panel: HistoryPanel,
+    show_minimap: bool,
+    minimap: Minimap,
+    show_markers: bool,
+    markers_panel: MarkersPanel,
+    marker_name_input: text_input::State,
+    marker_name: String,
+    marker_note_input: text_input::State,
+    marker_note: String,
+    add_marker_button: button::State,
+    delete_marker_button: button::State,
+    marker_action: Cell<Option<MarkerAction>>,
+    minimap_target: Cell<Option<[f32; 2]>>,
+    history_jump: Cell<Option<usize>>,
+    select_color: Cell<Option<u8>>,
+    clear_selection: Cell<bool>,
+    validate_button: button::State,
+    delete_selection_button: button::State,
+    isolate_selection_button: button::State,
+    selection_action: Cell<Option<SelectionAction>>,
+    center_button: button::State,
+    stand_button: button::State,
+    recenter: Cell<Option<Pivot>>,
+    mask_mode: MaskMode,
+    mask_name_input: text_input::State,
+    mask_name: String,
+    pivot_cell_input: text_input::State,
+    pivot_cell: String,
+    set_pivot_button: button::State,
+    instance_placement_input: text_input::State,
+    instance_placement: String,
+    instance_button: button::State,
+    unlink_button: button::State,
+    mask_combine: Combine,
+    mask_names: Vec<String>,
+    save_mask_button: button::State,
+    recall_mask_button: button::State,
+    delete_mask_button: button::State,
+    mask_action: Cell<Option<MaskAction>>,
+    snapshot_name_input: text_input::State,
+    snapshot_name: String,
+    snapshot_names: Vec<String>,
+    snapshot_diff: Option<(String, SnapshotDiff)>,
+    save_snapshot_button: button::State,
+    restore_snapshot_button: button::State,
+    diff_snapshot_button: button::State,
+    delete_snapshot_button: button::State,
+    snapshot_action: Cell<Option<SnapshotAction>>,
+    prefab_input: text_input::State,
+    prefab: String,
+    link_prefab_button: button::State,
+    unlink_prefab_button: button::State,
+    prefab_action: Cell<Option<PrefabAction>>,
+    voxel_scale_input: text_input::State,
+    voxel_scale: String,
+    set_voxel_scale_button: button::State,
+    voxel_scale_request: Cell<Option<[f32; 3]>>,
+    sub_grid_density_input: text_input::State,
+    sub_grid_density: String,
+    refine_button: button::State,
+    enter_sub_grid_button: button::State,
+    leave_sub_grid_button: button::State,
+    flatten_sub_grid_button: button::State,
+    sub_grid_action: Cell<Option<SubGridAction>>,
+    budget_input: text_input::State,
+    budget: String,
+    set_budget_button: button::State,
+    budget_request: Cell<Option<Budget>>,
+    budget_warnings: Option<Vec<BudgetWarning>>,
+    symmetry_axis: Axis,
+    keep_positive: bool,
+    symmetrize_button: button::State,
+    symmetrize_requested: Cell<bool>,
+    array_count_input: text_input::State,
+    array_count: String,
+    array_offset_input: text_input::State,
+    array_offset: String,
+    array_rotate: bool,
+    array_button: button::State,
+    array_requested: Cell<bool>,
+    radial_count_input: text_input::State,
+    radial_count: String,
+    radial_axis: Axis,
+    radial_symmetry: bool,
+    radial_button: button::State,
+    radial_requested: Cell<bool>,
+    revolve_button: button::State,
+    revolve_requested: Cell<bool>,
+    light_direction_input: text_input::State,
+    light_direction: String,
+    exposure: f32,
+    exposure_slider: slider::State,
+    tone_mapping: ToneMapping,
+    lighting: LightingPreset,
+    shading_slider: slider::State,
+    shading_strength: f32,
+    bake_shading_button: button::State,
+    bake_shading_requested: Cell<bool>,
+    bake_occlusion_button: button::State,
+    bake_occlusion_requested: Cell<bool>,
+    toggle_bake_button: button::State,
+    toggle_bake_requested: Cell<bool>,
+    discard_bake_button: button::State,
+    discard_bake_requested: Cell<bool>,
+    hsv_shift_input: text_input::State,
+    hsv_shift: String,
+    shift_colors_button: button::State,
+    shift_colors_requested: Cell<bool>,
+    emissive_toggle_button: button::State,
+    emissive_toggle_requested: Cell<bool>,
+    sort_by_hue_button: button::State,
+    sort_by_luminance_button: button::State,
+    merge_tolerance_slider: slider::State,
+    merge_tolerance: f32,
+    merge_colors_button: button::State,
+    palette_action: Cell<Option<PaletteAction>>,
+    // The number of palette entries and the ones no voxel uses
+    palette_usage: (usize, Vec<u8>),
+    box_position_input: text_input::State,
+    box_position: String,
+    box_size_input: text_input::State,
+    box_size: String,
+    place_box_button: button::State,
+    place_box_requested: Cell<bool>,
+    step_depth_input: text_input::State,
+    step_depth: String,
+    railing: bool,
+    text_input: text_input::State,
+    text: String,
+    font: FontKind,
+    text_scale_input: text_input::State,
+    text_scale: String,
+    text_depth_input: text_input::State,
+    text_depth: String,
+    curve_radius_input: text_input::State,
+    curve_radius: String,
+    curve_profile: BrushProfile,
+    shape: ShapeKind,
+    outline: bool,
+    validate_requested: Cell<bool>,
+    validation: Option<ValidationReport>,
+    gpu_picking: bool,
+    working_plane: bool,
+    snap_to_surface: bool,
+    rulers: bool,
+    ruler_step_slider: slider::State,
+    ruler_step: f32,
+    transform_gizmo: bool,
+    soft_selection: bool,
+    soft_radius_slider: slider::State,
+    soft_radius: f32,
+    pressure_mapping: PressureMapping,
+    theme: ThemeKind,
 }
 
 impl Controls {
-    pub fn new() -> Controls {
+    pub fn new(settings: &Settings) -> Controls {
         Controls {
             edit_op: Cell::new(EditOp::default()),
             export_button: button::State::default(),
+            usd_export_button: button::State::default(),
+            usd_save_file: Cell::new(None),
+            fbx_export_button: button::State::default(),
+            fbx_save_file: Cell::new(None),
+            collada_export_button: button::State::default(),
+            collada_save_file: Cell::new(None),
+            vdb_export_button: button::State::default(),
+            vdb_save_file: Cell::new(None),
+            screenshot_size_input: text_input::State::new(),
+            screenshot_size: "1920 1080".to_owned(),
+            safe_frame: false,
+            depth_of_field: false,
+            depth_of_field_blur: 8.0,
+            depth_of_field_slider: slider::State::new(),
+            bloom: false,
+            bloom_strength: 1.0,
+            bloom_strength_slider: slider::State::new(),
+            shadow_catcher: false,
+            screenshot_button: button::State::default(),
+            screenshot_file: Cell::new(None),
+            turntable_timing_input: text_input::State::new(),
+            turntable_timing: "15 4".to_owned(),
+            turntable_button: button::State::default(),
+            turntable_file: Cell::new(None),
+            stereo_separation: 2.0,
+            stereo_separation_slider: slider::State::new(),
+            stereo_layout: StereoLayout::SideBySide,
+            stereo_button: button::State::default(),
+            stereo_file: Cell::new(None),
+            texture_atlas: false,
+            export_crop: Crop::default(),
+            export_pivot: None,
+            quick_save_button: button::State::default(),
+            quick_save_requested: Cell::new(false),
+            journal_saves: false,
+            macro_recording: false,
+            macro_steps: 0,
+            macro_record_button: button::State::default(),
+            macro_play_button: button::State::default(),
+            macro_record_requested: Cell::new(false),
+            macro_play_requested: Cell::new(false),
+            recording: false,
+            replaying: false,
+            record_button: button::State::default(),
+            replay_button: button::State::default(),
+            record_session: Cell::new(None),
+            replay_session: Cell::new(None),
+            replay_speed: 1.0,
+            replay_speed_slider: slider::State::new(),
+            batch_export_button: button::State::default(),
+            reexport_button: button::State::default(),
+            reexport_requested: Cell::new(false),
+            batch_template_input: text_input::State::new(),
+            batch_template: "{name}_{n}".to_owned(),
+            batch_save_file: Cell::new(None),
+            save_project_button: button::State::default(),
+            open_project_button: button::State::default(),
+            volume_layout_input: text_input::State::new(),
+            volume_layout: String::new(),
+            import_colors_input: text_input::State::new(),
+            import_colors: String::new(),
+            import_dither: false,
+            volume_threshold_slider: slider::State::new(),
+            volume_threshold: 0.3,
+            transfer_function: TransferFunction::default(),
+            import_volume_button: button::State::default(),
+            import_volume_file: Cell::new(None),
+            cancel_button: button::State::default(),
             color_picker: ColorPicker::new(),
+            ramp_keys_input: text_input::State::new(),
+            ramp_keys: "#202040 #c04040 #f0e0a0".to_owned(),
+            ramp_steps_slider: slider::State::new(),
+            ramp_steps: 6,
+            ramp_space: RampSpace::Oklab,
+            add_ramp_button: button::State::default(),
             picked_color: PickedColor::new(Color::new(0.02, 0.02, 0.02, 1.0)),
+            harmony: Harmony::Complementary,
+            harmony_colors: ColorPicker::default(),
+            add_harmony_button: button::State::default(),
+            dither_color: PickedColor::new(Color::WHITE),
+            dither_pattern: DitherPattern::default(),
+            dither_slider: slider::State::new(),
+            dither_amount: 0.5,
             save_file: Cell::new(None),
+            save_project_file: Cell::new(None),
+            open_project_file: Cell::new(None),
+            progress: None,
+            cancel_requested: Cell::new(false),
+            show_stats: false,
+            stats_panel: StatsPanel::default(),
+            show_scene_stats: false,
+            scene_panel: ScenePanel::default(),
+            show_history: false,
+            history_panel: HistoryPanel::default(),
+            show_minimap: false,
+            minimap: Minimap::default(),
+            show_markers: false,
+            markers_panel: MarkersPanel::default(),
+            marker_name_input: text_input::State::default(),
+            marker_name: String::new(),
+            marker_note_input: text_input::State::default(),
+            marker_note: String::new(),
+            add_marker_button: button::State::default(),
+            delete_marker_button: button::State::default(),
+            marker_action: Cell::new(None),
+            minimap_target: Cell::new(None),
+            history_jump: Cell::new(None),
+            select_color: Cell::new(None),
+            clear_selection: Cell::new(false),
+            validate_button: button::State::default(),
+            delete_selection_button: button::State::default(),
+            isolate_selection_button: button::State::default(),
+            selection_action: Cell::new(None),
+            center_button: button::State::default(),
+            stand_button: button::State::default(),
+            recenter: Cell::new(None),
+            mask_mode: MaskMode::default(),
+            mask_name_input: text_input::State::new(),
+            mask_name: String::new(),
+            pivot_cell_input: text_input::State::new(),
+            pivot_cell: String::new(),
+            set_pivot_button: button::State::default(),
+            instance_placement_input: text_input::State::new(),
+            instance_placement: "8 0 0 0".to_owned(),
+            instance_button: button::State::default(),
+            unlink_button: button::State::default(),
+            mask_combine: Combine::default(),
+            mask_names: Vec::new(),
+            save_mask_button: button::State::default(),
+            recall_mask_button: button::State::default(),
+            delete_mask_button: button::State::default(),
+            mask_action: Cell::new(None),
+            snapshot_name_input: text_input::State::default(),
+            snapshot_name: String::new(),
+            snapshot_names: Vec::new(),
+            snapshot_diff: None,
+            save_snapshot_button: button::State::default(),
+            restore_snapshot_button: button::State::default(),
+            diff_snapshot_button: button::State::default(),
+            delete_snapshot_button: button::State::default(),
+            snapshot_action: Cell::new(None),
+            prefab_input: text_input::State::default(),
+            prefab: String::new(),
+            link_prefab_button: button::State::default(),
+            unlink_prefab_button: button::State::default(),
+            prefab_action: Cell::new(None),
+            voxel_scale_input: text_input::State::default(),
+            voxel_scale: "1 1 1".to_owned(),
+            set_voxel_scale_button: button::State::default(),
+            voxel_scale_request: Cell::new(None),
+            sub_grid_density_input: text_input::State::default(),
+            sub_grid_density: "4".to_owned(),
+            refine_button: button::State::default(),
+            enter_sub_grid_button: button::State::default(),
+            leave_sub_grid_button: button::State::default(),
+            flatten_sub_grid_button: button::State::default(),
+            sub_grid_action: Cell::new(None),
+            budget_input: text_input::State::default(),
+            budget: String::new(),
+            set_budget_button: button::State::default(),
+            budget_request: Cell::new(None),
+            budget_warnings: None,
+            symmetry_axis: Axis::default(),
+            keep_positive: true,
+            symmetrize_button: button::State::default(),
+            symmetrize_requested: Cell::new(false),
+            array_count_input: text_input::State::new(),
+            array_count: "3".to_owned(),
+            array_offset_input: text_input::State::new(),
+            array_offset: "2 0 0".to_owned(),
+            array_rotate: false,
+            array_button: button::State::default(),
+            array_requested: Cell::new(false),
+            radial_count_input: text_input::State::new(),
+            radial_count: "4".to_owned(),
+            radial_axis: Axis::Y,
+            radial_symmetry: false,
+            radial_button: button::State::default(),
+            radial_requested: Cell::new(false),
+            revolve_button: button::State::default(),
+            revolve_requested: Cell::new(false),
+            light_direction_input: text_input::State::new(),
+            light_direction: "1 2 1".to_owned(),
+            exposure: 0.0,
+            exposure_slider: slider::State::new(),
+            tone_mapping: ToneMapping::default(),
+            lighting: LightingPreset::default(),
+            shading_slider: slider::State::new(),
+            shading_strength: 0.3,
+            bake_shading_button: button::State::default(),
+            bake_shading_requested: Cell::new(false),
+            bake_occlusion_button: button::State::default(),
+            bake_occlusion_requested: Cell::new(false),
+            toggle_bake_button: button::State::default(),
+            toggle_bake_requested: Cell::new(false),
+            discard_bake_button: button::State::default(),
+            discard_bake_requested: Cell::new(false),
+            hsv_shift_input: text_input::State::new(),
+            hsv_shift: "30 0 0".to_owned(),
+            shift_colors_button: button::State::default(),
+            shift_colors_requested: Cell::new(false),
+            emissive_toggle_button: button::State::default(),
+            emissive_toggle_requested: Cell::new(false),
+            sort_by_hue_button: button::State::default(),
+            sort_by_luminance_button: button::State::default(),
+            merge_tolerance_slider: slider::State::new(),
+            merge_tolerance: 0.05,
+            merge_colors_button: button::State::default(),
+            palette_action: Cell::new(None),
+            palette_usage: (0, Vec::new()),
+            box_position_input: text_input::State::new(),
+            box_position: "0 0 0".to_owned(),
+            box_size_input: text_input::State::new(),
+            box_size: "1 1 1".to_owned(),
+            place_box_button: button::State::default(),
+            place_box_requested: Cell::new(false),
+            step_depth_input: text_input::State::new(),
+            step_depth: "1".to_owned(),
+            railing: false,
+            text_input: text_input::State::new(),
+            text: String::new(),
+            font: FontKind::default(),
+            text_scale_input: text_input::State::new(),
+            text_scale: "1".to_owned(),
+            text_depth_input: text_input::State::new(),
+            text_depth: "1".to_owned(),
+            curve_radius_input: text_input::State::new(),
+            curve_radius: "1".to_owned(),
+            curve_profile: BrushProfile::default(),
+            shape: ShapeKind::default(),
+            outline: false,
+            validate_requested: Cell::new(false),
+            validation: None,
+            gpu_picking: false,
+            working_plane: false,
+            snap_to_surface: false,
+            rulers: false,
+            ruler_step_slider: slider::State::new(),
+            ruler_step: 4.0,
+            transform_gizmo: false,
+            soft_selection: false,
+            soft_radius_slider: slider::State::new(),
+            soft_radius: 4.0,
+            pressure_mapping: PressureMapping::default(),
+            theme: settings.get("viewport.theme").unwrap_or_default(),
         }
     }
 
@@ -176,7 +1333,16 @@ impl Controls {
         match self.edit_op.get() {
             EditOp::Draw => self.edit_op.set(EditOp::Erase),
             EditOp::Erase => self.edit_op.set(EditOp::Refill),
-            EditOp::Refill => self.edit_op.set(EditOp::Draw),
+            EditOp::Refill => self.edit_op.set(EditOp::Paint),
+            EditOp::Paint => self.edit_op.set(EditOp::Select),
+            EditOp::Select => self.edit_op.set(EditOp::Stairs),
+            EditOp::Stairs => self.edit_op.set(EditOp::Text),
+            EditOp::Text => self.edit_op.set(EditOp::Curve),
+            EditOp::Curve => self.edit_op.set(EditOp::Shape),
+            EditOp::Shape => self.edit_op.set(EditOp::Clone),
+            EditOp::Clone => self.edit_op.set(EditOp::Smooth),
+            EditOp::Smooth => self.edit_op.set(EditOp::Focus),
+            EditOp::Focus => self.edit_op.set(EditOp::Draw),
         }
     }
 
@@ -188,123 +1354,2204 @@ impl Controls {
         self.picked_color.color
     }
 
+    /// Replaces the shown harmony colors with the ones of the draw color
+    fn suggest_harmony(&mut self) {
+        let Color { r, g, b, a } = self.picked_color.color;
+        self.harmony_colors = ColorPicker::default();
+        self.harmony_colors
+            .add_rows(&self.harmony.colors([r, g, b, a]));
+    }
+
+    /// The dither color, pattern and amount of painting
+    pub fn dither(&self) -> (Color, DitherPattern, f32) {
+        (
+            self.dither_color.color,
+            self.dither_pattern,
+            self.dither_amount,
+        )
+    }
+
     pub fn save_path(&self) -> Option<String> {
         self.save_file.take()
     }
-}
 
-impl Program for Controls {
-    type Renderer = Renderer;
-    type Message = Message;
+    pub fn usd_save_path(&self) -> Option<String> {
+        self.usd_save_file.take()
+    }
 
-    fn update(&mut self, message: Message) -> Command<Message> {
-        match message {
-            Message::EditChanged(op) => self.edit_op.set(op),
-            Message::ExportPressed => {
-                let result = nfd::open_save_dialog(Some("obj"), None).unwrap_or_else(|e| {
-                    panic!(e);
-                });
-
-                match result {
-                    nfd::Response::Okay(file_path) => self.save_file.set(Some(file_path)),
-                    _ => {}
-                }
-            }
-            Message::ColorPicked(color) => self.picked_color = PickedColor::new(color),
-        };
+    pub fn fbx_save_path(&self) -> Option<String> {
+        self.fbx_save_file.take()
+    }
 
-        Command::none()
+    pub fn collada_save_path(&self) -> Option<String> {
+        self.collada_save_file.take()
     }
 
-    fn view(&mut self) -> Element<Message, Renderer> {
-        let edit_bar = EditOp::ALL
-            .iter()
-            .fold(
-                Column::new()
-                    .width(Length::Units(150))
-                    .spacing(10)
-                    .push(Text::new("Edit state (Press space to step):")),
-                |column, state| {
-                    column.push(Radio::new(
-                        *state,
-                        &format!("{:?}", state),
-                        Some(self.edit_op.get()),
-                        Message::EditChanged,
-                    ))
-                },
-            )
-            .push(Text::new("Pick a color"))
-            .push(self.color_picker.view())
-            .push(Text::new("Draw color"))
-            .push(self.picked_color.view())
-            .push(
-                Button::new(&mut self.export_button, Text::new("Export as .obj"))
-                    .on_press(Message::ExportPressed),
+    pub fn vdb_save_path(&self) -> Option<String> {
+        self.vdb_save_file.take()
+    }
+
+    /// The offset and the quarter turns of a new instance
+    fn parse_instance_placement(&self) -> Option<([i64; 3], usize)> {
+        let values: Vec<i64> = self
+            .instance_placement
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .ok()?;
+        match values[..] {
+            [x, y, z, turns] if turns >= 0 => Some(([x, y, z], turns as usize)),
+            _ => None,
+        }
+    }
+
+    /// Sizes of a voxel along the axes, all of them positive
+    fn parse_voxel_scale(&self) -> Option<[f32; 3]> {
+        let values: Vec<f32> = self
+            .voxel_scale
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .ok()?;
+        match values[..] {
+            [x, y, z] if values.iter().all(|v| v.is_finite() && *v > 0.0) => Some([x, y, z]),
+            _ => None,
+        }
+    }
+
+    fn parse_screenshot_size(&self) -> Option<[u32; 2]> {
+        let size: Option<Vec<u32>> = self
+            .screenshot_size
+            .split_whitespace()
+            .map(|value| value.parse().ok())
+            .collect();
+        match size.as_deref() {
+            Some(&[width, height])
+                if (1..=MAX_SCREENSHOT_SIZE).contains(&width)
+                    && (1..=MAX_SCREENSHOT_SIZE).contains(&height) =>
+            {
+                Some([width, height])
+            }
+            _ => None,
+        }
+    }
+
+    /// The size of screenshots and turntables, None with a message when it isn't valid
+    fn screenshot_size(&self) -> Option<[u32; 2]> {
+        let size = self.parse_screenshot_size();
+        if size.is_none() {
+            println!(
+                "Screenshot sizes are two numbers up to {}",
+                MAX_SCREENSHOT_SIZE
             );
+        }
+        size
+    }
 
-        Container::new(edit_bar)
-            .width(Length::Units(150))
-            .height(Length::Fill)
-            .padding(10)
-            .style(UiStyle {})
-            .into()
+    /// Blur radius in pixels behind the focus when depth of field is on
+    pub fn depth_of_field_blur(&self) -> Option<f32> {
+        if self.depth_of_field {
+            Some(self.depth_of_field_blur)
+        } else {
+            None
+        }
     }
-}
 
-impl canvas::Program<Message> for ColorPicker {
-    fn draw(&self, bounds: Rectangle, _cursor: canvas::Cursor) -> Vec<canvas::Geometry> {
-        let theme = self.canvas_cache.draw(bounds.size(), |frame| {
-            self.draw(frame);
-        });
+    /// Glow of the emissive voxels in the viewport and saved renders when bloom is on
+    pub fn bloom(&self) -> Option<Bloom> {
+        if self.bloom {
+            Some(Bloom {
+                strength: self.bloom_strength,
+            })
+        } else {
+            None
+        }
+    }
 
-        vec![theme]
+    /// Whether screenshots and turntables keep the shadow the voxels cast on the ground
+    pub fn shadow_catcher(&self) -> bool {
+        self.shadow_catcher
     }
 
-    fn update(
-        &mut self,
-        event: canvas::Event,
-        bounds: Rectangle,
-        cursor: canvas::Cursor,
-    ) -> Option<Message> {
-        match event {
-            canvas::Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) => {
-                if let Some(pos) = cursor.position() {
-                    let (x_dist, y_dist) = (pos.x - bounds.x, pos.y - bounds.y);
-                    if x_dist.is_sign_positive()
-                        && y_dist.is_sign_positive()
-                        && x_dist < bounds.width
-                        && y_dist < bounds.height
-                    {
-                        let x_pos = x_dist as usize / COLOR_SIZE as usize;
-                        let y_pos = y_dist as usize / COLOR_SIZE as usize;
-                        let idx = y_pos * Self::COLORS_PER_LINE + x_pos;
-                        if idx < self.colors.len() {
-                            return Some(Message::ColorPicked(self.colors[idx]));
-                        }
-                    }
-                }
+    /// Aspect ratio of the screenshot size when the safe frame is shown
+    pub fn safe_frame(&self) -> Option<f32> {
+        if !self.safe_frame {
+            return None;
+        }
+        let [width, height] = self.parse_screenshot_size()?;
+        Some(width as f32 / height as f32)
+    }
+
+    /// The chosen file path and the image size, when saving a screenshot was pressed with a
+    /// valid size
+    pub fn screenshot_request(&self) -> Option<(String, [u32; 2])> {
+        let file_path = self.screenshot_file.take()?;
+        Some((file_path, self.screenshot_size()?))
+    }
+
+    /// The chosen file path and the animation, when saving a turntable was pressed with a
+    /// valid size and timing
+    pub fn turntable_request(&self) -> Option<(String, Turntable)> {
+        let file_path = self.turntable_file.take()?;
+        let size = self.screenshot_size()?;
+        let timing: Option<Vec<f32>> = self
+            .turntable_timing
+            .split_whitespace()
+            .map(|value| value.parse().ok())
+            .collect();
+        let turntable = match timing.as_deref() {
+            Some(&[fps, seconds]) => Turntable { size, fps, seconds },
+            _ => {
+                println!("Turntable timing is the frame rate and the length in seconds");
+                return None;
             }
-            _other => {}
+        };
+        if !turntable.is_valid() {
+            println!(
+                "Turntables run at {} fps at most and have up to {} frames of {} megapixels together",
+                MAX_TURNTABLE_FPS,
+                MAX_TURNTABLE_FRAMES,
+                MAX_TURNTABLE_PIXELS / 1_000_000
+            );
+            return None;
         }
-        None
+        Some((file_path, turntable))
     }
-}
 
-impl canvas::Program<Message> for PickedColor {
-    fn draw(&self, bounds: Rectangle, _cursor: canvas::Cursor) -> Vec<canvas::Geometry> {
-        let theme = self.canvas_cache.draw(bounds.size(), |frame| {
-            self.draw(frame);
-        });
+    /// The chosen file path and the stereo pair, when saving one was pressed with a valid size
+    pub fn stereo_request(&self) -> Option<(String, Stereo)> {
+        let file_path = self.stereo_file.take()?;
+        let stereo = Stereo {
+            size: self.screenshot_size()?,
+            separation: self.stereo_separation,
+            layout: self.stereo_layout,
+        };
+        Some((file_path, stereo))
+    }
 
-        vec![theme]
+    /// Whether exports bake the voxel colors into a texture atlas instead of vertex colors
+    pub fn texture_atlas(&self) -> bool {
+        self.texture_atlas
     }
-}
 
-struct UiStyle {}
-impl StyleSheet for UiStyle {
-    fn style(&self) -> Style {
-        Style {
-            background: Some(Background::Color(Color::WHITE)),
+    /// How much of the empty grid around the voxels exports keep
+    pub fn export_crop(&self) -> Crop {
+        self.export_crop
+    }
+
+    /// Point of the bounds of the voxels exports place at the origin, None for the grid's corner
+    pub fn export_pivot(&self) -> Option<Pivot> {
+        self.export_pivot
+    }
+
+    pub fn quick_save_requested(&self) -> bool {
+        self.quick_save_requested.take()
+    }
+
+    /// Whether quick saves append the changes to the project's journal instead of rewriting it
+    pub fn journal_saves(&self) -> bool {
+        self.journal_saves
+    }
+
+    /// Starting or stopping the macro recording was pressed
+    /// Whether the palette entry of the draw color should start or stop glowing
+    pub fn emissive_toggle_requested(&self) -> bool {
+        self.emissive_toggle_requested.take()
+    }
+
+    pub fn macro_record_requested(&self) -> bool {
+        self.macro_record_requested.take()
+    }
+
+    pub fn macro_play_requested(&self) -> bool {
+        self.macro_play_requested.take()
+    }
+
+    /// The file to record the session to after Record was pressed, None after Stop was pressed
+    pub fn record_session(&self) -> Option<Option<String>> {
+        self.record_session.take()
+    }
+
+    /// The recording to play back after Replay was pressed, None after Stop was pressed
+    pub fn replay_session(&self) -> Option<Option<String>> {
+        self.replay_session.take()
+    }
+
+    /// How many times faster than recorded the session is played back
+    pub fn replay_speed(&self) -> f32 {
+        self.replay_speed
+    }
+
+    /// The chosen file path and the file name template, when batch export was pressed
+    pub fn batch_save_path(&self) -> Option<(String, String)> {
+        let file_path = self.batch_save_file.take()?;
+        let template = if self.batch_template.trim().is_empty() {
+            "{name}_{n}".to_owned()
+        } else {
+            self.batch_template.trim().to_owned()
+        };
+        Some((file_path, template))
+    }
+
+    pub fn reexport_requested(&self) -> bool {
+        self.reexport_requested.take()
+    }
+
+    pub fn save_project_path(&self) -> Option<String> {
+        self.save_project_file.take()
+    }
+
+    pub fn open_project_path(&self) -> Option<String> {
+        self.open_project_file.take()
+    }
+
+    /// The chosen volume file and how to turn it into voxels, when importing was pressed
+    pub fn volume_import(&self) -> Option<(String, VolumeOptions)> {
+        let file_path = self.import_volume_file.take()?;
+        let layout = if self.volume_layout.trim().is_empty() {
+            None
+        } else {
+            match self.volume_layout.parse() {
+                Ok(layout) => Some(layout),
+                Err(_) => {
+                    println!("Invalid raw volume layout {:?}", self.volume_layout);
+                    return None;
+                }
+            }
+        };
+        let colors = if self.import_colors.trim().is_empty() {
+            None
+        } else {
+            match self.import_colors.trim().parse() {
+                Ok(colors) if colors > 0 => Some(colors),
+                _ => {
+                    println!("Invalid number of colors {:?}", self.import_colors);
+                    return None;
+                }
+            }
+        };
+        Some((
+            file_path,
+            VolumeOptions {
+                threshold: self.volume_threshold,
+                transfer: self.transfer_function,
+                layout,
+                colors,
+                dither: self.import_dither,
+            },
+        ))
+    }
+
+    pub fn cancel_requested(&self) -> bool {
+        self.cancel_requested.take()
+    }
+
+    pub fn stats_visible(&self) -> bool {
+        self.show_stats
+    }
+
+    pub fn scene_stats_visible(&self) -> bool {
+        self.show_scene_stats
+    }
+
+    pub fn minimap_visible(&self) -> bool {
+        self.show_minimap
+    }
+
+    /// Point of the ground the camera should look at, as x and z of the grid
+    pub fn minimap_target(&self) -> Option<[f32; 2]> {
+        self.minimap_target.take()
+    }
+
+    /// Index of the history entry the scene should be restored to
+    pub fn history_jump(&self) -> Option<usize> {
+        self.history_jump.take()
+    }
+
+    /// Palette index of the color whose voxels should be selected
+    pub fn select_color(&self) -> Option<u8> {
+        self.select_color.take()
+    }
+
+    pub fn clear_selection_requested(&self) -> bool {
+        self.clear_selection.take()
+    }
+
+    pub fn validate_requested(&self) -> bool {
+        self.validate_requested.take()
+    }
+
+    pub fn selection_action(&self) -> Option<SelectionAction> {
+        self.selection_action.take()
+    }
+
+    /// The pivot of the selection, or the scene without one, to move to the same point of the grid
+    pub fn recenter_requested(&self) -> Option<Pivot> {
+        self.recenter.take()
+    }
+
+    /// Whether the editing tools are limited to the selection or kept away from it
+    pub fn mask_mode(&self) -> MaskMode {
+        self.mask_mode
+    }
+
+    pub fn mask_action(&self) -> Option<MaskAction> {
+        self.mask_action.take()
+    }
+
+    pub fn snapshot_action(&self) -> Option<SnapshotAction> {
+        self.snapshot_action.take()
+    }
+
+    pub fn marker_action(&self) -> Option<MarkerAction> {
+        self.marker_action.take()
+    }
+
+    pub fn prefab_action(&self) -> Option<PrefabAction> {
+        self.prefab_action.take()
+    }
+
+    /// The size of a voxel along each axis typed in, when Set voxel size was pressed
+    pub fn voxel_scale_request(&self) -> Option<[f32; 3]> {
+        self.voxel_scale_request.take()
+    }
+
+    pub fn sub_grid_action(&self) -> Option<SubGridAction> {
+        self.sub_grid_action.take()
+    }
+
+    /// The budget typed in, when Set budget was pressed
+    pub fn budget_request(&self) -> Option<Budget> {
+        self.budget_request.take()
+    }
+
+    /// The mirror axis and whether the positive half is kept, when symmetrize was pressed
+    pub fn symmetrize_requested(&self) -> Option<(Axis, bool)> {
+        if self.symmetrize_requested.take() {
+            Some((self.symmetry_axis, self.keep_positive))
+        } else {
+            None
+        }
+    }
+
+    /// The copy count, the voxel offset between the copies and whether every copy is turned
+    /// a quarter around the Y axis, when array was pressed with a valid count and offset
+    pub fn array_requested(&self) -> Option<(usize, [i64; 3], bool)> {
+        if !self.array_requested.take() {
+            return None;
+        }
+        let count = self.array_count.trim().parse().ok()?;
+        let offset: Vec<i64> = self
+            .array_offset
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .ok()?;
+        match offset[..] {
+            [x, y, z] => Some((count, [x, y, z], self.array_rotate)),
+            _ => None,
+        }
+    }
+
+    /// Repeats the edits count times around the radial axis, when turned on with a count of 2 or more
+    pub fn radial_symmetry(&self) -> Option<RadialSymmetry> {
+        if !self.radial_symmetry {
+            return None;
+        }
+        match self.radial_count.trim().parse() {
+            Ok(count) if count >= 2 => Some(RadialSymmetry {
+                axis: self.radial_axis,
+                count,
+            }),
+            _ => None,
+        }
+    }
+
+    /// The number of instances around the axis, when radial array was pressed with a valid count
+    pub fn radial_array_requested(&self) -> Option<(usize, Axis)> {
+        if !self.radial_requested.take() {
+            return None;
+        }
+        let count = self.radial_count.trim().parse().ok()?;
+        Some((count, self.radial_axis))
+    }
+
+    /// Invalid step depths fall back to the default
+    /// The corner cell and the size in cells of the typed box, when place box was pressed with
+    /// three numbers in both fields and no empty side
+    pub fn place_box_requested(&self) -> Option<([i64; 3], [u32; 3])> {
+        if !self.place_box_requested.take() {
+            return None;
+        }
+        let position: Vec<i64> = self
+            .box_position
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .ok()?;
+        let size: Vec<u32> = self
+            .box_size
+            .split(|c: char| c.is_whitespace() || c == 'x' || c == '\u{d7}')
+            .filter(|side| !side.is_empty())
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .ok()?;
+        match (&position[..], &size[..]) {
+            ([x, y, z], [w, h, d]) if *w > 0 && *h > 0 && *d > 0 => {
+                Some(([*x, *y, *z], [*w, *h, *d]))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn stairs_options(&self) -> StairsOptions {
+        let mut options = StairsOptions::default();
+        if let Ok(depth) = self.step_depth.trim().parse() {
+            options.step_depth = depth;
+        }
+        options.railing = self.railing;
+        options
+    }
+
+    /// Invalid sizes and depths fall back to the default
+    pub fn text_options(&self) -> TextOptions {
+        let defaults = TextOptions::default();
+        TextOptions {
+            text: self.text.clone(),
+            font: self.font,
+            scale: self.text_scale.trim().parse().unwrap_or(defaults.scale),
+            depth: self.text_depth.trim().parse().unwrap_or(defaults.depth),
+        }
+    }
+
+    /// Profile and radius of the brush swept along curves, an invalid radius draws thin curves
+    pub fn curve_brush(&self) -> (BrushProfile, usize) {
+        (
+            self.curve_profile,
+            self.curve_radius.trim().parse().unwrap_or(0),
+        )
+    }
+
+    /// The lathe axis, when revolve was pressed
+    pub fn revolve_requested(&self) -> Option<Axis> {
+        if self.revolve_requested.take() {
+            Some(self.radial_axis)
+        } else {
+            None
+        }
+    }
+
+    /// The shape drawn on the plane and whether only its outline is filled
+    pub fn shape(&self) -> (ShapeKind, bool) {
+        (self.shape, self.outline)
+    }
+
+    /// The light direction and the shading strength, when bake shading was pressed with a valid
+    /// direction
+    pub fn bake_shading_requested(&self) -> Option<([f32; 3], f32)> {
+        if !self.bake_shading_requested.take() {
+            return None;
+        }
+        let direction: Vec<f32> = self
+            .light_direction
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .ok()?;
+        match direction[..] {
+            [x, y, z] => Some(([x, y, z], self.shading_strength)),
+            _ => None,
+        }
+    }
+
+    /// The shading strength, when bake ambient occlusion was pressed
+    pub fn bake_occlusion_requested(&self) -> Option<f32> {
+        if self.bake_occlusion_requested.take() {
+            Some(self.shading_strength)
+        } else {
+            None
+        }
+    }
+
+    pub fn toggle_bake_requested(&self) -> bool {
+        self.toggle_bake_requested.take()
+    }
+
+    pub fn discard_bake_requested(&self) -> bool {
+        self.discard_bake_requested.take()
+    }
+
+    pub fn palette_action(&self) -> Option<PaletteAction> {
+        self.palette_action.take()
+    }
+
+    /// The hue shift in degrees and the saturation and value shifts, when shift colors was
+    /// pressed with valid shifts
+    pub fn shift_colors_requested(&self) -> Option<[f32; 3]> {
+        if !self.shift_colors_requested.take() {
+            return None;
+        }
+        let shift: Vec<f32> = self
+            .hsv_shift
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .ok()?;
+        match shift[..] {
+            [hue, saturation, value] => Some([hue, saturation, value]),
+            _ => None,
+        }
+    }
+
+    /// Keys typed into a text field shouldn't trigger the editor's hotkeys
+    pub fn text_input_focused(&self) -> bool {
+        self.array_count_input.is_focused()
+            || self.array_offset_input.is_focused()
+            || self.radial_count_input.is_focused()
+            || self.box_position_input.is_focused()
+            || self.box_size_input.is_focused()
+            || self.step_depth_input.is_focused()
+            || self.text_input.is_focused()
+            || self.text_scale_input.is_focused()
+            || self.text_depth_input.is_focused()
+            || self.curve_radius_input.is_focused()
+            || self.light_direction_input.is_focused()
+            || self.hsv_shift_input.is_focused()
+            || self.snapshot_name_input.is_focused()
+            || self.prefab_input.is_focused()
+            || self.voxel_scale_input.is_focused()
+            || self.sub_grid_density_input.is_focused()
+            || self.budget_input.is_focused()
+            || self.marker_name_input.is_focused()
+            || self.marker_note_input.is_focused()
+            || self.mask_name_input.is_focused()
+            || self.pivot_cell_input.is_focused()
+            || self.instance_placement_input.is_focused()
+            || self.batch_template_input.is_focused()
+            || self.volume_layout_input.is_focused()
+            || self.ramp_keys_input.is_focused()
+            || self.import_colors_input.is_focused()
+            || self.screenshot_size_input.is_focused()
+            || self.turntable_timing_input.is_focused()
+    }
+
+    pub fn gpu_picking(&self) -> bool {
+        self.gpu_picking
+    }
+
+    /// Whether edits go onto the working plane instead of the ground planes
+    pub fn working_plane(&self) -> bool {
+        self.working_plane
+    }
+
+    /// Whether the working plane follows the face of the voxel under the cursor
+    pub fn snap_to_surface(&self) -> bool {
+        self.snap_to_surface
+    }
+
+    /// Cells between the coordinate labels of the rulers, None when they're off
+    pub fn rulers(&self) -> Option<usize> {
+        if self.rulers {
+            Some(self.ruler_step.round().max(1.0) as usize)
+        } else {
+            None
+        }
+    }
+
+    /// Whether the selection shows handles to move, turn and stretch it with
+    pub fn transform_gizmo(&self) -> bool {
+        self.transform_gizmo
+    }
+
+    /// The falloff radius in cells around the selection that color shifts and nudges reach with
+    /// a fading effect, when soft selection is on
+    pub fn soft_selection(&self) -> Option<f32> {
+        if self.soft_selection {
+            Some(self.soft_radius)
+        } else {
+            None
+        }
+    }
+
+    pub fn pressure_mapping(&self) -> PressureMapping {
+        self.pressure_mapping
+    }
+
+    pub fn theme(&self) -> ThemeKind {
+        self.theme
+    }
+
+    /// Exposure compensation in stops and the tone mapping of the viewport and the renders
+    pub fn tone_mapping(&self) -> (f32, ToneMapping) {
+        (self.exposure, self.tone_mapping)
+    }
+
+    /// The light setup of the viewport, screenshots and turntables
+    pub fn lighting(&self) -> LightingPreset {
+        self.lighting
+    }
+}
+
+impl Program for Controls {
+    type Renderer = Renderer;
+    type Message = Message;
+
+    fn update(&mut self, message: Message) -> Command<Message> {
+        match message {
+            Message::EditChanged(op) => self.edit_op.set(op),
+            Message::ExportPressed => self.save_file.set(dialog::save_file("obj")),
+            Message::UsdExportPressed => self.usd_save_file.set(dialog::save_file("usdz,usda")),
+            Message::FbxExportPressed => self.fbx_save_file.set(dialog::save_file("fbx")),
+            Message::ColladaExportPressed => self.collada_save_file.set(dialog::save_file("dae")),
+            Message::VdbExportPressed => self.vdb_save_file.set(dialog::save_file("vdb")),
+            Message::ScreenshotSizeChanged(size) => self.screenshot_size = size,
+            Message::FrameAspectChanged(aspect) => {
+                let [width, height] = aspect.size();
+                self.screenshot_size = format!("{} {}", width, height);
+            }
+            Message::SafeFrameToggled(safe_frame) => self.safe_frame = safe_frame,
+            Message::DepthOfFieldToggled(depth_of_field) => self.depth_of_field = depth_of_field,
+            Message::DepthOfFieldBlurChanged(blur) => self.depth_of_field_blur = blur,
+            Message::BloomToggled(bloom) => self.bloom = bloom,
+            Message::ShadowCatcherToggled(shadow_catcher) => self.shadow_catcher = shadow_catcher,
+            Message::BloomStrengthChanged(strength) => self.bloom_strength = strength,
+            Message::ScreenshotPressed => self.screenshot_file.set(dialog::save_file("png")),
+            Message::TurntableTimingChanged(timing) => self.turntable_timing = timing,
+            Message::TurntablePressed => self.turntable_file.set(dialog::save_file("gif")),
+            Message::StereoSeparationChanged(separation) => self.stereo_separation = separation,
+            Message::StereoLayoutChanged(layout) => self.stereo_layout = layout,
+            Message::StereoPressed => self.stereo_file.set(dialog::save_file("png")),
+            Message::TextureAtlasToggled(enabled) => self.texture_atlas = enabled,
+            Message::ExportCropChanged(crop) => self.export_crop = crop,
+            Message::ExportPivotChanged(pivot) => self.export_pivot = pivot,
+            Message::QuickSavePressed => self.quick_save_requested.set(true),
+            Message::JournalSavesToggled(enabled) => self.journal_saves = enabled,
+            Message::MacroRecordPressed => self.macro_record_requested.set(true),
+            Message::MacroPlayPressed => self.macro_play_requested.set(true),
+            Message::MacroStateChanged(recording, steps) => {
+                self.macro_recording = recording;
+                self.macro_steps = steps;
+            }
+            Message::RecordSessionPressed => self.record_session.set(if self.recording {
+                Some(None)
+            } else {
+                dialog::save_file("vxr").map(Some)
+            }),
+            Message::ReplaySessionPressed => self.replay_session.set(if self.replaying {
+                Some(None)
+            } else {
+                dialog::open_file("vxr").map(Some)
+            }),
+            Message::ReplaySpeedChanged(speed) => self.replay_speed = speed,
+            Message::SessionStateChanged(recording, replaying) => {
+                self.recording = recording;
+                self.replaying = replaying;
+            }
+            Message::BatchExportPressed => self.batch_save_file.set(dialog::save_file("obj")),
+            Message::ReexportPressed => self.reexport_requested.set(true),
+            Message::BatchTemplateChanged(template) => self.batch_template = template,
+            Message::SaveProjectPressed => self.save_project_file.set(dialog::save_file("vxe")),
+            Message::OpenProjectPressed => self.open_project_file.set(dialog::open_file("vxe")),
+            Message::VolumeLayoutChanged(layout) => self.volume_layout = layout,
+            Message::ImportColorsChanged(colors) => self.import_colors = colors,
+            Message::ImportDitherToggled(dither) => self.import_dither = dither,
+            Message::VolumeThresholdChanged(threshold) => self.volume_threshold = threshold,
+            Message::TransferFunctionChanged(transfer) => self.transfer_function = transfer,
+            Message::ImportVolumePressed => self
+                .import_volume_file
+                .set(dialog::open_file("nrrd,nhdr,raw")),
+            Message::CancelPressed => self.cancel_requested.set(true),
+            Message::ProgressChanged(progress) => self.progress = progress,
+            Message::GpuPickingToggled(enabled) => self.gpu_picking = enabled,
+            Message::WorkingPlaneToggled(enabled) => self.working_plane = enabled,
+            Message::SnapToSurfaceToggled(enabled) => self.snap_to_surface = enabled,
+            Message::RulersToggled(enabled) => self.rulers = enabled,
+            Message::RulerStepChanged(step) => self.ruler_step = step,
+            Message::TransformGizmoToggled(enabled) => self.transform_gizmo = enabled,
+            Message::SoftSelectionToggled(enabled) => self.soft_selection = enabled,
+            Message::SoftRadiusChanged(radius) => self.soft_radius = radius,
+            Message::PressureMappingChanged(mapping) => self.pressure_mapping = mapping,
+            Message::ThemeChanged(theme) => self.theme = theme,
+            Message::ExposureChanged(exposure) => self.exposure = exposure,
+            Message::ToneMappingChanged(tone_mapping) => self.tone_mapping = tone_mapping,
+            Message::LightingChanged(lighting) => self.lighting = lighting,
+            Message::ToggleStats => self.show_stats = !self.show_stats,
+            Message::StatsChanged(stats) => self.stats_panel = StatsPanel::new(stats),
+            Message::ToggleSceneStats => self.show_scene_stats = !self.show_scene_stats,
+            Message::SceneStatsChanged(stats) => self.scene_panel = ScenePanel::new(stats),
+            Message::ToggleHistory => self.show_history = !self.show_history,
+            Message::HistoryChanged(items) => self.history_panel = HistoryPanel::new(items),
+            Message::ToggleMinimap => self.show_minimap = !self.show_minimap,
+            Message::ToggleMarkers => self.show_markers = !self.show_markers,
+            Message::MarkersChanged(markers) => self.markers_panel = MarkersPanel::new(markers),
+            Message::MarkerNameChanged(name) => self.marker_name = name,
+            Message::MarkerNoteChanged(note) => self.marker_note = note,
+            Message::MarkerActionPressed(action) => self.marker_action.set(Some(action)),
+            Message::MinimapColumnsChanged(extent, columns) => {
+                self.minimap.set_columns(extent, columns)
+            }
+            Message::MinimapFrustumChanged(frustum) => self.minimap.frustum = frustum,
+            Message::MinimapPressed(target) => self.minimap_target.set(Some(target)),
+            Message::HistoryEntryPressed(index) => self.history_jump.set(Some(index)),
+            Message::SelectColorPressed(index) => self.select_color.set(Some(index)),
+            Message::ClearSelectionPressed => self.clear_selection.set(true),
+            Message::ValidatePressed => self.validate_requested.set(true),
+            Message::SelectionActionPressed(action) => self.selection_action.set(Some(action)),
+            Message::RecenterPressed(pivot) => self.recenter.set(Some(pivot)),
+            Message::MaskModeChanged(mode) => self.mask_mode = mode,
+            Message::MaskNameChanged(name) => self.mask_name = name,
+            Message::PivotCellChanged(cell) => self.pivot_cell = cell,
+            Message::InstancePlacementChanged(placement) => self.instance_placement = placement,
+            Message::MaskCombineChanged(combine) => self.mask_combine = combine,
+            Message::MaskActionPressed(action) => self.mask_action.set(Some(action)),
+            Message::MaskNamesChanged(names) => self.mask_names = names,
+            Message::SnapshotNameChanged(name) => self.snapshot_name = name,
+            Message::SnapshotActionPressed(action) => self.snapshot_action.set(Some(action)),
+            Message::SnapshotNamesChanged(names) => self.snapshot_names = names,
+            Message::SnapshotDiffChanged(name, diff) => self.snapshot_diff = Some((name, diff)),
+            Message::PrefabChanged(prefab) => self.prefab = prefab,
+            Message::PrefabActionPressed(action) => self.prefab_action.set(Some(action)),
+            Message::VoxelScaleChanged(voxel_scale) => self.voxel_scale = voxel_scale,
+            Message::SetVoxelScalePressed(voxel_scale) => {
+                self.voxel_scale_request.set(Some(voxel_scale))
+            }
+            Message::VoxelScaleLoaded([x, y, z]) => self.voxel_scale = format!("{} {} {}", x, y, z),
+            Message::SubGridDensityChanged(density) => self.sub_grid_density = density,
+            Message::SubGridActionPressed(action) => self.sub_grid_action.set(Some(action)),
+            Message::BudgetChanged(budget) => self.budget = budget,
+            Message::SetBudgetPressed(budget) => self.budget_request.set(Some(budget)),
+            Message::BudgetLoaded(budget) => {
+                self.budget = if budget.is_empty() {
+                    String::new()
+                } else {
+                    budget.to_string()
+                }
+            }
+            Message::BudgetWarningsChanged(warnings) => self.budget_warnings = warnings,
+            Message::SymmetryAxisChanged(axis) => self.symmetry_axis = axis,
+            Message::KeepPositiveToggled(keep) => self.keep_positive = keep,
+            Message::SymmetrizePressed => self.symmetrize_requested.set(true),
+            Message::ArrayCountChanged(count) => self.array_count = count,
+            Message::ArrayOffsetChanged(offset) => self.array_offset = offset,
+            Message::ArrayRotateToggled(rotate) => self.array_rotate = rotate,
+            Message::ArrayPressed => self.array_requested.set(true),
+            Message::RadialCountChanged(count) => self.radial_count = count,
+            Message::RadialAxisChanged(axis) => self.radial_axis = axis,
+            Message::RadialSymmetryToggled(enabled) => self.radial_symmetry = enabled,
+            Message::RadialArrayPressed => self.radial_requested.set(true),
+            Message::RevolvePressed => self.revolve_requested.set(true),
+            Message::LightDirectionChanged(direction) => self.light_direction = direction,
+            Message::ShadingStrengthChanged(strength) => self.shading_strength = strength,
+            Message::BakeShadingPressed => self.bake_shading_requested.set(true),
+            Message::BakeOcclusionPressed => self.bake_occlusion_requested.set(true),
+            Message::ToggleBakePressed => self.toggle_bake_requested.set(true),
+            Message::DiscardBakePressed => self.discard_bake_requested.set(true),
+            Message::HsvShiftChanged(shift) => self.hsv_shift = shift,
+            Message::ShiftColorsPressed => self.shift_colors_requested.set(true),
+            Message::EmissiveTogglePressed => self.emissive_toggle_requested.set(true),
+            Message::PaletteActionPressed(action) => self.palette_action.set(Some(action)),
+            Message::MergeToleranceChanged(tolerance) => self.merge_tolerance = tolerance,
+            Message::PaletteUsageChanged(entries, unused) => self.palette_usage = (entries, unused),
+            Message::BoxPositionChanged(position) => self.box_position = position,
+            Message::BoxSizeChanged(size) => self.box_size = size,
+            Message::PlaceBoxPressed => self.place_box_requested.set(true),
+            Message::StepDepthChanged(depth) => self.step_depth = depth,
+            Message::RailingToggled(railing) => self.railing = railing,
+            Message::TextChanged(text) => self.text = text,
+            Message::FontChanged(font) => self.font = font,
+            Message::TextScaleChanged(scale) => self.text_scale = scale,
+            Message::TextDepthChanged(depth) => self.text_depth = depth,
+            Message::CurveRadiusChanged(radius) => self.curve_radius = radius,
+            Message::CurveProfileChanged(profile) => self.curve_profile = profile,
+            Message::ShapeChanged(shape) => self.shape = shape,
+            Message::OutlineToggled(outline) => self.outline = outline,
+            Message::ValidationChanged(report) => self.validation = Some(report),
+            Message::DitherPatternChanged(pattern) => self.dither_pattern = pattern,
+            Message::DitherAmountChanged(amount) => self.dither_amount = amount,
+            Message::ColorPicked(color) => {
+                self.picked_color = PickedColor::new(color);
+                self.suggest_harmony();
+            }
+            Message::RampKeysChanged(keys) => self.ramp_keys = keys,
+            Message::RampStepsChanged(steps) => self.ramp_steps = steps.round() as usize,
+            Message::RampSpaceChanged(space) => self.ramp_space = space,
+            Message::AddRampPressed(keys) => {
+                let colors = palette_tools::ramp(&keys, self.ramp_steps, self.ramp_space);
+                self.color_picker.add_rows(&colors);
+            }
+            Message::HarmonyChanged(harmony) => {
+                self.harmony = harmony;
+                self.suggest_harmony();
+            }
+            Message::AddHarmonyPressed => {
+                let colors: Vec<[f32; 4]> = self
+                    .harmony_colors
+                    .colors
+                    .iter()
+                    .map(|color| [color.r, color.g, color.b, color.a])
+                    .collect();
+                self.color_picker.add_rows(&colors);
+            }
+            Message::DitherColorPicked(color) => self.dither_color = PickedColor::new(color),
+        };
+
+        Command::none()
+    }
+
+    fn view(&mut self) -> Element<Message, Renderer> {
+        let pressure_mapping = Some(self.pressure_mapping);
+        let dither_pattern = Some(self.dither_pattern);
+        let ramp_space = Some(self.ramp_space);
+        let harmony = Some(self.harmony);
+        let stereo_layout = Some(self.stereo_layout);
+        let has_harmony_colors = !self.harmony_colors.colors.is_empty();
+        let ramp_keys: Option<Vec<[f32; 4]>> = self
+            .ramp_keys
+            .split_whitespace()
+            .map(parse_hex)
+            .collect::<Option<_>>()
+            .filter(|keys: &Vec<[f32; 4]>| keys.len() >= 2);
+        let theme = Some(self.theme);
+        let mask_mode = Some(self.mask_mode);
+        let mask_combine = Some(self.mask_combine);
+        let tone_mapping = Some(self.tone_mapping);
+        let lighting = Some(self.lighting);
+        let transfer_function = Some(self.transfer_function);
+        let symmetry_axis = Some(self.symmetry_axis);
+        let radial_axis = Some(self.radial_axis);
+        let font = Some(self.font);
+        let curve_profile = Some(self.curve_profile);
+        let export_crop = Some(self.export_crop);
+        let export_pivot = Some(self.export_pivot);
+        // An empty pivot clears it, one that doesn't parse does nothing
+        let pivot_cell = self.pivot_cell.parse::<ObjectPivot>().ok();
+        let pivot_valid = pivot_cell.is_some() || self.pivot_cell.trim().is_empty();
+        let instance_placement = self.parse_instance_placement();
+        let prefab = self.prefab.parse::<Prefab>().ok();
+        let budget = self.budget.parse::<Budget>().ok();
+        let voxel_scale = self.parse_voxel_scale();
+        let palette_usage = {
+            let (entries, unused) = &self.palette_usage;
+            let listed: Vec<String> = unused.iter().map(|index| index.to_string()).collect();
+            match unused.len() {
+                0 => format!("Palette: {} entries, all used", entries),
+                n => format!(
+                    "Palette: {} entries, {} unused: {}",
+                    entries,
+                    n,
+                    listed.join(", ")
+                ),
+            }
+        };
+        let sub_grid_density = self.sub_grid_density.trim().parse::<usize>().ok();
+        let shape = Some(self.shape);
+        let frame_aspect = self
+            .parse_screenshot_size()
+            .and_then(|size| FrameAspect::ALL.iter().copied().find(|a| a.size() == size));
+        let mut edit_bar = EditOp::ALL
+            .iter()
+            .fold(
+                Column::new()
+                    .width(Length::Units(150))
+                    .spacing(10)
+                    .push(Text::new("Edit state (Press space to step):")),
+                |column, state| {
+                    column.push(Radio::new(
+                        *state,
+                        &format!("{:?}", state),
+                        Some(self.edit_op.get()),
+                        Message::EditChanged,
+                    ))
+                },
+            )
+            .push(Text::new("Box corner (x y z):"))
+            .push(
+                TextInput::new(
+                    &mut self.box_position_input,
+                    "x y z",
+                    &self.box_position,
+                    Message::BoxPositionChanged,
+                )
+                .padding(5),
+            )
+            .push(Text::new("Box size (w x h x d):"))
+            .push(
+                TextInput::new(
+                    &mut self.box_size_input,
+                    "w x h x d",
+                    &self.box_size,
+                    Message::BoxSizeChanged,
+                )
+                .padding(5),
+            )
+            .push(
+                Button::new(&mut self.place_box_button, Text::new("Place box"))
+                    .on_press(Message::PlaceBoxPressed),
+            )
+            .push(Text::new("Stair step depth (1 = ramp):"))
+            .push(
+                TextInput::new(
+                    &mut self.step_depth_input,
+                    "Depth",
+                    &self.step_depth,
+                    Message::StepDepthChanged,
+                )
+                .padding(5),
+            )
+            .push(Checkbox::new(
+                self.railing,
+                "Railing",
+                Message::RailingToggled,
+            ))
+            .push(Text::new("Text:"))
+            .push(
+                TextInput::new(
+                    &mut self.text_input,
+                    "Label",
+                    &self.text,
+                    Message::TextChanged,
+                )
+                .padding(5),
+            );
+        edit_bar = FontKind::ALL
+            .iter()
+            .fold(edit_bar, |column, kind| {
+                column.push(Radio::new(
+                    *kind,
+                    &format!("{:?} font", kind),
+                    font,
+                    Message::FontChanged,
+                ))
+            })
+            .push(Text::new("Text size and depth:"))
+            .push(
+                Row::new()
+                    .spacing(5)
+                    .push(
+                        TextInput::new(
+                            &mut self.text_scale_input,
+                            "Size",
+                            &self.text_scale,
+                            Message::TextScaleChanged,
+                        )
+                        .padding(5),
+                    )
+                    .push(
+                        TextInput::new(
+                            &mut self.text_depth_input,
+                            "Depth",
+                            &self.text_depth,
+                            Message::TextDepthChanged,
+                        )
+                        .padding(5),
+                    ),
+            )
+            .push(Text::new("Curve brush radius:"))
+            .push(
+                TextInput::new(
+                    &mut self.curve_radius_input,
+                    "Radius",
+                    &self.curve_radius,
+                    Message::CurveRadiusChanged,
+                )
+                .padding(5),
+            );
+        edit_bar = BrushProfile::ALL
+            .iter()
+            .fold(edit_bar, |column, profile| {
+                column.push(Radio::new(
+                    *profile,
+                    &format!("{:?}", profile),
+                    curve_profile,
+                    Message::CurveProfileChanged,
+                ))
+            })
+            .push(Text::new("Shape:"));
+        edit_bar = ShapeKind::ALL
+            .iter()
+            .fold(edit_bar, |column, kind| {
+                column.push(Radio::new(
+                    *kind,
+                    &format!("{:?}", kind),
+                    shape,
+                    Message::ShapeChanged,
+                ))
+            })
+            .push(Checkbox::new(
+                self.outline,
+                "Outline only",
+                Message::OutlineToggled,
+            ))
+            .push(Checkbox::new(
+                self.gpu_picking,
+                "GPU picking",
+                Message::GpuPickingToggled,
+            ))
+            .push(Checkbox::new(
+                self.working_plane,
+                "Working plane (drag the handle, click an edge to turn)",
+                Message::WorkingPlaneToggled,
+            ))
+            .push(Checkbox::new(
+                self.snap_to_surface,
+                "Snap the working plane to the hovered face",
+                Message::SnapToSurfaceToggled,
+            ))
+            .push(Checkbox::new(
+                self.rulers,
+                "Rulers with coordinates along the editing plane",
+                Message::RulersToggled,
+            ))
+            .push(Text::new(format!(
+                "Ruler labels every {} voxels",
+                self.ruler_step.round()
+            )))
+            .push(Slider::new(
+                &mut self.ruler_step_slider,
+                1.0..=16.0,
+                self.ruler_step,
+                Message::RulerStepChanged,
+            ))
+            .push(Checkbox::new(
+                self.transform_gizmo,
+                "Transform gizmo (arrows move, rings turn, cubes stretch the selection)",
+                Message::TransformGizmoToggled,
+            ))
+            .push(Checkbox::new(
+                self.soft_selection,
+                "Soft selection (color shifts and nudges fade out around it)",
+                Message::SoftSelectionToggled,
+            ))
+            .push(Text::new(format!(
+                "Falloff radius: {:.1} cells",
+                self.soft_radius
+            )))
+            .push(Slider::new(
+                &mut self.soft_radius_slider,
+                1.0..=16.0,
+                self.soft_radius,
+                Message::SoftRadiusChanged,
+            ))
+            .push(Text::new("Pen pressure:"));
+        edit_bar = PressureMapping::ALL
+            .iter()
+            .fold(edit_bar, |column, mapping| {
+                column.push(Radio::new(
+                    *mapping,
+                    &format!("{:?}", mapping),
+                    pressure_mapping,
+                    Message::PressureMappingChanged,
+                ))
+            })
+            .push(Text::new("Pick a color"))
+            .push(self.color_picker.view())
+            .push(Text::new("Ramp key colors (#rrggbb #rrggbb ...):"))
+            .push(
+                TextInput::new(
+                    &mut self.ramp_keys_input,
+                    "#rrggbb #rrggbb",
+                    &self.ramp_keys,
+                    Message::RampKeysChanged,
+                )
+                .padding(5),
+            )
+            .push(Text::new(format!("Ramp steps: {}", self.ramp_steps)))
+            .push(Slider::new(
+                &mut self.ramp_steps_slider,
+                2.0..=16.0,
+                self.ramp_steps as f32,
+                Message::RampStepsChanged,
+            ));
+        edit_bar = RampSpace::ALL
+            .iter()
+            .fold(edit_bar, |column, space| {
+                column.push(Radio::new(
+                    *space,
+                    &format!("Blend in {}", space),
+                    ramp_space,
+                    Message::RampSpaceChanged,
+                ))
+            })
+            .push({
+                let button = Button::new(&mut self.add_ramp_button, Text::new("Add ramp"));
+                match ramp_keys {
+                    Some(keys) => button.on_press(Message::AddRampPressed(keys)),
+                    None => button,
+                }
+            })
+            .push(Text::new("Draw color"))
+            .push(self.picked_color.view());
+        edit_bar = Harmony::ALL
+            .iter()
+            .fold(edit_bar, |column, option| {
+                column.push(Radio::new(
+                    *option,
+                    &format!("{} colors", option),
+                    harmony,
+                    Message::HarmonyChanged,
+                ))
+            })
+            .push(self.harmony_colors.view())
+            .push({
+                let button = Button::new(&mut self.add_harmony_button, Text::new("Add to palette"));
+                if has_harmony_colors {
+                    button.on_press(Message::AddHarmonyPressed)
+                } else {
+                    button
+                }
+            })
+            .push(Text::new("Dither color (right click)"))
+            .push(self.dither_color.view());
+        edit_bar = DitherPattern::ALL
+            .iter()
+            .fold(edit_bar, |column, pattern| {
+                column.push(Radio::new(
+                    *pattern,
+                    &format!("{:?} dither", pattern),
+                    dither_pattern,
+                    Message::DitherPatternChanged,
+                ))
+            })
+            .push(Slider::new(
+                &mut self.dither_slider,
+                0.0..=1.0,
+                self.dither_amount,
+                Message::DitherAmountChanged,
+            ))
+            .push(
+                Button::new(&mut self.export_button, Text::new("Export as .obj"))
+                    .on_press(Message::ExportPressed),
+            )
+            .push(
+                Button::new(&mut self.usd_export_button, Text::new("Export as USD"))
+                    .on_press(Message::UsdExportPressed),
+            )
+            .push(
+                Button::new(&mut self.fbx_export_button, Text::new("Export as .fbx"))
+                    .on_press(Message::FbxExportPressed),
+            )
+            .push(
+                Button::new(&mut self.collada_export_button, Text::new("Export as .dae"))
+                    .on_press(Message::ColladaExportPressed),
+            )
+            .push(
+                Button::new(&mut self.vdb_export_button, Text::new("Export as .vdb"))
+                    .on_press(Message::VdbExportPressed),
+            )
+            .push(Text::new("Screenshot size (width height):"))
+            .push(
+                TextInput::new(
+                    &mut self.screenshot_size_input,
+                    "1920 1080",
+                    &self.screenshot_size,
+                    Message::ScreenshotSizeChanged,
+                )
+                .padding(5),
+            );
+        edit_bar = FrameAspect::ALL
+            .iter()
+            .fold(edit_bar, |column, aspect| {
+                column.push(Radio::new(
+                    *aspect,
+                    &aspect.to_string(),
+                    frame_aspect,
+                    Message::FrameAspectChanged,
+                ))
+            })
+            .push(Checkbox::new(
+                self.safe_frame,
+                "Show safe frame",
+                Message::SafeFrameToggled,
+            ))
+            .push(Checkbox::new(
+                self.depth_of_field,
+                "Depth of field (Focus to pick)",
+                Message::DepthOfFieldToggled,
+            ))
+            .push(Slider::new(
+                &mut self.depth_of_field_slider,
+                0.0..=MAX_BLUR_RADIUS,
+                self.depth_of_field_blur,
+                Message::DepthOfFieldBlurChanged,
+            ))
+            .push(Checkbox::new(
+                self.bloom,
+                "Bloom of emissive colors (strength)",
+                Message::BloomToggled,
+            ))
+            .push(Slider::new(
+                &mut self.bloom_strength_slider,
+                0.0..=4.0,
+                self.bloom_strength,
+                Message::BloomStrengthChanged,
+            ))
+            .push(Checkbox::new(
+                self.shadow_catcher,
+                "Shadow on a transparent ground",
+                Message::ShadowCatcherToggled,
+            ))
+            .push(
+                Button::new(&mut self.screenshot_button, Text::new("Save screenshot"))
+                    .on_press(Message::ScreenshotPressed),
+            )
+            .push(Text::new("Turntable (fps seconds):"))
+            .push(
+                TextInput::new(
+                    &mut self.turntable_timing_input,
+                    "15 4",
+                    &self.turntable_timing,
+                    Message::TurntableTimingChanged,
+                )
+                .padding(5),
+            )
+            .push(
+                Button::new(&mut self.turntable_button, Text::new("Save turntable GIF"))
+                    .on_press(Message::TurntablePressed),
+            )
+            .push(Text::new(format!(
+                "Stereo eye separation: {:.1}",
+                self.stereo_separation
+            )))
+            .push(Slider::new(
+                &mut self.stereo_separation_slider,
+                0.0..=MAX_STEREO_SEPARATION,
+                self.stereo_separation,
+                Message::StereoSeparationChanged,
+            ));
+        edit_bar = StereoLayout::ALL
+            .iter()
+            .fold(edit_bar, |column, layout| {
+                column.push(Radio::new(
+                    *layout,
+                    &layout.to_string(),
+                    stereo_layout,
+                    Message::StereoLayoutChanged,
+                ))
+            })
+            .push(
+                Button::new(&mut self.stereo_button, Text::new("Save stereo pair"))
+                    .on_press(Message::StereoPressed),
+            )
+            .push(Checkbox::new(
+                self.texture_atlas,
+                "Bake colors into a texture atlas",
+                Message::TextureAtlasToggled,
+            ))
+            .push(Text::new("Export bounds:"));
+        edit_bar = Crop::ALL
+            .iter()
+            .fold(edit_bar, |column, crop| {
+                let label = match crop {
+                    Crop::Grid => "Whole grid",
+                    Crop::Content => "Cropped to the voxels",
+                };
+                column.push(Radio::new(
+                    *crop,
+                    label,
+                    export_crop,
+                    Message::ExportCropChanged,
+                ))
+            })
+            .push(Text::new("Export pivot:"));
+        edit_bar = [
+            None,
+            Some(Pivot::Corner),
+            Some(Pivot::Center),
+            Some(Pivot::BottomCenter),
+        ]
+        .iter()
+        .fold(edit_bar, |column, pivot| {
+            let label = match pivot {
+                None => "Corner of the grid",
+                Some(Pivot::Corner) => "Corner of the voxels",
+                Some(Pivot::Center) => "Center of the voxels",
+                Some(Pivot::BottomCenter) => "Bottom center of the voxels",
+            };
+            column.push(Radio::new(
+                *pivot,
+                label,
+                export_pivot,
+                Message::ExportPivotChanged,
+            ))
+        })
+        .push(Text::new("Object file names ({name}, {n}):"))
+        .push(
+            TextInput::new(
+                &mut self.batch_template_input,
+                "{name}_{n}",
+                &self.batch_template,
+                Message::BatchTemplateChanged,
+            )
+            .padding(5),
+        )
+        .push(
+            Button::new(&mut self.batch_export_button, Text::new("Export objects"))
+                .on_press(Message::BatchExportPressed),
+        )
+        .push(
+            Button::new(&mut self.reexport_button, Text::new("Re-export (F5)"))
+                .on_press(Message::ReexportPressed),
+        )
+        .push(
+            Button::new(&mut self.save_project_button, Text::new("Save project"))
+                .on_press(Message::SaveProjectPressed),
+        )
+        .push(
+            Button::new(
+                &mut self.quick_save_button,
+                Text::new("Quick save (Ctrl+S)"),
+            )
+            .on_press(Message::QuickSavePressed),
+        )
+        .push(Checkbox::new(
+            self.journal_saves,
+            "Quick saves append to the journal",
+            Message::JournalSavesToggled,
+        ))
+        .push(
+            Button::new(
+                &mut self.record_button,
+                Text::new(if self.recording {
+                    "Stop recording"
+                } else {
+                    "Record session"
+                }),
+            )
+            .on_press(Message::RecordSessionPressed),
+        )
+        .push(
+            Button::new(
+                &mut self.replay_button,
+                Text::new(if self.replaying {
+                    "Stop replay"
+                } else {
+                    "Replay session"
+                }),
+            )
+            .on_press(Message::ReplaySessionPressed),
+        )
+        .push(Text::new(format!(
+            "Replay speed: {:.2}x",
+            self.replay_speed
+        )))
+        .push(Slider::new(
+            &mut self.replay_speed_slider,
+            0.25..=16.0,
+            self.replay_speed,
+            Message::ReplaySpeedChanged,
+        ))
+        .push(
+            Button::new(&mut self.open_project_button, Text::new("Open project"))
+                .on_press(Message::OpenProjectPressed),
+        )
+        .push(Text::new(format!(
+            "Macro: {} steps{}",
+            self.macro_steps,
+            if self.macro_recording {
+                " (recording)"
+            } else {
+                ""
+            }
+        )))
+        .push(
+            Button::new(
+                &mut self.macro_record_button,
+                Text::new(if self.macro_recording {
+                    "Stop macro (Shift+M)"
+                } else {
+                    "Record macro (Shift+M)"
+                }),
+            )
+            .on_press(Message::MacroRecordPressed),
+        )
+        .push(
+            Button::new(&mut self.macro_play_button, Text::new("Play macro (M)"))
+                .on_press(Message::MacroPlayPressed),
+        )
+        .push(Text::new(
+            "Shift+R repeats the last box, array, clone stroke or instance at the cursor",
+        ))
+        .push(
+            Button::new(
+                &mut self.delete_selection_button,
+                Text::new("Delete selection"),
+            )
+            .on_press(Message::SelectionActionPressed(SelectionAction::Delete)),
+        )
+        .push(
+            Button::new(
+                &mut self.isolate_selection_button,
+                Text::new("Isolate selection"),
+            )
+            .on_press(Message::SelectionActionPressed(SelectionAction::Isolate)),
+        )
+        .push(
+            Button::new(&mut self.center_button, Text::new("Center in the grid"))
+                .on_press(Message::RecenterPressed(Pivot::Center)),
+        )
+        .push(
+            Button::new(
+                &mut self.stand_button,
+                Text::new("Stand on the middle of the floor"),
+            )
+            .on_press(Message::RecenterPressed(Pivot::BottomCenter)),
+        )
+        .push(Text::new("Mask edits by selection:"));
+        edit_bar = MaskMode::ALL
+            .iter()
+            .fold(edit_bar, |column, mode| {
+                column.push(Radio::new(
+                    *mode,
+                    &format!("{:?}", mode),
+                    mask_mode,
+                    Message::MaskModeChanged,
+                ))
+            })
+            .push(Text::new("Saved masks:"))
+            .push(Text::new(if self.mask_names.is_empty() {
+                "None".to_owned()
+            } else {
+                self.mask_names.join(", ")
+            }))
+            .push(
+                TextInput::new(
+                    &mut self.mask_name_input,
+                    "Mask name",
+                    &self.mask_name,
+                    Message::MaskNameChanged,
+                )
+                .padding(5),
+            )
+            .push(
+                Button::new(&mut self.save_mask_button, Text::new("Save selection")).on_press(
+                    Message::MaskActionPressed(MaskAction::Save(self.mask_name.clone())),
+                ),
+            );
+        edit_bar = Combine::ALL
+            .iter()
+            .fold(edit_bar, |column, combine| {
+                column.push(Radio::new(
+                    *combine,
+                    &format!("{:?}", combine),
+                    mask_combine,
+                    Message::MaskCombineChanged,
+                ))
+            })
+            .push(
+                Button::new(&mut self.recall_mask_button, Text::new("Recall mask")).on_press(
+                    Message::MaskActionPressed(MaskAction::Recall(
+                        self.mask_name.clone(),
+                        self.mask_combine,
+                    )),
+                ),
+            )
+            .push(
+                Button::new(&mut self.delete_mask_button, Text::new("Delete mask")).on_press(
+                    Message::MaskActionPressed(MaskAction::Delete(self.mask_name.clone())),
+                ),
+            )
+            .push(Text::new(
+                "Pivot of the mask (x y z, Corner, Center or BottomCenter, empty to clear):",
+            ))
+            .push(
+                TextInput::new(
+                    &mut self.pivot_cell_input,
+                    "x y z",
+                    &self.pivot_cell,
+                    Message::PivotCellChanged,
+                )
+                .padding(5),
+            )
+            .push({
+                let button = Button::new(&mut self.set_pivot_button, Text::new("Set pivot"));
+                if pivot_valid {
+                    button.on_press(Message::MaskActionPressed(MaskAction::SetPivot(
+                        self.mask_name.clone(),
+                        pivot_cell,
+                    )))
+                } else {
+                    button
+                }
+            })
+            .push(Text::new(
+                "Instance offset and quarter turns (x y z turns):",
+            ))
+            .push(
+                TextInput::new(
+                    &mut self.instance_placement_input,
+                    "x y z turns",
+                    &self.instance_placement,
+                    Message::InstancePlacementChanged,
+                )
+                .padding(5),
+            )
+            .push({
+                let button = Button::new(&mut self.instance_button, Text::new("Place instance"));
+                match instance_placement {
+                    Some((offset, turns)) => button.on_press(Message::MaskActionPressed(
+                        MaskAction::Instance(self.mask_name.clone(), offset, turns),
+                    )),
+                    None => button,
+                }
+            })
+            .push(
+                Button::new(&mut self.unlink_button, Text::new("Unlink instances")).on_press(
+                    Message::MaskActionPressed(MaskAction::Unlink(self.mask_name.clone())),
+                ),
+            )
+            .push(Text::new("Snapshots:"))
+            .push(Text::new(if self.snapshot_names.is_empty() {
+                "None".to_owned()
+            } else {
+                self.snapshot_names.join(", ")
+            }))
+            .push(
+                TextInput::new(
+                    &mut self.snapshot_name_input,
+                    "Snapshot name",
+                    &self.snapshot_name,
+                    Message::SnapshotNameChanged,
+                )
+                .padding(5),
+            )
+            .push(
+                Button::new(&mut self.save_snapshot_button, Text::new("Save snapshot")).on_press(
+                    Message::SnapshotActionPressed(SnapshotAction::Save(
+                        self.snapshot_name.clone(),
+                    )),
+                ),
+            )
+            .push(
+                Button::new(
+                    &mut self.restore_snapshot_button,
+                    Text::new("Restore snapshot"),
+                )
+                .on_press(Message::SnapshotActionPressed(SnapshotAction::Restore(
+                    self.snapshot_name.clone(),
+                ))),
+            )
+            .push(
+                Button::new(&mut self.diff_snapshot_button, Text::new("Diff snapshot")).on_press(
+                    Message::SnapshotActionPressed(SnapshotAction::Diff(
+                        self.snapshot_name.clone(),
+                    )),
+                ),
+            )
+            .push(
+                Button::new(
+                    &mut self.delete_snapshot_button,
+                    Text::new("Delete snapshot"),
+                )
+                .on_press(Message::SnapshotActionPressed(SnapshotAction::Delete(
+                    self.snapshot_name.clone(),
+                ))),
+            );
+        if let Some((name, diff)) = &self.snapshot_diff {
+            edit_bar = edit_bar.push(Text::new(format!(
+                "Since {}: {} added, {} removed, {} recolored (selected)",
+                name,
+                diff.added.len(),
+                diff.removed.len(),
+                diff.recolored.len()
+            )));
+        }
+        edit_bar = edit_bar
+            .push(Text::new("Prefab project (x y z turns path):"))
+            .push(
+                TextInput::new(
+                    &mut self.prefab_input,
+                    "x y z turns path",
+                    &self.prefab,
+                    Message::PrefabChanged,
+                )
+                .padding(5),
+            )
+            .push({
+                let button = Button::new(&mut self.link_prefab_button, Text::new("Link prefab"));
+                match &prefab {
+                    Some(prefab) => button.on_press(Message::PrefabActionPressed(
+                        PrefabAction::Link(prefab.clone()),
+                    )),
+                    None => button,
+                }
+            })
+            .push({
+                let button =
+                    Button::new(&mut self.unlink_prefab_button, Text::new("Unlink prefab"));
+                match prefab {
+                    Some(prefab) => button.on_press(Message::PrefabActionPressed(
+                        PrefabAction::Unlink(prefab.path),
+                    )),
+                    None => button,
+                }
+            })
+            .push(Text::new("Voxel size (x y z):"))
+            .push(
+                TextInput::new(
+                    &mut self.voxel_scale_input,
+                    "x y z",
+                    &self.voxel_scale,
+                    Message::VoxelScaleChanged,
+                )
+                .padding(5),
+            )
+            .push({
+                let button =
+                    Button::new(&mut self.set_voxel_scale_button, Text::new("Set voxel size"));
+                match voxel_scale {
+                    Some(voxel_scale) => button.on_press(Message::SetVoxelScalePressed(voxel_scale)),
+                    None => button,
+                }
+            })
+            .push(Text::new("Sub-grid density (cells per cell of the scene):"))
+            .push(
+                TextInput::new(
+                    &mut self.sub_grid_density_input,
+                    "density",
+                    &self.sub_grid_density,
+                    Message::SubGridDensityChanged,
+                )
+                .padding(5),
+            )
+            .push({
+                let button = Button::new(&mut self.refine_button, Text::new("Refine into sub-grid"));
+                match sub_grid_density {
+                    Some(density) => button.on_press(Message::SubGridActionPressed(
+                        SubGridAction::Refine(density),
+                    )),
+                    None => button,
+                }
+            })
+            .push(
+                Button::new(&mut self.enter_sub_grid_button, Text::new("Edit sub-grid"))
+                    .on_press(Message::SubGridActionPressed(SubGridAction::Enter)),
+            )
+            .push(
+                Button::new(&mut self.leave_sub_grid_button, Text::new("Leave sub-grid"))
+                    .on_press(Message::SubGridActionPressed(SubGridAction::Leave)),
+            )
+            .push(
+                Button::new(&mut self.flatten_sub_grid_button, Text::new("Flatten sub-grid"))
+                    .on_press(Message::SubGridActionPressed(SubGridAction::Flatten)),
+            )
+            .push(Text::new(
+                "Budget of the scene and of every object (voxels triangles voxels triangles, - for none):",
+            ))
+            .push(
+                TextInput::new(
+                    &mut self.budget_input,
+                    "voxels triangles voxels triangles",
+                    &self.budget,
+                    Message::BudgetChanged,
+                )
+                .padding(5),
+            )
+            .push({
+                let button = Button::new(&mut self.set_budget_button, Text::new("Set budget"));
+                match budget {
+                    Some(budget) => button.on_press(Message::SetBudgetPressed(budget)),
+                    None => button,
+                }
+            })
+            .push(Text::new(
+                "Marker on the selection or the camera target (F8 lists them):",
+            ))
+            .push(
+                TextInput::new(
+                    &mut self.marker_name_input,
+                    "Marker name",
+                    &self.marker_name,
+                    Message::MarkerNameChanged,
+                )
+                .padding(5),
+            )
+            .push(
+                TextInput::new(
+                    &mut self.marker_note_input,
+                    "Note",
+                    &self.marker_note,
+                    Message::MarkerNoteChanged,
+                )
+                .padding(5),
+            )
+            .push(
+                Button::new(&mut self.add_marker_button, Text::new("Add marker")).on_press(
+                    Message::MarkerActionPressed(MarkerAction::Add(
+                        self.marker_name.clone(),
+                        self.marker_note.clone(),
+                    )),
+                ),
+            )
+            .push(
+                Button::new(&mut self.delete_marker_button, Text::new("Delete marker")).on_press(
+                    Message::MarkerActionPressed(MarkerAction::Delete(self.marker_name.clone())),
+                ),
+            );
+        edit_bar = edit_bar.push(
+            Button::new(
+                &mut self.validate_button,
+                Text::new("Validate for printing"),
+            )
+            .on_press(Message::ValidatePressed),
+        );
+        if let Some(report) = &self.validation {
+            edit_bar = edit_bar.push(Text::new(if report.problem_cells().is_empty() {
+                "Ready to print".to_owned()
+            } else {
+                format!(
+                    "Floating: {}, thin: {}, voids: {} (selected)",
+                    report.floating.len(),
+                    report.thin.len(),
+                    report.voids.len()
+                )
+            }));
+        }
+        edit_bar = Axis::ALL
+            .iter()
+            .fold(
+                edit_bar.push(Text::new("Symmetrize over:")),
+                |column, axis| {
+                    column.push(Radio::new(
+                        *axis,
+                        &format!("{:?} axis", axis),
+                        symmetry_axis,
+                        Message::SymmetryAxisChanged,
+                    ))
+                },
+            )
+            .push(Checkbox::new(
+                self.keep_positive,
+                "Keep positive half",
+                Message::KeepPositiveToggled,
+            ))
+            .push(
+                Button::new(&mut self.symmetrize_button, Text::new("Symmetrize"))
+                    .on_press(Message::SymmetrizePressed),
+            )
+            .push(Text::new("Array copies:"))
+            .push(
+                TextInput::new(
+                    &mut self.array_count_input,
+                    "Count",
+                    &self.array_count,
+                    Message::ArrayCountChanged,
+                )
+                .padding(5),
+            )
+            .push(Text::new("Offset (x y z):"))
+            .push(
+                TextInput::new(
+                    &mut self.array_offset_input,
+                    "x y z",
+                    &self.array_offset,
+                    Message::ArrayOffsetChanged,
+                )
+                .padding(5),
+            )
+            .push(Checkbox::new(
+                self.array_rotate,
+                "Quarter turn per copy around Y",
+                Message::ArrayRotateToggled,
+            ))
+            .push(
+                Button::new(&mut self.array_button, Text::new("Array selection"))
+                    .on_press(Message::ArrayPressed),
+            )
+            .push(Text::new(
+                "Radial copies and revolving around the grid center:",
+            ))
+            .push(
+                TextInput::new(
+                    &mut self.radial_count_input,
+                    "Count",
+                    &self.radial_count,
+                    Message::RadialCountChanged,
+                )
+                .padding(5),
+            );
+        edit_bar = Axis::ALL
+            .iter()
+            .fold(edit_bar, |column, axis| {
+                column.push(Radio::new(
+                    *axis,
+                    &format!("{:?} axis", axis),
+                    radial_axis,
+                    Message::RadialAxisChanged,
+                ))
+            })
+            .push(Checkbox::new(
+                self.radial_symmetry,
+                "Repeat edits radially",
+                Message::RadialSymmetryToggled,
+            ))
+            .push(
+                Button::new(&mut self.radial_button, Text::new("Radial array"))
+                    .on_press(Message::RadialArrayPressed),
+            )
+            .push(
+                Button::new(&mut self.revolve_button, Text::new("Revolve selection"))
+                    .on_press(Message::RevolvePressed),
+            )
+            .push(Text::new("Shading light (x y z):"))
+            .push(
+                TextInput::new(
+                    &mut self.light_direction_input,
+                    "x y z",
+                    &self.light_direction,
+                    Message::LightDirectionChanged,
+                )
+                .padding(5),
+            )
+            .push(Slider::new(
+                &mut self.shading_slider,
+                0.0..=1.0,
+                self.shading_strength,
+                Message::ShadingStrengthChanged,
+            ))
+            .push(
+                Button::new(&mut self.bake_shading_button, Text::new("Bake shading"))
+                    .on_press(Message::BakeShadingPressed),
+            )
+            .push(
+                Button::new(&mut self.bake_occlusion_button, Text::new("Bake AO"))
+                    .on_press(Message::BakeOcclusionPressed),
+            )
+            .push(
+                Button::new(&mut self.toggle_bake_button, Text::new("Show/hide AO bake"))
+                    .on_press(Message::ToggleBakePressed),
+            )
+            .push(
+                Button::new(&mut self.discard_bake_button, Text::new("Discard AO bake"))
+                    .on_press(Message::DiscardBakePressed),
+            )
+            .push(Text::new("HSV shift (hue degrees, saturation, value):"))
+            .push(
+                TextInput::new(
+                    &mut self.hsv_shift_input,
+                    "h s v",
+                    &self.hsv_shift,
+                    Message::HsvShiftChanged,
+                )
+                .padding(5),
+            )
+            .push(
+                Button::new(&mut self.shift_colors_button, Text::new("Shift colors"))
+                    .on_press(Message::ShiftColorsPressed),
+            )
+            .push(Text::new(palette_usage))
+            .push(
+                Button::new(
+                    &mut self.emissive_toggle_button,
+                    Text::new("Toggle the glow of the draw color"),
+                )
+                .on_press(Message::EmissiveTogglePressed),
+            )
+            .push(
+                Button::new(
+                    &mut self.sort_by_hue_button,
+                    Text::new("Sort palette by hue"),
+                )
+                .on_press(Message::PaletteActionPressed(PaletteAction::Sort(
+                    PaletteOrder::Hue,
+                ))),
+            )
+            .push(
+                Button::new(
+                    &mut self.sort_by_luminance_button,
+                    Text::new("Sort palette by luminance"),
+                )
+                .on_press(Message::PaletteActionPressed(PaletteAction::Sort(
+                    PaletteOrder::Luminance,
+                ))),
+            )
+            .push(Text::new(format!(
+                "Merge colors closer than {:.2}",
+                self.merge_tolerance
+            )))
+            .push(Slider::new(
+                &mut self.merge_tolerance_slider,
+                0.0..=0.5,
+                self.merge_tolerance,
+                Message::MergeToleranceChanged,
+            ))
+            .push(
+                Button::new(
+                    &mut self.merge_colors_button,
+                    Text::new("Merge similar colors"),
+                )
+                .on_press(Message::PaletteActionPressed(PaletteAction::Merge(
+                    self.merge_tolerance,
+                ))),
+            )
+            .push(Text::new("Raw volume size and type:"))
+            .push(
+                TextInput::new(
+                    &mut self.volume_layout_input,
+                    "from the file name",
+                    &self.volume_layout,
+                    Message::VolumeLayoutChanged,
+                )
+                .padding(5),
+            )
+            .push(Text::new("Volume threshold:"))
+            .push(Slider::new(
+                &mut self.volume_threshold_slider,
+                0.0..=1.0,
+                self.volume_threshold,
+                Message::VolumeThresholdChanged,
+            ));
+        edit_bar = TransferFunction::ALL
+            .iter()
+            .fold(edit_bar, |column, transfer| {
+                column.push(Radio::new(
+                    *transfer,
+                    &transfer.to_string(),
+                    transfer_function,
+                    Message::TransferFunctionChanged,
+                ))
+            })
+            .push(Text::new("Quantize imports to colors:"))
+            .push(
+                TextInput::new(
+                    &mut self.import_colors_input,
+                    "all colors",
+                    &self.import_colors,
+                    Message::ImportColorsChanged,
+                )
+                .padding(5),
+            )
+            .push(Checkbox::new(
+                self.import_dither,
+                "Dither quantized colors",
+                Message::ImportDitherToggled,
+            ))
+            .push(
+                Button::new(&mut self.import_volume_button, Text::new("Import volume"))
+                    .on_press(Message::ImportVolumePressed),
+            );
+        edit_bar = edit_bar
+            .push(Text::new("Exposure and tone mapping:"))
+            .push(Slider::new(
+                &mut self.exposure_slider,
+                -4.0..=4.0,
+                self.exposure,
+                Message::ExposureChanged,
+            ));
+        edit_bar = ToneMapping::ALL
+            .iter()
+            .fold(edit_bar, |column, operator| {
+                column.push(Radio::new(
+                    *operator,
+                    &format!("{:?}", operator),
+                    tone_mapping,
+                    Message::ToneMappingChanged,
+                ))
+            })
+            .push(Text::new("Lighting:"));
+        edit_bar = LightingPreset::ALL
+            .iter()
+            .fold(edit_bar, |column, preset| {
+                column.push(Radio::new(
+                    *preset,
+                    &format!("{:?}", preset),
+                    lighting,
+                    Message::LightingChanged,
+                ))
+            })
+            .push(Text::new("Theme:"));
+        edit_bar = ThemeKind::ALL.iter().fold(edit_bar, |column, kind| {
+            column.push(Radio::new(
+                *kind,
+                &kind.to_string(),
+                theme,
+                Message::ThemeChanged,
+            ))
+        });
+
+        if let Some((name, progress)) = self.progress {
+            edit_bar = edit_bar
+                .push(Text::new(format!("{}...", name)))
+                .push(ProgressBar::new(0.0..=1.0, progress))
+                .push(
+                    Button::new(&mut self.cancel_button, Text::new("Cancel"))
+                        .on_press(Message::CancelPressed),
+                );
+        }
+
+        let sidebar = Container::new(edit_bar)
+            .width(Length::Units(150))
+            .height(Length::Fill)
+            .padding(10)
+            .style(UiStyle {});
+
+        let mut panels = Column::new().spacing(10);
+        if self.show_stats {
+            panels = panels.push(self.stats_panel.view());
+        }
+        if self.show_scene_stats {
+            panels = panels.push(self.scene_panel.view());
+        }
+        if self.show_history {
+            panels = panels.push(self.history_panel.view());
+        }
+        if self.show_minimap {
+            panels = panels.push(self.minimap.view());
+        }
+        if self.show_markers {
+            panels = panels.push(self.markers_panel.view());
+        }
+        let main = Row::new().height(Length::Fill).push(sidebar).push(
+            Container::new(panels)
+                .width(Length::Fill)
+                .align_x(Align::End)
+                .padding(10),
+        );
+        match &self.budget_warnings {
+            Some(warnings) => {
+                let status = if warnings.is_empty() {
+                    "Within budget".to_owned()
+                } else {
+                    warnings
+                        .iter()
+                        .map(|warning| warning.to_string())
+                        .collect::<Vec<_>>()
+                        .join("; ")
+                };
+                Column::new()
+                    .push(main)
+                    .push(
+                        Container::new(Text::new(status).size(16))
+                            .width(Length::Fill)
+                            .padding(5)
+                            .style(StatusStyle {
+                                over_budget: !warnings.is_empty(),
+                            }),
+                    )
+                    .into()
+            }
+            None => main.into(),
+        }
+    }
+}
+
+impl canvas::Program<Message> for ColorPicker {
+    fn draw(&self, bounds: Rectangle, _cursor: canvas::Cursor) -> Vec<canvas::Geometry> {
+        let theme = self.canvas_cache.draw(bounds.size(), |frame| {
+            self.draw(frame);
+        });
+
+        vec![theme]
+    }
+
+    fn update(
+        &mut self,
+        event: canvas::Event,
+        bounds: Rectangle,
+        cursor: canvas::Cursor,
+    ) -> Option<Message> {
+        let message: fn(Color) -> Message = match event {
+            canvas::Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) => {
+                Message::ColorPicked
+            }
+            // Right click picks the second color of dithered painting
+            canvas::Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Right)) => {
+                Message::DitherColorPicked
+            }
+            _other => return None,
+        };
+        if let Some(pos) = cursor.position() {
+            let (x_dist, y_dist) = (pos.x - bounds.x, pos.y - bounds.y);
+            if x_dist.is_sign_positive()
+                && y_dist.is_sign_positive()
+                && x_dist < bounds.width
+                && y_dist < bounds.height
+            {
+                let x_pos = x_dist as usize / COLOR_SIZE as usize;
+                let y_pos = y_dist as usize / COLOR_SIZE as usize;
+                let idx = y_pos * Self::COLORS_PER_LINE + x_pos;
+                // The padding before the ramps is transparent
+                if idx < self.colors.len() && self.colors[idx].a > 0.0 {
+                    return Some(message(self.colors[idx]));
+                }
+            }
+        }
+        None
+    }
+}
+
+impl canvas::Program<Message> for FrameGraph {
+    fn draw(&self, bounds: Rectangle, _cursor: canvas::Cursor) -> Vec<canvas::Geometry> {
+        let graph = self.canvas_cache.draw(bounds.size(), |frame| {
+            self.draw(frame);
+        });
+
+        vec![graph]
+    }
+}
+
+impl canvas::Program<Message> for Minimap {
+    fn draw(&self, bounds: Rectangle, _cursor: canvas::Cursor) -> Vec<canvas::Geometry> {
+        let map = self.canvas_cache.draw(bounds.size(), |frame| {
+            self.draw(frame);
+        });
+        // The camera moves far more often than the scene changes
+        let mut frame = canvas::Frame::new(bounds.size());
+        self.draw_frustum(&mut frame);
+        vec![map, frame.into_geometry()]
+    }
+
+    fn update(
+        &mut self,
+        event: canvas::Event,
+        bounds: Rectangle,
+        cursor: canvas::Cursor,
+    ) -> Option<Message> {
+        match event {
+            canvas::Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) => {
+                let pos = cursor.position_in(&bounds)?;
+                let scale = self.scale();
+                Some(Message::MinimapPressed([pos.x / scale, pos.y / scale]))
+            }
+            _other => None,
+        }
+    }
+}
+
+impl canvas::Program<Message> for PickedColor {
+    fn draw(&self, bounds: Rectangle, _cursor: canvas::Cursor) -> Vec<canvas::Geometry> {
+        let theme = self.canvas_cache.draw(bounds.size(), |frame| {
+            self.draw(frame);
+        });
+
+        vec![theme]
+    }
+}
+
+struct UiStyle {}
+impl StyleSheet for UiStyle {
+    fn style(&self) -> Style {
+        Style {
+            background: Some(Background::Color(Color::WHITE)),
+            border_width: 1,
+            border_color: Color::BLACK,
+            ..Default::default()
+        }
+    }
+}
+
+/// The status bar, red while the scene is over its budget
+struct StatusStyle {
+    over_budget: bool,
+}
+impl StyleSheet for StatusStyle {
+    fn style(&self) -> Style {
+        let background = if self.over_budget {
+            Color::from_rgb(0.9, 0.3, 0.3)
+        } else {
+            Color::from_rgb(0.7, 0.9, 0.7)
+        };
+        Style {
+            background: Some(Background::Color(background)),
             border_width: 1,
             border_color: Color::BLACK,
             ..Default::default()
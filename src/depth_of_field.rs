@@ -0,0 +1,340 @@
+use crate::screenshot::padded_row;
+use crate::vertex::{VoxelInstance, VoxelVertex};
+use futures::executor::block_on;
+use iced_wgpu::wgpu;
+use std::mem;
+
+const DISTANCE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Float;
+const DISTANCE_DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+const PIXEL_SIZE: usize = 4;
+/// Keeps the blur of voxels right in front of the camera within reason
+pub const MAX_BLUR_RADIUS: f32 = 32.0;
+
+/// Blurs rendered voxels by how far they are from the focus
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DepthOfField {
+    /// Point of the scene that stays sharp
+    pub focus: [f32; 3],
+    /// Blur radius in pixels of what is far behind the focus
+    pub blur: f32,
+}
+
+impl DepthOfField {
+    /// Radius of the circle of confusion, the background counts as infinitely far
+    fn radius(&self, focus_distance: f32, distance: f32) -> usize {
+        let radius = if distance > 0.0 {
+            self.blur * (distance - focus_distance).abs() / distance
+        } else {
+            self.blur
+        };
+        radius.min(MAX_BLUR_RADIUS).round() as usize
+    }
+
+    /// Averages each RGBA pixel over a box as large as its circle of confusion, the distances
+    /// from the camera are given for every pixel with zero for the background
+    pub fn apply(
+        &self,
+        focus_distance: f32,
+        pixels: &mut [u8],
+        distances: &[f32],
+        width: usize,
+        height: usize,
+    ) {
+        // Summed area table of the colors premultiplied by alpha, so the transparent
+        // background doesn't darken the edges
+        let stride = width + 1;
+        let mut sums = vec![[0u64; 4]; stride * (height + 1)];
+        for y in 0..height {
+            let mut row = [0u64; 4];
+            for x in 0..width {
+                let pixel = &pixels[(y * width + x) * PIXEL_SIZE..][..PIXEL_SIZE];
+                let alpha = pixel[3] as u64;
+                for c in 0..3 {
+                    row[c] += pixel[c] as u64 * alpha;
+                }
+                row[3] += alpha;
+                let above = sums[y * stride + x + 1];
+                for c in 0..4 {
+                    sums[(y + 1) * stride + x + 1][c] = above[c] + row[c];
+                }
+            }
+        }
+        for y in 0..height {
+            for x in 0..width {
+                let radius = self.radius(focus_distance, distances[y * width + x]);
+                if radius == 0 {
+                    continue;
+                }
+                let (x0, x1) = (x.saturating_sub(radius), (x + radius + 1).min(width));
+                let (y0, y1) = (y.saturating_sub(radius), (y + radius + 1).min(height));
+                let mut total = [0u64; 4];
+                for c in 0..4 {
+                    total[c] = sums[y1 * stride + x1][c] + sums[y0 * stride + x0][c]
+                        - sums[y0 * stride + x1][c]
+                        - sums[y1 * stride + x0][c];
+                }
+                let pixel = &mut pixels[(y * width + x) * PIXEL_SIZE..][..PIXEL_SIZE];
+                if total[3] > 0 {
+                    for c in 0..3 {
+                        pixel[c] = (total[c] / total[3]) as u8;
+                    }
+                }
+                let area = ((x1 - x0) * (y1 - y0)) as u64;
+                pixel[3] = (total[3] / area) as u8;
+            }
+        }
+    }
+}
+
+/// Offscreen target the distance pipeline renders the distance of the voxels from the eye into
+pub struct DistanceTarget {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    depth_view: wgpu::TextureView,
+    readback_buf: wgpu::Buffer,
+    width: u32,
+    height: u32,
+}
+
+impl DistanceTarget {
+    pub fn new(device: &wgpu::Device, width: u32, height: u32) -> Self {
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            size,
+            array_layer_count: 1,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: DISTANCE_FORMAT,
+            usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT | wgpu::TextureUsage::COPY_SRC,
+            label: Some("DistanceBuffer"),
+        });
+        let depth_view = device
+            .create_texture(&wgpu::TextureDescriptor {
+                size,
+                array_layer_count: 1,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: DISTANCE_DEPTH_FORMAT,
+                usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT,
+                label: Some("DistanceDepthBuffer"),
+            })
+            .create_default_view();
+        let readback_buf = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: padded_row(width) as u64 * height as u64,
+            usage: wgpu::BufferUsage::MAP_READ | wgpu::BufferUsage::COPY_DST,
+        });
+        DistanceTarget {
+            view: texture.create_default_view(),
+            texture,
+            depth_view,
+            readback_buf,
+            width,
+            height,
+        }
+    }
+
+    pub fn begin_pass<'a>(&'a self, encoder: &'a mut wgpu::CommandEncoder) -> wgpu::RenderPass<'a> {
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                attachment: &self.view,
+                resolve_target: None,
+                load_op: wgpu::LoadOp::Clear,
+                store_op: wgpu::StoreOp::Store,
+                clear_color: wgpu::Color::TRANSPARENT,
+            }],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachmentDescriptor {
+                attachment: &self.depth_view,
+                depth_load_op: wgpu::LoadOp::Clear,
+                depth_store_op: wgpu::StoreOp::Store,
+                stencil_load_op: wgpu::LoadOp::Clear,
+                stencil_store_op: wgpu::StoreOp::Store,
+                clear_depth: 1.0,
+                clear_stencil: 0,
+            }),
+        })
+    }
+
+    /// Copies the distances into the readback buffer
+    pub fn copy(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.copy_texture_to_buffer(
+            wgpu::TextureCopyView {
+                texture: &self.texture,
+                mip_level: 0,
+                array_layer: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            wgpu::BufferCopyView {
+                buffer: &self.readback_buf,
+                offset: 0,
+                bytes_per_row: padded_row(self.width),
+                rows_per_image: self.height,
+            },
+            wgpu::Extent3d {
+                width: self.width,
+                height: self.height,
+                depth: 1,
+            },
+        );
+    }
+
+    /// Waits for the copy submitted after copy and returns the distances from top to bottom
+    pub fn read(&self, device: &wgpu::Device) -> Option<Vec<f32>> {
+        let padded_row = padded_row(self.width) as usize;
+        let mapping = self
+            .readback_buf
+            .map_read(0, padded_row as u64 * self.height as u64);
+        device.poll(wgpu::Maintain::Wait);
+        let mapping = block_on(mapping).ok()?;
+        let row_size = self.width as usize * PIXEL_SIZE;
+        let mut distances = Vec::with_capacity(self.width as usize * self.height as usize);
+        for row in mapping.as_slice().chunks(padded_row) {
+            distances.extend_from_slice(bytemuck::cast_slice::<u8, f32>(&row[..row_size]));
+        }
+        Some(distances)
+    }
+}
+
+pub fn build_distance_pipeline(
+    device: &wgpu::Device,
+    uniform_buf: &wgpu::Buffer,
+    uniform_buf_size: u64,
+) -> (wgpu::BindGroup, wgpu::RenderPipeline) {
+    let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: None,
+        bindings: &[wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStage::VERTEX,
+            ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+        }],
+    });
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        bind_group_layouts: &[&bind_group_layout],
+    });
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &bind_group_layout,
+        bindings: &[wgpu::Binding {
+            binding: 0,
+            resource: wgpu::BindingResource::Buffer {
+                buffer: uniform_buf,
+                range: 0..uniform_buf_size,
+            },
+        }],
+        label: None,
+    });
+
+    let vs = include_bytes!("../shaders/distance.vert.spv");
+    let fs = include_bytes!("../shaders/distance.frag.spv");
+
+    let vs_module =
+        device.create_shader_module(&wgpu::read_spirv(std::io::Cursor::new(&vs[..])).unwrap());
+
+    let fs_module =
+        device.create_shader_module(&wgpu::read_spirv(std::io::Cursor::new(&fs[..])).unwrap());
+
+    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        layout: &pipeline_layout,
+        vertex_stage: wgpu::ProgrammableStageDescriptor {
+            module: &vs_module,
+            entry_point: "main",
+        },
+        fragment_stage: Some(wgpu::ProgrammableStageDescriptor {
+            module: &fs_module,
+            entry_point: "main",
+        }),
+        rasterization_state: Some(wgpu::RasterizationStateDescriptor {
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: wgpu::CullMode::Back,
+            depth_bias: 0,
+            depth_bias_slope_scale: 0.0,
+            depth_bias_clamp: 0.0,
+        }),
+        primitive_topology: wgpu::PrimitiveTopology::TriangleList,
+        color_states: &[wgpu::ColorStateDescriptor {
+            format: DISTANCE_FORMAT,
+            color_blend: wgpu::BlendDescriptor::REPLACE,
+            alpha_blend: wgpu::BlendDescriptor::REPLACE,
+            write_mask: wgpu::ColorWrite::ALL,
+        }],
+        depth_stencil_state: Some(wgpu::DepthStencilStateDescriptor {
+            format: DISTANCE_DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil_front: wgpu::StencilStateFaceDescriptor::IGNORE,
+            stencil_back: wgpu::StencilStateFaceDescriptor::IGNORE,
+            stencil_read_mask: 0,
+            stencil_write_mask: 0,
+        }),
+        vertex_state: wgpu::VertexStateDescriptor {
+            index_format: wgpu::IndexFormat::Uint16,
+            vertex_buffers: &[
+                wgpu::VertexBufferDescriptor {
+                    stride: mem::size_of::<VoxelVertex>() as wgpu::BufferAddress,
+                    step_mode: wgpu::InputStepMode::Vertex,
+                    attributes: &[
+                        // Position
+                        wgpu::VertexAttributeDescriptor {
+                            format: wgpu::VertexFormat::Float3,
+                            offset: 0,
+                            shader_location: 0,
+                        },
+                        // Normal
+                        wgpu::VertexAttributeDescriptor {
+                            format: wgpu::VertexFormat::Float3,
+                            offset: 3 * 4,
+                            shader_location: 1,
+                        },
+                    ],
+                },
+                wgpu::VertexBufferDescriptor {
+                    stride: mem::size_of::<VoxelInstance>() as wgpu::BufferAddress,
+                    step_mode: wgpu::InputStepMode::Instance,
+                    attributes: &[
+                        // Offset
+                        wgpu::VertexAttributeDescriptor {
+                            format: wgpu::VertexFormat::Float3,
+                            offset: 0,
+                            shader_location: 2,
+                        },
+                    ],
+                },
+            ],
+        },
+        sample_count: 1,
+        sample_mask: !0,
+        alpha_to_coverage_enabled: false,
+    });
+
+    (bind_group, pipeline)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn only_pixels_out_of_focus_are_blurred() {
+        let dof = DepthOfField {
+            focus: [0.0; 3],
+            blur: 2.0,
+        };
+        // An opaque red pixel at the focus next to an opaque blue one far behind it
+        let mut pixels = vec![255, 0, 0, 255, 0, 0, 255, 255];
+        dof.apply(10.0, &mut pixels, &[10.0, 1000.0], 2, 1);
+        assert_eq!(&pixels[..4], &[255, 0, 0, 255]);
+        assert_eq!(&pixels[4..], &[127, 0, 127, 255]);
+
+        // The transparent background lowers the alpha without darkening the color
+        let mut pixels = vec![0, 0, 0, 0, 0, 255, 0, 255];
+        dof.apply(10.0, &mut pixels, &[0.0, 20.0], 2, 1);
+        assert_eq!(&pixels[..4], &[0, 255, 0, 127]);
+        assert_eq!(&pixels[4..], &[0, 255, 0, 127]);
+    }
+}
@@ -0,0 +1,36 @@
+//! Native file dialogs. The browser has no file system paths to hand out,
+//! so on wasm32 the dialogs report that nothing was picked.
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_file(extension: &str) -> Option<String> {
+    let result = nfd::open_save_dialog(Some(extension), None).unwrap_or_else(|e| {
+        panic!(e);
+    });
+    match result {
+        nfd::Response::Okay(file_path) => Some(file_path),
+        _ => None,
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn open_file(extension: &str) -> Option<String> {
+    let result = nfd::open_file_dialog(Some(extension), None).unwrap_or_else(|e| {
+        panic!(e);
+    });
+    match result {
+        nfd::Response::Okay(file_path) => Some(file_path),
+        _ => None,
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn save_file(_extension: &str) -> Option<String> {
+    log::warn!("Saving files isn't supported in the browser yet");
+    None
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn open_file(_extension: &str) -> Option<String> {
+    log::warn!("Opening files isn't supported in the browser yet");
+    None
+}
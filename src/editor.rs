@@ -1,17 +1,51 @@
+use crate::analysis;
+use crate::bloom::Bloom;
+use crate::budget::Budget;
 use crate::camera::CameraWrapper;
-use crate::controls::EditOp;
+use crate::controls::{
+    EditOp, MarkerAction, MaskAction, PaletteAction, PrefabAction, PressureMapping,
+    SelectionAction, SnapshotAction, SubGridAction,
+};
+use crate::depth_of_field::DepthOfField;
+use crate::export::{ExportFormat, ExportPreset};
 use crate::fps::FpsCounter;
+use crate::gamepad::{GamepadAction, GamepadInput};
+use crate::generator::{self, box_shape_cells, polygon_cells, ShapeKind};
 use crate::geometry::*;
+use crate::gizmo::{Gizmo, GizmoGrab, GizmoHandle};
+use crate::highlight::scene_boxes;
+use crate::history::History;
+use crate::image;
+use crate::instance::{self, Instance};
+use crate::journal;
+use crate::marker::{Marker, Markers};
+use crate::palette_tools;
+use crate::pen::PenState;
+use crate::prefab::{self, Prefabs};
+use crate::project::{self, ProjectExtras};
+use crate::recording::{Recorder, Replay};
 use crate::renderer::{Renderer, DEFAULT_MESH_COUNT};
+use crate::screenshot::Turntable;
+use crate::selection::{self, Combine, Mask, MaskMode, SavedMasks, ScreenRegion, Selection};
+use crate::settings::Settings;
+use crate::shading;
+use crate::snapshot::{SnapshotDiff, Snapshots};
+use crate::stats::{FrameTimer, Stats};
+use crate::subgrid::{self, SubGrid};
+use crate::task::{self, Progress, Task, TaskEvent};
+use crate::theme::ThemeKind;
+use crate::touch::{Gesture, TouchGestures};
+use crate::transform::{self, Axis};
 use crate::ui::Ui;
-use crate::vertex::VoxelVertex;
+use crate::volume;
 use crate::voxel_manager::VoxelManager;
+use crate::window_state::{self, WindowState};
+use crate::working_plane::{Handle, WorkingPlane};
 use cgmath::Vector3;
 use futures::executor::block_on;
 use iced_wgpu::wgpu;
-use std::fs::File;
-use std::io::prelude::*;
-use std::io::BufWriter;
+use std::fs;
+use std::sync::mpsc;
 use std::time;
 
 use winit::{
@@ -19,6 +53,159 @@ use winit::{
     event_loop::ControlFlow,
 };
 
+// Pixels the cursor has to move with the select tool held down to drag a marquee, not click
+const MARQUEE_THRESHOLD: f32 = 4.0;
+
+/// Maps the arrow keys to the axes of the active plane, Page Up and Down move along its normal
+fn nudge_offset(key: event::VirtualKeyCode, plane_normal: Axis) -> Option<[i64; 3]> {
+    use event::VirtualKeyCode::*;
+    let (right, up) = plane_normal.plane_axes();
+    let (axis, step) = match key {
+        Right => (right, 1),
+        Left => (right, -1),
+        Up => (up, 1),
+        Down => (up, -1),
+        PageUp => (plane_normal, 1),
+        PageDown => (plane_normal, -1),
+        _ => return None,
+    };
+    let mut offset = [0; 3];
+    offset[axis.index()] = step;
+    Some(offset)
+}
+
+/// Results of the background tasks the editor has to apply to the scene
+enum TaskOutput {
+    Done,
+    /// The opened project, its snapshots and markers and its file path
+    Project(VoxelManager, ProjectExtras, String),
+    /// An imported scene that isn't saved as a project yet
+    Scene(VoxelManager),
+}
+
+/// Structural edits that Shift+R repeats with their lowest corner on the cursor cell
+#[derive(Clone)]
+enum RepeatableAction {
+    /// A box edit of the draw, erase or refill ops
+    Box(EditOp, BoundingBox),
+    /// Colored cells relative to the corner, copied count times along the offset
+    Array {
+        corner: [i64; 3],
+        voxels: Vec<([i64; 3], [f32; 4])>,
+        count: usize,
+        offset: [i64; 3],
+        rotation: Option<Axis>,
+    },
+    /// Colored cells relative to the corner, copied around the axis through the doubled center,
+    /// which is relative to the doubled corner so the axis moves along
+    RadialArray {
+        corner: [i64; 3],
+        voxels: Vec<([i64; 3], [f32; 4])>,
+        count: usize,
+        axis: Axis,
+        center2: [i64; 3],
+    },
+    /// The cells a clone stroke painted, relative to the corner
+    Clone {
+        corner: [i64; 3],
+        voxels: Vec<([i64; 3], [f32; 4])>,
+    },
+    /// An instance of a saved mask, the corner is where the offset puts its lowest cells
+    Instance {
+        source: String,
+        offset: [i64; 3],
+        turns: usize,
+        corner: [i64; 3],
+    },
+}
+
+impl RepeatableAction {
+    fn corner(&self) -> Vector3<f32> {
+        let [x, y, z] = match self {
+            RepeatableAction::Box(_, bbox) => return bbox.corner,
+            RepeatableAction::Array { corner, .. }
+            | RepeatableAction::RadialArray { corner, .. }
+            | RepeatableAction::Clone { corner, .. }
+            | RepeatableAction::Instance { corner, .. } => *corner,
+        };
+        Vector3::new(x as f32, y as f32, z as f32)
+    }
+
+    /// The same action with its corner moved to the given one
+    fn moved_to(&self, to: Vector3<f32>) -> RepeatableAction {
+        let target = [
+            to.x.round() as i64,
+            to.y.round() as i64,
+            to.z.round() as i64,
+        ];
+        let mut moved = self.clone();
+        match &mut moved {
+            RepeatableAction::Box(_, bbox) => {
+                *bbox = BoundingBox::new(to, bbox.extent, bbox.color);
+            }
+            RepeatableAction::Instance { offset, corner, .. } => {
+                for i in 0..3 {
+                    offset[i] += target[i] - corner[i];
+                }
+                *corner = target;
+            }
+            RepeatableAction::Array { corner, .. }
+            | RepeatableAction::RadialArray { corner, .. }
+            | RepeatableAction::Clone { corner, .. } => *corner = target,
+        }
+        moved
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            RepeatableAction::Box(..) => "box",
+            RepeatableAction::Array { .. } => "array",
+            RepeatableAction::RadialArray { .. } => "radial array",
+            RepeatableAction::Clone { .. } => "clone stroke",
+            RepeatableAction::Instance { .. } => "instance",
+        }
+    }
+}
+
+/// Structural edits recorded as one step each, M plays them back from the cursor cell
+#[derive(Default)]
+struct Macro {
+    // Actions with their corners relative to the corner of the first one
+    steps: Vec<RepeatableAction>,
+    recording: bool,
+    origin: Option<Vector3<f32>>,
+}
+
+impl Macro {
+    fn start(&mut self) {
+        self.steps.clear();
+        self.recording = true;
+        self.origin = None;
+    }
+
+    fn record(&mut self, action: RepeatableAction) {
+        if !self.recording {
+            return;
+        }
+        let origin = *self.origin.get_or_insert(action.corner());
+        self.steps.push(action.moved_to(action.corner() - origin));
+    }
+}
+
+/// A session being recorded, a frame is captured whenever the scene's revision changes
+struct Recording {
+    recorder: Recorder,
+    start: time::Instant,
+    revision: u64,
+}
+
+/// A recorded session being played back, its position in milliseconds advances by the replay speed
+struct Playback {
+    replay: Replay,
+    position: f64,
+    last_tick: time::Instant,
+}
+
 #[derive(Eq, PartialEq)]
 enum EditorState {
     ChangeView,
@@ -28,245 +215,2434 @@ enum EditorState {
 
 pub struct Editor {
     window: winit::window::Window,
+    window_state: WindowState,
     camera: CameraWrapper,
     voxel_manager: VoxelManager,
     renderer: Renderer,
     ui: Ui,
     state: EditorState,
     cursor_ray: Ray,
+    cursor_position: winit::dpi::PhysicalPosition<f64>,
+    // Cursor positions since the select tool was pressed, in pixels
+    marquee: Vec<[f32; 2]>,
+    // Last painted cell of the current paint stroke and whether it was on a ground plane
+    stroke: Option<([i32; 3], bool)>,
+    pen: PenState,
+    touch: TouchGestures,
+    task: Option<Task<TaskOutput>>,
+    settings: Settings,
+    theme: ThemeKind,
+    selection: Selection,
+    // Normal of the ground plane the cursor was last over, it maps the nudge keys to axes
+    active_plane: Axis,
+    working_plane: WorkingPlane,
+    // Gizmo handle held down, with the layer and the coordinate along the normal it was grabbed at
+    working_plane_grab: Option<(Handle, usize, f32)>,
+    // Handles to transform the selection with, None while nothing is selected
+    gizmo: Option<Gizmo>,
+    gizmo_grab: Option<GizmoGrab>,
+    // X, Y or Z held down to keep drags on that axis
+    held_axis: Option<Axis>,
+    modifiers: event::ModifiersState,
+    last_action: Option<RepeatableAction>,
+    tool_macro: Macro,
+    // Control points of the curve or the corners of the polygon being placed
+    control_points: Vec<[i32; 3]>,
+    // Source cell of the clone tool and its offset from the brush during a stroke
+    clone_anchor: Option<[i32; 3]>,
+    clone_offset: Option<[i32; 3]>,
+    // Cells the current clone stroke painted, repeated by Shift+R once it ends
+    clone_painted: Vec<[usize; 3]>,
+    // Center of the voxel the depth of field focuses on, the orbit target until one is picked
+    focus: Option<[f32; 3]>,
+    // The editing tools only change the cells it allows
+    mask_mode: MaskMode,
+    mask: Option<Mask>,
+    // Named selections kept next to the project
+    saved_masks: SavedMasks,
+    // The saved mask last saved or recalled as the selection, its pivot is the selection's
+    active_object: Option<String>,
+    // Cells every instance was last stamped on, in the order of the instances
+    stamped: Vec<Vec<[usize; 3]>>,
+    // Revision of the scene the instances were last stamped at
+    instances_revision: u64,
+    // Other projects placed into the scene and the modification times they were last stamped at
+    prefabs: Prefabs,
+    prefab_times: Vec<Option<time::SystemTime>>,
+    // Named scenes saved inside the project file
+    snapshots: Snapshots,
+    // Named cells with notes, saved inside the project file too
+    markers: Markers,
+    // Whether the snapshots, markers, sub-grids, voxel size or emissive colors changed since the
+    // last save, the journal only holds the chunks of the scene
+    unjournaled_extras: bool,
+    // Finer grids embedded in the scene, and while one of them is edited in place of the scene
+    // its index with the scene around it
+    sub_grids: Vec<SubGrid>,
+    parent_scene: Option<(usize, VoxelManager)>,
+    // Limits of the voxel and triangle counts and the revision of the scene last checked
+    budget: Budget,
+    budget_revision: Option<u64>,
+    // Scene states to jump back to, the revision it last recorded and the name of the next entry
+    history: History,
+    history_revision: u64,
+    history_label: Option<String>,
+    recording: Option<Recording>,
+    // Edits are disabled while a session is played back
+    playback: Option<Playback>,
+    // The ambient occlusion bake of the scene, or the unbaked scene while the bake is shown.
+    // Showing and hiding the bake swaps it with the scene.
+    occlusion_layer: Option<VoxelManager>,
+    showing_occlusion: bool,
+    // File of the saved or opened project, its export preset is kept next to it
+    project_path: Option<String>,
+    export_preset: Option<ExportPreset>,
 }
 
-impl Editor {
-    fn resize(&mut self, size: winit::dpi::PhysicalSize<u32>) {
-        self.renderer.resize(size, &mut self.camera);
-        self.ui = Ui::new(&self.window, self.renderer.device_mut())
+impl Editor {
+    fn resize(&mut self, size: winit::dpi::PhysicalSize<u32>, scale_factor: f64) {
+        // Minimized windows have no area to render into
+        if size.width == 0 || size.height == 0 {
+            return;
+        }
+        self.renderer.resize(size, &mut self.camera);
+        self.ui.resize(size, scale_factor);
+        // The same cursor position points somewhere else in the resized viewport
+        self.move_cursor(self.cursor_position);
+    }
+
+    fn update(&mut self, event: winit::event::WindowEvent) {
+        self.pen.update(&event);
+        if let event::WindowEvent::KeyboardInput { .. } = event {
+            if self.ui.controls().text_input_focused() {
+                return;
+            }
+        }
+        // Don't change the view if we're editing the 3d canvas
+        if let event::WindowEvent::MouseInput {
+            state,
+            button: event::MouseButton::Left,
+            ..
+        } = event
+        {
+            if self.playback.is_none() {
+                match state {
+                    event::ElementState::Pressed => {
+                        if !self.grab_gizmo() && !self.grab_working_plane() {
+                            self.state = EditorState::Edit;
+                        }
+                    }
+                    event::ElementState::Released => {
+                        let grabbed = self.gizmo_grab.take().is_some()
+                            | self.working_plane_grab.take().is_some();
+                        if !grabbed {
+                            self.state = EditorState::EditFinished;
+                        }
+                    }
+                }
+            }
+        };
+        if let event::WindowEvent::KeyboardInput {
+            input:
+                event::KeyboardInput {
+                    virtual_keycode: Some(event::VirtualKeyCode::Space),
+                    state: event::ElementState::Pressed,
+                    ..
+                },
+            ..
+        } = event
+        {
+            self.ui.controls().step_edit_op();
+        };
+
+        if let event::WindowEvent::ModifiersChanged(modifiers) = event {
+            self.modifiers = modifiers;
+        }
+
+        if let event::WindowEvent::KeyboardInput {
+            input:
+                event::KeyboardInput {
+                    virtual_keycode: Some(key),
+                    state,
+                    ..
+                },
+            ..
+        } = event
+        {
+            let axis = match key {
+                event::VirtualKeyCode::X => Some(Axis::X),
+                event::VirtualKeyCode::Y => Some(Axis::Y),
+                event::VirtualKeyCode::Z => Some(Axis::Z),
+                _ => None,
+            };
+            match (axis, state) {
+                (Some(axis), event::ElementState::Pressed) => self.held_axis = Some(axis),
+                (Some(axis), event::ElementState::Released) if self.held_axis == Some(axis) => {
+                    self.held_axis = None
+                }
+                _ => {}
+            }
+        };
+
+        if let event::WindowEvent::KeyboardInput {
+            input:
+                event::KeyboardInput {
+                    virtual_keycode: Some(event::VirtualKeyCode::R),
+                    state: event::ElementState::Pressed,
+                    ..
+                },
+            ..
+        } = event
+        {
+            if self.modifiers.shift() {
+                self.repeat_last_action();
+            }
+        };
+
+        if let event::WindowEvent::KeyboardInput {
+            input:
+                event::KeyboardInput {
+                    virtual_keycode: Some(event::VirtualKeyCode::M),
+                    state: event::ElementState::Pressed,
+                    ..
+                },
+            ..
+        } = event
+        {
+            if self.modifiers.shift() {
+                self.toggle_macro_recording();
+            } else {
+                self.play_macro();
+            }
+        };
+
+        if let event::WindowEvent::KeyboardInput {
+            input:
+                event::KeyboardInput {
+                    virtual_keycode: Some(event::VirtualKeyCode::Return),
+                    state: event::ElementState::Pressed,
+                    ..
+                },
+            ..
+        } = event
+        {
+            if self.ui.controls().edit_op() == EditOp::Shape {
+                self.finish_polygon();
+            } else {
+                self.finish_curve();
+            }
+        };
+
+        if let event::WindowEvent::KeyboardInput {
+            input:
+                event::KeyboardInput {
+                    virtual_keycode: Some(event::VirtualKeyCode::Back),
+                    state: event::ElementState::Pressed,
+                    ..
+                },
+            ..
+        } = event
+        {
+            self.control_points.pop();
+            self.show_control_points();
+        };
+
+        if let event::WindowEvent::KeyboardInput {
+            input:
+                event::KeyboardInput {
+                    virtual_keycode: Some(event::VirtualKeyCode::G),
+                    state: event::ElementState::Pressed,
+                    ..
+                },
+            ..
+        } = event
+        {
+            self.renderer.toggle_render_mesh();
+        };
+
+        if let event::WindowEvent::KeyboardInput {
+            input:
+                event::KeyboardInput {
+                    virtual_keycode: Some(event::VirtualKeyCode::F4),
+                    state: event::ElementState::Pressed,
+                    ..
+                },
+            ..
+        } = event
+        {
+            self.ui.toggle_scene_stats();
+        };
+
+        if let event::WindowEvent::KeyboardInput {
+            input:
+                event::KeyboardInput {
+                    virtual_keycode: Some(event::VirtualKeyCode::F11),
+                    state: event::ElementState::Pressed,
+                    ..
+                },
+            ..
+        } = event
+        {
+            window_state::toggle_fullscreen(&self.window);
+        };
+
+        if let event::WindowEvent::KeyboardInput {
+            input:
+                event::KeyboardInput {
+                    virtual_keycode: Some(event::VirtualKeyCode::F5),
+                    state: event::ElementState::Pressed,
+                    ..
+                },
+            ..
+        } = event
+        {
+            self.reexport();
+        };
+
+        if let event::WindowEvent::KeyboardInput {
+            input:
+                event::KeyboardInput {
+                    virtual_keycode: Some(event::VirtualKeyCode::F3),
+                    state: event::ElementState::Pressed,
+                    ..
+                },
+            ..
+        } = event
+        {
+            self.ui.toggle_stats();
+        };
+
+        if let event::WindowEvent::KeyboardInput {
+            input:
+                event::KeyboardInput {
+                    virtual_keycode: Some(event::VirtualKeyCode::F6),
+                    state: event::ElementState::Pressed,
+                    ..
+                },
+            ..
+        } = event
+        {
+            self.ui.toggle_history();
+        };
+
+        if let event::WindowEvent::KeyboardInput {
+            input:
+                event::KeyboardInput {
+                    virtual_keycode: Some(event::VirtualKeyCode::F7),
+                    state: event::ElementState::Pressed,
+                    ..
+                },
+            ..
+        } = event
+        {
+            self.ui.toggle_minimap();
+        };
+
+        if let event::WindowEvent::KeyboardInput {
+            input:
+                event::KeyboardInput {
+                    virtual_keycode: Some(event::VirtualKeyCode::F8),
+                    state: event::ElementState::Pressed,
+                    ..
+                },
+            ..
+        } = event
+        {
+            self.ui.toggle_markers();
+        };
+
+        if let event::WindowEvent::KeyboardInput {
+            input:
+                event::KeyboardInput {
+                    virtual_keycode: Some(event::VirtualKeyCode::S),
+                    state: event::ElementState::Pressed,
+                    ..
+                },
+            ..
+        } = event
+        {
+            if self.modifiers.ctrl() {
+                self.quick_save();
+            }
+        };
+
+        if let event::WindowEvent::KeyboardInput {
+            input:
+                event::KeyboardInput {
+                    virtual_keycode: Some(event::VirtualKeyCode::Z),
+                    state: event::ElementState::Pressed,
+                    ..
+                },
+            ..
+        } = event
+        {
+            if self.modifiers.ctrl() {
+                let restored = if self.modifiers.shift() {
+                    self.history.redo()
+                } else {
+                    self.history.undo()
+                };
+                if let Some(restored) = restored {
+                    self.restore_history(restored);
+                }
+            }
+        };
+
+        if let event::WindowEvent::KeyboardInput {
+            input:
+                event::KeyboardInput {
+                    virtual_keycode: Some(key),
+                    state: event::ElementState::Pressed,
+                    ..
+                },
+            ..
+        } = event
+        {
+            if let Some(offset) = nudge_offset(key, self.active_plane) {
+                self.nudge_selection(offset);
+            }
+        };
+
+        if let event::WindowEvent::CursorMoved { position, .. } = event {
+            self.move_cursor(position);
+            self.drag_working_plane();
+            self.drag_gizmo();
+        }
+
+        if let event::WindowEvent::Touch(touch) = event {
+            for gesture in self.touch.update(touch.id, touch.phase, touch.location) {
+                self.apply_gesture(gesture);
+            }
+        }
+
+        if self.state == EditorState::ChangeView {
+            let viewport_changed = self.camera.update(&event);
+            if viewport_changed {
+                self.renderer.update_view(&mut self.camera);
+            }
+        }
+        self.update_cursor();
+    }
+
+    fn move_cursor(&mut self, position: winit::dpi::PhysicalPosition<f64>) {
+        self.cursor_position = position;
+        let window_size = self.window.inner_size();
+        self.cursor_ray = self.camera.ray_at(
+            [position.x as f32, position.y as f32],
+            [window_size.width, window_size.height],
+        );
+    }
+
+    /// Grabs the gizmo handle under the cursor when the working plane is on, the rotation
+    /// handles turn the plane right away. Returns whether a handle was grabbed.
+    fn grab_working_plane(&mut self) -> bool {
+        if !self.ui.controls().working_plane() {
+            return false;
+        }
+        let extent = self.voxel_manager.extent();
+        let handle = match self.working_plane.handle_at(&self.cursor_ray, extent) {
+            Some(handle) => handle,
+            None => return false,
+        };
+        if let Handle::Rotate(axis) = handle {
+            self.working_plane.rotate(axis);
+        }
+        let layer = self.working_plane.layer;
+        let grabbed = self
+            .working_plane
+            .normal_coordinate(&self.cursor_ray, extent)
+            .unwrap_or(layer as f32);
+        self.working_plane_grab = Some((handle, layer, grabbed));
+        true
+    }
+
+    /// The region dragged with the select tool, a rectangle or a freehand lasso while Alt is
+    /// held. None while the cursor stayed where it was pressed, that's a click.
+    fn marquee_region(&self) -> Option<ScreenRegion> {
+        let first = *self.marquee.first()?;
+        let dragged = self.marquee.iter().any(|position| {
+            (position[0] - first[0]).abs() > MARQUEE_THRESHOLD
+                || (position[1] - first[1]).abs() > MARQUEE_THRESHOLD
+        });
+        if !dragged {
+            return None;
+        }
+        Some(if self.modifiers.alt() {
+            ScreenRegion::Lasso(self.marquee.clone())
+        } else {
+            ScreenRegion::Rectangle(first, *self.marquee.last()?)
+        })
+    }
+
+    /// Holding an axis key keeps drags on that axis, holding Shift on the one moved along most
+    fn drag_lock(&self) -> Option<AxisLock> {
+        match self.held_axis {
+            Some(axis) => Some(AxisLock::Axis(axis)),
+            None if self.modifiers.shift() => Some(AxisLock::Dominant),
+            None => None,
+        }
+    }
+
+    /// Whether the cursor goes onto the working plane instead of the ground planes
+    fn working_plane_enabled(&self) -> bool {
+        self.ui.controls().working_plane() || self.ui.controls().snap_to_surface()
+    }
+
+    fn drag_working_plane(&mut self) {
+        if let Some((Handle::Move, start_layer, grabbed)) = self.working_plane_grab {
+            let extent = self.voxel_manager.extent();
+            if let Some(coordinate) = self
+                .working_plane
+                .normal_coordinate(&self.cursor_ray, extent)
+            {
+                self.working_plane
+                    .drag(start_layer, grabbed, coordinate, extent);
+            }
+        }
+    }
+
+    /// Grabs the handle of the transform gizmo under the cursor. Returns whether one was grabbed.
+    fn grab_gizmo(&mut self) -> bool {
+        if !self.ui.controls().transform_gizmo() {
+            return false;
+        }
+        let gizmo = match self.gizmo {
+            Some(gizmo) => gizmo,
+            None => return false,
+        };
+        let handle = match gizmo.handle_at(&self.cursor_ray) {
+            Some(handle) => handle,
+            None => return false,
+        };
+        let grabbed = match gizmo.handle_coordinate(handle, &self.cursor_ray) {
+            Some(grabbed) => grabbed,
+            None => return false,
+        };
+        let i = handle.axis().index();
+        let cells = self.selection.cells();
+        let min = cells.iter().map(|cell| cell[i]).min().unwrap_or(0);
+        let max = cells.iter().map(|cell| cell[i]).max().unwrap_or(0);
+        self.gizmo_grab = Some(GizmoGrab {
+            handle,
+            grabbed,
+            applied: 0,
+            length: max - min + 1,
+        });
+        self.history_label = Some(
+            match handle {
+                GizmoHandle::Move(_) => "Move selection",
+                GizmoHandle::Rotate(_) => "Rotate selection",
+                GizmoHandle::Scale(_) => "Scale selection",
+            }
+            .to_owned(),
+        );
+        true
+    }
+
+    /// Moves, turns or stretches the selection by the whole steps the held handle was dragged
+    fn drag_gizmo(&mut self) {
+        let (gizmo, mut grab) = match (self.gizmo, self.gizmo_grab) {
+            (Some(gizmo), Some(grab)) => (gizmo, grab),
+            _ => return,
+        };
+        let steps = match gizmo.handle_coordinate(grab.handle, &self.cursor_ray) {
+            Some(coordinate) => grab.steps(coordinate),
+            None => return,
+        };
+        if steps == grab.applied {
+            return;
+        }
+        let cells = self.selection.cells();
+        let transformed = match grab.handle {
+            GizmoHandle::Move(axis) => {
+                let mut offset = [0; 3];
+                offset[axis.index()] = steps - grab.applied;
+                transform::translate(&mut self.voxel_manager, &cells, offset)
+            }
+            GizmoHandle::Rotate(axis) => {
+                // Turning by more than a full turn at once is the same as by the rest of it
+                let turns = (steps - grab.applied).rem_euclid(4) as usize;
+                let pivot = self.pivot();
+                transform::rotate(&mut self.voxel_manager, &cells, axis, turns, pivot)
+            }
+            GizmoHandle::Scale(axis) => {
+                let length = (grab.length as i64 + steps).max(1) as usize;
+                transform::scale(&mut self.voxel_manager, &cells, axis, length)
+            }
+        };
+        // A step that would leave the grid is tried again once the cursor moves on
+        if let Some(cells) = transformed {
+            grab.applied = steps;
+            self.gizmo_grab = Some(grab);
+            self.renderer.update_voxels(&mut self.voxel_manager);
+            self.set_selection(Selection::from_cells(cells));
+        }
+    }
+
+    fn apply_gesture(&mut self, gesture: Gesture) {
+        match gesture {
+            Gesture::PaintStarted(position) => {
+                // There is no hovering with touch, so place the cursor before the edit starts
+                self.move_cursor(position);
+                self.update_cursor();
+                self.state = EditorState::Edit;
+            }
+            Gesture::PaintMoved(position) => self.move_cursor(position),
+            Gesture::PaintEnded => self.state = EditorState::EditFinished,
+            Gesture::PaintCancelled => {
+                self.renderer.cancel_draw_rectangle();
+                self.stroke = None;
+                self.state = EditorState::ChangeView;
+            }
+            Gesture::Orbit(dx, dy) => {
+                self.camera.orbit(-dx / 10.0, -dy / 10.0);
+                self.renderer.update_view(&mut self.camera);
+            }
+            Gesture::Pan(dx, dy) => {
+                self.camera.pan(-dx / 10.0, dy / 10.0);
+                self.renderer.update_view(&mut self.camera);
+            }
+            Gesture::Pinch(delta) => {
+                self.camera.zoom(-delta / 100.0);
+                self.renderer.update_view(&mut self.camera);
+            }
+        }
+    }
+
+    /// Moves the cursor cube or the edited rectangle under the cursor and applies finished edits
+    fn update_cursor(&mut self) {
+        #[cfg(feature = "debug_ray")]
+        self.renderer
+            .cursor_helper(Some(self.cursor_ray.origin), self.cursor_ray.end);
+
+        let (erase_box, draw_box) = if self.ui.controls().gpu_picking() {
+            match self.renderer.pick(self.cursor_position) {
+                Some(pick) => self.voxel_manager.get_picked_boxes(pick),
+                None => (None, None),
+            }
+        } else {
+            self.voxel_manager.get_intersection_boxes(&self.cursor_ray)
+        };
+        // The snapped plane stays while a rectangle is dragged off the surface
+        if self.ui.controls().snap_to_surface() && self.state == EditorState::ChangeView {
+            if let (Some(voxel), Some(outside)) = (erase_box, draw_box) {
+                let cell = |bbox: BoundingBox| {
+                    [
+                        bbox.corner.x as usize,
+                        bbox.corner.y as usize,
+                        bbox.corner.z as usize,
+                    ]
+                };
+                if let Some(working_plane) = WorkingPlane::on_face(cell(voxel), cell(outside)) {
+                    self.working_plane = working_plane;
+                }
+            }
+        }
+        #[cfg(feature = "debug_ray")]
+        let mut closest_plane_name = "None";
+        let mut closest_plane = None;
+        let mut intersection_point = Vector3::new(0.0, 0.0, 0.0);
+        let mesh_count = DEFAULT_MESH_COUNT as f32;
+        let working_plane;
+        let planes: &[Plane] = if self.working_plane_enabled() {
+            working_plane = [self.working_plane.plane()];
+            &working_plane
+        } else {
+            &[XY_PLANE, YZ_PLANE, XZ_PLANE]
+        };
+        let margin = self.cursor_ray.tolerance.margin(mesh_count);
+        if erase_box.is_none() {
+            // Every plane is tested and the nearest hit inside the grid wins, voxels under the
+            // cursor still come first
+            let nearest = planes
+                .iter()
+                .filter_map(|plane| {
+                    let (point, distance) = self.cursor_ray.plane_intersection(plane)?;
+                    #[cfg(feature = "debug_ray")]
+                    log::debug!(
+                        "{:?} intersects with mouse world position at {:?}",
+                        plane.name,
+                        point
+                    );
+                    let inside =
+                        (0..3).all(|i| point[i] >= -margin && point[i] <= mesh_count + margin);
+                    if inside {
+                        Some((plane, point, distance))
+                    } else {
+                        None
+                    }
+                })
+                .fold(None, |nearest, hit| match nearest {
+                    Some((_, _, distance)) if distance <= hit.2 => nearest,
+                    _ => Some(hit),
+                });
+            if let Some((plane, point, _)) = nearest {
+                intersection_point = plane.project(point);
+
+                // Hits a rounding error off the grid boundary land on it, really small floating
+                // point coordinates caused stuttering in the cursor movement
+                for i in 0..3 {
+                    if intersection_point[i] < margin {
+                        intersection_point[i] = 0.0;
+                    } else if intersection_point[i] > mesh_count - margin {
+                        intersection_point[i] = mesh_count;
+                    }
+                }
+
+                #[cfg(feature = "debug_ray")]
+                {
+                    closest_plane_name = plane.name;
+                }
+                closest_plane = Some(plane);
+                self.active_plane = if plane.normal.x > 0.0 {
+                    Axis::X
+                } else if plane.normal.y > 0.0 {
+                    Axis::Y
+                } else {
+                    Axis::Z
+                };
+            }
+        }
+
+        #[cfg(feature = "debug_ray")]
+        log::debug!(
+            "Mouse intersects {:?} plane at {:?}",
+            closest_plane_name,
+            intersection_point
+        );
+        let c = self.ui.controls().draw_color();
+        let theme = self.theme.theme();
+        self.renderer
+            .set_cursor_color(match self.ui.controls().edit_op() {
+                EditOp::Draw
+                | EditOp::Refill
+                | EditOp::Paint
+                | EditOp::Stairs
+                | EditOp::Text
+                | EditOp::Curve
+                | EditOp::Shape
+                | EditOp::Clone
+                | EditOp::Smooth => theme.ghost([c.r, c.g, c.b, c.a]),
+                EditOp::Erase => theme.erase_highlight,
+                EditOp::Select | EditOp::Focus => theme.selection,
+            });
+        // Only the editing tools are masked and repeated, transforming the selection isn't
+        self.voxel_manager.set_mask(self.mask.take());
+        self.voxel_manager
+            .set_symmetry(self.ui.controls().radial_symmetry());
+        match self.state {
+            EditorState::ChangeView => {
+                if let Some(bbox) = erase_box {
+                    match self.ui.controls().edit_op() {
+                        EditOp::Draw
+                        | EditOp::Stairs
+                        | EditOp::Text
+                        | EditOp::Curve
+                        | EditOp::Shape => self.renderer.update_cursor_pos(draw_box.unwrap()),
+                        EditOp::Erase => self.renderer.update_cursor_pos(bbox),
+                        EditOp::Refill => self.renderer.update_cursor_pos(bbox),
+                        EditOp::Paint
+                        | EditOp::Select
+                        | EditOp::Clone
+                        | EditOp::Smooth
+                        | EditOp::Focus => self.renderer.update_cursor_pos(bbox),
+                    };
+                } else {
+                    self.renderer
+                        .update_cursor_pos_on_plane(intersection_point, closest_plane);
+                }
+            }
+            EditorState::Edit
+                if self.ui.controls().edit_op() == EditOp::Paint
+                    || self.ui.controls().edit_op() == EditOp::Clone
+                    || self.ui.controls().edit_op() == EditOp::Smooth =>
+            {
+                let on_plane = erase_box.is_none();
+                let on_grid = match erase_box {
+                    Some(bbox) => {
+                        self.renderer.update_cursor_pos(bbox);
+                        true
+                    }
+                    None if closest_plane.is_some() => {
+                        self.renderer
+                            .update_cursor_pos_on_plane(intersection_point, closest_plane);
+                        true
+                    }
+                    None => false,
+                };
+                if on_grid {
+                    let cell = self.renderer.cursor_cell();
+                    match self.ui.controls().edit_op() {
+                        EditOp::Paint => self.paint_stroke(cell, on_plane),
+                        EditOp::Clone => self.clone_stroke(cell),
+                        _ => self.smooth_stroke(cell),
+                    }
+                }
+            }
+            EditorState::Edit if self.ui.controls().edit_op() == EditOp::Select => {
+                let position = [self.cursor_position.x as f32, self.cursor_position.y as f32];
+                if self.marquee.last() != Some(&position) {
+                    self.marquee.push(position);
+                }
+                self.renderer.set_marquee(self.marquee_region().as_ref());
+            }
+            EditorState::Edit => {
+                let lock = self.drag_lock();
+                if let Some(bbox) = erase_box {
+                    match self.ui.controls().edit_op() {
+                        EditOp::Draw | EditOp::Stairs | EditOp::Shape => {
+                            self.renderer.update_draw_rectangle(draw_box.unwrap(), lock)
+                        }
+                        EditOp::Erase => self.renderer.update_draw_rectangle(bbox, lock),
+                        EditOp::Refill => self.renderer.update_draw_rectangle(bbox, lock),
+                        EditOp::Paint
+                        | EditOp::Select
+                        | EditOp::Text
+                        | EditOp::Curve
+                        | EditOp::Clone
+                        | EditOp::Smooth
+                        | EditOp::Focus => {}
+                    };
+                } else {
+                    self.renderer.update_draw_rectangle_on_plane(
+                        intersection_point,
+                        closest_plane,
+                        lock,
+                    );
+                }
+            }
+            EditorState::EditFinished => {
+                let edit_op = self.ui.controls().edit_op();
+                let applied = match edit_op {
+                    EditOp::Draw => {
+                        let c = self.ui.controls().draw_color();
+                        self.renderer
+                            .draw_rectangle([c.r, c.g, c.b, c.a], &mut self.voxel_manager)
+                    }
+                    EditOp::Erase => self.renderer.erase_rectangle(&mut self.voxel_manager),
+                    EditOp::Refill => {
+                        let c = self.ui.controls().draw_color();
+                        self.renderer
+                            .fill_rectangle([c.r, c.g, c.b, c.a], &mut self.voxel_manager)
+                    }
+                    EditOp::Paint | EditOp::Smooth => {
+                        self.stroke = None;
+                        None
+                    }
+                    EditOp::Clone => {
+                        if self.modifiers.alt() {
+                            self.clone_anchor = Some(self.renderer.cursor_cell());
+                        } else {
+                            self.remember_clone_stroke();
+                        }
+                        self.stroke = None;
+                        self.clone_offset = None;
+                        None
+                    }
+                    EditOp::Select => {
+                        let window_size = self.window.inner_size();
+                        let cells = match (self.marquee_region(), erase_box) {
+                            (Some(region), _) => selection::cells_in_region(
+                                &self.voxel_manager,
+                                &region,
+                                self.camera.model_view_mat(),
+                                self.camera.projection_mat(),
+                                [window_size.width, window_size.height],
+                            ),
+                            (None, Some(_)) => {
+                                let [x, y, z] = self.renderer.cursor_cell();
+                                analysis::component_at(
+                                    &self.voxel_manager,
+                                    [x as usize, y as usize, z as usize],
+                                )
+                            }
+                            (None, None) => Vec::new(),
+                        };
+                        self.marquee.clear();
+                        self.renderer.set_marquee(None);
+                        self.select(Selection::from_cells(cells));
+                        None
+                    }
+                    EditOp::Shape => {
+                        self.draw_shape();
+                        None
+                    }
+                    EditOp::Focus => {
+                        if erase_box.is_some() {
+                            let [x, y, z] = self.renderer.cursor_cell();
+                            self.focus = Some([x as f32 + 0.5, y as f32 + 0.5, z as f32 + 0.5]);
+                        }
+                        None
+                    }
+                    EditOp::Curve => {
+                        self.control_points.push(self.renderer.cursor_cell());
+                        self.show_control_points();
+                        None
+                    }
+                    EditOp::Text => {
+                        let c = self.ui.controls().draw_color();
+                        generator::text(
+                            &mut self.voxel_manager,
+                            self.renderer.cursor_cell(),
+                            self.active_plane,
+                            &self.ui.controls().text_options(),
+                            [c.r, c.g, c.b, c.a],
+                        );
+                        self.renderer.update_voxels(&mut self.voxel_manager);
+                        None
+                    }
+                    EditOp::Stairs => {
+                        let start = self.renderer.cursor_cell();
+                        if let Some(bbox) = self.renderer.take_draw_rectangle() {
+                            // The stairs end in the corner of the rectangle opposite to the start
+                            let min = [bbox.corner.x, bbox.corner.y, bbox.corner.z];
+                            let size = [bbox.extent.x, bbox.extent.y, bbox.extent.z];
+                            let mut end = [0; 3];
+                            for i in 0..3 {
+                                end[i] = if start[i] == min[i] as i32 {
+                                    (min[i] + size[i]) as i32 - 1
+                                } else {
+                                    min[i] as i32
+                                };
+                            }
+                            let c = self.ui.controls().draw_color();
+                            generator::stairs(
+                                &mut self.voxel_manager,
+                                start,
+                                end,
+                                self.ui.controls().stairs_options(),
+                                [c.r, c.g, c.b, c.a],
+                            );
+                            self.renderer.update_voxels(&mut self.voxel_manager);
+                        }
+                        None
+                    }
+                };
+                if let Some(bbox) = applied {
+                    self.remember_action(RepeatableAction::Box(edit_op, bbox));
+                }
+                self.history_label = Some(format!("{:?}", edit_op));
+                self.state = EditorState::ChangeView;
+            }
+        }
+        // Shapes select the cells they filled, which already made the mask of the new selection
+        let mask = self.voxel_manager.take_mask();
+        if self.mask.is_none() {
+            self.mask = mask;
+        }
+        self.voxel_manager.set_symmetry(None);
+    }
+
+    /// Paints the cells between the previous and the current position of the stroke.
+    /// Empty cells are only filled while the stroke stays on the ground planes,
+    /// so dragging from a model onto the ground doesn't fill the air in between.
+    /// Pen pressure widens the brush, which only recolors the surrounding filled cells,
+    /// or jitters the value of the painted color. Dithering interleaves the dither color.
+    fn paint_stroke(&mut self, cell: [i32; 3], on_plane: bool) {
+        let (from, fill_empty) = match self.stroke {
+            Some((last, last_on_plane)) => (last, on_plane && last_on_plane),
+            None => (cell, on_plane),
+        };
+        let draw_color = self.ui.controls().draw_color();
+        let (dither_color, pattern, amount) = self.ui.controls().dither();
+        let mapping = self.ui.controls().pressure_mapping();
+        let radius = match mapping {
+            PressureMapping::BrushSize => self.pen.brush_radius(),
+            _ => 0,
+        };
+        for line_cell in grid_line(from, cell) {
+            for brush_cell in brush_cells(line_cell, radius) {
+                let c = if pattern.uses_dither_color(brush_cell, amount) {
+                    dither_color
+                } else {
+                    draw_color
+                };
+                let color = match mapping {
+                    PressureMapping::ValueJitter => {
+                        self.pen.jitter([c.r, c.g, c.b, c.a], brush_cell)
+                    }
+                    _ => [c.r, c.g, c.b, c.a],
+                };
+                self.voxel_manager.paint_cell(
+                    brush_cell,
+                    color,
+                    fill_empty && brush_cell == line_cell,
+                );
+            }
+        }
+        self.renderer.update_voxels(&mut self.voxel_manager);
+        self.stroke = Some((cell, on_plane));
+    }
+
+    /// Copies the voxels under the brush from the same offset to the source anchor that the
+    /// stroke started at. Empty source cells leave the painted cells unchanged.
+    fn clone_stroke(&mut self, cell: [i32; 3]) {
+        let anchor = match self.clone_anchor {
+            Some(anchor) if !self.modifiers.alt() => anchor,
+            _ => return,
+        };
+        let offset = *self.clone_offset.get_or_insert([
+            anchor[0] - cell[0],
+            anchor[1] - cell[1],
+            anchor[2] - cell[2],
+        ]);
+        let from = self.stroke.map_or(cell, |(last, _)| last);
+        let radius = match self.ui.controls().pressure_mapping() {
+            PressureMapping::BrushSize => self.pen.brush_radius(),
+            _ => 0,
+        };
+        let extent = self.voxel_manager.extent() as i32;
+        for line_cell in grid_line(from, cell) {
+            for target in brush_cells(line_cell, radius) {
+                let source = [
+                    target[0] + offset[0],
+                    target[1] + offset[1],
+                    target[2] + offset[2],
+                ];
+                if source.iter().any(|c| *c < 0 || *c >= extent) {
+                    continue;
+                }
+                let value = self.voxel_manager.voxel(
+                    source[0] as usize,
+                    source[1] as usize,
+                    source[2] as usize,
+                );
+                if let Some(index) = value {
+                    let color = self.voxel_manager.palette().color(index);
+                    self.voxel_manager.paint_cell(target, color, true);
+                    if target.iter().all(|c| *c >= 0 && *c < extent) {
+                        let [x, y, z] = target;
+                        self.clone_painted
+                            .push([x as usize, y as usize, z as usize]);
+                    }
+                }
+            }
+        }
+        self.renderer.update_voxels(&mut self.voxel_manager);
+        self.stroke = Some((cell, false));
+    }
+
+    /// Smooths the colors between the previous and the current position of the stroke.
+    /// Unlike painting smoothing isn't idempotent, so a resting brush doesn't smooth again.
+    fn smooth_stroke(&mut self, cell: [i32; 3]) {
+        if self.stroke.map(|(last, _)| last) == Some(cell) {
+            return;
+        }
+        let from = self.stroke.map_or(cell, |(last, _)| last);
+        let radius = match self.ui.controls().pressure_mapping() {
+            PressureMapping::BrushSize => self.pen.brush_radius(),
+            _ => 0,
+        };
+        for line_cell in grid_line(from, cell) {
+            self.voxel_manager
+                .smooth_colors(&brush_cells(line_cell, radius));
+        }
+        self.renderer.update_voxels(&mut self.voxel_manager);
+        self.stroke = Some((cell, false));
+    }
+
+    fn redraw(&mut self) {
+        let mouse_interaction = self.renderer.render(
+            &mut self.ui,
+            #[cfg(feature = "debug_ray")]
+            &mut self.voxel_manager,
+        );
+        // Update the mouse cursor
+        self.window
+            .set_cursor_icon(iced_winit::conversion::mouse_interaction(mouse_interaction));
+    }
+
+    fn open_project(&mut self, voxel_manager: VoxelManager) -> std::io::Result<()> {
+        if voxel_manager.extent() != DEFAULT_MESH_COUNT as usize {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("unsupported grid extent {}", voxel_manager.extent()),
+            ));
+        }
+        self.voxel_manager = voxel_manager;
+        self.occlusion_layer = None;
+        self.showing_occlusion = false;
+        self.renderer.update_voxels(&mut self.voxel_manager);
+        self.show_voxel_scale();
+        self.unjournaled_extras = true;
+        Ok(())
+    }
+
+    /// Stretches the view by the size of the voxels of the scene
+    fn show_voxel_scale(&mut self) {
+        let voxel_scale = self.voxel_manager.voxel_scale();
+        self.camera.set_voxel_scale(voxel_scale);
+        self.renderer.update_view(&mut self.camera);
+        self.ui.set_voxel_scale(voxel_scale);
+    }
+
+    fn reset_history(&mut self, label: &str) {
+        self.history = History::new(&self.voxel_manager, label);
+        self.history_revision = self.voxel_manager.revision();
+        self.ui.set_history(self.history.items());
+    }
+
+    /// Adds the scene to the history when it changed since the last entry
+    fn record_history(&mut self) {
+        let label = self.history_label.take();
+        if self.voxel_manager.revision() == self.history_revision {
+            return;
+        }
+        self.history_revision = self.voxel_manager.revision();
+        let label = label.unwrap_or_else(|| "Edit".to_owned());
+        if self.history.record(&label, &self.voxel_manager) {
+            self.ui.set_history(self.history.items());
+        }
+    }
+
+    fn restore_history(&mut self, restored: std::io::Result<VoxelManager>) {
+        match restored {
+            Ok(voxel_manager) => {
+                self.voxel_manager = voxel_manager;
+                self.renderer.update_voxels(&mut self.voxel_manager);
+                self.show_voxel_scale();
+                self.unjournaled_extras = true;
+                self.history_revision = self.voxel_manager.revision();
+                self.ui.set_history(self.history.items());
+            }
+            Err(e) => println!("Failed restoring the history entry reason: {:?}", e),
+        }
+    }
+
+    fn save_project(&mut self, file_path: String) {
+        self.project_path = Some(file_path.clone());
+        self.save_export_preset();
+        self.save_masks();
+        self.save_prefabs();
+        self.save_budget();
+        // The branches off the saved state are dropped with it
+        self.history.prune_branches();
+        self.ui.set_history(self.history.items());
+        // Edits made while saving aren't part of the snapshot
+        let (voxel_manager, sub_grids) = self.scene_with_sub_grids();
+        self.unjournaled_extras = false;
+        let extras = ProjectExtras {
+            snapshots: self.snapshots.clone(),
+            markers: self.markers.clone(),
+            sub_grids,
+        };
+        self.start_task("saving", move |progress| {
+            project::save_project_with_extras(&voxel_manager, &extras, &file_path, progress)?;
+            Ok(TaskOutput::Done)
+        });
+    }
+
+    /// Saves into the opened project, appending only the changed chunks in journal mode
+    fn quick_save(&mut self) {
+        let file_path = match &self.project_path {
+            Some(file_path) => file_path.clone(),
+            None => {
+                println!("Save the project once before quick saving");
+                return;
+            }
+        };
+        // The journal only holds the chunks of the scene, not its sub-grids or other extras
+        if !self.ui.controls().journal_saves()
+            || !self.sub_grids.is_empty()
+            || self.unjournaled_extras
+        {
+            self.save_project(file_path);
+            return;
+        }
+        // A full save running in the background would discard the journal after the append
+        if let Some(running) = &self.task {
+            println!("Wait for {} to finish", running.name());
+            return;
+        }
+        let record = match journal::record(&self.voxel_manager) {
+            Some(record) => record,
+            None => {
+                println!("Nothing changed since the last save");
+                return;
+            }
+        };
+        match journal::append(&file_path, self.voxel_manager.extent(), &record) {
+            Ok(_) => {
+                self.voxel_manager.clear_unjournaled_chunks();
+                println!("Appended {} bytes to the journal", record.len());
+            }
+            Err(e) => println!("Failed appending to the journal reason: {:?}", e),
+        }
+    }
+
+    /// Starts recording to the file, or stops the recording when there is none
+    fn set_recording(&mut self, file_path: Option<String>) {
+        self.recording = None;
+        if let Some(file_path) = file_path {
+            match Recorder::create(&file_path, &self.voxel_manager) {
+                Ok(recorder) => {
+                    self.voxel_manager.clear_unrecorded_chunks();
+                    self.recording = Some(Recording {
+                        recorder,
+                        start: time::Instant::now(),
+                        revision: self.voxel_manager.revision(),
+                    })
+                }
+                Err(e) => println!("Failed recording the session reason: {:?}", e),
+            }
+        }
+        self.show_session_state();
+    }
+
+    fn capture_recording(&mut self) {
+        let recording = match &mut self.recording {
+            Some(recording) if recording.revision != self.voxel_manager.revision() => recording,
+            _ => return,
+        };
+        recording.revision = self.voxel_manager.revision();
+        let time = recording.start.elapsed().as_millis() as u32;
+        match recording.recorder.capture(&self.voxel_manager, time) {
+            Ok(_) => self.voxel_manager.clear_unrecorded_chunks(),
+            Err(e) => {
+                println!("Failed recording the session reason: {:?}", e);
+                self.set_recording(None);
+            }
+        }
+    }
+
+    /// Replaces the scene with the start of the recording, or stops the playback when there is none
+    fn set_playback(&mut self, file_path: Option<String>) {
+        if let Some(file_path) = file_path {
+            let started = Replay::load(&file_path).and_then(|replay| {
+                self.open_project(replay.scene())?;
+                // Quick saves shouldn't write the replayed scene over the opened project
+                self.project_path = None;
+                Ok(replay)
+            });
+            match started {
+                Ok(replay) => {
+                    println!(
+                        "Replaying {:.1} s of editing",
+                        replay.duration() as f32 / 1000.0
+                    );
+                    self.playback = Some(Playback {
+                        replay,
+                        position: 0.0,
+                        last_tick: time::Instant::now(),
+                    });
+                    self.step_playback();
+                }
+                Err(e) => println!("Failed replaying the session reason: {:?}", e),
+            }
+        } else if self.playback.take().is_some() {
+            self.history_label = Some("Replay".to_owned());
+        }
+        self.show_session_state();
+    }
+
+    fn step_playback(&mut self) {
+        let speed = self.ui.controls().replay_speed() as f64;
+        let playback = match &mut self.playback {
+            Some(playback) => playback,
+            None => return,
+        };
+        playback.position += playback.last_tick.elapsed().as_secs_f64() * 1000.0 * speed;
+        playback.last_tick = time::Instant::now();
+        let advanced = playback
+            .replay
+            .advance(&mut self.voxel_manager, playback.position as u32);
+        let stopped = match advanced {
+            Ok(changed) => {
+                if changed {
+                    self.renderer.update_voxels(&mut self.voxel_manager);
+                }
+                playback.replay.finished()
+            }
+            Err(e) => {
+                println!("Failed replaying the session reason: {:?}", e);
+                true
+            }
+        };
+        if stopped {
+            self.set_playback(None);
+        }
+    }
+
+    fn show_session_state(&mut self) {
+        self.ui
+            .set_session_state(self.recording.is_some(), self.playback.is_some());
+    }
+
+    /// Writes the export preset next to the project, when the project has been saved already
+    fn save_export_preset(&self) {
+        if let (Some(preset), Some(project_path)) = (&self.export_preset, &self.project_path) {
+            if let Err(e) = preset.save(project_path) {
+                println!("Failed saving the export preset reason: {:?}", e);
+            }
+        }
+    }
+
+    /// Writes the saved masks next to the project, when the project has been saved already
+    fn save_masks(&self) {
+        if let Some(project_path) = &self.project_path {
+            if let Err(e) = self.saved_masks.save(project_path) {
+                println!("Failed saving the masks reason: {:?}", e);
+            }
+        }
+    }
+
+    /// Writes the prefabs next to the project, when the project has been saved already
+    fn save_prefabs(&self) {
+        if let Some(project_path) = &self.project_path {
+            if let Err(e) = self.prefabs.save(project_path) {
+                println!("Failed saving the prefabs reason: {:?}", e);
+            }
+        }
+    }
+
+    /// Writes the budget next to the project, when the project has been saved already
+    fn save_budget(&self) {
+        if let Some(project_path) = &self.project_path {
+            if let Err(e) = self.budget.save(project_path) {
+                println!("Failed saving the budget reason: {:?}", e);
+            }
+        }
+    }
+
+    fn set_budget(&mut self, budget: Budget) {
+        self.budget = budget;
+        // Checked again with the new limits
+        self.budget_revision = None;
+        self.ui.set_budget(budget);
+    }
+
+    fn set_prefabs(&mut self, prefabs: Prefabs) {
+        self.prefabs = prefabs;
+        // Stamped again on the next check, the files may have changed while the project was closed
+        self.prefab_times.clear();
+    }
+
+    /// Stamps the prefabs again whose project files changed since they were last stamped
+    fn refresh_prefabs(&mut self) {
+        let project_path = self.project_path.clone().unwrap_or_default();
+        // The project can't be placed into itself
+        let including: Vec<_> = fs::canonicalize(&project_path).into_iter().collect();
+        self.prefab_times.resize(self.prefabs.prefabs().len(), None);
+        let mut restamped = false;
+        for index in 0..self.prefabs.prefabs().len() {
+            let path = self.prefabs.prefabs()[index].source_path(&project_path);
+            let modified = prefab::modified(&path);
+            if modified.is_none() || modified == self.prefab_times[index] {
+                continue;
+            }
+            self.prefab_times[index] = modified;
+            match prefab::load_composed(&path, &including) {
+                Ok(source) => {
+                    self.prefabs
+                        .restamp(index, &mut self.voxel_manager, &source);
+                    restamped = true;
+                }
+                Err(e) => println!("Failed loading the prefab {:?} reason: {:?}", path, e),
+            }
+        }
+        if restamped {
+            self.history_label
+                .get_or_insert_with(|| "Update prefabs".to_owned());
+            self.renderer.update_voxels(&mut self.voxel_manager);
+            self.save_prefabs();
+        }
+    }
+
+    fn apply_prefab_action(&mut self, action: PrefabAction) {
+        match action {
+            PrefabAction::Link(prefab) => {
+                let project_path = self.project_path.clone().unwrap_or_default();
+                if !prefab.source_path(&project_path).is_file() {
+                    println!("There is no project at {:?}", prefab.path);
+                    return;
+                }
+                self.history_label = Some("Link prefab".to_owned());
+                self.prefabs.add(prefab);
+                self.refresh_prefabs();
+                self.save_prefabs();
+            }
+            PrefabAction::Unlink(path) => {
+                if self.prefabs.unlink(&path) > 0 {
+                    self.prefab_times.clear();
+                    self.save_prefabs();
+                }
+            }
+        }
+    }
+
+    fn set_saved_masks(&mut self, saved_masks: SavedMasks) {
+        self.saved_masks = saved_masks;
+        self.stamped.clear();
+        self.show_mask_names();
+    }
+
+    /// Copies the saved masks onto their instances once the scene changed
+    fn sync_instances(&mut self) {
+        if self.voxel_manager.revision() == self.instances_revision {
+            return;
+        }
+        let instances = self.saved_masks.instances();
+        self.stamped.resize(instances.len(), Vec::new());
+        for (instance, stamped) in instances.iter().zip(self.stamped.iter_mut()) {
+            if let Some(mask) = self.saved_masks.get(&instance.source) {
+                *stamped =
+                    instance::stamp(&mut self.voxel_manager, &mask.cells(), instance, stamped);
+            }
+        }
+        if self.voxel_manager.revision() != self.instances_revision {
+            self.renderer.update_voxels(&mut self.voxel_manager);
+        }
+        self.instances_revision = self.voxel_manager.revision();
+    }
+
+    fn show_mask_names(&mut self) {
+        self.ui
+            .set_mask_names(self.saved_masks.names().map(str::to_owned).collect());
+    }
+
+    fn apply_mask_action(&mut self, action: MaskAction) {
+        match action {
+            MaskAction::Save(name) => {
+                if !self.saved_masks.insert(&name, self.selection.clone()) {
+                    println!("Mask names can't be empty or contain '='");
+                    return;
+                }
+                self.active_object = Some(name.trim().to_owned());
+                self.show_mask_names();
+                self.save_masks();
+            }
+            MaskAction::Recall(name, combine) => match self.saved_masks.get(&name) {
+                Some(mask) => {
+                    let mask = mask.within(self.voxel_manager.extent());
+                    let selection = self.selection.combine(&mask, combine);
+                    if combine == Combine::Replace {
+                        self.active_object = Some(name.trim().to_owned());
+                        self.set_selection(selection);
+                    } else {
+                        self.select(selection);
+                    }
+                }
+                None => println!("There is no mask named {:?}", name.trim()),
+            },
+            MaskAction::Delete(name) => {
+                if self.saved_masks.remove(&name) {
+                    if self.active_object.as_deref() == Some(name.trim()) {
+                        self.active_object = None;
+                    }
+                    self.stamped.clear();
+                    self.show_mask_names();
+                    self.save_masks();
+                }
+            }
+            MaskAction::SetPivot(name, pivot) => {
+                if !self.saved_masks.set_pivot(&name, pivot) {
+                    println!("There is no mask named {:?}", name.trim());
+                    return;
+                }
+                self.save_masks();
+                self.set_selection(self.selection.clone());
+            }
+            MaskAction::Instance(name, offset, turns) => {
+                let source = name.trim().to_owned();
+                let cells = match self.saved_masks.get(&source) {
+                    Some(mask) => mask.cells(),
+                    None => {
+                        println!("There is no mask named {:?}", source);
+                        return;
+                    }
+                };
+                let instance = Instance {
+                    source: source.clone(),
+                    offset,
+                    turns,
+                };
+                let corner = instance.corner(&cells);
+                self.history_label = Some(format!("Instance {}", source));
+                let action = RepeatableAction::Instance {
+                    source,
+                    offset,
+                    turns,
+                    corner,
+                };
+                match self.apply_action(action) {
+                    Some(applied) if !cells.is_empty() => self.remember_action(applied),
+                    _ => {}
+                }
+            }
+            MaskAction::Unlink(name) => {
+                if self.saved_masks.unlink(&name) > 0 {
+                    self.stamped.clear();
+                    self.save_masks();
+                }
+            }
+        }
+    }
+
+    fn set_markers(&mut self, markers: Markers) {
+        self.markers = markers;
+        self.show_markers();
+    }
+
+    fn show_markers(&mut self) {
+        let cells: Vec<[usize; 3]> = self.markers.iter().map(|(_, marker)| marker.cell).collect();
+        self.renderer.set_markers(&cells);
+        self.ui.set_markers(
+            self.markers
+                .iter()
+                .map(|(name, marker)| (name.to_owned(), marker.clone()))
+                .collect(),
+        );
+    }
+
+    fn set_sub_grids(&mut self, sub_grids: Vec<SubGrid>) {
+        self.sub_grids = sub_grids;
+        self.parent_scene = None;
+        self.show_sub_grids();
+    }
+
+    /// The scene and its sub-grids with the edits of the sub-grid edited in place of the scene
+    fn scene_with_sub_grids(&self) -> (VoxelManager, Vec<SubGrid>) {
+        let mut sub_grids = self.sub_grids.clone();
+        match &self.parent_scene {
+            Some((index, parent)) => {
+                sub_grids[*index].scene = self.voxel_manager.clone();
+                (parent.clone(), sub_grids)
+            }
+            None => (self.voxel_manager.clone(), sub_grids),
+        }
+    }
+
+    /// Draws the sub-grids into the scene, or the scene and the other sub-grids around the
+    /// edited sub-grid
+    fn show_sub_grids(&mut self) {
+        let mut boxes = Vec::new();
+        match &self.parent_scene {
+            None => {
+                for sub_grid in self.sub_grids.iter() {
+                    let [x, y, z] = sub_grid.corner;
+                    let size = 1.0 / sub_grid.density as f32;
+                    boxes.extend(scene_boxes(
+                        &sub_grid.scene,
+                        [x as f32, y as f32, z as f32],
+                        size,
+                    ));
+                }
+            }
+            Some((index, parent)) => {
+                let edited = &self.sub_grids[*index];
+                let density = edited.density as f32;
+                // Where a cell of the scene is in the cells of the edited sub-grid
+                let corner = |cell: [usize; 3]| {
+                    let mut corner = [0.0; 3];
+                    for i in 0..3 {
+                        corner[i] = (cell[i] as f32 - edited.corner[i] as f32) * density;
+                    }
+                    corner
+                };
+                boxes.extend(scene_boxes(parent, corner([0; 3]), density));
+                for (i, sub_grid) in self.sub_grids.iter().enumerate() {
+                    if i != *index {
+                        let size = density / sub_grid.density as f32;
+                        boxes.extend(scene_boxes(&sub_grid.scene, corner(sub_grid.corner), size));
+                    }
+                }
+            }
+        }
+        self.renderer.set_context(&boxes);
+    }
+
+    /// The corner of the selection, or the cell of the camera target without one
+    fn action_cell(&self) -> [usize; 3] {
+        let cells = self.selection.cells();
+        let target = self.camera.target();
+        let last = self.voxel_manager.extent() - 1;
+        let mut cell = [0; 3];
+        for i in 0..3 {
+            cell[i] = match cells.iter().map(|cell| cell[i]).min() {
+                Some(min) => min,
+                None => target[i].max(0.0).min(last as f32) as usize,
+            };
+        }
+        cell
+    }
+
+    /// Sub-grids are written with the project like the markers. The history starts over on
+    /// every operation since its entries hold only the edited grid.
+    fn apply_sub_grid_action(&mut self, action: SubGridAction) {
+        if action == SubGridAction::Leave {
+            match self.parent_scene.take() {
+                Some((index, parent)) => {
+                    let target = self.camera.target();
+                    let sub_grid = &mut self.sub_grids[index];
+                    let mut parent_target = [0.0; 3];
+                    for i in 0..3 {
+                        parent_target[i] =
+                            sub_grid.corner[i] as f32 + target[i] / sub_grid.density as f32;
+                    }
+                    sub_grid.scene = std::mem::replace(&mut self.voxel_manager, parent);
+                    self.show_scene("Leave sub-grid", parent_target);
+                }
+                None => println!("No sub-grid is being edited"),
+            }
+            return;
+        }
+        if self.parent_scene.is_some() {
+            println!("Leave the sub-grid first");
+            return;
+        }
+        let cell = self.action_cell();
+        match action {
+            SubGridAction::Refine(density) => {
+                let extent = self.voxel_manager.extent();
+                if density < 2 || extent % density != 0 {
+                    println!("The density has to divide the grid extent {}", extent);
+                    return;
+                }
+                let cells = extent / density;
+                // Moved back into the grid where the cube would stick out of it
+                let mut corner = [0; 3];
+                for i in 0..3 {
+                    corner[i] = cell[i].min(extent - cells);
+                }
+                if self
+                    .sub_grids
+                    .iter()
+                    .any(|sub_grid| sub_grid.overlaps(corner, cells))
+                {
+                    println!("The sub-grid would overlap another one");
+                    return;
+                }
+                if let Some(sub_grid) = SubGrid::refine(&mut self.voxel_manager, corner, density) {
+                    self.sub_grids.push(sub_grid);
+                    self.unjournaled_extras = true;
+                    self.renderer.update_voxels(&mut self.voxel_manager);
+                    self.reset_history("Refine into sub-grid");
+                    self.show_sub_grids();
+                }
+            }
+            SubGridAction::Enter => match subgrid::find(&self.sub_grids, cell) {
+                Some(index) => {
+                    let sub_grid = &self.sub_grids[index];
+                    let target = self.camera.target();
+                    let voxel_scale = self.voxel_manager.voxel_scale();
+                    let mut scene = sub_grid.scene.clone();
+                    let mut sub_grid_target = [0.0; 3];
+                    let mut sub_grid_scale = [1.0; 3];
+                    for i in 0..3 {
+                        let density = sub_grid.density as f32;
+                        sub_grid_target[i] = (target[i] - sub_grid.corner[i] as f32) * density;
+                        sub_grid_scale[i] = voxel_scale[i] / density;
+                    }
+                    scene.set_voxel_scale(sub_grid_scale);
+                    let parent = std::mem::replace(&mut self.voxel_manager, scene);
+                    self.parent_scene = Some((index, parent));
+                    self.show_scene("Edit sub-grid", sub_grid_target);
+                }
+                None => println!("There is no sub-grid at {:?}", cell),
+            },
+            SubGridAction::Flatten => match subgrid::find(&self.sub_grids, cell) {
+                Some(index) => {
+                    let sub_grid = self.sub_grids.remove(index);
+                    sub_grid.flatten(&mut self.voxel_manager);
+                    self.unjournaled_extras = true;
+                    self.renderer.update_voxels(&mut self.voxel_manager);
+                    self.reset_history("Flatten sub-grid");
+                    self.show_sub_grids();
+                }
+                None => println!("There is no sub-grid at {:?}", cell),
+            },
+            SubGridAction::Leave => (),
+        }
+    }
+
+    /// Shows the grid swapped in for the scene or a sub-grid with the camera on the target
+    fn show_scene(&mut self, label: &str, target: [f32; 3]) {
+        // The bake belongs to the scene that was replaced
+        self.occlusion_layer = None;
+        self.showing_occlusion = false;
+        self.voxel_manager.mark_all_dirty();
+        self.renderer.update_voxels(&mut self.voxel_manager);
+        self.show_voxel_scale();
+        self.camera.set_target(target);
+        self.renderer.update_view(&mut self.camera);
+        self.select(Selection::default());
+        self.reset_history(label);
+        self.show_sub_grids();
+    }
+
+    /// Markers are written with the project like the snapshots
+    fn apply_marker_action(&mut self, action: MarkerAction) {
+        match action {
+            MarkerAction::Add(name, note) => {
+                let cells = self.selection.cells();
+                let target = self.camera.target();
+                let last = self.voxel_manager.extent() - 1;
+                let mut cell = [0; 3];
+                for i in 0..3 {
+                    // The middle of the selection, or the cell of the camera target
+                    cell[i] = match cells.iter().map(|cell| cell[i]).min() {
+                        Some(min) => {
+                            (min + cells.iter().map(|cell| cell[i]).max().unwrap_or(min)) / 2
+                        }
+                        None => target[i].max(0.0).min(last as f32) as usize,
+                    };
+                }
+                let marker = Marker {
+                    cell,
+                    note: note.trim().to_owned(),
+                };
+                if !self.markers.insert(&name, marker) {
+                    println!("Name the marker first");
+                    return;
+                }
+                self.unjournaled_extras = true;
+                self.show_markers();
+            }
+            MarkerAction::Jump(name) => {
+                if let Some(marker) = self.markers.get(&name) {
+                    let [x, y, z] = marker.cell;
+                    self.camera
+                        .set_target([x as f32 + 0.5, y as f32 + 0.5, z as f32 + 0.5]);
+                    self.renderer.update_view(&mut self.camera);
+                }
+            }
+            MarkerAction::Delete(name) => {
+                if self.markers.remove(&name) {
+                    self.unjournaled_extras = true;
+                    self.show_markers();
+                }
+            }
+        }
+    }
+
+    fn set_snapshots(&mut self, snapshots: Snapshots) {
+        self.snapshots = snapshots;
+        self.show_snapshot_names();
+    }
+
+    fn show_snapshot_names(&mut self) {
+        self.ui
+            .set_snapshot_names(self.snapshots.names().map(str::to_owned).collect());
+    }
+
+    /// Snapshots are written with the project, so they are kept once it's saved
+    fn apply_snapshot_action(&mut self, action: SnapshotAction) {
+        if self.parent_scene.is_some() {
+            println!("Leave the sub-grid first, snapshots hold the whole scene");
+            return;
+        }
+        match action {
+            SnapshotAction::Save(name) => {
+                if !self.snapshots.insert(&name, &self.voxel_manager) {
+                    println!("Snapshot names can't be empty");
+                    return;
+                }
+                self.unjournaled_extras = true;
+                self.show_snapshot_names();
+            }
+            SnapshotAction::Restore(name) => match self.snapshots.restore(&name) {
+                Some(restored) => {
+                    self.history_label = Some(format!("Restore {}", name.trim()));
+                    if let Err(e) = restored.and_then(|scene| self.open_project(scene)) {
+                        println!("Failed restoring the snapshot reason: {:?}", e);
+                    }
+                }
+                None => println!("There is no snapshot named {:?}", name.trim()),
+            },
+            SnapshotAction::Diff(name) => match self.snapshots.restore(&name) {
+                Some(restored) => {
+                    match restored
+                        .and_then(|snapshot| SnapshotDiff::between(&snapshot, &self.voxel_manager))
+                    {
+                        Ok(diff) => {
+                            self.select(Selection::from_cells(diff.cells()));
+                            self.ui.set_snapshot_diff(name.trim().to_owned(), diff);
+                        }
+                        Err(e) => println!("Failed comparing the snapshot reason: {:?}", e),
+                    }
+                }
+                None => println!("There is no snapshot named {:?}", name.trim()),
+            },
+            SnapshotAction::Delete(name) => {
+                if self.snapshots.remove(&name) {
+                    self.unjournaled_extras = true;
+                    self.show_snapshot_names();
+                }
+            }
+        }
+    }
+
+    /// Exports the scene, keeping the settings as the project's preset for re-exporting
+    fn export(&mut self, preset: ExportPreset) {
+        self.export_preset = Some(preset);
+        self.save_export_preset();
+        self.reexport();
+    }
+
+    fn reexport(&mut self) {
+        let preset = match &self.export_preset {
+            Some(preset) => preset.clone(),
+            None => {
+                println!("Nothing to re-export, export the scene once first");
+                return;
+            }
+        };
+        let (voxel_manager, sub_grids) = self.scene_with_sub_grids();
+        let masks = self.saved_masks.clone();
+        self.start_task("exporting", move |progress| {
+            preset.export_with_sub_grids(&voxel_manager, &sub_grids, &masks, progress)?;
+            Ok(TaskOutput::Done)
+        });
+    }
+
+    fn start_task<F>(&mut self, name: &'static str, work: F)
+    where
+        F: FnOnce(&mut Progress) -> std::io::Result<TaskOutput> + Send + 'static,
+    {
+        if let Some(running) = &self.task {
+            println!("Wait for {} to finish", running.name());
+            return;
+        }
+        self.ui.set_progress(Some((name, 0.0)));
+        self.task = Some(Task::spawn(name, work));
+    }
+
+    fn poll_task(&mut self) {
+        let (name, events) = match &self.task {
+            Some(task) => (task.name(), task.poll()),
+            None => return,
+        };
+        for event in events {
+            match event {
+                TaskEvent::Progress(progress) => self.ui.set_progress(Some((name, progress))),
+                TaskEvent::Finished(result) => {
+                    match result.and_then(|output| self.finish_task(output)) {
+                        Err(e) => println!("Failed {} reason: {:?}", name, e),
+                        Ok(_) => println!("Finished {}", name),
+                    }
+                    self.ui.set_progress(None);
+                    self.task = None;
+                }
+            }
+        }
+    }
+
+    fn finish_task(&mut self, output: TaskOutput) -> std::io::Result<()> {
+        match output {
+            TaskOutput::Done => Ok(()),
+            TaskOutput::Project(voxel_manager, extras, file_path) => {
+                self.open_project(voxel_manager)?;
+                // The project and its journal hold every chunk of the opened scene
+                self.voxel_manager.clear_unjournaled_chunks();
+                self.unjournaled_extras = false;
+                self.export_preset = ExportPreset::load(&file_path);
+                let extent = self.voxel_manager.extent();
+                self.set_saved_masks(SavedMasks::load(&file_path, extent));
+                self.set_prefabs(Prefabs::load(&file_path, extent));
+                self.set_budget(Budget::load(&file_path));
+                self.set_snapshots(extras.snapshots);
+                self.set_markers(extras.markers);
+                self.set_sub_grids(extras.sub_grids);
+                self.project_path = Some(file_path);
+                self.reset_history("Open");
+                Ok(())
+            }
+            TaskOutput::Scene(voxel_manager) => {
+                self.open_project(voxel_manager)?;
+                self.export_preset = None;
+                self.set_saved_masks(SavedMasks::default());
+                self.set_prefabs(Prefabs::default());
+                self.set_budget(Budget::default());
+                self.set_snapshots(Snapshots::default());
+                self.set_markers(Markers::default());
+                self.set_sub_grids(Vec::new());
+                self.project_path = None;
+                self.reset_history("Import");
+                Ok(())
+            }
+        }
+    }
+
+    /// The selected cells, or every filled cell when nothing is selected
+    fn edited_cells(&self) -> Vec<[usize; 3]> {
+        if self.selection.cells().is_empty() {
+            analysis::filled_cells(&self.voxel_manager)
+                .into_iter()
+                .map(|(cell, _)| cell)
+                .collect()
+        } else {
+            self.selection.cells()
+        }
+    }
+
+    /// The edited cells with the weights operations scale their effect by, fading out around the
+    /// selection with soft selection on and all 1 otherwise
+    fn weighted_cells(&self) -> Vec<([usize; 3], f32)> {
+        match self.ui.controls().soft_selection() {
+            Some(radius) if !self.selection.is_empty() => {
+                selection::soft_weights(&self.voxel_manager, &self.selection, radius)
+            }
+            _ => self
+                .edited_cells()
+                .into_iter()
+                .map(|cell| (cell, 1.0))
+                .collect(),
+        }
+    }
+
+    /// The cell the selection turns around, when it is a saved mask with a pivot
+    fn pivot(&self) -> Option<[usize; 3]> {
+        self.saved_masks.pivot_cell(self.active_object.as_ref()?)
+    }
+
+    /// Selects cells that aren't a saved mask, unlike the selection moved or recalled by edits
+    fn select(&mut self, selection: Selection) {
+        self.active_object = None;
+        self.set_selection(selection);
+    }
+
+    fn set_selection(&mut self, selection: Selection) {
+        let cells = selection.cells();
+        let theme = self.theme.theme();
+        self.renderer.set_highlight(&cells, theme.selection);
+        self.gizmo = Gizmo::around(&cells, theme.axes);
+        // The rings go around the pivot
+        let pivot = self.pivot();
+        if let (Some(gizmo), Some(pivot)) = (&mut self.gizmo, pivot) {
+            gizmo.center = [
+                pivot[0] as f32 + 0.5,
+                pivot[1] as f32 + 0.5,
+                pivot[2] as f32 + 0.5,
+            ];
+        }
+        self.selection = selection;
+        self.mask = Mask::new(self.selection.clone(), self.mask_mode);
+    }
+
+    /// Highlights the placed points instead of the selection while there are any
+    fn show_control_points(&mut self) {
+        if self.control_points.is_empty() {
+            self.set_selection(self.selection.clone());
+            return;
+        }
+        let extent = self.voxel_manager.extent() as i32;
+        let cells: Vec<[usize; 3]> = self
+            .control_points
+            .iter()
+            .filter(|cell| cell.iter().all(|c| *c >= 0 && *c < extent))
+            .map(|[x, y, z]| [*x as usize, *y as usize, *z as usize])
+            .collect();
+        self.renderer
+            .set_highlight(&cells, self.theme.theme().selection);
+    }
+
+    fn finish_curve(&mut self) {
+        if self.control_points.is_empty() {
+            return;
+        }
+        self.history_label = Some("Curve".to_owned());
+        let (profile, radius) = self.ui.controls().curve_brush();
+        let c = self.ui.controls().draw_color();
+        self.voxel_manager.set_mask(self.mask.take());
+        self.voxel_manager
+            .set_symmetry(self.ui.controls().radial_symmetry());
+        generator::sweep(
+            &mut self.voxel_manager,
+            &self.control_points,
+            profile,
+            radius,
+            [c.r, c.g, c.b, c.a],
+        );
+        self.mask = self.voxel_manager.take_mask();
+        self.voxel_manager.set_symmetry(None);
+        self.renderer.update_voxels(&mut self.voxel_manager);
+        self.control_points.clear();
+        self.show_control_points();
+    }
+
+    /// Fills the dragged rectangle or ellipse into the layer of the active plane the drag
+    /// started on, or places the next corner of a polygon. The filled cells get selected.
+    fn draw_shape(&mut self) {
+        let (kind, outlined) = self.ui.controls().shape();
+        if kind == ShapeKind::Polygon {
+            self.renderer.cancel_draw_rectangle();
+            self.control_points.push(self.renderer.cursor_cell());
+            self.show_control_points();
+            return;
+        }
+        let start = self.renderer.cursor_cell();
+        let bbox = match self.renderer.take_draw_rectangle() {
+            Some(bbox) => bbox,
+            None => return,
+        };
+        let (right, up) = self.active_plane.plane_axes();
+        let min = [bbox.corner.x, bbox.corner.y, bbox.corner.z];
+        let size = [bbox.extent.x, bbox.extent.y, bbox.extent.z];
+        let mut origin = [min[0] as i32, min[1] as i32, min[2] as i32];
+        origin[self.active_plane.index()] = start[self.active_plane.index()];
+        let max = [size[right.index()] as i32 - 1, size[up.index()] as i32 - 1];
+        let cells = box_shape_cells(kind, [0, 0], max, outlined);
+        let c = self.ui.controls().draw_color();
+        let filled = generator::fill_plane(
+            &mut self.voxel_manager,
+            origin,
+            self.active_plane,
+            &cells,
+            [c.r, c.g, c.b, c.a],
+        );
+        self.renderer.update_voxels(&mut self.voxel_manager);
+        self.set_selection(Selection::from_cells(filled));
+    }
+
+    fn finish_polygon(&mut self) {
+        let origin = match self.control_points.first() {
+            Some(origin) => *origin,
+            None => return,
+        };
+        self.history_label = Some("Shape".to_owned());
+        let (right, up) = self.active_plane.plane_axes();
+        let corners: Vec<[i32; 2]> = self
+            .control_points
+            .iter()
+            .map(|point| {
+                [
+                    point[right.index()] - origin[right.index()],
+                    point[up.index()] - origin[up.index()],
+                ]
+            })
+            .collect();
+        let (_, outlined) = self.ui.controls().shape();
+        let c = self.ui.controls().draw_color();
+        let filled = generator::fill_plane(
+            &mut self.voxel_manager,
+            origin,
+            self.active_plane,
+            &polygon_cells(&corners, outlined),
+            [c.r, c.g, c.b, c.a],
+        );
+        self.renderer.update_voxels(&mut self.voxel_manager);
+        self.control_points.clear();
+        self.set_selection(Selection::from_cells(filled));
+    }
+
+    /// Shows the ambient occlusion bake in place of the scene or the other way around. Edits go
+    /// to the one in view, the other one is kept as it is.
+    fn swap_occlusion_layer(&mut self) {
+        let layer = match self.occlusion_layer.as_mut() {
+            Some(layer) => layer,
+            None => return,
+        };
+        std::mem::swap(&mut self.voxel_manager, layer);
+        self.showing_occlusion = !self.showing_occlusion;
+        self.voxel_manager.mark_all_dirty();
+        self.renderer.update_voxels(&mut self.voxel_manager);
+    }
+
+    /// Keeps the action for Shift+R and the macro being recorded
+    fn remember_action(&mut self, action: RepeatableAction) {
+        self.tool_macro.record(action.clone());
+        self.last_action = Some(action);
+        self.show_macro_state();
+    }
+
+    /// The filled cells among the given ones with their colors, relative to their lowest corner
+    fn pattern_voxels(
+        &self,
+        cells: &[[usize; 3]],
+    ) -> Option<([i64; 3], Vec<([i64; 3], [f32; 4])>)> {
+        let mut voxels: Vec<([i64; 3], [f32; 4])> = Vec::new();
+        let mut filled: Vec<[usize; 3]> = cells.to_vec();
+        filled.sort();
+        filled.dedup();
+        for [x, y, z] in filled {
+            if let Some(index) = self.voxel_manager.voxel(x, y, z) {
+                let color = self.voxel_manager.palette().color(index);
+                voxels.push(([x as i64, y as i64, z as i64], color));
+            }
+        }
+        let mut corner = voxels.first()?.0;
+        for (cell, _) in voxels.iter() {
+            for i in 0..3 {
+                corner[i] = corner[i].min(cell[i]);
+            }
+        }
+        for (cell, _) in voxels.iter_mut() {
+            for i in 0..3 {
+                cell[i] -= corner[i];
+            }
+        }
+        Some((corner, voxels))
+    }
+
+    /// Keeps the cells the finished clone stroke painted as the action for Shift+R
+    fn remember_clone_stroke(&mut self) {
+        let painted = std::mem::take(&mut self.clone_painted);
+        if let Some((corner, voxels)) = self.pattern_voxels(&painted) {
+            self.remember_action(RepeatableAction::Clone { corner, voxels });
+        }
     }
 
-    pub fn get_model_data(&self) -> (Vec<VoxelVertex>, Vec<u32>) {
-        self.voxel_manager.vertices()
+    /// Fills the colored cells at the corner, returns the cells that were filled
+    fn place_voxels(
+        &mut self,
+        corner: [i64; 3],
+        voxels: &[([i64; 3], [f32; 4])],
+    ) -> Vec<[usize; 3]> {
+        let extent = self.voxel_manager.extent() as i64;
+        let mut placed = Vec::new();
+        for (cell, color) in voxels {
+            let target = [
+                corner[0] + cell[0],
+                corner[1] + cell[1],
+                corner[2] + cell[2],
+            ];
+            if target.iter().any(|c| *c < 0 || *c >= extent) {
+                continue;
+            }
+            let [x, y, z] = target;
+            self.voxel_manager
+                .paint_cell([x as i32, y as i32, z as i32], *color, true);
+            let [x, y, z] = [x as usize, y as usize, z as usize];
+            if self.voxel_manager.voxel(x, y, z).is_some() {
+                placed.push([x, y, z]);
+            }
+        }
+        placed
     }
 
-    fn update(&mut self, event: winit::event::WindowEvent) {
-        // Don't change the view if we're editing the 3d canvas
-        if let event::WindowEvent::MouseInput {
-            state,
-            button: event::MouseButton::Left,
-            ..
-        } = event
-        {
-            match state {
-                event::ElementState::Pressed => {
-                    self.state = EditorState::Edit;
+    /// Applies an action through the mask and the radial symmetry, like edits with the mouse
+    fn apply_action(&mut self, action: RepeatableAction) -> Option<RepeatableAction> {
+        self.voxel_manager.set_mask(self.mask.take());
+        self.voxel_manager
+            .set_symmetry(self.ui.controls().radial_symmetry());
+        let applied = self.apply_edit(action);
+        self.mask = self.voxel_manager.take_mask();
+        self.voxel_manager.set_symmetry(None);
+        applied
+    }
+
+    /// Applies an action at its corner, None when it had nothing to apply
+    fn apply_edit(&mut self, action: RepeatableAction) -> Option<RepeatableAction> {
+        match &action {
+            RepeatableAction::Box(edit_op, bbox) => return self.apply_box(*edit_op, *bbox),
+            RepeatableAction::Array {
+                corner,
+                voxels,
+                count,
+                offset,
+                rotation,
+            } => {
+                let cells = self.place_voxels(*corner, voxels);
+                if cells.is_empty() {
+                    return None;
+                }
+                transform::array(&mut self.voxel_manager, &cells, *count, *offset, *rotation);
+            }
+            RepeatableAction::RadialArray {
+                corner,
+                voxels,
+                count,
+                axis,
+                center2,
+            } => {
+                let cells = self.place_voxels(*corner, voxels);
+                if cells.is_empty() {
+                    return None;
                 }
-                event::ElementState::Released => {
-                    self.state = EditorState::EditFinished;
+                let mut around = *center2;
+                for i in 0..3 {
+                    around[i] += 2 * corner[i];
                 }
+                transform::radial_array_around(
+                    &mut self.voxel_manager,
+                    &cells,
+                    *count,
+                    *axis,
+                    around,
+                );
             }
-        };
-        if let event::WindowEvent::KeyboardInput {
-            input:
-                event::KeyboardInput {
-                    virtual_keycode: Some(event::VirtualKeyCode::Space),
-                    state: event::ElementState::Pressed,
-                    ..
-                },
-            ..
-        } = event
-        {
-            self.ui.controls().step_edit_op();
-        };
-
-        if let event::WindowEvent::KeyboardInput {
-            input:
-                event::KeyboardInput {
-                    virtual_keycode: Some(event::VirtualKeyCode::G),
-                    state: event::ElementState::Pressed,
-                    ..
-                },
-            ..
-        } = event
-        {
-            self.renderer.toggle_render_mesh();
-        };
+            RepeatableAction::Clone { corner, voxels } => {
+                if self.place_voxels(*corner, voxels).is_empty() {
+                    return None;
+                }
+            }
+            RepeatableAction::Instance {
+                source,
+                offset,
+                turns,
+                ..
+            } => {
+                let instance = Instance {
+                    source: source.clone(),
+                    offset: *offset,
+                    turns: *turns,
+                };
+                if !self.saved_masks.add_instance(instance) {
+                    println!("There is no mask named {:?}", source);
+                    return None;
+                }
+                self.save_masks();
+                // Stamps the new instance right away
+                self.instances_revision = self.voxel_manager.revision().wrapping_sub(1);
+            }
+        }
+        Some(action)
+    }
 
-        if let event::WindowEvent::CursorMoved { position, .. } = event {
-            self.cursor_ray.from_cursor(
-                position.x as f32,
-                position.y as f32,
-                &self.camera,
-                self.window.inner_size(),
-            );
+    /// Applies a box edit, clipped where it would stick out of the grid
+    fn apply_box(&mut self, edit_op: EditOp, bbox: BoundingBox) -> Option<RepeatableAction> {
+        let cells = bbox.cell_box().clipped(self.voxel_manager.extent())?;
+        let bbox = cells.to_bounding_box(bbox.color);
+        match edit_op {
+            EditOp::Draw => self.voxel_manager.add_box(bbox),
+            EditOp::Erase => self.voxel_manager.erase_box(bbox),
+            EditOp::Refill => self.voxel_manager.refill(bbox),
+            EditOp::Paint
+            | EditOp::Select
+            | EditOp::Stairs
+            | EditOp::Text
+            | EditOp::Curve
+            | EditOp::Shape
+            | EditOp::Clone
+            | EditOp::Smooth
+            | EditOp::Focus => return None,
         }
+        Some(RepeatableAction::Box(edit_op, bbox))
+    }
 
-        if self.state == EditorState::ChangeView {
-            let viewport_changed = self.camera.update(&event);
-            if viewport_changed {
-                self.renderer.update_view(&mut self.camera);
+    /// Applies the edit tool to a box typed in by its corner cell and size
+    fn place_box(&mut self, [x, y, z]: [i64; 3], [width, height, depth]: [u32; 3]) {
+        let edit_op = self.ui.controls().edit_op();
+        if ![EditOp::Draw, EditOp::Erase, EditOp::Refill].contains(&edit_op) {
+            println!("Typed boxes are placed with the Draw, Erase and Refill tools");
+            return;
+        }
+        let c = self.ui.controls().draw_color();
+        let bbox = BoundingBox::new(
+            Vector3::new(x as f32, y as f32, z as f32),
+            Vector3::new(width as f32, height as f32, depth as f32),
+            [c.r, c.g, c.b, c.a],
+        );
+        self.history_label = Some(format!("{:?} box", edit_op));
+        match self.apply_action(RepeatableAction::Box(edit_op, bbox)) {
+            Some(applied) => {
+                self.remember_action(applied);
+                self.renderer.update_voxels(&mut self.voxel_manager);
             }
+            None => println!("The box is outside the grid"),
         }
-        #[cfg(feature = "debug_ray")]
-        self.renderer
-            .cursor_helper(Some(self.cursor_ray.origin), self.cursor_ray.end);
-
-        let (erase_box, draw_box) = self.voxel_manager.get_intersection_boxes(&self.cursor_ray);
-        #[cfg(feature = "debug_ray")]
-        let mut closest_plane_name = "None";
-        let mut closest_plane = None;
-        let mut intersection_point = Vector3::new(0.0, 0.0, 0.0);
-        let mesh_count = DEFAULT_MESH_COUNT as f32;
-        if erase_box.is_none() {
-            for plane in [XY_PLANE, YZ_PLANE, XZ_PLANE].iter() {
-                if let Some(point) = self.cursor_ray.plane_intersection(plane) {
-                    #[cfg(feature = "debug_ray")]
-                    log::debug!(
-                        "{:?} intersects with mouse world position at {:?}",
-                        plane.name,
-                        point
-                    );
-                    if point.x <= mesh_count
-                        && point.x >= 0.0
-                        && point.y <= mesh_count
-                        && point.y >= 0.0
-                        && point.z <= mesh_count
-                        && point.z >= 0.0
-                    {
-                        intersection_point = point;
+    }
 
-                        // Workaround for really small floating point coordinates can cause stuttering in the cursor movement
-                        if intersection_point.x < EPSYLON {
-                            intersection_point.x = 0.0;
-                        }
-                        if intersection_point.y < EPSYLON {
-                            intersection_point.y = 0.0;
-                        }
-                        if intersection_point.z < EPSYLON {
-                            intersection_point.z = 0.0;
-                        }
+    /// The cursor cell as the corner to apply actions at, None when the cursor is off the grid
+    fn cursor_corner(&self) -> Option<Vector3<f32>> {
+        let extent = self.voxel_manager.extent() as i32;
+        let cursor = self.renderer.cursor_cell();
+        if cursor.iter().any(|c| *c < 0 || *c >= extent) {
+            return None;
+        }
+        Some(Vector3::new(
+            cursor[0] as f32,
+            cursor[1] as f32,
+            cursor[2] as f32,
+        ))
+    }
 
-                        #[cfg(feature = "debug_ray")]
-                        {
-                            closest_plane_name = plane.name;
-                        }
-                        closest_plane = Some(plane);
-                        break;
-                    }
-                    // An aletrnative way to compute closest plane for more general cases using a dist variable
-                    /*let dist_vec = self.cursor_ray.origin - point;
-                    let dot = dist_vec.dot(dist_vec);
-                    if dot < dist {
-                        intersection_point = point;
-                        dist = dot;
-                    }*/
-                }
+    fn repeat_last_action(&mut self) {
+        let corner = match self.cursor_corner() {
+            Some(corner) => corner,
+            None => return,
+        };
+        let moved = match &self.last_action {
+            Some(action) => action.moved_to(corner),
+            None => {
+                println!(
+                    "Shift+R repeats the last box, array, clone stroke or instance, make one first"
+                );
+                return;
             }
+        };
+        self.history_label = Some(format!("Repeat {}", moved.label()));
+        if let Some(applied) = self.apply_action(moved) {
+            self.tool_macro.record(applied);
+            self.show_macro_state();
+            self.renderer.update_voxels(&mut self.voxel_manager);
         }
+    }
 
-        #[cfg(feature = "debug_ray")]
-        log::debug!(
-            "Mouse intersects {:?} plane at {:?}",
-            closest_plane_name,
-            intersection_point
-        );
-        match self.state {
-            EditorState::ChangeView => {
-                if let Some(bbox) = erase_box {
-                    match self.ui.controls().edit_op() {
-                        EditOp::Draw => self.renderer.update_cursor_pos(draw_box.unwrap()),
-                        EditOp::Erase => self.renderer.update_cursor_pos(bbox),
-                        EditOp::Refill => self.renderer.update_cursor_pos(bbox),
-                    };
-                } else {
-                    self.renderer
-                        .update_cursor_pos_on_plane(intersection_point, closest_plane);
-                }
+    fn toggle_macro_recording(&mut self) {
+        if self.tool_macro.recording {
+            self.tool_macro.recording = false;
+            println!(
+                "Recorded a macro of {} steps, play it with M",
+                self.tool_macro.steps.len()
+            );
+        } else {
+            self.tool_macro.start();
+        }
+        self.show_macro_state();
+    }
+
+    /// Replays the macro with the corner of its first step on the cursor cell
+    fn play_macro(&mut self) {
+        if self.tool_macro.recording {
+            println!("Stop recording the macro with Shift+M before playing it");
+            return;
+        }
+        if self.tool_macro.steps.is_empty() {
+            println!("Record a macro with Shift+M first");
+            return;
+        }
+        let corner = match self.cursor_corner() {
+            Some(corner) => corner,
+            None => return,
+        };
+        self.history_label = Some("Macro".to_owned());
+        for step in self.tool_macro.steps.clone() {
+            self.apply_action(step.moved_to(corner + step.corner()));
+        }
+        self.renderer.update_voxels(&mut self.voxel_manager);
+    }
+
+    fn show_macro_state(&mut self) {
+        self.ui
+            .set_macro_state(self.tool_macro.recording, self.tool_macro.steps.len());
+    }
+
+    /// Renders a full turn around the scene into a GIF. The frames go to the saving task as they
+    /// are read back, the channel only holds a few so the animation is never in memory at once.
+    fn save_turntable(
+        &mut self,
+        file_path: String,
+        turntable: Turntable,
+        depth_of_field: Option<DepthOfField>,
+        bloom: Option<Bloom>,
+    ) {
+        if let Some(running) = &self.task {
+            println!("Wait for {} to finish", running.name());
+            return;
+        }
+        let [width, height] = turntable.size;
+        let frames = turntable.frames();
+        let file = match fs::File::create(&file_path) {
+            Ok(file) => file,
+            Err(e) => {
+                println!("Failed saving the turntable reason: {:?}", e);
+                return;
             }
-            EditorState::Edit => {
-                if let Some(bbox) = erase_box {
-                    match self.ui.controls().edit_op() {
-                        EditOp::Draw => self.renderer.update_draw_rectangle(draw_box.unwrap()),
-                        EditOp::Erase => self.renderer.update_draw_rectangle(bbox),
-                        EditOp::Refill => self.renderer.update_draw_rectangle(bbox),
-                    };
-                } else {
-                    self.renderer
-                        .update_draw_rectangle_on_plane(intersection_point, closest_plane);
+        };
+        let (sender, receiver) = mpsc::sync_channel::<Vec<u8>>(2);
+        self.start_task("saving turntable", move |progress| {
+            let mut gif =
+                image::GifWriter::new(std::io::BufWriter::new(file), width, height, turntable.fps);
+            let mut written = 0;
+            for frame in receiver {
+                if !progress(written as f32 / frames as f32) {
+                    return Err(task::cancelled());
                 }
+                gif.add_frame(&frame)?;
+                written += 1;
             }
-            EditorState::EditFinished => {
-                match self.ui.controls().edit_op() {
-                    EditOp::Draw => {
-                        let c = self.ui.controls().draw_color();
-                        self.renderer
-                            .draw_rectangle([c.r, c.g, c.b, c.a], &mut self.voxel_manager)
-                    }
-                    EditOp::Erase => self.renderer.erase_rectangle(&mut self.voxel_manager),
-                    EditOp::Refill => {
-                        let c = self.ui.controls().draw_color();
-                        self.renderer
-                            .fill_rectangle([c.r, c.g, c.b, c.a], &mut self.voxel_manager)
-                    }
-                };
-                self.state = EditorState::ChangeView;
+            if written < frames {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    "reading back the turntable failed",
+                ));
+            }
+            gif.finish()?;
+            Ok(TaskOutput::Done)
+        });
+        // Sending fails once the task stopped, after being cancelled or failing to write
+        self.renderer.turntable(
+            &mut self.camera,
+            width,
+            height,
+            frames,
+            depth_of_field,
+            bloom,
+            |frame| sender.send(frame).is_ok(),
+        );
+    }
+
+    /// Makes the palette entry of the draw color glow, or stop glowing
+    fn toggle_emissive(&mut self) {
+        let c = self.ui.controls().draw_color();
+        let color = [c.r, c.g, c.b, c.a];
+        let index = match self
+            .voxel_manager
+            .palette()
+            .colors()
+            .iter()
+            .position(|entry| *entry == color)
+        {
+            Some(index) => index as u8,
+            None => {
+                println!("Draw with the color first, the palette doesn't have it yet");
+                return;
             }
+        };
+        let emissive = !self.voxel_manager.palette().is_emissive(index);
+        self.history_label = Some(if emissive { "Glow" } else { "Stop glowing" }.to_owned());
+        self.voxel_manager.set_emissive(index, emissive);
+        self.unjournaled_extras = true;
+        self.renderer.update_voxels(&mut self.voxel_manager);
+    }
+
+    /// Depth of field of screenshots and turntables when it is turned on
+    fn depth_of_field(&self) -> Option<DepthOfField> {
+        let blur = self.ui.controls().depth_of_field_blur()?;
+        Some(DepthOfField {
+            focus: self.focus.unwrap_or_else(|| self.camera.target()),
+            blur,
+        })
+    }
+
+    fn nudge_selection(&mut self, offset: [i64; 3]) {
+        self.history_label = Some("Nudge".to_owned());
+        let soft = self.ui.controls().soft_selection().is_some() && !self.selection.is_empty();
+        let moved = if soft {
+            let cells = self.weighted_cells();
+            transform::translate_weighted(&mut self.voxel_manager, &cells, offset)
+        } else {
+            transform::translate(&mut self.voxel_manager, &self.selection.cells(), offset)
+        };
+        if let Some(moved) = moved {
+            self.renderer.update_voxels(&mut self.voxel_manager);
+            self.set_selection(Selection::from_cells(moved));
         }
     }
 
-    fn redraw(&mut self) {
-        let mouse_interaction = self.renderer.render(
-            &mut self.ui,
-            #[cfg(feature = "debug_ray")]
-            &mut self.voxel_manager,
-        );
-        // Update the mouse cursor
-        self.window
-            .set_cursor_icon(iced_winit::conversion::mouse_interaction(mouse_interaction));
+    fn apply_selection_action(&mut self, action: SelectionAction) {
+        self.history_label = Some(format!("{:?} selection", action));
+        match action {
+            SelectionAction::Delete => {
+                self.voxel_manager.erase_cells(&self.selection.cells());
+                self.set_selection(Selection::default());
+            }
+            SelectionAction::Isolate => {
+                let unselected: Vec<[usize; 3]> = analysis::filled_cells(&self.voxel_manager)
+                    .into_iter()
+                    .map(|(cell, _)| cell)
+                    .filter(|cell| !self.selection.contains(*cell))
+                    .collect();
+                self.voxel_manager.erase_cells(&unselected);
+            }
+        }
+        self.renderer.update_voxels(&mut self.voxel_manager);
     }
 
-    fn save_vertices(&self, file_path: String) -> std::io::Result<()> {
-        let (vertex_data, indices) = self.get_model_data();
-        let mut buffer = BufWriter::new(File::create(&file_path)?);
-
-        buffer.write_all(b"# List of geometric vertices, with (x, y, z [,w]) coordinates, w is optional and defaults to 1.0.\n")?;
-        buffer.write_all(
-            b"# List of vertex normals in (x,y,z) form; normals might not be unit vectors.\n",
-        )?;
-
-        for vd in vertex_data.iter() {
-            buffer.write_all(
-                format!(
-                    "v {:.3} {:.3} {:.3} 1.0\n",
-                    vd.pos[0] as f32, vd.pos[1] as f32, vd.pos[2] as f32
-                )
-                .as_ref(),
-            )?;
-            buffer.write_all(
-                format!(
-                    "vn {:.3} {:.3} {:.3}\n",
-                    vd.normal[0], vd.normal[1], vd.normal[2]
-                )
-                .as_ref(),
-            )?;
-        }
-
-        buffer.write_all(b"# Polygonal face element\n")?;
-
-        for id in indices.chunks(3) {
-            buffer.write_all(
-                format!(
-                    "f {0}//{0} {1}//{1} {2}//{2}\n",
-                    id[0] + 1,
-                    id[1] + 1,
-                    id[2] + 1
-                )
-                .as_ref(),
-            )?;
-        }
-
-        buffer.flush()?;
-        Ok(())
+    /// Stores the window state and writes the settings before exiting
+    fn save_settings(&mut self) {
+        self.window_state.store(&mut self.settings);
+        if let Err(e) = self.settings.save() {
+            println!("Failed saving the settings reason: {:?}", e);
+        }
     }
 
-    pub fn init(window: winit::window::Window) -> Self {
+    pub fn init(
+        window: winit::window::Window,
+        window_state: Option<WindowState>,
+        settings: Settings,
+    ) -> Self {
         log::info!("Initializing the surface...");
 
         let (size, surface) = {
@@ -299,7 +2675,7 @@ impl Editor {
             present_mode: wgpu::PresentMode::Mailbox,
         };
         let swap_chain = device.create_swap_chain(&surface, &sc_desc);
-        let ui = Ui::new(&window, &mut device);
+        let ui = Ui::new(&window, &mut device, &settings);
 
         let mut camera = CameraWrapper::new(
             sc_desc.width as f32 / sc_desc.height as f32,
@@ -307,7 +2683,7 @@ impl Editor {
         );
 
         log::info!("Initializing the Renderer...");
-        let renderer = Renderer::init(
+        let mut renderer = Renderer::init(
             surface,
             device,
             queue,
@@ -316,20 +2692,83 @@ impl Editor {
             DEFAULT_MESH_COUNT,
             &mut camera,
         );
+        let theme = ui.controls().theme();
+        renderer.set_theme(theme.theme());
+        let voxel_manager = VoxelManager::new(DEFAULT_MESH_COUNT as usize);
+        let history = History::new(&voxel_manager, "New");
+        let history_revision = voxel_manager.revision();
         Editor {
+            window_state: WindowState::new(&window, window_state),
             window,
             renderer,
             ui,
             state: EditorState::ChangeView,
             cursor_ray: Ray::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 1.0, 1.0)),
+            cursor_position: winit::dpi::PhysicalPosition::new(-1.0, -1.0),
+            marquee: Vec::new(),
+            stroke: None,
+            pen: PenState::default(),
+            touch: TouchGestures::default(),
             camera,
-            voxel_manager: VoxelManager::new(DEFAULT_MESH_COUNT as usize),
+            voxel_manager,
+            task: None,
+            settings,
+            theme,
+            selection: Selection::default(),
+            active_plane: Axis::Y,
+            working_plane: WorkingPlane::default(),
+            working_plane_grab: None,
+            gizmo: None,
+            gizmo_grab: None,
+            held_axis: None,
+            modifiers: event::ModifiersState::default(),
+            last_action: None,
+            tool_macro: Macro::default(),
+            control_points: Vec::new(),
+            clone_anchor: None,
+            clone_offset: None,
+            clone_painted: Vec::new(),
+            focus: None,
+            mask_mode: MaskMode::default(),
+            mask: None,
+            saved_masks: SavedMasks::default(),
+            active_object: None,
+            stamped: Vec::new(),
+            instances_revision: 0,
+            prefabs: Prefabs::default(),
+            prefab_times: Vec::new(),
+            snapshots: Snapshots::default(),
+            markers: Markers::default(),
+            unjournaled_extras: false,
+            sub_grids: Vec::new(),
+            parent_scene: None,
+            budget: Budget::default(),
+            budget_revision: None,
+            history,
+            history_revision,
+            history_label: None,
+            recording: None,
+            playback: None,
+            occlusion_layer: None,
+            showing_occlusion: false,
+            project_path: None,
+            export_preset: None,
         }
     }
 
     pub fn run(mut self, event_loop: winit::event_loop::EventLoop<()>) {
         let mut last_update_inst = time::Instant::now();
         let mut fps_counter = FpsCounter::init();
+        let mut frame_timer = FrameTimer::new();
+        let mut last_stats_inst = time::Instant::now();
+        let mut last_prefab_check = time::Instant::now();
+        let mut last_budget_check = time::Instant::now();
+        let mut last_minimap_inst = time::Instant::now();
+        let mut minimap_revision = None;
+        let mut gamepad = GamepadInput::new();
+        let mut scene_stats_revision = None;
+        let mut palette_revision = None;
+        self.ui.set_history(self.history.items());
 
         log::info!("Entering render loop...");
         event_loop.run(move |event, _, control_flow| {
@@ -338,13 +2777,465 @@ impl Editor {
                     .set_title(&format!("Voxel-editor (FPS: {:?})", fps));
             }
             if let Some(file_path) = self.ui.controls().save_path() {
-                match self.save_vertices(file_path) {
-                    Err(e) => println!("Failed to save file reason: {:?}", e),
-                    Ok(_) => println!("File saved"),
-                };
+                self.export(ExportPreset {
+                    format: ExportFormat::Obj,
+                    file_path,
+                    texture_atlas: self.ui.controls().texture_atlas(),
+                    template: String::new(),
+                    crop: self.ui.controls().export_crop(),
+                    pivot: self.ui.controls().export_pivot(),
+                });
+            }
+            if let Some(file_path) = self.ui.controls().usd_save_path() {
+                self.export(ExportPreset {
+                    format: ExportFormat::Usd,
+                    file_path,
+                    texture_atlas: false,
+                    template: String::new(),
+                    crop: self.ui.controls().export_crop(),
+                    pivot: self.ui.controls().export_pivot(),
+                });
+            }
+            if let Some(file_path) = self.ui.controls().fbx_save_path() {
+                self.export(ExportPreset {
+                    format: ExportFormat::Fbx,
+                    file_path,
+                    texture_atlas: false,
+                    template: String::new(),
+                    crop: self.ui.controls().export_crop(),
+                    pivot: self.ui.controls().export_pivot(),
+                });
+            }
+            if let Some(file_path) = self.ui.controls().collada_save_path() {
+                self.export(ExportPreset {
+                    format: ExportFormat::Collada,
+                    file_path,
+                    texture_atlas: false,
+                    template: String::new(),
+                    crop: self.ui.controls().export_crop(),
+                    pivot: self.ui.controls().export_pivot(),
+                });
+            }
+            let depth_of_field = self.depth_of_field();
+            let bloom = self.ui.controls().bloom();
+            if let Some((file_path, turntable)) = self.ui.controls().turntable_request() {
+                self.save_turntable(file_path, turntable, depth_of_field, bloom);
+            }
+            self.renderer
+                .set_safe_frame(self.ui.controls().safe_frame());
+            let working_plane = Some(self.working_plane).filter(|_| self.working_plane_enabled());
+            self.renderer.set_working_plane(working_plane);
+            // Without a working plane the rulers lie on the ground
+            let ruler_plane = working_plane.unwrap_or_default();
+            let rulers = self.ui.controls().rulers().map(|step| (ruler_plane, step));
+            self.renderer.set_rulers(rulers);
+            let gizmo = self
+                .gizmo
+                .filter(|_| self.ui.controls().transform_gizmo())
+                .map(|mut gizmo| {
+                    gizmo.active = self.gizmo_grab.map(|grab| grab.handle);
+                    if gizmo.active.is_none() {
+                        gizmo.hovered = gizmo.handle_at(&self.cursor_ray);
+                    }
+                    gizmo
+                });
+            self.renderer.set_gizmo(gizmo);
+            if self.ui.controls().mask_mode() != self.mask_mode {
+                self.mask_mode = self.ui.controls().mask_mode();
+                self.mask = Mask::new(self.selection.clone(), self.mask_mode);
+            }
+            let (exposure, tone_mapping) = self.ui.controls().tone_mapping();
+            self.renderer.set_tone_mapping(exposure, tone_mapping);
+            self.renderer.set_lighting(self.ui.controls().lighting());
+            self.renderer
+                .set_shadow_catcher(self.ui.controls().shadow_catcher());
+            self.renderer.set_bloom(bloom);
+            if let Some((file_path, [width, height])) = self.ui.controls().screenshot_request() {
+                match self.renderer.screenshot(
+                    &mut self.camera,
+                    width,
+                    height,
+                    depth_of_field,
+                    bloom,
+                ) {
+                    Some(pixels) => self.start_task("saving screenshot", move |_| {
+                        image::write_png(&file_path, width, height, &pixels)?;
+                        Ok(TaskOutput::Done)
+                    }),
+                    None => println!("Failed reading back the screenshot"),
+                }
+            }
+            if let Some((file_path, stereo)) = self.ui.controls().stereo_request() {
+                let [width, height] = stereo.size;
+                match self.renderer.stereo_pair(
+                    &mut self.camera,
+                    width,
+                    height,
+                    stereo.separation,
+                    depth_of_field,
+                    bloom,
+                ) {
+                    Some([left, right]) => self.start_task("saving stereo pair", move |_| {
+                        let pixels =
+                            stereo
+                                .layout
+                                .combine(&left, &right, width as usize, height as usize);
+                        image::write_png(&file_path, stereo.layout.width(width), height, &pixels)?;
+                        Ok(TaskOutput::Done)
+                    }),
+                    None => println!("Failed reading back the stereo pair"),
+                }
+            }
+            if let Some(file_path) = self.ui.controls().vdb_save_path() {
+                self.export(ExportPreset {
+                    format: ExportFormat::Vdb,
+                    file_path,
+                    texture_atlas: false,
+                    template: String::new(),
+                    crop: self.ui.controls().export_crop(),
+                    pivot: self.ui.controls().export_pivot(),
+                });
+            }
+            if let Some((file_path, template)) = self.ui.controls().batch_save_path() {
+                self.export(ExportPreset {
+                    format: ExportFormat::Objects,
+                    file_path,
+                    texture_atlas: self.ui.controls().texture_atlas(),
+                    template,
+                    crop: self.ui.controls().export_crop(),
+                    pivot: self.ui.controls().export_pivot(),
+                });
+            }
+            if self.ui.controls().reexport_requested() {
+                self.reexport();
+            }
+            if let Some(file_path) = self.ui.controls().save_project_path() {
+                self.save_project(file_path);
+            }
+            if self.ui.controls().quick_save_requested() {
+                self.quick_save();
+            }
+            if let Some(file_path) = self.ui.controls().open_project_path() {
+                self.start_task("opening", move |progress| {
+                    let (voxel_manager, extras) =
+                        project::load_project_with_extras(&file_path, progress)?;
+                    // Quick saves append to the journal of the opened project
+                    journal::repair(&file_path, voxel_manager.extent())?;
+                    Ok(TaskOutput::Project(voxel_manager, extras, file_path))
+                });
+            }
+            if let Some((file_path, options)) = self.ui.controls().volume_import() {
+                self.start_task("importing", move |progress| {
+                    let extent = DEFAULT_MESH_COUNT as usize;
+                    let voxel_manager =
+                        volume::import_volume(&file_path, &options, extent, progress)?;
+                    Ok(TaskOutput::Scene(voxel_manager))
+                });
+            }
+            if self.ui.controls().cancel_requested() {
+                if let Some(task) = &self.task {
+                    task.cancel();
+                }
+            }
+            self.poll_task();
+            if self.ui.controls().theme() != self.theme {
+                self.theme = self.ui.controls().theme();
+                self.renderer.set_theme(self.theme.theme());
+                self.set_selection(self.selection.clone());
+                self.settings.set("viewport.theme", self.theme);
+            }
+            if let Some(color_index) = self.ui.controls().select_color() {
+                let cells = analysis::cells_with_color(&self.voxel_manager, color_index);
+                self.select(Selection::from_cells(cells));
+            }
+            if self.ui.controls().validate_requested() {
+                let report = analysis::validate(&self.voxel_manager);
+                self.select(Selection::from_cells(report.problem_cells()));
+                self.ui.set_validation(report);
+            }
+            if let Some((axis, keep_positive)) = self.ui.controls().symmetrize_requested() {
+                self.history_label = Some("Symmetrize".to_owned());
+                transform::symmetrize(&mut self.voxel_manager, axis, keep_positive);
+                self.renderer.update_voxels(&mut self.voxel_manager);
+            }
+            if let Some((corner, size)) = self.ui.controls().place_box_requested() {
+                self.place_box(corner, size);
+            }
+            if let Some((count, offset, rotate)) = self.ui.controls().array_requested() {
+                let mut cells = self.selection.cells();
+                let rotation = if rotate { Some(Axis::Y) } else { None };
+                self.history_label = Some("Array".to_owned());
+                let pattern = self.pattern_voxels(&cells);
+                let copied =
+                    transform::array(&mut self.voxel_manager, &cells, count, offset, rotation);
+                self.renderer.update_voxels(&mut self.voxel_manager);
+                if let Some((corner, voxels)) = pattern {
+                    self.remember_action(RepeatableAction::Array {
+                        corner,
+                        voxels,
+                        count,
+                        offset,
+                        rotation,
+                    });
+                }
+                cells.extend(copied);
+                self.set_selection(Selection::from_cells(cells));
+            }
+            if let Some((count, axis)) = self.ui.controls().radial_array_requested() {
+                let mut cells = self.selection.cells();
+                self.history_label = Some("Radial array".to_owned());
+                let mut center2 =
+                    transform::radial_center2(self.voxel_manager.extent(), self.pivot());
+                let pattern = self.pattern_voxels(&cells);
+                let copied = transform::radial_array_around(
+                    &mut self.voxel_manager,
+                    &cells,
+                    count,
+                    axis,
+                    center2,
+                );
+                self.renderer.update_voxels(&mut self.voxel_manager);
+                if let Some((corner, voxels)) = pattern {
+                    for i in 0..3 {
+                        center2[i] -= 2 * corner[i];
+                    }
+                    self.remember_action(RepeatableAction::RadialArray {
+                        corner,
+                        voxels,
+                        count,
+                        axis,
+                        center2,
+                    });
+                }
+                cells.extend(copied);
+                self.set_selection(Selection::from_cells(cells));
+            }
+            if let Some((light, strength)) = self.ui.controls().bake_shading_requested() {
+                let [x, y, z] = light;
+                let cells = self.edited_cells();
+                self.history_label = Some("Bake shading".to_owned());
+                shading::shade_by_orientation(
+                    &mut self.voxel_manager,
+                    &cells,
+                    Vector3::new(x, y, z),
+                    strength,
+                );
+                self.renderer.update_voxels(&mut self.voxel_manager);
+            }
+            if let Some(strength) = self.ui.controls().bake_occlusion_requested() {
+                // Bakes again start from the unbaked scene
+                if self.showing_occlusion {
+                    self.swap_occlusion_layer();
+                }
+                let cells = self.edited_cells();
+                let baked = shading::bake_ambient_occlusion(&self.voxel_manager, &cells, strength);
+                self.occlusion_layer = Some(baked);
+                self.history_label = Some("Bake occlusion".to_owned());
+                self.swap_occlusion_layer();
+            }
+            if self.ui.controls().toggle_bake_requested() {
+                if self.occlusion_layer.is_none() {
+                    println!("Bake AO first");
+                } else {
+                    let label = if self.showing_occlusion {
+                        "Hide"
+                    } else {
+                        "Show"
+                    };
+                    self.history_label = Some(format!("{} occlusion bake", label));
+                    self.swap_occlusion_layer();
+                }
+            }
+            if self.ui.controls().discard_bake_requested() {
+                if self.showing_occlusion {
+                    self.history_label = Some("Discard occlusion bake".to_owned());
+                    self.swap_occlusion_layer();
+                }
+                self.occlusion_layer = None;
+            }
+            if let Some(shift) = self.ui.controls().shift_colors_requested() {
+                let cells = self.weighted_cells();
+                self.history_label = Some("Shift colors".to_owned());
+                shading::shift_hsv(&mut self.voxel_manager, &cells, shift);
+                self.renderer.update_voxels(&mut self.voxel_manager);
+            }
+            if self.ui.controls().emissive_toggle_requested() {
+                self.toggle_emissive();
+            }
+            if let Some(action) = self.ui.controls().palette_action() {
+                match action {
+                    PaletteAction::Sort(order) => {
+                        self.history_label = Some(format!("Sort palette by {}", order));
+                        palette_tools::sort_palette(&mut self.voxel_manager, order);
+                    }
+                    PaletteAction::Merge(tolerance) => {
+                        self.history_label = Some("Merge colors".to_owned());
+                        let merged =
+                            palette_tools::merge_duplicates(&mut self.voxel_manager, tolerance);
+                        println!("Merged {} palette entries", merged);
+                    }
+                }
+                self.renderer.update_voxels(&mut self.voxel_manager);
+            }
+            if let Some(axis) = self.ui.controls().revolve_requested() {
+                self.history_label = Some("Revolve".to_owned());
+                let solid =
+                    transform::revolve(&mut self.voxel_manager, &self.selection.cells(), axis);
+                self.renderer.update_voxels(&mut self.voxel_manager);
+                self.set_selection(Selection::from_cells(solid));
+            }
+            if let Some(action) = self.ui.controls().selection_action() {
+                self.apply_selection_action(action);
+            }
+            if let Some(pivot) = self.ui.controls().recenter_requested() {
+                let cells = self.edited_cells();
+                self.history_label = Some("Recenter".to_owned());
+                if let Some(moved) = transform::recenter(&mut self.voxel_manager, &cells, pivot) {
+                    self.renderer.update_voxels(&mut self.voxel_manager);
+                    if !self.selection.is_empty() {
+                        self.set_selection(Selection::from_cells(moved));
+                    }
+                }
+            }
+            if let Some(action) = self.ui.controls().mask_action() {
+                self.apply_mask_action(action);
+            }
+            if let Some(action) = self.ui.controls().snapshot_action() {
+                self.apply_snapshot_action(action);
+            }
+            if let Some(action) = self.ui.controls().marker_action() {
+                self.apply_marker_action(action);
+            }
+            if let Some(action) = self.ui.controls().prefab_action() {
+                self.apply_prefab_action(action);
+            }
+            if let Some(voxel_scale) = self.ui.controls().voxel_scale_request() {
+                if self.parent_scene.is_some() {
+                    println!("Leave the sub-grid first, its voxels follow the size of the scene's");
+                } else {
+                    self.history_label = Some("Voxel size".to_owned());
+                    self.voxel_manager.set_voxel_scale(voxel_scale);
+                    self.unjournaled_extras = true;
+                    self.show_voxel_scale();
+                }
+            }
+            if let Some(action) = self.ui.controls().sub_grid_action() {
+                self.apply_sub_grid_action(action);
+            }
+            if let Some(budget) = self.ui.controls().budget_request() {
+                self.set_budget(budget);
+                self.save_budget();
+            }
+            if let Some([x, z]) = self.ui.controls().minimap_target() {
+                let [_, y, _] = self.camera.target();
+                self.camera.set_target([x, y, z]);
+                self.renderer.update_view(&mut self.camera);
+            }
+            if let Some(index) = self.ui.controls().history_jump() {
+                let restored = self.history.jump(index);
+                self.restore_history(restored);
+            }
+            if self.ui.controls().macro_record_requested() {
+                self.toggle_macro_recording();
+            }
+            if self.ui.controls().macro_play_requested() {
+                self.play_macro();
+            }
+            if let Some(request) = self.ui.controls().record_session() {
+                self.set_recording(request);
+            }
+            if let Some(request) = self.ui.controls().replay_session() {
+                self.set_playback(request);
+            }
+            // A gizmo drag goes into the history as one entry once the handle is let go
+            if self.state == EditorState::ChangeView
+                && self.playback.is_none()
+                && self.gizmo_grab.is_none()
+            {
+                // Prefabs and instances are stamped into the scene, not into its sub-grids
+                if self.parent_scene.is_none() {
+                    if last_prefab_check.elapsed() > time::Duration::from_secs(1) {
+                        self.refresh_prefabs();
+                        last_prefab_check = time::Instant::now();
+                    }
+                    self.sync_instances();
+                }
+                self.record_history();
+            }
+            self.capture_recording();
+            if self.ui.controls().clear_selection_requested() {
+                self.select(Selection::default());
+            }
+            if self.ui.controls().scene_stats_visible()
+                && scene_stats_revision != Some(self.voxel_manager.revision())
+            {
+                self.ui
+                    .set_scene_stats(analysis::scene_stats(&self.voxel_manager));
+                scene_stats_revision = Some(self.voxel_manager.revision());
+            }
+            if palette_revision != Some(self.voxel_manager.revision()) {
+                let entries = self.voxel_manager.palette().colors().len();
+                let unused = palette_tools::unused_entries(&self.voxel_manager);
+                self.voxel_manager.palette_mut().set_unused(unused.clone());
+                self.ui.set_palette_usage(entries, unused);
+                palette_revision = Some(self.voxel_manager.revision());
+            }
+            if self.voxel_manager.palette_mut().take_snapped() {
+                println!("The palette is full, new colors were replaced by the closest entries");
+            }
+            // Counting the objects takes a while in big scenes, so not on every edit of a stroke
+            if self.budget_revision != Some(self.voxel_manager.revision())
+                && (self.budget_revision.is_none()
+                    || last_budget_check.elapsed() > time::Duration::from_millis(500))
+            {
+                let warnings = Some(&self.budget)
+                    .filter(|budget| !budget.is_empty())
+                    .map(|budget| budget.check(&self.voxel_manager));
+                self.ui.set_budget_warnings(warnings);
+                self.budget_revision = Some(self.voxel_manager.revision());
+                last_budget_check = time::Instant::now();
             }
             match event {
                 event::Event::MainEventsCleared => {
+                    for action in gamepad.actions() {
+                        match action {
+                            GamepadAction::StepEditOp => self.ui.controls().step_edit_op(),
+                            GamepadAction::ToggleRenderMesh => self.renderer.toggle_render_mesh(),
+                        }
+                    }
+                    self.step_playback();
+                    if let Some(motion) = gamepad.camera_motion() {
+                        self.camera.orbit(motion.orbit[0], motion.orbit[1]);
+                        self.camera.pan(motion.pan[0], motion.pan[1]);
+                        self.camera.zoom(motion.zoom);
+                        self.renderer.update_view(&mut self.camera);
+                    }
+                    if self.ui.controls().stats_visible()
+                        && last_stats_inst.elapsed() > time::Duration::from_millis(250)
+                    {
+                        self.ui.set_stats(Stats {
+                            frame_times: frame_timer.frame_times(),
+                            render: self.renderer.stats(),
+                            scene_memory: self.voxel_manager.memory_usage(),
+                        });
+                        last_stats_inst = time::Instant::now();
+                    }
+                    if self.ui.controls().minimap_visible()
+                        && last_minimap_inst.elapsed() > time::Duration::from_millis(100)
+                    {
+                        if minimap_revision != Some(self.voxel_manager.revision()) {
+                            self.ui.set_minimap_columns(
+                                self.voxel_manager.extent(),
+                                analysis::top_view(&self.voxel_manager),
+                            );
+                            minimap_revision = Some(self.voxel_manager.revision());
+                        }
+                        let size = self.window.inner_size();
+                        self.ui.set_minimap_frustum(
+                            self.camera.ground_footprint([size.width, size.height]),
+                        );
+                        last_minimap_inst = time::Instant::now();
+                    }
                     if last_update_inst.elapsed() > time::Duration::from_millis(16) {
                         self.ui.update_state();
                         self.window.request_redraw();
@@ -356,7 +3247,24 @@ impl Editor {
                     ..
                 } => {
                     log::info!("Resizing to {:?}", size);
-                    self.resize(size);
+                    self.window_state
+                        .track(&self.window, &WindowEvent::Resized(size));
+                    self.resize(size, self.window.scale_factor());
+                }
+                event::Event::WindowEvent {
+                    event:
+                        WindowEvent::ScaleFactorChanged {
+                            scale_factor,
+                            new_inner_size,
+                        },
+                    ..
+                } => {
+                    log::info!(
+                        "Scale factor changed to {}, resizing to {:?}",
+                        scale_factor,
+                        new_inner_size
+                    );
+                    self.resize(*new_inner_size, scale_factor);
                 }
                 event::Event::WindowEvent { event, .. } => {
                     match event {
@@ -370,16 +3278,19 @@ impl Editor {
                             ..
                         }
                         | WindowEvent::CloseRequested => {
+                            self.save_settings();
                             *control_flow = ControlFlow::Exit;
                         }
+                        WindowEvent::Moved(_) => self.window_state.track(&self.window, &event),
                         _ => {}
                     }
-                    self.ui.update(&event, self.window.scale_factor());
+                    self.ui.update(&event);
                     self.update(event);
                 }
                 event::Event::RedrawRequested(_) => {
                     self.redraw();
                     fps_counter.incr_frame();
+                    frame_timer.frame();
                 }
                 _ => {}
             }
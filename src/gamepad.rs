@@ -0,0 +1,101 @@
+use gilrs::{Axis, Button, EventType, Gilrs};
+use std::time;
+
+// Stick deflection ignored to avoid drifting on worn sticks
+const DEAD_ZONE: f32 = 0.15;
+// Camera control units per second at full stick deflection
+const ORBIT_SPEED: f32 = 30.0;
+const PAN_SPEED: f32 = 30.0;
+const ZOOM_SPEED: f32 = 1.0;
+
+/// Editor actions bound to gamepad buttons
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GamepadAction {
+    StepEditOp,
+    ToggleRenderMesh,
+}
+
+/// Camera movement from the sticks and the d-pad since the last poll
+pub struct CameraMotion {
+    pub orbit: [f32; 2],
+    pub pan: [f32; 2],
+    pub zoom: f32,
+}
+
+pub struct GamepadInput {
+    gilrs: Option<Gilrs>,
+    last_poll: time::Instant,
+}
+
+impl GamepadInput {
+    pub fn new() -> Self {
+        let gilrs = match Gilrs::new() {
+            Ok(gilrs) => Some(gilrs),
+            Err(e) => {
+                log::warn!("Gamepad support is unavailable: {:?}", e);
+                None
+            }
+        };
+        GamepadInput {
+            gilrs,
+            last_poll: time::Instant::now(),
+        }
+    }
+
+    /// Drains the pending gamepad events, returning the actions of the pressed buttons
+    pub fn actions(&mut self) -> Vec<GamepadAction> {
+        let mut actions = Vec::new();
+        if let Some(gilrs) = &mut self.gilrs {
+            while let Some(event) = gilrs.next_event() {
+                match event.event {
+                    EventType::ButtonPressed(Button::South, _)
+                    | EventType::ButtonPressed(Button::RightTrigger, _) => {
+                        actions.push(GamepadAction::StepEditOp)
+                    }
+                    EventType::ButtonPressed(Button::West, _) => {
+                        actions.push(GamepadAction::ToggleRenderMesh)
+                    }
+                    EventType::Connected => {
+                        log::info!("Gamepad connected: {}", gilrs.gamepad(event.id).name())
+                    }
+                    _ => {}
+                }
+            }
+        }
+        actions
+    }
+
+    /// The left stick orbits, the d-pad pans and the right stick zooms the camera.
+    /// Returns None while the sticks are at rest and the d-pad is released.
+    pub fn camera_motion(&mut self) -> Option<CameraMotion> {
+        let dt = self.last_poll.elapsed().as_secs_f32();
+        self.last_poll = time::Instant::now();
+        let gilrs = self.gilrs.as_ref()?;
+        let mut motion = CameraMotion {
+            orbit: [0.0, 0.0],
+            pan: [0.0, 0.0],
+            zoom: 0.0,
+        };
+        for (_, gamepad) in gilrs.gamepads() {
+            motion.orbit[0] -= dead_zone(gamepad.value(Axis::LeftStickX)) * ORBIT_SPEED * dt;
+            motion.orbit[1] += dead_zone(gamepad.value(Axis::LeftStickY)) * ORBIT_SPEED * dt;
+            let held = |button| if gamepad.is_pressed(button) { 1.0 } else { 0.0 };
+            motion.pan[0] += (held(Button::DPadRight) - held(Button::DPadLeft)) * PAN_SPEED * dt;
+            motion.pan[1] += (held(Button::DPadUp) - held(Button::DPadDown)) * PAN_SPEED * dt;
+            motion.zoom -= dead_zone(gamepad.value(Axis::RightStickY)) * ZOOM_SPEED * dt;
+        }
+        if motion.orbit == [0.0, 0.0] && motion.pan == [0.0, 0.0] && motion.zoom == 0.0 {
+            None
+        } else {
+            Some(motion)
+        }
+    }
+}
+
+fn dead_zone(value: f32) -> f32 {
+    if value.abs() < DEAD_ZONE {
+        0.0
+    } else {
+        value
+    }
+}
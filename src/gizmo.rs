@@ -0,0 +1,294 @@
+use crate::geometry::{BoundingBox, Ray, XY_PLANE, XZ_PLANE, YZ_PLANE};
+use crate::highlight::box_vertices;
+use crate::transform::Axis;
+use crate::vertex::Vertex;
+use cgmath::Vector3;
+use std::f32::consts::PI;
+
+const ACTIVE_COLOR: [f32; 4] = [1.0, 0.8, 0.2, 1.0];
+// Sizes in cells
+const SHAFT_WIDTH: f32 = 0.15;
+const TIP_SIZE: f32 = 0.6;
+const SCALE_SIZE: f32 = 0.5;
+// Where the cubes sit on the arrows and the size of the rings, relative to the arrows
+const SCALE_DISTANCE: f32 = 0.45;
+const RING_RADIUS: f32 = 0.75;
+const RING_WIDTH: f32 = 0.25;
+const RING_SEGMENTS: usize = 48;
+// Handles are hit a bit outside of what is drawn, the shafts and rings are thin
+const PICK_MARGIN: f32 = 0.2;
+
+/// Part of the gizmo under the cursor
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GizmoHandle {
+    /// The arrow moving the selection along the axis
+    Move(Axis),
+    /// The ring turning the selection around the axis
+    Rotate(Axis),
+    /// The cube stretching the selection along the axis
+    Scale(Axis),
+}
+
+impl GizmoHandle {
+    pub fn axis(self) -> Axis {
+        match self {
+            GizmoHandle::Move(axis) | GizmoHandle::Rotate(axis) | GizmoHandle::Scale(axis) => axis,
+        }
+    }
+}
+
+/// A handle being dragged
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GizmoGrab {
+    pub handle: GizmoHandle,
+    /// Coordinate of the handle where it was grabbed
+    pub grabbed: f32,
+    /// Whole steps the selection was moved, turned or stretched by since
+    pub applied: i64,
+    /// Length of the selection along the axis when it was grabbed
+    pub length: usize,
+}
+
+impl GizmoGrab {
+    /// Whole steps the handle was dragged by to the coordinate
+    pub fn steps(&self, coordinate: f32) -> i64 {
+        (coordinate - self.grabbed).round() as i64
+    }
+}
+
+/// Arrows, rings and cubes around the selection to move, turn and stretch it with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gizmo {
+    pub center: [f32; 3],
+    /// Length of the arrows from the center in cells
+    pub size: f32,
+    /// Colors of the x, y and z handles
+    pub colors: [[f32; 4]; 3],
+    pub hovered: Option<GizmoHandle>,
+    /// The handle being dragged
+    pub active: Option<GizmoHandle>,
+}
+
+fn centered_box(center: [f32; 3], size: [f32; 3], color: [f32; 4]) -> BoundingBox {
+    BoundingBox::new(
+        Vector3::new(
+            center[0] - size[0] / 2.0,
+            center[1] - size[1] / 2.0,
+            center[2] - size[2] / 2.0,
+        ),
+        Vector3::new(size[0], size[1], size[2]),
+        color,
+    )
+}
+
+/// The axes a positive quarter turn around the axis takes the first to the second of, as the
+/// transforms turn cells
+fn turn_axes(axis: Axis) -> (usize, usize) {
+    match axis {
+        Axis::X => (1, 2),
+        Axis::Y => (2, 0),
+        Axis::Z => (0, 1),
+    }
+}
+
+impl Gizmo {
+    /// The gizmo in the middle of the cells, reaching out of them. None without cells.
+    pub fn around(cells: &[[usize; 3]], colors: [[f32; 4]; 3]) -> Option<Self> {
+        let mut center = [0.0; 3];
+        let mut size: f32 = 0.0;
+        for i in 0..3 {
+            let min = cells.iter().map(|cell| cell[i]).min()? as f32;
+            let max = cells.iter().map(|cell| cell[i]).max()? as f32 + 1.0;
+            center[i] = (min + max) / 2.0;
+            size = size.max((max - min) / 2.0);
+        }
+        Some(Gizmo {
+            center,
+            size: size + 2.0,
+            colors,
+            hovered: None,
+            active: None,
+        })
+    }
+
+    fn color(&self, handle: GizmoHandle) -> [f32; 4] {
+        let [r, g, b, _] = self.colors[handle.axis().index()];
+        if self.active == Some(handle) {
+            ACTIVE_COLOR
+        } else if self.hovered == Some(handle) {
+            [(r + 1.0) / 2.0, (g + 1.0) / 2.0, (b + 1.0) / 2.0, 1.0]
+        } else {
+            [r, g, b, 0.8]
+        }
+    }
+
+    /// The center moved along the axis
+    fn along(&self, axis: Axis, distance: f32) -> [f32; 3] {
+        let mut point = self.center;
+        point[axis.index()] += distance;
+        point
+    }
+
+    /// The boxes the handles are made of, grown by the margin, a handle can have several
+    fn handles(&self, margin: f32) -> Vec<(GizmoHandle, BoundingBox)> {
+        let mut boxes = Vec::new();
+        for axis in Axis::ALL.iter() {
+            let i = axis.index();
+            let handle = GizmoHandle::Move(*axis);
+            let color = self.color(handle);
+            let mut shaft = [SHAFT_WIDTH + 2.0 * margin; 3];
+            shaft[i] = self.size;
+            boxes.push((
+                handle,
+                centered_box(self.along(*axis, self.size / 2.0), shaft, color),
+            ));
+            // The tip narrows towards the end
+            for step in 0..3 {
+                let width = TIP_SIZE * (3 - step) as f32 / 3.0 + 2.0 * margin;
+                let mut tip = [width; 3];
+                tip[i] = TIP_SIZE / 3.0;
+                let distance = self.size + TIP_SIZE * (step as f32 + 0.5) / 3.0;
+                boxes.push((
+                    handle,
+                    centered_box(self.along(*axis, distance), tip, color),
+                ));
+            }
+
+            let handle = GizmoHandle::Scale(*axis);
+            boxes.push((
+                handle,
+                centered_box(
+                    self.along(*axis, self.size * SCALE_DISTANCE),
+                    [SCALE_SIZE + 2.0 * margin; 3],
+                    self.color(handle),
+                ),
+            ));
+
+            let handle = GizmoHandle::Rotate(*axis);
+            let color = self.color(handle);
+            let (a, b) = turn_axes(*axis);
+            let radius = self.size * RING_RADIUS;
+            for segment in 0..RING_SEGMENTS {
+                let angle = segment as f32 / RING_SEGMENTS as f32 * 2.0 * PI;
+                let mut center = self.center;
+                center[a] += radius * angle.cos();
+                center[b] += radius * angle.sin();
+                boxes.push((
+                    handle,
+                    centered_box(center, [RING_WIDTH + 2.0 * margin; 3], color),
+                ));
+            }
+        }
+        boxes
+    }
+
+    pub fn vertices(&self) -> (Vec<Vertex>, Vec<u32>) {
+        let boxes = self
+            .handles(0.0)
+            .into_iter()
+            .map(|(_, bbox)| bbox)
+            .collect::<Vec<_>>();
+        box_vertices(&boxes)
+    }
+
+    /// The handle closest to the ray origin the ray passes through
+    pub fn handle_at(&self, ray: &Ray) -> Option<GizmoHandle> {
+        self.handles(PICK_MARGIN)
+            .into_iter()
+            .filter_map(|(handle, bbox)| Some((ray.box_distance(&bbox)?, handle)))
+            .fold(
+                None,
+                |closest: Option<(f32, GizmoHandle)>, hit| match closest {
+                    Some((closest_dist, _)) if closest_dist <= hit.0 => closest,
+                    _ => Some(hit),
+                },
+            )
+            .map(|(_, handle)| handle)
+    }
+
+    /// Where the ray points on the handle, the coordinate along the axis for the arrows and cubes
+    /// and the angle around it in quarter turns for the rings. None when the ray runs along the
+    /// line or plane of the handle.
+    pub fn handle_coordinate(&self, handle: GizmoHandle, ray: &Ray) -> Option<f32> {
+        let [x, y, z] = self.center;
+        let i = handle.axis().index();
+        match handle {
+            GizmoHandle::Move(axis) | GizmoHandle::Scale(axis) => {
+                let mut direction = Vector3::new(0.0, 0.0, 0.0);
+                direction[axis.index()] = 1.0;
+                let t = ray.line_coordinate(Vector3::new(x, y, z), direction)?;
+                // Along the grid rather than from the center, which moves with the selection
+                Some(self.center[i] + t)
+            }
+            GizmoHandle::Rotate(axis) => {
+                let plane = match axis {
+                    Axis::X => YZ_PLANE,
+                    Axis::Y => XZ_PLANE,
+                    Axis::Z => XY_PLANE,
+                };
+                let (point, _) = ray.plane_intersection(&plane.shifted(self.center[i]))?;
+                let (a, b) = turn_axes(axis);
+                let angle = (point[b] - self.center[b]).atan2(point[a] - self.center[a]);
+                Some(angle / (PI / 2.0))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn handles_are_hit_and_measured_along_their_axes() {
+        let cells = [[4, 4, 4], [5, 4, 4], [6, 4, 4]];
+        let gizmo = Gizmo::around(&cells, [[1.0; 4]; 3]).unwrap();
+        assert_eq!(gizmo.center, [5.5, 4.5, 4.5]);
+        assert_eq!(gizmo.size, 3.5);
+        assert_eq!(Gizmo::around(&[], [[1.0; 4]; 3]), None);
+
+        // Looking down on the tip of the x arrow
+        let down = Ray::new(Vector3::new(9.5, 30.0, 4.5), Vector3::new(9.5, -30.0, 4.5));
+        let handle = gizmo.handle_at(&down).unwrap();
+        assert_eq!(handle, GizmoHandle::Move(Axis::X));
+        let grab = GizmoGrab {
+            handle,
+            grabbed: gizmo.handle_coordinate(handle, &down).unwrap(),
+            applied: 0,
+            length: 3,
+        };
+        let dragged = Ray::new(
+            Vector3::new(11.6, 30.0, 4.5),
+            Vector3::new(11.6, -30.0, 4.5),
+        );
+        assert_eq!(
+            grab.steps(gizmo.handle_coordinate(handle, &dragged).unwrap()),
+            2
+        );
+
+        // From above and the front, straight down the rings of y and z would be in the way
+        let scale = Ray::new(
+            Vector3::new(7.0, 30.0, 30.0),
+            Vector3::new(7.0, -21.0, -21.0),
+        );
+        assert_eq!(gizmo.handle_at(&scale), Some(GizmoHandle::Scale(Axis::X)));
+
+        // A quarter of the way around the ring of the y axis, from between +z and +x to between
+        // +x and -z
+        let offset = 2.625 * (PI / 4.0).sin();
+        let ring = Ray::new(
+            Vector3::new(5.5 + offset, 30.0, 4.5 + offset),
+            Vector3::new(5.5 + offset, -30.0, 4.5 + offset),
+        );
+        let handle = gizmo.handle_at(&ring).unwrap();
+        assert_eq!(handle, GizmoHandle::Rotate(Axis::Y));
+        let turned = Ray::new(
+            Vector3::new(5.5 + offset, 30.0, 4.5 - offset),
+            Vector3::new(5.5 + offset, -30.0, 4.5 - offset),
+        );
+        let start = gizmo.handle_coordinate(handle, &ring).unwrap();
+        let end = gizmo.handle_coordinate(handle, &turned).unwrap();
+        assert!((end - start - 1.0).abs() < 0.001);
+    }
+}
@@ -0,0 +1,262 @@
+use crate::analysis::{neighbour, FACE_OFFSETS};
+use crate::geometry::BoundingBox;
+use crate::vertex::Vertex;
+use crate::voxel_manager::VoxelManager;
+use iced_wgpu::wgpu;
+use std::mem;
+
+// Triangles of a box in the vertex order of BoundingBox::vertices
+const BOX_INDICES: [u32; 36] = [
+    0, 1, 2, 2, 3, 0, 4, 5, 6, 6, 7, 4, 8, 9, 10, 10, 11, 8, 12, 13, 14, 14, 15, 12, 16, 17, 18,
+    18, 19, 16, 20, 21, 22, 22, 23, 20,
+];
+// How much the faces are darkened in the order of BoundingBox::vertices, lit from above
+const FACE_SHADES: [f32; 6] = [0.8, 0.5, 0.7, 1.0, 0.7, 0.8];
+
+/// Triangles of the boxes in their own colors
+pub fn box_vertices(boxes: &[BoundingBox]) -> (Vec<Vertex>, Vec<u32>) {
+    let mut vertex_data = Vec::with_capacity(boxes.len() * 24);
+    let mut index_data = Vec::with_capacity(boxes.len() * BOX_INDICES.len());
+    for bbox in boxes {
+        let base = vertex_data.len() as u32;
+        vertex_data.extend(bbox.vertices());
+        index_data.extend(BOX_INDICES.iter().map(|i| base + i));
+    }
+    (vertex_data, index_data)
+}
+
+/// Triangles of the boxes with every face darkened by its direction, so the boxes keep their
+/// shape without lighting
+pub fn shaded_box_vertices(boxes: &[BoundingBox]) -> (Vec<Vertex>, Vec<u32>) {
+    let (_, index_data) = box_vertices(boxes);
+    let mut vertex_data = Vec::with_capacity(boxes.len() * 24);
+    for bbox in boxes {
+        for (face, shade) in FACE_SHADES.iter().enumerate() {
+            let mut shaded = *bbox;
+            for channel in shaded.color.iter_mut().take(3) {
+                *channel *= shade;
+            }
+            vertex_data.extend(shaded.vertices().into_iter().skip(face * 4).take(4));
+        }
+    }
+    (vertex_data, index_data)
+}
+
+/// Boxes over the voxels of a scene with an exposed face, its cells scaled to size and its
+/// grid corner moved to corner
+pub fn scene_boxes(scene: &VoxelManager, corner: [f32; 3], size: f32) -> Vec<BoundingBox> {
+    let extent = scene.extent();
+    let mut boxes = Vec::new();
+    for x in 0..extent {
+        for y in 0..extent {
+            for z in 0..extent {
+                let value = match scene.voxel(x, y, z) {
+                    Some(value) => value,
+                    None => continue,
+                };
+                let hidden =
+                    FACE_OFFSETS
+                        .iter()
+                        .all(|offset| match neighbour(extent, [x, y, z], *offset) {
+                            Some([nx, ny, nz]) => scene.voxel(nx, ny, nz).is_some(),
+                            None => false,
+                        });
+                if hidden {
+                    continue;
+                }
+                boxes.push(BoundingBox::new(
+                    cgmath::Vector3::new(
+                        corner[0] + x as f32 * size,
+                        corner[1] + y as f32 * size,
+                        corner[2] + z as f32 * size,
+                    ),
+                    cgmath::Vector3::new(size, size, size),
+                    scene.palette().color(value),
+                ));
+            }
+        }
+    }
+    boxes
+}
+
+/// Translucent boxes over the given cells, drawn on top of the scene
+pub fn highlight_vertices(cells: &[[usize; 3]], color: [f32; 4]) -> (Vec<Vertex>, Vec<u32>) {
+    let boxes = cells
+        .iter()
+        .map(|cell| {
+            BoundingBox::new(
+                cgmath::Vector3::new(cell[0] as f32, cell[1] as f32, cell[2] as f32),
+                cgmath::Vector3::new(1.0, 1.0, 1.0),
+                color,
+            )
+        })
+        .collect::<Vec<_>>();
+    box_vertices(&boxes)
+}
+
+/// Pins standing on the given cells, a thin stem with a cube on top
+pub fn marker_vertices(cells: &[[usize; 3]], color: [f32; 4]) -> (Vec<Vertex>, Vec<u32>) {
+    // Sizes in cells
+    const STEM_WIDTH: f32 = 0.15;
+    const STEM_HEIGHT: f32 = 2.0;
+    const HEAD_SIZE: f32 = 0.6;
+    let mut boxes = Vec::with_capacity(cells.len() * 2);
+    for cell in cells {
+        let [x, y, z] = [cell[0] as f32 + 0.5, cell[1] as f32, cell[2] as f32 + 0.5];
+        boxes.push(BoundingBox::new(
+            cgmath::Vector3::new(x - STEM_WIDTH / 2.0, y, z - STEM_WIDTH / 2.0),
+            cgmath::Vector3::new(STEM_WIDTH, STEM_HEIGHT, STEM_WIDTH),
+            color,
+        ));
+        boxes.push(BoundingBox::new(
+            cgmath::Vector3::new(x - HEAD_SIZE / 2.0, y + STEM_HEIGHT, z - HEAD_SIZE / 2.0),
+            cgmath::Vector3::new(HEAD_SIZE, HEAD_SIZE, HEAD_SIZE),
+            color,
+        ));
+    }
+    box_vertices(&boxes)
+}
+
+/// Same as the cursor pipeline, but with 32 bit indices to fit any number of cells
+pub fn build_highlight_pipeline(
+    device: &wgpu::Device,
+    uniform_buf: &wgpu::Buffer,
+    uniform_buf_size: u64,
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+) -> (wgpu::BindGroup, wgpu::RenderPipeline) {
+    build_box_pipeline(
+        device,
+        uniform_buf,
+        uniform_buf_size,
+        format,
+        sample_count,
+        None,
+    )
+}
+
+/// Same as the highlight pipeline, but hidden behind the voxels like the scene itself
+pub fn build_depth_tested_pipeline(
+    device: &wgpu::Device,
+    uniform_buf: &wgpu::Buffer,
+    uniform_buf_size: u64,
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+    depth_format: wgpu::TextureFormat,
+) -> (wgpu::BindGroup, wgpu::RenderPipeline) {
+    build_box_pipeline(
+        device,
+        uniform_buf,
+        uniform_buf_size,
+        format,
+        sample_count,
+        Some(depth_format),
+    )
+}
+
+fn build_box_pipeline(
+    device: &wgpu::Device,
+    uniform_buf: &wgpu::Buffer,
+    uniform_buf_size: u64,
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+    depth_format: Option<wgpu::TextureFormat>,
+) -> (wgpu::BindGroup, wgpu::RenderPipeline) {
+    let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: None,
+        bindings: &[wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStage::VERTEX,
+            ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+        }],
+    });
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        bind_group_layouts: &[&bind_group_layout],
+    });
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &bind_group_layout,
+        bindings: &[wgpu::Binding {
+            binding: 0,
+            resource: wgpu::BindingResource::Buffer {
+                buffer: uniform_buf,
+                range: 0..uniform_buf_size,
+            },
+        }],
+        label: None,
+    });
+
+    let vs = include_bytes!("../shaders/mesh.vert.spv");
+    let fs = include_bytes!("../shaders/mesh.frag.spv");
+
+    let vs_module =
+        device.create_shader_module(&wgpu::read_spirv(std::io::Cursor::new(&vs[..])).unwrap());
+
+    let fs_module =
+        device.create_shader_module(&wgpu::read_spirv(std::io::Cursor::new(&fs[..])).unwrap());
+
+    let blend = wgpu::BlendDescriptor {
+        src_factor: wgpu::BlendFactor::SrcAlpha,
+        dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+        operation: wgpu::BlendOperation::Add,
+    };
+    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        layout: &pipeline_layout,
+        vertex_stage: wgpu::ProgrammableStageDescriptor {
+            module: &vs_module,
+            entry_point: "main",
+        },
+        fragment_stage: Some(wgpu::ProgrammableStageDescriptor {
+            module: &fs_module,
+            entry_point: "main",
+        }),
+        rasterization_state: Some(wgpu::RasterizationStateDescriptor {
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: wgpu::CullMode::None,
+            depth_bias: 0,
+            depth_bias_slope_scale: 0.0,
+            depth_bias_clamp: 0.0,
+        }),
+        primitive_topology: wgpu::PrimitiveTopology::TriangleList,
+        color_states: &[wgpu::ColorStateDescriptor {
+            format,
+            color_blend: blend.clone(),
+            alpha_blend: blend,
+            write_mask: wgpu::ColorWrite::ALL,
+        }],
+        depth_stencil_state: depth_format.map(|format| wgpu::DepthStencilStateDescriptor {
+            format,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil_front: wgpu::StencilStateFaceDescriptor::IGNORE,
+            stencil_back: wgpu::StencilStateFaceDescriptor::IGNORE,
+            stencil_read_mask: 0,
+            stencil_write_mask: 0,
+        }),
+        vertex_state: wgpu::VertexStateDescriptor {
+            index_format: wgpu::IndexFormat::Uint32,
+            vertex_buffers: &[wgpu::VertexBufferDescriptor {
+                stride: mem::size_of::<Vertex>() as wgpu::BufferAddress,
+                step_mode: wgpu::InputStepMode::Vertex,
+                attributes: &[
+                    // Position
+                    wgpu::VertexAttributeDescriptor {
+                        format: wgpu::VertexFormat::Float3,
+                        offset: 0,
+                        shader_location: 0,
+                    },
+                    // Color
+                    wgpu::VertexAttributeDescriptor {
+                        format: wgpu::VertexFormat::Float4,
+                        offset: 3 * 4,
+                        shader_location: 1,
+                    },
+                ],
+            }],
+        },
+        sample_count,
+        sample_mask: !0,
+        alpha_to_coverage_enabled: false,
+    });
+
+    (bind_group, pipeline)
+}
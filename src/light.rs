@@ -2,14 +2,125 @@ use bytemuck::{Pod, Zeroable};
 use cgmath::{EuclideanSpace, Matrix4, Ortho, Point3, Vector3};
 use iced_wgpu::wgpu;
 
+/// How the lit colors are brought into the displayable range
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToneMapping {
+    /// Clips everything brighter than white
+    Clamp,
+    Reinhard,
+    /// The filmic curve of the Academy Color Encoding System
+    Aces,
+}
+
+impl ToneMapping {
+    pub const ALL: [ToneMapping; 3] =
+        [ToneMapping::Clamp, ToneMapping::Reinhard, ToneMapping::Aces];
+}
+
+impl Default for ToneMapping {
+    fn default() -> ToneMapping {
+        ToneMapping::Clamp
+    }
+}
+
+/// Light setups for consistent renders, chosen in one click
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LightingPreset {
+    /// The light the editor always had, over a sky blue background
+    Default,
+    /// A key light from the front left with a bright fill over neutral gray
+    Studio,
+    /// Soft light from above with a lot of ambient
+    Overcast,
+    /// A warm light low from the side over a dusky background
+    Sunset,
+}
+
+impl LightingPreset {
+    pub const ALL: [LightingPreset; 4] = [
+        LightingPreset::Default,
+        LightingPreset::Studio,
+        LightingPreset::Overcast,
+        LightingPreset::Sunset,
+    ];
+
+    /// Where the light shines from in grid sizes, its color and intensity, and how much of the
+    /// voxel color stays lit in the shadows
+    fn rig(self) -> ([f32; 3], [f32; 3], f32) {
+        match self {
+            LightingPreset::Default => ([0.5, 1.5, 2.0], [1.0, 1.0, 1.0], 0.2),
+            LightingPreset::Studio => ([-0.8, 1.4, 1.6], [1.1, 1.06, 1.0], 0.35),
+            LightingPreset::Overcast => ([0.2, 3.0, 0.4], [0.75, 0.78, 0.82], 0.5),
+            LightingPreset::Sunset => ([2.5, 0.6, 1.0], [1.3, 0.65, 0.35], 0.15),
+        }
+    }
+
+    /// Clear color of the viewport, in linear light
+    pub fn background(self) -> wgpu::Color {
+        let [r, g, b] = match self {
+            LightingPreset::Default => [0.0, 0.8, 1.0],
+            LightingPreset::Studio => [0.18, 0.18, 0.18],
+            LightingPreset::Overcast => [0.45, 0.5, 0.55],
+            LightingPreset::Sunset => [0.35, 0.12, 0.08],
+        };
+        wgpu::Color { r, g, b, a: 1.0 }
+    }
+}
+
+impl Default for LightingPreset {
+    fn default() -> LightingPreset {
+        LightingPreset::Default
+    }
+}
+
 pub struct Light {
     pos: Point3<f32>,
     color: wgpu::Color,
+    /// Share of the voxel color lit without the light reaching it
+    ambient: f32,
+    preset: LightingPreset,
+    /// Exposure compensation in stops
+    exposure: f32,
+    tone_mapping: ToneMapping,
 }
 
 impl Light {
     pub fn new(pos: Point3<f32>, color: wgpu::Color) -> Self {
-        Light { pos, color }
+        Light {
+            pos,
+            color,
+            ambient: LightingPreset::default().rig().2,
+            preset: LightingPreset::default(),
+            exposure: 0.0,
+            tone_mapping: ToneMapping::default(),
+        }
+    }
+
+    /// Changes the exposure and the tone mapping, returns whether either changed
+    pub fn set_tone(&mut self, exposure: f32, tone_mapping: ToneMapping) -> bool {
+        let changed = exposure != self.exposure || tone_mapping != self.tone_mapping;
+        self.exposure = exposure;
+        self.tone_mapping = tone_mapping;
+        changed
+    }
+
+    /// Sets up the light of the preset for a grid of mesh_count cells, returns whether the
+    /// preset changed
+    pub fn set_preset(&mut self, preset: LightingPreset, mesh_count: f32) -> bool {
+        if preset == self.preset {
+            return false;
+        }
+        let ([x, y, z], [r, g, b], ambient) = preset.rig();
+        self.pos = Point3::new(x * mesh_count, y * mesh_count, z * mesh_count);
+        self.color = wgpu::Color {
+            r: r as f64,
+            g: g as f64,
+            b: b as f64,
+            a: 1.0,
+        };
+        self.ambient = ambient;
+        self.preset = preset;
+        true
     }
 }
 
@@ -17,8 +128,11 @@ impl Light {
 #[derive(Clone, Copy)]
 pub struct LightRaw {
     direction: [f32; 4],
+    // The color of the light and the ambient share of the voxel shader
     color: [f32; 4],
     proj: [[f32; 4]; 4],
+    // The exposure scale and the tone mapping operator of the voxel shader
+    tone: [f32; 4],
 }
 
 unsafe impl Pod for LightRaw {}
@@ -44,9 +158,15 @@ impl Light {
                 self.color.r as f32,
                 self.color.g as f32,
                 self.color.b as f32,
-                1.0,
+                self.ambient,
             ],
             proj: *mx_view_proj.as_ref(),
+            tone: [
+                self.exposure.exp2(),
+                self.tone_mapping as u32 as f32,
+                0.0,
+                0.0,
+            ],
         }
     }
 }
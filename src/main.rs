@@ -1,30 +1,71 @@
+mod bloom;
 mod camera;
-mod color;
 mod controls;
+mod depth_of_field;
+mod dialog;
 mod editor;
 mod fps;
-mod geometry;
+mod gamepad;
+mod gizmo;
+mod highlight;
 mod light;
+mod marquee;
+mod pen;
+mod picking;
 mod renderer;
+mod ruler;
+mod safe_frame;
+mod screenshot;
+mod stats;
+mod stereo;
+mod touch;
 mod ui;
-mod vertex;
-mod voxel_manager;
+#[cfg(not(target_arch = "wasm32"))]
+mod watch;
+mod window_state;
+mod working_plane;
+
+// The scene model, meshing and file formats live in the core library
+use voxel_editor_core::{
+    analysis, budget, color, export, font, generator, geometry, history, image, instance, journal,
+    marker, palette, palette_tools, prefab, project, recording, selection, settings, shading,
+    snapshot, subgrid, task, theme, transform, vertex, view, volume, voxel_manager,
+};
 
 use editor::Editor;
+use settings::Settings;
+use window_state::WindowState;
 use winit::event_loop::EventLoop;
 
 fn run(title: &str) {
     let event_loop = EventLoop::new();
-    let mut builder = winit::window::WindowBuilder::new();
-    builder = builder
-        .with_title(title)
-        .with_inner_size(winit::dpi::LogicalSize::new(1280, 720));
+    let settings = Settings::load();
+    let window_state = WindowState::load(&settings);
+    let builder = WindowState::window_builder(window_state.as_ref()).with_title(title);
     let window = builder.build(&event_loop).unwrap();
+    if let Some(state) = &window_state {
+        state.restore_position(&window, event_loop.available_monitors());
+    }
+    #[cfg(not(target_arch = "wasm32"))]
     env_logger::init();
-    let editor = Editor::init(window);
+    let editor = Editor::init(window, window_state, settings);
     editor.run(event_loop)
 }
 
 fn main() {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let args: Vec<String> = std::env::args().skip(1).collect();
+        if args.first().map(String::as_str) == Some("watch") {
+            let result = watch::parse_args(&args[1..]).and_then(|options| {
+                watch::run(options).map_err(|e| format!("watching failed: {:?}", e))
+            });
+            if let Err(e) = result {
+                eprintln!("{}", e);
+                std::process::exit(2);
+            }
+            return;
+        }
+    }
     run("Voxel-editor");
 }
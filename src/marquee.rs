@@ -0,0 +1,43 @@
+use crate::selection::ScreenRegion;
+use crate::vertex::{vertex, Vertex};
+
+const OUTLINE_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.8];
+// Width in pixels
+const LINE_WIDTH: f32 = 1.5;
+
+/// Corners of the region in pixels, in order around it
+fn outline(region: &ScreenRegion) -> Vec<[f32; 2]> {
+    match region {
+        ScreenRegion::Rectangle(a, b) => vec![*a, [b[0], a[1]], *b, [a[0], b[1]]],
+        ScreenRegion::Lasso(path) => path.clone(),
+    }
+}
+
+/// Screen space triangles outlining the region, in normalized device coordinates
+pub fn marquee_vertices(region: &ScreenRegion, window_size: [u32; 2]) -> (Vec<Vertex>, Vec<u32>) {
+    let [width, height] = [window_size[0].max(1) as f32, window_size[1].max(1) as f32];
+    let ndc = |[x, y]: [f32; 2]| [x / width * 2.0 - 1.0, 1.0 - y / height * 2.0, 0.0];
+    let corners = outline(region);
+    let mut vertices = Vec::with_capacity(corners.len() * 4);
+    let mut indices = Vec::with_capacity(corners.len() * 6);
+    for (i, a) in corners.iter().enumerate() {
+        let b = corners[(i + 1) % corners.len()];
+        let [dx, dy] = [b[0] - a[0], b[1] - a[1]];
+        let length = (dx * dx + dy * dy).sqrt();
+        if length == 0.0 {
+            continue;
+        }
+        // Across the segment, half the line width to either side
+        let [nx, ny] = [
+            -dy / length * LINE_WIDTH / 2.0,
+            dx / length * LINE_WIDTH / 2.0,
+        ];
+        let base = vertices.len() as u32;
+        vertices.push(vertex(ndc([a[0] + nx, a[1] + ny]), OUTLINE_COLOR));
+        vertices.push(vertex(ndc([b[0] + nx, b[1] + ny]), OUTLINE_COLOR));
+        vertices.push(vertex(ndc([b[0] - nx, b[1] - ny]), OUTLINE_COLOR));
+        vertices.push(vertex(ndc([a[0] - nx, a[1] - ny]), OUTLINE_COLOR));
+        indices.extend([0, 1, 2, 2, 3, 0].iter().map(|i| base + i));
+    }
+    (vertices, indices)
+}
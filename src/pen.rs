@@ -0,0 +1,155 @@
+use winit::event::{Force, TouchPhase, WindowEvent};
+
+/// Largest brush radius in cells, reached at full pressure
+const MAX_BRUSH_RADIUS: f32 = 3.0;
+/// Largest brightness change of value jitter, reached at full pressure
+const MAX_JITTER: f32 = 0.3;
+// Jittered colors are quantized so a stroke doesn't use up the palette
+const JITTER_LEVELS: f32 = 8.0;
+
+// Ordered dithering thresholds, scaled to the 0..1 range
+#[rustfmt::skip]
+const BAYER: [[u8; 4]; 4] = [
+    [0, 8, 2, 10],
+    [12, 4, 14, 6],
+    [3, 11, 1, 9],
+    [15, 7, 13, 5],
+];
+
+/// How the paint brush interleaves the draw color with the dither color
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DitherPattern {
+    Off,
+    Checker,
+    /// Ordered dithering, the dither amount sets the share of the dither color
+    Bayer,
+}
+
+impl DitherPattern {
+    pub const ALL: [DitherPattern; 3] = [
+        DitherPattern::Off,
+        DitherPattern::Checker,
+        DitherPattern::Bayer,
+    ];
+
+    /// Whether the cell gets the dither color instead of the draw color
+    pub fn uses_dither_color(self, cell: [i32; 3], amount: f32) -> bool {
+        match self {
+            DitherPattern::Off => false,
+            DitherPattern::Checker => (cell[0] + cell[1] + cell[2]).rem_euclid(2) == 1,
+            DitherPattern::Bayer => {
+                // Shift the pattern on every layer, so neighbours differ along each axis
+                let row = (cell[0] + cell[2]).rem_euclid(4) as usize;
+                let column = (cell[1] + cell[2]).rem_euclid(4) as usize;
+                (BAYER[row][column] as f32 + 0.5) / 16.0 < amount
+            }
+        }
+    }
+}
+
+impl Default for DitherPattern {
+    fn default() -> DitherPattern {
+        DitherPattern::Off
+    }
+}
+
+/// Pressure and tilt of the pen, for devices and backends reporting them
+#[derive(Default)]
+pub struct PenState {
+    pressure: Option<f32>,
+    // Angle between the pen and the tablet surface in radians, PI/2 is perpendicular
+    altitude: Option<f32>,
+}
+
+impl PenState {
+    pub fn update(&mut self, event: &WindowEvent) {
+        match event {
+            WindowEvent::Touch(touch) => match touch.phase {
+                TouchPhase::Started | TouchPhase::Moved => {
+                    self.pressure = touch.force.map(|force| force.normalized() as f32);
+                    self.altitude = match touch.force {
+                        Some(Force::Calibrated {
+                            altitude_angle: Some(angle),
+                            ..
+                        }) => Some(angle as f32),
+                        _ => None,
+                    };
+                }
+                TouchPhase::Ended | TouchPhase::Cancelled => *self = PenState::default(),
+            },
+            WindowEvent::TouchpadPressure { pressure, .. } => self.pressure = Some(*pressure),
+            _ => {}
+        }
+    }
+
+    /// Pressure in the 0..1 range, full pressure is assumed for a mouse
+    fn pressure(&self) -> f32 {
+        self.pressure.unwrap_or(1.0).max(0.0).min(1.0)
+    }
+
+    /// Brush radius from the pressure, tilting the pen widens the brush like a pencil
+    pub fn brush_radius(&self) -> i32 {
+        if self.pressure.is_none() {
+            return 0;
+        }
+        let tilt = self
+            .altitude
+            .map_or(0.0, |angle| 1.0 - angle / std::f32::consts::FRAC_PI_2);
+        (self.pressure() * MAX_BRUSH_RADIUS * (1.0 + tilt.max(0.0))).round() as i32
+    }
+
+    /// Randomly darkens or lightens a color per cell, more with higher pressure
+    pub fn jitter(&self, color: [f32; 4], cell: [i32; 3]) -> [f32; 4] {
+        let noise = (cell_hash(cell) * JITTER_LEVELS).floor() / JITTER_LEVELS - 0.5;
+        let value = 1.0 + noise * 2.0 * MAX_JITTER * self.pressure();
+        [
+            (color[0] * value).min(1.0),
+            (color[1] * value).min(1.0),
+            (color[2] * value).min(1.0),
+            color[3],
+        ]
+    }
+}
+
+// Deterministic pseudo random value in the 0..1 range
+fn cell_hash(cell: [i32; 3]) -> f32 {
+    let mut h = (cell[0] as u32).wrapping_mul(73_856_093)
+        ^ (cell[1] as u32).wrapping_mul(19_349_663)
+        ^ (cell[2] as u32).wrapping_mul(83_492_791);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0x5bd1_e995);
+    h ^= h >> 15;
+    (h % 1024) as f32 / 1024.0
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn dither_patterns_pick_the_dither_color() {
+        let checker = |cell| DitherPattern::Checker.uses_dither_color(cell, 0.0);
+        assert!(!checker([0, 0, 0]));
+        assert!(checker([1, 0, 0]));
+        assert!(checker([0, 0, -1]));
+        assert!(!checker([1, 1, 0]));
+        assert!(!DitherPattern::Off.uses_dither_color([1, 0, 0], 1.0));
+
+        // Thresholds run from 1/32 to 31/32, amounts of 0 and 1 dither none and all of the cells
+        let cells: Vec<[i32; 3]> = (0..4)
+            .flat_map(|x| (0..4).map(move |y| [x, y, 0]))
+            .collect();
+        let dithered = |amount| {
+            cells
+                .iter()
+                .filter(|cell| DitherPattern::Bayer.uses_dither_color(**cell, amount))
+                .count()
+        };
+        assert_eq!(dithered(0.0), 0);
+        assert_eq!(dithered(1.0), 16);
+        assert_eq!(dithered(0.5), 8);
+        assert!(DitherPattern::Bayer.uses_dither_color([0, 0, 0], 0.05));
+        assert!(!DitherPattern::Bayer.uses_dither_color([1, 0, 0], 0.05));
+    }
+}
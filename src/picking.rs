@@ -0,0 +1,289 @@
+use crate::vertex::{VoxelInstance, VoxelVertex};
+use crate::voxel_manager::Pick;
+use futures::executor::block_on;
+use iced_wgpu::wgpu;
+use std::mem;
+
+const PICK_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg32Uint;
+const PICK_DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+// Rows copied out of a texture have to be aligned to 256 bytes
+const READBACK_ROW_SIZE: u32 = 256;
+const PIXEL_SIZE: u64 = 2 * mem::size_of::<u32>() as u64;
+// Voxel coordinates are packed into 10 bits per axis by the pick shader
+const MAX_PICK_EXTENT: usize = 1024;
+
+/// Normals of the cube faces in the order the pick shader numbers them
+pub const FACE_NORMALS: [[i32; 3]; 6] = [
+    [1, 0, 0],
+    [-1, 0, 0],
+    [0, 1, 0],
+    [0, -1, 0],
+    [0, 0, 1],
+    [0, 0, -1],
+];
+
+/// Decodes the value the pick shader wrote for a pixel, zero means no voxel was hit
+fn decode(id: u32, face: u32) -> Option<Pick> {
+    if id == 0 || face as usize >= FACE_NORMALS.len() {
+        return None;
+    }
+    let cell = id as usize - 1;
+    Some(Pick {
+        voxel: [
+            cell % MAX_PICK_EXTENT,
+            cell / MAX_PICK_EXTENT % MAX_PICK_EXTENT,
+            cell / (MAX_PICK_EXTENT * MAX_PICK_EXTENT),
+        ],
+        normal: FACE_NORMALS[face as usize],
+    })
+}
+
+/// Offscreen target the pick pipeline renders the voxel ids into
+pub struct PickTarget {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    depth_view: wgpu::TextureView,
+    readback_buf: wgpu::Buffer,
+    width: u32,
+    height: u32,
+}
+
+impl PickTarget {
+    pub fn new(device: &wgpu::Device, width: u32, height: u32) -> Self {
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            size,
+            array_layer_count: 1,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: PICK_FORMAT,
+            usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT | wgpu::TextureUsage::COPY_SRC,
+            label: Some("PickBuffer"),
+        });
+        let depth_view = device
+            .create_texture(&wgpu::TextureDescriptor {
+                size,
+                array_layer_count: 1,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: PICK_DEPTH_FORMAT,
+                usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT,
+                label: Some("PickDepthBuffer"),
+            })
+            .create_default_view();
+        let readback_buf = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: READBACK_ROW_SIZE as u64,
+            usage: wgpu::BufferUsage::MAP_READ | wgpu::BufferUsage::COPY_DST,
+        });
+        PickTarget {
+            view: texture.create_default_view(),
+            texture,
+            depth_view,
+            readback_buf,
+            width,
+            height,
+        }
+    }
+
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        x < self.width && y < self.height
+    }
+
+    /// Bytes used by the id and depth textures
+    pub fn memory(&self) -> u64 {
+        (self.width * self.height) as u64 * (PIXEL_SIZE + 4)
+    }
+
+    pub fn begin_pass<'a>(&'a self, encoder: &'a mut wgpu::CommandEncoder) -> wgpu::RenderPass<'a> {
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                attachment: &self.view,
+                resolve_target: None,
+                load_op: wgpu::LoadOp::Clear,
+                store_op: wgpu::StoreOp::Store,
+                clear_color: wgpu::Color::TRANSPARENT,
+            }],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachmentDescriptor {
+                attachment: &self.depth_view,
+                depth_load_op: wgpu::LoadOp::Clear,
+                depth_store_op: wgpu::StoreOp::Store,
+                stencil_load_op: wgpu::LoadOp::Clear,
+                stencil_store_op: wgpu::StoreOp::Store,
+                clear_depth: 1.0,
+                clear_stencil: 0,
+            }),
+        })
+    }
+
+    /// Copies the pixel at (x, y) into the readback buffer
+    pub fn copy_pixel(&self, encoder: &mut wgpu::CommandEncoder, x: u32, y: u32) {
+        encoder.copy_texture_to_buffer(
+            wgpu::TextureCopyView {
+                texture: &self.texture,
+                mip_level: 0,
+                array_layer: 0,
+                origin: wgpu::Origin3d { x, y, z: 0 },
+            },
+            wgpu::BufferCopyView {
+                buffer: &self.readback_buf,
+                offset: 0,
+                bytes_per_row: READBACK_ROW_SIZE,
+                rows_per_image: 1,
+            },
+            wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth: 1,
+            },
+        );
+    }
+
+    /// Waits for the copy submitted after copy_pixel and decodes the picked voxel
+    pub fn read_pixel(&self, device: &wgpu::Device) -> Option<Pick> {
+        let mapping = self.readback_buf.map_read(0, PIXEL_SIZE);
+        device.poll(wgpu::Maintain::Wait);
+        let mapping = block_on(mapping).ok()?;
+        let pixel: &[u32] = bytemuck::cast_slice(mapping.as_slice());
+        decode(pixel[0], pixel[1])
+    }
+}
+
+pub fn build_pick_pipeline(
+    device: &wgpu::Device,
+    uniform_buf: &wgpu::Buffer,
+    uniform_buf_size: u64,
+) -> (wgpu::BindGroup, wgpu::RenderPipeline) {
+    let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: None,
+        bindings: &[wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStage::VERTEX,
+            ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+        }],
+    });
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        bind_group_layouts: &[&bind_group_layout],
+    });
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &bind_group_layout,
+        bindings: &[wgpu::Binding {
+            binding: 0,
+            resource: wgpu::BindingResource::Buffer {
+                buffer: uniform_buf,
+                range: 0..uniform_buf_size,
+            },
+        }],
+        label: None,
+    });
+
+    let vs = include_bytes!("../shaders/pick.vert.spv");
+    let fs = include_bytes!("../shaders/pick.frag.spv");
+
+    let vs_module =
+        device.create_shader_module(&wgpu::read_spirv(std::io::Cursor::new(&vs[..])).unwrap());
+
+    let fs_module =
+        device.create_shader_module(&wgpu::read_spirv(std::io::Cursor::new(&fs[..])).unwrap());
+
+    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        layout: &pipeline_layout,
+        vertex_stage: wgpu::ProgrammableStageDescriptor {
+            module: &vs_module,
+            entry_point: "main",
+        },
+        fragment_stage: Some(wgpu::ProgrammableStageDescriptor {
+            module: &fs_module,
+            entry_point: "main",
+        }),
+        rasterization_state: Some(wgpu::RasterizationStateDescriptor {
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: wgpu::CullMode::Back,
+            depth_bias: 0,
+            depth_bias_slope_scale: 0.0,
+            depth_bias_clamp: 0.0,
+        }),
+        primitive_topology: wgpu::PrimitiveTopology::TriangleList,
+        color_states: &[wgpu::ColorStateDescriptor {
+            format: PICK_FORMAT,
+            color_blend: wgpu::BlendDescriptor::REPLACE,
+            alpha_blend: wgpu::BlendDescriptor::REPLACE,
+            write_mask: wgpu::ColorWrite::ALL,
+        }],
+        depth_stencil_state: Some(wgpu::DepthStencilStateDescriptor {
+            format: PICK_DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil_front: wgpu::StencilStateFaceDescriptor::IGNORE,
+            stencil_back: wgpu::StencilStateFaceDescriptor::IGNORE,
+            stencil_read_mask: 0,
+            stencil_write_mask: 0,
+        }),
+        vertex_state: wgpu::VertexStateDescriptor {
+            index_format: wgpu::IndexFormat::Uint16,
+            vertex_buffers: &[
+                wgpu::VertexBufferDescriptor {
+                    stride: mem::size_of::<VoxelVertex>() as wgpu::BufferAddress,
+                    step_mode: wgpu::InputStepMode::Vertex,
+                    attributes: &[
+                        // Position
+                        wgpu::VertexAttributeDescriptor {
+                            format: wgpu::VertexFormat::Float3,
+                            offset: 0,
+                            shader_location: 0,
+                        },
+                        // Normal
+                        wgpu::VertexAttributeDescriptor {
+                            format: wgpu::VertexFormat::Float3,
+                            offset: 3 * 4,
+                            shader_location: 1,
+                        },
+                    ],
+                },
+                wgpu::VertexBufferDescriptor {
+                    stride: mem::size_of::<VoxelInstance>() as wgpu::BufferAddress,
+                    step_mode: wgpu::InputStepMode::Instance,
+                    attributes: &[
+                        // Offset
+                        wgpu::VertexAttributeDescriptor {
+                            format: wgpu::VertexFormat::Float3,
+                            offset: 0,
+                            shader_location: 2,
+                        },
+                    ],
+                },
+            ],
+        },
+        sample_count: 1,
+        sample_mask: !0,
+        alpha_to_coverage_enabled: false,
+    });
+
+    (bind_group, pipeline)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn decodes_shader_ids() {
+        assert_eq!(decode(0, 0), None);
+        let (x, y, z) = (3, 17, 31);
+        let id = 1 + x + 1024 * (y + 1024 * z);
+        assert_eq!(
+            decode(id, 3),
+            Some(Pick {
+                voxel: [x as usize, y as usize, z as usize],
+                normal: [0, -1, 0],
+            })
+        );
+    }
+}
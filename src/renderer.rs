@@ -1,20 +1,45 @@
+use crate::bloom::{build_emission_pipeline, Bloom, EmissionTarget, ViewportBloom};
 use crate::camera::CameraWrapper;
 use crate::color::*;
+use crate::depth_of_field::{build_distance_pipeline, DepthOfField, DistanceTarget};
 use crate::geometry::*;
+use crate::gizmo::Gizmo;
+use crate::highlight::{
+    build_depth_tested_pipeline, build_highlight_pipeline, highlight_vertices, marker_vertices,
+    shaded_box_vertices,
+};
 use crate::light::*;
+use crate::marquee::marquee_vertices;
+use crate::palette::PALETTE_SIZE;
+use crate::picking::{build_pick_pipeline, PickTarget};
+use crate::ruler::ruler_vertices;
+use crate::safe_frame::safe_frame_vertices;
+use crate::screenshot::ScreenshotTarget;
+use crate::selection::ScreenRegion;
+use crate::stats::RenderStats;
+use crate::theme::{Theme, ThemeKind};
 use crate::ui::{build_ui_pipeline, Ui};
 use crate::vertex::*;
-use crate::voxel_manager::VoxelManager;
+use crate::voxel_manager::{Pick, VoxelManager, CHUNK_SIZE};
+use crate::working_plane::WorkingPlane;
 use cgmath;
 use iced_wgpu::wgpu;
 use iced_winit::mouse::Interaction;
+use std::ops::Range;
 use std::rc::Rc;
+use std::time;
 
 pub const DEFAULT_MESH_COUNT: u16 = 32;
 const SAMPLE_COUNT: u32 = 4;
+// Every chunk owns a fixed slot in the instance buffer big enough to hold all of its voxels
+const CHUNK_CAPACITY: u32 = (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as u32;
 
 const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
 const SHADOW_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+const SHADOW_MAP_SIZE: u32 = 2048;
+const MARKER_COLOR: [f32; 4] = [1.0, 0.35, 0.6, 0.9];
+// The palette colors followed by their emission
+const PALETTE_UNIFORM_SIZE: u64 = (2 * PALETTE_SIZE * std::mem::size_of::<[f32; 4]>()) as u64;
 
 fn create_texture_view(
     device: &wgpu::Device,
@@ -59,11 +84,20 @@ struct Pipeline {
     instance_buf: Option<Rc<wgpu::Buffer>>,
     index_buf: Rc<wgpu::Buffer>,
     index_count: usize,
-    instance_count: usize,
+    // One draw call is issued for every non-empty range
+    instance_ranges: Vec<Range<u32>>,
+    // False for line lists, which don't count towards the triangle statistics
+    triangle_list: bool,
 }
 
 impl Pipeline {
-    fn draw<'a>(&'a mut self, render_pass: &mut wgpu::RenderPass<'a>) {
+    fn has_instances(&self) -> bool {
+        self.instance_ranges
+            .iter()
+            .any(|range| range.start < range.end)
+    }
+
+    fn draw<'a>(&'a mut self, render_pass: &mut wgpu::RenderPass<'a>, stats: &mut RenderStats) {
         render_pass.set_pipeline(&self.pipeline);
         render_pass.set_bind_group(0, &self.bind_group, &[]);
         render_pass.set_index_buffer(&self.index_buf, 0, 0);
@@ -71,7 +105,16 @@ impl Pipeline {
         if let Some(ref instance_buf) = self.instance_buf {
             render_pass.set_vertex_buffer(1, instance_buf, 0, 0);
         }
-        render_pass.draw_indexed(0..self.index_count as u32, 0, 0..self.instance_count as u32);
+        for range in self.instance_ranges.iter() {
+            if range.start < range.end {
+                render_pass.draw_indexed(0..self.index_count as u32, 0, range.clone());
+                stats.draw_calls += 1;
+                if self.triangle_list {
+                    stats.triangles +=
+                        (self.index_count / 3) as u64 * (range.end - range.start) as u64;
+                }
+            }
+        }
     }
 }
 
@@ -85,20 +128,50 @@ pub struct Renderer {
     multisampled_framebuffer: wgpu::TextureView,
     mvp_buf: wgpu::Buffer,
     light_uniform_buf: wgpu::Buffer,
+    palette_uniform_buf: wgpu::Buffer,
     command_buffers: Vec<wgpu::CommandBuffer>,
     mesh_pipeline: Pipeline,
     render_cursor: bool,
     render_mesh: bool,
     cursor_pipeline: Pipeline,
+    highlight_pipeline: Pipeline,
+    safe_frame_pipeline: Pipeline,
+    safe_frame: Option<f32>,
+    marquee_pipeline: Pipeline,
+    working_plane_pipeline: Pipeline,
+    working_plane: Option<WorkingPlane>,
+    gizmo_pipeline: Pipeline,
+    gizmo: Option<Gizmo>,
+    marker_pipeline: Pipeline,
+    ruler_pipeline: Pipeline,
+    // The plane the rulers lie in and the cells between their labels
+    rulers: Option<(WorkingPlane, usize)>,
+    // The scenes around the edited grid, solid and hidden behind the voxels
+    context_pipeline: Pipeline,
     voxel_pipeline: Pipeline,
+    // A ground below the grid in screenshots, transparent but for the shadow falling on it
+    catcher_pipeline: Pipeline,
     shadow_pipeline: Pipeline,
     shadow_view: wgpu::TextureView,
+    pick_pipeline: Pipeline,
+    pick_target: PickTarget,
+    distance_pipeline: Pipeline,
+    emission_pipeline: Pipeline,
+    viewport_bloom: ViewportBloom,
+    bloom: Option<Bloom>,
+    // Whether any palette entry is emissive, the viewport skips the bloom passes without one
+    has_emission: bool,
     ui_pipeline: wgpu::RenderPipeline,
     cursor_cube: BoundingBox,
+    cursor_color: [f32; 4],
+    theme: Theme,
     draw_cube: Option<BoundingBox>,
     mesh_count: u16,
     light: Light,
     lights_are_dirty: bool,
+    // Clear colors skip the shaders and are given in linear light
+    background: wgpu::Color,
+    stats: RenderStats,
 }
 
 impl Renderer {
@@ -117,7 +190,8 @@ impl Renderer {
         let vertex_size = mem::size_of::<Vertex>();
 
         //****************************** Setting up mesh pipeline ******************************
-        let (vertex_data, mesh_index_data) = generate_mesh_vertices(mesh_count);
+        let theme = ThemeKind::default().theme();
+        let (vertex_data, mesh_index_data) = generate_mesh_vertices(mesh_count, &theme);
 
         let vertex_buf_mesh = device.create_buffer_with_data(
             bytemuck::cast_slice(&vertex_data),
@@ -337,7 +411,8 @@ impl Renderer {
         });
 
         //****************************** Setting up voxel pipeline ******************************
-        let mc = mesh_count as u64;
+        let chunks_per_axis = (mesh_count as usize + CHUNK_SIZE - 1) / CHUNK_SIZE;
+        let chunk_count = (chunks_per_axis * chunks_per_axis * chunks_per_axis) as u64;
         let origin_cube = BoundingBox::new(
             cgmath::Vector3::new(0.0, 0.0, 0.0),
             cgmath::Vector3::new(1.0, 1.0, 1.0),
@@ -354,9 +429,9 @@ impl Renderer {
 
         let instance_buf_voxel = Rc::new(device.create_buffer(&wgpu::BufferDescriptor {
             label: None,
-            // We can have a maximum number of mc * mc * mc voxel
+            // Each chunk has a slot for CHUNK_CAPACITY voxels
             // Each voxel has one instance data
-            size: mc * mc * mc * mem::size_of::<VoxelInstance>() as u64,
+            size: chunk_count * CHUNK_CAPACITY as u64 * mem::size_of::<VoxelInstance>() as u64,
             usage: wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
         }));
 
@@ -366,6 +441,12 @@ impl Renderer {
             usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
         });
 
+        let palette_uniform_buf = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: PALETTE_UNIFORM_SIZE,
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        });
+
         let shadow_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
             address_mode_u: wgpu::AddressMode::ClampToEdge,
             address_mode_v: wgpu::AddressMode::ClampToEdge,
@@ -380,8 +461,8 @@ impl Renderer {
 
         let shadow_texture = device.create_texture(&wgpu::TextureDescriptor {
             size: wgpu::Extent3d {
-                width: 2048,
-                height: 2048,
+                width: SHADOW_MAP_SIZE,
+                height: SHADOW_MAP_SIZE,
                 depth: 1,
             },
             array_layer_count: 1,
@@ -422,6 +503,11 @@ impl Renderer {
                     visibility: wgpu::ShaderStage::FRAGMENT,
                     ty: wgpu::BindingType::Sampler { comparison: true },
                 },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4, // palette
+                    visibility: wgpu::ShaderStage::VERTEX,
+                    ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+                },
             ],
         });
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
@@ -429,34 +515,46 @@ impl Renderer {
         });
 
         // Create bind group
-        let voxel_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &bind_group_layout,
-            bindings: &[
-                wgpu::Binding {
-                    binding: 0,
-                    resource: wgpu::BindingResource::Buffer {
-                        buffer: &uniform_buf,
-                        range: 0..uniform_buf_size,
+        // The shadow catcher samples the same lights and shadow map as the voxels
+        let create_voxel_bind_group = || {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &bind_group_layout,
+                bindings: &[
+                    wgpu::Binding {
+                        binding: 0,
+                        resource: wgpu::BindingResource::Buffer {
+                            buffer: &uniform_buf,
+                            range: 0..uniform_buf_size,
+                        },
                     },
-                },
-                wgpu::Binding {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Buffer {
-                        buffer: &light_uniform_buf,
-                        range: 0..mem::size_of::<LightRaw>() as u64,
+                    wgpu::Binding {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Buffer {
+                            buffer: &light_uniform_buf,
+                            range: 0..mem::size_of::<LightRaw>() as u64,
+                        },
                     },
-                },
-                wgpu::Binding {
-                    binding: 2,
-                    resource: wgpu::BindingResource::TextureView(&shadow_view),
-                },
-                wgpu::Binding {
-                    binding: 3,
-                    resource: wgpu::BindingResource::Sampler(&shadow_sampler),
-                },
-            ],
-            label: None,
-        });
+                    wgpu::Binding {
+                        binding: 2,
+                        resource: wgpu::BindingResource::TextureView(&shadow_view),
+                    },
+                    wgpu::Binding {
+                        binding: 3,
+                        resource: wgpu::BindingResource::Sampler(&shadow_sampler),
+                    },
+                    wgpu::Binding {
+                        binding: 4,
+                        resource: wgpu::BindingResource::Buffer {
+                            buffer: &palette_uniform_buf,
+                            range: 0..PALETTE_UNIFORM_SIZE,
+                        },
+                    },
+                ],
+                label: None,
+            })
+        };
+        let voxel_bind_group = create_voxel_bind_group();
+        let catcher_bind_group = create_voxel_bind_group();
 
         let vs_bytes = include_bytes!("../shaders/voxel.vert.spv");
         let fs_bytes = include_bytes!("../shaders/voxel.frag.spv");
@@ -467,84 +565,107 @@ impl Renderer {
         let fs_module_voxel = device
             .create_shader_module(&wgpu::read_spirv(std::io::Cursor::new(&fs_bytes[..])).unwrap());
 
-        let voxel_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            layout: &pipeline_layout,
-            vertex_stage: wgpu::ProgrammableStageDescriptor {
-                module: &vs_module_voxel,
-                entry_point: "main",
-            },
-            fragment_stage: Some(wgpu::ProgrammableStageDescriptor {
-                module: &fs_module_voxel,
-                entry_point: "main",
-            }),
-            rasterization_state: Some(wgpu::RasterizationStateDescriptor {
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: wgpu::CullMode::Back,
-                depth_bias: 0,
-                depth_bias_slope_scale: 0.0,
-                depth_bias_clamp: 0.0,
-            }),
-            primitive_topology: wgpu::PrimitiveTopology::TriangleList,
-            color_states: &[wgpu::ColorStateDescriptor {
-                format: sc_desc.format,
-                color_blend: wgpu::BlendDescriptor::REPLACE,
-                alpha_blend: wgpu::BlendDescriptor::REPLACE,
-                write_mask: wgpu::ColorWrite::ALL,
-            }],
-            depth_stencil_state: Some(wgpu::DepthStencilStateDescriptor {
-                format: DEPTH_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil_front: wgpu::StencilStateFaceDescriptor::IGNORE,
-                stencil_back: wgpu::StencilStateFaceDescriptor::IGNORE,
-                stencil_read_mask: 0,
-                stencil_write_mask: 0,
-            }),
-            vertex_state: wgpu::VertexStateDescriptor {
-                index_format: wgpu::IndexFormat::Uint16,
-                vertex_buffers: &[
-                    wgpu::VertexBufferDescriptor {
-                        stride: mem::size_of::<VoxelVertex>() as wgpu::BufferAddress,
-                        step_mode: wgpu::InputStepMode::Vertex,
-                        attributes: &[
-                            // Position
-                            wgpu::VertexAttributeDescriptor {
-                                format: wgpu::VertexFormat::Float3,
-                                offset: 0,
-                                shader_location: 0,
-                            },
-                            // Normal
-                            wgpu::VertexAttributeDescriptor {
-                                format: wgpu::VertexFormat::Float3,
-                                offset: 3 * 4,
-                                shader_location: 1,
-                            },
-                        ],
-                    },
-                    wgpu::VertexBufferDescriptor {
-                        stride: mem::size_of::<VoxelInstance>() as wgpu::BufferAddress,
-                        step_mode: wgpu::InputStepMode::Instance,
-                        attributes: &[
-                            // Offset
-                            wgpu::VertexAttributeDescriptor {
-                                format: wgpu::VertexFormat::Float3,
-                                offset: 0,
-                                shader_location: 2,
-                            },
-                            // Color
-                            wgpu::VertexAttributeDescriptor {
-                                format: wgpu::VertexFormat::Float4,
-                                offset: 3 * 4,
-                                shader_location: 3,
-                            },
-                        ],
-                    },
-                ],
-            },
-            sample_count: SAMPLE_COUNT,
-            sample_mask: !0,
-            alpha_to_coverage_enabled: false,
-        });
+        let create_voxel_pipeline = |fs_module: &wgpu::ShaderModule| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                layout: &pipeline_layout,
+                vertex_stage: wgpu::ProgrammableStageDescriptor {
+                    module: &vs_module_voxel,
+                    entry_point: "main",
+                },
+                fragment_stage: Some(wgpu::ProgrammableStageDescriptor {
+                    module: fs_module,
+                    entry_point: "main",
+                }),
+                rasterization_state: Some(wgpu::RasterizationStateDescriptor {
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: wgpu::CullMode::Back,
+                    depth_bias: 0,
+                    depth_bias_slope_scale: 0.0,
+                    depth_bias_clamp: 0.0,
+                }),
+                primitive_topology: wgpu::PrimitiveTopology::TriangleList,
+                color_states: &[wgpu::ColorStateDescriptor {
+                    format: sc_desc.format,
+                    color_blend: wgpu::BlendDescriptor::REPLACE,
+                    alpha_blend: wgpu::BlendDescriptor::REPLACE,
+                    write_mask: wgpu::ColorWrite::ALL,
+                }],
+                depth_stencil_state: Some(wgpu::DepthStencilStateDescriptor {
+                    format: DEPTH_FORMAT,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil_front: wgpu::StencilStateFaceDescriptor::IGNORE,
+                    stencil_back: wgpu::StencilStateFaceDescriptor::IGNORE,
+                    stencil_read_mask: 0,
+                    stencil_write_mask: 0,
+                }),
+                vertex_state: wgpu::VertexStateDescriptor {
+                    index_format: wgpu::IndexFormat::Uint16,
+                    vertex_buffers: &[
+                        wgpu::VertexBufferDescriptor {
+                            stride: mem::size_of::<VoxelVertex>() as wgpu::BufferAddress,
+                            step_mode: wgpu::InputStepMode::Vertex,
+                            attributes: &[
+                                // Position
+                                wgpu::VertexAttributeDescriptor {
+                                    format: wgpu::VertexFormat::Float3,
+                                    offset: 0,
+                                    shader_location: 0,
+                                },
+                                // Normal
+                                wgpu::VertexAttributeDescriptor {
+                                    format: wgpu::VertexFormat::Float3,
+                                    offset: 3 * 4,
+                                    shader_location: 1,
+                                },
+                            ],
+                        },
+                        wgpu::VertexBufferDescriptor {
+                            stride: mem::size_of::<VoxelInstance>() as wgpu::BufferAddress,
+                            step_mode: wgpu::InputStepMode::Instance,
+                            attributes: &[
+                                // Offset
+                                wgpu::VertexAttributeDescriptor {
+                                    format: wgpu::VertexFormat::Float3,
+                                    offset: 0,
+                                    shader_location: 2,
+                                },
+                                // Palette index
+                                wgpu::VertexAttributeDescriptor {
+                                    format: wgpu::VertexFormat::Uint,
+                                    offset: 3 * 4,
+                                    shader_location: 3,
+                                },
+                            ],
+                        },
+                    ],
+                },
+                sample_count: SAMPLE_COUNT,
+                sample_mask: !0,
+                alpha_to_coverage_enabled: false,
+            })
+        };
+        let voxel_pipeline = create_voxel_pipeline(&fs_module_voxel);
+
+        // The ground of screenshots, drawn like a voxel but only showing its shadow
+        let fs_bytes = include_bytes!("../shaders/catcher.frag.spv");
+        let fs_module_catcher = device
+            .create_shader_module(&wgpu::read_spirv(std::io::Cursor::new(&fs_bytes[..])).unwrap());
+        let catcher_pipeline = create_voxel_pipeline(&fs_module_catcher);
+        let mesh_extent = mesh_count as f32;
+        let ground = BoundingBox::new(
+            cgmath::Vector3::new(-mesh_extent, 0.0, -mesh_extent),
+            cgmath::Vector3::new(3.0 * mesh_extent, 0.0, 3.0 * mesh_extent),
+            [1.0; 4],
+        );
+        let vertex_buf_catcher = device.create_buffer_with_data(
+            bytemuck::cast_slice(&ground.voxel_vertices()),
+            wgpu::BufferUsage::VERTEX,
+        );
+        let instance_buf_catcher = device.create_buffer_with_data(
+            bytemuck::bytes_of(&instance([0.0; 3], 0)),
+            wgpu::BufferUsage::VERTEX,
+        );
 
         //****************************** Setting up shadow pipeline ******************************
 
@@ -645,6 +766,163 @@ impl Renderer {
             alpha_to_coverage_enabled: false,
         });
 
+        //****************************** Setting up pick pipeline ******************************
+        let (pick_bind_group, pick_pipeline) =
+            build_pick_pipeline(&device, &uniform_buf, uniform_buf_size);
+        let pick_target = PickTarget::new(&device, sc_desc.width, sc_desc.height);
+        let (distance_bind_group, distance_pipeline) =
+            build_distance_pipeline(&device, &uniform_buf, uniform_buf_size);
+        let (emission_bind_group, emission_pipeline) = build_emission_pipeline(
+            &device,
+            &uniform_buf,
+            uniform_buf_size,
+            &palette_uniform_buf,
+            PALETTE_UNIFORM_SIZE,
+        );
+        let viewport_bloom = ViewportBloom::new(
+            &device,
+            sc_desc.width,
+            sc_desc.height,
+            sc_desc.format,
+            SAMPLE_COUNT,
+        );
+
+        let (highlight_bind_group, highlight_pipeline) = build_highlight_pipeline(
+            &device,
+            &uniform_buf,
+            uniform_buf_size,
+            sc_desc.format,
+            SAMPLE_COUNT,
+        );
+        // Placeholder buffers until something gets highlighted
+        let (vertex_data, highlight_index_data) = highlight_vertices(&[[0, 0, 0]], TRANSPARENT);
+        let vertex_buf_highlight = device.create_buffer_with_data(
+            bytemuck::cast_slice(&vertex_data),
+            wgpu::BufferUsage::VERTEX,
+        );
+        let index_buf_highlight = device.create_buffer_with_data(
+            bytemuck::cast_slice(&highlight_index_data),
+            wgpu::BufferUsage::INDEX,
+        );
+
+        // The safe frame is drawn in normalized device coordinates
+        let identity: [[[f32; 4]; 4]; 3] = [cgmath::Matrix4::from_scale(1.0).into(); 3];
+        let safe_frame_uniform_buf = device.create_buffer_with_data(
+            bytemuck::cast_slice(identity.as_ref()),
+            wgpu::BufferUsage::UNIFORM,
+        );
+        let (safe_frame_bind_group, safe_frame_pipeline) = build_highlight_pipeline(
+            &device,
+            &safe_frame_uniform_buf,
+            uniform_buf_size,
+            sc_desc.format,
+            SAMPLE_COUNT,
+        );
+        let (marquee_bind_group, marquee_pipeline) = build_highlight_pipeline(
+            &device,
+            &safe_frame_uniform_buf,
+            uniform_buf_size,
+            sc_desc.format,
+            SAMPLE_COUNT,
+        );
+        let vertex_buf_marquee = device.create_buffer_with_data(
+            bytemuck::cast_slice(&vertex_data),
+            wgpu::BufferUsage::VERTEX,
+        );
+        let index_buf_marquee = device.create_buffer_with_data(
+            bytemuck::cast_slice(&highlight_index_data),
+            wgpu::BufferUsage::INDEX,
+        );
+        let vertex_buf_safe_frame = device.create_buffer_with_data(
+            bytemuck::cast_slice(&vertex_data),
+            wgpu::BufferUsage::VERTEX,
+        );
+        let index_buf_safe_frame = device.create_buffer_with_data(
+            bytemuck::cast_slice(&highlight_index_data),
+            wgpu::BufferUsage::INDEX,
+        );
+
+        let (working_plane_bind_group, working_plane_pipeline) = build_highlight_pipeline(
+            &device,
+            &uniform_buf,
+            uniform_buf_size,
+            sc_desc.format,
+            SAMPLE_COUNT,
+        );
+        let vertex_buf_working_plane = device.create_buffer_with_data(
+            bytemuck::cast_slice(&vertex_data),
+            wgpu::BufferUsage::VERTEX,
+        );
+        let index_buf_working_plane = device.create_buffer_with_data(
+            bytemuck::cast_slice(&highlight_index_data),
+            wgpu::BufferUsage::INDEX,
+        );
+
+        let (marker_bind_group, marker_pipeline) = build_highlight_pipeline(
+            &device,
+            &uniform_buf,
+            uniform_buf_size,
+            sc_desc.format,
+            SAMPLE_COUNT,
+        );
+        let vertex_buf_marker = device.create_buffer_with_data(
+            bytemuck::cast_slice(&vertex_data),
+            wgpu::BufferUsage::VERTEX,
+        );
+        let index_buf_marker = device.create_buffer_with_data(
+            bytemuck::cast_slice(&highlight_index_data),
+            wgpu::BufferUsage::INDEX,
+        );
+
+        let (ruler_bind_group, ruler_pipeline) = build_highlight_pipeline(
+            &device,
+            &uniform_buf,
+            uniform_buf_size,
+            sc_desc.format,
+            SAMPLE_COUNT,
+        );
+        let vertex_buf_ruler = device.create_buffer_with_data(
+            bytemuck::cast_slice(&vertex_data),
+            wgpu::BufferUsage::VERTEX,
+        );
+        let index_buf_ruler = device.create_buffer_with_data(
+            bytemuck::cast_slice(&highlight_index_data),
+            wgpu::BufferUsage::INDEX,
+        );
+
+        let (context_bind_group, context_pipeline) = build_depth_tested_pipeline(
+            &device,
+            &uniform_buf,
+            uniform_buf_size,
+            sc_desc.format,
+            SAMPLE_COUNT,
+            DEPTH_FORMAT,
+        );
+        let vertex_buf_context = device.create_buffer_with_data(
+            bytemuck::cast_slice(&vertex_data),
+            wgpu::BufferUsage::VERTEX,
+        );
+        let index_buf_context = device.create_buffer_with_data(
+            bytemuck::cast_slice(&highlight_index_data),
+            wgpu::BufferUsage::INDEX,
+        );
+
+        let (gizmo_bind_group, gizmo_pipeline) = build_highlight_pipeline(
+            &device,
+            &uniform_buf,
+            uniform_buf_size,
+            sc_desc.format,
+            SAMPLE_COUNT,
+        );
+        let vertex_buf_gizmo = device.create_buffer_with_data(
+            bytemuck::cast_slice(&vertex_data),
+            wgpu::BufferUsage::VERTEX,
+        );
+        let index_buf_gizmo = device.create_buffer_with_data(
+            bytemuck::cast_slice(&highlight_index_data),
+            wgpu::BufferUsage::INDEX,
+        );
+
         let ui_pipeline = build_ui_pipeline(&device);
         let multisampled_framebuffer = create_texture_view(
             &device,
@@ -676,7 +954,8 @@ impl Renderer {
                 instance_buf: None,
                 index_buf: Rc::new(index_buf_mesh),
                 index_count: mesh_index_data.len(),
-                instance_count: 1,
+                instance_ranges: vec![0..1],
+                triangle_list: false,
             },
             cursor_pipeline: Pipeline {
                 pipeline: cursor_pipeline,
@@ -685,7 +964,102 @@ impl Renderer {
                 instance_buf: None,
                 index_buf: index_buf_cursor.clone(),
                 index_count: cursor_index_data.len(),
-                instance_count: 1,
+                instance_ranges: vec![0..1],
+                triangle_list: true,
+            },
+            highlight_pipeline: Pipeline {
+                pipeline: highlight_pipeline,
+                bind_group: highlight_bind_group,
+                vertex_buf: Rc::new(vertex_buf_highlight),
+                instance_buf: None,
+                index_buf: Rc::new(index_buf_highlight),
+                index_count: highlight_index_data.len(),
+                instance_ranges: vec![0..0],
+                triangle_list: true,
+            },
+            safe_frame_pipeline: Pipeline {
+                pipeline: safe_frame_pipeline,
+                bind_group: safe_frame_bind_group,
+                vertex_buf: Rc::new(vertex_buf_safe_frame),
+                instance_buf: None,
+                index_buf: Rc::new(index_buf_safe_frame),
+                index_count: highlight_index_data.len(),
+                instance_ranges: vec![0..0],
+                triangle_list: true,
+            },
+            safe_frame: None,
+            marquee_pipeline: Pipeline {
+                pipeline: marquee_pipeline,
+                bind_group: marquee_bind_group,
+                vertex_buf: Rc::new(vertex_buf_marquee),
+                instance_buf: None,
+                index_buf: Rc::new(index_buf_marquee),
+                index_count: highlight_index_data.len(),
+                instance_ranges: vec![0..0],
+                triangle_list: true,
+            },
+            working_plane_pipeline: Pipeline {
+                pipeline: working_plane_pipeline,
+                bind_group: working_plane_bind_group,
+                vertex_buf: Rc::new(vertex_buf_working_plane),
+                instance_buf: None,
+                index_buf: Rc::new(index_buf_working_plane),
+                index_count: highlight_index_data.len(),
+                instance_ranges: vec![0..0],
+                triangle_list: true,
+            },
+            working_plane: None,
+            marker_pipeline: Pipeline {
+                pipeline: marker_pipeline,
+                bind_group: marker_bind_group,
+                vertex_buf: Rc::new(vertex_buf_marker),
+                instance_buf: None,
+                index_buf: Rc::new(index_buf_marker),
+                index_count: highlight_index_data.len(),
+                instance_ranges: vec![0..0],
+                triangle_list: true,
+            },
+            ruler_pipeline: Pipeline {
+                pipeline: ruler_pipeline,
+                bind_group: ruler_bind_group,
+                vertex_buf: Rc::new(vertex_buf_ruler),
+                instance_buf: None,
+                index_buf: Rc::new(index_buf_ruler),
+                index_count: highlight_index_data.len(),
+                instance_ranges: vec![0..0],
+                triangle_list: true,
+            },
+            rulers: None,
+            context_pipeline: Pipeline {
+                pipeline: context_pipeline,
+                bind_group: context_bind_group,
+                vertex_buf: Rc::new(vertex_buf_context),
+                instance_buf: None,
+                index_buf: Rc::new(index_buf_context),
+                index_count: highlight_index_data.len(),
+                instance_ranges: vec![0..0],
+                triangle_list: true,
+            },
+            gizmo_pipeline: Pipeline {
+                pipeline: gizmo_pipeline,
+                bind_group: gizmo_bind_group,
+                vertex_buf: Rc::new(vertex_buf_gizmo),
+                instance_buf: None,
+                index_buf: Rc::new(index_buf_gizmo),
+                index_count: highlight_index_data.len(),
+                instance_ranges: vec![0..0],
+                triangle_list: true,
+            },
+            gizmo: None,
+            catcher_pipeline: Pipeline {
+                pipeline: catcher_pipeline,
+                bind_group: catcher_bind_group,
+                vertex_buf: Rc::new(vertex_buf_catcher),
+                instance_buf: Some(Rc::new(instance_buf_catcher)),
+                index_buf: index_buf_cursor.clone(),
+                index_count: cursor_index_data.len(),
+                instance_ranges: vec![0..0],
+                triangle_list: true,
             },
             voxel_pipeline: Pipeline {
                 pipeline: voxel_pipeline,
@@ -694,8 +1068,43 @@ impl Renderer {
                 instance_buf: Some(instance_buf_voxel.clone()),
                 index_buf: index_buf_cursor.clone(),
                 index_count: cursor_index_data.len(),
-                instance_count: 0,
+                instance_ranges: vec![0..0; chunk_count as usize],
+                triangle_list: true,
             },
+            pick_pipeline: Pipeline {
+                pipeline: pick_pipeline,
+                bind_group: pick_bind_group,
+                vertex_buf: vertex_buf_voxel.clone(),
+                instance_buf: Some(instance_buf_voxel.clone()),
+                index_buf: index_buf_cursor.clone(),
+                index_count: cursor_index_data.len(),
+                instance_ranges: vec![0..0; chunk_count as usize],
+                triangle_list: true,
+            },
+            pick_target,
+            distance_pipeline: Pipeline {
+                pipeline: distance_pipeline,
+                bind_group: distance_bind_group,
+                vertex_buf: vertex_buf_voxel.clone(),
+                instance_buf: Some(instance_buf_voxel.clone()),
+                index_buf: index_buf_cursor.clone(),
+                index_count: cursor_index_data.len(),
+                instance_ranges: vec![0..0; chunk_count as usize],
+                triangle_list: true,
+            },
+            emission_pipeline: Pipeline {
+                pipeline: emission_pipeline,
+                bind_group: emission_bind_group,
+                vertex_buf: vertex_buf_voxel.clone(),
+                instance_buf: Some(instance_buf_voxel.clone()),
+                index_buf: index_buf_cursor.clone(),
+                index_count: cursor_index_data.len(),
+                instance_ranges: vec![0..0; chunk_count as usize],
+                triangle_list: true,
+            },
+            viewport_bloom,
+            bloom: None,
+            has_emission: false,
             shadow_pipeline: Pipeline {
                 pipeline: shadow_pipeline,
                 bind_group: shadow_bind_group,
@@ -703,9 +1112,12 @@ impl Renderer {
                 instance_buf: Some(instance_buf_voxel),
                 index_buf: index_buf_cursor,
                 index_count: cursor_index_data.len(),
-                instance_count: 0,
+                instance_ranges: vec![0..0; chunk_count as usize],
+                triangle_list: true,
             },
             cursor_cube,
+            cursor_color: HALF_ALPHA_RED,
+            theme,
             draw_cube: None,
             render_cursor: true,
             render_mesh: true,
@@ -726,10 +1138,13 @@ impl Renderer {
                 },
             ),
             light_uniform_buf,
+            palette_uniform_buf,
             shadow_view,
             lights_are_dirty: true,
+            background: LightingPreset::default().background(),
             command_buffers: Vec::new(),
             ui_pipeline,
+            stats: RenderStats::default(),
         }
     }
 
@@ -744,6 +1159,459 @@ impl Renderer {
         );
     }
 
+    /// Renders the voxels alone over a transparent background into an RGBA image, the grid,
+    /// cursor, highlights and the UI are left out
+    pub fn screenshot(
+        &mut self,
+        camera: &mut CameraWrapper,
+        width: u32,
+        height: u32,
+        depth_of_field: Option<DepthOfField>,
+        bloom: Option<Bloom>,
+    ) -> Option<Vec<u8>> {
+        let target = ScreenshotTarget::new(
+            &self.device,
+            width,
+            height,
+            self.sc_desc.format,
+            DEPTH_FORMAT,
+            SAMPLE_COUNT,
+        );
+        let distance_target =
+            depth_of_field.map(|dof| (DistanceTarget::new(&self.device, width, height), dof));
+        let emission_target =
+            bloom.map(|bloom| (EmissionTarget::new(&self.device, width, height), bloom));
+        self.render_offscreen(
+            &target,
+            distance_target.as_ref(),
+            emission_target.as_ref(),
+            camera,
+            [width, height],
+            0.0,
+        )
+    }
+
+    /// Screenshots of a full turn around the scene, the camera ends up where it started
+    pub fn turntable(
+        &mut self,
+        camera: &mut CameraWrapper,
+        width: u32,
+        height: u32,
+        frames: u32,
+        depth_of_field: Option<DepthOfField>,
+        bloom: Option<Bloom>,
+        mut frame: impl FnMut(Vec<u8>) -> bool,
+    ) -> bool {
+        let target = ScreenshotTarget::new(
+            &self.device,
+            width,
+            height,
+            self.sc_desc.format,
+            DEPTH_FORMAT,
+            SAMPLE_COUNT,
+        );
+        let distance_target =
+            depth_of_field.map(|dof| (DistanceTarget::new(&self.device, width, height), dof));
+        let emission_target =
+            bloom.map(|bloom| (EmissionTarget::new(&self.device, width, height), bloom));
+        let step = 2.0 * std::f32::consts::PI / frames as f32;
+        let view = camera.view();
+        let mut rendered = 0;
+        while rendered < frames {
+            let image = self.render_offscreen(
+                &target,
+                distance_target.as_ref(),
+                emission_target.as_ref(),
+                camera,
+                [width, height],
+                0.0,
+            );
+            camera.turn(step);
+            rendered += 1;
+            if !image.map_or(false, &mut frame) {
+                break;
+            }
+        }
+        camera.set_view(&view);
+        self.update_view(camera);
+        rendered == frames
+    }
+
+    /// Screenshots of the left and the right eye separation apart in the world, both looking
+    /// at the point the camera orbits around
+    pub fn stereo_pair(
+        &mut self,
+        camera: &mut CameraWrapper,
+        width: u32,
+        height: u32,
+        separation: f32,
+        depth_of_field: Option<DepthOfField>,
+        bloom: Option<Bloom>,
+    ) -> Option<[Vec<u8>; 2]> {
+        let target = ScreenshotTarget::new(
+            &self.device,
+            width,
+            height,
+            self.sc_desc.format,
+            DEPTH_FORMAT,
+            SAMPLE_COUNT,
+        );
+        let distance_target =
+            depth_of_field.map(|dof| (DistanceTarget::new(&self.device, width, height), dof));
+        let emission_target =
+            bloom.map(|bloom| (EmissionTarget::new(&self.device, width, height), bloom));
+        let mut eye = |offset| {
+            self.render_offscreen(
+                &target,
+                distance_target.as_ref(),
+                emission_target.as_ref(),
+                camera,
+                [width, height],
+                offset,
+            )
+        };
+        let left = eye(-0.5 * separation)?;
+        let right = eye(0.5 * separation)?;
+        Some([left, right])
+    }
+
+    /// Renders the view of an eye moved sideways from the camera by the offset
+    fn render_offscreen(
+        &mut self,
+        target: &ScreenshotTarget,
+        depth_of_field: Option<&(DistanceTarget, DepthOfField)>,
+        bloom: Option<&(EmissionTarget, Bloom)>,
+        camera: &mut CameraWrapper,
+        [width, height]: [u32; 2],
+        eye: f32,
+    ) -> Option<Vec<u8>> {
+        let aspect_ratio = width as f32 / height as f32;
+        let window_aspect_ratio = self.sc_desc.width as f32 / self.sc_desc.height as f32;
+        let matrices = camera.eye_mvp_matrices(aspect_ratio, window_aspect_ratio, eye);
+        Self::write_buffer(
+            &self.device,
+            bytemuck::cast_slice(matrices.as_ref()),
+            &self.mvp_buf,
+            &mut self.command_buffers,
+        );
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        {
+            let mut pass = target.begin_pass(&mut encoder);
+            if self.voxel_pipeline.has_instances() {
+                self.voxel_pipeline
+                    .draw(&mut pass, &mut RenderStats::default());
+            }
+            self.catcher_pipeline
+                .draw(&mut pass, &mut RenderStats::default());
+        }
+        target.copy(&mut encoder);
+        if let Some((distance_target, _)) = depth_of_field {
+            {
+                let mut pass = distance_target.begin_pass(&mut encoder);
+                if self.distance_pipeline.has_instances() {
+                    self.distance_pipeline
+                        .draw(&mut pass, &mut RenderStats::default());
+                }
+            }
+            distance_target.copy(&mut encoder);
+        }
+        if let Some((emission_target, _)) = bloom {
+            {
+                let mut pass = emission_target.begin_pass(&mut encoder);
+                if self.emission_pipeline.has_instances() {
+                    self.emission_pipeline
+                        .draw(&mut pass, &mut RenderStats::default());
+                }
+            }
+            emission_target.copy(&mut encoder);
+        }
+        let mut command_buffers = self.command_buffers.drain(..).collect::<Vec<_>>();
+        command_buffers.push(encoder.finish());
+        self.queue.submit(&command_buffers);
+        // Back to the window's aspect ratio for the next frame
+        self.update_view(camera);
+        let mut pixels = target.read(&self.device)?;
+        if let Some((distance_target, dof)) = depth_of_field {
+            let distances = distance_target.read(&self.device)?;
+            dof.apply(
+                camera.distance_to(dof.focus),
+                &mut pixels,
+                &distances,
+                width as usize,
+                height as usize,
+            );
+        }
+        // The glow spreads over the blurred image, like it would through a lens
+        if let Some((emission_target, bloom)) = bloom {
+            let emission = emission_target.read(&self.device)?;
+            bloom.apply(&mut pixels, &emission, width as usize, height as usize);
+        }
+        Some(pixels)
+    }
+
+    /// Grid cell under the cursor cube
+    pub fn cursor_cell(&self) -> [i32; 3] {
+        let mut cursor = self.cursor_cube;
+        cursor.rearrange();
+        [
+            cursor.corner.x.round() as i32,
+            cursor.corner.y.round() as i32,
+            cursor.corner.z.round() as i32,
+        ]
+    }
+
+    /// Color of the ghost voxel and the draw rectangle previewing the next edit
+    pub fn set_cursor_color(&mut self, color: [f32; 4]) {
+        self.cursor_color = color;
+    }
+
+    /// Whether screenshots and turntables show the shadow of the voxels on the ground, for
+    /// compositing them over other backgrounds
+    pub fn set_shadow_catcher(&mut self, shadow_catcher: bool) {
+        self.catcher_pipeline.instance_ranges = vec![0..shadow_catcher as u32];
+    }
+
+    /// Glow of the emissive palette entries in the viewport, saved renders take theirs along
+    pub fn set_bloom(&mut self, bloom: Option<Bloom>) {
+        if bloom == self.bloom {
+            return;
+        }
+        if let Some(bloom) = bloom {
+            Self::write_buffer(
+                &self.device,
+                bytemuck::cast_slice(&[bloom.strength, 0.0, 0.0, 0.0]),
+                self.viewport_bloom.strength_buf(),
+                &mut self.command_buffers,
+            );
+        }
+        self.bloom = bloom;
+    }
+
+    /// Exposure in stops and the curve the lit voxel colors are mapped to the screen with
+    pub fn set_tone_mapping(&mut self, exposure: f32, tone_mapping: ToneMapping) {
+        if self.light.set_tone(exposure, tone_mapping) {
+            self.lights_are_dirty = true;
+        }
+    }
+
+    /// Light direction, color, ambient and the background of the viewport
+    pub fn set_lighting(&mut self, preset: LightingPreset) {
+        if self.light.set_preset(preset, self.mesh_count as f32) {
+            self.lights_are_dirty = true;
+            self.background = preset.background();
+        }
+    }
+
+    /// Highlights the given cells on top of the scene, an empty slice clears the highlight
+    pub fn set_highlight(&mut self, cells: &[[usize; 3]], color: [f32; 4]) {
+        if cells.is_empty() {
+            self.highlight_pipeline.instance_ranges = vec![0..0];
+            return;
+        }
+        let (vertex_data, index_data) = highlight_vertices(cells, color);
+        self.highlight_pipeline.vertex_buf = Rc::new(self.device.create_buffer_with_data(
+            bytemuck::cast_slice(&vertex_data),
+            wgpu::BufferUsage::VERTEX,
+        ));
+        self.highlight_pipeline.index_buf =
+            Rc::new(self.device.create_buffer_with_data(
+                bytemuck::cast_slice(&index_data),
+                wgpu::BufferUsage::INDEX,
+            ));
+        self.highlight_pipeline.index_count = index_data.len();
+        self.highlight_pipeline.instance_ranges = vec![0..1];
+    }
+
+    /// Masks the viewport outside a centered frame of the given aspect ratio, `None` hides it
+    pub fn set_safe_frame(&mut self, aspect_ratio: Option<f32>) {
+        if aspect_ratio != self.safe_frame {
+            self.safe_frame = aspect_ratio;
+            self.update_safe_frame();
+        }
+    }
+
+    fn update_safe_frame(&mut self) {
+        let aspect_ratio = match self.safe_frame {
+            Some(aspect_ratio) => aspect_ratio,
+            None => {
+                self.safe_frame_pipeline.instance_ranges = vec![0..0];
+                return;
+            }
+        };
+        let (vertex_data, index_data) =
+            safe_frame_vertices([self.sc_desc.width, self.sc_desc.height], aspect_ratio);
+        self.safe_frame_pipeline.vertex_buf = Rc::new(self.device.create_buffer_with_data(
+            bytemuck::cast_slice(&vertex_data),
+            wgpu::BufferUsage::VERTEX,
+        ));
+        self.safe_frame_pipeline.index_buf =
+            Rc::new(self.device.create_buffer_with_data(
+                bytemuck::cast_slice(&index_data),
+                wgpu::BufferUsage::INDEX,
+            ));
+        self.safe_frame_pipeline.index_count = index_data.len();
+        self.safe_frame_pipeline.instance_ranges = vec![0..1];
+    }
+
+    /// Outlines the region of a marquee selection in the window, `None` hides it
+    pub fn set_marquee(&mut self, region: Option<&ScreenRegion>) {
+        let region = match region {
+            Some(region) => region,
+            None => {
+                self.marquee_pipeline.instance_ranges = vec![0..0];
+                return;
+            }
+        };
+        let (vertex_data, index_data) =
+            marquee_vertices(region, [self.sc_desc.width, self.sc_desc.height]);
+        self.marquee_pipeline.vertex_buf = Rc::new(self.device.create_buffer_with_data(
+            bytemuck::cast_slice(&vertex_data),
+            wgpu::BufferUsage::VERTEX,
+        ));
+        self.marquee_pipeline.index_buf =
+            Rc::new(self.device.create_buffer_with_data(
+                bytemuck::cast_slice(&index_data),
+                wgpu::BufferUsage::INDEX,
+            ));
+        self.marquee_pipeline.index_count = index_data.len();
+        self.marquee_pipeline.instance_ranges = vec![0..1];
+    }
+
+    /// Shows the working plane and its gizmo over the scene, `None` hides it
+    pub fn set_working_plane(&mut self, working_plane: Option<WorkingPlane>) {
+        if working_plane == self.working_plane {
+            return;
+        }
+        self.working_plane = working_plane;
+        let working_plane = match working_plane {
+            Some(working_plane) => working_plane,
+            None => {
+                self.working_plane_pipeline.instance_ranges = vec![0..0];
+                return;
+            }
+        };
+        let (vertex_data, index_data) = working_plane.vertices(self.mesh_count as usize);
+        self.working_plane_pipeline.vertex_buf = Rc::new(self.device.create_buffer_with_data(
+            bytemuck::cast_slice(&vertex_data),
+            wgpu::BufferUsage::VERTEX,
+        ));
+        self.working_plane_pipeline.index_buf =
+            Rc::new(self.device.create_buffer_with_data(
+                bytemuck::cast_slice(&index_data),
+                wgpu::BufferUsage::INDEX,
+            ));
+        self.working_plane_pipeline.index_count = index_data.len();
+        self.working_plane_pipeline.instance_ranges = vec![0..1];
+    }
+
+    /// Shows rulers along the edges of the plane labelled every step cells, `None` hides them
+    pub fn set_rulers(&mut self, rulers: Option<(WorkingPlane, usize)>) {
+        if rulers == self.rulers {
+            return;
+        }
+        self.rulers = rulers;
+        let (plane, step) = match rulers {
+            Some(rulers) => rulers,
+            None => {
+                self.ruler_pipeline.instance_ranges = vec![0..0];
+                return;
+            }
+        };
+        let (vertex_data, index_data) = ruler_vertices(plane, self.mesh_count as usize, step);
+        self.ruler_pipeline.vertex_buf = Rc::new(self.device.create_buffer_with_data(
+            bytemuck::cast_slice(&vertex_data),
+            wgpu::BufferUsage::VERTEX,
+        ));
+        self.ruler_pipeline.index_buf =
+            Rc::new(self.device.create_buffer_with_data(
+                bytemuck::cast_slice(&index_data),
+                wgpu::BufferUsage::INDEX,
+            ));
+        self.ruler_pipeline.index_count = index_data.len();
+        self.ruler_pipeline.instance_ranges = vec![0..1];
+    }
+
+    /// Draws the boxes of the scenes around the edited grid, an empty slice clears them
+    pub fn set_context(&mut self, boxes: &[BoundingBox]) {
+        if boxes.is_empty() {
+            self.context_pipeline.instance_ranges = vec![0..0];
+            return;
+        }
+        let (vertex_data, index_data) = shaded_box_vertices(boxes);
+        self.context_pipeline.vertex_buf = Rc::new(self.device.create_buffer_with_data(
+            bytemuck::cast_slice(&vertex_data),
+            wgpu::BufferUsage::VERTEX,
+        ));
+        self.context_pipeline.index_buf =
+            Rc::new(self.device.create_buffer_with_data(
+                bytemuck::cast_slice(&index_data),
+                wgpu::BufferUsage::INDEX,
+            ));
+        self.context_pipeline.index_count = index_data.len();
+        self.context_pipeline.instance_ranges = vec![0..1];
+    }
+
+    /// Stands pins on the cells of the markers, an empty slice clears them
+    pub fn set_markers(&mut self, cells: &[[usize; 3]]) {
+        if cells.is_empty() {
+            self.marker_pipeline.instance_ranges = vec![0..0];
+            return;
+        }
+        let (vertex_data, index_data) = marker_vertices(cells, MARKER_COLOR);
+        self.marker_pipeline.vertex_buf = Rc::new(self.device.create_buffer_with_data(
+            bytemuck::cast_slice(&vertex_data),
+            wgpu::BufferUsage::VERTEX,
+        ));
+        self.marker_pipeline.index_buf =
+            Rc::new(self.device.create_buffer_with_data(
+                bytemuck::cast_slice(&index_data),
+                wgpu::BufferUsage::INDEX,
+            ));
+        self.marker_pipeline.index_count = index_data.len();
+        self.marker_pipeline.instance_ranges = vec![0..1];
+    }
+
+    /// Shows the transform gizmo over the scene, `None` hides it
+    pub fn set_gizmo(&mut self, gizmo: Option<Gizmo>) {
+        if gizmo == self.gizmo {
+            return;
+        }
+        self.gizmo = gizmo;
+        let gizmo = match gizmo {
+            Some(gizmo) => gizmo,
+            None => {
+                self.gizmo_pipeline.instance_ranges = vec![0..0];
+                return;
+            }
+        };
+        let (vertex_data, index_data) = gizmo.vertices();
+        self.gizmo_pipeline.vertex_buf = Rc::new(self.device.create_buffer_with_data(
+            bytemuck::cast_slice(&vertex_data),
+            wgpu::BufferUsage::VERTEX,
+        ));
+        self.gizmo_pipeline.index_buf =
+            Rc::new(self.device.create_buffer_with_data(
+                bytemuck::cast_slice(&index_data),
+                wgpu::BufferUsage::INDEX,
+            ));
+        self.gizmo_pipeline.index_count = index_data.len();
+        self.gizmo_pipeline.instance_ranges = vec![0..1];
+    }
+
+    /// Recolors the axes and the grid
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+        let (vertex_data, _) = generate_mesh_vertices(self.mesh_count, &self.theme);
+        Self::write_buffer(
+            &self.device,
+            bytemuck::cast_slice(&vertex_data),
+            &self.mesh_pipeline.vertex_buf,
+            &mut self.command_buffers,
+        );
+    }
+
     pub fn toggle_render_mesh(&mut self) {
         self.render_mesh = !self.render_mesh
     }
@@ -757,7 +1625,7 @@ impl Renderer {
             self.cursor_cube = BoundingBox::new(
                 Self::get_grid_pos(pos),
                 plane.left + plane.down + plane.normal,
-                HALF_ALPHA_RED.into(),
+                self.cursor_color,
             );
             let vertex_data = self.cursor_cube.vertices();
             Self::write_buffer(
@@ -774,7 +1642,7 @@ impl Renderer {
 
     pub fn update_cursor_pos(&mut self, bbox: BoundingBox) {
         self.cursor_cube = bbox;
-        self.cursor_cube.color = HALF_ALPHA_RED.into();
+        self.cursor_cube.color = self.cursor_color;
         let vertex_data = self.cursor_cube.vertices();
         Self::write_buffer(
             &self.device,
@@ -785,17 +1653,23 @@ impl Renderer {
         self.render_cursor = true;
     }
 
+    /// The rectangle from the cursor cube to the cell under the position on the plane, kept on
+    /// one axis when locked
     pub fn update_draw_rectangle_on_plane(
         &mut self,
         pos: cgmath::Vector3<f32>,
         plane: Option<&Plane>,
+        lock: Option<AxisLock>,
     ) {
         if let Some(plane) = plane {
-            let end_cube = BoundingBox::new(
+            let mut end_cube = BoundingBox::new(
                 Self::get_grid_pos(pos),
                 plane.left + plane.down + plane.normal,
-                HALF_ALPHA_RED.into(),
+                self.cursor_color,
             );
+            if let Some(lock) = lock {
+                end_cube = end_cube.locked_to(&self.cursor_cube, lock);
+            }
             let draw_cube = self.cursor_cube.containing_box(&end_cube);
             let vertex_data = draw_cube.vertices();
             Self::write_buffer(
@@ -809,8 +1683,22 @@ impl Renderer {
         self.render_cursor = true;
     }
 
-    pub fn update_draw_rectangle(&mut self, mut bbox: BoundingBox) {
-        bbox.color = HALF_ALPHA_RED.into();
+    /// Takes the dragged rectangle without editing the voxels
+    pub fn take_draw_rectangle(&mut self) -> Option<BoundingBox> {
+        let mut cube = self.draw_cube.take()?;
+        cube.rearrange();
+        Some(cube)
+    }
+
+    pub fn cancel_draw_rectangle(&mut self) {
+        self.draw_cube = None;
+    }
+
+    pub fn update_draw_rectangle(&mut self, mut bbox: BoundingBox, lock: Option<AxisLock>) {
+        bbox.color = self.cursor_color;
+        if let Some(lock) = lock {
+            bbox = bbox.locked_to(&self.cursor_cube, lock);
+        }
         let draw_cube = self.cursor_cube.containing_box(&bbox);
         let vertex_data = draw_cube.vertices();
         Self::write_buffer(
@@ -823,71 +1711,141 @@ impl Renderer {
         self.render_cursor = true;
     }
 
-    pub fn draw_rectangle(&mut self, color: [f32; 4], voxel_manager: &mut VoxelManager) {
-        if let Some(mut cube) = self.draw_cube.take() {
-            cube.rearrange();
-            cube.color = color;
-            voxel_manager.add_box(cube);
-            let instance_data = voxel_manager.instance_data();
+    /// The rectangle edits return the applied box, so the editor can repeat them
+    pub fn draw_rectangle(
+        &mut self,
+        color: [f32; 4],
+        voxel_manager: &mut VoxelManager,
+    ) -> Option<BoundingBox> {
+        let mut cube = self.draw_cube.take()?;
+        cube.rearrange();
+        cube.color = color;
+        voxel_manager.add_box(cube);
+        self.update_voxels(voxel_manager);
+        Some(cube)
+    }
+
+    pub fn fill_rectangle(
+        &mut self,
+        color: [f32; 4],
+        voxel_manager: &mut VoxelManager,
+    ) -> Option<BoundingBox> {
+        let mut cube = self.draw_cube.take()?;
+        cube.rearrange();
+        cube.color = color;
+        voxel_manager.refill(cube);
+        self.update_voxels(voxel_manager);
+        Some(cube)
+    }
+
+    #[cfg(feature = "debug_ray")]
+    pub fn debug_update(&mut self, voxel_manager: &mut VoxelManager) {
+        voxel_manager.mark_all_dirty();
+        self.update_voxels(voxel_manager);
+    }
+
+    pub fn erase_rectangle(&mut self, voxel_manager: &mut VoxelManager) -> Option<BoundingBox> {
+        let mut cube = self.draw_cube.take()?;
+        cube.rearrange();
+        voxel_manager.erase_box(cube);
+        self.update_voxels(voxel_manager);
+        Some(cube)
+    }
+
+    /// Uploads the instance data of the changed chunks into their slot of the instance buffer
+    pub fn update_voxels(&mut self, voxel_manager: &mut VoxelManager) {
+        if voxel_manager.palette_mut().take_dirty() {
+            let palette = voxel_manager.palette().raw();
             Self::write_buffer(
                 &self.device,
-                bytemuck::cast_slice(&instance_data),
-                &self.voxel_pipeline.instance_buf.as_ref().unwrap(),
+                bytemuck::cast_slice(&palette),
+                &self.palette_uniform_buf,
+                &mut self.command_buffers,
+            );
+            Self::write_buffer_at(
+                &self.device,
+                bytemuck::cast_slice(&voxel_manager.palette().raw_emission()),
+                &self.palette_uniform_buf,
+                PALETTE_UNIFORM_SIZE / 2,
                 &mut self.command_buffers,
             );
-            self.voxel_pipeline.instance_count = instance_data.len();
-            self.shadow_pipeline.instance_count = self.voxel_pipeline.instance_count;
+            self.has_emission = voxel_manager.palette().emissive().next().is_some();
         }
-    }
-
-    pub fn fill_rectangle(&mut self, color: [f32; 4], voxel_manager: &mut VoxelManager) {
-        if let Some(mut cube) = self.draw_cube.take() {
-            cube.rearrange();
-            cube.color = color;
-            voxel_manager.refill(cube);
-            let instance_data = voxel_manager.instance_data();
+        let dirty_chunks = voxel_manager.take_dirty_chunks();
+        let remesh_start = time::Instant::now();
+        for chunk in dirty_chunks.iter().cloned() {
+            let instance_data = voxel_manager.chunk_instance_data(chunk);
+            let base = chunk as u32 * CHUNK_CAPACITY;
             if instance_data.len() > 0 {
-                Self::write_buffer(
+                Self::write_buffer_at(
                     &self.device,
                     bytemuck::cast_slice(&instance_data),
                     &self.voxel_pipeline.instance_buf.as_ref().unwrap(),
+                    base as u64 * std::mem::size_of::<VoxelInstance>() as u64,
                     &mut self.command_buffers,
                 );
             }
-            self.voxel_pipeline.instance_count = instance_data.len();
-            self.shadow_pipeline.instance_count = self.voxel_pipeline.instance_count;
+            let range = base..base + instance_data.len() as u32;
+            self.voxel_pipeline.instance_ranges[chunk] = range.clone();
+            self.pick_pipeline.instance_ranges[chunk] = range.clone();
+            self.distance_pipeline.instance_ranges[chunk] = range.clone();
+            self.emission_pipeline.instance_ranges[chunk] = range.clone();
+            self.shadow_pipeline.instance_ranges[chunk] = range;
+        }
+        if !dirty_chunks.is_empty() {
+            self.stats.remeshed_chunks = dirty_chunks.len() as u32;
+            self.stats.remesh_time = remesh_start.elapsed() / dirty_chunks.len() as u32;
         }
     }
 
-    #[cfg(feature = "debug_ray")]
-    pub fn debug_update(&mut self, voxel_manager: &VoxelManager) {
-        let instance_data = voxel_manager.instance_data();
-        if instance_data.len() == 0 {
-            return;
+    /// Estimates the memory of the buffers and render targets owned by the renderer
+    fn gpu_memory(&self) -> u64 {
+        let pixels = (self.sc_desc.width * self.sc_desc.height) as u64;
+        let render_targets = pixels * 4 * SAMPLE_COUNT as u64 * 2;
+        let shadow_map = (SHADOW_MAP_SIZE * SHADOW_MAP_SIZE) as u64 * 4;
+        let instances = self.voxel_pipeline.instance_ranges.len() as u64
+            * CHUNK_CAPACITY as u64
+            * std::mem::size_of::<VoxelInstance>() as u64;
+        let uniforms = PALETTE_UNIFORM_SIZE + std::mem::size_of::<LightRaw>() as u64;
+        render_targets
+            + shadow_map
+            + instances
+            + uniforms
+            + self.pick_target.memory()
+            + self.viewport_bloom.memory()
+    }
+
+    /// Renders the voxel ids under the cursor into the pick buffer and reads back the hit voxel
+    pub fn pick(&mut self, cursor: winit::dpi::PhysicalPosition<f64>) -> Option<Pick> {
+        if cursor.x < 0.0 || cursor.y < 0.0 {
+            return None;
         }
-        Self::write_buffer(
-            &self.device,
-            bytemuck::cast_slice(&instance_data),
-            &self.voxel_pipeline.instance_buf.as_ref().unwrap(),
-            &mut self.command_buffers,
-        );
+        let (x, y) = (cursor.x as u32, cursor.y as u32);
+        if !self.pick_target.contains(x, y) || !self.pick_pipeline.has_instances() {
+            return None;
+        }
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        {
+            let mut pick_pass = self.pick_target.begin_pass(&mut encoder);
+            // Only the pixel under the cursor is needed
+            pick_pass.set_scissor_rect(x, y, 1, 1);
+            self.pick_pipeline
+                .draw(&mut pick_pass, &mut RenderStats::default());
+        }
+        self.pick_target.copy_pixel(&mut encoder, x, y);
+        // Flush the pending uploads so the pick sees the latest voxels
+        let mut command_buffers = self.command_buffers.drain(..).collect::<Vec<_>>();
+        command_buffers.push(encoder.finish());
+        self.queue.submit(&command_buffers);
+        self.pick_target.read_pixel(&self.device)
     }
 
-    pub fn erase_rectangle(&mut self, voxel_manager: &mut VoxelManager) {
-        if let Some(mut cube) = self.draw_cube.take() {
-            cube.rearrange();
-            voxel_manager.erase_box(cube);
-            let instance_data = voxel_manager.instance_data();
-            if instance_data.len() > 0 {
-                Self::write_buffer(
-                    &self.device,
-                    bytemuck::cast_slice(&instance_data),
-                    &self.voxel_pipeline.instance_buf.as_ref().unwrap(),
-                    &mut self.command_buffers,
-                );
-            }
-            self.voxel_pipeline.instance_count = instance_data.len();
-            self.shadow_pipeline.instance_count = self.voxel_pipeline.instance_count;
+    pub fn stats(&self) -> RenderStats {
+        RenderStats {
+            gpu_memory: self.gpu_memory(),
+            ..self.stats
         }
     }
 
@@ -897,7 +1855,8 @@ impl Renderer {
         near_pos: Option<cgmath::Vector3<f32>>,
         far_pos: cgmath::Vector3<f32>,
     ) {
-        let (mut vertex_data, mut index_data) = generate_mesh_vertices(self.mesh_count);
+        let (mut vertex_data, mut index_data) =
+            generate_mesh_vertices(self.mesh_count, &self.theme);
         for _ in 0..2 {
             vertex_data.pop();
             index_data.pop();
@@ -952,6 +1911,10 @@ impl Renderer {
             DEPTH_FORMAT,
             Some("DepthBuffer"),
         );
+        self.pick_target = PickTarget::new(&self.device, size.width, size.height);
+        self.viewport_bloom
+            .resize(&self.device, size.width, size.height);
+        self.update_safe_frame();
     }
 
     pub fn write_buffer(
@@ -959,12 +1922,22 @@ impl Renderer {
         data: &[u8],
         buffer: &wgpu::Buffer,
         command_buffers: &mut Vec<wgpu::CommandBuffer>,
+    ) {
+        Self::write_buffer_at(device, data, buffer, 0, command_buffers);
+    }
+
+    pub fn write_buffer_at(
+        device: &wgpu::Device,
+        data: &[u8],
+        buffer: &wgpu::Buffer,
+        offset: wgpu::BufferAddress,
+        command_buffers: &mut Vec<wgpu::CommandBuffer>,
     ) {
         let mut encoder =
             device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
         let temp_buf = device.create_buffer_with_data(data, wgpu::BufferUsage::COPY_SRC);
 
-        encoder.copy_buffer_to_buffer(&temp_buf, 0, &buffer, 0, data.len() as u64);
+        encoder.copy_buffer_to_buffer(&temp_buf, 0, &buffer, offset, data.len() as u64);
         let command_buf = encoder.finish();
         command_buffers.push(command_buf);
     }
@@ -1000,8 +1973,10 @@ impl Renderer {
         let mut encoder = self
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        self.stats.draw_calls = 0;
+        self.stats.triangles = 0;
 
-        if self.shadow_pipeline.instance_count > 0 {
+        if self.shadow_pipeline.has_instances() {
             let mut shadow_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 color_attachments: &[],
                 depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachmentDescriptor {
@@ -1014,7 +1989,7 @@ impl Renderer {
                     clear_stencil: 0,
                 }),
             });
-            self.shadow_pipeline.draw(&mut shadow_pass);
+            self.shadow_pipeline.draw(&mut shadow_pass, &mut self.stats);
         }
         {
             let mut rpass_depth = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
@@ -1023,12 +1998,7 @@ impl Renderer {
                     resolve_target: Some(&frame.view),
                     load_op: wgpu::LoadOp::Clear,
                     store_op: wgpu::StoreOp::Store,
-                    clear_color: wgpu::Color {
-                        r: 0.0,
-                        g: 0.8,
-                        b: 1.0,
-                        a: 1.0,
-                    },
+                    clear_color: self.background,
                 }],
                 depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachmentDescriptor {
                     attachment: &self.depth_buffer,
@@ -1041,11 +2011,24 @@ impl Renderer {
                 }),
             });
             if self.render_mesh {
-                self.mesh_pipeline.draw(&mut rpass_depth);
+                self.mesh_pipeline.draw(&mut rpass_depth, &mut self.stats);
+            }
+            if self.voxel_pipeline.has_instances() {
+                self.voxel_pipeline.draw(&mut rpass_depth, &mut self.stats);
             }
-            if self.voxel_pipeline.instance_count > 0 {
-                self.voxel_pipeline.draw(&mut rpass_depth);
+            if self.context_pipeline.has_instances() {
+                self.context_pipeline
+                    .draw(&mut rpass_depth, &mut self.stats);
+            }
+        }
+        // The glow goes under the overlays, which resolve the framebuffer again
+        if self.bloom.is_some() && self.has_emission && self.emission_pipeline.has_instances() {
+            {
+                let mut pass = self.viewport_bloom.begin_emission_pass(&mut encoder);
+                self.emission_pipeline.draw(&mut pass, &mut self.stats);
             }
+            self.viewport_bloom
+                .composite(&mut encoder, &self.multisampled_framebuffer);
         }
         {
             let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
@@ -1058,8 +2041,30 @@ impl Renderer {
                 }],
                 depth_stencil_attachment: None,
             });
+            if self.highlight_pipeline.has_instances() {
+                self.highlight_pipeline.draw(&mut rpass, &mut self.stats);
+            }
+            if self.working_plane_pipeline.has_instances() {
+                self.working_plane_pipeline
+                    .draw(&mut rpass, &mut self.stats);
+            }
+            if self.marker_pipeline.has_instances() {
+                self.marker_pipeline.draw(&mut rpass, &mut self.stats);
+            }
+            if self.ruler_pipeline.has_instances() {
+                self.ruler_pipeline.draw(&mut rpass, &mut self.stats);
+            }
+            if self.gizmo_pipeline.has_instances() {
+                self.gizmo_pipeline.draw(&mut rpass, &mut self.stats);
+            }
             if self.render_cursor {
-                self.cursor_pipeline.draw(&mut rpass);
+                self.cursor_pipeline.draw(&mut rpass, &mut self.stats);
+            }
+            if self.safe_frame_pipeline.has_instances() {
+                self.safe_frame_pipeline.draw(&mut rpass, &mut self.stats);
+            }
+            if self.marquee_pipeline.has_instances() {
+                self.marquee_pipeline.draw(&mut rpass, &mut self.stats);
             }
         }
         // Render ui
@@ -1084,8 +2089,4 @@ impl Renderer {
         self.queue.submit(&command_buffers);
         mouse_interaction
     }
-
-    pub fn device_mut(&mut self) -> &mut wgpu::Device {
-        &mut self.device
-    }
 }
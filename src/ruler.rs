@@ -0,0 +1,102 @@
+use crate::font::FontKind;
+use crate::geometry::BoundingBox;
+use crate::highlight::box_vertices;
+use crate::vertex::Vertex;
+use crate::working_plane::WorkingPlane;
+use cgmath::Vector3;
+
+const RULER_COLOR: [f32; 4] = [0.9, 0.9, 0.9, 0.8];
+// Sizes in cells
+const TICK_WIDTH: f32 = 0.06;
+const TICK_LENGTH: f32 = 0.4;
+const MAJOR_TICK_LENGTH: f32 = 0.8;
+const LABEL_PIXEL: f32 = 0.2;
+// Between the end of the major ticks and the labels
+const LABEL_GAP: f32 = 0.2;
+const THICKNESS: f32 = 0.02;
+// Rows of a glyph of the tiny font and the columns from one glyph to the next
+const LABEL_ROWS: usize = 5;
+const LABEL_ADVANCE: usize = 4;
+
+/// A box lying in the plane, from a corner spanning the sizes along the two plane axes
+fn flat_box(plane: WorkingPlane, corner: [f32; 2], size: [f32; 2]) -> BoundingBox {
+    let (right, up) = plane.normal.plane_axes();
+    let mut min = [0.0; 3];
+    let mut extent = [THICKNESS; 3];
+    min[right.index()] = corner[0];
+    min[up.index()] = corner[1];
+    min[plane.normal.index()] = plane.layer as f32 - THICKNESS / 2.0;
+    extent[right.index()] = size[0];
+    extent[up.index()] = size[1];
+    BoundingBox::new(
+        Vector3::new(min[0], min[1], min[2]),
+        Vector3::new(extent[0], extent[1], extent[2]),
+        RULER_COLOR,
+    )
+}
+
+/// Tick marks outside the two edges of the plane through the origin, one every cell and a
+/// longer one with the coordinate printed next to it every step cells
+pub fn ruler_vertices(plane: WorkingPlane, extent: usize, step: usize) -> (Vec<Vertex>, Vec<u32>) {
+    let step = step.max(1);
+    let mut boxes = Vec::new();
+    // Along the first plane axis and then along the second, ticks pointing away from the grid
+    for along in 0..2 {
+        // Plane coordinates with the ruler's axis first
+        let oriented = |a: f32, b: f32| if along == 0 { [a, b] } else { [b, a] };
+        for c in 0..=extent {
+            let major = c % step == 0;
+            let length = if major {
+                MAJOR_TICK_LENGTH
+            } else {
+                TICK_LENGTH
+            };
+            boxes.push(flat_box(
+                plane,
+                oriented(c as f32 - TICK_WIDTH / 2.0, -length),
+                oriented(TICK_WIDTH, length),
+            ));
+            if !major {
+                continue;
+            }
+            let label = c.to_string();
+            let label_width = (label.len() * LABEL_ADVANCE - 1) as f32 * LABEL_PIXEL;
+            let left = c as f32 - label_width / 2.0;
+            let bottom = -MAJOR_TICK_LENGTH - LABEL_GAP - LABEL_ROWS as f32 * LABEL_PIXEL;
+            for [column, row] in FontKind::Tiny.rasterize(&label) {
+                boxes.push(flat_box(
+                    plane,
+                    oriented(
+                        left + column as f32 * LABEL_PIXEL,
+                        bottom + row as f32 * LABEL_PIXEL,
+                    ),
+                    [LABEL_PIXEL; 2],
+                ));
+            }
+        }
+    }
+    box_vertices(&boxes)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::transform::Axis;
+
+    #[test]
+    fn rulers_tick_every_cell_and_label_every_step() {
+        let plane = WorkingPlane {
+            normal: Axis::Z,
+            layer: 3,
+        };
+        let (vertices, indices) = ruler_vertices(plane, 8, 4);
+        // 9 ticks along each edge and a box for every pixel of the labels 0, 4 and 8
+        let pixels: usize = ["0", "4", "8"]
+            .iter()
+            .map(|label| FontKind::Tiny.rasterize(label).len())
+            .sum();
+        assert_eq!(vertices.len(), 2 * (9 + pixels) * 24);
+        assert_eq!(indices.len(), 2 * (9 + pixels) * 36);
+    }
+}
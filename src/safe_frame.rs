@@ -0,0 +1,98 @@
+use crate::vertex::{vertex, Vertex};
+
+// Darkens what renders of the frame's aspect ratio cut off
+const MASK_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.5];
+const FRAME_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.8];
+const SAFE_AREA_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.35];
+// Share of the frame the action safe area keeps
+const SAFE_AREA: f32 = 0.9;
+const LINE_WIDTH: f32 = 2.0;
+
+/// Half width and height in normalized device coordinates of the largest centered region of
+/// the window with the frame's aspect ratio
+pub fn frame_extent(window_size: [u32; 2], frame_aspect: f32) -> [f32; 2] {
+    let window_aspect = window_size[0] as f32 / window_size[1].max(1) as f32;
+    if frame_aspect < window_aspect {
+        [frame_aspect / window_aspect, 1.0]
+    } else {
+        [1.0, window_aspect / frame_aspect]
+    }
+}
+
+fn push_rect(
+    vertices: &mut Vec<Vertex>,
+    indices: &mut Vec<u32>,
+    min: [f32; 2],
+    max: [f32; 2],
+    color: [f32; 4],
+) {
+    let base = vertices.len() as u32;
+    vertices.push(vertex([min[0], min[1], 0.0], color));
+    vertices.push(vertex([max[0], min[1], 0.0], color));
+    vertices.push(vertex([max[0], max[1], 0.0], color));
+    vertices.push(vertex([min[0], max[1], 0.0], color));
+    indices.extend([0, 1, 2, 2, 3, 0].iter().map(|i| base + i));
+}
+
+fn push_outline(
+    vertices: &mut Vec<Vertex>,
+    indices: &mut Vec<u32>,
+    [x, y]: [f32; 2],
+    [dx, dy]: [f32; 2],
+    color: [f32; 4],
+) {
+    push_rect(vertices, indices, [-x, -y], [x, -y + dy], color);
+    push_rect(vertices, indices, [-x, y - dy], [x, y], color);
+    push_rect(vertices, indices, [-x, -y], [-x + dx, y], color);
+    push_rect(vertices, indices, [x - dx, -y], [x, y], color);
+}
+
+/// Screen space triangles masking the window outside the frame and outlining the frame and
+/// its action safe area
+pub fn safe_frame_vertices(window_size: [u32; 2], frame_aspect: f32) -> (Vec<Vertex>, Vec<u32>) {
+    let [x, y] = frame_extent(window_size, frame_aspect);
+    let line = [
+        2.0 * LINE_WIDTH / window_size[0].max(1) as f32,
+        2.0 * LINE_WIDTH / window_size[1].max(1) as f32,
+    ];
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    push_rect(
+        &mut vertices,
+        &mut indices,
+        [-1.0, -1.0],
+        [-x, 1.0],
+        MASK_COLOR,
+    );
+    push_rect(
+        &mut vertices,
+        &mut indices,
+        [x, -1.0],
+        [1.0, 1.0],
+        MASK_COLOR,
+    );
+    push_rect(&mut vertices, &mut indices, [-x, -1.0], [x, -y], MASK_COLOR);
+    push_rect(&mut vertices, &mut indices, [-x, y], [x, 1.0], MASK_COLOR);
+    push_outline(&mut vertices, &mut indices, [x, y], line, FRAME_COLOR);
+    push_outline(
+        &mut vertices,
+        &mut indices,
+        [x * SAFE_AREA, y * SAFE_AREA],
+        line,
+        SAFE_AREA_COLOR,
+    );
+    (vertices, indices)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn frames_fit_inside_the_window() {
+        assert_eq!(frame_extent([1000, 1000], 16.0 / 9.0), [1.0, 0.5625]);
+        assert_eq!(frame_extent([1600, 900], 1.0), [0.5625, 1.0]);
+        assert_eq!(frame_extent([1600, 900], 16.0 / 9.0), [1.0, 1.0]);
+    }
+}
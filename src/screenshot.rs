@@ -0,0 +1,227 @@
+use futures::executor::block_on;
+use iced_wgpu::wgpu;
+
+// Rows copied out of a texture have to be aligned to 256 bytes
+const ROW_ALIGNMENT: u32 = 256;
+const PIXEL_SIZE: u32 = 4;
+/// Largest screenshot side the texture size limits allow
+pub const MAX_SCREENSHOT_SIZE: u32 = 8192;
+
+/// Highest frame rate GIF frame delays can represent
+pub const MAX_TURNTABLE_FPS: f32 = 50.0;
+pub const MAX_TURNTABLE_FRAMES: u32 = 600;
+/// Pixels of all frames of a turntable together, a 1080p turntable has up to 240 frames. Frames
+/// are encoded as they are rendered, the budget bounds the time that takes and the file size.
+pub const MAX_TURNTABLE_PIXELS: u64 = 1920 * 1080 * 240;
+
+/// Size and timing of a full turn around the scene
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Turntable {
+    pub size: [u32; 2],
+    pub fps: f32,
+    pub seconds: f32,
+}
+
+impl Turntable {
+    pub fn frames(&self) -> u32 {
+        (self.fps * self.seconds).round().max(0.0) as u32
+    }
+
+    pub fn is_valid(&self) -> bool {
+        let [width, height] = self.size;
+        self.fps > 0.0
+            && self.fps <= MAX_TURNTABLE_FPS
+            && (1..=MAX_TURNTABLE_FRAMES).contains(&self.frames())
+            && width as u64 * height as u64 * self.frames() as u64 <= MAX_TURNTABLE_PIXELS
+    }
+}
+
+/// Common image proportions screenshot sizes can be picked by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameAspect {
+    Wide,
+    Square,
+    Classic,
+    Portrait,
+}
+
+impl FrameAspect {
+    pub const ALL: [FrameAspect; 4] = [
+        FrameAspect::Wide,
+        FrameAspect::Square,
+        FrameAspect::Classic,
+        FrameAspect::Portrait,
+    ];
+
+    /// Screenshot size of the proportions at 1080p
+    pub fn size(self) -> [u32; 2] {
+        match self {
+            FrameAspect::Wide => [1920, 1080],
+            FrameAspect::Square => [1080, 1080],
+            FrameAspect::Classic => [1440, 1080],
+            FrameAspect::Portrait => [1080, 1920],
+        }
+    }
+}
+
+impl std::fmt::Display for FrameAspect {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let ratio = match self {
+            FrameAspect::Wide => "16:9",
+            FrameAspect::Square => "1:1",
+            FrameAspect::Classic => "4:3",
+            FrameAspect::Portrait => "9:16",
+        };
+        write!(f, "{:?} {}", self, ratio)
+    }
+}
+
+pub fn padded_row(width: u32) -> u32 {
+    (width * PIXEL_SIZE + ROW_ALIGNMENT - 1) / ROW_ALIGNMENT * ROW_ALIGNMENT
+}
+
+/// Offscreen target screenshots are rendered into, multisampled like the window but cleared
+/// to a transparent background
+pub struct ScreenshotTarget {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    multisampled_view: wgpu::TextureView,
+    depth_view: wgpu::TextureView,
+    readback_buf: wgpu::Buffer,
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+}
+
+impl ScreenshotTarget {
+    pub fn new(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        depth_format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Self {
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth: 1,
+        };
+        let create = |format, sample_count, usage, label| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                size,
+                array_layer_count: 1,
+                mip_level_count: 1,
+                sample_count,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage,
+                label: Some(label),
+            })
+        };
+        let texture = create(
+            format,
+            1,
+            wgpu::TextureUsage::OUTPUT_ATTACHMENT | wgpu::TextureUsage::COPY_SRC,
+            "ScreenshotBuffer",
+        );
+        let multisampled_view = create(
+            format,
+            sample_count,
+            wgpu::TextureUsage::OUTPUT_ATTACHMENT,
+            "ScreenshotMSAABuffer",
+        )
+        .create_default_view();
+        let depth_view = create(
+            depth_format,
+            sample_count,
+            wgpu::TextureUsage::OUTPUT_ATTACHMENT,
+            "ScreenshotDepthBuffer",
+        )
+        .create_default_view();
+        let readback_buf = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: padded_row(width) as u64 * height as u64,
+            usage: wgpu::BufferUsage::MAP_READ | wgpu::BufferUsage::COPY_DST,
+        });
+        ScreenshotTarget {
+            view: texture.create_default_view(),
+            texture,
+            multisampled_view,
+            depth_view,
+            readback_buf,
+            format,
+            width,
+            height,
+        }
+    }
+
+    fn padded_row(&self) -> u32 {
+        padded_row(self.width)
+    }
+
+    pub fn begin_pass<'a>(&'a self, encoder: &'a mut wgpu::CommandEncoder) -> wgpu::RenderPass<'a> {
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                attachment: &self.multisampled_view,
+                resolve_target: Some(&self.view),
+                load_op: wgpu::LoadOp::Clear,
+                store_op: wgpu::StoreOp::Store,
+                clear_color: wgpu::Color::TRANSPARENT,
+            }],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachmentDescriptor {
+                attachment: &self.depth_view,
+                depth_load_op: wgpu::LoadOp::Clear,
+                depth_store_op: wgpu::StoreOp::Store,
+                stencil_load_op: wgpu::LoadOp::Clear,
+                stencil_store_op: wgpu::StoreOp::Store,
+                clear_depth: 1.0,
+                clear_stencil: 0,
+            }),
+        })
+    }
+
+    /// Copies the resolved image into the readback buffer
+    pub fn copy(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.copy_texture_to_buffer(
+            wgpu::TextureCopyView {
+                texture: &self.texture,
+                mip_level: 0,
+                array_layer: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            wgpu::BufferCopyView {
+                buffer: &self.readback_buf,
+                offset: 0,
+                bytes_per_row: self.padded_row(),
+                rows_per_image: self.height,
+            },
+            wgpu::Extent3d {
+                width: self.width,
+                height: self.height,
+                depth: 1,
+            },
+        );
+    }
+
+    /// Waits for the copy submitted after copy and returns the RGBA rows from top to bottom
+    pub fn read(&self, device: &wgpu::Device) -> Option<Vec<u8>> {
+        let mapping = self
+            .readback_buf
+            .map_read(0, self.padded_row() as u64 * self.height as u64);
+        device.poll(wgpu::Maintain::Wait);
+        let mapping = block_on(mapping).ok()?;
+        let row_size = (self.width * PIXEL_SIZE) as usize;
+        let mut pixels = Vec::with_capacity(row_size * self.height as usize);
+        for row in mapping.as_slice().chunks(self.padded_row() as usize) {
+            pixels.extend_from_slice(&row[..row_size]);
+        }
+        // The window's format stores blue first
+        if let wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb = self.format {
+            for pixel in pixels.chunks_mut(PIXEL_SIZE as usize) {
+                pixel.swap(0, 2);
+            }
+        }
+        Some(pixels)
+    }
+}
@@ -0,0 +1,53 @@
+use std::collections::VecDeque;
+use std::time;
+
+/// Number of frames shown in the frame time graph
+pub const FRAME_HISTORY: usize = 120;
+
+/// Counters collected by the renderer
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RenderStats {
+    pub draw_calls: u32,
+    pub triangles: u64,
+    pub remeshed_chunks: u32,
+    /// Average time spent collecting the instances of a chunk in the last voxel update
+    pub remesh_time: time::Duration,
+    /// Estimated size of the buffers and render targets allocated by the renderer
+    pub gpu_memory: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    /// Frame times in milliseconds, oldest first
+    pub frame_times: Vec<f32>,
+    pub render: RenderStats,
+    pub scene_memory: usize,
+}
+
+pub struct FrameTimer {
+    last_frame: time::Instant,
+    frame_times: VecDeque<f32>,
+}
+
+impl FrameTimer {
+    pub fn new() -> Self {
+        FrameTimer {
+            last_frame: time::Instant::now(),
+            frame_times: VecDeque::with_capacity(FRAME_HISTORY),
+        }
+    }
+
+    pub fn frame(&mut self) {
+        let now = time::Instant::now();
+        if self.frame_times.len() == FRAME_HISTORY {
+            self.frame_times.pop_front();
+        }
+        self.frame_times
+            .push_back((now - self.last_frame).as_secs_f32() * 1000.0);
+        self.last_frame = now;
+    }
+
+    pub fn frame_times(&self) -> Vec<f32> {
+        self.frame_times.iter().cloned().collect()
+    }
+}
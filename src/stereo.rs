@@ -0,0 +1,98 @@
+use std::fmt;
+
+const PIXEL_SIZE: usize = 4;
+/// Widest eye separation in the units of the world, beyond it the views don't fuse anymore
+pub const MAX_STEREO_SEPARATION: f32 = 8.0;
+
+/// How the views of the two eyes are put into one image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StereoLayout {
+    /// Side by side with the view of the left eye on the left, for parallel viewing
+    SideBySide,
+    /// Side by side with the views swapped, for viewing with crossed eyes
+    CrossEye,
+    /// The red channel of the left eye over the green and blue of the right one, for red/cyan
+    /// glasses
+    Anaglyph,
+}
+
+impl StereoLayout {
+    pub const ALL: [StereoLayout; 3] = [
+        StereoLayout::SideBySide,
+        StereoLayout::CrossEye,
+        StereoLayout::Anaglyph,
+    ];
+
+    /// Width of the image made of views of the given width
+    pub fn width(self, width: u32) -> u32 {
+        match self {
+            StereoLayout::SideBySide | StereoLayout::CrossEye => 2 * width,
+            StereoLayout::Anaglyph => width,
+        }
+    }
+
+    /// Puts the RGBA views of the left and the right eye into one image
+    pub fn combine(self, left: &[u8], right: &[u8], width: usize, height: usize) -> Vec<u8> {
+        let row = width * PIXEL_SIZE;
+        let (first, second) = match self {
+            StereoLayout::SideBySide => (left, right),
+            StereoLayout::CrossEye => (right, left),
+            StereoLayout::Anaglyph => {
+                return left
+                    .chunks(PIXEL_SIZE)
+                    .zip(right.chunks(PIXEL_SIZE))
+                    .flat_map(|(l, r)| vec![l[0], r[1], r[2], l[3].max(r[3])])
+                    .collect();
+            }
+        };
+        let mut pixels = Vec::with_capacity(2 * row * height);
+        for y in 0..height {
+            pixels.extend_from_slice(&first[y * row..(y + 1) * row]);
+            pixels.extend_from_slice(&second[y * row..(y + 1) * row]);
+        }
+        pixels
+    }
+}
+
+impl fmt::Display for StereoLayout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StereoLayout::SideBySide => write!(f, "Side by side"),
+            StereoLayout::CrossEye => write!(f, "Cross-eye"),
+            StereoLayout::Anaglyph => write!(f, "Red/cyan anaglyph"),
+        }
+    }
+}
+
+/// A stereo pair of screenshots
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stereo {
+    /// Size of the view of each eye
+    pub size: [u32; 2],
+    /// Distance between the eyes in the units of the world
+    pub separation: f32,
+    pub layout: StereoLayout,
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn views_are_put_side_by_side_or_over_each_other() {
+        // Two pixels wide and one high, the left eye sees red and the right one white
+        let left = [255, 0, 0, 255, 255, 0, 0, 0];
+        let right = [255, 255, 255, 255, 255, 255, 255, 255];
+        let side_by_side = StereoLayout::SideBySide.combine(&left, &right, 2, 1);
+        assert_eq!(&side_by_side[..8], &left[..]);
+        assert_eq!(&side_by_side[8..], &right[..]);
+        let cross_eye = StereoLayout::CrossEye.combine(&left, &right, 2, 1);
+        assert_eq!(&cross_eye[..8], &right[..]);
+        assert_eq!(StereoLayout::CrossEye.width(2), 4);
+
+        let anaglyph = StereoLayout::Anaglyph.combine(&left, &right, 2, 1);
+        assert_eq!(anaglyph, vec![255, 255, 255, 255, 255, 255, 255, 255]);
+        assert_eq!(StereoLayout::Anaglyph.width(2), 2);
+    }
+}
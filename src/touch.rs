@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+use winit::dpi::PhysicalPosition;
+use winit::event::TouchPhase;
+
+/// Editor interactions recognized from the touch events
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Gesture {
+    PaintStarted(PhysicalPosition<f64>),
+    PaintMoved(PhysicalPosition<f64>),
+    PaintEnded,
+    /// More fingers touched down or the system took over the touch while painting
+    PaintCancelled,
+    /// Movement of the fingers' center in pixels
+    Orbit(f32, f32),
+    Pan(f32, f32),
+    /// Change of the distance between two fingers in pixels
+    Pinch(f32),
+}
+
+/// Tracks the active touches: one finger paints, two fingers orbit and pinch to zoom,
+/// three or more fingers pan the camera
+#[derive(Default)]
+pub struct TouchGestures {
+    touches: HashMap<u64, PhysicalPosition<f64>>,
+    painting: bool,
+}
+
+impl TouchGestures {
+    pub fn update(
+        &mut self,
+        id: u64,
+        phase: TouchPhase,
+        location: PhysicalPosition<f64>,
+    ) -> Vec<Gesture> {
+        let (center_before, spread_before) = self.center_and_spread();
+        match phase {
+            TouchPhase::Started | TouchPhase::Moved => {
+                self.touches.insert(id, location);
+            }
+            TouchPhase::Ended | TouchPhase::Cancelled => {
+                self.touches.remove(&id);
+            }
+        }
+        let (center, spread) = self.center_and_spread();
+
+        let mut gestures = Vec::new();
+        match (phase, self.touches.len()) {
+            (TouchPhase::Started, 1) => {
+                self.painting = true;
+                gestures.push(Gesture::PaintStarted(location));
+            }
+            (TouchPhase::Started, _) if self.painting => {
+                self.painting = false;
+                gestures.push(Gesture::PaintCancelled);
+            }
+            (TouchPhase::Moved, 1) if self.painting => {
+                gestures.push(Gesture::PaintMoved(location));
+            }
+            (TouchPhase::Ended, 0) if self.painting => {
+                self.painting = false;
+                gestures.push(Gesture::PaintEnded);
+            }
+            (TouchPhase::Cancelled, 0) if self.painting => {
+                self.painting = false;
+                gestures.push(Gesture::PaintCancelled);
+            }
+            (TouchPhase::Moved, 2) => {
+                gestures.push(Gesture::Orbit(
+                    (center.x - center_before.x) as f32,
+                    (center.y - center_before.y) as f32,
+                ));
+                gestures.push(Gesture::Pinch((spread - spread_before) as f32));
+            }
+            (TouchPhase::Moved, count) if count > 2 => {
+                gestures.push(Gesture::Pan(
+                    (center.x - center_before.x) as f32,
+                    (center.y - center_before.y) as f32,
+                ));
+            }
+            _ => {}
+        }
+        gestures
+    }
+
+    // Center of the touches and their average distance from it
+    fn center_and_spread(&self) -> (PhysicalPosition<f64>, f64) {
+        let count = self.touches.len().max(1) as f64;
+        let (x, y) = self
+            .touches
+            .values()
+            .fold((0.0, 0.0), |(x, y), p| (x + p.x, y + p.y));
+        let center = PhysicalPosition::new(x / count, y / count);
+        let spread = self
+            .touches
+            .values()
+            .map(|p| ((p.x - center.x).powi(2) + (p.y - center.y).powi(2)).sqrt())
+            .sum::<f64>()
+            / count;
+        (center, spread)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn second_finger_cancels_paint_and_pinches() {
+        let mut touch = TouchGestures::default();
+        let started = touch.update(0, TouchPhase::Started, PhysicalPosition::new(100.0, 100.0));
+        assert_eq!(
+            started,
+            vec![Gesture::PaintStarted(PhysicalPosition::new(100.0, 100.0))]
+        );
+        let second = touch.update(1, TouchPhase::Started, PhysicalPosition::new(200.0, 100.0));
+        assert_eq!(second, vec![Gesture::PaintCancelled]);
+
+        // Moving the second finger away moves the center and spreads the fingers
+        let moved = touch.update(1, TouchPhase::Moved, PhysicalPosition::new(300.0, 100.0));
+        assert_eq!(moved, vec![Gesture::Orbit(50.0, 0.0), Gesture::Pinch(50.0)]);
+
+        touch.update(1, TouchPhase::Ended, PhysicalPosition::new(300.0, 100.0));
+        assert!(touch
+            .update(0, TouchPhase::Ended, PhysicalPosition::new(100.0, 100.0))
+            .is_empty());
+    }
+}
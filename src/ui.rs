@@ -1,8 +1,15 @@
-use crate::controls::Controls;
+use crate::analysis::{SceneStats, ValidationReport};
+use crate::budget::{Budget, BudgetWarning};
+use crate::controls::{Controls, Message};
+use crate::history;
+use crate::marker::Marker;
+use crate::settings;
+use crate::snapshot::SnapshotDiff;
+use crate::stats::Stats;
 use iced_wgpu::{wgpu, Backend, Renderer, Settings, Viewport};
 use iced_winit::{conversion, mouse::Interaction, program, winit, Debug, Size};
 use winit::{
-    dpi::PhysicalPosition,
+    dpi::{PhysicalPosition, PhysicalSize},
     event::{ModifiersState, WindowEvent},
     window::Window,
 };
@@ -17,7 +24,7 @@ pub struct Ui {
 }
 
 impl Ui {
-    pub fn new(window: &Window, device: &mut wgpu::Device) -> Ui {
+    pub fn new(window: &Window, device: &mut wgpu::Device, settings: &settings::Settings) -> Ui {
         let physical_size = window.inner_size();
         let viewport = Viewport::with_physical_size(
             Size::new(physical_size.width, physical_size.height),
@@ -26,7 +33,7 @@ impl Ui {
         let cursor_position = PhysicalPosition::new(-1.0, -1.0);
         let modifiers = ModifiersState::default();
 
-        let controls = Controls::new();
+        let controls = Controls::new(settings);
         let mut debug = Debug::new();
         let mut renderer = Renderer::new(Backend::new(device, Settings::default()));
 
@@ -52,6 +59,113 @@ impl Ui {
         &self.state.program()
     }
 
+    /// Shows the name and progress of a background task in the sidebar, None hides it
+    pub fn set_progress(&mut self, progress: Option<(&'static str, f32)>) {
+        self.state.queue_message(Message::ProgressChanged(progress));
+    }
+
+    /// Fills in the voxel size of the scene
+    pub fn set_voxel_scale(&mut self, voxel_scale: [f32; 3]) {
+        self.state
+            .queue_message(Message::VoxelScaleLoaded(voxel_scale));
+    }
+
+    /// Fills in the budget of the opened project
+    pub fn set_budget(&mut self, budget: Budget) {
+        self.state.queue_message(Message::BudgetLoaded(budget));
+    }
+
+    /// Shows the counts over the budget in the status bar, None hides it for projects without
+    /// a budget
+    pub fn set_budget_warnings(&mut self, warnings: Option<Vec<BudgetWarning>>) {
+        self.state
+            .queue_message(Message::BudgetWarningsChanged(warnings));
+    }
+
+    pub fn toggle_stats(&mut self) {
+        self.state.queue_message(Message::ToggleStats);
+    }
+
+    pub fn set_stats(&mut self, stats: Stats) {
+        self.state.queue_message(Message::StatsChanged(stats));
+    }
+
+    pub fn toggle_scene_stats(&mut self) {
+        self.state.queue_message(Message::ToggleSceneStats);
+    }
+
+    pub fn set_palette_usage(&mut self, entries: usize, unused: Vec<u8>) {
+        self.state
+            .queue_message(Message::PaletteUsageChanged(entries, unused));
+    }
+
+    pub fn set_scene_stats(&mut self, stats: SceneStats) {
+        self.state.queue_message(Message::SceneStatsChanged(stats));
+    }
+
+    pub fn toggle_history(&mut self) {
+        self.state.queue_message(Message::ToggleHistory);
+    }
+
+    pub fn set_history(&mut self, items: Vec<history::Item>) {
+        self.state.queue_message(Message::HistoryChanged(items));
+    }
+
+    pub fn toggle_markers(&mut self) {
+        self.state.queue_message(Message::ToggleMarkers);
+    }
+
+    /// Lists the markers of the project with their cells and notes
+    pub fn set_markers(&mut self, markers: Vec<(String, Marker)>) {
+        self.state.queue_message(Message::MarkersChanged(markers));
+    }
+
+    pub fn toggle_minimap(&mut self) {
+        self.state.queue_message(Message::ToggleMinimap);
+    }
+
+    /// The heights and colors of the columns of the scene, see `analysis::top_view`
+    pub fn set_minimap_columns(&mut self, extent: usize, columns: Vec<Option<(usize, [f32; 4])>>) {
+        self.state
+            .queue_message(Message::MinimapColumnsChanged(extent, columns));
+    }
+
+    pub fn set_minimap_frustum(&mut self, frustum: Vec<[f32; 2]>) {
+        self.state
+            .queue_message(Message::MinimapFrustumChanged(frustum));
+    }
+
+    pub fn set_validation(&mut self, report: ValidationReport) {
+        self.state.queue_message(Message::ValidationChanged(report));
+    }
+
+    /// Lists the names of the masks saved with the project
+    pub fn set_mask_names(&mut self, names: Vec<String>) {
+        self.state.queue_message(Message::MaskNamesChanged(names));
+    }
+
+    /// Lists the names of the snapshots in the project
+    pub fn set_snapshot_names(&mut self, names: Vec<String>) {
+        self.state
+            .queue_message(Message::SnapshotNamesChanged(names));
+    }
+
+    pub fn set_snapshot_diff(&mut self, name: String, diff: SnapshotDiff) {
+        self.state
+            .queue_message(Message::SnapshotDiffChanged(name, diff));
+    }
+
+    pub fn set_session_state(&mut self, recording: bool, replaying: bool) {
+        self.state
+            .queue_message(Message::SessionStateChanged(recording, replaying));
+    }
+
+    /// Whether a macro is being recorded and how many steps it has
+    pub fn set_macro_state(&mut self, recording: bool, steps: usize) {
+        self.state
+            .queue_message(Message::MacroStateChanged(recording, steps));
+    }
+
     pub fn update_state(&mut self) {
         if !self.state.is_queue_empty() {
             self.state.update(
@@ -64,10 +178,26 @@ impl Ui {
         }
     }
 
-    pub fn update(&mut self, event: &WindowEvent, scale_factor: f64) {
-        if let Some(event) =
-            iced_winit::conversion::window_event(&event, scale_factor, self.modifiers)
-        {
+    /// Lays out the controls again for the new window size or after moving to a monitor
+    /// with a different scale factor, keeping the state of the controls
+    pub fn resize(&mut self, size: PhysicalSize<u32>, scale_factor: f64) {
+        self.viewport =
+            Viewport::with_physical_size(Size::new(size.width, size.height), scale_factor);
+        self.state.update(
+            self.viewport.logical_size(),
+            conversion::cursor_position(self.cursor_position, self.viewport.scale_factor()),
+            None,
+            &mut self.renderer,
+            &mut self.debug,
+        );
+    }
+
+    pub fn update(&mut self, event: &WindowEvent) {
+        if let Some(event) = iced_winit::conversion::window_event(
+            &event,
+            self.viewport.scale_factor(),
+            self.modifiers,
+        ) {
             self.state.queue_event(event);
         }
         match *event {
@@ -77,12 +207,6 @@ impl Ui {
             WindowEvent::ModifiersChanged(new_modifiers) => {
                 self.modifiers = new_modifiers;
             }
-            WindowEvent::Resized(new_size) => {
-                self.viewport = Viewport::with_physical_size(
-                    Size::new(new_size.width, new_size.height),
-                    scale_factor,
-                );
-            }
             _ => {}
         };
     }
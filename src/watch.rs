@@ -0,0 +1,185 @@
+//! Command line watch mode for game build systems, re-running the export of every project in a
+//! directory whenever the project or its export preset changes:
+//! `voxel-editor watch assets/ --out build/`
+use crate::export::{Crop, ExportFormat, ExportPreset};
+use crate::journal;
+use crate::project;
+use crate::selection::SavedMasks;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+const USAGE: &str = "usage: voxel-editor watch <directory> [--out <directory>] [--interval <ms>]";
+
+#[derive(Debug, PartialEq)]
+pub struct WatchOptions {
+    pub dir: PathBuf,
+    /// Exports are written here instead of the paths in the presets
+    pub out: Option<PathBuf>,
+    pub interval: Duration,
+}
+
+/// Parses the arguments following `watch`
+pub fn parse_args(args: &[String]) -> Result<WatchOptions, String> {
+    let mut dir = None;
+    let mut out = None;
+    let mut interval = Duration::from_millis(500);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--out" => out = Some(PathBuf::from(args.next().ok_or(USAGE)?)),
+            "--interval" => {
+                let ms = args.next().and_then(|ms| ms.parse().ok()).ok_or(USAGE)?;
+                interval = Duration::from_millis(ms);
+            }
+            _ if dir.is_none() && !arg.starts_with("--") => dir = Some(PathBuf::from(arg)),
+            _ => return Err(USAGE.to_owned()),
+        }
+    }
+    Ok(WatchOptions {
+        dir: dir.ok_or(USAGE)?,
+        out,
+        interval,
+    })
+}
+
+/// The export of the project: its preset, or a plain .obj next to it when it has none.
+/// The output is moved into out when given, keeping the project's place within the watched
+/// directory so projects of the same name in different folders don't overwrite each other.
+fn pipeline(project_path: &Path, dir: &Path, out: Option<&Path>) -> ExportPreset {
+    let mut preset =
+        ExportPreset::load(&project_path.to_string_lossy()).unwrap_or_else(|| ExportPreset {
+            format: ExportFormat::Obj,
+            file_path: project_path
+                .with_extension("obj")
+                .to_string_lossy()
+                .into_owned(),
+            texture_atlas: false,
+            template: "{name}_{n}".to_owned(),
+            crop: Crop::default(),
+            pivot: None,
+        });
+    if let Some(out) = out {
+        let file_name = Path::new(&preset.file_path)
+            .file_name()
+            .map(PathBuf::from)
+            .unwrap_or_else(|| project_path.with_extension("obj"));
+        let folder = project_path
+            .parent()
+            .and_then(|parent| parent.strip_prefix(dir).ok())
+            .unwrap_or_else(|| Path::new(""));
+        preset.file_path = out
+            .join(folder)
+            .join(file_name)
+            .to_string_lossy()
+            .into_owned();
+    }
+    preset
+}
+
+/// Latest modification time of the project, its journal, its preset and its saved masks
+fn modified(project_path: &Path) -> Option<SystemTime> {
+    let time = |path: &Path| fs::metadata(path).and_then(|meta| meta.modified()).ok();
+    let project_path_str = project_path.to_string_lossy();
+    let sidecars = [
+        time(&ExportPreset::path(&project_path_str)),
+        time(Path::new(&journal::path(&project_path_str))),
+        time(&SavedMasks::path(&project_path_str)),
+    ];
+    time(project_path).map(|project| sidecars.iter().flatten().fold(project, |a, b| a.max(*b)))
+}
+
+fn projects(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut found = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            found.extend(projects(&path)?);
+        } else if path.extension().map_or(false, |ext| ext == "vxe") {
+            found.push(path);
+        }
+    }
+    Ok(found)
+}
+
+fn export_project(project_path: &Path, dir: &Path, out: Option<&Path>) -> io::Result<()> {
+    let (voxel_manager, extras) =
+        project::load_project_with_extras(&project_path.to_string_lossy(), &mut |_| true)?;
+    let masks = SavedMasks::load(&project_path.to_string_lossy(), voxel_manager.extent());
+    let preset = pipeline(project_path, dir, out);
+    if let Some(dir) = Path::new(&preset.file_path).parent() {
+        fs::create_dir_all(dir)?;
+    }
+    preset.export_with_sub_grids(&voxel_manager, &extras.sub_grids, &masks, &mut |_| true)
+}
+
+/// Exports every project once, then again whenever it changes. Runs until the process is killed.
+pub fn run(options: WatchOptions) -> io::Result<()> {
+    let mut exported: HashMap<PathBuf, SystemTime> = HashMap::new();
+    println!("Watching {}", options.dir.display());
+    loop {
+        for project_path in projects(&options.dir)? {
+            let time = match modified(&project_path) {
+                Some(time) => time,
+                None => continue,
+            };
+            if exported.get(&project_path) == Some(&time) {
+                continue;
+            }
+            match export_project(&project_path, &options.dir, options.out.as_deref()) {
+                Ok(_) => println!("Exported {}", project_path.display()),
+                Err(e) => println!(
+                    "Failed exporting {} reason: {:?}",
+                    project_path.display(),
+                    e
+                ),
+            }
+            exported.insert(project_path, time);
+        }
+        thread::sleep(options.interval);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn watch_arguments() {
+        let options = parse_args(&args(&["assets/", "--out", "build/"])).unwrap();
+        assert_eq!(options.dir, PathBuf::from("assets/"));
+        assert_eq!(options.out, Some(PathBuf::from("build/")));
+        assert_eq!(options.interval, Duration::from_millis(500));
+
+        assert!(parse_args(&args(&["--out", "build/"])).is_err());
+        assert!(parse_args(&args(&["assets/", "--interval"])).is_err());
+        assert!(parse_args(&args(&["assets/", "more/"])).is_err());
+    }
+
+    #[test]
+    fn projects_without_a_preset_export_next_to_them_or_into_out() {
+        let dir = Path::new("/nonexistent/assets");
+        let project = dir.join("crate.vxe");
+        assert_eq!(
+            pipeline(&project, dir, None).file_path,
+            "/nonexistent/assets/crate.obj"
+        );
+        let out = Path::new("/nonexistent/build");
+        let preset = pipeline(&project, dir, Some(out));
+        assert_eq!(preset.format, ExportFormat::Obj);
+        assert_eq!(preset.file_path, "/nonexistent/build/crate.obj");
+        let project = dir.join("props/crate.vxe");
+        assert_eq!(
+            pipeline(&project, dir, Some(out)).file_path,
+            "/nonexistent/build/props/crate.obj"
+        );
+    }
+}
@@ -0,0 +1,119 @@
+use crate::settings::Settings;
+use winit::dpi::{LogicalSize, PhysicalPosition, PhysicalSize};
+use winit::event::WindowEvent;
+use winit::monitor::MonitorHandle;
+use winit::window::{Fullscreen, Window, WindowBuilder};
+
+/// Size and position the window has when it isn't maximized and whether it is, restored on the
+/// next start
+pub struct WindowState {
+    size: PhysicalSize<u32>,
+    position: Option<PhysicalPosition<i32>>,
+    maximized: bool,
+}
+
+impl WindowState {
+    pub fn load(settings: &Settings) -> Option<Self> {
+        let size = PhysicalSize::new(
+            settings.get("window.width")?,
+            settings.get("window.height")?,
+        );
+        let position = match (settings.get("window.x"), settings.get("window.y")) {
+            (Some(x), Some(y)) => Some(PhysicalPosition::new(x, y)),
+            _ => None,
+        };
+        Some(WindowState {
+            size,
+            position,
+            maximized: settings.get("window.maximized").unwrap_or(false),
+        })
+    }
+
+    /// The state of the window built from the restored state, or of a new window
+    pub fn new(window: &Window, restored: Option<WindowState>) -> Self {
+        restored.unwrap_or_else(|| WindowState {
+            size: window.inner_size(),
+            position: window.outer_position().ok(),
+            maximized: false,
+        })
+    }
+
+    /// Follows the resizes and moves of the window. winit has no maximize events, a resize
+    /// reaching the monitor's corner and spanning it maximizes the window and one leaving it
+    /// restores it. Only windowed sizes and positions are kept, so they survive maximizing.
+    pub fn track(&mut self, window: &Window, event: &WindowEvent) {
+        if window.fullscreen().is_some() {
+            return;
+        }
+        match event {
+            WindowEvent::Resized(size) => {
+                let monitor = window.current_monitor();
+                let (origin, monitor_size) = (monitor.position(), monitor.size());
+                let outer_size = window.outer_size();
+                self.maximized = match window.outer_position() {
+                    Ok(position) => {
+                        position.x <= origin.x
+                            && position.y <= origin.y
+                            && outer_size.width >= monitor_size.width
+                    }
+                    Err(_) => false,
+                };
+                if !self.maximized {
+                    self.size = *size;
+                }
+            }
+            WindowEvent::Moved(position) if !self.maximized => self.position = Some(*position),
+            _ => {}
+        }
+    }
+
+    pub fn store(&self, settings: &mut Settings) {
+        settings.set("window.width", self.size.width);
+        settings.set("window.height", self.size.height);
+        match self.position {
+            Some(position) => {
+                settings.set("window.x", position.x);
+                settings.set("window.y", position.y);
+            }
+            None => {
+                settings.remove("window.x");
+                settings.remove("window.y");
+            }
+        }
+        settings.set("window.maximized", self.maximized);
+    }
+
+    pub fn window_builder(state: Option<&WindowState>) -> WindowBuilder {
+        let builder = WindowBuilder::new();
+        match state {
+            Some(state) => builder
+                .with_inner_size(state.size)
+                .with_maximized(state.maximized),
+            None => builder.with_inner_size(LogicalSize::new(1280, 720)),
+        }
+    }
+
+    /// Moves the window to the stored position unless that's off the current monitors
+    pub fn restore_position(&self, window: &Window, monitors: impl Iterator<Item = MonitorHandle>) {
+        if let Some(position) = self.position {
+            let visible = monitors.into_iter().any(|monitor| {
+                let origin = monitor.position();
+                let size = monitor.size();
+                position.x >= origin.x
+                    && position.y >= origin.y
+                    && position.x < origin.x + size.width as i32
+                    && position.y < origin.y + size.height as i32
+            });
+            if visible {
+                window.set_outer_position(position);
+            }
+        }
+    }
+}
+
+pub fn toggle_fullscreen(window: &Window) {
+    match window.fullscreen() {
+        Some(_) => window.set_fullscreen(None),
+        None => window.set_fullscreen(Some(Fullscreen::Borderless(window.current_monitor()))),
+    }
+}
@@ -0,0 +1,224 @@
+use crate::geometry::{BoundingBox, Plane, Ray, XY_PLANE, XZ_PLANE, YZ_PLANE};
+use crate::highlight::box_vertices;
+use crate::transform::Axis;
+use crate::vertex::Vertex;
+use cgmath::Vector3;
+
+const OUTLINE_COLOR: [f32; 4] = [0.3, 0.6, 1.0, 0.8];
+const FILL_COLOR: [f32; 4] = [0.3, 0.6, 1.0, 0.1];
+const MOVE_COLOR: [f32; 4] = [1.0, 0.8, 0.2, 0.9];
+const ROTATE_COLOR: [f32; 4] = [0.3, 1.0, 0.4, 0.9];
+// Sizes in cells
+const LINE_WIDTH: f32 = 0.1;
+const HANDLE_LENGTH: f32 = 4.0;
+const HANDLE_WIDTH: f32 = 0.6;
+const ROTATE_SIZE: f32 = 1.2;
+
+/// Part of the gizmo under the cursor
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Handle {
+    /// Moves the plane along its normal while dragged
+    Move,
+    /// Turns the plane a quarter around the axis
+    Rotate(Axis),
+}
+
+/// Plane the cursor places voxels on when it isn't over a voxel, in place of the ground planes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WorkingPlane {
+    pub normal: Axis,
+    /// Layer of cells along the normal the plane lies under
+    pub layer: usize,
+}
+
+impl Default for WorkingPlane {
+    fn default() -> Self {
+        WorkingPlane {
+            normal: Axis::Y,
+            layer: 0,
+        }
+    }
+}
+
+fn centered_box(center: [f32; 3], size: [f32; 3], color: [f32; 4]) -> BoundingBox {
+    BoundingBox::new(
+        Vector3::new(
+            center[0] - size[0] / 2.0,
+            center[1] - size[1] / 2.0,
+            center[2] - size[2] / 2.0,
+        ),
+        Vector3::new(size[0], size[1], size[2]),
+        color,
+    )
+}
+
+impl WorkingPlane {
+    /// The plane of the face a voxel shares with the empty cell next to it, the cells drawn on
+    /// it are in the layer of the empty cell. None when the cells aren't neighbours.
+    pub fn on_face(voxel: [usize; 3], outside: [usize; 3]) -> Option<Self> {
+        let mut differing = Axis::ALL
+            .iter()
+            .filter(|axis| voxel[axis.index()] != outside[axis.index()]);
+        let normal = *differing.next()?;
+        let distance = voxel[normal.index()] as i64 - outside[normal.index()] as i64;
+        if differing.next().is_some() || distance.abs() != 1 {
+            return None;
+        }
+        Some(WorkingPlane {
+            normal,
+            layer: outside[normal.index()],
+        })
+    }
+
+    /// The ground plane of the normal moved up to the layer
+    pub fn plane(&self) -> Plane {
+        let ground = match self.normal {
+            Axis::X => YZ_PLANE,
+            Axis::Y => XZ_PLANE,
+            Axis::Z => XY_PLANE,
+        };
+        ground.shifted(self.layer as f32)
+    }
+
+    /// Turns the plane a quarter around an axis lying in it, its layer stays the same
+    pub fn rotate(&mut self, around: Axis) {
+        if around == self.normal {
+            return;
+        }
+        if let Some(normal) = Axis::ALL
+            .iter()
+            .find(|axis| **axis != around && **axis != self.normal)
+        {
+            self.normal = *normal;
+        }
+    }
+
+    fn center(&self, extent: usize) -> [f32; 3] {
+        let mut center = [extent as f32 / 2.0; 3];
+        center[self.normal.index()] = self.layer as f32;
+        center
+    }
+
+    fn handles(&self, extent: usize) -> Vec<(Handle, BoundingBox)> {
+        let normal = self.normal.index();
+        let mut center = self.center(extent);
+        center[normal] += HANDLE_LENGTH / 2.0;
+        let mut size = [HANDLE_WIDTH; 3];
+        size[normal] = HANDLE_LENGTH;
+        let mut handles = vec![(Handle::Move, centered_box(center, size, MOVE_COLOR))];
+
+        // Dragging the far edge of one axis over turns the plane around the other
+        let (right, up) = self.normal.plane_axes();
+        for (edge, around) in [(right, up), (up, right)].iter() {
+            let mut center = self.center(extent);
+            center[edge.index()] = extent as f32;
+            handles.push((
+                Handle::Rotate(*around),
+                centered_box(center, [ROTATE_SIZE; 3], ROTATE_COLOR),
+            ));
+        }
+        handles
+    }
+
+    /// The outline of the plane over the grid, a translucent fill and the handles
+    pub fn vertices(&self, extent: usize) -> (Vec<Vertex>, Vec<u32>) {
+        let normal = self.normal.index();
+        let (right, up) = self.normal.plane_axes();
+        let mut size = [extent as f32; 3];
+        size[normal] = 0.01;
+        let mut boxes = vec![centered_box(self.center(extent), size, FILL_COLOR)];
+        for (along, across) in [(right, up), (up, right)].iter() {
+            for side in [0.0, extent as f32].iter() {
+                let mut center = self.center(extent);
+                center[across.index()] = *side;
+                let mut size = [LINE_WIDTH; 3];
+                size[along.index()] = extent as f32 + LINE_WIDTH;
+                boxes.push(centered_box(center, size, OUTLINE_COLOR));
+            }
+        }
+        boxes.extend(self.handles(extent).into_iter().map(|(_, bbox)| bbox));
+        box_vertices(&boxes)
+    }
+
+    /// The handle closest to the ray origin the ray passes through
+    pub fn handle_at(&self, ray: &Ray, extent: usize) -> Option<Handle> {
+        self.handles(extent)
+            .into_iter()
+            .fold(None, |closest, (handle, bbox)| {
+                let mut dist = 0.0;
+                if !ray.box_intersection(&bbox, &mut dist) {
+                    return closest;
+                }
+                match closest {
+                    Some((closest_dist, _)) if closest_dist <= dist => closest,
+                    _ => Some((dist, handle)),
+                }
+            })
+            .map(|(_, handle)| handle)
+    }
+
+    /// Coordinate along the normal where the ray passes closest to the line of the move handle,
+    /// None when the ray runs along the normal
+    pub fn normal_coordinate(&self, ray: &Ray, extent: usize) -> Option<f32> {
+        let mut normal = Vector3::new(0.0, 0.0, 0.0);
+        normal[self.normal.index()] = 1.0;
+        let [x, y, z] = self.center(extent);
+        let t = ray.line_coordinate(Vector3::new(x, y, z), normal)?;
+        Some(self.layer as f32 + t)
+    }
+
+    /// Moves the plane from the layer it had when the handle was grabbed at a coordinate along
+    /// the normal, by whole layers and inside the grid
+    pub fn drag(&mut self, start_layer: usize, grabbed: f32, coordinate: f32, extent: usize) {
+        let layer = (start_layer as f32 + coordinate - grabbed).round();
+        self.layer = layer.max(0.0).min(extent.saturating_sub(1) as f32) as usize;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn handles_move_and_rotate_the_plane() {
+        let mut working_plane = WorkingPlane {
+            normal: Axis::Z,
+            layer: 4,
+        };
+        let down = Ray::new(
+            Vector3::new(16.0, 16.0, 50.0),
+            Vector3::new(16.0, 16.0, -50.0),
+        );
+        assert_eq!(working_plane.handle_at(&down, 32), Some(Handle::Move));
+        assert_eq!(working_plane.normal_coordinate(&down, 32), None);
+
+        let across = Ray::new(
+            Vector3::new(50.0, 16.0, 10.0),
+            Vector3::new(-50.0, 16.0, 10.0),
+        );
+        assert_eq!(working_plane.normal_coordinate(&across, 32), Some(10.0));
+        working_plane.drag(4, 6.0, 10.0, 32);
+        assert_eq!(working_plane.layer, 8);
+        working_plane.drag(4, 6.0, 100.0, 32);
+        assert_eq!(working_plane.layer, 31);
+
+        let edge = Ray::new(
+            Vector3::new(50.0, 16.0, 31.0),
+            Vector3::new(-50.0, 16.0, 31.0),
+        );
+        assert_eq!(
+            working_plane.handle_at(&edge, 32),
+            Some(Handle::Rotate(Axis::Y))
+        );
+        working_plane.rotate(Axis::Y);
+        assert_eq!(working_plane.normal, Axis::X);
+        working_plane.rotate(Axis::X);
+        assert_eq!(working_plane.normal, Axis::X);
+
+        let top = WorkingPlane::on_face([3, 5, 3], [3, 6, 3]).unwrap();
+        assert_eq!((top.normal, top.layer), (Axis::Y, 6));
+        assert_eq!(WorkingPlane::on_face([3, 5, 3], [3, 5, 3]), None);
+        assert_eq!(WorkingPlane::on_face([3, 5, 3], [4, 6, 3]), None);
+    }
+}